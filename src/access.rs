@@ -0,0 +1,127 @@
+// 権限が足りずに読めないソース（hidepid の /proc、コンテナ、一般ユーザー）
+//
+// 読めないソースはパネルごと消えたり 0 のまま出たりして、無いのか見えないのか区別が付かない。
+// 起動時に主なファイルを開いてみて、権限で断られたものを覚えておき、そのパネルには権限が
+// 足りないと書き、Info と --doctor にまとめる。ファイルが無い（NotFound）ものは数えない。
+use std::io;
+
+use crate::layout::Slot;
+
+/// /proc の hidepid による、他のユーザーのプロセスの見え方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HidePid {
+    /// 1 / noaccess: 一覧には出るが中身が読めない
+    NoAccess,
+    /// 2 / invisible: 一覧にも出ない
+    Invisible,
+}
+
+/// 開いてみるソースの名前とパス、読めないと空になるパネル
+type Probe = (&'static str, &'static str, &'static [Slot]);
+
+const PROBES: [Probe; 6] = [
+    // CPU 時間の内訳とカーネルのパネルも空になるが、理由は CPU のパネルに書けば足りる
+    ("CPU usage", "/proc/stat", &[Slot::Cpu]),
+    ("Pressure", "/proc/pressure/cpu", &[Slot::Pressure]),
+    ("cgroup", "/sys/fs/cgroup", &[Slot::Cgroup]),
+    ("NUMA", "/sys/devices/system/node", &[Slot::Numa]),
+    (
+        "Huge pages",
+        "/sys/kernel/mm/transparent_hugepage",
+        &[Slot::HugePages],
+    ),
+    ("Connections", "/proc/net/tcp", &[Slot::Ports, Slot::Tcp]),
+];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Access {
+    /// 権限で断られたソース
+    denied: Vec<Probe>,
+    /// root なら hidepid が掛かっていても全部見えるので None
+    pub hidepid: Option<HidePid>,
+}
+
+impl Access {
+    /// この機械で調べる
+    #[cfg(target_os = "linux")]
+    pub fn probe() -> Access {
+        let mounts = std::fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+        let root = unsafe { libc::geteuid() } == 0;
+        Access::check(|path| std::fs::File::open(path).map(drop), &mounts, root)
+    }
+
+    // /proc も /sys も無いので、断られるものも無い
+    #[cfg(not(target_os = "linux"))]
+    pub fn probe() -> Access {
+        Access::check(|_| Ok(()), "", true)
+    }
+
+    /// open でそれぞれのパスを開いてみる。mounts は /proc/self/mounts の中身
+    pub fn check(open: impl Fn(&str) -> io::Result<()>, mounts: &str, root: bool) -> Access {
+        Access {
+            denied: PROBES
+                .into_iter()
+                .filter(|(_, path, _)| {
+                    open(path).is_err_and(|e| e.kind() == io::ErrorKind::PermissionDenied)
+                })
+                .collect(),
+            hidepid: (!root).then(|| hidepid(mounts)).flatten(),
+        }
+    }
+
+    /// slot のパネルのソースが読めなければ、その名前とパス
+    pub fn denied(&self, slot: Slot) -> Option<(&'static str, &'static str)> {
+        self.denied
+            .iter()
+            .find(|(_, _, slots)| slots.contains(&slot))
+            .map(|(name, path, _)| (*name, *path))
+    }
+
+    pub fn is_limited(&self) -> bool {
+        !self.denied.is_empty() || self.hidepid.is_some()
+    }
+
+    /// Info と --doctor に出す 1 行（「limited: Pressure unreadable, hidepid」）
+    pub fn summary(&self) -> String {
+        if !self.is_limited() {
+            return "full".to_string();
+        }
+        let mut parts = Vec::new();
+        if !self.denied.is_empty() {
+            let names: Vec<&str> = self.denied.iter().map(|(name, _, _)| *name).collect();
+            parts.push(format!("{} unreadable", names.join(", ")));
+        }
+        if self.hidepid.is_some() {
+            parts.push("hidepid".to_string());
+        }
+        format!("limited: {}", parts.join(", "))
+    }
+}
+
+impl HidePid {
+    /// プロセス表のタイトルに添える説明
+    pub fn note(self) -> &'static str {
+        match self {
+            HidePid::NoAccess => "other users' processes are unreadable (hidepid)",
+            HidePid::Invisible => "other users' processes are hidden (hidepid)",
+        }
+    }
+}
+
+// /proc にマウントした proc の hidepid= を読む
+fn hidepid(mounts: &str) -> Option<HidePid> {
+    mounts
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .filter(|fields| fields.get(1) == Some(&"/proc") && fields.get(2) == Some(&"proc"))
+        .filter_map(|fields| fields.get(3).copied())
+        .flat_map(|options| options.split(','))
+        .filter_map(|option| option.strip_prefix("hidepid="))
+        .map(|value| match value {
+            "1" | "noaccess" => Some(HidePid::NoAccess),
+            "2" | "invisible" => Some(HidePid::Invisible),
+            _ => None,
+        })
+        .next_back()
+        .flatten()
+}
@@ -0,0 +1,731 @@
+// プロセスに対する操作（シグナル送信など）
+//
+// System を持つサンプラースレッド上で実行し、結果を UI に返す。
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io;
+
+use sysinfo::{Pid, Process, ProcessRefreshKind, ProcessesToUpdate, Signal, System, ThreadKind};
+
+/// 操作対象のプロセス。PID は再利用されるため起動時刻と組で識別する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProcessRef {
+    pub pid: Pid,
+    pub start_time: u64,
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Signal {
+        target: ProcessRef,
+        signal: Signal,
+    },
+    /// 子孫のプロセスにも同じシグナルを送る（子孫から先に送る）
+    SignalTree {
+        target: ProcessRef,
+        signal: Signal,
+    },
+    /// nice 値（-20 から 19）を設定する
+    Renice {
+        target: ProcessRef,
+        nice: i32,
+    },
+    /// I/O のスケジューリングクラスと優先度（Linux の ioprio_set）
+    SetIoPriority {
+        target: ProcessRef,
+        priority: IoPriority,
+    },
+    /// 動かしてよい CPU を番号で指定する（Linux の sched_setaffinity）
+    SetAffinity {
+        target: ProcessRef,
+        cpus: Vec<usize>,
+    },
+    /// rust-top で変えた nice 値・I/O 優先度・アフィニティを変える前に戻す
+    Revert {
+        target: ProcessRef,
+    },
+}
+
+impl Command {
+    pub fn target(&self) -> ProcessRef {
+        match self {
+            Command::Signal { target, .. }
+            | Command::SignalTree { target, .. }
+            | Command::Renice { target, .. }
+            | Command::SetIoPriority { target, .. }
+            | Command::SetAffinity { target, .. }
+            | Command::Revert { target } => *target,
+        }
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.target().pid
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Signal { target, signal } => {
+                write!(f, "send SIG{} to {}", signal_name(*signal), target.pid)
+            }
+            Command::SignalTree { target, signal } => write!(
+                f,
+                "send SIG{} to {} and its children",
+                signal_name(*signal),
+                target.pid
+            ),
+            Command::Renice { target, nice } => write!(f, "renice {} to {nice}", target.pid),
+            Command::SetIoPriority { target, priority } => {
+                write!(f, "set the I/O priority of {} to {priority}", target.pid)
+            }
+            Command::SetAffinity { target, cpus } => {
+                write!(f, "pin {} to CPUs {}", target.pid, cpu_list(cpus))
+            }
+            Command::Revert { target } => write!(f, "revert the changes to {}", target.pid),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ActionError {
+    /// 操作の対象が既に終了している
+    NoSuchProcess,
+    /// 同じ PID が別のプロセスに再利用されている
+    PidReused,
+    /// このプラットフォームでは使えない
+    Unsupported,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ActionOutcome {
+    pub command: Command,
+    pub result: Result<(), ActionError>,
+}
+
+impl fmt::Display for ActionOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.result, &self.command) {
+            (Ok(()), Command::Signal { target, signal }) => {
+                write!(f, "Sent SIG{} to {}", signal_name(*signal), target.pid)
+            }
+            (Ok(()), Command::SignalTree { target, signal }) => write!(
+                f,
+                "Sent SIG{} to {} and its children",
+                signal_name(*signal),
+                target.pid
+            ),
+            (Ok(()), Command::Renice { target, nice }) => {
+                write!(f, "Reniced {} to {nice}", target.pid)
+            }
+            (Ok(()), Command::SetIoPriority { target, priority }) => {
+                write!(f, "Set the I/O priority of {} to {priority}", target.pid)
+            }
+            (Ok(()), Command::SetAffinity { target, cpus }) => {
+                write!(f, "Pinned {} to CPUs {}", target.pid, cpu_list(cpus))
+            }
+            (Ok(()), Command::Revert { target }) => {
+                write!(
+                    f,
+                    "Restored the original priority and CPUs of {}",
+                    target.pid
+                )
+            }
+            (Err(ActionError::NoSuchProcess), _) => {
+                write!(f, "Process {} no longer exists", self.command.pid())
+            }
+            (Err(ActionError::PidReused), _) => write!(
+                f,
+                "Not done: PID {} now belongs to a different process",
+                self.command.pid()
+            ),
+            (Err(ActionError::Unsupported), _) => {
+                write!(f, "Cannot {}: unsupported", self.command)
+            }
+            (Err(ActionError::Failed(reason)), _) => {
+                write!(f, "Cannot {}: {reason}", self.command)
+            }
+        }
+    }
+}
+
+/// rust-top で最初に変える前の値。変えていないものは None
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Original {
+    nice: Option<i32>,
+    io_priority: Option<IoPriority>,
+    affinity: Option<Vec<usize>>,
+}
+
+/// この起動中に nice 値・I/O 優先度・アフィニティを変えたプロセスの元の値
+#[derive(Debug, Default)]
+pub struct Originals(HashMap<ProcessRef, Original>);
+
+impl Originals {
+    // 初めて変えるときだけ今の値を覚えてから変える。変えられなければ覚えた値も捨てる
+    fn track<T>(
+        &mut self,
+        target: ProcessRef,
+        field: fn(&mut Original) -> &mut Option<T>,
+        read: impl FnOnce() -> Option<T>,
+        apply: impl FnOnce() -> Result<(), ActionError>,
+    ) -> Result<(), ActionError> {
+        let original = self.0.entry(target).or_default();
+        let first = field(original).is_none();
+        if first {
+            *field(original) = read();
+        }
+        let result = apply();
+        if result.is_err() && first {
+            *field(original) = None;
+        }
+        if *original == Original::default() {
+            self.0.remove(&target);
+        }
+        result
+    }
+
+    // 覚えた値に戻す。戻せなかったものがあれば最初の失敗を返す
+    fn revert(&mut self, target: ProcessRef) -> Result<(), ActionError> {
+        let original = self.0.remove(&target).ok_or_else(|| {
+            ActionError::Failed("nothing was changed through rust-top".to_string())
+        })?;
+        let pid = target.pid;
+        [
+            original.nice.map(|nice| set_nice(pid, nice)),
+            original
+                .io_priority
+                .map(|priority| set_io_priority(pid, priority)),
+            original.affinity.map(|cpus| set_affinity(pid, &cpus)),
+        ]
+        .into_iter()
+        .flatten()
+        .find(Result::is_err)
+        .unwrap_or(Ok(()))
+    }
+}
+
+pub fn execute(sys: &mut System, originals: &mut Originals, command: Command) -> ActionOutcome {
+    let result = run(sys, originals, &command);
+    ActionOutcome { command, result }
+}
+
+fn run(sys: &mut System, originals: &mut Originals, command: &Command) -> Result<(), ActionError> {
+    let pid = command.pid();
+    let process = verify(sys, command.target())?;
+
+    match command {
+        Command::Signal { signal, .. } => signal_result(process.kill_with(*signal)),
+        Command::SignalTree { target, signal } => {
+            // 子孫は前回の収集から入れ替わっているかもしれないので、全体を読み直して開始時刻を控える
+            sys.refresh_processes_specifics(
+                ProcessesToUpdate::All,
+                true,
+                ProcessRefreshKind::nothing(),
+            );
+            let tree: Vec<ProcessRef> = descendants(sys, pid)
+                .into_iter()
+                .filter_map(|child| {
+                    Some(ProcessRef {
+                        pid: child,
+                        start_time: sys.process(child)?.start_time(),
+                    })
+                })
+                .collect();
+            // 子孫の失敗（既に終了した、PID が使い回されたなど）は無視し、対象自身の結果を返す
+            for child in tree.into_iter().rev() {
+                if let Ok(process) = verify(sys, child) {
+                    process.kill_with(*signal);
+                }
+            }
+            signal_result(verify(sys, *target)?.kill_with(*signal))
+        }
+        Command::Renice { target, nice } => originals.track(
+            *target,
+            |o| &mut o.nice,
+            || current_nice(pid),
+            || set_nice(pid, *nice),
+        ),
+        Command::SetIoPriority { target, priority } => originals.track(
+            *target,
+            |o| &mut o.io_priority,
+            || current_io_priority(pid),
+            || set_io_priority(pid, *priority),
+        ),
+        Command::SetAffinity { target, cpus } => originals.track(
+            *target,
+            |o| &mut o.affinity,
+            || current_affinity(pid),
+            || set_affinity(pid, cpus),
+        ),
+        Command::Revert { target } => originals.revert(*target),
+    }
+}
+
+// 直前に読み直し、終了済みのプロセスや PID を使い回した別のプロセスに操作しないようにする
+fn verify(sys: &mut System, target: ProcessRef) -> Result<&Process, ActionError> {
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[target.pid]),
+        true,
+        ProcessRefreshKind::nothing(),
+    );
+    let process = sys.process(target.pid).ok_or(ActionError::NoSuchProcess)?;
+    if process.start_time() != target.start_time {
+        return Err(ActionError::PidReused);
+    }
+    Ok(process)
+}
+
+fn signal_result(sent: Option<bool>) -> Result<(), ActionError> {
+    match sent {
+        Some(true) => Ok(()),
+        Some(false) => Err(ActionError::Failed(io::Error::last_os_error().to_string())),
+        None => Err(ActionError::Unsupported),
+    }
+}
+
+// 直近の収集時点での子孫（親に近い順）
+fn descendants(sys: &System, root: Pid) -> Vec<Pid> {
+    let mut children: HashMap<Pid, Vec<Pid>> = HashMap::new();
+    // スレッドは持ち主を親として列挙されるので除く
+    for process in sys.processes().values() {
+        if process.thread_kind() == Some(ThreadKind::Userland) {
+            continue;
+        }
+        if let Some(parent) = process.parent() {
+            children.entry(parent).or_default().push(process.pid());
+        }
+    }
+    let mut found = Vec::new();
+    // 循環していても止まるようにする
+    let mut seen = HashSet::from([root]);
+    let mut queue = VecDeque::from([root]);
+    while let Some(pid) = queue.pop_front() {
+        for child in children.get(&pid).into_iter().flatten() {
+            if seen.insert(*child) {
+                found.push(*child);
+                queue.push_back(*child);
+            }
+        }
+    }
+    found
+}
+
+#[cfg(unix)]
+fn set_nice(pid: Pid, nice: i32) -> Result<(), ActionError> {
+    // SAFETY: 引数は整数だけで、メモリには触れない
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, pid.as_u32() as libc::id_t, nice) } == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        // 他人のプロセスや、nice 値を下げる（優先度を上げる）には権限が要る
+        Some(libc::EPERM) | Some(libc::EACCES) => Err(ActionError::Failed(
+            "permission denied: only root can lower nice or renice others".to_string(),
+        )),
+        _ => Err(ActionError::Failed(err.to_string())),
+    }
+}
+
+#[cfg(not(unix))]
+fn set_nice(_pid: Pid, _nice: i32) -> Result<(), ActionError> {
+    Err(ActionError::Unsupported)
+}
+
+/// 現在の nice 値。取得できなければ None
+#[cfg(unix)]
+pub fn current_nice(pid: Pid) -> Option<i32> {
+    // SAFETY: 整数引数だけの呼び出し
+    let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, pid.as_u32() as libc::id_t) };
+    // -1 は正しい値でもある。getpriority が失敗するのはプロセスが無いときだけなので、
+    // errno を読む代わりに（errno の場所は OS ごとに違う）居るかどうかを確かめる
+    // SAFETY: シグナル 0 は存在を確かめるだけで何も送らない
+    if nice == -1
+        && unsafe { libc::kill(pid.as_u32() as libc::pid_t, 0) } != 0
+        && io::Error::last_os_error().raw_os_error() == Some(libc::ESRCH)
+    {
+        return None;
+    }
+    Some(nice)
+}
+
+#[cfg(not(unix))]
+pub fn current_nice(_pid: Pid) -> Option<i32> {
+    None
+}
+
+/// I/O のスケジューリングクラス（ionice -c）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoClass {
+    /// 指定なし。nice 値から best-effort の優先度が決まる
+    None,
+    RealTime,
+    BestEffort,
+    /// ほかに I/O が無いときだけ
+    Idle,
+}
+
+/// I/O 優先度。level は 0（高）から 7（低）で、realtime と best-effort だけが使う
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoPriority {
+    pub class: IoClass,
+    pub level: u8,
+}
+
+// カーネルの ioprio の値は上位 3 ビットがクラス、下位 13 ビットが優先度
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const IOPRIO_CLASS_SHIFT: i32 = 13;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+
+impl IoPriority {
+    /// "be 4" / "best-effort 4" / "rt" / "idle" / "none" のような入力を読む
+    /// 優先度を省いたら 4 にする
+    pub fn parse(text: &str) -> Option<IoPriority> {
+        let mut words = text.split_whitespace();
+        let class = match words.next()?.to_ascii_lowercase().as_str() {
+            "none" | "0" => IoClass::None,
+            "rt" | "realtime" | "real-time" | "1" => IoClass::RealTime,
+            "be" | "best-effort" | "besteffort" | "2" => IoClass::BestEffort,
+            "idle" | "3" => IoClass::Idle,
+            _ => return None,
+        };
+        let level = match words.next() {
+            Some(level) => level.parse::<u8>().ok().filter(|l| *l <= 7)?,
+            None => 4,
+        };
+        if words.next().is_some() {
+            return None;
+        }
+        Some(IoPriority { class, level })
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn from_raw(raw: i32) -> Option<IoPriority> {
+        let class = match raw >> IOPRIO_CLASS_SHIFT {
+            0 => IoClass::None,
+            1 => IoClass::RealTime,
+            2 => IoClass::BestEffort,
+            3 => IoClass::Idle,
+            _ => return None,
+        };
+        let level = (raw & ((1 << IOPRIO_CLASS_SHIFT) - 1)) as u8;
+        Some(IoPriority { class, level })
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn to_raw(self) -> i32 {
+        let (class, level) = match self.class {
+            IoClass::None => (0, 0),
+            IoClass::RealTime => (1, self.level),
+            IoClass::BestEffort => (2, self.level),
+            IoClass::Idle => (3, 0),
+        };
+        (class << IOPRIO_CLASS_SHIFT) | i32::from(level)
+    }
+}
+
+impl fmt::Display for IoPriority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.class {
+            IoClass::None => f.write_str("none"),
+            IoClass::RealTime => write!(f, "realtime {}", self.level),
+            IoClass::BestEffort => write!(f, "best-effort {}", self.level),
+            IoClass::Idle => f.write_str("idle"),
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn set_io_priority(pid: Pid, priority: IoPriority) -> Result<(), ActionError> {
+    // SAFETY: 整数引数だけのシステムコール
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            pid.as_u32() as libc::c_int,
+            priority.to_raw(),
+        )
+    };
+    if result == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        // realtime には CAP_SYS_ADMIN が、他人のプロセスには root が要る
+        Some(libc::EPERM) => Err(ActionError::Failed(
+            "permission denied: only root can use realtime or change others".to_string(),
+        )),
+        _ => Err(ActionError::Failed(err.to_string())),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn set_io_priority(_pid: Pid, _priority: IoPriority) -> Result<(), ActionError> {
+    Err(ActionError::Unsupported)
+}
+
+/// 現在の I/O 優先度。取得できなければ None
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn current_io_priority(pid: Pid) -> Option<IoPriority> {
+    // SAFETY: 整数引数だけのシステムコール
+    let raw = unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_get,
+            IOPRIO_WHO_PROCESS,
+            pid.as_u32() as libc::c_int,
+        )
+    };
+    if raw < 0 {
+        return None;
+    }
+    IoPriority::from_raw(raw as i32)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn current_io_priority(_pid: Pid) -> Option<IoPriority> {
+    None
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn set_affinity(pid: Pid, cpus: &[usize]) -> Result<(), ActionError> {
+    // SAFETY: cpu_set_t はただのビット列なので 0 埋めで空の集合になる
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for &cpu in cpus {
+        if cpu >= libc::CPU_SETSIZE as usize {
+            return Err(ActionError::Failed(format!("CPU {cpu} is out of range")));
+        }
+        // SAFETY: 範囲は上で確かめた
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    // SAFETY: set は size バイトの有効な cpu_set_t
+    if unsafe { libc::sched_setaffinity(pid.as_u32() as libc::pid_t, size, &set) } == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EPERM) => Err(ActionError::Failed(
+            "permission denied: only root can change other users' processes".to_string(),
+        )),
+        // オンラインの CPU が 1 つも含まれていない
+        Some(libc::EINVAL) => Err(ActionError::Failed(
+            "none of the selected CPUs is online".to_string(),
+        )),
+        _ => Err(ActionError::Failed(err.to_string())),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn set_affinity(_pid: Pid, _cpus: &[usize]) -> Result<(), ActionError> {
+    Err(ActionError::Unsupported)
+}
+
+/// 動かしてよい CPU の番号（昇順）。取得できなければ None
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn current_affinity(pid: Pid) -> Option<Vec<usize>> {
+    // SAFETY: 0 埋めの cpu_set_t に size バイトまで書き込ませるだけ
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(pid.as_u32() as libc::pid_t, size, &mut set) } != 0 {
+        return None;
+    }
+    let cpus = (0..libc::CPU_SETSIZE as usize)
+        // SAFETY: cpu は CPU_SETSIZE 未満
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .collect();
+    Some(cpus)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn current_affinity(_pid: Pid) -> Option<Vec<usize>> {
+    None
+}
+
+/// CPU の番号を "0-3,8" のように続いた範囲をまとめて書く
+pub fn cpu_list(cpus: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &cpu in cpus {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == cpu => *end = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}-{end}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+const SIGNALS: [(&str, Signal); 12] = [
+    ("HUP", Signal::Hangup),
+    ("INT", Signal::Interrupt),
+    ("QUIT", Signal::Quit),
+    ("ABRT", Signal::Abort),
+    ("KILL", Signal::Kill),
+    ("USR1", Signal::User1),
+    ("SEGV", Signal::Segv),
+    ("USR2", Signal::User2),
+    ("TERM", Signal::Term),
+    ("CONT", Signal::Continue),
+    ("STOP", Signal::Stop),
+    ("TSTP", Signal::TSTP),
+];
+
+pub fn signal_name(signal: Signal) -> &'static str {
+    SIGNALS
+        .iter()
+        .find(|(_, s)| *s == signal)
+        .map_or("?", |(name, _)| name)
+}
+
+/// "TERM" / "SIGTERM" / "15" のような入力をシグナルに変換する
+pub fn parse_signal(text: &str) -> Option<Signal> {
+    let text = text.trim().to_ascii_uppercase();
+    let name = text.strip_prefix("SIG").unwrap_or(&text);
+    if let Some((_, signal)) = SIGNALS.iter().find(|(n, _)| *n == name) {
+        return Some(*signal);
+    }
+    let number = match name.parse::<u32>().ok()? {
+        1 => Signal::Hangup,
+        2 => Signal::Interrupt,
+        3 => Signal::Quit,
+        6 => Signal::Abort,
+        9 => Signal::Kill,
+        15 => Signal::Term,
+        _ => return None,
+    };
+    Some(number)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::process::Command as Child;
+
+    use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+    use super::*;
+
+    #[test]
+    fn revert_restores_the_values_from_before_the_first_change() {
+        let mut child = Child::new("sleep").arg("30").spawn().expect("spawn sleep");
+        let pid = Pid::from_u32(child.id());
+        let mut sys = System::new();
+        sys.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&[pid]),
+            true,
+            ProcessRefreshKind::nothing(),
+        );
+        let target = ProcessRef {
+            pid,
+            start_time: sys.process(pid).expect("child").start_time(),
+        };
+        let mut originals = Originals::default();
+        let mut run = |command| execute(&mut sys, &mut originals, command).result;
+
+        // 何も変えていなければ戻すものは無い
+        assert!(run(Command::Revert { target }).is_err());
+
+        let cpus = current_affinity(pid).expect("affinity");
+        let priority = current_io_priority(pid).expect("I/O priority");
+        run(Command::SetAffinity {
+            target,
+            cpus: vec![cpus[0]],
+        })
+        .expect("pin");
+        // 2 回目に変えても、覚えておくのは最初の値
+        run(Command::SetIoPriority {
+            target,
+            priority: IoPriority::parse("be 7").expect("priority"),
+        })
+        .expect("ionice");
+        run(Command::SetIoPriority {
+            target,
+            priority: IoPriority::parse("idle").expect("priority"),
+        })
+        .expect("ionice");
+        assert_eq!(current_affinity(pid), Some(vec![cpus[0]]));
+
+        run(Command::Revert { target }).expect("revert");
+        assert_eq!(current_affinity(pid), Some(cpus));
+        assert_eq!(current_io_priority(pid), Some(priority));
+        // 戻したら忘れる
+        assert!(run(Command::Revert { target }).is_err());
+
+        // 変えられなかったものは覚えない
+        assert!(
+            run(Command::SetAffinity {
+                target,
+                cpus: vec![usize::MAX],
+            })
+            .is_err()
+        );
+        assert!(originals.0.is_empty());
+
+        child.kill().ok();
+        child.wait().ok();
+    }
+
+    #[test]
+    fn signal_tree_reaches_descendants_started_after_the_last_collection() {
+        let mut shell = Child::new("sh")
+            .args(["-c", "sleep 30 & wait"])
+            .spawn()
+            .expect("spawn sh");
+        let pid = Pid::from_u32(shell.id());
+        // サンプラーが最後に読んだときには子がまだいない
+        let mut sys = System::new();
+        sys.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&[pid]),
+            true,
+            ProcessRefreshKind::nothing(),
+        );
+        let target = ProcessRef {
+            pid,
+            start_time: sys.process(pid).expect("shell").start_time(),
+        };
+        let mut probe = System::new();
+        let sleep = (0..100)
+            .find_map(|_| {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                probe.refresh_processes(ProcessesToUpdate::All, true);
+                descendants(&probe, pid).first().copied()
+            })
+            .expect("sleep started");
+
+        let command = Command::SignalTree {
+            target,
+            signal: Signal::Kill,
+        };
+        execute(&mut sys, &mut Originals::default(), command)
+            .result
+            .expect("signal tree");
+        shell.wait().expect("wait");
+        let gone = (0..100).any(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            probe.refresh_processes(ProcessesToUpdate::Some(&[sleep]), true);
+            probe
+                .process(sleep)
+                .is_none_or(|p| p.status() == sysinfo::ProcessStatus::Zombie)
+        });
+        assert!(gone);
+    }
+
+    #[test]
+    fn current_nice_tells_a_missing_process_from_nice_minus_one() {
+        let mut child = Child::new("true").spawn().expect("spawn true");
+        let pid = Pid::from_u32(child.id());
+        child.wait().expect("wait");
+        assert_eq!(current_nice(pid), None);
+        let own = current_nice(Pid::from_u32(std::process::id()));
+        assert!(own.is_some_and(|nice| (-20..=19).contains(&nice)));
+    }
+}
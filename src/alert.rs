@@ -0,0 +1,502 @@
+// 設定ファイルの [[alert]] に書いた条件をスナップショットごとに評価する
+//
+// 例:
+//   [[alert]]
+//   name = "CPU hot"
+//   metric = "cpu"          # cpu / memory / swap（%）, process_cpu（%）, process_memory（バイト）
+//   above = 90
+//   for = 30                # 秒。この間ずっと超えていたら発報する
+//
+//   [[alert]]
+//   metric = "process_memory"
+//   process = "^java$"      # 名前かコマンドラインに一致するプロセスだけを見る
+//   above = "2GiB"
+//   notify = true           # デスクトップ通知も出す（notify 機能）
+//   notify_every = 600      # 秒。出たり消えたりしても、通知はこの間隔に 1 回まで（既定 10 分）
+//   run = "jstack $RUST_TOP_PID > /tmp/jstack.$RUST_TOP_PID"
+//
+// run は発報するたびにシェル（Windows では cmd）で実行する。ルールの名前・説明・値と、
+// プロセスの指標なら一番値の大きいプロセスの PID と名前を環境変数で渡す
+// （RUST_TOP_ALERT, RUST_TOP_DETAIL, RUST_TOP_VALUE, RUST_TOP_PID, RUST_TOP_NAME）。
+// 手元のマシンを見ているときだけ実行し、他のホストや履歴を見ているときは実行しない。
+//
+// プロセスに付けたタイマー（T キー）もここで評価する。
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use sysinfo::Pid;
+
+use crate::actions::ProcessRef;
+use crate::custom;
+use crate::pattern::Pattern;
+use crate::sampler::{ProcessInfo, Snapshot};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// 全 CPU を 100% とした使用率
+    Cpu,
+    /// メモリ使用率（%）
+    Memory,
+    /// スワップ使用率（%）
+    Swap,
+    /// プロセスの CPU 使用率（1 コア = 100%）
+    ProcessCpu,
+    /// プロセスのメモリ（バイト）
+    ProcessMemory,
+}
+
+impl Metric {
+    pub fn from_name(name: &str) -> Option<Metric> {
+        match name {
+            "cpu" => Some(Metric::Cpu),
+            "memory" => Some(Metric::Memory),
+            "swap" => Some(Metric::Swap),
+            "process_cpu" => Some(Metric::ProcessCpu),
+            "process_memory" => Some(Metric::ProcessMemory),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Metric::Cpu => "CPU",
+            Metric::Memory => "Memory",
+            Metric::Swap => "Swap",
+            Metric::ProcessCpu => "CPU",
+            Metric::ProcessMemory => "Memory",
+        }
+    }
+
+    pub fn is_process(self) -> bool {
+        matches!(self, Metric::ProcessCpu | Metric::ProcessMemory)
+    }
+
+    pub fn format(self, value: f64) -> String {
+        match self {
+            Metric::ProcessMemory => format!("{:.0} MB", value / 1024.0 / 1024.0),
+            _ => format!("{value:.1}%"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertRule {
+    pub name: String,
+    pub metric: Metric,
+    pub above: f64,
+    pub duration: Duration,
+    /// プロセスの指標で対象を絞る
+    pub process: Option<Pattern>,
+    pub notify: bool,
+    /// 同じルールのデスクトップ通知を出す最短の間隔
+    pub notify_every: Duration,
+    /// 発報したときに実行するシェルのコマンド
+    pub run: Option<String>,
+}
+
+/// notify_every の既定
+pub const NOTIFY_EVERY: Duration = Duration::from_secs(10 * 60);
+
+/// 発報中の警告
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub name: String,
+    /// 「CPU 95.0% > 90.0%」など
+    pub detail: String,
+    pub since: Instant,
+}
+
+/// 新しく発報した警告
+#[derive(Debug)]
+pub struct Fired<'a> {
+    pub rule: &'a AlertRule,
+    pub alert: &'a Alert,
+    pub value: f64,
+    /// プロセスの指標なら、一番値の大きいプロセス
+    pub process: Option<(Pid, Arc<str>)>,
+    /// デスクトップに通知するか（notify で、notify_every の間に通知していない）
+    #[cfg_attr(not(feature = "notify"), allow(dead_code))]
+    pub notify: bool,
+}
+
+impl Fired<'_> {
+    /// run に書いたコマンド。警告の内容を環境変数で渡す
+    pub fn command(&self) -> Option<Command> {
+        let mut command = custom::shell(self.rule.run.as_deref()?);
+        command
+            .env("RUST_TOP_ALERT", &self.rule.name)
+            .env("RUST_TOP_DETAIL", &self.alert.detail)
+            .env("RUST_TOP_VALUE", format!("{:.1}", self.value))
+            // 画面を崩さないよう、端末には何も書かせない
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if let Some((pid, name)) = &self.process {
+            command
+                .env("RUST_TOP_PID", pid.to_string())
+                .env("RUST_TOP_NAME", &**name);
+        }
+        Some(command)
+    }
+}
+
+/// プロセスに付けたタイマー
+#[derive(Debug, Clone)]
+pub struct Timer {
+    pub target: ProcessRef,
+    pub name: Arc<str>,
+    pub kind: TimerKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerKind {
+    /// この時刻にまだ動いていたら知らせる
+    StillRunning { deadline: Instant, after: Duration },
+    /// 終了したら知らせる
+    Exit,
+}
+
+impl TimerKind {
+    fn same_kind(self, other: TimerKind) -> bool {
+        std::mem::discriminant(&self) == std::mem::discriminant(&other)
+    }
+}
+
+/// ルールごとの評価状態
+#[derive(Debug, Default)]
+pub struct Alerts {
+    rules: Vec<AlertRule>,
+    /// 条件を満たし始めた時刻（ルールと同じ順）
+    exceeded_since: Vec<Option<Instant>>,
+    active: Vec<Option<Alert>>,
+    /// 最後にデスクトップに通知した時刻（ルールと同じ順）
+    notified: Vec<Option<Instant>>,
+    /// まだ発報していないタイマー
+    timers: Vec<Timer>,
+    /// 期限を過ぎても動いているプロセスの警告。終了すると消える
+    overdue: Vec<(ProcessRef, Alert)>,
+}
+
+impl Alerts {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Self {
+            exceeded_since: vec![None; rules.len()],
+            active: vec![None; rules.len()],
+            notified: vec![None; rules.len()],
+            rules,
+            ..Self::default()
+        }
+    }
+
+    /// タイマーを付ける。同じプロセスの同じ種類のタイマーは置き換える
+    pub fn add_timer(&mut self, timer: Timer) {
+        self.timers
+            .retain(|t| t.target != timer.target || !t.kind.same_kind(timer.kind));
+        self.timers.push(timer);
+    }
+
+    /// プロセスのタイマーと、期限切れの警告を外す。外した数を返す
+    pub fn clear_timers(&mut self, target: ProcessRef) -> usize {
+        let before = self.timers.len() + self.overdue.len();
+        self.timers.retain(|t| t.target != target);
+        self.overdue.retain(|(t, _)| *t != target);
+        before - self.timers.len() - self.overdue.len()
+    }
+
+    pub fn timers_for(&self, target: ProcessRef) -> impl Iterator<Item = &Timer> {
+        self.timers.iter().filter(move |t| t.target == target)
+    }
+
+    /// タイマーを評価し、発報したものの説明を返す
+    pub fn check_timers(&mut self, snapshot: &Snapshot, now: Instant) -> Vec<String> {
+        let alive = |target: ProcessRef| {
+            snapshot
+                .processes
+                .iter()
+                .any(|p| p.pid == target.pid && p.start_time == target.start_time)
+        };
+        self.overdue.retain(|(target, _)| alive(*target));
+        let mut fired = Vec::new();
+        let mut overdue = Vec::new();
+        self.timers.retain(|timer| {
+            let subject = format!("{} ({})", timer.name, timer.target.pid);
+            match timer.kind {
+                // 期限前に終了したら何もせずに外す
+                TimerKind::StillRunning { .. } if !alive(timer.target) => false,
+                TimerKind::StillRunning { deadline, after } if now >= deadline => {
+                    let detail = format!(
+                        "{subject} is still running after {}",
+                        format_duration(after)
+                    );
+                    fired.push(detail.clone());
+                    overdue.push((
+                        timer.target,
+                        Alert {
+                            name: "Timer".to_string(),
+                            detail,
+                            since: deadline,
+                        },
+                    ));
+                    false
+                }
+                TimerKind::Exit if !alive(timer.target) => {
+                    fired.push(format!("{subject} exited"));
+                    false
+                }
+                _ => true,
+            }
+        });
+        self.overdue.extend(overdue);
+        fired
+    }
+
+    /// 新しいスナップショットで評価し、新しく発報したものを返す
+    pub fn evaluate(&mut self, snapshot: &Snapshot, now: Instant) -> Vec<Fired<'_>> {
+        let mut fired = Vec::new();
+        if !snapshot.cpu_primed {
+            return Vec::new();
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            let Some((value, process)) = current_value(rule, snapshot) else {
+                self.exceeded_since[i] = None;
+                self.active[i] = None;
+                continue;
+            };
+            if value <= rule.above {
+                self.exceeded_since[i] = None;
+                self.active[i] = None;
+                continue;
+            }
+            let since = *self.exceeded_since[i].get_or_insert(now);
+            if now.duration_since(since) < rule.duration {
+                continue;
+            }
+            let subject = process.map_or_else(String::new, |p| format!("{} ({}) ", p.name, p.pid));
+            let detail = format!(
+                "{subject}{} {} > {}",
+                rule.metric.label(),
+                rule.metric.format(value),
+                rule.metric.format(rule.above)
+            );
+            match &mut self.active[i] {
+                Some(alert) => alert.detail = detail,
+                slot => {
+                    *slot = Some(Alert {
+                        name: rule.name.clone(),
+                        detail,
+                        since,
+                    });
+                    // 出たり消えたりする条件で通知が続かないようにする
+                    let quiet = self.notified[i]
+                        .is_some_and(|at| now.duration_since(at) < rule.notify_every);
+                    let notify = rule.notify && !quiet;
+                    if notify {
+                        self.notified[i] = Some(now);
+                    }
+                    let process = process.map(|p| (p.pid, Arc::clone(&p.name)));
+                    fired.push((i, value, process, notify));
+                }
+            }
+        }
+        fired
+            .into_iter()
+            .filter_map(|(i, value, process, notify)| {
+                Some(Fired {
+                    rule: &self.rules[i],
+                    alert: self.active[i].as_ref()?,
+                    value,
+                    process,
+                    notify,
+                })
+            })
+            .collect()
+    }
+
+    /// いずれかのルールがこの指標を見ているか
+    pub fn watches(&self, metric: Metric) -> bool {
+        self.rules.iter().any(|rule| rule.metric == metric)
+    }
+
+    pub fn active(&self) -> impl Iterator<Item = &Alert> {
+        self.active
+            .iter()
+            .flatten()
+            .chain(self.overdue.iter().map(|(_, alert)| alert))
+    }
+}
+
+// ルールが見る値と、プロセスの指標なら最も値の大きいプロセス
+fn current_value<'a>(
+    rule: &AlertRule,
+    snapshot: &'a Snapshot,
+) -> Option<(f64, Option<&'a ProcessInfo>)> {
+    let percent = |used: u64, total: u64| (total > 0).then(|| used as f64 / total as f64 * 100.0);
+    let value = match rule.metric {
+        Metric::Cpu => snapshot.cpu.normalized() as f64,
+        Metric::Memory => percent(snapshot.memory.used, snapshot.memory.total)?,
+        Metric::Swap => percent(snapshot.memory.used_swap, snapshot.memory.total_swap)?,
+        Metric::ProcessCpu | Metric::ProcessMemory => {
+            let process = snapshot
+                .processes
+                .iter()
+                // スレッドはプロセスと同じメモリを持つので数えない
+                .filter(|p| !p.is_thread)
+                .filter(|p| {
+                    rule.process
+                        .as_ref()
+                        .is_none_or(|pattern| pattern.is_match(&p.name) || pattern.is_match(&p.cmd))
+                })
+                .max_by(|a, b| {
+                    process_value(rule.metric, a).total_cmp(&process_value(rule.metric, b))
+                })?;
+            return Some((process_value(rule.metric, process), Some(process)));
+        }
+    };
+    Some((value, None))
+}
+
+pub fn process_value(metric: Metric, process: &ProcessInfo) -> f64 {
+    match metric {
+        Metric::ProcessMemory => process.memory as f64,
+        _ => process.cpu_usage as f64,
+    }
+}
+
+/// 「2GiB」「512MB」「1.5G」などをバイト数として読む
+pub fn parse_bytes(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let scale = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kb" | "kib" => 1024.0,
+        "m" | "mb" | "mib" => 1024.0 * 1024.0,
+        "g" | "gb" | "gib" => 1024.0 * 1024.0 * 1024.0,
+        "t" | "tb" | "tib" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some(number * scale)
+}
+
+/// 「30m」「90s」「1h」などを時間として読む。単位が無ければ分
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let scale = match unit.trim().to_ascii_lowercase().as_str() {
+        "s" | "sec" => 1.0,
+        "" | "m" | "min" => 60.0,
+        "h" | "hour" => 60.0 * 60.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(number * scale)
+        .ok()
+        .filter(|d| !d.is_zero())
+}
+
+/// 「1h05m」「30m」「45s」のように短く書く
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, 0) => format!("{m}m"),
+        (0, m, s) => format!("{m}m{s:02}s"),
+        (h, 0, _) => format!("{h}h"),
+        (h, m, _) => format!("{h}h{m:02}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{AlertRule, Alerts, Metric};
+    use crate::sampler::{Collector, Snapshot};
+    use crate::source::MockSource;
+
+    // 2 回収集して CPU 使用率が確定した扱いにする
+    fn snapshot() -> Snapshot {
+        let mut collector = Collector::with_source(MockSource::default());
+        collector.collect_all();
+        collector.collect_all();
+        let mut snapshot = collector.snapshot().clone();
+        snapshot.cpu_primed = true;
+        snapshot
+    }
+
+    #[test]
+    fn flapping_rules_notify_once_per_interval() {
+        let mut busy = snapshot();
+        busy.cpu.usage_sum = 95.0 * busy.cpu.count as f32;
+        let mut idle = busy.clone();
+        idle.cpu.usage_sum = 0.0;
+        let mut alerts = Alerts::new(vec![AlertRule {
+            name: "busy".to_string(),
+            metric: Metric::Cpu,
+            above: 90.0,
+            duration: Duration::ZERO,
+            process: None,
+            notify: true,
+            notify_every: Duration::from_secs(60),
+            run: None,
+        }]);
+        let start = Instant::now();
+        let notified = |alerts: &mut Alerts, snapshot: &Snapshot, secs| {
+            alerts
+                .evaluate(snapshot, start + Duration::from_secs(secs))
+                .iter()
+                .filter(|fired| fired.notify)
+                .count()
+        };
+        assert_eq!(notified(&mut alerts, &busy, 0), 1);
+        // 下がってまた上がっても、間隔の内は警告だけで通知しない
+        assert_eq!(notified(&mut alerts, &idle, 10), 0);
+        assert_eq!(notified(&mut alerts, &busy, 20), 0);
+        assert_eq!(alerts.active().count(), 1);
+        notified(&mut alerts, &idle, 30);
+        assert_eq!(notified(&mut alerts, &busy, 61), 1);
+    }
+
+    #[test]
+    fn commands_get_the_triggering_process() {
+        let snapshot = snapshot();
+        let mut alerts = Alerts::new(vec![AlertRule {
+            name: "hog".to_string(),
+            metric: Metric::ProcessCpu,
+            above: 0.0,
+            duration: Duration::ZERO,
+            process: None,
+            notify: false,
+            notify_every: Duration::ZERO,
+            run: Some("jstack $RUST_TOP_PID".to_string()),
+        }]);
+        let fired = alerts.evaluate(&snapshot, Instant::now());
+        let [fired] = fired.as_slice() else {
+            panic!("one alert should fire")
+        };
+        let busiest = snapshot
+            .processes
+            .iter()
+            .filter(|p| !p.is_thread)
+            .max_by(|a, b| a.cpu_usage.total_cmp(&b.cpu_usage))
+            .expect("mock processes");
+        let command = fired.command().expect("run is set");
+        let env = |name: &str| {
+            command
+                .get_envs()
+                .find(|(key, _)| *key == name)
+                .and_then(|(_, value)| value?.to_str())
+                .map(str::to_string)
+        };
+        assert_eq!(env("RUST_TOP_ALERT").as_deref(), Some("hog"));
+        assert_eq!(env("RUST_TOP_PID"), Some(busiest.pid.to_string()));
+        assert_eq!(env("RUST_TOP_NAME").as_deref(), Some(&*busiest.name));
+    }
+}
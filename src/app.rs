@@ -0,0 +1,3557 @@
+// アプリケーションの状態とキー操作
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crossterm::event::{
+    KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use ratatui::buffer::Buffer;
+use sysinfo::{Pid, ProcessStatus, Signal};
+
+use crate::access::Access;
+use crate::actions::{self, ActionError, ActionOutcome, Command, IoPriority, ProcessRef};
+use crate::alert::{self, Alerts, Metric, Timer, TimerKind};
+use crate::baseline::{Baseline, Change, DiffRow};
+use crate::cgtree::{CgroupTree, TreeRow};
+use crate::cli::Args;
+use crate::clipboard::Yank;
+use crate::config::{
+    CategoryConfig, ColumnConfig, Config, ExitNotice, FilterConfig, FilterPreset, Keymap,
+    NoisyConfig, Profile, ProtectConfig,
+};
+use crate::custom::CustomPanel;
+#[cfg(feature = "net")]
+use crate::dns::Resolver;
+use crate::events::{self, EventKind, EventLog};
+use crate::export::{self, ExportFormat};
+use crate::files::{self, OpenFile};
+use crate::filter::Filter;
+use crate::health::Finding;
+use crate::inhibit::Inhibitor;
+#[cfg(feature = "journal")]
+use crate::journal::JournalView;
+use crate::layout::{self, ScreenLayout, SideColumn};
+use crate::leak::LeakDetector;
+use crate::locale::Locale;
+use crate::log;
+#[cfg(feature = "net")]
+use crate::net;
+#[cfg(feature = "notify")]
+use crate::notify;
+use crate::numa;
+use crate::palette::{self, Action};
+use crate::par;
+use crate::pattern::Pattern;
+use crate::peers::{self, Peer};
+#[cfg(feature = "plugin")]
+use crate::plugin::Plugin;
+#[cfg(feature = "profile")]
+use crate::profile::ProfileView;
+use crate::sampler::{Paused, ProcessChanges, ProcessInfo, Snapshot};
+use crate::search::{self, Search, SearchHit};
+use crate::state::{self, UiState};
+use crate::summary::SessionStats;
+use crate::term::{self, ColorDepth};
+use crate::termux::{self, Restrictions};
+use crate::theme::{Theme, Threshold};
+use crate::ui::RowCache;
+use crate::watchdog::Watchdog;
+
+/// プロセス表の既定の行数。実際に収まった行数は描画のたびに Drawn から受け取るので、
+/// 最初に描画するまでの値と、他のパネルと並べるときの表の高さにだけ使う
+pub const PROCESS_ROWS: usize = 5;
+
+/// コマンドラインを Left / Right で送る文字数
+const COMMAND_SCROLL_STEP: usize = 8;
+
+/// 新しく現れたプロセスを強調表示する収集回数
+pub const NEW_PROCESS_TICKS: u8 = 3;
+
+/// 値が大きく動いたセルを強調表示する収集回数
+pub const CHANGE_TICKS: u8 = 3;
+/// 終了したプロセスを最後の値のまま表に残す収集回数
+pub const EXITED_TICKS: u8 = 2;
+/// 前回の収集からこれだけ動いたら強調する
+const CHANGE_CPU_POINTS: f32 = 20.0;
+const CHANGE_MEMORY_BYTES: u64 = 100 * 1024 * 1024;
+
+/// ピン留めしたプロセスについて保持する履歴の長さ
+pub const WATCH_HISTORY: usize = 60;
+/// グラフのパネルのために保持するシステム全体の使用率の数（広い端末の幅くらい）
+pub const GRAPH_HISTORY: usize = 300;
+
+/// メッセージを表示し続ける時間
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// ピン留めしたプロセスの終了を画面の反転で知らせる時間
+const FLASH_DURATION: Duration = Duration::from_millis(300);
+
+/// 入力中のプロンプト
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum PromptKind {
+    User,
+    Signal(ProcessRef),
+    /// 子孫にも送る
+    SignalTree(ProcessRef),
+    Renice(ProcessRef),
+    /// Space で印を付けたプロセスすべてに送る
+    SignalTagged(Vec<ProcessRef>),
+    ReniceTagged(Vec<ProcessRef>),
+    IoPriority(ProcessRef),
+    Timer(ProcessRef),
+    Search,
+    /// 表を絞らずに一致する行へ移る
+    Locate,
+    /// 入力したポートで待ち受けているプロセスへ移る
+    #[cfg(feature = "net")]
+    Port,
+    /// 画面の保存。text か html
+    Export,
+    /// コマンドモード（:）
+    Command,
+    /// 保護したプロセスへのシグナル。名前を入力したら送る
+    Confirm {
+        command: Command,
+        name: Arc<str>,
+    },
+}
+
+impl PromptKind {
+    pub fn label(&self) -> String {
+        match *self {
+            PromptKind::User => "Which user (blank for all)".to_string(),
+            PromptKind::Locate => "Locate in the table (n for next, < for previous)".to_string(),
+            #[cfg(feature = "net")]
+            PromptKind::Port => "Go to the process listening on port".to_string(),
+            PromptKind::Signal(target) => format!("Send signal to {}", target.pid),
+            PromptKind::SignalTree(target) => {
+                format!("Send signal to {} and its children", target.pid)
+            }
+            PromptKind::Renice(target) => format!("Renice {} (-20..19)", target.pid),
+            PromptKind::SignalTagged(ref targets) => {
+                format!("Send signal to {} tagged processes", targets.len())
+            }
+            PromptKind::ReniceTagged(ref targets) => {
+                format!("Renice {} tagged processes (-20..19)", targets.len())
+            }
+            PromptKind::IoPriority(target) => format!(
+                "I/O priority of {} (realtime 0-7, best-effort 0-7, idle or none)",
+                target.pid
+            ),
+            PromptKind::Timer(target) => format!(
+                "Alert if {} is still running after (e.g. 30m; blank = when it exits, - = clear)",
+                target.pid
+            ),
+            PromptKind::Search => "Search processes, connections and containers".to_string(),
+            PromptKind::Export => {
+                "Save the screen (text or html) or processes (csv or json) as".to_string()
+            }
+            PromptKind::Command => "Command (Tab to complete)".to_string(),
+            PromptKind::Confirm {
+                ref command,
+                ref name,
+            } => format!("{name} is protected. Type \"{name}\" to {command}"),
+        }
+    }
+}
+
+/// CPU 使用率の表示モード（top の Irix / Solaris モード）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CpuMode {
+    /// 1 コア = 100% としてそのまま表示する
+    #[default]
+    Irix,
+    /// コア数で割り、全体を 100% として表示する
+    Solaris,
+}
+
+impl CpuMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            CpuMode::Irix => "Irix",
+            CpuMode::Solaris => "Solaris",
+        }
+    }
+
+    /// 生の使用率（コアごとの合計）を表示用の値に変換する
+    pub fn scale(self, usage: f32, cpu_count: usize) -> f32 {
+        match self {
+            CpuMode::Irix => usage,
+            CpuMode::Solaris => usage / cpu_count.max(1) as f32,
+        }
+    }
+}
+
+/// プロセス表の列の数
+pub const COLUMN_COUNT: usize = SortKey::COLUMNS.len();
+
+/// プロセス表の列（並べ替えの基準）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SortKey {
+    Pid,
+    User,
+    /// ブラウザやシェルなどの種類（[categories]）。d で出したときだけ表示する
+    Category,
+    Name,
+    Container,
+    State,
+    /// 眠っているプロセスが待っているカーネルの関数。i で出したときだけ表示する（Linux）
+    WaitChannel,
+    Threads,
+    /// 開いているファイルの数。D で出したときだけ表示する
+    Files,
+    #[default]
+    Cpu,
+    /// CPU 使用率の推移。H で出したときだけ表示する
+    History,
+    Memory,
+    /// 仮想メモリ。Virtual 以降の 3 列はメモリの内訳（M）を出したときだけ表示する
+    Virtual,
+    Shared,
+    /// スワップに出た量。m でフォールトの列を出したときも表示する
+    Swap,
+    /// 1 秒あたりのマイナーフォールト。MinorFaults と MajorFaults は m で出したときだけ表示する
+    MinorFaults,
+    /// 1 秒あたりのメジャーフォールト（ディスクから読み直したページ）
+    MajorFaults,
+    /// メモリを持っている NUMA ノード。n で出したときだけ表示する
+    Node,
+    /// TCP の受信量（毎秒）。Received と Sent は b で出したときだけ表示する（net 機能）
+    Received,
+    /// TCP の送信量（毎秒）
+    Sent,
+    Time,
+    /// 起動時刻。Started と Elapsed は E で出したときだけ表示する
+    Started,
+    /// 起動からの経過時間
+    Elapsed,
+}
+
+impl SortKey {
+    /// 表の左からの並び
+    pub const COLUMNS: [SortKey; 23] = [
+        SortKey::Pid,
+        SortKey::User,
+        SortKey::Category,
+        SortKey::Name,
+        SortKey::Container,
+        SortKey::State,
+        SortKey::WaitChannel,
+        SortKey::Threads,
+        SortKey::Files,
+        SortKey::Cpu,
+        SortKey::History,
+        SortKey::Memory,
+        SortKey::Virtual,
+        SortKey::Shared,
+        SortKey::Swap,
+        SortKey::MinorFaults,
+        SortKey::MajorFaults,
+        SortKey::Node,
+        SortKey::Received,
+        SortKey::Sent,
+        SortKey::Time,
+        SortKey::Started,
+        SortKey::Elapsed,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::Pid => "PID",
+            SortKey::User => "User",
+            SortKey::Category => "Category",
+            SortKey::Name => "Name",
+            SortKey::Container => "Container",
+            SortKey::State => "State",
+            SortKey::WaitChannel => "WChan",
+            SortKey::Threads => "THR",
+            SortKey::Files => "FDs",
+            SortKey::Cpu => "CPU",
+            SortKey::History => "History",
+            SortKey::Memory => "Memory",
+            SortKey::Virtual => "VIRT",
+            SortKey::Shared => "SHR",
+            SortKey::Swap => "Swap",
+            SortKey::MinorFaults => "MinFlt",
+            SortKey::MajorFaults => "MajFlt",
+            SortKey::Node => "Node",
+            SortKey::Received => "RX",
+            SortKey::Sent => "TX",
+            SortKey::Time => "TIME+",
+            SortKey::Started => "Started",
+            SortKey::Elapsed => "Elapsed",
+        }
+    }
+
+    /// 列名（大文字小文字を区別しない）から探す
+    pub fn from_name(name: &str) -> Option<SortKey> {
+        SortKey::COLUMNS
+            .into_iter()
+            .find(|key| key.label().eq_ignore_ascii_case(name))
+    }
+
+    /// 数値の列（既定で右寄せにする）
+    pub fn is_numeric(self) -> bool {
+        !matches!(
+            self,
+            SortKey::User
+                | SortKey::Category
+                | SortKey::Name
+                | SortKey::Container
+                | SortKey::State
+                | SortKey::WaitChannel
+        )
+    }
+
+    /// メモリの内訳の列（既定では出さない）
+    pub fn is_memory_detail(self) -> bool {
+        matches!(self, SortKey::Virtual | SortKey::Shared | SortKey::Swap)
+    }
+
+    // 数値の列は大きい順から始める（PID とノード番号、経過時間は小さい順）
+    // Started は新しい順、Elapsed は短い順なので、どちらも最近起動し直したプロセスが上に来る
+    fn descending_by_default(self) -> bool {
+        self.is_numeric() && !matches!(self, SortKey::Pid | SortKey::Node | SortKey::Elapsed)
+    }
+
+    /// 起動時刻の列
+    pub fn is_process_time(self) -> bool {
+        matches!(self, SortKey::Started | SortKey::Elapsed)
+    }
+
+    /// ページフォールトの列
+    pub fn is_fault(self) -> bool {
+        matches!(self, SortKey::MinorFaults | SortKey::MajorFaults)
+    }
+
+    /// 送受信量の列
+    pub fn is_traffic(self) -> bool {
+        matches!(self, SortKey::Received | SortKey::Sent)
+    }
+
+    fn compare(self, a: &ProcessInfo, b: &ProcessInfo, categories: &CategoryConfig) -> Ordering {
+        match self {
+            SortKey::Pid => a.pid.cmp(&b.pid),
+            SortKey::User => a.user.cmp(&b.user),
+            SortKey::Category => {
+                let name = |p: &ProcessInfo| {
+                    categories
+                        .classify(&p.name, p.is_kernel_thread())
+                        .map(|c| c.name.as_str())
+                };
+                name(a).cmp(&name(b))
+            }
+            SortKey::Name => a.name.cmp(&b.name),
+            SortKey::Container => a.container.cmp(&b.container),
+            SortKey::State => a.status.cmp(&b.status),
+            SortKey::WaitChannel => a.wait_channel.cmp(&b.wait_channel),
+            SortKey::Threads => a.threads.cmp(&b.threads),
+            SortKey::Files => a.open_files.cmp(&b.open_files),
+            SortKey::Cpu => a.cpu_usage.total_cmp(&b.cpu_usage),
+            // 推移の列は期間中の平均で並べる
+            SortKey::History => average(&a.cpu_history).total_cmp(&average(&b.cpu_history)),
+            SortKey::Memory => a.memory.cmp(&b.memory),
+            SortKey::Virtual => a.virtual_memory.cmp(&b.virtual_memory),
+            SortKey::Shared => a.shared.cmp(&b.shared),
+            SortKey::Swap => a.swap.cmp(&b.swap),
+            SortKey::MinorFaults => a.minor_faults.cmp(&b.minor_faults),
+            SortKey::MajorFaults => a.major_faults.cmp(&b.major_faults),
+            // いちばん多く持っているノードで並べる
+            SortKey::Node => {
+                let node = |p: &ProcessInfo| p.numa_pages.as_deref().and_then(numa::main_node);
+                node(a).cmp(&node(b))
+            }
+            SortKey::Received => a.net_received.cmp(&b.net_received),
+            SortKey::Sent => a.net_sent.cmp(&b.net_sent),
+            SortKey::Time => a.cpu_time.cmp(&b.cpu_time),
+            SortKey::Started => a.start_time.cmp(&b.start_time),
+            // 早く起動したものほど経過時間が長い
+            SortKey::Elapsed => b.start_time.cmp(&a.start_time),
+        }
+    }
+}
+
+/// ユーザーごとの集計（W）の並び順
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UserSort {
+    #[default]
+    Cpu,
+    Memory,
+    Processes,
+    User,
+}
+
+impl UserSort {
+    pub fn label(self) -> &'static str {
+        match self {
+            UserSort::Cpu => "CPU",
+            UserSort::Memory => "Memory",
+            UserSort::Processes => "Procs",
+            UserSort::User => "User",
+        }
+    }
+
+    // s で次の列へ
+    fn next(self) -> UserSort {
+        match self {
+            UserSort::Cpu => UserSort::Memory,
+            UserSort::Memory => UserSort::Processes,
+            UserSort::Processes => UserSort::User,
+            UserSort::User => UserSort::Cpu,
+        }
+    }
+}
+
+/// 1 人のユーザーのプロセスの合計
+#[derive(Debug, Clone)]
+pub struct UserUsage {
+    pub user: Arc<str>,
+    pub processes: usize,
+    /// 表示する値（CPU モードで換算済み）
+    pub cpu: f32,
+    pub memory: u64,
+}
+
+/// CPU アフィニティの編集中の状態（a）
+#[derive(Debug, Clone)]
+pub struct AffinityDialog {
+    pub target: ProcessRef,
+    /// CPU 番号ごとに、動かしてよいか
+    pub cpus: Vec<bool>,
+    pub cursor: usize,
+}
+
+/// 開いているファイルの一覧（f）
+#[derive(Debug, Clone)]
+pub struct FilesView {
+    pub pid: Pid,
+    pub files: Vec<OpenFile>,
+    pub selected: usize,
+}
+
+/// パイプやソケットでつながっているプロセスの一覧（X）
+#[derive(Debug, Clone)]
+pub struct PeersView {
+    pub pid: Pid,
+    pub peers: Vec<Peer>,
+    pub selected: usize,
+}
+
+/// cgroup の階層（s）。collapsed は畳んだグループのパス
+#[derive(Debug)]
+pub struct CgroupTreeView {
+    pub tree: CgroupTree,
+    pub collapsed: HashSet<String>,
+    pub selected: usize,
+}
+
+impl CgroupTreeView {
+    /// 最初はルートとその直下（system.slice など）だけを見せる
+    pub fn new(tree: CgroupTree) -> Self {
+        let collapsed = tree
+            .nodes
+            .iter()
+            .filter(|node| node.depth >= 1 && node.has_children)
+            .map(|node| node.path.clone())
+            .collect();
+        Self {
+            tree,
+            collapsed,
+            selected: 0,
+        }
+    }
+
+    pub fn rows(&self) -> Vec<TreeRow> {
+        self.tree.rows(&self.collapsed)
+    }
+
+    // 畳む・開くでグループの行の位置が変わるので、選び直す
+    fn toggle(&mut self, group: usize, collapse: bool) {
+        let path = self.tree.nodes[group].path.clone();
+        if collapse {
+            self.collapsed.insert(path);
+        } else {
+            self.collapsed.remove(&path);
+        }
+        if let Some(row) = self.rows().iter().position(|r| *r == TreeRow::Group(group)) {
+            self.selected = row;
+        }
+    }
+}
+
+/// 基準にした時点との比較（B）。開いたときの一覧を比べる
+#[derive(Debug, Clone)]
+pub struct BaselineView {
+    /// 基準にした時刻（UNIX 時間の秒）
+    pub since: u64,
+    pub rows: Vec<DiffRow>,
+    pub selected: usize,
+}
+
+/// 環境変数の一覧（v）。文字を打つと名前か値で絞り込む
+#[cfg(feature = "environ")]
+#[derive(Debug, Clone)]
+pub struct EnvironView {
+    pub target: ProcessRef,
+    /// "名前=値" を名前順に
+    pub vars: Vec<String>,
+    pub query: String,
+    /// 絞り込んだ後の何行目か
+    pub selected: usize,
+}
+
+#[cfg(feature = "environ")]
+impl EnvironView {
+    /// 絞り込みに一致する変数（大文字と小文字は区別しない）
+    pub fn shown(&self) -> Vec<&str> {
+        let query = self.query.to_lowercase();
+        self.vars
+            .iter()
+            .filter(|var| var.to_lowercase().contains(&query))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// アフィニティの画面で 1 行に並べる CPU の数
+pub const AFFINITY_COLUMNS: usize = 8;
+
+fn average(values: &[f32]) -> f32 {
+    values.iter().sum::<f32>() / values.len().max(1) as f32
+}
+
+/// マウスで指した場所
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClickTarget {
+    /// プロセス表の見出し
+    Header(SortKey),
+    /// プロセス表の行（画面上の何行目か）
+    Row(usize),
+}
+
+/// Alt+数字で隠せるパネル
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Panel {
+    Cpu,
+    Memory,
+    Processes,
+    Info,
+}
+
+impl Panel {
+    fn from_key(c: char) -> Option<Panel> {
+        match c {
+            '1' => Some(Panel::Cpu),
+            '2' => Some(Panel::Memory),
+            '3' => Some(Panel::Processes),
+            '4' => Some(Panel::Info),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Panel::Cpu => "CPU",
+            Panel::Memory => "Memory",
+            Panel::Processes => "Processes",
+            Panel::Info => "Info",
+        }
+    }
+}
+
+/// キー入力の結果としてメインループに依頼すること
+pub enum Effect {
+    None,
+    Quit,
+    Send(Command),
+    /// 印を付けたプロセスそれぞれへの操作
+    SendAll(Vec<Command>),
+    /// 更新間隔を変える（:interval）
+    Interval(Duration),
+    /// クリップボードに写す（y / Y / :yank）
+    Copy(String),
+    /// 環境変数を読ませる（v）
+    #[cfg(feature = "environ")]
+    Environ(ProcessRef),
+}
+
+#[derive(Debug, Default)]
+pub struct App {
+    pub user_filter: Option<String>,
+    /// :category で選んだ種類（[categories] の名前）
+    pub category_filter: Option<String>,
+    /// 式での絞り込み（:filter と --filter）
+    pub name_filter: Option<Filter>,
+    /// 表を絞らずに、名前かコマンドラインが一致する行を強調して n と < で移る（l）
+    pub locate: Option<Pattern>,
+    pub prompt: Option<Prompt>,
+    pub cpu_mode: CpuMode,
+    pub sort: SortKey,
+    /// 既定の向き（SortKey ごとに決まる）と逆に並べる
+    pub sort_reversed: bool,
+    /// 設定ファイルの [sort] then。同じ値の行を並べる列
+    sort_then: Vec<SortKey>,
+    /// 選択中のプロセス（並び順が変わっても PID で追従する）
+    pub selected: Option<Pid>,
+    /// 選択中の行番号。選択したプロセスが消えたときは近くの行を選び直す
+    pub selected_row: usize,
+    pub offset: usize,
+    /// 前回の描画でプロセス表に収まった行数
+    process_rows: usize,
+    /// 前回の描画で一覧のポップアップに収まった行数
+    popup_rows: usize,
+    /// Alt+数字で隠したパネル
+    hidden: HashSet<Panel>,
+    /// 詳細表示中のプロセス
+    pub detail: Option<Pid>,
+    /// スレッド一覧（t）を表示中のプロセス
+    pub threads: Option<Pid>,
+    /// Name 列にコマンドラインを表示する（C）
+    pub full_command: bool,
+    /// コマンドライン表示で左に送った文字数（Left / Right）
+    pub command_scroll: usize,
+    /// 同じ名前のプロセスを 1 行にまとめる（g）
+    pub group_by_name: bool,
+    /// VIRT / SHR / Swap の列を出す（M）。SHR と Swap は表示中だけ読む
+    pub memory_detail: bool,
+    /// CPU 使用率の推移の列を出す（H）
+    pub cpu_history: bool,
+    /// NUMA ノードの列を出す（n）。表示中だけ numa_maps を読む
+    pub numa_nodes: bool,
+    /// 開いているファイルの数の列を出す（D）。表示中だけ /proc/<pid>/fd を数える
+    pub fd_counts: bool,
+    /// WChan 列（i）。眠っているプロセスの待っている関数を読む
+    pub wait_channels: bool,
+    /// Category 列（d）。[categories] で分けたプロセスの種類を出す
+    pub category_column: bool,
+    /// ページフォールトの速さとスワップの量の列を出す（m）。表示中だけ stat と status を読む
+    pub page_faults: bool,
+    /// プロセスごとの送受信量の列を出す（b）。表示中だけ ss を動かす
+    pub net_traffic: bool,
+    /// 起動時刻と経過時間の列を出す（E）
+    pub process_times: bool,
+    /// 設定ファイルの [processes] rows。None なら空いた高さいっぱいに出す
+    pub page_rows: Option<usize>,
+    /// 組み込みと [layouts] のレイアウト（V で順に切り替える）
+    pub layouts: Vec<ScreenLayout>,
+    /// 使っているレイアウトの layouts での位置
+    pub layout: usize,
+    /// [layouts] side
+    pub side_column: SideColumn,
+    /// グラフのパネルに出す CPU とメモリの使用率の推移
+    pub usage_history: UsageHistory,
+    /// 前の描画で整形したプロセス表のセル。描画は &App で行うので Mutex に入れる
+    pub row_cache: Mutex<RowCache>,
+    /// 設定ファイルの [[panel]]。他のホストを見ているときは空
+    pub custom_panels: Vec<CustomPanel>,
+    /// 設定ファイルの [[plugin]]。他のホストを見ているときは空
+    #[cfg(feature = "plugin")]
+    pub plugins: Vec<Plugin>,
+    /// [[alert]] の run を実行する。手元のマシンを見ているときだけ
+    pub alert_actions: bool,
+    /// 設定ファイルの [[watchdog]]。他のホストを見ているときは空
+    pub watchdog: Watchdog,
+    /// RSS が増え続けているプロセス（[leak]）
+    pub leaks: LeakDetector,
+    /// watchdog が送るシグナル。メインループがサンプラーに渡す
+    watchdog_commands: Vec<Command>,
+    /// watchdog がしたことの一覧（w）を表示中
+    pub watchdog_log: bool,
+    /// コアごとの使用率の推移をヒートマップで表示中か（O）
+    pub heatmap: bool,
+    /// 起動したときの健康診断で見つかった問題。空でなければ一覧を表示する（Esc で閉じる）
+    pub health: Vec<Finding>,
+    /// プロセスの起動と終了・警告・操作・失敗の記録
+    pub events: EventLog,
+    /// 記録の一覧（L）を表示中なら、選択している行
+    pub events_view: Option<usize>,
+    /// 他のパネルを隠してプロセス表だけを画面いっぱいに出す（A）
+    pub show_all: bool,
+    /// カーネルスレッドも表に出す（h）。既定では隠す
+    pub kernel_threads: bool,
+    /// まとめた行のうち、メンバーを開いている名前（e）
+    expanded: HashSet<Arc<str>>,
+    pub message: Option<(String, Instant)>,
+    /// 最後に起きた失敗と時刻。メッセージが消えた後もステータスバーに出す
+    pub last_error: Option<(String, SystemTime)>,
+    /// 前回の収集で見えていた PID
+    /// 新しく現れたプロセスと、現れてからの収集回数
+    new_pids: HashMap<Pid, u8>,
+    /// 終了したプロセスの最後の値と、終了してからの収集回数
+    exited: Vec<(ProcessInfo, u8)>,
+    /// 前回の収集での CPU（表示する値）とメモリ
+    previous_values: HashMap<Pid, (f32, u64)>,
+    /// 値が大きく動いたセルと、動いてからの収集回数
+    changed: HashMap<(Pid, SortKey), u8>,
+    process_generation: u64,
+    /// ピン留めしたプロセス。空でなければ表をこれらとその子孫に絞る
+    pub pinned: Vec<Pid>,
+    /// Space で印を付けたプロセス。k / N / S はこれらすべてに効く
+    pub tagged: Vec<ProcessRef>,
+    pub pin_history: HashMap<Pid, PinHistory>,
+    /// 追いかけているプロセス。並び順が変わっても更新のたびに選択し直す
+    pub followed: Option<Followed>,
+    /// 設定ファイルの [follow] restarts
+    follow_restarts: bool,
+    /// ピン留めしたプロセスが終了したときの知らせ方
+    on_exit: Vec<ExitNotice>,
+    /// 次の描画の後にベルを鳴らす
+    bell: bool,
+    /// この時刻まで画面を反転する
+    flash_until: Option<Instant>,
+    pub locale: Locale,
+    pub theme: Theme,
+    /// [[theme.threshold]]。空なら theme の注意・警告の色を使う
+    pub thresholds: Vec<Threshold>,
+    /// [profiles.<name>]（--profile と :profile）
+    profiles: Vec<Profile>,
+    /// 最後に切り替えたプロファイルの名前
+    pub active_profile: Option<String>,
+    /// 端末が出せる色。配色はこれに丸めて使う
+    colors: ColorDepth,
+    /// 罫線や記号を ASCII で描く（--ascii、または Unicode を出せない端末）
+    pub ascii: bool,
+    /// スクリーンリーダー向けの表示（--plain）。罫線とグラフを消し、状態の変化を文章で知らせる
+    pub plain: bool,
+    /// 見せるだけの端末（--readonly）。シグナルや nice などプロセスを変える操作を断る
+    pub readonly: bool,
+    /// スリープを止めている間だけ持つ（:caffeinate、--caffeinate）
+    inhibitor: Option<Inhibitor>,
+    /// 計測値の表示（F12）。表示中は前のフレームの計測値を持つ
+    pub profile: Option<FrameTimes>,
+    /// プロセス一覧の更新間隔（ステータスバーに表示する）
+    pub refresh_interval: Duration,
+    /// :interval で変えた更新間隔。終了するときに状態ファイルに残す
+    pub chosen_interval: Option<Duration>,
+    /// 操作も負荷も無いので延ばしている間の、プロセス一覧の間隔（[idle]）
+    pub idle_interval: Option<Duration>,
+    /// 起動してからの平均・最大と CPU を多く使ったプロセス（--summary で終了時に表示する）
+    pub session: SessionStats,
+    /// 設定ファイルの include / exclude
+    pub filter: FilterConfig,
+    /// 設定ファイルの [[preset]]
+    presets: Vec<FilterPreset>,
+    /// 数字キーで選んでいる preset（presets の位置）
+    preset: Option<usize>,
+    /// 設定ファイルの [protect]
+    protect: ProtectConfig,
+    /// 設定ファイルの [noisy]
+    pub noisy: NoisyConfig,
+    /// 設定ファイルの [categories]
+    pub categories: CategoryConfig,
+    /// 設定ファイルの [keys] keymap
+    pub keymap: Keymap,
+    /// vim のキー割り当てで g を 1 回押したところ（もう一度で先頭へ）
+    pending_g: bool,
+    /// 設定ファイルの [[alert]] の評価状態
+    pub alerts: Alerts,
+    /// 設定ファイルで指定した列ごとの幅と寄せ方
+    pub columns: HashMap<SortKey, ColumnConfig>,
+    /// Termux の互換モードなら、読めなかったもの
+    pub termux: Option<Restrictions>,
+    /// 権限が足りずに読めなかったソース。作り物や別の機械の値を出すときは調べない
+    pub access: Access,
+    /// 保存したスナップショットを表示中なら、その時刻と位置
+    #[cfg(feature = "history")]
+    pub history: Option<String>,
+    /// 検索結果（/）を表示中なら、その一覧
+    pub search: Option<Search>,
+    /// 次に描いた画面を保存する形式（S）
+    export: Option<ExportFormat>,
+    /// 接続一覧（c）を表示中なら、選択している行
+    #[cfg(feature = "net")]
+    pub connections: Option<usize>,
+    /// 接続一覧の相手をホスト名で出す（r）。引くスレッドは初めて有効にしたときに立てる
+    #[cfg(feature = "net")]
+    pub resolver: Option<Resolver>,
+    #[cfg(feature = "net")]
+    pub resolve_names: bool,
+    /// サービス一覧（U）を表示中なら、選択している行
+    #[cfg(any(feature = "systemd", windows))]
+    pub services: Option<usize>,
+    /// 表をこのユニットのプロセスに絞る
+    #[cfg(any(feature = "systemd", windows))]
+    pub service_filter: Option<Arc<str>>,
+    /// ユーザーごとの集計（W）を表示中なら、選択している行
+    pub users: Option<usize>,
+    pub user_sort: UserSort,
+    pub affinity: Option<AffinityDialog>,
+    pub files: Option<FilesView>,
+    pub peers: Option<PeersView>,
+    /// B で覚えた時点と、それとの比較
+    pub baseline: Option<Baseline>,
+    pub baseline_view: Option<BaselineView>,
+    pub cgroup_tree: Option<CgroupTreeView>,
+    #[cfg(feature = "environ")]
+    pub environ: Option<EnvironView>,
+    /// 選んだプロセスのログ（J）
+    #[cfg(feature = "journal")]
+    pub journal: Option<JournalView>,
+    #[cfg(feature = "journal")]
+    journal_file: Option<std::path::PathBuf>,
+    /// 選んだプロセスのプロファイル（R）
+    #[cfg(feature = "profile")]
+    pub cpu_profile: Option<ProfileView>,
+    #[cfg(feature = "profile")]
+    profile_config: crate::config::ProfileConfig,
+}
+
+/// 追いかけているプロセス（F）
+#[derive(Debug, Clone)]
+pub struct Followed {
+    pub pid: Pid,
+    start_time: u64,
+    pub name: Arc<str>,
+    cmd: Arc<str>,
+    /// 終了を知らせた後なら true
+    pub exited: bool,
+}
+
+/// 1 フレームの描画にかかった時間
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameTimes {
+    /// 並べ替えと表の整形
+    pub prepare: Duration,
+    /// 整形を除いた描画と端末への出力
+    pub render: Duration,
+}
+
+/// ピン留めしたプロセスの CPU / メモリの推移
+#[derive(Debug, Default)]
+pub struct PinHistory {
+    /// 最後に見えていたときの名前
+    pub name: Arc<str>,
+    /// 終了を知らせた後なら true
+    exited: bool,
+    /// 0.1% 単位
+    pub cpu: VecDeque<u64>,
+    /// バイト
+    pub memory: VecDeque<u64>,
+}
+
+/// システム全体の使用率の推移（0.1% 単位）
+#[derive(Debug, Default)]
+pub struct UsageHistory {
+    pub cpu: VecDeque<u64>,
+    pub memory: VecDeque<u64>,
+    /// コアごとの使用率（CPU 番号の順、%）。ヒートマップ（O）に使う
+    pub cores: VecDeque<Vec<u8>>,
+    /// パッケージの消費電力（0.1 W 単位）。読めたときだけ溜める
+    pub power: VecDeque<u64>,
+}
+
+impl UsageHistory {
+    fn push(&mut self, snapshot: &Snapshot) {
+        if !snapshot.cpu_primed {
+            return;
+        }
+        if self.cpu.len() == GRAPH_HISTORY {
+            self.cpu.pop_front();
+            self.memory.pop_front();
+            self.cores.pop_front();
+        }
+        let memory = &snapshot.memory;
+        let percent = memory.used as f64 * 100.0 / memory.total.max(1) as f64;
+        self.cpu
+            .push_back((snapshot.cpu.normalized() * 10.0) as u64);
+        self.memory.push_back((percent * 10.0) as u64);
+        self.cores.push_back(
+            snapshot
+                .cpu
+                .usages
+                .iter()
+                .map(|u| u.clamp(0.0, 100.0).round() as u8)
+                .collect(),
+        );
+        if let Some(watts) = snapshot.sensors.as_ref().and_then(|s| s.package_watts) {
+            if self.power.len() == GRAPH_HISTORY {
+                self.power.pop_front();
+            }
+            self.power.push_back((watts * 10.0).round() as u64);
+        }
+    }
+}
+
+// --plain では、色で示す状態を行頭の記号でも示す
+fn with_symbols(mut theme: Theme, plain: bool) -> Theme {
+    theme.symbols |= plain;
+    theme
+}
+
+fn fit_thresholds(colors: ColorDepth, thresholds: &[Threshold]) -> Vec<Threshold> {
+    thresholds
+        .iter()
+        .map(|t| Threshold {
+            style: colors.fit_style(t.style),
+            ..*t
+        })
+        .collect()
+}
+
+impl PinHistory {
+    fn push(&mut self, process: &ProcessInfo) {
+        self.name = Arc::clone(&process.name);
+        self.exited = false;
+        if self.cpu.len() == WATCH_HISTORY {
+            self.cpu.pop_front();
+            self.memory.pop_front();
+        }
+        self.cpu.push_back((process.cpu_usage * 10.0) as u64);
+        self.memory.push_back(process.memory);
+    }
+}
+
+impl App {
+    pub fn new(args: &Args, config: &Config) -> Self {
+        let (colors, ascii) = term::capabilities(args, config);
+        let plain = args.plain || config.plain;
+        let layouts = layout::all(&config.layouts);
+        Self {
+            pinned: args.pids.clone(),
+            name_filter: args.filter.clone(),
+            process_rows: PROCESS_ROWS,
+            popup_rows: PROCESS_ROWS,
+            on_exit: config
+                .on_exit
+                .clone()
+                .unwrap_or_else(|| vec![ExitNotice::Bell]),
+            locale: Locale::from_config(&config.format),
+            theme: with_symbols(colors.fit_theme(config.theme), plain),
+            thresholds: fit_thresholds(colors, &config.thresholds),
+            profiles: config.profiles.clone(),
+            active_profile: None,
+            colors,
+            ascii: ascii || plain,
+            readonly: args.readonly,
+            inhibitor: None,
+            plain,
+            refresh_interval: config.refresh.processes,
+            filter: config.filter.clone(),
+            presets: config.presets.clone(),
+            protect: config.protect.clone(),
+            noisy: config.noisy.clone(),
+            categories: config.categories.clone(),
+            keymap: config.keymap,
+            follow_restarts: config.follow_restarts,
+            sort_then: config.sort_then.clone(),
+            page_rows: config.process_rows,
+            layout: config
+                .layout
+                .as_ref()
+                .and_then(|name| layouts.iter().position(|l| &l.name == name))
+                .unwrap_or(0),
+            layouts,
+            side_column: config.side_column,
+            usage_history: UsageHistory::default(),
+            columns: config.columns.clone(),
+            alerts: Alerts::new(config.alerts.clone()),
+            leaks: LeakDetector::new(config.leak.clone()),
+            events: EventLog::new(config.events_keep.unwrap_or(events::KEEP)),
+            #[cfg(feature = "journal")]
+            journal_file: config.journal_file.clone(),
+            #[cfg(feature = "profile")]
+            profile_config: config.profile.clone(),
+            termux: config
+                .termux
+                .unwrap_or_else(termux::detected)
+                .then(Restrictions::probe),
+            access: if args.mock || args.connect.is_some() {
+                Access::default()
+            } else {
+                Access::probe()
+            },
+            ..Self::default()
+        }
+    }
+
+    /// キー入力を処理する
+    pub fn handle_key(&mut self, key: KeyEvent, snapshot: &Snapshot) -> Effect {
+        if key.kind != KeyEventKind::Press {
+            return Effect::None;
+        }
+        let before = self.plain.then(|| {
+            (
+                UiState::from_app(self),
+                self.selected,
+                self.message.as_ref().map(|(_, at)| *at),
+            )
+        });
+        let mut effect = self.dispatch_key(key, snapshot);
+        // :kill や確認の済んだ操作など、どこから来ても送らない
+        if self.readonly && matches!(effect, Effect::Send(_) | Effect::SendAll(_)) {
+            self.refuse_readonly();
+            effect = Effect::None;
+        }
+        // フィルタの変更などで選択行が表から外れないようにする
+        self.sync_selection(snapshot);
+        if let Some((state, selected, message)) = before {
+            // キーの処理が自分で知らせたときはそちらを残す
+            if self.message.as_ref().map(|(_, at)| *at) == message {
+                self.announce(&state, selected, snapshot);
+            }
+        }
+        effect
+    }
+
+    fn refuse_readonly(&mut self) {
+        self.set_message("Read-only: processes cannot be changed (--readonly)".to_string());
+    }
+
+    // --plain。並び順・絞り込み・列・選択の変化をステータスバーの文章にして、スクリーンリーダーに読ませる
+    fn announce(&mut self, before: &UiState, selected: Option<Pid>, snapshot: &Snapshot) {
+        let mut changes = before.changes(&UiState::from_app(self));
+        if self.selected != selected
+            && let Some(p) = self
+                .selected
+                .and_then(|pid| snapshot.processes.iter().find(|p| p.pid == pid))
+        {
+            changes.push(format!(
+                "{} ({}), CPU {}, memory {}",
+                p.name,
+                p.pid,
+                self.locale.percent(p.cpu_usage as f64),
+                self.locale.megabytes(p.memory, 1)
+            ));
+        }
+        if !changes.is_empty() {
+            self.set_message(changes.join("; "));
+        }
+    }
+
+    fn dispatch_key(&mut self, key: KeyEvent, snapshot: &Snapshot) -> Effect {
+        if self.prompt.is_some() {
+            return self.handle_prompt_key(key, snapshot);
+        }
+        let key = match self.keymap {
+            Keymap::Default => key,
+            Keymap::Vim => match self.vim_key(key) {
+                Some(key) => key,
+                None => return Effect::None,
+            },
+        };
+        if self.search.is_some() {
+            self.handle_search_key(key, snapshot);
+            return Effect::None;
+        }
+        #[cfg(feature = "net")]
+        if self.connections.is_some() {
+            self.handle_connections_key(key, snapshot);
+            return Effect::None;
+        }
+        #[cfg(any(feature = "systemd", windows))]
+        if self.services.is_some() {
+            self.handle_services_key(key, snapshot);
+            return Effect::None;
+        }
+        if self.users.is_some() {
+            self.handle_users_key(key, snapshot);
+            return Effect::None;
+        }
+        if self.events_view.is_some() {
+            self.handle_events_key(key);
+            return Effect::None;
+        }
+        if self.affinity.is_some() {
+            return self.handle_affinity_key(key, snapshot);
+        }
+        if self.files.is_some() {
+            self.handle_files_key(key);
+            return Effect::None;
+        }
+        if self.peers.is_some() {
+            self.handle_peers_key(key, snapshot);
+            return Effect::None;
+        }
+        if self.baseline_view.is_some() {
+            self.handle_baseline_key(key, snapshot);
+            return Effect::None;
+        }
+        if self.cgroup_tree.is_some() {
+            self.handle_cgroup_tree_key(key, snapshot);
+            return Effect::None;
+        }
+        #[cfg(feature = "environ")]
+        if self.environ.is_some() {
+            self.handle_environ_key(key);
+            return Effect::None;
+        }
+        #[cfg(feature = "journal")]
+        if self.journal.is_some() {
+            self.handle_journal_key(key);
+            return Effect::None;
+        }
+        // 採っている途中でも閉じられる（perf も止める）
+        #[cfg(feature = "profile")]
+        if self.cpu_profile.is_some() {
+            if matches!(
+                key.code,
+                KeyCode::Esc | KeyCode::Char('R') | KeyCode::Char('q')
+            ) {
+                self.cpu_profile = None;
+            }
+            return Effect::None;
+        }
+        if self.watchdog_log {
+            if matches!(
+                key.code,
+                KeyCode::Esc | KeyCode::Char('w') | KeyCode::Char('q')
+            ) {
+                self.watchdog_log = false;
+            }
+            return Effect::None;
+        }
+        // 一度閉じたら出さない
+        if !self.health.is_empty() {
+            if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q')) {
+                self.health.clear();
+            }
+            return Effect::None;
+        }
+        if self.heatmap {
+            if matches!(
+                key.code,
+                KeyCode::Esc | KeyCode::Char('O') | KeyCode::Char('q')
+            ) {
+                self.heatmap = false;
+            }
+            return Effect::None;
+        }
+        match key.code {
+            KeyCode::Char('q') => return Effect::Quit,
+            KeyCode::Char('I') => {
+                self.cpu_mode = match self.cpu_mode {
+                    CpuMode::Irix => CpuMode::Solaris,
+                    CpuMode::Solaris => CpuMode::Irix,
+                };
+            }
+            KeyCode::Char('/') => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::Search,
+                    text: self
+                        .search
+                        .as_ref()
+                        .map(|s| s.query.clone())
+                        .unwrap_or_default(),
+                });
+            }
+            KeyCode::Char('l') => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::Locate,
+                    text: self
+                        .locate
+                        .as_ref()
+                        .map(|p| p.to_string())
+                        .unwrap_or_default(),
+                });
+            }
+            // 探している間は n（と >）を次、< を前の一致に使う。n は NUMA の表示より先に取る。
+            // N は renice のままにしておく
+            KeyCode::Char('n' | '>') if self.locate.is_some() => {
+                self.locate_next(snapshot, true, false)
+            }
+            KeyCode::Char('<') if self.locate.is_some() => self.locate_next(snapshot, false, false),
+            KeyCode::Char('u') => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::User,
+                    text: self.user_filter.clone().unwrap_or_default(),
+                });
+            }
+            KeyCode::Char('p') => {
+                if let Some(target) = self.target(snapshot) {
+                    self.toggle_pin(target.pid);
+                }
+            }
+            KeyCode::Char(' ') => {
+                if let Some(target) = self.target(snapshot) {
+                    self.toggle_tag(target);
+                    self.move_selection(snapshot, 1);
+                }
+            }
+            KeyCode::Char('F') => {
+                if let Some(target) = self.target(snapshot) {
+                    self.toggle_follow(target.pid, snapshot);
+                }
+            }
+            KeyCode::Char('k' | 'K' | 'Z' | 'N' | 'o' | 'a') if self.readonly => {
+                self.refuse_readonly()
+            }
+            KeyCode::Char('k') if !self.tagged.is_empty() => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::SignalTagged(self.tagged.clone()),
+                    text: "TERM".to_string(),
+                });
+            }
+            KeyCode::Char('N') if !self.tagged.is_empty() => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::ReniceTagged(self.tagged.clone()),
+                    text: actions::current_nice(self.tagged[0].pid)
+                        .map(|n| n.to_string())
+                        .unwrap_or_default(),
+                });
+            }
+            KeyCode::Char('k') => {
+                if let Some(target) = self.target(snapshot) {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::Signal(target),
+                        text: "TERM".to_string(),
+                    });
+                }
+            }
+            KeyCode::Char('K') => {
+                if let Some(target) = self.target(snapshot) {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::SignalTree(target),
+                        text: "TERM".to_string(),
+                    });
+                }
+            }
+            KeyCode::Char('Z') => {
+                if let Some(target) = self.target(snapshot) {
+                    return self.toggle_freeze(target, snapshot);
+                }
+            }
+            KeyCode::Char('N') => {
+                if let Some(target) = self.target(snapshot) {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::Renice(target),
+                        text: actions::current_nice(target.pid)
+                            .map(|n| n.to_string())
+                            .unwrap_or_default(),
+                    });
+                }
+            }
+            KeyCode::Char('o') => {
+                if let Some(target) = self.target(snapshot) {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::IoPriority(target),
+                        text: actions::current_io_priority(target.pid)
+                            .map(|p| p.to_string())
+                            .unwrap_or_default(),
+                    });
+                }
+            }
+            KeyCode::Char('a') => {
+                if let Some(target) = self.target(snapshot) {
+                    self.open_affinity(target, snapshot);
+                }
+            }
+            KeyCode::Char('f') => {
+                if let Some(target) = self.target(snapshot) {
+                    match files::list(target.pid) {
+                        Ok(list) => {
+                            self.files = Some(FilesView {
+                                pid: target.pid,
+                                files: list,
+                                selected: 0,
+                            })
+                        }
+                        Err(e) => self.set_message(format!(
+                            "Cannot list the open files of {}: {e}",
+                            target.pid
+                        )),
+                    }
+                }
+            }
+            KeyCode::Char('X') => {
+                if let Some(target) = self.target(snapshot) {
+                    match peers::collect(target.pid) {
+                        Ok(list) => {
+                            self.peers = Some(PeersView {
+                                pid: target.pid,
+                                peers: list,
+                                selected: 0,
+                            })
+                        }
+                        Err(e) => self.set_message(format!(
+                            "Cannot list the processes connected to {}: {e}",
+                            target.pid
+                        )),
+                    }
+                }
+            }
+            KeyCode::Char('s') => match CgroupTree::open() {
+                Ok(tree) => self.cgroup_tree = Some(CgroupTreeView::new(tree)),
+                Err(e) => self.set_message(format!("Cannot read the cgroup tree: {e}")),
+            },
+            // b は RX/TX の表示の切り替えに使っているので、ベースラインは B にする
+            KeyCode::Char('B') => match &self.baseline {
+                Some(baseline) => {
+                    self.baseline_view = Some(BaselineView {
+                        since: baseline.time,
+                        rows: baseline.diff(snapshot),
+                        selected: 0,
+                    })
+                }
+                None => self.mark_baseline(snapshot),
+            },
+            KeyCode::Char('O') => self.heatmap = true,
+            KeyCode::Char('w') => {
+                if self.watchdog.is_empty() {
+                    self.set_message("No [[watchdog]] in the config file".to_string());
+                } else {
+                    self.watchdog_log = true;
+                }
+            }
+            #[cfg(feature = "journal")]
+            KeyCode::Char('J') => {
+                if let Some(target) = self.target(snapshot) {
+                    self.journal = Some(JournalView::open(target.pid, self.journal_file.clone()));
+                }
+            }
+            #[cfg(feature = "profile")]
+            KeyCode::Char('R') => {
+                if let Some(target) = self.target(snapshot) {
+                    let name = snapshot
+                        .processes
+                        .iter()
+                        .find(|p| p.pid == target.pid)
+                        .map(|p| p.name.to_string())
+                        .unwrap_or_default();
+                    self.cpu_profile =
+                        Some(ProfileView::start(target.pid, name, &self.profile_config));
+                }
+            }
+            KeyCode::Char('y') => return self.yank(Yank::Pid, snapshot),
+            KeyCode::Char('Y') => return self.yank(Yank::Command, snapshot),
+            // 読むのはサンプラー側（ソースごとに読み方が違う）
+            #[cfg(feature = "environ")]
+            KeyCode::Char('v') => {
+                if let Some(target) = self.target(snapshot) {
+                    return Effect::Environ(target);
+                }
+            }
+            KeyCode::Char(':') => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::Command,
+                    text: String::new(),
+                });
+            }
+            KeyCode::Char('S') => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::Export,
+                    text: "text".to_string(),
+                });
+            }
+            KeyCode::Char('T') => {
+                if let Some(target) = self.target(snapshot) {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::Timer(target),
+                        text: String::new(),
+                    });
+                }
+            }
+            KeyCode::Enter => {
+                if let Some(target) = self.target(snapshot) {
+                    self.detail = Some(target.pid);
+                }
+            }
+            KeyCode::Char('t') => {
+                if let Some(target) = self.target(snapshot) {
+                    self.threads = Some(target.pid);
+                }
+            }
+            KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::ALT) => {
+                if let Some(panel) = Panel::from_key(c) {
+                    self.toggle_panel(panel);
+                }
+            }
+            // 同じキーをもう一度押すか 0 で解除する
+            KeyCode::Char('0') => self.preset = None,
+            KeyCode::Char(c @ '1'..='9') => match self.presets.iter().position(|p| p.key == c) {
+                Some(i) if self.preset == Some(i) => self.preset = None,
+                Some(i) => self.preset = Some(i),
+                None => self.set_message(format!("No preset is bound to {c}")),
+            },
+            KeyCode::Char('C') => {
+                self.full_command = !self.full_command;
+                self.command_scroll = 0;
+            }
+            KeyCode::Left if self.full_command => {
+                self.command_scroll = self.command_scroll.saturating_sub(COMMAND_SCROLL_STEP);
+            }
+            KeyCode::Right if self.full_command => {
+                let longest = snapshot
+                    .processes
+                    .iter()
+                    .map(|p| p.cmd.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                self.command_scroll =
+                    (self.command_scroll + COMMAND_SCROLL_STEP).min(longest.saturating_sub(1));
+            }
+            KeyCode::Char('g') => self.group_by_name = !self.group_by_name,
+            KeyCode::Char('M') => self.memory_detail = !self.memory_detail,
+            KeyCode::Char('H') => self.cpu_history = !self.cpu_history,
+            KeyCode::Char('E') => self.process_times = !self.process_times,
+            KeyCode::Char('D') => self.fd_counts = !self.fd_counts,
+            KeyCode::Char('i') => self.wait_channels = !self.wait_channels,
+            KeyCode::Char('d') => self.category_column = !self.category_column,
+            KeyCode::Char('m') => self.page_faults = !self.page_faults,
+            #[cfg(feature = "net")]
+            KeyCode::Char('b') => {
+                if self.termux.is_some_and(|r| r.net) {
+                    self.set_message(
+                        "Traffic is not visible on Android (/proc/net is restricted)".to_string(),
+                    );
+                } else {
+                    self.net_traffic = !self.net_traffic;
+                }
+            }
+            KeyCode::Char('n') => {
+                if self.numa_nodes || snapshot.numa.is_some() {
+                    self.numa_nodes = !self.numa_nodes;
+                } else {
+                    self.set_message("This machine has a single NUMA node".to_string());
+                }
+            }
+            KeyCode::Char('A') => self.show_all = !self.show_all,
+            KeyCode::Char('V') => {
+                self.layout = (self.layout + 1) % self.layouts.len();
+                self.set_message(format!("Layout: {}", self.layouts[self.layout].name));
+            }
+            // K は子孫ごとの kill に使っているので、カーネルスレッドの表示は h にする
+            KeyCode::Char('h') => {
+                self.kernel_threads = !self.kernel_threads;
+                let state = if self.kernel_threads {
+                    "shown"
+                } else {
+                    "hidden"
+                };
+                self.set_message(format!("Kernel threads {state}"));
+            }
+            KeyCode::Char('e') if self.group_by_name => {
+                // メンバーの行で押したときは、そのグループを閉じる
+                if let Some(pid) = self.selected
+                    && let Some(process) = snapshot.processes.iter().find(|p| p.pid == pid)
+                    && !self.expanded.remove(&process.name)
+                {
+                    self.expanded.insert(Arc::clone(&process.name));
+                }
+            }
+            KeyCode::Esc => {
+                self.detail = None;
+                self.threads = None;
+                self.tagged.clear();
+                self.name_filter = None;
+                self.locate = None;
+                #[cfg(any(feature = "systemd", windows))]
+                {
+                    self.service_filter = None;
+                }
+            }
+            #[cfg(feature = "net")]
+            KeyCode::Char('c') => {
+                if self.termux.is_some_and(|r| r.net) {
+                    self.set_message(
+                        "Connections are not visible on Android (/proc/net is restricted)"
+                            .to_string(),
+                    );
+                } else {
+                    self.connections = Some(0);
+                }
+            }
+            #[cfg(feature = "net")]
+            KeyCode::Char('P') => {
+                if self.termux.is_some_and(|r| r.net) {
+                    self.set_message(
+                        "Ports are not visible on Android (/proc/net is restricted)".to_string(),
+                    );
+                } else {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::Port,
+                        text: String::new(),
+                    });
+                }
+            }
+            #[cfg(any(feature = "systemd", windows))]
+            KeyCode::Char('U') => {
+                if snapshot.services.is_empty() {
+                    self.set_message("No services found".to_string());
+                } else {
+                    self.services = Some(0);
+                }
+            }
+            KeyCode::Char('W') => self.users = Some(0),
+            // 最新の記録から見る
+            KeyCode::Char('L') => self.events_view = Some(self.events.len().saturating_sub(1)),
+            KeyCode::F(12) => {
+                self.profile = match self.profile {
+                    Some(_) => None,
+                    None => Some(FrameTimes::default()),
+                };
+            }
+            KeyCode::Up => self.move_selection(snapshot, -1),
+            KeyCode::Down => self.move_selection(snapshot, 1),
+            // vim の Ctrl-u / Ctrl-d は半ページ
+            KeyCode::PageUp | KeyCode::PageDown => {
+                let mut rows = self.process_rows as isize;
+                if key.modifiers.contains(KeyModifiers::CONTROL) {
+                    rows = (rows / 2).max(1);
+                }
+                if key.code == KeyCode::PageUp {
+                    rows = -rows;
+                }
+                self.move_selection(snapshot, rows);
+            }
+            KeyCode::Home => self.move_selection(snapshot, isize::MIN / 2),
+            KeyCode::End => self.move_selection(snapshot, isize::MAX / 2),
+            _ => {}
+        }
+        Effect::None
+    }
+
+    // 接続一覧の中での操作。Enter で持ち主のプロセスを表で選ぶ
+    #[cfg(feature = "net")]
+    fn handle_connections_key(&mut self, key: KeyEvent, snapshot: &Snapshot) {
+        let page = self.popup_rows;
+        let Some(row) = self.connections else {
+            return;
+        };
+        let last = snapshot.connections.len().saturating_sub(1);
+        let row = match key.code {
+            KeyCode::Esc | KeyCode::Char('c') | KeyCode::Char('q') => {
+                self.connections = None;
+                return;
+            }
+            KeyCode::Enter => {
+                let Some(connection) = snapshot.connections.get(row) else {
+                    return;
+                };
+                let Some(pid) = connection.pid else {
+                    self.set_message("The owner of this socket is unknown".to_string());
+                    return;
+                };
+                if self.select_process(pid, snapshot) {
+                    self.connections = None;
+                }
+                return;
+            }
+            KeyCode::Char('r') => {
+                self.resolve_names = !self.resolve_names;
+                if self.resolve_names {
+                    self.resolver.get_or_insert_with(Resolver::start);
+                }
+                row
+            }
+            KeyCode::Up => row.saturating_sub(1),
+            KeyCode::Down => row + 1,
+            KeyCode::PageUp => row.saturating_sub(page),
+            KeyCode::PageDown => row + page,
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => row,
+        };
+        self.connections = Some(row.min(last));
+    }
+
+    // サービス一覧の中での操作。Enter でメインのプロセスを選び、f で表をそのユニットに絞る
+    #[cfg(any(feature = "systemd", windows))]
+    fn handle_services_key(&mut self, key: KeyEvent, snapshot: &Snapshot) {
+        let page = self.popup_rows;
+        let Some(row) = self.services else {
+            return;
+        };
+        let last = snapshot.services.len().saturating_sub(1);
+        let row = match key.code {
+            KeyCode::Esc | KeyCode::Char('U') | KeyCode::Char('q') => {
+                self.services = None;
+                return;
+            }
+            KeyCode::Enter => {
+                let Some(service) = snapshot.services.get(row) else {
+                    return;
+                };
+                let Some(pid) = service.main_pid.or_else(|| service.pids.first().copied()) else {
+                    self.set_message(format!("{} has no processes", service.name));
+                    return;
+                };
+                if self.select_process(pid, snapshot) {
+                    self.services = None;
+                }
+                return;
+            }
+            KeyCode::Char('f') => {
+                let Some(service) = snapshot.services.get(row) else {
+                    return;
+                };
+                if service.pids.is_empty() {
+                    self.set_message(format!("{} has no processes", service.name));
+                    return;
+                }
+                self.service_filter = Some(Arc::clone(&service.name));
+                self.services = None;
+                self.offset = 0;
+                self.sync_selection(snapshot);
+                return;
+            }
+            KeyCode::Up => row.saturating_sub(1),
+            KeyCode::Down => row + 1,
+            KeyCode::PageUp => row.saturating_sub(page),
+            KeyCode::PageDown => row + page,
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => row,
+        };
+        self.services = Some(row.min(last));
+    }
+
+    // ユーザーごとの集計の中での操作。Enter でそのユーザーのプロセスに表を絞り、s で並び順を変える
+    fn handle_users_key(&mut self, key: KeyEvent, snapshot: &Snapshot) {
+        let page = self.popup_rows;
+        let Some(row) = self.users else {
+            return;
+        };
+        let users = self.user_usage(snapshot);
+        let last = users.len().saturating_sub(1);
+        let row = match key.code {
+            KeyCode::Esc | KeyCode::Char('W') | KeyCode::Char('q') => {
+                self.users = None;
+                return;
+            }
+            KeyCode::Enter => {
+                let Some(usage) = users.get(row) else {
+                    return;
+                };
+                self.user_filter = Some(usage.user.to_string());
+                self.users = None;
+                self.offset = 0;
+                self.sync_selection(snapshot);
+                return;
+            }
+            KeyCode::Char('s') => {
+                self.user_sort = self.user_sort.next();
+                0
+            }
+            KeyCode::Up => row.saturating_sub(1),
+            KeyCode::Down => row + 1,
+            KeyCode::PageUp => row.saturating_sub(page),
+            KeyCode::PageDown => row + page,
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => row,
+        };
+        self.users = Some(row.min(last));
+    }
+
+    // ログの中での操作。上に戻ると最新への追従をやめ、End で戻る
+    #[cfg(feature = "journal")]
+    fn handle_journal_key(&mut self, key: KeyEvent) {
+        let page = self.popup_rows;
+        let Some(view) = self.journal.as_mut() else {
+            return;
+        };
+        view.scroll = match key.code {
+            KeyCode::Esc | KeyCode::Char('J') | KeyCode::Char('q') => {
+                self.journal = None;
+                return;
+            }
+            KeyCode::Up => view.scroll + 1,
+            KeyCode::Down => view.scroll.saturating_sub(1),
+            KeyCode::PageUp => view.scroll + page,
+            KeyCode::PageDown => view.scroll.saturating_sub(page),
+            KeyCode::Home => usize::MAX,
+            KeyCode::End => 0,
+            _ => view.scroll,
+        };
+    }
+
+    // 記録の一覧の中での操作。s でファイルに書き出す
+    fn handle_events_key(&mut self, key: KeyEvent) {
+        let page = self.popup_rows;
+        let Some(row) = self.events_view else {
+            return;
+        };
+        let last = self.events.len().saturating_sub(1);
+        let row = match key.code {
+            KeyCode::Esc | KeyCode::Char('L') | KeyCode::Char('q') => {
+                self.events_view = None;
+                return;
+            }
+            KeyCode::Char('s') => {
+                match self.events.save(&self.locale) {
+                    Ok(path) => self.set_message(format!(
+                        "Saved {} events to {}",
+                        self.events.len(),
+                        path.display()
+                    )),
+                    Err(e) => self.set_message(format!("Cannot save the events: {e}")),
+                }
+                row
+            }
+            KeyCode::Up => row.saturating_sub(1),
+            KeyCode::Down => row + 1,
+            KeyCode::PageUp => row.saturating_sub(page),
+            KeyCode::PageDown => row + page,
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => row,
+        };
+        self.events_view = Some(row.min(last));
+    }
+
+    // 開いているファイルの一覧の中での操作
+    fn handle_files_key(&mut self, key: KeyEvent) {
+        let page = self.popup_rows;
+        let Some(view) = self.files.as_mut() else {
+            return;
+        };
+        let last = view.files.len().saturating_sub(1);
+        view.selected = match key.code {
+            KeyCode::Esc | KeyCode::Char('f') | KeyCode::Char('q') => {
+                self.files = None;
+                return;
+            }
+            KeyCode::Up => view.selected.saturating_sub(1),
+            KeyCode::Down => view.selected + 1,
+            KeyCode::PageUp => view.selected.saturating_sub(page),
+            KeyCode::PageDown => view.selected + page,
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => view.selected,
+        }
+        .min(last);
+    }
+
+    // つながっているプロセスの一覧の中での操作。Enter でそのプロセスを選ぶ
+    fn handle_peers_key(&mut self, key: KeyEvent, snapshot: &Snapshot) {
+        let page = self.popup_rows;
+        let Some(view) = self.peers.as_mut() else {
+            return;
+        };
+        let last = view.peers.len().saturating_sub(1);
+        view.selected = match key.code {
+            KeyCode::Esc | KeyCode::Char('X') | KeyCode::Char('q') => {
+                self.peers = None;
+                return;
+            }
+            KeyCode::Enter => {
+                let Some(pid) = view.peers.get(view.selected).map(|peer| peer.pid) else {
+                    return;
+                };
+                if self.select_process(pid, snapshot) {
+                    self.peers = None;
+                }
+                return;
+            }
+            KeyCode::Up => view.selected.saturating_sub(1),
+            KeyCode::Down => view.selected + 1,
+            KeyCode::PageUp => view.selected.saturating_sub(page),
+            KeyCode::PageDown => view.selected + page,
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => view.selected,
+        }
+        .min(last);
+    }
+
+    // 基準との比較の中での操作。Enter でそのプロセスを選び、m で今を基準にし直す
+    fn handle_baseline_key(&mut self, key: KeyEvent, snapshot: &Snapshot) {
+        let page = self.popup_rows;
+        let Some(view) = self.baseline_view.as_mut() else {
+            return;
+        };
+        let last = view.rows.len().saturating_sub(1);
+        view.selected = match key.code {
+            KeyCode::Esc | KeyCode::Char('B') | KeyCode::Char('q') => {
+                self.baseline_view = None;
+                return;
+            }
+            KeyCode::Char('m') => {
+                self.baseline_view = None;
+                self.mark_baseline(snapshot);
+                return;
+            }
+            KeyCode::Enter => {
+                let Some(row) = view.rows.get(view.selected) else {
+                    return;
+                };
+                if row.change == Change::Exited {
+                    let text = format!("{} ({}) has exited", row.name, row.pid);
+                    self.set_message(text);
+                    return;
+                }
+                let pid = row.pid;
+                if self.select_process(pid, snapshot) {
+                    self.baseline_view = None;
+                }
+                return;
+            }
+            KeyCode::Up => view.selected.saturating_sub(1),
+            KeyCode::Down => view.selected + 1,
+            KeyCode::PageUp => view.selected.saturating_sub(page),
+            KeyCode::PageDown => view.selected + page,
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => view.selected,
+        }
+        .min(last);
+    }
+
+    // cgroup の階層の中での操作。← → と Space でグループを畳み、プロセスの行で Enter を押すと選ぶ
+    fn handle_cgroup_tree_key(&mut self, key: KeyEvent, snapshot: &Snapshot) {
+        let page = self.popup_rows;
+        let Some(view) = self.cgroup_tree.as_mut() else {
+            return;
+        };
+        let rows = view.rows();
+        let last = rows.len().saturating_sub(1);
+        let current = rows.get(view.selected.min(last)).copied();
+        view.selected = match key.code {
+            KeyCode::Esc | KeyCode::Char('s') | KeyCode::Char('q') => {
+                self.cgroup_tree = None;
+                return;
+            }
+            KeyCode::Left => {
+                match current {
+                    Some(TreeRow::Group(i) | TreeRow::Process(i, _)) => view.toggle(i, true),
+                    None => {}
+                }
+                return;
+            }
+            KeyCode::Right => {
+                if let Some(TreeRow::Group(i)) = current {
+                    view.toggle(i, false);
+                }
+                return;
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                match current {
+                    Some(TreeRow::Group(i)) => {
+                        let collapse = !view.collapsed.contains(&view.tree.nodes[i].path);
+                        view.toggle(i, collapse);
+                    }
+                    Some(TreeRow::Process(_, pid)) if self.select_process(pid, snapshot) => {
+                        self.cgroup_tree = None;
+                    }
+                    Some(TreeRow::Process(..)) => {}
+                    None => {}
+                }
+                return;
+            }
+            KeyCode::Up => view.selected.saturating_sub(1),
+            KeyCode::Down => view.selected + 1,
+            KeyCode::PageUp => view.selected.saturating_sub(page),
+            KeyCode::PageDown => view.selected + page,
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => view.selected,
+        }
+        .min(last);
+    }
+
+    fn mark_baseline(&mut self, snapshot: &Snapshot) {
+        let baseline = Baseline::capture(snapshot);
+        self.set_message(format!(
+            "Baseline marked at {} ({} processes, B again to compare)",
+            self.locale.time(baseline.time),
+            snapshot.processes.iter().filter(|p| !p.is_thread).count()
+        ));
+        self.baseline = Some(baseline);
+    }
+
+    // 環境変数の一覧の中での操作。文字は絞り込みに足す
+    #[cfg(feature = "environ")]
+    fn handle_environ_key(&mut self, key: KeyEvent) {
+        let page = self.popup_rows;
+        let Some(view) = self.environ.as_mut() else {
+            return;
+        };
+        let last = view.shown().len().saturating_sub(1);
+        view.selected = match key.code {
+            KeyCode::Esc => {
+                self.environ = None;
+                return;
+            }
+            KeyCode::Char(c) => {
+                view.query.push(c);
+                0
+            }
+            KeyCode::Backspace => {
+                view.query.pop();
+                0
+            }
+            KeyCode::Up => view.selected.saturating_sub(1),
+            KeyCode::Down => view.selected + 1,
+            KeyCode::PageUp => view.selected.saturating_sub(page),
+            KeyCode::PageDown => view.selected + page,
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => view.selected,
+        };
+        let last = view.shown().len().saturating_sub(1);
+        view.selected = view.selected.min(last);
+    }
+
+    /// サンプラーが読んだ環境変数を表示する
+    #[cfg(feature = "environ")]
+    pub fn handle_environ(&mut self, target: ProcessRef, result: Result<Vec<String>, String>) {
+        match result {
+            Ok(mut vars) => {
+                vars.sort();
+                self.environ = Some(EnvironView {
+                    target,
+                    vars,
+                    query: String::new(),
+                    selected: 0,
+                });
+            }
+            Err(e) => self.set_message(format!("Cannot read the environment: {e}")),
+        }
+    }
+
+    fn open_affinity(&mut self, target: ProcessRef, snapshot: &Snapshot) {
+        let Some(current) = actions::current_affinity(target.pid) else {
+            self.set_message(format!("Cannot read the CPU affinity of {}", target.pid));
+            return;
+        };
+        // オフラインの CPU が含まれていても選べるように、大きい方に合わせる
+        let count = current
+            .last()
+            .map_or(0, |cpu| cpu + 1)
+            .max(snapshot.cpu.count);
+        let mut cpus = vec![false; count];
+        for cpu in current {
+            cpus[cpu] = true;
+        }
+        self.affinity = Some(AffinityDialog {
+            target,
+            cpus,
+            cursor: 0,
+        });
+    }
+
+    // アフィニティの画面での操作。Space で切り替え、Enter で設定する
+    fn handle_affinity_key(&mut self, key: KeyEvent, snapshot: &Snapshot) -> Effect {
+        let Some(dialog) = self.affinity.as_mut() else {
+            return Effect::None;
+        };
+        let last = dialog.cpus.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.affinity = None,
+            KeyCode::Left => dialog.cursor = dialog.cursor.saturating_sub(1),
+            KeyCode::Right => dialog.cursor = (dialog.cursor + 1).min(last),
+            KeyCode::Up => dialog.cursor = dialog.cursor.saturating_sub(AFFINITY_COLUMNS),
+            KeyCode::Down => dialog.cursor = (dialog.cursor + AFFINITY_COLUMNS).min(last),
+            KeyCode::Home => dialog.cursor = 0,
+            KeyCode::End => dialog.cursor = last,
+            KeyCode::Char(' ') => {
+                if let Some(cpu) = dialog.cpus.get_mut(dialog.cursor) {
+                    *cpu = !*cpu;
+                }
+            }
+            // すべて選んでいれば外し、そうでなければすべて選ぶ
+            KeyCode::Char('a') => {
+                let all = dialog.cpus.iter().all(|&cpu| cpu);
+                dialog.cpus.fill(!all);
+            }
+            KeyCode::Enter => {
+                let target = dialog.target;
+                let cpus: Vec<usize> = (0..dialog.cpus.len())
+                    .filter(|&cpu| dialog.cpus[cpu])
+                    .collect();
+                if cpus.is_empty() {
+                    self.set_message("Select at least one CPU".to_string());
+                    return Effect::None;
+                }
+                self.affinity = None;
+                if !self.check_alive(target, snapshot) {
+                    return Effect::None;
+                }
+                return Effect::Send(Command::SetAffinity { target, cpus });
+            }
+            _ => {}
+        }
+        Effect::None
+    }
+
+    /// ユーザーごとに CPU・メモリ・プロセス数を合計し、user_sort の順に並べる
+    /// 隠しているカーネルスレッドは数えない
+    pub fn user_usage(&self, snapshot: &Snapshot) -> Vec<UserUsage> {
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        let mut users: Vec<UserUsage> = Vec::new();
+        for p in snapshot
+            .processes
+            .iter()
+            .filter(|p| self.kernel_threads || !p.is_kernel_thread())
+        {
+            let i = *index.entry(&*p.user).or_insert_with(|| {
+                users.push(UserUsage {
+                    user: Arc::clone(&p.user),
+                    processes: 0,
+                    cpu: 0.0,
+                    memory: 0,
+                });
+                users.len() - 1
+            });
+            let usage = &mut users[i];
+            usage.processes += 1;
+            usage.cpu += self.cpu_mode.scale(p.cpu_usage, snapshot.cpu.count);
+            usage.memory += p.memory;
+        }
+        // 同じ値なら名前の順にして、更新のたびに入れ替わらないようにする
+        users.sort_by(|a, b| {
+            let order = match self.user_sort {
+                UserSort::Cpu => b.cpu.total_cmp(&a.cpu),
+                UserSort::Memory => b.memory.cmp(&a.memory),
+                UserSort::Processes => b.processes.cmp(&a.processes),
+                UserSort::User => Ordering::Equal,
+            };
+            order.then_with(|| a.user.cmp(&b.user))
+        });
+        users
+    }
+
+    // vim のキーを既定のキーに読み替える。gg の 1 回目なら None
+    fn vim_key(&mut self, key: KeyEvent) -> Option<KeyEvent> {
+        let pending_g = std::mem::take(&mut self.pending_g);
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let code = match key.code {
+            KeyCode::Char('j') => KeyCode::Down,
+            KeyCode::Char('k') => KeyCode::Up,
+            KeyCode::Char('G') => KeyCode::End,
+            KeyCode::Char('g') if pending_g => KeyCode::Home,
+            KeyCode::Char('g') => {
+                self.pending_g = true;
+                return None;
+            }
+            KeyCode::Char('d') if ctrl => KeyCode::PageDown,
+            KeyCode::Char('u') if ctrl => KeyCode::PageUp,
+            // 空いたキーの代わり
+            KeyCode::Char('x') => KeyCode::Char('k'),
+            KeyCode::Char('z') => KeyCode::Char('g'),
+            code => code,
+        };
+        Some(KeyEvent::new(code, key.modifiers))
+    }
+
+    // 検索結果の中での操作。Enter でその行の持ち主（表・接続一覧）に移る
+    fn handle_search_key(&mut self, key: KeyEvent, snapshot: &Snapshot) {
+        let page = self.popup_rows;
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+        let last = search.results.len().saturating_sub(1);
+        search.selected = match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.search = None;
+                return;
+            }
+            KeyCode::Char('/') => {
+                self.search = None;
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::Search,
+                    text: String::new(),
+                });
+                return;
+            }
+            KeyCode::Enter => {
+                if let Some(result) = search.results.get(search.selected) {
+                    let hit = result.hit.clone();
+                    if self.jump_to(hit, snapshot) {
+                        self.search = None;
+                    }
+                }
+                return;
+            }
+            KeyCode::Up => search.selected.saturating_sub(1),
+            KeyCode::Down => search.selected + 1,
+            KeyCode::PageUp => search.selected.saturating_sub(page),
+            KeyCode::PageDown => search.selected + page,
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => search.selected,
+        }
+        .min(last);
+    }
+
+    // 移れなかったときはメッセージを出して false
+    fn jump_to(&mut self, hit: SearchHit, snapshot: &Snapshot) -> bool {
+        match hit {
+            SearchHit::Process(pid) => self.select_process(pid, snapshot),
+            #[cfg(feature = "net")]
+            SearchHit::Connection { local, remote } => {
+                let Some(row) = snapshot
+                    .connections
+                    .iter()
+                    .position(|c| c.local == local && c.remote == remote)
+                else {
+                    self.set_message(format!("Connection {local} -> {remote} is closed"));
+                    return false;
+                };
+                self.connections = Some(row);
+                true
+            }
+            SearchHit::Container(name) => {
+                let top = snapshot
+                    .processes
+                    .iter()
+                    .filter(|p| p.container.as_ref() == Some(&name))
+                    .max_by(|a, b| a.cpu_usage.total_cmp(&b.cpu_usage));
+                match top {
+                    Some(process) => self.select_process(process.pid, snapshot),
+                    None => {
+                        self.set_message(format!("Container {name} has no processes"));
+                        false
+                    }
+                }
+            }
+        }
+    }
+
+    // プロセス表でそのプロセスの行を選ぶ。表に出ていなければメッセージを出して false
+    // 同じポートを TCP と UDP の両方で待ち受けていれば TCP の方へ移る。
+    // 待ち受けているものが無ければ、そのポートを使っている接続の持ち主へ移る
+    #[cfg(feature = "net")]
+    fn go_to_port(&mut self, port: u16, snapshot: &Snapshot) {
+        let ports = net::listening(&snapshot.connections);
+        let owner = match ports.iter().find(|p| p.port == port) {
+            Some(listening) => Some(listening.pid),
+            None => snapshot
+                .connections
+                .iter()
+                .find(|c| c.local.port() == port)
+                .map(|c| c.pid),
+        };
+        match owner {
+            Some(Some(pid)) => {
+                self.select_process(pid, snapshot);
+            }
+            Some(None) => {
+                self.set_message(format!("The owner of port {port} is unknown (try as root)"))
+            }
+            None => self.set_message(format!("Nothing is listening on port {port}")),
+        }
+    }
+
+    // 開いているものが複数あれば、表に出ている最初のものを選び、残りの数を知らせる
+    fn go_to_file(&mut self, path: &Path, snapshot: &Snapshot) {
+        let holders = match files::holders(path) {
+            Ok(holders) => holders,
+            Err(e) => {
+                self.set_message(format!("Cannot search for {}: {e}", path.display()));
+                return;
+            }
+        };
+        let visible: HashSet<Pid> = self
+            .visible_processes(snapshot)
+            .iter()
+            .map(|p| p.pid)
+            .collect();
+        match holders.iter().find(|pid| visible.contains(pid)) {
+            Some(&pid) => {
+                self.select_process(pid, snapshot);
+                if holders.len() > 1 {
+                    self.set_message(format!(
+                        "{} is open in {} processes: {}",
+                        path.display(),
+                        holders.len(),
+                        holders
+                            .iter()
+                            .map(|pid| pid.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                }
+            }
+            None if holders.is_empty() => {
+                self.set_message(format!("No readable process has {} open", path.display()))
+            }
+            None => self.set_message(format!(
+                "{} is open only in processes hidden from the table",
+                path.display()
+            )),
+        }
+    }
+
+    fn select_process(&mut self, pid: Pid, snapshot: &Snapshot) -> bool {
+        let visible = self.visible_processes(snapshot);
+        let Some(index) = visible.iter().position(|p| p.pid == pid) else {
+            self.set_message(format!("Process {pid} is not shown in the table"));
+            return false;
+        };
+        self.selected = Some(pid);
+        self.selected_row = index;
+        self.scroll_to_selection(visible.len());
+        true
+    }
+
+    /// マウス操作を処理する
+    pub fn handle_mouse(
+        &mut self,
+        mouse: MouseEvent,
+        target: Option<ClickTarget>,
+        snapshot: &Snapshot,
+    ) {
+        match mouse.kind {
+            MouseEventKind::ScrollUp => self.move_selection(snapshot, -1),
+            MouseEventKind::ScrollDown => self.move_selection(snapshot, 1),
+            // 入力中や詳細表示中は表をクリックできない
+            MouseEventKind::Down(MouseButton::Left)
+                if self.prompt.is_none()
+                    && self.detail.is_none()
+                    && self.threads.is_none()
+                    && self.search.is_none()
+                    && !self.list_open() =>
+            {
+                match target {
+                    Some(ClickTarget::Header(key)) => self.sort_by(key),
+                    Some(ClickTarget::Row(row)) => self.select_row(snapshot, self.offset + row),
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+        self.sync_selection(snapshot);
+    }
+
+    // 接続一覧・サービス一覧・ユーザーごとの集計・アフィニティ・開いているファイル・環境変数を表示中か
+    fn list_open(&self) -> bool {
+        if self.users.is_some()
+            || self.events_view.is_some()
+            || self.affinity.is_some()
+            || self.files.is_some()
+            || self.peers.is_some()
+            || self.baseline_view.is_some()
+            || self.cgroup_tree.is_some()
+            || self.watchdog_log
+            || self.heatmap
+            || !self.health.is_empty()
+        {
+            return true;
+        }
+        #[cfg(feature = "environ")]
+        if self.environ.is_some() {
+            return true;
+        }
+        #[cfg(feature = "journal")]
+        if self.journal.is_some() {
+            return true;
+        }
+        #[cfg(feature = "profile")]
+        if self.cpu_profile.is_some() {
+            return true;
+        }
+        #[cfg(feature = "net")]
+        if self.connections.is_some() {
+            return true;
+        }
+        #[cfg(any(feature = "systemd", windows))]
+        if self.services.is_some() {
+            return true;
+        }
+        false
+    }
+
+    // 同じ列をもう一度選んだら向きを反転する
+    fn sort_by(&mut self, key: SortKey) {
+        if self.sort == key {
+            self.sort_reversed = !self.sort_reversed;
+        } else {
+            self.sort = key;
+            self.sort_reversed = false;
+        }
+    }
+
+    /// 並べ替えが昇順かどうか
+    pub fn sort_ascending(&self) -> bool {
+        self.sort.descending_by_default() == self.sort_reversed
+    }
+
+    // 選んだ列で並べ、同じ値なら [sort] then の列、最後に PID で決める。
+    // 続きの列はそれぞれの既定の向きで、毎回同じ並びになるようにする
+    fn compare_rows(&self, a: &ProcessInfo, b: &ProcessInfo) -> Ordering {
+        let order = self.sort.compare(a, b, &self.categories);
+        let order = if self.sort_ascending() {
+            order
+        } else {
+            order.reverse()
+        };
+        self.sort_then
+            .iter()
+            .chain([&SortKey::Pid])
+            .filter(|&&key| key != self.sort)
+            .fold(order, |order, &key| {
+                order.then_with(|| {
+                    let order = key.compare(a, b, &self.categories);
+                    if key.descending_by_default() {
+                        order.reverse()
+                    } else {
+                        order
+                    }
+                })
+            })
+    }
+
+    fn handle_prompt_key(&mut self, key: KeyEvent, snapshot: &Snapshot) -> Effect {
+        let Some(prompt) = self.prompt.as_mut() else {
+            return Effect::None;
+        };
+        match key.code {
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Enter => {
+                let prompt = self.prompt.take().expect("prompt");
+                return self.submit(prompt, snapshot);
+            }
+            KeyCode::Backspace => {
+                prompt.text.pop();
+            }
+            KeyCode::Tab if matches!(prompt.kind, PromptKind::Command) => {
+                let (text, choices) = palette::complete(&prompt.text);
+                prompt.text = text;
+                if !choices.is_empty() {
+                    self.set_message(choices.join(" "));
+                }
+            }
+            KeyCode::Char(c) => prompt.text.push(c),
+            _ => {}
+        }
+        Effect::None
+    }
+
+    fn submit(&mut self, prompt: Prompt, snapshot: &Snapshot) -> Effect {
+        let text = prompt.text.trim();
+        match prompt.kind {
+            PromptKind::User => {
+                self.user_filter = (!text.is_empty()).then(|| text.to_string());
+            }
+            PromptKind::Locate => {
+                if text.is_empty() {
+                    self.locate = None;
+                    return Effect::None;
+                }
+                match Pattern::new(text) {
+                    Ok(pattern) => {
+                        self.locate = Some(pattern);
+                        self.locate_next(snapshot, true, true);
+                    }
+                    Err(e) => self.set_message(e),
+                }
+            }
+            #[cfg(feature = "net")]
+            PromptKind::Port if text.is_empty() => {}
+            #[cfg(feature = "net")]
+            PromptKind::Port => match text.trim_start_matches(':').parse() {
+                Ok(port) => self.go_to_port(port, snapshot),
+                Err(_) => self.set_message(format!("Invalid port: {text}")),
+            },
+            PromptKind::Signal(target) | PromptKind::SignalTree(target) => {
+                let Some(signal) = actions::parse_signal(text) else {
+                    self.set_message(format!("Unknown signal: {text}"));
+                    return Effect::None;
+                };
+                if !self.check_alive(target, snapshot) {
+                    return Effect::None;
+                }
+                let tree = matches!(prompt.kind, PromptKind::SignalTree(_));
+                let command = if tree {
+                    Command::SignalTree { target, signal }
+                } else {
+                    Command::Signal { target, signal }
+                };
+                if let Some(name) = self.protected(target, tree, snapshot) {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::Confirm { command, name },
+                        text: String::new(),
+                    });
+                    return Effect::None;
+                }
+                return Effect::Send(command);
+            }
+            PromptKind::SignalTagged(targets) => {
+                let Some(signal) = actions::parse_signal(text) else {
+                    self.set_message(format!("Unknown signal: {text}"));
+                    return Effect::None;
+                };
+                let Some(targets) = self.tagged_alive(targets, snapshot) else {
+                    return Effect::None;
+                };
+                // 1 つずつ名前を打たせる代わりに、保護したものが混ざっていれば送らない
+                if let Some(name) = targets
+                    .iter()
+                    .find_map(|&target| self.protected(target, false, snapshot))
+                {
+                    self.set_message(format!("Cancelled: {name} is protected, untag it first"));
+                    return Effect::None;
+                }
+                return Effect::SendAll(
+                    targets
+                        .into_iter()
+                        .map(|target| Command::Signal { target, signal })
+                        .collect(),
+                );
+            }
+            PromptKind::ReniceTagged(targets) => {
+                let nice = match text.parse::<i32>() {
+                    Ok(n) if (-20..=19).contains(&n) => n,
+                    _ => {
+                        self.set_message(format!("Invalid nice value: {text}"));
+                        return Effect::None;
+                    }
+                };
+                let Some(targets) = self.tagged_alive(targets, snapshot) else {
+                    return Effect::None;
+                };
+                return Effect::SendAll(
+                    targets
+                        .into_iter()
+                        .map(|target| Command::Renice { target, nice })
+                        .collect(),
+                );
+            }
+            PromptKind::Confirm { command, name } => {
+                if text != &*name {
+                    self.set_message(format!("Cancelled: {command}"));
+                    return Effect::None;
+                }
+                if !self.check_alive(command.target(), snapshot) {
+                    return Effect::None;
+                }
+                return Effect::Send(command);
+            }
+            PromptKind::Renice(target) => {
+                let nice = match text.parse::<i32>() {
+                    Ok(n) if (-20..=19).contains(&n) => n,
+                    _ => {
+                        self.set_message(format!("Invalid nice value: {text}"));
+                        return Effect::None;
+                    }
+                };
+                if !self.check_alive(target, snapshot) {
+                    return Effect::None;
+                }
+                return Effect::Send(Command::Renice { target, nice });
+            }
+            PromptKind::IoPriority(target) => {
+                let Some(priority) = IoPriority::parse(text) else {
+                    self.set_message(format!("Invalid I/O priority: {text}"));
+                    return Effect::None;
+                };
+                if !self.check_alive(target, snapshot) {
+                    return Effect::None;
+                }
+                return Effect::Send(Command::SetIoPriority { target, priority });
+            }
+            PromptKind::Timer(target) => self.set_timer(target, text, snapshot),
+            PromptKind::Export => match ExportFormat::from_name(text) {
+                Some(format) => self.save(format, snapshot),
+                None => {
+                    self.set_message(format!("Unknown format: {text} (text, html, csv or json)"))
+                }
+            },
+            PromptKind::Command if text.is_empty() => {}
+            PromptKind::Command => match palette::parse(text) {
+                Ok(action) => return self.run_action(action, snapshot),
+                Err(e) => self.set_message(e),
+            },
+            PromptKind::Search => {
+                if text.is_empty() {
+                    return Effect::None;
+                }
+                let pattern = match Pattern::new(text) {
+                    Ok(pattern) => pattern,
+                    Err(e) => {
+                        self.set_message(format!("Invalid pattern: {e}"));
+                        return Effect::None;
+                    }
+                };
+                let results = search::search(snapshot, &pattern);
+                if results.is_empty() {
+                    self.set_message(format!("No matches for {text}"));
+                } else {
+                    self.search = Some(Search {
+                        query: text.to_string(),
+                        results,
+                        selected: 0,
+                    });
+                }
+            }
+        }
+        Effect::None
+    }
+
+    // コマンドモードの操作。キーで同じことをするときと同じ確認を通す
+    fn run_action(&mut self, action: Action, snapshot: &Snapshot) -> Effect {
+        match action {
+            Action::Sort(key) => {
+                self.sort = key;
+                self.sort_reversed = false;
+            }
+            Action::Filter(pattern) => self.name_filter = pattern,
+            Action::User(user) => self.user_filter = user,
+            Action::Interval(interval) => return Effect::Interval(interval),
+            Action::Kill { pid, signal } => {
+                let Some(process) = snapshot.processes.iter().find(|p| p.pid == pid) else {
+                    self.set_message(format!("Process {pid} no longer exists"));
+                    return Effect::None;
+                };
+                let target = ProcessRef {
+                    pid,
+                    start_time: process.start_time,
+                };
+                let command = Command::Signal { target, signal };
+                if let Some(name) = self.protected(target, false, snapshot) {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::Confirm { command, name },
+                        text: String::new(),
+                    });
+                    return Effect::None;
+                }
+                return Effect::Send(command);
+            }
+            #[cfg(feature = "net")]
+            Action::Port(port) => self.go_to_port(port, snapshot),
+            #[cfg(not(feature = "net"))]
+            Action::Port(port) => {
+                self.set_message(format!("Looking up port {port} needs the `net` feature"))
+            }
+            Action::File(path) => self.go_to_file(&path, snapshot),
+            Action::Theme(theme) => {
+                self.theme = with_symbols(self.colors.fit_theme(theme), self.plain);
+            }
+            Action::Layout(name) => {
+                let names: Vec<&str> = self.layouts.iter().map(|l| l.name.as_str()).collect();
+                match palette::unique(&name, names.iter().copied()) {
+                    Some(found) => {
+                        self.layout = names.iter().position(|n| *n == found).unwrap_or(0);
+                        self.set_message(format!("Layout: {found}"));
+                    }
+                    None => {
+                        self.set_message(format!("Unknown layout: {name} ({})", names.join(", ")))
+                    }
+                }
+            }
+            Action::Profile(name) => return self.apply_profile(&name),
+            Action::Save(format) => self.save(format, snapshot),
+            Action::Caffeinate(on) => self.caffeinate(on),
+            Action::Category(name) => self.filter_category(name.as_deref()),
+            Action::Yank(yank) => return self.yank(yank, snapshot),
+            Action::Revert => {
+                if let Some(target) = self.target(snapshot) {
+                    return Effect::Send(Command::Revert { target });
+                }
+            }
+            Action::Quit => return Effect::Quit,
+        }
+        Effect::None
+    }
+
+    /// サンプラーが更新間隔を変えた（またはリモートで断られた）
+    /// 書き換わった設定ファイルのうち、起動し直さなくても変えられるものを入れ替える
+    /// （配色・キー割り当て・レイアウト。更新間隔はメインループがサンプラーに送る）
+    pub fn reload_config(&mut self, config: &Config) {
+        self.theme = with_symbols(self.colors.fit_theme(config.theme), self.plain);
+        self.thresholds = fit_thresholds(self.colors, &config.thresholds);
+        self.keymap = config.keymap;
+        self.pending_g = false;
+        let current = self.layouts.get(self.layout).map(|l| l.name.clone());
+        self.layouts = layout::all(&config.layouts);
+        self.layout = current
+            .iter()
+            .chain(&config.layout)
+            .find_map(|name| self.layouts.iter().position(|l| &l.name == name))
+            .unwrap_or(0);
+        self.side_column = config.side_column;
+        self.page_rows = config.process_rows;
+        self.profiles = config.profiles.clone();
+        self.categories = config.categories.clone();
+        // 種類のパターンが変わっても値の同じ行は作り直されないので、覚えた行を捨てる
+        *self.row_cache.get_mut().expect("row cache lock") = RowCache::default();
+        // 使っているプロファイルの閾値は読み直した値で掛け直す
+        if let Some(thresholds) = self
+            .profiles
+            .iter()
+            .find(|p| self.active_profile.as_ref() == Some(&p.name))
+            .and_then(|p| p.thresholds.as_ref())
+        {
+            self.thresholds = fit_thresholds(self.colors, thresholds);
+        }
+        self.set_message("Config reloaded".to_string());
+    }
+
+    /// :category で種類を選ぶ。名前は省略でき、None なら解除する
+    fn filter_category(&mut self, name: Option<&str>) {
+        let Some(name) = name else {
+            self.category_filter = None;
+            return;
+        };
+        let names: Vec<&str> = self
+            .categories
+            .kinds
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        match palette::unique(name, names.iter().copied()) {
+            Some(found) => self.category_filter = Some(found.to_string()),
+            None => self.set_message(format!("Unknown category: {name} ({})", names.join(", "))),
+        }
+    }
+
+    /// [profiles.<name>] の表示に切り替える（--profile と :profile）。名前は省略できる
+    /// 書いていないものは今のままにし、更新間隔があればそれを返して送らせる
+    pub fn apply_profile(&mut self, name: &str) -> Effect {
+        let names: Vec<&str> = self.profiles.iter().map(|p| p.name.as_str()).collect();
+        let Some(profile) = palette::unique(name, names.iter().copied())
+            .and_then(|found| self.profiles.iter().find(|p| p.name == found))
+            .cloned()
+        else {
+            let message = if names.is_empty() {
+                "No [profiles.<name>] in the config file".to_string()
+            } else {
+                format!("Unknown profile: {name} ({})", names.join(", "))
+            };
+            self.set_message(message);
+            return Effect::None;
+        };
+        if let Some(position) = profile
+            .layout
+            .as_ref()
+            .and_then(|name| self.layouts.iter().position(|l| &l.name == name))
+        {
+            self.layout = position;
+        }
+        if let Some(columns) = &profile.columns {
+            state::show_columns(self, columns);
+        }
+        if let Some(sort) = profile.sort {
+            self.sort = sort;
+            self.sort_reversed = false;
+        }
+        if let Some(thresholds) = &profile.thresholds {
+            self.thresholds = fit_thresholds(self.colors, thresholds);
+        }
+        self.set_message(format!("Profile: {}", profile.name));
+        self.active_profile = Some(profile.name);
+        profile.interval.map_or(Effect::None, Effect::Interval)
+    }
+
+    /// いま実際にプロセス一覧を読み直している間隔
+    pub fn current_interval(&self) -> Duration {
+        self.idle_interval.unwrap_or(self.refresh_interval)
+    }
+
+    pub fn handle_interval(&mut self, result: Result<Duration, String>) {
+        match result {
+            Ok(interval) => {
+                self.refresh_interval = interval;
+                self.chosen_interval = Some(interval);
+                self.set_message(format!(
+                    "Refreshing every {}",
+                    alert::format_duration(interval)
+                ));
+            }
+            Err(e) => self.error(format!("Cannot change the interval: {e}")),
+        }
+    }
+
+    // 空ならば終了時、「-」なら解除、それ以外は「まだ動いていたら」の時間
+    fn set_timer(&mut self, target: ProcessRef, text: &str, snapshot: &Snapshot) {
+        if text == "-" {
+            let removed = self.alerts.clear_timers(target);
+            self.set_message(format!("Cleared {removed} timer(s) on {}", target.pid));
+            return;
+        }
+        let kind = if text.is_empty() {
+            TimerKind::Exit
+        } else {
+            let Some(after) = alert::parse_duration(text) else {
+                self.set_message(format!("Invalid duration: {text}"));
+                return;
+            };
+            TimerKind::StillRunning {
+                deadline: Instant::now() + after,
+                after,
+            }
+        };
+        if !self.check_alive(target, snapshot) {
+            return;
+        }
+        let name = snapshot
+            .processes
+            .iter()
+            .find(|p| p.pid == target.pid)
+            .map(|p| Arc::clone(&p.name))
+            .unwrap_or_default();
+        self.set_message(match kind {
+            TimerKind::Exit => format!("Will alert when {} exits", target.pid),
+            TimerKind::StillRunning { after, .. } => format!(
+                "Will alert if {} is still running in {}",
+                target.pid,
+                alert::format_duration(after)
+            ),
+        });
+        self.alerts.add_timer(Timer { target, name, kind });
+    }
+
+    // 対象（tree なら子孫も）に保護したプロセスがあれば、その名前を返す
+    // 止まっていれば SIGCONT、動いていれば SIGSTOP を送る（Z）。守るプロセスは名前を打たせてから
+    fn toggle_freeze(&mut self, target: ProcessRef, snapshot: &Snapshot) -> Effect {
+        let stopped = snapshot
+            .processes
+            .iter()
+            .any(|p| p.pid == target.pid && p.status == ProcessStatus::Stop);
+        let signal = if stopped {
+            Signal::Continue
+        } else {
+            Signal::Stop
+        };
+        let command = Command::Signal { target, signal };
+        if let Some(name) = self.protected(target, false, snapshot) {
+            self.prompt = Some(Prompt {
+                kind: PromptKind::Confirm { command, name },
+                text: String::new(),
+            });
+            return Effect::None;
+        }
+        Effect::Send(command)
+    }
+
+    fn protected(&self, target: ProcessRef, tree: bool, snapshot: &Snapshot) -> Option<Arc<str>> {
+        let mut pids = HashSet::from([target.pid]);
+        if tree {
+            pids = subtree(snapshot, &pids);
+        }
+        snapshot
+            .processes
+            .iter()
+            .filter(|p| pids.contains(&p.pid))
+            .find(|p| self.protect.protects(p.pid, &p.name))
+            .map(|p| Arc::clone(&p.name))
+    }
+
+    /// 操作対象（選択中のプロセス）。既に終了していればメッセージを出して None
+    fn target(&mut self, snapshot: &Snapshot) -> Option<ProcessRef> {
+        let pid = self.selected?;
+        let Some(process) = snapshot.processes.iter().find(|p| p.pid == pid) else {
+            self.process_gone(pid, snapshot);
+            return None;
+        };
+        Some(ProcessRef {
+            pid,
+            start_time: process.start_time,
+        })
+    }
+
+    // 書き込むのはメインループ（端末に送る）
+    fn yank(&mut self, yank: Yank, snapshot: &Snapshot) -> Effect {
+        let Some(target) = self.target(snapshot) else {
+            return Effect::None;
+        };
+        let Some(process) = snapshot.processes.iter().find(|p| p.pid == target.pid) else {
+            return Effect::None;
+        };
+        self.set_message(format!(
+            "Copied the {} of {} to the clipboard",
+            yank.label(),
+            target.pid
+        ));
+        Effect::Copy(yank.text(process))
+    }
+
+    // 選択したときと同じプロセス（PID と起動時刻が一致）がまだ居るか
+    fn check_alive(&mut self, target: ProcessRef, snapshot: &Snapshot) -> bool {
+        if snapshot
+            .processes
+            .iter()
+            .any(|p| p.pid == target.pid && p.start_time == target.start_time)
+        {
+            return true;
+        }
+        self.process_gone(target.pid, snapshot);
+        false
+    }
+
+    // 印を付けたもののうち、まだ動いているもの。1 つも無ければ知らせて None
+    fn tagged_alive(
+        &mut self,
+        targets: Vec<ProcessRef>,
+        snapshot: &Snapshot,
+    ) -> Option<Vec<ProcessRef>> {
+        let alive: Vec<ProcessRef> = targets
+            .into_iter()
+            .filter(|t| {
+                snapshot
+                    .processes
+                    .iter()
+                    .any(|p| p.pid == t.pid && p.start_time == t.start_time)
+            })
+            .collect();
+        if alive.is_empty() {
+            self.set_message("The tagged processes no longer exist".to_string());
+            return None;
+        }
+        Some(alive)
+    }
+
+    fn toggle_tag(&mut self, target: ProcessRef) {
+        match self.tagged.iter().position(|t| *t == target) {
+            Some(i) => {
+                self.tagged.remove(i);
+            }
+            None => self.tagged.push(target),
+        }
+    }
+
+    pub fn is_tagged(&self, pid: Pid) -> bool {
+        self.tagged.iter().any(|t| t.pid == pid)
+    }
+
+    // 対象が消えていたことを知らせ、近くの行を選び直す
+    fn process_gone(&mut self, pid: Pid, snapshot: &Snapshot) {
+        self.set_message(format!("Process {pid} no longer exists"));
+        if self.detail == Some(pid) {
+            self.detail = None;
+        }
+        if self.threads == Some(pid) {
+            self.threads = None;
+        }
+        if self.selected == Some(pid) {
+            self.selected = None;
+        }
+        self.sync_selection(snapshot);
+    }
+
+    /// サンプラーから返ってきた操作結果を反映する
+    pub fn handle_outcome(&mut self, outcome: ActionOutcome, snapshot: &Snapshot) {
+        self.watchdog.record_outcome(&outcome);
+        if let Err(ActionError::NoSuchProcess) = outcome.result {
+            self.process_gone(outcome.command.pid(), snapshot);
+            return;
+        }
+        // 権限が無いなどで断られたものは、メッセージが消えた後もステータスバーに残す
+        if outcome.result.is_err() {
+            log::warn(&outcome);
+            self.last_error = Some((outcome.to_string(), SystemTime::now()));
+        } else {
+            log::info(&outcome);
+        }
+        self.events.push(EventKind::Action, outcome.to_string());
+        self.set_message(outcome.to_string());
+    }
+
+    /// 失敗を知らせ、記録にも残す
+    pub fn error(&mut self, text: String) {
+        log::error(&text);
+        self.events.push(EventKind::Error, text.clone());
+        self.last_error = Some((text.clone(), SystemTime::now()));
+        self.set_message(text);
+    }
+
+    pub fn record_frame(&mut self, times: FrameTimes) {
+        if let Some(profile) = self.profile.as_mut() {
+            *profile = times;
+        }
+    }
+
+    /// スリープの抑止を入れる・切る。None なら今と逆にする
+    pub fn caffeinate(&mut self, on: Option<bool>) {
+        let on = on.unwrap_or(self.inhibitor.is_none());
+        if !on {
+            if self.inhibitor.take().is_some() {
+                self.set_message("The system may sleep again".to_string());
+            }
+            return;
+        }
+        if self.inhibitor.is_some() {
+            return;
+        }
+        match Inhibitor::start() {
+            Ok(inhibitor) => {
+                self.inhibitor = Some(inhibitor);
+                self.set_message("Keeping the system awake while rust-top runs".to_string());
+            }
+            Err(e) => self.error(format!("Cannot keep the system awake: {e}")),
+        }
+    }
+
+    pub fn caffeinated(&self) -> bool {
+        self.inhibitor.is_some()
+    }
+
+    pub fn set_message(&mut self, text: String) {
+        self.message = Some((text, Instant::now()));
+    }
+
+    pub fn current_message(&self) -> Option<&str> {
+        self.message
+            .as_ref()
+            .filter(|(_, at)| at.elapsed() < MESSAGE_TIMEOUT)
+            .map(|(text, _)| text.as_str())
+    }
+
+    fn select_row(&mut self, snapshot: &Snapshot, row: usize) {
+        if let Some(process) = self.visible_processes(snapshot).get(row) {
+            self.selected = Some(process.pid);
+            self.selected_row = row;
+        }
+    }
+
+    // 選択行の次（前）から一周して、locate に一致する行を選ぶ。current なら選択行から調べる
+    fn locate_next(&mut self, snapshot: &Snapshot, forward: bool, current: bool) {
+        let Some(pattern) = &self.locate else {
+            return;
+        };
+        let visible = self.visible_processes(snapshot);
+        let len = visible.len();
+        let start = self.selected_row.min(len.saturating_sub(1));
+        let found = (0..len)
+            .map(|i| {
+                let step = if current { i } else { i + 1 };
+                if forward {
+                    (start + step) % len
+                } else {
+                    (start + len * 2 - step) % len
+                }
+            })
+            .find(|&row| {
+                let p = &visible[row];
+                pattern.is_match(&p.name) || pattern.is_match(&p.cmd)
+            });
+        let Some(row) = found else {
+            self.set_message(format!("No match for {pattern}"));
+            return;
+        };
+        self.selected = Some(visible[row].pid);
+        self.selected_row = row;
+        self.scroll_to_selection(len);
+    }
+
+    fn move_selection(&mut self, snapshot: &Snapshot, delta: isize) {
+        let visible = self.visible_processes(snapshot);
+        if visible.is_empty() {
+            return;
+        }
+        let row = self
+            .selected_row
+            .saturating_add_signed(delta)
+            .min(visible.len() - 1);
+        self.selected = Some(visible[row].pid);
+        self.selected_row = row;
+        self.scroll_to_selection(visible.len());
+    }
+
+    /// 新しいスナップショットを受け取ったときの処理
+    /// changes は前回のスナップショットからのプロセスの出入り
+    pub fn on_snapshot(&mut self, snapshot: &Snapshot, changes: &ProcessChanges) {
+        if snapshot.process_generation != self.process_generation {
+            // 最初の一覧はすべて既存のプロセスとして扱う
+            let first = self.process_generation == 0;
+            self.process_generation = snapshot.process_generation;
+            self.track_new_processes(if first { &[] } else { &changes.added });
+            if !first {
+                self.record_started_and_exited(snapshot, changes);
+            }
+            self.track_exited(snapshot, &changes.exited);
+            // 終了したものの印は外す（同じ PID が別のプロセスに使われることがある）
+            self.tagged.retain(|t| {
+                snapshot
+                    .processes
+                    .iter()
+                    .any(|p| p.pid == t.pid && p.start_time == t.start_time)
+            });
+            self.track_changes(snapshot);
+            self.record_pin_history(snapshot);
+            self.usage_history.push(snapshot);
+            self.session.record(snapshot, Instant::now());
+            if let Some(view) = &mut self.cgroup_tree {
+                view.tree.refresh(Instant::now());
+            }
+            self.track_leaks(snapshot);
+            self.track_followed(snapshot);
+            self.reload_files();
+        }
+        let mut failed = Vec::new();
+        for fired in self.alerts.evaluate(snapshot, Instant::now()) {
+            let text = format!("{}: {}", fired.rule.name, fired.alert.detail);
+            self.session.alert(text.clone());
+            self.events.push(EventKind::Alert, text);
+            #[cfg(feature = "notify")]
+            if fired.notify {
+                notify::desktop(
+                    &format!("rust-top: {}", fired.rule.name),
+                    &fired.alert.detail,
+                );
+            }
+            if self.alert_actions
+                && let Some(mut command) = fired.command()
+            {
+                match command.spawn() {
+                    // 終了を待たないと zombie が残るので、別スレッドで回収する
+                    Ok(mut child) => {
+                        std::thread::spawn(move || child.wait());
+                    }
+                    Err(e) => failed.push(format!("Alert {}: cannot run: {e}", fired.rule.name)),
+                }
+            }
+        }
+        for text in failed {
+            self.error(text);
+        }
+        for action in self
+            .watchdog
+            .evaluate(snapshot, &self.protect, Instant::now())
+        {
+            self.watchdog_commands.extend(action.command);
+            let text = format!("Watchdog: {}", action.text);
+            self.events.push(EventKind::Action, text.clone());
+            self.set_message(text);
+        }
+        for text in self.alerts.check_timers(snapshot, Instant::now()) {
+            let text = format!("Timer: {text}");
+            self.session.alert(text.clone());
+            self.events.push(EventKind::Alert, text.clone());
+            self.notice(text);
+        }
+        self.sync_selection(snapshot);
+    }
+
+    fn track_leaks(&mut self, snapshot: &Snapshot) {
+        let window = alert::format_duration(self.leaks.window());
+        for suspect in self.leaks.update(&snapshot.processes, Instant::now()) {
+            let text = format!(
+                "Possible memory leak: {} ({}) grew {}/min for {window}, now {}",
+                suspect.name,
+                suspect.pid,
+                self.locale.megabytes(suspect.growth as u64, 1),
+                self.locale.megabytes(suspect.memory, 1)
+            );
+            self.events.push(EventKind::Alert, text.clone());
+            self.set_message(text);
+        }
+    }
+
+    // 一覧を開いている間は収集のたびに読み直す。終了して読めなくなったら最後の一覧を残す
+    fn reload_files(&mut self) {
+        if let Some(view) = self.files.as_mut()
+            && let Ok(list) = files::list(view.pid)
+        {
+            view.selected = view.selected.min(list.len().saturating_sub(1));
+            view.files = list;
+        }
+    }
+
+    // スレッドは数が多いので記録しない
+    fn record_started_and_exited(&mut self, snapshot: &Snapshot, changes: &ProcessChanges) {
+        let added: HashSet<Pid> = changes.added.iter().copied().collect();
+        for p in snapshot.processes.iter() {
+            if !p.is_thread && added.contains(&p.pid) {
+                let text = if p.cmd.is_empty() {
+                    format!("{} ({})", p.name, p.pid)
+                } else {
+                    format!("{} ({}) {}", p.name, p.pid, p.cmd)
+                };
+                self.events.push(EventKind::Started, text);
+            }
+        }
+        for p in changes.exited.iter().filter(|p| !p.is_thread) {
+            self.events
+                .push(EventKind::Exited, format!("{} ({})", p.name, p.pid));
+        }
+    }
+
+    fn track_new_processes(&mut self, added: &[Pid]) {
+        self.new_pids.retain(|_, age| {
+            *age += 1;
+            *age < NEW_PROCESS_TICKS
+        });
+        for pid in added {
+            self.new_pids.insert(*pid, 0);
+        }
+    }
+
+    fn track_exited(&mut self, snapshot: &Snapshot, exited: &[ProcessInfo]) {
+        self.exited.retain_mut(|(_, age)| {
+            *age += 1;
+            *age < EXITED_TICKS
+        });
+        self.exited.extend(exited.iter().map(|p| (p.clone(), 0)));
+        // PID が使い回されたら新しいプロセスの方だけを出す
+        if !self.exited.is_empty() {
+            let alive: HashSet<Pid> = snapshot.processes.iter().map(|p| p.pid).collect();
+            self.exited.retain(|(p, _)| !alive.contains(&p.pid));
+        }
+    }
+
+    fn track_changes(&mut self, snapshot: &Snapshot) {
+        self.changed.retain(|_, age| {
+            *age += 1;
+            *age < CHANGE_TICKS
+        });
+        let count = snapshot.cpu.count;
+        let previous = std::mem::take(&mut self.previous_values);
+        for p in &snapshot.processes {
+            let cpu = self.cpu_mode.scale(p.cpu_usage, count);
+            // CPU は 2 回目の収集までは 0 なので比べない
+            if let Some(&(old_cpu, old_memory)) = previous.get(&p.pid) {
+                if snapshot.cpu_primed && (cpu - old_cpu).abs() >= CHANGE_CPU_POINTS {
+                    self.changed.insert((p.pid, SortKey::Cpu), 0);
+                }
+                if p.memory.abs_diff(old_memory) >= CHANGE_MEMORY_BYTES {
+                    self.changed.insert((p.pid, SortKey::Memory), 0);
+                }
+            }
+            self.previous_values.insert(p.pid, (cpu, p.memory));
+        }
+    }
+
+    fn toggle_pin(&mut self, pid: Pid) {
+        if let Some(i) = self.pinned.iter().position(|p| *p == pid) {
+            self.pinned.remove(i);
+            self.pin_history.remove(&pid);
+            self.set_message(format!("Unpinned {pid}"));
+        } else {
+            self.pinned.push(pid);
+            self.set_message(format!("Pinned {pid}"));
+        }
+    }
+
+    fn toggle_follow(&mut self, pid: Pid, snapshot: &Snapshot) {
+        if self.followed.as_ref().is_some_and(|f| f.pid == pid) {
+            self.followed = None;
+            self.set_message(format!("Stopped following {pid}"));
+            return;
+        }
+        let Some(process) = snapshot.processes.iter().find(|p| p.pid == pid) else {
+            return;
+        };
+        self.followed = Some(Followed {
+            pid,
+            start_time: process.start_time,
+            name: Arc::clone(&process.name),
+            cmd: Arc::clone(&process.cmd),
+            exited: false,
+        });
+        self.set_message(format!("Following {} ({pid})", process.name));
+    }
+
+    // 追いかけているプロセスを選び直す。終了していれば、設定に応じて再起動したものに移る
+    fn track_followed(&mut self, snapshot: &Snapshot) {
+        let Some(followed) = self.followed.as_mut() else {
+            return;
+        };
+        if snapshot
+            .processes
+            .iter()
+            .any(|p| p.pid == followed.pid && p.start_time == followed.start_time)
+        {
+            self.selected = Some(followed.pid);
+            return;
+        }
+        let restarted = self
+            .follow_restarts
+            .then(|| {
+                snapshot
+                    .processes
+                    .iter()
+                    .filter(|p| p.name == followed.name && p.cmd == followed.cmd)
+                    .max_by_key(|p| p.start_time)
+            })
+            .flatten();
+        let text = match restarted {
+            Some(process) => {
+                followed.pid = process.pid;
+                followed.start_time = process.start_time;
+                followed.exited = false;
+                self.selected = Some(process.pid);
+                format!("{} restarted as {}", followed.name, process.pid)
+            }
+            None if followed.exited => return,
+            None => {
+                followed.exited = true;
+                format!(
+                    "Followed process {} ({}) exited",
+                    followed.name, followed.pid
+                )
+            }
+        };
+        self.set_message(text);
+    }
+
+    /// 追いかけているプロセスか
+    pub fn is_followed(&self, pid: Pid) -> bool {
+        self.followed
+            .as_ref()
+            .is_some_and(|f| !f.exited && f.pid == pid)
+    }
+
+    fn record_pin_history(&mut self, snapshot: &Snapshot) {
+        let mut exited = Vec::new();
+        for pid in &self.pinned {
+            match snapshot.processes.iter().find(|p| p.pid == *pid) {
+                Some(process) => self.pin_history.entry(*pid).or_default().push(process),
+                // 動いているのを見たプロセスが消えたときだけ、1 回知らせる
+                None => {
+                    if let Some(history) = self.pin_history.get_mut(pid)
+                        && !history.exited
+                    {
+                        history.exited = true;
+                        exited.push((*pid, Arc::clone(&history.name)));
+                    }
+                }
+            }
+        }
+        for (pid, name) in exited {
+            self.pin_exited(pid, &name);
+        }
+    }
+
+    fn pin_exited(&mut self, pid: Pid, name: &str) {
+        self.notice(format!("Pinned process {pid} ({name}) exited"));
+    }
+
+    // [pin] on_exit の方法で知らせ、メッセージにも出す
+    fn notice(&mut self, text: String) {
+        for notice in &self.on_exit {
+            match notice {
+                ExitNotice::Bell => self.bell = true,
+                ExitNotice::Flash => self.flash_until = Some(Instant::now() + FLASH_DURATION),
+                #[cfg(feature = "notify")]
+                ExitNotice::Desktop => notify::desktop("rust-top", &text),
+                #[cfg(not(feature = "notify"))]
+                ExitNotice::Desktop => {}
+            }
+        }
+        self.set_message(text);
+    }
+
+    /// watchdog が送るシグナルを取り出す
+    pub fn take_watchdog_commands(&mut self) -> Vec<Command> {
+        std::mem::take(&mut self.watchdog_commands)
+    }
+
+    /// ベルを鳴らす必要があれば true を返し、要求を消す
+    pub fn take_bell(&mut self) -> bool {
+        std::mem::take(&mut self.bell)
+    }
+
+    // csv と json はすぐにプロセス表を書き出し、text と html は次に描き終えた画面を書き出す
+    fn save(&mut self, format: ExportFormat, snapshot: &Snapshot) {
+        if !format.is_table() {
+            self.export = Some(format);
+            return;
+        }
+        match export::save_processes(self, snapshot, format) {
+            Ok((path, rows)) => {
+                self.set_message(format!("Saved {rows} processes to {}", path.display()))
+            }
+            Err(e) => self.set_message(format!("Failed to save processes: {e}")),
+        }
+    }
+
+    /// S で保存を頼まれていれば、描き終えた画面を書き出して結果をメッセージにする。
+    /// 書き出したら true（メッセージを出すため描き直す）
+    pub fn export_screen(&mut self, buffer: &Buffer) -> bool {
+        let Some(format) = self.export.take() else {
+            return false;
+        };
+        match export::save(buffer, format) {
+            Ok(path) => self.set_message(format!("Saved screen to {}", path.display())),
+            Err(e) => self.set_message(format!("Failed to save screen: {e}")),
+        }
+        true
+    }
+
+    /// 画面を反転して描く間は true
+    pub fn flashing(&self) -> bool {
+        self.flash_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    /// 時計の他にも時間とともに変わる表示（経過時間の列、発報中の警告の秒数、
+    /// タイマーの残り時間、点滅、描画時間）があるときは true
+    pub fn ticking(&self) -> bool {
+        self.flashing()
+            || self.process_times
+            || self.profile.is_some()
+            || self.detail.is_some()
+            || self.alerts.active().next().is_some()
+    }
+
+    // ピン留めしたプロセスとその子孫の PID
+    fn watched_pids(&self, snapshot: &Snapshot) -> HashSet<Pid> {
+        subtree(snapshot, &self.pinned.iter().copied().collect())
+    }
+
+    /// 数字キーで選んでいる絞り込み
+    pub fn current_preset(&self) -> Option<&FilterPreset> {
+        self.presets.get(self.preset?)
+    }
+
+    /// 終了して、まだ表に残しているプロセスか
+    pub fn is_exited(&self, pid: Pid) -> bool {
+        self.exited.iter().any(|(p, _)| p.pid == pid)
+    }
+
+    /// 新しく現れたプロセスなら、現れてからの収集回数を返す
+    pub fn new_process_age(&self, pid: Pid) -> Option<u8> {
+        self.new_pids.get(&pid).copied()
+    }
+
+    /// 前回の収集から大きく動いたセルなら、動いてからの収集回数を返す
+    pub fn change_age(&self, pid: Pid, key: SortKey) -> Option<u8> {
+        self.changed.get(&(pid, key)).copied()
+    }
+
+    /// 新しいスナップショットに合わせて選択状態を整える
+    pub fn sync_selection(&mut self, snapshot: &Snapshot) {
+        for pid in [self.detail, self.threads].into_iter().flatten() {
+            if !snapshot.processes.iter().any(|p| p.pid == pid) {
+                self.process_gone(pid, snapshot);
+                return;
+            }
+        }
+
+        let visible = self.visible_processes(snapshot);
+        if visible.is_empty() {
+            self.selected = None;
+            self.selected_row = 0;
+            self.offset = 0;
+            return;
+        }
+        match self
+            .selected
+            .and_then(|pid| visible.iter().position(|p| p.pid == pid))
+        {
+            Some(row) => self.selected_row = row,
+            None => {
+                // 選択していたプロセスが消えたら同じ位置に近い行を選ぶ
+                let row = self.selected_row.min(visible.len() - 1);
+                self.selected = Some(visible[row].pid);
+                self.selected_row = row;
+            }
+        }
+        self.scroll_to_selection(visible.len());
+    }
+
+    fn scroll_to_selection(&mut self, len: usize) {
+        if self.selected_row < self.offset {
+            self.offset = self.selected_row;
+        } else if self.selected_row >= self.offset + self.process_rows {
+            self.offset = self.selected_row + 1 - self.process_rows;
+        }
+        self.offset = self.offset.min(len.saturating_sub(self.process_rows));
+    }
+
+    /// 描画したプロセス表の行数を覚える。表を出さなかったとき（0）は前の値のまま
+    pub fn set_process_rows(&mut self, rows: usize) {
+        if rows > 0 {
+            self.process_rows = rows;
+        }
+    }
+
+    /// 描画した一覧のポップアップの行数を覚える。PageUp / PageDown はこの行数ずつ動く
+    pub fn set_popup_rows(&mut self, rows: usize) {
+        if rows > 0 {
+            self.popup_rows = rows;
+        }
+    }
+
+    pub fn shows(&self, panel: Panel) -> bool {
+        !self.hidden.contains(&panel)
+    }
+
+    /// 隠したパネルのうち、警告にも使わないものは収集を止める
+    pub fn paused_sources(&self) -> Paused {
+        Paused {
+            cpu: !self.shows(Panel::Cpu) && !self.alerts.watches(Metric::Cpu) && !self.heatmap,
+            memory: !self.shows(Panel::Memory)
+                && !self.alerts.watches(Metric::Memory)
+                && !self.alerts.watches(Metric::Swap),
+            info: !self.shows(Panel::Info),
+        }
+    }
+
+    fn toggle_panel(&mut self, panel: Panel) {
+        let state = if self.hidden.remove(&panel) {
+            "shown"
+        } else {
+            self.hidden.insert(panel);
+            "hidden"
+        };
+        self.set_message(format!("{} panel {state}", panel.label()));
+    }
+
+    /// 表に出すかどうか（k の種類・:filter・ユーザー・プリセット・ピン留め・サービス）
+    pub fn row_filter<'s>(&'s self, snapshot: &'s Snapshot) -> impl Fn(&ProcessInfo) -> bool + 's {
+        let watched = (!self.pinned.is_empty()).then(|| self.watched_pids(snapshot));
+        #[cfg(any(feature = "systemd", windows))]
+        let service: Option<HashSet<Pid>> = self.service_filter.as_ref().map(|name| {
+            snapshot
+                .services
+                .iter()
+                .find(|u| u.name == *name)
+                .map_or_else(HashSet::new, |u| u.pids.iter().copied().collect())
+        });
+        #[cfg(not(any(feature = "systemd", windows)))]
+        let service: Option<HashSet<Pid>> = None;
+        move |p: &ProcessInfo| {
+            // :category kernel ならカーネルのスレッドも出す
+            (self.kernel_threads || !p.is_kernel_thread() || self.category_filter.is_some())
+                && self.filter.allows(&p.name, &p.cmd)
+                && self.user_filter.as_deref().is_none_or(|u| &*p.user == u)
+                && self.category_filter.as_deref().is_none_or(|name| {
+                    self.categories
+                        .classify(&p.name, p.is_kernel_thread())
+                        .is_some_and(|c| c.name == name)
+                })
+                && self.name_filter.as_ref().is_none_or(|f| {
+                    f.matches(
+                        p,
+                        self.cpu_mode.scale(p.cpu_usage, snapshot.cpu.count) as f64,
+                    )
+                })
+                && self
+                    .current_preset()
+                    .is_none_or(|preset| preset.allows(&p.name, &p.cmd, &p.user))
+                && watched.as_ref().is_none_or(|w| w.contains(&p.pid))
+                && service.as_ref().is_none_or(|s| s.contains(&p.pid))
+        }
+    }
+
+    /// 表の並び順にする
+    pub fn sort_processes(&self, processes: &mut Vec<&ProcessInfo>) {
+        par::sort_by(processes, |a, b| self.compare_rows(a, b));
+    }
+
+    /// 表示対象のプロセスをフィルタ・ソートして返す
+    /// 終了したばかりのプロセスも最後の値で並べる。名前でまとめているときは、まとめた行を合計値で作る
+    pub fn visible_processes<'a>(&self, snapshot: &'a Snapshot) -> Vec<Cow<'a, ProcessInfo>> {
+        let allowed = self.row_filter(snapshot);
+        let mut processes: Vec<_> = snapshot.processes.iter().filter(|p| allowed(p)).collect();
+        self.sort_processes(&mut processes);
+        let order = |a: &ProcessInfo, b: &ProcessInfo| self.compare_rows(a, b);
+        if self.group_by_name {
+            // まとめた行の合計に終了したプロセスは混ぜない
+            return self.group_processes(processes);
+        }
+        let mut rows: Vec<Cow<'a, ProcessInfo>> =
+            processes.into_iter().map(Cow::Borrowed).collect();
+        // 終了したプロセスは数が少ないので、並び順の位置に差し込む
+        for (p, _) in self.exited.iter().filter(|(p, _)| allowed(p)) {
+            let at = rows.partition_point(|row| order(row, p) != Ordering::Greater);
+            rows.insert(at, Cow::Owned(p.clone()));
+        }
+        rows
+    }
+
+    // 並べ替え済みの一覧を名前ごとにまとめる。まとめた行はいちばん古いメンバーの PID を持ち、
+    // 開いたときはそれ以外のメンバーを並び順のまま下に続ける
+    fn group_processes<'a>(&self, processes: Vec<&'a ProcessInfo>) -> Vec<Cow<'a, ProcessInfo>> {
+        let mut groups: Vec<Vec<&ProcessInfo>> = Vec::new();
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        for p in processes {
+            match index.entry(&p.name) {
+                Entry::Occupied(e) => groups[*e.get()].push(p),
+                Entry::Vacant(e) => {
+                    e.insert(groups.len());
+                    groups.push(vec![p]);
+                }
+            }
+        }
+        let mut rows: Vec<(Cow<ProcessInfo>, Vec<&ProcessInfo>)> = groups
+            .into_iter()
+            .map(|members| {
+                let [single] = members[..] else {
+                    let leader = *members
+                        .iter()
+                        .min_by_key(|p| (p.start_time, p.pid))
+                        .expect("group has members");
+                    let mut row = leader.clone();
+                    row.cpu_usage = members.iter().map(|p| p.cpu_usage).sum();
+                    row.memory = members.iter().map(|p| p.memory).sum();
+                    row.virtual_memory = members.iter().map(|p| p.virtual_memory).sum();
+                    row.shared = members.iter().map(|p| p.shared).sum();
+                    row.swap = members.iter().map(|p| p.swap).sum();
+                    row.minor_faults = members.iter().map(|p| p.minor_faults).sum();
+                    row.major_faults = members.iter().map(|p| p.major_faults).sum();
+                    row.cpu_time = members.iter().map(|p| p.cpu_time).sum();
+                    row.cpu_history = sum_history(&members);
+                    row.threads = members.iter().map(|p| p.threads).sum();
+                    return (Cow::Owned(row), members);
+                };
+                (Cow::Borrowed(single), Vec::new())
+            })
+            .collect();
+        rows.sort_by(|a, b| self.compare_rows(&a.0, &b.0));
+
+        let mut visible = Vec::with_capacity(rows.len());
+        for (mut row, members) in rows {
+            if members.is_empty() {
+                visible.push(row);
+                continue;
+            }
+            let expanded = self.expanded.contains(&row.name);
+            let marker = if expanded { '▾' } else { '▸' };
+            let leader = row.pid;
+            let name: Arc<str> = format!("{marker} {} ({})", row.name, members.len()).into();
+            // コマンドライン表示（C）でもまとめた行とわかるようにする
+            let row_mut = row.to_mut();
+            row_mut.cmd = Arc::clone(&name);
+            row_mut.name = name;
+            visible.push(row);
+            if expanded {
+                visible.extend(members.into_iter().filter(|p| p.pid != leader).map(|p| {
+                    let mut member = p.clone();
+                    member.name = format!("  {}", p.name).into();
+                    member.cmd = format!("  {}", p.cmd).into();
+                    Cow::Owned(member)
+                }));
+            }
+        }
+        visible
+    }
+}
+
+// 新しい方の端をそろえて足す。途中から現れたメンバーは古い方が欠けている
+fn sum_history(members: &[&ProcessInfo]) -> Arc<[f32]> {
+    let len = members
+        .iter()
+        .map(|p| p.cpu_history.len())
+        .max()
+        .unwrap_or(0);
+    let mut sum = vec![0.0; len];
+    for p in members {
+        let start = len - p.cpu_history.len();
+        for (total, value) in sum[start..].iter_mut().zip(p.cpu_history.iter()) {
+            *total += value;
+        }
+    }
+    sum.into()
+}
+
+// roots とその子孫の PID
+fn subtree(snapshot: &Snapshot, roots: &HashSet<Pid>) -> HashSet<Pid> {
+    let parents: HashMap<Pid, Option<Pid>> = snapshot
+        .processes
+        .iter()
+        .map(|p| (p.pid, p.parent))
+        .collect();
+    snapshot
+        .processes
+        .iter()
+        .map(|p| p.pid)
+        .filter(|pid| {
+            let mut current = Some(*pid);
+            // 親をたどる。循環していても止まるように深さを制限する
+            for _ in 0..64 {
+                let Some(pid) = current else {
+                    return false;
+                };
+                if roots.contains(&pid) {
+                    return true;
+                }
+                current = parents.get(&pid).copied().flatten();
+            }
+            false
+        })
+        .collect()
+}
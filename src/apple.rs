@@ -0,0 +1,183 @@
+// Apple Silicon の P コア / E コア別の使用率・熱の状態・電力（macOS）
+//
+// コアの種類は sysctl の hw.perflevel* から、熱の状態は通知センターの
+// com.apple.system.thermalpressurelevel から読む。電力は powermetrics が
+// root でしか動かないため、root で起動したときだけ取る。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreKind {
+    Performance,
+    Efficiency,
+}
+
+impl CoreKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            CoreKind::Performance => "P",
+            CoreKind::Efficiency => "E",
+        }
+    }
+}
+
+/// 熱による制限の段階（OSThermalPressureLevel）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ThermalPressure {
+    Nominal,
+    Moderate,
+    Heavy,
+    Trapping,
+    Sleeping,
+}
+
+impl ThermalPressure {
+    pub fn label(self) -> &'static str {
+        match self {
+            ThermalPressure::Nominal => "nominal",
+            ThermalPressure::Moderate => "moderate",
+            ThermalPressure::Heavy => "heavy",
+            ThermalPressure::Trapping => "trapping",
+            ThermalPressure::Sleeping => "sleeping",
+        }
+    }
+
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    fn from_level(level: u64) -> Option<Self> {
+        match level {
+            0 => Some(ThermalPressure::Nominal),
+            1 => Some(ThermalPressure::Moderate),
+            2 => Some(ThermalPressure::Heavy),
+            3 => Some(ThermalPressure::Trapping),
+            4 => Some(ThermalPressure::Sleeping),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AppleSnapshot {
+    /// コアごとの種類と使用率（%）。sysinfo の CPU の並び順
+    pub cores: Vec<(CoreKind, f32)>,
+    pub thermal: Option<ThermalPressure>,
+    /// CPU・GPU・ANE を合わせた電力（W）。root でなければ None
+    pub power: Option<f64>,
+}
+
+impl AppleSnapshot {
+    /// その種類のコアの数と平均使用率
+    pub fn average(&self, kind: CoreKind) -> Option<(usize, f32)> {
+        let usage: Vec<f32> = self
+            .cores
+            .iter()
+            .filter(|(k, _)| *k == kind)
+            .map(|(_, usage)| *usage)
+            .collect();
+        (!usage.is_empty()).then(|| (usage.len(), usage.iter().sum::<f32>() / usage.len() as f32))
+    }
+}
+
+/// Apple Silicon でなければ None。CPU の使用率は Cpu の収集で更新したものを使う
+#[cfg(target_os = "macos")]
+pub fn collect(usages: &[f32]) -> Option<AppleSnapshot> {
+    // P コアが perflevel0、E コアが perflevel1。Intel の Mac には perflevel が無い
+    let performance = sysctl_u32("hw.perflevel0.logicalcpu")? as usize;
+    let efficiency = sysctl_u32("hw.perflevel1.logicalcpu").unwrap_or(0) as usize;
+    // CPU 番号は E コアが先に並ぶ
+    let cores = usages
+        .iter()
+        .enumerate()
+        .take(performance + efficiency)
+        .map(|(i, usage)| {
+            let kind = if i < efficiency {
+                CoreKind::Efficiency
+            } else {
+                CoreKind::Performance
+            };
+            (kind, *usage)
+        })
+        .collect();
+    Some(AppleSnapshot {
+        cores,
+        thermal: thermal_pressure(),
+        power: package_power(),
+    })
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn collect(_usages: &[f32]) -> Option<AppleSnapshot> {
+    None
+}
+
+#[cfg(target_os = "macos")]
+fn sysctl_u32(name: &str) -> Option<u32> {
+    let name = std::ffi::CString::new(name).ok()?;
+    let mut value: u32 = 0;
+    let mut size = std::mem::size_of::<u32>();
+    // SAFETY: value と size は呼び出しの間有効で、size は value の大きさ
+    let status = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr(),
+            (&mut value as *mut u32).cast(),
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    (status == 0).then_some(value)
+}
+
+#[cfg(target_os = "macos")]
+fn thermal_pressure() -> Option<ThermalPressure> {
+    use std::os::raw::{c_char, c_int};
+
+    unsafe extern "C" {
+        fn notify_register_check(name: *const c_char, out_token: *mut c_int) -> u32;
+        fn notify_get_state(token: c_int, state: *mut u64) -> u32;
+        fn notify_cancel(token: c_int) -> u32;
+    }
+
+    let mut token: c_int = 0;
+    let mut level: u64 = 0;
+    // SAFETY: 名前は終端付きで、token は登録に成功したときだけ使って解除する
+    unsafe {
+        if notify_register_check(
+            c"com.apple.system.thermalpressurelevel".as_ptr(),
+            &mut token,
+        ) != 0
+        {
+            return None;
+        }
+        let status = notify_get_state(token, &mut level);
+        notify_cancel(token);
+        if status != 0 {
+            return None;
+        }
+    }
+    ThermalPressure::from_level(level)
+}
+
+// powermetrics を 1 回だけ短く動かし、"Combined Power (CPU + GPU + ANE): 1234 mW" を読む
+#[cfg(target_os = "macos")]
+fn package_power() -> Option<f64> {
+    use std::process::Command;
+
+    // SAFETY: 引数の無い問い合わせ
+    if unsafe { libc::geteuid() } != 0 {
+        return None;
+    }
+    let output = Command::new("powermetrics")
+        .args(["--samplers", "cpu_power", "-i", "200", "-n", "1"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let milliwatts = text.lines().find_map(|line| {
+        let (label, value) = line.split_once(':')?;
+        if !label.starts_with("Combined Power") {
+            return None;
+        }
+        value.trim().strip_suffix("mW")?.trim().parse::<f64>().ok()
+    })?;
+    Some(milliwatts / 1000.0)
+}
@@ -0,0 +1,127 @@
+// 基準にした時点との比較（B）
+//
+// B で今のプロセスの一覧を基準として覚え、もう一度 B を押すと、それから増えたプロセス、
+// 消えたプロセス、続いているプロセスの CPU 時間とメモリの増減を並べる。
+// PID は使い回されるので、起動時刻と組にして同じプロセスかを見分ける。
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sysinfo::Pid;
+
+use crate::sampler::Snapshot;
+
+/// 基準にした時点のプロセス
+#[derive(Debug, Clone)]
+struct Entry {
+    name: Arc<str>,
+    /// ミリ秒
+    cpu_time: u64,
+    memory: u64,
+}
+
+/// 覚えておいた時点
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    /// UNIX 時間の秒
+    pub time: u64,
+    processes: HashMap<(Pid, u64), Entry>,
+}
+
+/// 基準からの変わり方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Started,
+    Exited,
+    Running,
+}
+
+/// 比較の 1 行。増減は基準からの差（起動したものは起動してからの値、消えたものは 0 との差）
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffRow {
+    pub pid: Pid,
+    pub name: Arc<str>,
+    pub change: Change,
+    /// ミリ秒
+    pub cpu_time: i64,
+    /// バイト
+    pub memory: i64,
+}
+
+impl Baseline {
+    pub fn capture(snapshot: &Snapshot) -> Self {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let processes = snapshot
+            .processes
+            .iter()
+            .filter(|p| !p.is_thread)
+            .map(|p| {
+                let entry = Entry {
+                    name: p.name.clone(),
+                    cpu_time: p.cpu_time,
+                    memory: p.memory,
+                };
+                ((p.pid, p.start_time), entry)
+            })
+            .collect();
+        Self { time, processes }
+    }
+
+    /// 増えたもの、消えたもの、CPU 時間の増えた順に続いているものを並べる。
+    /// 続いていて CPU 時間もメモリも変わっていないものは省く
+    pub fn diff(&self, snapshot: &Snapshot) -> Vec<DiffRow> {
+        let mut rows = Vec::new();
+        let mut seen = HashSet::new();
+        for p in snapshot.processes.iter().filter(|p| !p.is_thread) {
+            let key = (p.pid, p.start_time);
+            let row = match self.processes.get(&key) {
+                Some(before) => {
+                    seen.insert(key);
+                    DiffRow {
+                        pid: p.pid,
+                        name: p.name.clone(),
+                        change: Change::Running,
+                        cpu_time: p.cpu_time as i64 - before.cpu_time as i64,
+                        memory: p.memory as i64 - before.memory as i64,
+                    }
+                }
+                None => DiffRow {
+                    pid: p.pid,
+                    name: p.name.clone(),
+                    change: Change::Started,
+                    cpu_time: p.cpu_time as i64,
+                    memory: p.memory as i64,
+                },
+            };
+            if row.change != Change::Running || row.cpu_time != 0 || row.memory != 0 {
+                rows.push(row);
+            }
+        }
+        rows.extend(
+            self.processes
+                .iter()
+                .filter(|(key, _)| !seen.contains(key))
+                .map(|(&(pid, _), before)| DiffRow {
+                    pid,
+                    name: before.name.clone(),
+                    change: Change::Exited,
+                    cpu_time: 0,
+                    memory: -(before.memory as i64),
+                }),
+        );
+        let order = |change: Change| match change {
+            Change::Started => 0,
+            Change::Exited => 1,
+            Change::Running => 2,
+        };
+        rows.sort_by(|a, b| {
+            order(a.change)
+                .cmp(&order(b.change))
+                .then(b.cpu_time.cmp(&a.cpu_time))
+                .then(a.pid.cmp(&b.pid))
+        });
+        rows
+    }
+}
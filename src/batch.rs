@@ -0,0 +1,285 @@
+// 端末を使わずにプロセス表を 1 回だけ出力する（rust-top batch）
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::thread;
+
+use ratatui::layout::Alignment;
+use sysinfo::MINIMUM_CPU_UPDATE_INTERVAL;
+
+use crate::app::{App, SortKey};
+use crate::cli::Args;
+use crate::config::Config;
+use crate::json;
+use crate::numa;
+use crate::sampler::{Collector, ProcessInfo, Snapshot};
+use crate::source::{MockSource, SystemSource};
+use crate::text;
+use crate::ui;
+
+/// 出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// 画面と同じ書式の表
+    #[default]
+    Text,
+    Json,
+    Csv,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+}
+
+pub fn run(args: &Args, config: &Config) -> io::Result<()> {
+    if args.mock {
+        print(args, config, Collector::with_source(MockSource::default()))
+    } else {
+        print(args, config, Collector::new())
+    }
+}
+
+fn print<S: SystemSource>(
+    args: &Args,
+    config: &Config,
+    mut collector: Collector<S>,
+) -> io::Result<()> {
+    let app = App::new(args, config);
+    // SHR と Swap は頼まれたときだけ読む
+    collector.set_memory_detail(
+        args.columns
+            .iter()
+            .any(|key| matches!(key, SortKey::Shared | SortKey::Swap)),
+    );
+    collector.set_numa_nodes(args.columns.contains(&SortKey::Node));
+    collector.set_fd_counts(args.columns.contains(&SortKey::Files));
+    collector.set_wait_channels(args.columns.contains(&SortKey::WaitChannel));
+    collector.set_page_faults(args.columns.iter().any(|key| key.is_fault()));
+    collector.set_net_traffic(args.columns.iter().any(|key| key.is_traffic()));
+    // CPU 使用率は 2 回の計測の差から求まる
+    collector.collect_all();
+    thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL);
+    collector.collect_all();
+    let mut snapshot = collector.snapshot().clone();
+    snapshot.cpu_primed = true;
+
+    let mut processes = app.visible_processes(&snapshot);
+    if args.limit > 0 {
+        processes.truncate(args.limit);
+    }
+    // 既定の列は画面と同じく、コンテナ内のプロセスが無ければ Container を省く
+    let columns = if args.columns.is_empty() {
+        let show_container = snapshot.processes.iter().any(|p| p.container.is_some());
+        SortKey::COLUMNS
+            .into_iter()
+            .filter(|key| show_container || *key != SortKey::Container)
+            .filter(|key| !key.is_memory_detail())
+            .filter(|key| {
+                !matches!(
+                    key,
+                    SortKey::Category
+                        | SortKey::History
+                        | SortKey::Node
+                        | SortKey::Files
+                        | SortKey::WaitChannel
+                )
+            })
+            .filter(|key| !key.is_process_time() && !key.is_traffic() && !key.is_fault())
+            .collect()
+    } else {
+        args.columns.clone()
+    };
+
+    // 出力先が閉じられても（head などにつないだとき）エラーにしない
+    match write(args.format, &app, &snapshot, &processes, &columns) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+fn write(
+    format: Format,
+    app: &App,
+    snapshot: &Snapshot,
+    processes: &[Cow<ProcessInfo>],
+    columns: &[SortKey],
+) -> io::Result<()> {
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    write_table(&mut out, format, app, snapshot, processes, columns)?;
+    out.flush()
+}
+
+/// プロセス表を format で書く。画面からの書き出し（S）でも使う
+pub fn write_table(
+    out: &mut impl Write,
+    format: Format,
+    app: &App,
+    snapshot: &Snapshot,
+    processes: &[Cow<ProcessInfo>],
+    columns: &[SortKey],
+) -> io::Result<()> {
+    match format {
+        Format::Text => {
+            let rows: Vec<Vec<String>> = processes
+                .iter()
+                .map(|p| {
+                    columns
+                        .iter()
+                        .map(|key| ui::process_cell(app, snapshot, p, *key))
+                        .collect()
+                })
+                .collect();
+            let widths: Vec<usize> = columns
+                .iter()
+                .enumerate()
+                .map(|(i, key)| {
+                    rows.iter()
+                        .map(|row| text::width(&row[i]))
+                        .chain([key.label().len()])
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+            let alignments: Vec<Alignment> = columns
+                .iter()
+                .map(|key| ui::column_alignment(app, *key))
+                .collect();
+            let labels: Vec<&str> = columns.iter().map(|key| key.label()).collect();
+            write_text_row(out, &labels, &widths, &alignments)?;
+            for row in &rows {
+                let cells: Vec<&str> = row.iter().map(String::as_str).collect();
+                write_text_row(out, &cells, &widths, &alignments)?;
+            }
+        }
+        Format::Json => {
+            writeln!(out, "[")?;
+            for (i, p) in processes.iter().enumerate() {
+                let fields: Vec<String> = columns
+                    .iter()
+                    .map(|key| {
+                        format!(
+                            "{}: {}",
+                            json::quote(&key.label().to_ascii_lowercase()),
+                            json_value(app, snapshot, p, *key)
+                        )
+                    })
+                    .collect();
+                let comma = if i + 1 < processes.len() { "," } else { "" };
+                writeln!(out, "  {{{}}}{comma}", fields.join(", "))?;
+            }
+            writeln!(out, "]")?;
+        }
+        Format::Csv => {
+            let labels: Vec<String> = columns
+                .iter()
+                .map(|key| key.label().to_ascii_lowercase())
+                .collect();
+            writeln!(out, "{}", labels.join(","))?;
+            for p in processes {
+                let cells: Vec<String> = columns
+                    .iter()
+                    .map(|key| csv_field(&raw_value(app, snapshot, p, *key)))
+                    .collect();
+                writeln!(out, "{}", cells.join(","))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_text_row(
+    out: &mut impl Write,
+    cells: &[&str],
+    widths: &[usize],
+    alignments: &[Alignment],
+) -> io::Result<()> {
+    let line: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .zip(alignments)
+        .map(|((cell, width), alignment)| text::pad(cell, *width, *alignment))
+        .collect();
+    writeln!(out, "{}", line.join(" ").trim_end())
+}
+
+// JSON と CSV ではロケールに依らない値を出す（メモリはバイト、CPU は %、CPU 時間は秒）
+fn raw_value(app: &App, snapshot: &Snapshot, p: &ProcessInfo, key: SortKey) -> String {
+    match key {
+        SortKey::Pid => p.pid.to_string(),
+        SortKey::User => p.user.to_string(),
+        // 表の短い名前やアイコンではなく、:category で使う名前
+        SortKey::Category => app
+            .categories
+            .classify(&p.name, p.is_kernel_thread())
+            .map_or_else(String::new, |c| c.name.clone()),
+        SortKey::Name => p.name.to_string(),
+        SortKey::Container => p.container.as_deref().unwrap_or("").to_string(),
+        SortKey::State => ui::state_label(p.status).to_string(),
+        SortKey::WaitChannel => p.wait_channel.as_deref().unwrap_or("").to_string(),
+        SortKey::Threads => p.threads.to_string(),
+        SortKey::Files => p.open_files.map_or_else(String::new, |n| n.to_string()),
+        // バイト/秒
+        SortKey::Received => p.net_received.map_or_else(String::new, |n| n.to_string()),
+        SortKey::Sent => p.net_sent.map_or_else(String::new, |n| n.to_string()),
+        SortKey::Cpu => format!("{:.1}", app.cpu_mode.scale(p.cpu_usage, snapshot.cpu.count)),
+        SortKey::History => history(app, snapshot, p).join(" "),
+        SortKey::Memory => p.memory.to_string(),
+        SortKey::Virtual => p.virtual_memory.to_string(),
+        SortKey::Shared => p.shared.map_or_else(String::new, |bytes| bytes.to_string()),
+        SortKey::Swap => p.swap.map_or_else(String::new, |bytes| bytes.to_string()),
+        // 1 秒あたりの回数
+        SortKey::MinorFaults => p.minor_faults.map_or_else(String::new, |n| n.to_string()),
+        SortKey::MajorFaults => p.major_faults.map_or_else(String::new, |n| n.to_string()),
+        SortKey::Node => p
+            .numa_pages
+            .as_deref()
+            .map_or_else(String::new, numa::label),
+        SortKey::Time => format!("{:.2}", p.cpu_time as f64 / 1000.0),
+        // 起動時刻は UNIX 時間、経過時間は秒
+        SortKey::Started => p.start_time.to_string(),
+        SortKey::Elapsed => ui::unix_now().saturating_sub(p.start_time).to_string(),
+    }
+}
+
+fn history(app: &App, snapshot: &Snapshot, p: &ProcessInfo) -> Vec<String> {
+    p.cpu_history
+        .iter()
+        .map(|&usage| format!("{:.1}", app.cpu_mode.scale(usage, snapshot.cpu.count)))
+        .collect()
+}
+
+fn json_value(app: &App, snapshot: &Snapshot, p: &ProcessInfo, key: SortKey) -> String {
+    match key {
+        SortKey::Shared if p.shared.is_none() => "null".to_string(),
+        SortKey::Swap if p.swap.is_none() => "null".to_string(),
+        SortKey::Files if p.open_files.is_none() => "null".to_string(),
+        SortKey::MinorFaults if p.minor_faults.is_none() => "null".to_string(),
+        SortKey::MajorFaults if p.major_faults.is_none() => "null".to_string(),
+        SortKey::Received if p.net_received.is_none() => "null".to_string(),
+        SortKey::Sent if p.net_sent.is_none() => "null".to_string(),
+        SortKey::History => format!("[{}]", history(app, snapshot, p).join(",")),
+        // "1,0" のように複数のノードが入るので文字列にする
+        SortKey::Node if p.numa_pages.is_none() => "null".to_string(),
+        SortKey::Node => json::quote(&raw_value(app, snapshot, p, key)),
+        _ if key.is_numeric() => raw_value(app, snapshot, p, key),
+        SortKey::Container if p.container.is_none() => "null".to_string(),
+        SortKey::WaitChannel if p.wait_channel.is_none() => "null".to_string(),
+        SortKey::Category if raw_value(app, snapshot, p, key).is_empty() => "null".to_string(),
+        _ => json::quote(&raw_value(app, snapshot, p, key)),
+    }
+}
+
+// 区切りや引用符を含むときだけ引用符で囲む
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,131 @@
+// バッテリーの状態（Linux の /sys/class/power_supply を読む）
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct BatterySnapshot {
+    /// 残量（%）
+    pub percent: f64,
+    pub state: ChargeState,
+    /// 満充電または空になるまでの見込み時間
+    pub time_remaining: Option<Duration>,
+    /// 消費（充電）電力（W）
+    pub power: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChargeState {
+    Charging,
+    Discharging,
+    Full,
+    Unknown,
+}
+
+impl ChargeState {
+    pub fn label(self) -> &'static str {
+        match self {
+            ChargeState::Charging => "Charging",
+            ChargeState::Discharging => "Discharging",
+            ChargeState::Full => "Full",
+            ChargeState::Unknown => "Unknown",
+        }
+    }
+}
+
+/// 残量がこれ以下で放電中なら警告する
+pub const LOW_PERCENT: f64 = 20.0;
+
+impl BatterySnapshot {
+    pub fn is_low(&self) -> bool {
+        self.state == ChargeState::Discharging && self.percent <= LOW_PERCENT
+    }
+}
+
+/// バッテリーが無ければ（デスクトップなど）None
+#[cfg(target_os = "linux")]
+pub fn collect() -> Option<BatterySnapshot> {
+    use std::fs;
+    use std::path::Path;
+
+    let read = |dir: &Path, name: &str| {
+        fs::read_to_string(dir.join(name))
+            .ok()
+            .map(|s| s.trim().to_string())
+    };
+    let number = |dir: &Path, name: &str| read(dir, name)?.parse::<f64>().ok();
+
+    // 複数ある場合は合算する（ThinkPad の BAT0 + BAT1 など）
+    let mut now = 0.0;
+    let mut full = 0.0;
+    let mut rate = 0.0;
+    let mut has_rate = false;
+    let mut states = Vec::new();
+    for entry in fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        let dir = entry.path();
+        if read(&dir, "type").as_deref() != Some("Battery") {
+            continue;
+        }
+        // energy_* は µWh / µW、charge_* は µAh / µA で電圧（µV）を掛けて揃える
+        let voltage = number(&dir, "voltage_now").map(|v| v / 1e6);
+        let (battery_now, battery_full, battery_rate) =
+            match (number(&dir, "energy_now"), number(&dir, "energy_full")) {
+                (Some(n), Some(f)) => (n, f, number(&dir, "power_now")),
+                _ => {
+                    let v = voltage.unwrap_or(1.0);
+                    let (Some(n), Some(f)) =
+                        (number(&dir, "charge_now"), number(&dir, "charge_full"))
+                    else {
+                        continue;
+                    };
+                    (
+                        n * v,
+                        f * v,
+                        number(&dir, "current_now").zip(voltage).map(|(c, v)| c * v),
+                    )
+                }
+            };
+        now += battery_now;
+        full += battery_full;
+        if let Some(r) = battery_rate {
+            rate += r.abs();
+            has_rate = true;
+        }
+        states.push(match read(&dir, "status").as_deref() {
+            Some("Charging") => ChargeState::Charging,
+            Some("Discharging") => ChargeState::Discharging,
+            Some("Full") | Some("Not charging") => ChargeState::Full,
+            _ => ChargeState::Unknown,
+        });
+    }
+    if states.is_empty() || full <= 0.0 {
+        return None;
+    }
+
+    let state = [
+        ChargeState::Discharging,
+        ChargeState::Charging,
+        ChargeState::Full,
+    ]
+    .into_iter()
+    .find(|s| states.contains(s))
+    .unwrap_or(ChargeState::Unknown);
+    // rate は µW（1e-6 W）、now / full は µWh
+    let power = (has_rate && rate > 0.0).then_some(rate / 1e6);
+    let hours = match state {
+        ChargeState::Discharging => power.map(|_| now / rate),
+        ChargeState::Charging => power.map(|_| (full - now).max(0.0) / rate),
+        _ => None,
+    };
+    Some(BatterySnapshot {
+        percent: (now / full * 100.0).clamp(0.0, 100.0),
+        state,
+        time_remaining: hours
+            .filter(|h| h.is_finite())
+            .map(|h| Duration::from_secs_f64(h * 3600.0)),
+        power,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn collect() -> Option<BatterySnapshot> {
+    None
+}
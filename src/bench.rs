@@ -0,0 +1,300 @@
+// 端末を使わずに収集・絞り込み・並べ替え・整形・描画の時間を計る（rust-top bench）
+//
+// --processes（--mock なら既定で 100 / 1000 / 10000）で作り物のプロセスを並べ、プロセス数に
+// 比例して重くなるところを計る。--baseline に前回の結果を残しておけば、遅くなった段を挙げて失敗する。
+use std::fs;
+use std::hint::black_box;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use ratatui::{Terminal, backend::TestBackend};
+
+use crate::app::App;
+use crate::cli::Args;
+use crate::config::Config;
+use crate::sampler::{Collector, ProcessInfo};
+use crate::source::{MockSource, SystemSource};
+use crate::ui;
+
+const DEFAULT_ITERATIONS: usize = 20;
+/// --mock で --processes を付けなかったときのプロセス数
+const DEFAULT_SIZES: [usize; 3] = [100, 1_000, 10_000];
+/// 基準よりこの割合を超えて遅くなったら失敗にする。ほかの処理に割り込まれた回で
+/// 外れないよう、平均ではなくいちばん速かった回で比べる
+const TOLERANCE: f64 = 0.25;
+/// これより小さい差は揺らぎとみなす
+const NOISE: Duration = Duration::from_micros(50);
+
+const STAGE_COUNT: usize = 12;
+const STAGES: [&str; STAGE_COUNT] = [
+    "collect cpu",
+    "collect memory",
+    "collect procs",
+    "collect info",
+    "collect battery",
+    "collect cgroup",
+    "collect pressure",
+    "collect soc",
+    "filter",
+    "sort",
+    "format",
+    "render",
+];
+
+// 1 回の計測（プロセス数ごと）
+struct Run {
+    processes: usize,
+    samples: Vec<[Duration; STAGE_COUNT]>,
+}
+
+impl Run {
+    fn average(&self, stage: usize) -> Duration {
+        self.samples.iter().map(|s| s[stage]).sum::<Duration>() / self.samples.len().max(1) as u32
+    }
+
+    fn fastest(&self, stage: usize) -> Duration {
+        self.samples
+            .iter()
+            .map(|s| s[stage])
+            .min()
+            .unwrap_or_default()
+    }
+}
+
+pub fn run(args: &Args, config: &Config) -> io::Result<()> {
+    let sizes = match (&args.processes[..], args.mock) {
+        ([], false) => Vec::new(),
+        ([], true) => DEFAULT_SIZES.to_vec(),
+        (sizes, _) => sizes.to_vec(),
+    };
+    let runs = if sizes.is_empty() {
+        vec![measure(args, config, Collector::new())?]
+    } else {
+        // 同じ数なら機械に依らず同じプロセス一覧で計る
+        sizes
+            .into_iter()
+            .map(|n| {
+                measure(
+                    args,
+                    config,
+                    Collector::with_source(MockSource::with_processes(n)),
+                )
+            })
+            .collect::<io::Result<_>>()?
+    };
+
+    // 出力先が閉じられても（head などにつないだとき）エラーにしない
+    match report(&runs) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+        result => result?,
+    }
+    let Some(path) = &args.baseline else {
+        return Ok(());
+    };
+    let Ok(text) = fs::read_to_string(path) else {
+        fs::write(path, baseline_text(&runs))?;
+        println!("saved the baseline to {}", path.display());
+        return Ok(());
+    };
+    let slower = compare(&runs, &parse_baseline(&text));
+    if slower.is_empty() {
+        println!("no stage slower than {}", path.display());
+        return Ok(());
+    }
+    for line in &slower {
+        eprintln!("rust-top bench: slower than the baseline: {line}");
+    }
+    std::process::exit(1);
+}
+
+fn measure<S: SystemSource>(
+    args: &Args,
+    config: &Config,
+    mut collector: Collector<S>,
+) -> io::Result<Run> {
+    let iterations = args.iterations.unwrap_or(DEFAULT_ITERATIONS);
+    let app = App::new(args, config);
+    let mut terminal = Terminal::new(TestBackend::new(120, 40))?;
+    // 初回はキャッシュの準備などで遅いので計測に含めない
+    collector.collect_all();
+
+    let mut samples = Vec::with_capacity(iterations);
+    let mut processes = 0;
+    for _ in 0..iterations {
+        collector.collect_all();
+        // CPU 列も整形されるように、使用率が確定した扱いにする
+        let mut snapshot = collector.snapshot().clone();
+        snapshot.cpu_primed = true;
+        processes = snapshot.processes.len();
+
+        // 表示の行のキャッシュに頼らず、全部の行を絞り込み・並べ替え・整形する
+        let started = Instant::now();
+        let allowed = app.row_filter(&snapshot);
+        let mut rows: Vec<&ProcessInfo> =
+            snapshot.processes.iter().filter(|p| allowed(p)).collect();
+        let filter = started.elapsed();
+
+        let started = Instant::now();
+        app.sort_processes(&mut rows);
+        let sort = started.elapsed();
+
+        let started = Instant::now();
+        let columns = ui::table_columns(&app, &snapshot);
+        for p in &rows {
+            black_box(ui::format_row(&app, &snapshot, &columns, p));
+        }
+        let format = started.elapsed();
+
+        let mut drawn = ui::Drawn::default();
+        let started = Instant::now();
+        terminal.draw(|f| drawn = ui::draw(f, &app, &snapshot))?;
+        let render = started.elapsed().saturating_sub(drawn.prepare_time);
+
+        let collect = snapshot.collect_times;
+        samples.push([
+            collect.cpu,
+            collect.memory,
+            collect.processes,
+            collect.info,
+            collect.battery,
+            collect.cgroup,
+            collect.pressure,
+            collect.soc,
+            filter,
+            sort,
+            format,
+            render,
+        ]);
+    }
+    Ok(Run { processes, samples })
+}
+
+fn report(runs: &[Run]) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    for run in runs {
+        writeln!(
+            out,
+            "rust-top bench: {} iterations, {} processes",
+            run.samples.len(),
+            run.processes
+        )?;
+        writeln!(
+            out,
+            "{:<18}{:>10}{:>10}{:>10}",
+            "stage", "min", "avg", "max"
+        )?;
+        for (i, stage) in STAGES.iter().enumerate() {
+            let max = run.samples.iter().map(|s| s[i]).max().unwrap_or_default();
+            writeln!(
+                out,
+                "{stage:<18}{:>10}{:>10}{:>10}",
+                millis(run.fastest(i)),
+                millis(run.average(i)),
+                millis(max)
+            )?;
+        }
+        writeln!(out, "(times in ms)")?;
+    }
+    Ok(())
+}
+
+fn millis(d: Duration) -> String {
+    format!("{:.3}", d.as_secs_f64() * 1000.0)
+}
+
+// 1 行に「プロセス数、段、いちばん速かった回（ms）」をタブで区切って書く
+fn baseline_text(runs: &[Run]) -> String {
+    let mut out = String::from("# rust-top bench --baseline\n");
+    for run in runs {
+        for (i, stage) in STAGES.iter().enumerate() {
+            out.push_str(&format!(
+                "{}\t{stage}\t{}\n",
+                run.processes,
+                millis(run.fastest(i))
+            ));
+        }
+    }
+    out
+}
+
+// 読めない行は飛ばす
+fn parse_baseline(text: &str) -> Vec<(usize, String, Duration)> {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let processes = fields.next()?.parse().ok()?;
+            let stage = fields.next()?.to_string();
+            let ms = fields.next()?.parse::<f64>().ok()?;
+            (ms.is_finite() && ms >= 0.0)
+                .then(|| (processes, stage, Duration::from_secs_f64(ms / 1000.0)))
+        })
+        .collect()
+}
+
+// 基準と同じプロセス数・段のうち、TOLERANCE と NOISE を超えて遅くなったもの
+fn compare(runs: &[Run], baseline: &[(usize, String, Duration)]) -> Vec<String> {
+    let mut slower = Vec::new();
+    for run in runs {
+        for (i, stage) in STAGES.iter().enumerate() {
+            let Some(&(_, _, before)) = baseline
+                .iter()
+                .find(|(n, s, _)| *n == run.processes && s == stage)
+            else {
+                continue;
+            };
+            let now = run.fastest(i);
+            if now > before.mul_f64(1.0 + TOLERANCE) && now - before > NOISE {
+                let change = (now.as_secs_f64() / before.as_secs_f64().max(1e-9) - 1.0) * 100.0;
+                slower.push(format!(
+                    "{stage} with {} processes: {} ms -> {} ms (+{change:.0}%)",
+                    run.processes,
+                    millis(before),
+                    millis(now)
+                ));
+            }
+        }
+    }
+    slower
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::App;
+    use crate::cli::Args;
+    use crate::config::Config;
+    use crate::filter::Filter;
+    use crate::locale::Locale;
+    use crate::sampler::{Collector, ProcessChanges};
+    use crate::source::MockSource;
+    use crate::ui;
+
+    #[test]
+    fn synthetic_process_sets_filter_sort_and_format_every_row() {
+        let mut collector = Collector::with_source(MockSource::with_processes(1_000));
+        collector.collect_all();
+        collector.collect_all();
+        let mut snapshot = collector.snapshot().clone();
+        snapshot.cpu_primed = true;
+        assert_eq!(snapshot.processes.len(), 1_000);
+        let mut app = App::new(&Args::default(), &Config::default());
+        app.locale = Locale::default();
+        app.on_snapshot(&snapshot, &ProcessChanges::default());
+        app.name_filter = Some(Filter::parse("user == \"alice\"").expect("filter"));
+
+        let allowed = app.row_filter(&snapshot);
+        let mut rows: Vec<_> = snapshot.processes.iter().filter(|p| allowed(p)).collect();
+        assert!(rows.len() > 100 && rows.iter().all(|p| &*p.user == "alice"));
+        app.sort_processes(&mut rows);
+        assert!(rows.windows(2).all(|w| w[0].cpu_usage >= w[1].cpu_usage));
+        // 表が整形するものと同じ列を、表示の行のキャッシュを通さずに作る
+        let columns = ui::table_columns(&app, &snapshot);
+        for p in &rows {
+            let cells = ui::format_row(&app, &snapshot, &columns, p);
+            assert_eq!(cells.len(), columns.len());
+            assert_eq!(cells[0], p.pid.to_string());
+        }
+        let visible: Vec<_> = app.visible_processes(&snapshot);
+        assert_eq!(visible.len(), rows.len());
+    }
+}
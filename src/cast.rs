@@ -0,0 +1,82 @@
+// 画面への出力を asciicast v2（.cast）として保存する（--record）
+//
+// 端末に書いた内容をそのまま複製し、フラッシュごと（ratatui では 1 フレームごと）に
+// 1 つの出力イベントとして記録する。asciinema などのプレーヤーで再生できる。
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::json;
+
+/// 端末への出力。録画中なら同じ内容をファイルにも書く
+pub struct Output {
+    stdout: io::Stdout,
+    cast: Option<Cast>,
+}
+
+struct Cast {
+    file: BufWriter<File>,
+    started: Instant,
+    /// 次のフラッシュで 1 イベントにまとめる出力
+    pending: Vec<u8>,
+}
+
+impl Output {
+    pub fn new(record: Option<&Path>) -> io::Result<Self> {
+        let cast = match record {
+            Some(path) => {
+                let mut file = BufWriter::new(File::create(path)?);
+                let (width, height) = crossterm::terminal::size()?;
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs());
+                writeln!(
+                    file,
+                    "{{\"version\": 2, \"width\": {width}, \"height\": {height}, \"timestamp\": {timestamp}, \"env\": {{\"TERM\": {}}}}}",
+                    json::quote(&std::env::var("TERM").unwrap_or_default())
+                )?;
+                Some(Cast {
+                    file,
+                    started: Instant::now(),
+                    pending: Vec::new(),
+                })
+            }
+            None => None,
+        };
+        Ok(Self {
+            stdout: io::stdout(),
+            cast,
+        })
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.stdout.write(buf)?;
+        if let Some(cast) = &mut self.cast {
+            cast.pending.extend_from_slice(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()?;
+        let Some(cast) = &mut self.cast else {
+            return Ok(());
+        };
+        if cast.pending.is_empty() {
+            return Ok(());
+        }
+        // 文字の途中で切れていても再生に困らないよう、壊れたバイトは置き換える
+        let data = String::from_utf8_lossy(&cast.pending);
+        writeln!(
+            cast.file,
+            "[{:.6}, \"o\", {}]",
+            cast.started.elapsed().as_secs_f64(),
+            json::quote(&data)
+        )?;
+        cast.pending.clear();
+        cast.file.flush()
+    }
+}
@@ -0,0 +1,119 @@
+// 自分が属する cgroup（v2）の CPU・メモリの上限と使用量
+use std::time::Instant;
+
+#[derive(Debug, Clone)]
+pub struct CgroupSnapshot {
+    /// /proc/self/cgroup に書かれたパス
+    pub path: String,
+    /// 使用中の CPU（コア数）。前回の値が無い初回は None
+    pub cpu_usage: Option<f64>,
+    /// cpu.max から求めた上限（コア数）
+    pub cpu_limit: Option<f64>,
+    /// 単位はバイト
+    pub memory_used: u64,
+    pub memory_limit: Option<u64>,
+}
+
+/// memory.max に対する使用量がこれ以上なら警告する
+pub const MEMORY_WARN_RATIO: f64 = 0.9;
+
+impl CgroupSnapshot {
+    pub fn memory_ratio(&self) -> Option<f64> {
+        self.memory_limit
+            .filter(|limit| *limit > 0)
+            .map(|limit| self.memory_used as f64 / limit as f64)
+    }
+
+    pub fn memory_near_limit(&self) -> bool {
+        self.memory_ratio()
+            .is_some_and(|ratio| ratio >= MEMORY_WARN_RATIO)
+    }
+}
+
+/// CPU 使用量を出すために前回の累計（µs）を覚えておく
+#[derive(Default)]
+pub struct CgroupReader {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    last_cpu: Option<(Instant, u64)>,
+}
+
+impl CgroupReader {
+    /// 上限が一つも無い（ホスト上で直接動いている）ときは None
+    #[cfg(target_os = "linux")]
+    pub fn collect(&mut self) -> Option<CgroupSnapshot> {
+        use std::fs;
+        use std::path::Path;
+
+        let root = Path::new("/sys/fs/cgroup");
+        // v2 の統合階層でなければ対象外
+        if !root.join("cgroup.controllers").exists() {
+            return None;
+        }
+        let text = fs::read_to_string("/proc/self/cgroup").ok()?;
+        let path = text
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))?
+            .trim()
+            .to_string();
+        let dir = root.join(path.trim_start_matches('/'));
+        let read = |dir: &Path, name: &str| fs::read_to_string(dir.join(name)).ok();
+
+        // 上限は祖先にも掛かるので、ルートまで遡って最も厳しいものを採る
+        let mut memory_limit: Option<u64> = None;
+        let mut cpu_limit: Option<f64> = None;
+        for ancestor in dir.ancestors().take_while(|d| d.starts_with(root)) {
+            if let Some(max) = read(ancestor, "memory.max").and_then(|s| s.trim().parse().ok()) {
+                memory_limit = Some(memory_limit.map_or(max, |m| m.min(max)));
+            }
+            if let Some(cores) = read(ancestor, "cpu.max").and_then(|s| parse_cpu_max(&s)) {
+                cpu_limit = Some(cpu_limit.map_or(cores, |c| c.min(cores)));
+            }
+        }
+        if memory_limit.is_none() && cpu_limit.is_none() {
+            self.last_cpu = None;
+            return None;
+        }
+
+        let memory_used = read(&dir, "memory.current")
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(0);
+        let usage_usec = read(&dir, "cpu.stat").and_then(|s| {
+            s.lines()
+                .find_map(|line| line.strip_prefix("usage_usec "))?
+                .trim()
+                .parse::<u64>()
+                .ok()
+        });
+        let now = Instant::now();
+        let cpu_usage = match (usage_usec, self.last_cpu) {
+            (Some(usage), Some((at, last))) => {
+                let secs = now.duration_since(at).as_secs_f64();
+                (secs > 0.0).then(|| usage.saturating_sub(last) as f64 / 1e6 / secs)
+            }
+            _ => None,
+        };
+        self.last_cpu = usage_usec.map(|usage| (now, usage));
+
+        Some(CgroupSnapshot {
+            path,
+            cpu_usage,
+            cpu_limit,
+            memory_used,
+            memory_limit,
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn collect(&mut self) -> Option<CgroupSnapshot> {
+        None
+    }
+}
+
+// "200000 100000" は 2 コア分、"max 100000" は無制限
+#[cfg(target_os = "linux")]
+fn parse_cpu_max(text: &str) -> Option<f64> {
+    let mut parts = text.split_whitespace();
+    let quota: f64 = parts.next()?.parse().ok()?;
+    let period: f64 = parts.next()?.parse().ok()?;
+    (period > 0.0).then(|| quota / period)
+}
@@ -0,0 +1,197 @@
+// cgroup（v2）の階層（s）
+//
+// systemd は system.slice / user.slice / machine.slice の下にサービスやコンテナを置くので、
+// /sys/fs/cgroup をたどって、グループごとの CPU（cpu.stat の usage_usec の差）とメモリ
+// （memory.current）、直接属するプロセス（cgroup.procs）を集める。CPU は 2 回目から分かる。
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
+
+use sysinfo::Pid;
+
+/// グループ 1 つ
+#[derive(Debug, Clone, PartialEq)]
+pub struct CgroupNode {
+    /// ルートからのパス（ルートは "/"）
+    pub path: String,
+    /// ルートが 0
+    pub depth: usize,
+    /// 使用率（%、1 コア = 100）。初回は None
+    pub cpu: Option<f64>,
+    /// バイト。ルートには無いので None
+    pub memory: Option<u64>,
+    /// このグループに直接属するプロセス
+    pub pids: Vec<Pid>,
+    pub has_children: bool,
+}
+
+impl CgroupNode {
+    pub fn name(&self) -> &str {
+        match self.path.rsplit_once('/') {
+            Some((_, "")) | None => "/",
+            Some((_, name)) => name,
+        }
+    }
+}
+
+/// 表に出す 1 行
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeRow {
+    /// nodes の位置
+    Group(usize),
+    /// 属するグループの位置と PID
+    Process(usize, Pid),
+}
+
+/// 階層と、CPU を出すための前回の累計
+#[derive(Debug)]
+pub struct CgroupTree {
+    root: PathBuf,
+    /// 親のすぐ後に子が名前の順で並ぶ
+    pub nodes: Vec<CgroupNode>,
+    last: Option<(Instant, HashMap<String, u64>)>,
+}
+
+impl CgroupTree {
+    /// この機械の階層を読む。v2 の統合階層でなければエラー
+    #[cfg(target_os = "linux")]
+    pub fn open() -> io::Result<Self> {
+        let root = Path::new("/sys/fs/cgroup");
+        if !root.join("cgroup.controllers").exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "the unified cgroup v2 hierarchy is not mounted",
+            ));
+        }
+        Ok(Self::read(root, Instant::now()))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn open() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the cgroup tree is only shown on Linux",
+        ))
+    }
+
+    pub fn read(root: &Path, now: Instant) -> Self {
+        let mut tree = Self {
+            root: root.to_path_buf(),
+            nodes: Vec::new(),
+            last: None,
+        };
+        tree.refresh(now);
+        tree
+    }
+
+    /// 読み直す。消えたグループは一覧から消え、新しいグループの CPU は次から出る
+    pub fn refresh(&mut self, now: Instant) {
+        let mut usage = HashMap::new();
+        let mut nodes = Vec::new();
+        walk(&self.root, "/", 0, &mut nodes, &mut usage);
+        if let Some((at, last)) = &self.last {
+            let secs = now.duration_since(*at).as_secs_f64();
+            for node in &mut nodes {
+                // µs の差を経過秒で割り、1 コア = 100% にする
+                node.cpu = match (usage.get(&node.path), last.get(&node.path)) {
+                    (Some(now), Some(before)) if secs > 0.0 => {
+                        Some(now.saturating_sub(*before) as f64 / 1e4 / secs)
+                    }
+                    _ => None,
+                };
+            }
+        }
+        self.nodes = nodes;
+        self.last = Some((now, usage));
+    }
+
+    /// 畳んだグループの子孫を除いて並べる。開いたグループには直接属するプロセスも続ける
+    pub fn rows(&self, collapsed: &HashSet<String>) -> Vec<TreeRow> {
+        let mut rows = Vec::new();
+        // 畳んだグループの深さ。これより深いものは飛ばす
+        let mut hidden_below: Option<usize> = None;
+        // 子を並べ終えたら、そのグループのプロセスを出す
+        let mut open: Vec<usize> = Vec::new();
+        let flush = |rows: &mut Vec<TreeRow>, open: &mut Vec<usize>, depth: usize| {
+            while let Some(&i) = open.last() {
+                if self.nodes[i].depth < depth {
+                    break;
+                }
+                open.pop();
+                rows.extend(
+                    self.nodes[i]
+                        .pids
+                        .iter()
+                        .map(|pid| TreeRow::Process(i, *pid)),
+                );
+            }
+        };
+        for (i, node) in self.nodes.iter().enumerate() {
+            if hidden_below.is_some_and(|depth| node.depth > depth) {
+                continue;
+            }
+            hidden_below = None;
+            flush(&mut rows, &mut open, node.depth);
+            rows.push(TreeRow::Group(i));
+            if collapsed.contains(&node.path) {
+                hidden_below = Some(node.depth);
+            } else {
+                open.push(i);
+            }
+        }
+        flush(&mut rows, &mut open, 0);
+        rows
+    }
+}
+
+// usage は後で CPU を出すためのパスごとの累計
+fn walk(
+    dir: &Path,
+    path: &str,
+    depth: usize,
+    nodes: &mut Vec<CgroupNode>,
+    usage: &mut HashMap<String, u64>,
+) {
+    let read = |name: &str| fs::read_to_string(dir.join(name)).ok();
+    if let Some(usec) = read("cpu.stat").and_then(|text| {
+        text.lines()
+            .find_map(|line| line.strip_prefix("usage_usec "))?
+            .trim()
+            .parse::<u64>()
+            .ok()
+    }) {
+        usage.insert(path.to_string(), usec);
+    }
+    let mut children: Vec<String> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    children.sort();
+    nodes.push(CgroupNode {
+        path: path.to_string(),
+        depth,
+        cpu: None,
+        memory: read("memory.current").and_then(|s| s.trim().parse().ok()),
+        pids: read("cgroup.procs")
+            .map(|text| {
+                text.lines()
+                    .filter_map(|l| Pid::from_str(l.trim()).ok())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        has_children: !children.is_empty(),
+    });
+    for child in children {
+        let child_path = match path {
+            "/" => format!("/{child}"),
+            _ => format!("{path}/{child}"),
+        };
+        walk(&dir.join(&child), &child_path, depth + 1, nodes, usage);
+    }
+}
@@ -0,0 +1,240 @@
+// コマンドライン引数の解析
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use sysinfo::Pid;
+
+use crate::app::SortKey;
+use crate::batch::Format;
+use crate::filter::Filter;
+use crate::log::Level;
+
+pub const USAGE: &str = "\
+Usage: rust-top [OPTIONS]
+       rust-top bench [-n <N>] [--processes <N,...>] [--baseline <FILE>] [OPTIONS]
+       rust-top batch [-f <FORMAT>] [-l <N>] [-c <COLUMN,...>] [OPTIONS]
+       rust-top history [OPTIONS]
+       rust-top --serve <ADDR>
+       rust-top --connect <ADDR>
+       rust-top --dashboard
+
+Commands:
+  bench                 Time collection, sorting/formatting and rendering
+                        without a terminal and print the results
+  batch                 Print the process table once and exit
+  history               Browse the snapshots saved to [history] path
+                        (needs the `history` feature)
+
+Options:
+  -p, --pid <PID,...>   Watch only the given processes and their children
+  -n, --iterations <N>  Number of rounds for bench (default: 20)
+      --processes <N,...>
+                        Bench made-up sets of N processes instead of this
+                        machine (default with --mock: 100,1000,10000)
+      --baseline <FILE> Compare bench with the times saved in FILE and
+                        fail if a stage got slower (saves FILE if missing)
+  -f, --format <FORMAT> Output of batch: text, json or csv (default: text)
+  -l, --limit <N>       Print at most N processes in batch (0 for all,
+                        default: all)
+  -c, --columns <COLUMN,...>
+                        Columns to print in batch (pid, user, category,
+                        name, container, state, wchan, thr, fds, cpu, history,
+                        memory, virt, shr, swap, minflt, majflt,
+                        node, rx, tx, time+, started, elapsed)
+      --filter <EXPR>   Show only matching processes, e.g.
+                        'cpu > 50 && user == \"postgres\"' or
+                        'name ~ \"python.*worker\"' (also :filter)
+      --serve <ADDR>    Send snapshots to clients connecting to ADDR
+                        (e.g. 0.0.0.0:7878) instead of showing the TUI
+      --connect <ADDR>  Show the snapshots of an agent started with --serve
+      --dashboard       Show a summary of the agents listed as [[host]]
+                        in the config file
+      --record <FILE>   Save the session as an asciicast (.cast) file
+      --doctor          Report which data sources are available and why
+                        the others are missing
+      --mock            Show a fixed set of made-up processes instead of
+                        this machine (for screenshots and benchmarks)
+      --ascii           Draw borders and symbols with ASCII and use 16
+                        colors, for serial consoles and old terminals
+                        (also [terminal] ascii)
+      --plain           Screen-reader friendly display: no borders, bars
+                        or graphs, symbols alongside colors, the cursor
+                        on the selected process and changes spelled out
+                        in the status line (also [terminal] plain)
+      --low-bandwidth   Redraw less often and report only mouse clicks,
+                        for slow SSH links (also [terminal] low_bandwidth)
+      --log-file <FILE> Append collection and action failures and panics
+                        to FILE (also [log] file)
+      --log-level <LEVEL>
+                        What to write to the log file: debug, info, warn
+                        or error (default: info, also [log] level)
+      --profile <NAME>  Start with the layout, columns, sort, thresholds
+                        and interval of [profiles.NAME] in the config
+                        file (also :profile)
+      --caffeinate      Keep the system from sleeping while rust-top runs
+                        (also :caffeinate)
+      --fresh           Start without restoring the sort, filter, layout,
+                        columns and interval saved when rust-top last quit
+      --summary         On quitting, print the session length, average and
+                        peak CPU and memory, the top 5 CPU consumers and
+                        the alerts that fired
+      --readonly        Refuse to kill, renice, freeze or change the
+                        affinity or I/O priority of processes and turn
+                        off [[watchdog]] and [[alert]] run, for wall
+                        monitors and kiosks
+  -h, --help            Print this help
+";
+
+#[derive(Debug, Default)]
+pub struct Args {
+    /// 監視対象として最初からピン留めする PID
+    pub pids: Vec<Pid>,
+    pub help: bool,
+    /// `bench` サブコマンド
+    pub bench: bool,
+    pub iterations: Option<usize>,
+    /// bench で計る作り物のプロセスの数
+    pub processes: Vec<usize>,
+    /// bench の結果を比べる（無ければ保存する）ファイル
+    pub baseline: Option<PathBuf>,
+    /// `batch` サブコマンド
+    pub batch: bool,
+    /// `history` サブコマンド
+    pub history: bool,
+    pub format: Format,
+    /// 0 なら全件
+    pub limit: usize,
+    /// 空なら既定の列
+    pub columns: Vec<SortKey>,
+    /// スナップショットを配信するアドレス
+    pub serve: Option<String>,
+    /// 接続するエージェントのアドレス
+    pub connect: Option<String>,
+    /// [[host]] の一覧を表示する
+    pub dashboard: bool,
+    /// 使えるデータソースを調べて表示する
+    pub doctor: bool,
+    /// 画面を asciicast として保存するファイル
+    pub record: Option<PathBuf>,
+    /// 最初から掛けておく絞り込み（:filter と同じ式）
+    pub filter: Option<Filter>,
+    /// この機械の代わりに決まった作り物の値を表示する
+    pub mock: bool,
+    /// 罫線や記号を ASCII で描き、色も 16 色にする
+    pub ascii: bool,
+    /// スクリーンリーダー向けに、罫線やグラフを描かず、変化を文章で知らせる
+    pub plain: bool,
+    /// 遅い回線向けに、描き直しとマウスの報告を減らす
+    pub low_bandwidth: bool,
+    /// 動かしている間システムをスリープさせない
+    pub caffeinate: bool,
+    /// 前回の表示の状態を戻さずに起動する
+    pub fresh: bool,
+    /// 起動したときに切り替える [profiles.<name>]
+    pub profile: Option<String>,
+    /// 終了したときに起動してからの統計を標準出力に書く
+    pub summary: bool,
+    /// プロセスを変える操作をすべて断り、watchdog も動かさない
+    pub readonly: bool,
+    /// 失敗やパニックを書くファイル
+    pub log_file: Option<PathBuf>,
+    pub log_level: Option<Level>,
+}
+
+pub fn parse() -> Result<Args, String> {
+    let mut iter = std::env::args().skip(1).peekable();
+    let mut args = Args::default();
+    match iter.peek().map(String::as_str) {
+        Some("bench") => args.bench = true,
+        Some("batch") => args.batch = true,
+        Some("history") => args.history = true,
+        _ => {}
+    }
+    if args.bench || args.batch || args.history {
+        iter.next();
+    }
+    while let Some(arg) = iter.next() {
+        // --name=value 形式も受け付ける
+        let (name, inline) = match arg.split_once('=') {
+            Some((n, v)) if n.starts_with("--") => (n.to_string(), Some(v.to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| {
+            inline
+                .clone()
+                .or_else(|| iter.next())
+                .ok_or_else(|| format!("{name} requires a value"))
+        };
+        match name.as_str() {
+            "-h" | "--help" => args.help = true,
+            "-p" | "--pid" => {
+                for pid in value(&name)?.split(',').filter(|s| !s.is_empty()) {
+                    let pid =
+                        Pid::from_str(pid.trim()).map_err(|_| format!("invalid PID `{pid}`"))?;
+                    args.pids.push(pid);
+                }
+            }
+            "-n" | "--iterations" => {
+                let n = value(&name)?;
+                match n.parse::<usize>() {
+                    Ok(n) if n > 0 => args.iterations = Some(n),
+                    _ => return Err(format!("invalid iteration count `{n}`")),
+                }
+            }
+            "--processes" => {
+                for n in value(&name)?.split(',').filter(|s| !s.is_empty()) {
+                    match n.trim().parse::<usize>() {
+                        Ok(n) if n > 0 => args.processes.push(n),
+                        _ => return Err(format!("invalid process count `{n}`")),
+                    }
+                }
+            }
+            "--baseline" => args.baseline = Some(PathBuf::from(value(&name)?)),
+            "-f" | "--format" => {
+                let format = value(&name)?;
+                args.format = Format::from_name(&format)
+                    .ok_or_else(|| format!("unknown format `{format}`"))?;
+            }
+            "-l" | "--limit" => {
+                let n = value(&name)?;
+                args.limit = n.parse().map_err(|_| format!("invalid limit `{n}`"))?;
+            }
+            "-c" | "--columns" => {
+                for column in value(&name)?.split(',').filter(|s| !s.is_empty()) {
+                    let key = SortKey::from_name(column.trim())
+                        .ok_or_else(|| format!("unknown column `{column}`"))?;
+                    args.columns.push(key);
+                }
+            }
+            "--serve" => args.serve = Some(value(&name)?),
+            "--connect" => args.connect = Some(value(&name)?),
+            "--dashboard" => args.dashboard = true,
+            "--doctor" => args.doctor = true,
+            "--mock" => args.mock = true,
+            "--ascii" => args.ascii = true,
+            "--plain" => args.plain = true,
+            "--low-bandwidth" => args.low_bandwidth = true,
+            "--caffeinate" => args.caffeinate = true,
+            "--fresh" => args.fresh = true,
+            "--profile" => args.profile = Some(value(&name)?),
+            "--summary" => args.summary = true,
+            "--readonly" => args.readonly = true,
+            "--record" => args.record = Some(PathBuf::from(value(&name)?)),
+            "--log-file" => args.log_file = Some(PathBuf::from(value(&name)?)),
+            "--log-level" => {
+                let level = value(&name)?;
+                args.log_level = Some(
+                    Level::from_name(&level)
+                        .ok_or_else(|| format!("unknown log level `{level}`"))?,
+                );
+            }
+            "--filter" => {
+                let expr = value(&name)?;
+                args.filter =
+                    Some(Filter::parse(&expr).map_err(|e| format!("invalid filter: {e}"))?);
+            }
+            _ => return Err(format!("unknown option `{arg}`")),
+        }
+    }
+    Ok(args)
+}
@@ -0,0 +1,136 @@
+// 選択したプロセスの情報をクリップボードに写す（y / Y / :yank）
+//
+// SSH の先では端末の OSC 52 で送り、手元のクリップボードに入れる。
+// 手元では OSC 52 を無視する端末も多いので、pbcopy などがあればそちらを使う。
+// （tmux では set-clipboard を on にしておく）
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use crate::sampler::ProcessInfo;
+use crate::term;
+
+/// 何を写すか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Yank {
+    Pid,
+    /// コマンドライン全体
+    Command,
+    /// PID・ユーザー・名前・CPU・メモリ・コマンドラインを 1 行に
+    Summary,
+}
+
+impl Yank {
+    pub const NAMES: [&'static str; 3] = ["pid", "command", "summary"];
+
+    pub fn from_name(name: &str) -> Option<Yank> {
+        match name {
+            "pid" => Some(Yank::Pid),
+            "command" => Some(Yank::Command),
+            "summary" => Some(Yank::Summary),
+            _ => None,
+        }
+    }
+
+    /// 「Copied ... to the clipboard」に入れる言葉
+    pub fn label(self) -> &'static str {
+        match self {
+            Yank::Pid => "PID",
+            Yank::Command => "command line",
+            Yank::Summary => "summary",
+        }
+    }
+
+    pub fn text(self, process: &ProcessInfo) -> String {
+        // カーネルスレッドにはコマンドラインが無いので、ps と同じく名前を [] で囲む
+        let cmd = if process.cmd.is_empty() {
+            format!("[{}]", process.name)
+        } else {
+            process.cmd.to_string()
+        };
+        match self {
+            Yank::Pid => process.pid.to_string(),
+            Yank::Command => cmd,
+            Yank::Summary => format!(
+                "{} {} {} CPU {:.1}% MEM {:.1} MiB {cmd}",
+                process.pid,
+                process.user,
+                process.name,
+                process.cpu_usage,
+                process.memory as f64 / (1024.0 * 1024.0)
+            ),
+        }
+    }
+}
+
+/// SSH 越しなら OSC 52、手元ならクリップボードのコマンド（無ければ OSC 52）で写す
+pub fn copy(out: &mut impl Write, text: &str) -> io::Result<()> {
+    if !term::over_ssh() && native(text).is_ok() {
+        return Ok(());
+    }
+    osc52(out, text)
+}
+
+// 最初に見つかったコマンドに標準入力で渡す
+fn native(text: &str) -> io::Result<()> {
+    let candidates: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else if cfg!(windows) {
+        &[("clip", &[])]
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        &[("wl-copy", &[])]
+    } else if std::env::var_os("DISPLAY").is_some() {
+        &[("xclip", &["-selection", "clipboard"]), ("xsel", &["-ib"])]
+    } else {
+        &[("termux-clipboard-set", &[])]
+    };
+    let mut last = io::Error::from(io::ErrorKind::NotFound);
+    for (program, args) in candidates {
+        let child = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                last = e;
+                continue;
+            }
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            return Ok(());
+        }
+        last = io::Error::other(format!("{program} failed"));
+    }
+    Err(last)
+}
+
+/// OSC 52 で端末に送る
+pub fn osc52(out: &mut impl Write, text: &str) -> io::Result<()> {
+    write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    out.flush()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        // 3 バイトに満たない最後のまとまりは = で埋める
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,94 @@
+// macOS のサンドボックスとコード署名の情報
+//
+// 署名は codesign コマンドで実行ファイルを調べる。時間がかかるので、
+// 同じプロセスについては最初の結果を使い回す。
+use std::ffi::{CStr, c_char, c_int};
+use std::process::Command;
+use std::sync::Mutex;
+
+use sysinfo::Pid;
+
+#[derive(Debug, Clone, Default)]
+pub struct SigningInfo {
+    /// App Sandbox の中で動いているか（調べられなければ None）
+    pub sandboxed: Option<bool>,
+    /// Hardened Runtime を有効にして署名されているか
+    pub hardened: Option<bool>,
+    /// 署名者（Authority の先頭）。アドホック署名なら "ad-hoc"
+    pub identity: Option<String>,
+}
+
+unsafe extern "C" {
+    // libsystem_sandbox。operation に null を渡すとサンドボックス下かどうかを返す
+    fn sandbox_check(pid: libc::pid_t, operation: *const c_char, filter: c_int, ...) -> c_int;
+}
+
+/// 直前に調べたプロセス（PID と開始時刻）とその結果
+static CACHE: Mutex<Option<(Pid, u64, SigningInfo)>> = Mutex::new(None);
+
+pub fn signing_info(pid: Pid, start_time: u64) -> SigningInfo {
+    let mut cache = CACHE.lock().expect("signing cache lock");
+    if let Some((cached, started, info)) = cache.as_ref()
+        && *cached == pid
+        && *started == start_time
+    {
+        return info.clone();
+    }
+    let info = inspect(pid);
+    *cache = Some((pid, start_time, info.clone()));
+    info
+}
+
+fn inspect(pid: Pid) -> SigningInfo {
+    let raw = pid.as_u32() as libc::pid_t;
+    // SAFETY: 可変長引数は渡さず、operation は null
+    let sandboxed = unsafe { sandbox_check(raw, std::ptr::null(), 0) };
+    let mut info = SigningInfo {
+        sandboxed: (sandboxed >= 0).then_some(sandboxed > 0),
+        ..SigningInfo::default()
+    };
+    let Some(path) = executable(raw) else {
+        return info;
+    };
+    // codesign は情報を標準エラーに出す
+    let Ok(output) = Command::new("codesign")
+        .args(["-d", "--verbose=2", &path])
+        .output()
+    else {
+        return info;
+    };
+    let text = String::from_utf8_lossy(&output.stderr);
+    if text.contains("not signed at all") {
+        info.hardened = Some(false);
+        info.identity = Some("unsigned".to_string());
+        return info;
+    }
+    for line in text.lines() {
+        if let Some(flags) = line
+            .strip_prefix("CodeDirectory ")
+            .and_then(|l| l.split_whitespace().find(|f| f.starts_with("flags=")))
+        {
+            info.hardened = Some(flags.contains("runtime"));
+        } else if let Some(authority) = line.strip_prefix("Authority=")
+            && info.identity.is_none()
+        {
+            info.identity = Some(authority.to_string());
+        } else if line == "Signature=adhoc" {
+            info.identity = Some("ad-hoc".to_string());
+        }
+    }
+    info
+}
+
+// 実行ファイルのパス
+fn executable(pid: libc::pid_t) -> Option<String> {
+    let mut buffer = [0 as c_char; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    // SAFETY: バッファの大きさを渡し、成功時は NUL 終端された文字列が入る
+    let len = unsafe { libc::proc_pidpath(pid, buffer.as_mut_ptr().cast(), buffer.len() as u32) };
+    if len <= 0 {
+        return None;
+    }
+    // SAFETY: 成功時は NUL 終端されている
+    let path = unsafe { CStr::from_ptr(buffer.as_ptr()) };
+    Some(path.to_string_lossy().into_owned())
+}
@@ -0,0 +1,1537 @@
+// 設定ファイル（~/.config/rust-top/config.toml）の読み込み
+pub mod parser;
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use std::{fs, io};
+
+use parser::{Document, Table};
+use ratatui::layout::Alignment;
+use sysinfo::{Pid, Signal};
+
+use crate::actions;
+use crate::alert::{self, AlertRule, Metric};
+use crate::app::SortKey;
+use crate::i18n::Language;
+use crate::layout::{self, ScreenLayout, SideColumn};
+use crate::log::Level;
+use crate::pattern::Pattern;
+use crate::state;
+use crate::term::ColorDepth;
+use crate::theme::{self, Theme, Threshold};
+use crate::watchdog::WatchdogRule;
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub refresh: RefreshConfig,
+    pub format: FormatConfig,
+    pub theme: Theme,
+    /// [[theme.threshold]] 使用率ごとの見た目（above の小さい順）
+    pub thresholds: Vec<Threshold>,
+    /// [profiles.<name>] --profile か :profile でまとめて切り替える表示（名前の順）
+    pub profiles: Vec<Profile>,
+    pub filter: FilterConfig,
+    /// [[preset]] 数字キーで切り替える絞り込み
+    pub presets: Vec<FilterPreset>,
+    /// [protect] シグナルを送る前に確認するプロセス
+    pub protect: ProtectConfig,
+    /// [[alert]] で定義した警告の条件
+    pub alerts: Vec<AlertRule>,
+    /// [[watchdog]] 自動でシグナルを送る条件
+    pub watchdogs: Vec<WatchdogRule>,
+    /// [columns.<name>] で指定したプロセス表の列
+    pub columns: HashMap<SortKey, ColumnConfig>,
+    /// [noisy] ビルドや負荷試験などのプロセス
+    pub noisy: NoisyConfig,
+    /// [categories] ブラウザやシェルなどのプロセスの種類
+    pub categories: CategoryConfig,
+    /// [sort] then。選んだ列が同じ値の行を、この列の順で並べる（最後は PID）
+    pub sort_then: Vec<SortKey>,
+    /// [compat] termux。None なら環境から判定する
+    pub termux: Option<bool>,
+    /// [pin] on_exit。ピン留めしたプロセスの終了やタイマーの知らせ方（None ならベル）
+    pub on_exit: Option<Vec<ExitNotice>>,
+    /// [[host]] で定義した --dashboard の接続先
+    pub hosts: Vec<HostConfig>,
+    /// [keys] keymap
+    pub keymap: Keymap,
+    /// [follow] restarts。追いかけているプロセスが終了したら、同じ名前とコマンドラインのものに移る
+    pub follow_restarts: bool,
+    /// [processes] rows。プロセス表に出す行数（None なら空いた高さいっぱい）
+    pub process_rows: Option<usize>,
+    /// [[panel]] コマンドの出力やファイルの値を表示するパネル
+    pub panels: Vec<PanelConfig>,
+    /// [layouts] で書いたレイアウト（組み込みのものに足す）
+    pub layouts: Vec<ScreenLayout>,
+    /// [layouts] start。起動したときのレイアウトの名前（None なら default）
+    pub layout: Option<String>,
+    /// [layouts] side。広い端末で Info とセンサーを右の列に移すか
+    pub side_column: SideColumn,
+    /// [terminal] colors。None なら環境から判定する
+    pub colors: Option<ColorDepth>,
+    /// [terminal] ascii。None なら TERM とロケールから判定する
+    pub ascii: Option<bool>,
+    /// [terminal] plain。スクリーンリーダー向けの表示にする
+    pub plain: bool,
+    /// [terminal] low_bandwidth。遅い回線向けに描き直しとマウスの報告を減らす
+    pub low_bandwidth: bool,
+    /// [events] keep。出来事の記録（L）に残す件数（None なら 1000 件）
+    pub events_keep: Option<usize>,
+    #[cfg(feature = "history")]
+    pub history: HistoryConfig,
+    #[cfg(feature = "metrics")]
+    pub metrics: MetricsConfig,
+    #[cfg(feature = "mqtt")]
+    pub mqtt: MqttConfig,
+    /// [leak] 増え続けるメモリの検出
+    pub leak: LeakConfig,
+    /// [idle] 操作も負荷も無いあいだ更新間隔を延ばす
+    pub idle: IdleConfig,
+    /// [log] file。収集や操作の失敗とパニックを書くファイル（--log-file が優先）
+    pub log_file: Option<PathBuf>,
+    /// [log] level
+    pub log_level: Option<Level>,
+    /// [journal] file。J で journalctl の代わりに読むログファイル
+    #[cfg(feature = "journal")]
+    pub journal_file: Option<PathBuf>,
+    /// [profile] R で選んだプロセスを採るときの perf と長さ
+    #[cfg(feature = "profile")]
+    pub profile: ProfileConfig,
+    /// [[plugin]] 起動しておく外部のプラグイン
+    #[cfg(feature = "plugin")]
+    pub plugins: Vec<PluginConfig>,
+}
+
+/// [[plugin]] スナップショットを渡して、列・パネル・警告を返してもらうコマンド
+#[cfg(feature = "plugin")]
+#[derive(Debug, Clone)]
+pub struct PluginConfig {
+    pub name: String,
+    pub command: String,
+}
+
+/// [history] スナップショットの保存先と間隔
+#[cfg(feature = "history")]
+#[derive(Debug, Clone)]
+pub struct HistoryConfig {
+    /// 無ければ保存しない
+    pub path: Option<PathBuf>,
+    pub interval: Duration,
+    /// これより古いものは消す
+    pub keep: Duration,
+}
+
+/// [metrics] 値を送る先と形式
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    /// udp://host:port、tcp://host:port かファイルのパス。無ければ送らない
+    pub target: Option<String>,
+    pub format: MetricsFormat,
+    /// StatsD の名前の頭、InfluxDB の measurement
+    pub prefix: String,
+    pub interval: Duration,
+    /// CPU 上位の何プロセス分を送るか
+    pub processes: usize,
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsFormat {
+    Statsd,
+    /// InfluxDB の line protocol
+    Influx,
+}
+
+#[cfg(feature = "metrics")]
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            target: None,
+            format: MetricsFormat::Statsd,
+            prefix: "rust_top".to_string(),
+            interval: Duration::from_secs(10),
+            processes: 0,
+        }
+    }
+}
+
+/// [leak] RSS が増え続けるプロセスをリークの疑いとして知らせる
+#[derive(Debug, Clone)]
+pub struct LeakConfig {
+    pub enabled: bool,
+    /// この長さのあいだ増え続けたら疑う
+    pub window: Duration,
+    /// 1 分あたりにこれだけ（バイト）以上増えていたら疑う
+    pub growth: u64,
+}
+
+impl Default for LeakConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window: Duration::from_secs(600),
+            growth: 1024 * 1024,
+        }
+    }
+}
+
+/// [profile] perf record で選んだプロセスを採る
+#[cfg(feature = "profile")]
+#[derive(Debug, Clone)]
+pub struct ProfileConfig {
+    /// perf のコマンド（Debian の perf_6.1 など、名前に版が付いていることがある）
+    pub perf: String,
+    pub duration: Duration,
+}
+
+#[cfg(feature = "profile")]
+impl Default for ProfileConfig {
+    fn default() -> Self {
+        Self {
+            perf: "perf".to_string(),
+            duration: Duration::from_secs(5),
+        }
+    }
+}
+
+/// [idle] キーが押されず CPU も空いているあいだ、収集の間隔を延ばしてノート PC の電池を持たせる
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdleConfig {
+    pub enabled: bool,
+    /// 入力が無く、CPU がしきい値を下回ってからこれだけ経ったら延ばす
+    pub after: Duration,
+    /// 全体の CPU 使用率（%）がこれ以上なら止まっていない
+    pub cpu: f64,
+    /// 延ばした間隔。設定の間隔のほうが長ければそちらのまま
+    pub interval: Duration,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            after: Duration::from_secs(30),
+            cpu: 10.0,
+            interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// [mqtt] 値を publish するブローカー
+#[cfg(feature = "mqtt")]
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    /// host:port（ポートを省くと 1883）。無ければ publish しない
+    pub broker: Option<String>,
+    /// トピックの頭。"{topic}/cpu" などに publish する
+    pub topic: String,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub interval: Duration,
+    /// 購読し始めた側がすぐ最後の値を受け取れるよう retain を付ける
+    pub retain: bool,
+}
+
+#[cfg(feature = "mqtt")]
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            broker: None,
+            topic: "rust-top".to_string(),
+            client_id: format!("rust-top-{}", std::process::id()),
+            username: None,
+            password: None,
+            interval: Duration::from_secs(10),
+            retain: true,
+        }
+    }
+}
+
+#[cfg(feature = "history")]
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            interval: Duration::from_secs(60),
+            keep: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitNotice {
+    /// 端末のベル
+    Bell,
+    /// 画面を一瞬反転する
+    Flash,
+    /// デスクトップ通知
+    Desktop,
+}
+
+impl ExitNotice {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bell" => Some(ExitNotice::Bell),
+            "flash" => Some(ExitNotice::Flash),
+            "desktop" => Some(ExitNotice::Desktop),
+            _ => None,
+        }
+    }
+}
+
+/// キー操作の割り当て
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Keymap {
+    #[default]
+    Default,
+    /// j / k / gg / G / Ctrl-d / Ctrl-u で移動する。k（シグナル）は x、g（まとめる）は z に移す
+    Vim,
+}
+
+impl Keymap {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Keymap::Default),
+            "vim" => Some(Keymap::Vim),
+            _ => None,
+        }
+    }
+}
+
+/// --serve で動いているエージェントの接続先
+#[derive(Debug, Clone)]
+pub struct HostConfig {
+    pub name: String,
+    pub addr: String,
+}
+
+/// [[panel]] で定義したパネル
+#[derive(Debug, Clone)]
+pub struct PanelConfig {
+    pub title: String,
+    pub source: PanelSource,
+    pub interval: Duration,
+    pub kind: PanelKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum PanelSource {
+    /// sh -c で実行した標準出力
+    Command(String),
+    /// ファイルの中身（/sys/class/hwmon/... など）
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub enum PanelKind {
+    /// 出力をそのまま表示する
+    Text,
+    /// 出力の最初の数値に scale を掛け、max を満杯とした棒で表示する
+    Gauge { max: f64, scale: f64, unit: String },
+}
+
+/// プロセス表の列の見た目。未指定の項目は列ごとの既定値を使う
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ColumnConfig {
+    /// 列の幅（見出しが収まる幅より狭くはしない）
+    pub min_width: Option<u16>,
+    pub align: Option<Alignment>,
+}
+
+/// 常に適用するプロセスの絞り込み。名前かコマンドラインに一致するかで判定する
+#[derive(Debug, Clone, Default)]
+pub struct FilterConfig {
+    /// 空でなければ、いずれかに一致するプロセスだけを表示する
+    pub include: Vec<Pattern>,
+    /// いずれかに一致するプロセスは表示しない
+    pub exclude: Vec<Pattern>,
+}
+
+impl FilterConfig {
+    pub fn allows(&self, name: &str, cmd: &str) -> bool {
+        let matches = |p: &Pattern| p.is_match(name) || p.is_match(cmd);
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+/// 数字キーで切り替える絞り込み。[filter] に重ねて適用する
+#[derive(Debug, Clone)]
+pub struct FilterPreset {
+    /// 1 から 9
+    pub key: char,
+    pub name: String,
+    pub filter: FilterConfig,
+    pub user: Option<String>,
+}
+
+impl FilterPreset {
+    pub fn allows(&self, name: &str, cmd: &str, user: &str) -> bool {
+        self.filter.allows(name, cmd) && self.user.as_deref().is_none_or(|u| u == user)
+    }
+}
+
+/// [profiles.<name>] レイアウト・列・並び順・閾値・更新間隔の組。書いていないものは変えない
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub layout: Option<String>,
+    /// 出す列（状態ファイルと同じ command / memory / wchan などの名前）
+    pub columns: Option<Vec<String>>,
+    pub sort: Option<SortKey>,
+    /// [[profiles.<name>.threshold]]（above の小さい順）
+    pub thresholds: Option<Vec<Threshold>>,
+    pub interval: Option<Duration>,
+}
+
+/// シグナルを送る前に名前の入力を求めるプロセス
+#[derive(Debug, Clone)]
+pub struct ProtectConfig {
+    pub pids: Vec<Pid>,
+    /// 名前に一致するかで判定する（コマンドラインは見ない）
+    pub names: Vec<Pattern>,
+}
+
+impl Default for ProtectConfig {
+    fn default() -> Self {
+        Self {
+            pids: vec![Pid::from(1)],
+            names: Vec::new(),
+        }
+    }
+}
+
+impl ProtectConfig {
+    pub fn protects(&self, pid: Pid, name: &str) -> bool {
+        self.pids.contains(&pid) || self.names.iter().any(|p| p.is_match(name))
+    }
+}
+
+/// [noisy] 負荷をかけるのが仕事のプロセス（ビルド・負荷試験・バックアップ）。
+/// 表では名前の前にラベルを付け、異常な負荷と見分けられるようにする
+#[derive(Debug, Clone)]
+pub struct NoisyConfig {
+    /// ラベルと、名前に一致させるパターン
+    pub kinds: Vec<(&'static str, Vec<Pattern>)>,
+    /// Top consumers の集計から外す
+    pub exclude_from_top: bool,
+}
+
+/// [noisy] の種類と、既定のパターン（設定ファイルで同じ名前を書くと置き換える）
+const NOISY_KINDS: [(&str, &str); 3] = [
+    (
+        "build",
+        "^cc1|^gcc|^g\\+\\+|^clang|^rustc$|^ld$|^ld\\.|^lld$|^mold$|^javac$|^compile$|^cargo$|^make$|^ninja$",
+    ),
+    ("stress", "^stress|^fio$|^sysbench$|^dd$|^memtester$|^iperf"),
+    (
+        "backup",
+        "^restic$|^borg$|^duplicity$|^duplicati|^rsnapshot$|^rdiff-backup$|^bacula-fd$|^veeam",
+    ),
+];
+
+impl Default for NoisyConfig {
+    fn default() -> Self {
+        Self {
+            kinds: NOISY_KINDS
+                .iter()
+                .map(|&(label, source)| {
+                    let pattern = Pattern::new(source).expect("built-in noisy pattern");
+                    (label, vec![pattern])
+                })
+                .collect(),
+            exclude_from_top: false,
+        }
+    }
+}
+
+impl NoisyConfig {
+    /// 名前が一致した種類のラベル
+    pub fn kind(&self, name: &str) -> Option<&'static str> {
+        self.kinds
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|p| p.is_match(name)))
+            .map(|(label, _)| *label)
+    }
+}
+
+/// [categories] プロセスの種類。表の Category 列（d）に短い名前かアイコンを出し、:category で絞り込む
+#[derive(Debug, Clone)]
+pub struct CategoryConfig {
+    /// 先に書いたものから調べる。設定ファイルで足した種類は組み込みのものより先
+    pub kinds: Vec<Category>,
+    /// [categories] icons。短い名前の代わりに Nerd Font のアイコンを出す
+    pub icons: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Category {
+    pub name: String,
+    /// 表に出す短い名前
+    pub tag: String,
+    /// Nerd Font のアイコン（設定ファイルで足した種類には無い）
+    pub icon: Option<char>,
+    /// 名前に一致させるパターン
+    pub patterns: Vec<Pattern>,
+    /// カーネルのスレッドもこの種類にする
+    pub kernel_threads: bool,
+}
+
+impl Category {
+    /// 表に出す印。アイコンが無ければ短い名前
+    pub fn label(&self, icons: bool) -> String {
+        match self.icon {
+            Some(icon) if icons => icon.to_string(),
+            _ => self.tag.clone(),
+        }
+    }
+}
+
+/// [categories] の種類・短い名前・アイコン・既定のパターン（設定ファイルで同じ名前を書くと置き換える）
+const CATEGORIES: [(&str, &str, char, &str); 6] = [
+    (
+        "browser",
+        "web",
+        '\u{f0ac}',
+        "^firefox|^chrome|^chromium|^brave|^msedge|^opera|^vivaldi|^Safari$|^librewolf|^epiphany|^Web Content$|^WebKitWebProces|^Isolated Web Co",
+    ),
+    (
+        "compiler",
+        "cc",
+        '\u{f085}',
+        "^cc1|^gcc|^g\\+\\+|^clang|^rustc$|^ld$|^ld\\.|^lld$|^mold$|^javac$|^go$|^tsc$|^swiftc$|^ghc$|^cl\\.exe$",
+    ),
+    (
+        "database",
+        "db",
+        '\u{f1c0}',
+        "^postgres|^mysqld|^mariadbd|^mongod|^redis-server|^sqlite3$|^clickhouse|^cockroach|^etcd$|^memcached$|^influxd$|^sqlservr",
+    ),
+    (
+        "container",
+        "ctr",
+        '\u{f308}',
+        "^dockerd$|^containerd|^runc$|^crun$|^podman$|^conmon$|^crio$|^kubelet$|^buildkitd$|^lxc|^k3s",
+    ),
+    (
+        "shell",
+        "sh",
+        '\u{f120}',
+        "^bash$|^zsh$|^fish$|^sh$|^dash$|^ksh|^tcsh$|^csh$|^nu$|^xonsh$|^pwsh|^powershell|^cmd\\.exe$",
+    ),
+    // カーネルのスレッドはパターンに関係なくこの種類にする
+    ("kernel", "kern", '\u{f2db}', ""),
+];
+
+impl Default for CategoryConfig {
+    fn default() -> Self {
+        Self {
+            kinds: CATEGORIES
+                .iter()
+                .map(|&(name, tag, icon, source)| Category {
+                    name: name.to_string(),
+                    tag: tag.to_string(),
+                    icon: Some(icon),
+                    patterns: (!source.is_empty())
+                        .then(|| Pattern::new(source).expect("built-in category pattern"))
+                        .into_iter()
+                        .collect(),
+                    kernel_threads: name == "kernel",
+                })
+                .collect(),
+            icons: false,
+        }
+    }
+}
+
+impl CategoryConfig {
+    /// 名前が一致した種類
+    pub fn classify(&self, name: &str, kernel_thread: bool) -> Option<&Category> {
+        self.kinds.iter().find(|c| {
+            (kernel_thread && c.kernel_threads) || c.patterns.iter().any(|p| p.is_match(name))
+        })
+    }
+}
+
+/// 数値・時刻の書式。未指定の項目はロケールから決める
+#[derive(Debug, Clone, Default)]
+pub struct FormatConfig {
+    pub locale: Option<String>,
+    pub thousands_separator: Option<String>,
+    pub decimal_separator: Option<char>,
+    pub hour24: Option<bool>,
+    /// 画面の文言の言語。None ならロケールから決める
+    pub language: Option<Language>,
+}
+
+/// データソースごとの更新間隔
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshConfig {
+    pub cpu: Duration,
+    pub memory: Duration,
+    pub processes: Duration,
+    pub info: Duration,
+    pub battery: Duration,
+    pub cgroup: Duration,
+    pub pressure: Duration,
+    pub soc: Duration,
+    pub sensors: Duration,
+    #[cfg(feature = "gpu")]
+    pub gpu: Duration,
+    #[cfg(feature = "ipmi")]
+    pub ipmi: Duration,
+    #[cfg(feature = "smart")]
+    pub smart: Duration,
+    #[cfg(feature = "apple")]
+    pub apple: Duration,
+    #[cfg(feature = "net")]
+    pub connections: Duration,
+    #[cfg(any(feature = "systemd", windows))]
+    pub services: Duration,
+}
+
+impl Default for RefreshConfig {
+    fn default() -> Self {
+        Self {
+            cpu: Duration::from_secs(1),
+            memory: Duration::from_secs(1),
+            processes: Duration::from_secs(1),
+            info: Duration::from_secs(1),
+            battery: Duration::from_secs(5),
+            cgroup: Duration::from_secs(1),
+            pressure: Duration::from_secs(2),
+            soc: Duration::from_secs(2),
+            sensors: Duration::from_secs(1),
+            // nvidia-smi の起動は重いので間隔を空ける
+            #[cfg(feature = "gpu")]
+            gpu: Duration::from_secs(2),
+            #[cfg(feature = "ipmi")]
+            ipmi: Duration::from_secs(10),
+            // ディスクごとに smartctl を起動し、値もめったに変わらないので間隔を大きく空ける
+            #[cfg(feature = "smart")]
+            smart: Duration::from_secs(60),
+            // root なら powermetrics を動かすので間隔を空ける
+            #[cfg(feature = "apple")]
+            apple: Duration::from_secs(2),
+            // 全プロセスの fd をたどるので間隔を空ける
+            #[cfg(feature = "net")]
+            connections: Duration::from_secs(2),
+            #[cfg(any(feature = "systemd", windows))]
+            services: Duration::from_secs(2),
+        }
+    }
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(base.join("rust-top").join("config.toml"))
+}
+
+/// 設定ファイルを読み込む。ファイルが無ければデフォルト値を返す。
+pub fn load() -> io::Result<Config> {
+    match config_path() {
+        Some(path) => read(&path),
+        None => Ok(Config::default()),
+    }
+}
+
+fn read(path: &Path) -> io::Result<Config> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e),
+    };
+    let doc = parser::parse(&text).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {e}", path.display()),
+        )
+    })?;
+    from_document(&doc).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {e}", path.display()),
+        )
+    })
+}
+
+/// 設定ファイルが書き換わったかを見る間隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 設定ファイルの変更を見張り、変わったら読み直す
+///
+/// 依存を増やさないよう、ファイルの通知ではなく更新時刻と大きさを WATCH_INTERVAL ごとに見る。
+pub struct ConfigWatcher {
+    path: PathBuf,
+    /// 最後に見た（更新時刻、大きさ）。ファイルが無ければ None
+    seen: Option<(SystemTime, u64)>,
+    checked: Instant,
+}
+
+impl ConfigWatcher {
+    pub fn new() -> Option<Self> {
+        Self::watch(config_path()?)
+    }
+
+    pub fn watch(path: PathBuf) -> Option<Self> {
+        Some(Self {
+            seen: stamp(&path),
+            path,
+            checked: Instant::now(),
+        })
+    }
+
+    /// 前に見てから書き換わっていれば読み直した結果。書き換わっていなければ None
+    pub fn poll(&mut self) -> Option<io::Result<Config>> {
+        if self.checked.elapsed() < WATCH_INTERVAL {
+            return None;
+        }
+        self.checked = Instant::now();
+        let seen = stamp(&self.path);
+        if seen == self.seen {
+            return None;
+        }
+        self.seen = seen;
+        Some(read(&self.path))
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn from_document(doc: &Document) -> Result<Config, String> {
+    let mut config = Config::default();
+    if let Some(table) = doc.table("refresh") {
+        let refresh = &mut config.refresh;
+        read_interval(table, "cpu", &mut refresh.cpu)?;
+        read_interval(table, "memory", &mut refresh.memory)?;
+        read_interval(table, "processes", &mut refresh.processes)?;
+        read_interval(table, "info", &mut refresh.info)?;
+        read_interval(table, "battery", &mut refresh.battery)?;
+        read_interval(table, "cgroup", &mut refresh.cgroup)?;
+        read_interval(table, "pressure", &mut refresh.pressure)?;
+        read_interval(table, "soc", &mut refresh.soc)?;
+        read_interval(table, "sensors", &mut refresh.sensors)?;
+        #[cfg(feature = "gpu")]
+        read_interval(table, "gpu", &mut refresh.gpu)?;
+        #[cfg(feature = "ipmi")]
+        read_interval(table, "ipmi", &mut refresh.ipmi)?;
+        #[cfg(feature = "smart")]
+        read_interval(table, "smart", &mut refresh.smart)?;
+        #[cfg(feature = "apple")]
+        read_interval(table, "apple", &mut refresh.apple)?;
+        #[cfg(feature = "net")]
+        read_interval(table, "connections", &mut refresh.connections)?;
+        #[cfg(any(feature = "systemd", windows))]
+        read_interval(table, "services", &mut refresh.services)?;
+    }
+    if let Some(table) = doc.table("format") {
+        let format = &mut config.format;
+        format.locale = read_string(table, "format", "locale")?;
+        format.thousands_separator = read_string(table, "format", "thousands_separator")?;
+        if let Some(sep) = read_string(table, "format", "decimal_separator")? {
+            let mut chars = sep.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => format.decimal_separator = Some(c),
+                _ => return Err("format.decimal_separator must be one character".to_string()),
+            }
+        }
+        if let Some(name) = read_string(table, "format", "language")? {
+            format.language = Some(Language::from_name(&name).ok_or(format!(
+                "format.language must be \"en\" or \"ja\", not \"{name}\""
+            ))?);
+        }
+        format.hour24 = match read_string(table, "format", "clock")?.as_deref() {
+            None => None,
+            Some("24h") => Some(true),
+            Some("12h") => Some(false),
+            Some(other) => {
+                return Err(format!(
+                    "format.clock must be \"12h\" or \"24h\", not \"{other}\""
+                ));
+            }
+        };
+    }
+    if let Some(table) = doc.table("theme")
+        && let Some(name) = read_string(table, "theme", "name")?
+    {
+        config.theme = theme::by_name(&name).ok_or_else(|| {
+            let names: Vec<_> = theme::THEMES.iter().map(|t| t.name).collect();
+            format!(
+                "theme.name must be one of {}, not \"{name}\"",
+                names.join(", ")
+            )
+        })?;
+    }
+    for (i, table) in doc
+        .arrays
+        .get("theme.threshold")
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        config
+            .thresholds
+            .push(read_threshold(table, &format!("theme.threshold[{i}]"))?);
+    }
+    config
+        .thresholds
+        .sort_by(|a, b| a.above.total_cmp(&b.above));
+    for key in SortKey::COLUMNS {
+        let name = key.label().to_ascii_lowercase();
+        let section = format!("columns.{name}");
+        let Some(table) = doc.table(&section) else {
+            continue;
+        };
+        let mut column = ColumnConfig::default();
+        if let Some(value) = table.get("min_width") {
+            column.min_width = match value.as_f64() {
+                Some(w) if w.fract() == 0.0 && (1.0..=200.0).contains(&w) => Some(w as u16),
+                _ => {
+                    return Err(format!(
+                        "{section}.min_width must be an integer from 1 to 200"
+                    ));
+                }
+            };
+        }
+        column.align = match read_string(table, &section, "align")?.as_deref() {
+            None => None,
+            Some("left") => Some(Alignment::Left),
+            Some("right") => Some(Alignment::Right),
+            Some(other) => {
+                return Err(format!(
+                    "{section}.align must be \"left\" or \"right\", not \"{other}\""
+                ));
+            }
+        };
+        config.columns.insert(key, column);
+    }
+    if let Some(name) = doc.tables.keys().find_map(|name| {
+        name.strip_prefix("columns.")
+            .filter(|column| SortKey::from_name(column).is_none())
+    }) {
+        return Err(format!("unknown column [columns.{name}]"));
+    }
+    if let Some(table) = doc.table("noisy") {
+        for (label, patterns) in &mut config.noisy.kinds {
+            if table.get(*label).is_some() {
+                *patterns = read_patterns(table, "noisy", label)?;
+            }
+        }
+        if let Some(value) = table.get("exclude_from_top") {
+            config.noisy.exclude_from_top = value
+                .as_bool()
+                .ok_or("noisy.exclude_from_top must be true or false")?;
+        }
+    }
+    if let Some(table) = doc.table("categories") {
+        let mut added = Vec::new();
+        for name in table.keys() {
+            if name == "icons" {
+                config.categories.icons = table[name]
+                    .as_bool()
+                    .ok_or("categories.icons must be true or false")?;
+                continue;
+            }
+            let patterns = read_patterns(table, "categories", name)?;
+            match config.categories.kinds.iter_mut().find(|c| c.name == *name) {
+                Some(category) => category.patterns = patterns,
+                None => added.push(Category {
+                    name: name.clone(),
+                    tag: name.chars().take(4).collect(),
+                    icon: None,
+                    patterns,
+                    kernel_threads: false,
+                }),
+            }
+        }
+        config.categories.kinds.splice(0..0, added);
+    }
+    if let Some(value) = doc.table("sort").and_then(|table| table.get("then")) {
+        let not_columns = || "sort.then must be an array of column names".to_string();
+        config.sort_then = value
+            .as_array()
+            .ok_or_else(not_columns)?
+            .iter()
+            .map(|item| {
+                let name = item.as_str().ok_or_else(not_columns)?;
+                SortKey::from_name(name).ok_or_else(|| format!("sort.then: unknown column {name}"))
+            })
+            .collect::<Result<_, _>>()?;
+    }
+    if let Some(table) = doc.table("layouts") {
+        for (name, value) in table
+            .iter()
+            .filter(|(name, _)| !matches!(name.as_str(), "start" | "side"))
+        {
+            let not_panels = || format!("layouts.{name} must be an array of panel names");
+            let items: Vec<&str> = value
+                .as_array()
+                .ok_or_else(not_panels)?
+                .iter()
+                .map(|item| item.as_str().ok_or_else(not_panels))
+                .collect::<Result<_, _>>()?;
+            let layout =
+                ScreenLayout::new(name, &items).map_err(|e| format!("layouts.{name}: {e}"))?;
+            config.layouts.push(layout);
+        }
+        if let Some(start) = read_string(table, "layouts", "start")? {
+            if !layout::all(&config.layouts).iter().any(|l| l.name == start) {
+                return Err(format!("layouts.start: no layout named \"{start}\""));
+            }
+            config.layout = Some(start);
+        }
+        if let Some(name) = read_string(table, "layouts", "side")? {
+            config.side_column = SideColumn::from_name(&name).ok_or_else(|| {
+                format!(
+                    "layouts.side must be one of {}, not \"{name}\"",
+                    SideColumn::NAMES.join(", ")
+                )
+            })?;
+        }
+    }
+    // [[profiles.<name>.threshold]] だけを書いたものも名前に数える
+    let names: BTreeSet<&str> = doc
+        .tables
+        .keys()
+        .filter_map(|name| name.strip_prefix("profiles."))
+        .chain(
+            doc.arrays
+                .keys()
+                .filter_map(|name| name.strip_prefix("profiles.")?.strip_suffix(".threshold")),
+        )
+        .collect();
+    for name in names {
+        config
+            .profiles
+            .push(read_profile(doc, name, &config.layouts)?);
+    }
+    for (i, table) in doc.arrays.get("alert").into_iter().flatten().enumerate() {
+        config.alerts.push(read_alert(table, i)?);
+    }
+    for (i, table) in doc.arrays.get("watchdog").into_iter().flatten().enumerate() {
+        config.watchdogs.push(read_watchdog(table, i)?);
+    }
+    if let Some(table) = doc.table("filter") {
+        config.filter.include = read_patterns(table, "filter", "include")?;
+        config.filter.exclude = read_patterns(table, "filter", "exclude")?;
+    }
+    for (i, table) in doc.arrays.get("preset").into_iter().flatten().enumerate() {
+        let preset = read_preset(table, i)?;
+        if config.presets.iter().any(|p| p.key == preset.key) {
+            return Err(format!("preset[{i}].key {} is used twice", preset.key));
+        }
+        config.presets.push(preset);
+    }
+    if let Some(table) = doc.table("protect") {
+        if let Some(value) = table.get("pids") {
+            let not_pids = || "protect.pids must be an array of process IDs".to_string();
+            config.protect.pids = value
+                .as_array()
+                .ok_or_else(not_pids)?
+                .iter()
+                .map(|item| match item.as_f64() {
+                    Some(n) if n >= 0.0 && n.fract() == 0.0 => Ok(Pid::from(n as usize)),
+                    _ => Err(not_pids()),
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        config.protect.names = read_patterns(table, "protect", "names")?;
+    }
+    if let Some(value) = doc.table("compat").and_then(|table| table.get("termux")) {
+        config.termux = Some(
+            value
+                .as_bool()
+                .ok_or("compat.termux must be true or false")?,
+        );
+    }
+    if let Some(table) = doc.table("terminal") {
+        if let Some(name) = read_string(table, "terminal", "colors")? {
+            config.colors = ColorDepth::from_name(&name).ok_or_else(|| {
+                format!(
+                    "terminal.colors must be one of {}, not \"{name}\"",
+                    ColorDepth::NAMES.join(", ")
+                )
+            })?;
+        }
+        if let Some(value) = table.get("ascii") {
+            config.ascii = Some(
+                value
+                    .as_bool()
+                    .ok_or("terminal.ascii must be true or false")?,
+            );
+        }
+        if let Some(value) = table.get("plain") {
+            config.plain = value
+                .as_bool()
+                .ok_or("terminal.plain must be true or false")?;
+        }
+        if let Some(value) = table.get("low_bandwidth") {
+            config.low_bandwidth = value
+                .as_bool()
+                .ok_or("terminal.low_bandwidth must be true or false")?;
+        }
+    }
+    if let Some(value) = doc.table("follow").and_then(|table| table.get("restarts")) {
+        config.follow_restarts = value
+            .as_bool()
+            .ok_or("follow.restarts must be true or false")?;
+    }
+    if let Some(value) = doc.table("processes").and_then(|table| table.get("rows")) {
+        match value.as_f64() {
+            Some(n) if n >= 1.0 && n.fract() == 0.0 => config.process_rows = Some(n as usize),
+            _ => return Err("processes.rows must be a positive integer".to_string()),
+        }
+    }
+    if let Some(value) = doc.table("events").and_then(|table| table.get("keep")) {
+        match value.as_f64() {
+            Some(n) if n >= 0.0 && n.fract() == 0.0 => config.events_keep = Some(n as usize),
+            _ => return Err("events.keep must be a non-negative integer".to_string()),
+        }
+    }
+    if let Some(value) = doc.table("pin").and_then(|table| table.get("on_exit")) {
+        // 1 つだけなら文字列でもよい。空の配列なら知らせない
+        let items = match value.as_array() {
+            Some(items) => items,
+            None => std::slice::from_ref(value),
+        };
+        let notices = items
+            .iter()
+            .map(|item| {
+                let name = item.as_str().unwrap_or_default();
+                ExitNotice::from_name(name).ok_or_else(|| {
+                    format!(
+                        "pin.on_exit must be \"bell\", \"flash\" or \"desktop\", not \"{name}\""
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        config.on_exit = Some(notices);
+    }
+    if let Some(table) = doc.table("keys")
+        && let Some(name) = read_string(table, "keys", "keymap")?
+    {
+        config.keymap = Keymap::from_name(&name)
+            .ok_or_else(|| format!("keys.keymap must be \"default\" or \"vim\", not \"{name}\""))?;
+    }
+    for (i, table) in doc.arrays.get("host").into_iter().flatten().enumerate() {
+        let section = format!("host[{i}]");
+        let addr = read_string(table, &section, "addr")?
+            .ok_or_else(|| format!("{section}.addr is required"))?;
+        let name = read_string(table, &section, "name")?.unwrap_or_else(|| addr.clone());
+        config.hosts.push(HostConfig { name, addr });
+    }
+    for (i, table) in doc.arrays.get("panel").into_iter().flatten().enumerate() {
+        config
+            .panels
+            .push(read_panel(table, &format!("panel[{i}]"))?);
+    }
+    #[cfg(feature = "plugin")]
+    for (i, table) in doc.arrays.get("plugin").into_iter().flatten().enumerate() {
+        let section = format!("plugin[{i}]");
+        let command = read_string(table, &section, "command")?
+            .ok_or_else(|| format!("{section}.command is required"))?;
+        let name = read_string(table, &section, "name")?.unwrap_or_else(|| command.clone());
+        config.plugins.push(PluginConfig { name, command });
+    }
+    #[cfg(feature = "journal")]
+    if let Some(table) = doc.table("journal") {
+        config.journal_file = read_string(table, "journal", "file")?.map(PathBuf::from);
+    }
+    #[cfg(feature = "profile")]
+    if let Some(table) = doc.table("profile") {
+        let profile = &mut config.profile;
+        if let Some(perf) = read_string(table, "profile", "perf")? {
+            profile.perf = perf;
+        }
+        if let Some(value) = table.get("seconds") {
+            profile.duration = match value.as_f64() {
+                Some(secs) if secs > 0.0 && secs <= 60.0 => Duration::from_secs_f64(secs),
+                _ => return Err("profile.seconds must be a number of seconds up to 60".to_string()),
+            };
+        }
+    }
+    #[cfg(feature = "history")]
+    if let Some(table) = doc.table("history") {
+        let history = &mut config.history;
+        history.path = read_string(table, "history", "path")?.map(PathBuf::from);
+        if let Some(value) = table.get("interval") {
+            history.interval = match value.as_f64().and_then(seconds) {
+                Some(interval) => interval,
+                None => {
+                    return Err("history.interval must be a positive number of seconds".to_string());
+                }
+            };
+        }
+        if let Some(value) = table.get("keep_hours") {
+            history.keep = match value
+                .as_f64()
+                .and_then(|hours| seconds(hours * 60.0 * 60.0))
+            {
+                Some(keep) => keep,
+                None => return Err("history.keep_hours must be a positive number".to_string()),
+            };
+        }
+    }
+    #[cfg(feature = "metrics")]
+    if let Some(table) = doc.table("metrics") {
+        let metrics = &mut config.metrics;
+        metrics.target = read_string(table, "metrics", "target")?;
+        if let Some(target) = &metrics.target
+            && let Some((scheme, addr)) = target.split_once("://")
+            && (!matches!(scheme, "udp" | "tcp") || !addr.contains(':'))
+        {
+            return Err(format!(
+                "metrics.target must be udp://host:port, tcp://host:port or a file path, not \"{target}\""
+            ));
+        }
+        if let Some(format) = read_string(table, "metrics", "format")? {
+            metrics.format = match format.as_str() {
+                "statsd" => MetricsFormat::Statsd,
+                "influx" => MetricsFormat::Influx,
+                _ => {
+                    return Err(format!(
+                        "metrics.format must be \"statsd\" or \"influx\", not \"{format}\""
+                    ));
+                }
+            };
+        }
+        if let Some(prefix) = read_string(table, "metrics", "prefix")? {
+            metrics.prefix = prefix;
+        }
+        if let Some(value) = table.get("interval") {
+            metrics.interval = match value.as_f64().and_then(seconds) {
+                Some(interval) => interval,
+                None => {
+                    return Err("metrics.interval must be a positive number of seconds".to_string());
+                }
+            };
+        }
+        if let Some(value) = table.get("processes") {
+            match value.as_f64() {
+                Some(n) if n >= 0.0 && n.fract() == 0.0 => metrics.processes = n as usize,
+                _ => return Err("metrics.processes must be a non-negative integer".to_string()),
+            }
+        }
+    }
+    if let Some(table) = doc.table("leak") {
+        let leak = &mut config.leak;
+        if let Some(value) = table.get("enabled") {
+            leak.enabled = value
+                .as_bool()
+                .ok_or("leak.enabled must be true or false")?;
+        }
+        if let Some(value) = table.get("window") {
+            leak.window = match value.as_f64().and_then(seconds) {
+                Some(interval) => interval,
+                None => return Err("leak.window must be a positive number of seconds".to_string()),
+            };
+        }
+        // 設定ファイルでは MB/分
+        if let Some(value) = table.get("growth") {
+            leak.growth = match value.as_f64() {
+                Some(mb) if mb > 0.0 && mb.is_finite() => (mb * 1024.0 * 1024.0) as u64,
+                _ => {
+                    return Err(
+                        "leak.growth must be a positive number of MB per minute".to_string()
+                    );
+                }
+            };
+        }
+    }
+    if let Some(table) = doc.table("idle") {
+        let idle = &mut config.idle;
+        if let Some(value) = table.get("enabled") {
+            idle.enabled = value
+                .as_bool()
+                .ok_or("idle.enabled must be true or false")?;
+        }
+        for (key, out) in [("after", &mut idle.after), ("interval", &mut idle.interval)] {
+            if let Some(value) = table.get(key) {
+                *out = match value.as_f64().and_then(seconds) {
+                    Some(interval) => interval,
+                    None => return Err(format!("idle.{key} must be a positive number of seconds")),
+                };
+            }
+        }
+        if let Some(value) = table.get("cpu") {
+            idle.cpu = match value.as_f64() {
+                Some(percent) if (0.0..=100.0).contains(&percent) => percent,
+                _ => return Err("idle.cpu must be a percentage from 0 to 100".to_string()),
+            };
+        }
+    }
+    if let Some(table) = doc.table("log") {
+        config.log_file = read_string(table, "log", "file")?.map(PathBuf::from);
+        if let Some(name) = read_string(table, "log", "level")? {
+            config.log_level = Some(Level::from_name(&name).ok_or_else(|| {
+                format!(
+                    "log.level must be one of {}, not \"{name}\"",
+                    Level::NAMES.join(", ")
+                )
+            })?);
+        }
+    }
+    #[cfg(feature = "mqtt")]
+    if let Some(table) = doc.table("mqtt") {
+        let mqtt = &mut config.mqtt;
+        mqtt.broker = read_string(table, "mqtt", "broker")?;
+        if let Some(topic) = read_string(table, "mqtt", "topic")? {
+            // ワイルドカードは publish するトピックには使えない
+            if topic.is_empty() || topic.contains(['+', '#']) {
+                return Err(format!(
+                    "mqtt.topic must be a non-empty topic without + or #, not \"{topic}\""
+                ));
+            }
+            mqtt.topic = topic.trim_end_matches('/').to_string();
+        }
+        if let Some(client_id) = read_string(table, "mqtt", "client_id")? {
+            mqtt.client_id = client_id;
+        }
+        mqtt.username = read_string(table, "mqtt", "username")?;
+        mqtt.password = read_string(table, "mqtt", "password")?;
+        if let Some(value) = table.get("interval") {
+            mqtt.interval = match value.as_f64().and_then(seconds) {
+                Some(interval) => interval,
+                None => {
+                    return Err("mqtt.interval must be a positive number of seconds".to_string());
+                }
+            };
+        }
+        if let Some(value) = table.get("retain") {
+            mqtt.retain = value.as_bool().ok_or("mqtt.retain must be true or false")?;
+        }
+    }
+    Ok(config)
+}
+
+fn read_alert(table: &Table, index: usize) -> Result<AlertRule, String> {
+    let section = format!("alert[{index}]");
+    let metric_name = read_string(table, &section, "metric")?
+        .ok_or_else(|| format!("{section}.metric is required"))?;
+    let metric = Metric::from_name(&metric_name).ok_or_else(|| {
+        format!(
+            "{section}.metric must be cpu, memory, swap, process_cpu or process_memory, not \"{metric_name}\""
+        )
+    })?;
+    // メモリの量は "2GiB" のように単位を付けて書ける
+    let above = match table.get("above") {
+        Some(value) => value
+            .as_f64()
+            .or_else(|| value.as_str().and_then(alert::parse_bytes))
+            .ok_or_else(|| format!("{section}.above must be a number"))?,
+        None => return Err(format!("{section}.above is required")),
+    };
+    let mut duration = Duration::ZERO;
+    if table.contains_key("for") {
+        read_interval(table, "for", &mut duration)
+            .map_err(|_| format!("{section}.for must be a positive number of seconds"))?;
+    }
+    let process = match read_string(table, &section, "process")? {
+        Some(source) if metric.is_process() => {
+            Some(Pattern::new(&source).map_err(|e| format!("{section}.process: {e}"))?)
+        }
+        Some(_) => return Err(format!("{section}.process needs a process_* metric")),
+        None => None,
+    };
+    let notify = match table.get("notify") {
+        None => false,
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| format!("{section}.notify must be true or false"))?,
+    };
+    let mut notify_every = alert::NOTIFY_EVERY;
+    if table.contains_key("notify_every") {
+        read_interval(table, "notify_every", &mut notify_every)
+            .map_err(|_| format!("{section}.notify_every must be a positive number of seconds"))?;
+    }
+    let run = read_string(table, &section, "run")?;
+    let name = read_string(table, &section, "name")?.unwrap_or_else(|| metric_name.clone());
+    Ok(AlertRule {
+        name,
+        metric,
+        above,
+        duration,
+        process,
+        notify,
+        notify_every,
+        run,
+    })
+}
+
+fn read_watchdog(table: &Table, index: usize) -> Result<WatchdogRule, String> {
+    let section = format!("watchdog[{index}]");
+    let metric_name = read_string(table, &section, "metric")?
+        .ok_or_else(|| format!("{section}.metric is required"))?;
+    let metric = Metric::from_name(&metric_name)
+        .filter(|metric| metric.is_process())
+        .ok_or_else(|| {
+            format!("{section}.metric must be process_cpu or process_memory, not \"{metric_name}\"")
+        })?;
+    let above = match table.get("above") {
+        Some(value) => value
+            .as_f64()
+            .or_else(|| value.as_str().and_then(alert::parse_bytes))
+            .ok_or_else(|| format!("{section}.above must be a number"))?,
+        None => return Err(format!("{section}.above is required")),
+    };
+    let mut duration = Duration::ZERO;
+    if table.contains_key("for") {
+        read_interval(table, "for", &mut duration)
+            .map_err(|_| format!("{section}.for must be a positive number of seconds"))?;
+    }
+    // 全部のプロセスに送ることがないよう、対象は必ず書いてもらう
+    let source = read_string(table, &section, "process")?
+        .ok_or_else(|| format!("{section}.process is required"))?;
+    let process = Pattern::new(&source).map_err(|e| format!("{section}.process: {e}"))?;
+    let signal = match read_string(table, &section, "signal")? {
+        Some(name) => actions::parse_signal(&name)
+            .ok_or_else(|| format!("{section}.signal: unknown signal {name}"))?,
+        None => Signal::Term,
+    };
+    let dry_run = match table.get("dry_run") {
+        None => false,
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| format!("{section}.dry_run must be true or false"))?,
+    };
+    let name = read_string(table, &section, "name")?.unwrap_or(source);
+    Ok(WatchdogRule {
+        name,
+        process,
+        metric,
+        above,
+        duration,
+        signal,
+        dry_run,
+    })
+}
+
+fn read_threshold(table: &Table, section: &str) -> Result<Threshold, String> {
+    let above = match table.get("above").and_then(|v| v.as_f64()) {
+        Some(above) if (0.0..=100.0).contains(&above) => above,
+        _ => {
+            return Err(format!(
+                "{section}.above must be a percentage from 0 to 100"
+            ));
+        }
+    };
+    let style = read_string(table, section, "style")?
+        .ok_or_else(|| format!("{section}.style is required"))?;
+    let style = theme::parse_style(&style).map_err(|e| format!("{section}.style: {e}"))?;
+    Ok(Threshold { above, style })
+}
+
+fn read_profile(doc: &Document, name: &str, layouts: &[ScreenLayout]) -> Result<Profile, String> {
+    let section = format!("profiles.{name}");
+    let mut profile = Profile {
+        name: name.to_string(),
+        ..Profile::default()
+    };
+    if let Some(table) = doc.table(&section) {
+        if let Some(layout) = read_string(table, &section, "layout")? {
+            if !layout::all(layouts).iter().any(|l| l.name == layout) {
+                return Err(format!("{section}.layout: no layout named \"{layout}\""));
+            }
+            profile.layout = Some(layout);
+        }
+        if let Some(value) = table.get("columns") {
+            let not_columns = || {
+                format!(
+                    "{section}.columns must be an array of {}",
+                    state::COLUMNS.join(", ")
+                )
+            };
+            let columns = value
+                .as_array()
+                .ok_or_else(not_columns)?
+                .iter()
+                .map(|item| match item.as_str() {
+                    Some(column) if state::COLUMNS.contains(&column) => Ok(column.to_string()),
+                    _ => Err(not_columns()),
+                })
+                .collect::<Result<_, _>>()?;
+            profile.columns = Some(columns);
+        }
+        if let Some(sort) = read_string(table, &section, "sort")? {
+            profile.sort = Some(
+                SortKey::from_name(&sort)
+                    .ok_or_else(|| format!("{section}.sort: unknown column {sort}"))?,
+            );
+        }
+        if let Some(value) = table.get("interval") {
+            let interval = value.as_f64().and_then(seconds).ok_or_else(|| {
+                format!("{section}.interval must be a positive number of seconds")
+            })?;
+            profile.interval = Some(interval);
+        }
+    }
+    if let Some(tables) = doc.arrays.get(&format!("{section}.threshold")) {
+        let mut thresholds = tables
+            .iter()
+            .enumerate()
+            .map(|(i, table)| read_threshold(table, &format!("{section}.threshold[{i}]")))
+            .collect::<Result<Vec<_>, _>>()?;
+        thresholds.sort_by(|a, b| a.above.total_cmp(&b.above));
+        profile.thresholds = Some(thresholds);
+    }
+    Ok(profile)
+}
+
+fn read_preset(table: &Table, index: usize) -> Result<FilterPreset, String> {
+    let section = format!("preset[{index}]");
+    let key =
+        read_string(table, &section, "key")?.ok_or_else(|| format!("{section}.key is required"))?;
+    let key = match key.as_bytes() {
+        [digit @ b'1'..=b'9'] => *digit as char,
+        _ => return Err(format!("{section}.key must be a digit from 1 to 9")),
+    };
+    Ok(FilterPreset {
+        key,
+        name: read_string(table, &section, "name")?.unwrap_or_else(|| key.to_string()),
+        filter: FilterConfig {
+            include: read_patterns(table, &section, "include")?,
+            exclude: read_patterns(table, &section, "exclude")?,
+        },
+        user: read_string(table, &section, "user")?,
+    })
+}
+
+fn read_patterns(table: &Table, section: &str, key: &str) -> Result<Vec<Pattern>, String> {
+    let Some(value) = table.get(key) else {
+        return Ok(Vec::new());
+    };
+    let not_strings = || format!("{section}.{key} must be an array of strings");
+    value
+        .as_array()
+        .ok_or_else(not_strings)?
+        .iter()
+        .map(|item| {
+            let source = item.as_str().ok_or_else(not_strings)?;
+            Pattern::new(source).map_err(|e| format!("{section}.{key}: {e}"))
+        })
+        .collect()
+}
+
+fn read_panel(table: &Table, section: &str) -> Result<PanelConfig, String> {
+    let command = read_string(table, section, "command")?;
+    let file = read_string(table, section, "file")?;
+    let source = match (command, file) {
+        (Some(command), None) => PanelSource::Command(command),
+        (None, Some(file)) => PanelSource::File(PathBuf::from(file)),
+        _ => return Err(format!("{section} needs either command or file")),
+    };
+    let title = read_string(table, section, "title")?
+        .ok_or_else(|| format!("{section}.title is required"))?;
+    let number = |key: &str, default: f64| match table.get(key) {
+        None => Ok(default),
+        Some(value) => value
+            .as_f64()
+            .filter(|n| n.is_finite())
+            .ok_or_else(|| format!("{section}.{key} must be a number")),
+    };
+    let interval = seconds(number("interval", 2.0)?)
+        .ok_or_else(|| format!("{section}.interval must be a positive number of seconds"))?;
+    let kind = match read_string(table, section, "kind")?.as_deref() {
+        None | Some("text") => PanelKind::Text,
+        Some("gauge") => {
+            let max = number("max", 100.0)?;
+            if max <= 0.0 {
+                return Err(format!("{section}.max must be a positive number"));
+            }
+            PanelKind::Gauge {
+                max,
+                scale: number("scale", 1.0)?,
+                unit: read_string(table, section, "unit")?.unwrap_or_default(),
+            }
+        }
+        Some(other) => {
+            return Err(format!(
+                "{section}.kind must be \"text\" or \"gauge\", not \"{other}\""
+            ));
+        }
+    };
+    Ok(PanelConfig {
+        title,
+        source,
+        interval,
+        kind,
+    })
+}
+
+fn read_string(table: &Table, section: &str, key: &str) -> Result<Option<String>, String> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(value) => value
+            .as_str()
+            .map(|s| Some(s.to_string()))
+            .ok_or_else(|| format!("{section}.{key} must be a string")),
+    }
+}
+
+// 秒数（整数または小数）を Duration として読む
+fn read_interval(table: &Table, key: &str, out: &mut Duration) -> Result<(), String> {
+    let Some(value) = table.get(key) else {
+        return Ok(());
+    };
+    match value.as_f64().and_then(seconds) {
+        Some(interval) => {
+            *out = interval;
+            Ok(())
+        }
+        None => Err(format!(
+            "refresh.{key} must be a positive number of seconds"
+        )),
+    }
+}
+
+// 0 より長く、Duration で表せる秒数だけを受け付ける（1e20 などは None）
+fn seconds(secs: f64) -> Option<Duration> {
+    Duration::try_from_secs_f64(secs)
+        .ok()
+        .filter(|interval| !interval.is_zero())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, from_document, parser};
+
+    fn load(text: &str) -> Result<Config, String> {
+        from_document(&parser::parse(text).expect("valid TOML"))
+    }
+
+    #[test]
+    fn refresh_intervals_too_long_to_represent_are_rejected() {
+        assert!(load("[refresh]\ncpu = 0.5\n").is_ok());
+        let error = load("[refresh]\ncpu = 1e20\n").expect_err("out of range");
+        assert_eq!(error, "refresh.cpu must be a positive number of seconds");
+    }
+
+    #[test]
+    fn profile_intervals_too_long_to_represent_are_rejected() {
+        assert!(load("[profiles.db]\ninterval = 5\n").is_ok());
+        let error = load("[profiles.db]\ninterval = 1e20\n").expect_err("out of range");
+        assert!(
+            error.ends_with("interval must be a positive number of seconds"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn panel_intervals_too_long_to_represent_are_rejected() {
+        let panel = "[[panel]]\ntitle = \"x\"\ncommand = \"true\"\ninterval = ";
+        assert!(load(&format!("{panel}5\n")).is_ok());
+        let error = load(&format!("{panel}1e20\n")).expect_err("out of range");
+        assert!(
+            error.ends_with("interval must be a positive number of seconds"),
+            "{error}"
+        );
+    }
+}
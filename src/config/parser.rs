@@ -0,0 +1,255 @@
+// 設定ファイル用の小さな TOML サブセットパーサー
+//
+// 対応する構文:
+//   [section] / [section.sub]   テーブル
+//   [[section]]                 テーブルの配列
+//   key = "string" | 'string' | 123 | 1.5 | true | false | [値, ...]
+//   # コメント
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+}
+
+pub type Table = BTreeMap<String, Value>;
+
+#[derive(Debug, Default)]
+pub struct Document {
+    pub tables: BTreeMap<String, Table>,
+    pub arrays: BTreeMap<String, Vec<Table>>,
+}
+
+impl Document {
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.get(name)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+enum Target {
+    Table(String),
+    Array(String),
+}
+
+pub fn parse(input: &str) -> Result<Document, ParseError> {
+    let mut doc = Document::default();
+    let mut target = Target::Table(String::new());
+    doc.tables.insert(String::new(), Table::new());
+
+    for (index, raw) in input.lines().enumerate() {
+        let line_no = index + 1;
+        let err = |message: &str| ParseError {
+            line: line_no,
+            message: message.to_string(),
+        };
+        let line = strip_comment(raw).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(rest) = line.strip_prefix("[[") {
+            let name = rest
+                .strip_suffix("]]")
+                .ok_or_else(|| err("unterminated array table header"))?
+                .trim()
+                .to_string();
+            doc.arrays
+                .entry(name.clone())
+                .or_default()
+                .push(Table::new());
+            target = Target::Array(name);
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('[') {
+            let name = rest
+                .strip_suffix(']')
+                .ok_or_else(|| err("unterminated table header"))?
+                .trim()
+                .to_string();
+            doc.tables.entry(name.clone()).or_default();
+            target = Target::Table(name);
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| err("expected `key = value`"))?;
+        let key = unquote_key(key.trim());
+        if key.is_empty() {
+            return Err(err("empty key"));
+        }
+        let mut chars = value.trim().chars().peekable();
+        let value = parse_value(&mut chars).map_err(|m| err(&m))?;
+        if chars.any(|c| !c.is_whitespace()) {
+            return Err(err("unexpected characters after value"));
+        }
+
+        let table = match &target {
+            Target::Table(name) => doc.tables.get_mut(name),
+            Target::Array(name) => doc.arrays.get_mut(name).and_then(|v| v.last_mut()),
+        }
+        .expect("current table exists");
+        table.insert(key, value);
+    }
+
+    Ok(doc)
+}
+
+// 文字列リテラル内の # はコメントとして扱わない
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '#' => return &line[..i],
+                _ => {}
+            },
+        }
+    }
+    line
+}
+
+fn unquote_key(key: &str) -> String {
+    key.trim_matches(|c| c == '"' || c == '\'').to_string()
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_ws(chars: &mut Chars) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn parse_value(chars: &mut Chars) -> Result<Value, String> {
+    skip_ws(chars);
+    match chars.peek() {
+        Some('"') | Some('\'') => parse_string(chars).map(Value::String),
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            loop {
+                skip_ws(chars);
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                    break;
+                }
+                items.push(parse_value(chars)?);
+                skip_ws(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(']') => break,
+                    _ => return Err("expected `,` or `]` in array".to_string()),
+                }
+            }
+            Ok(Value::Array(items))
+        }
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == ',' || c == ']' || c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            parse_scalar(&word)
+        }
+        None => Err("missing value".to_string()),
+    }
+}
+
+fn parse_string(chars: &mut Chars) -> Result<String, String> {
+    let quote = chars.next().expect("quote");
+    let mut out = String::new();
+    loop {
+        match chars.next() {
+            Some(c) if c == quote => return Ok(out),
+            Some('\\') if quote == '"' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('\\') => out.push('\\'),
+                Some('"') => out.push('"'),
+                Some(c) => return Err(format!("unknown escape `\\{c}`")),
+                None => return Err("unterminated string".to_string()),
+            },
+            Some(c) => out.push(c),
+            None => return Err("unterminated string".to_string()),
+        }
+    }
+}
+
+fn parse_scalar(word: &str) -> Result<Value, String> {
+    match word {
+        "true" => return Ok(Value::Boolean(true)),
+        "false" => return Ok(Value::Boolean(false)),
+        _ => {}
+    }
+    let digits = word.replace('_', "");
+    if let Ok(i) = digits.parse::<i64>() {
+        return Ok(Value::Integer(i));
+    }
+    if let Ok(f) = digits.parse::<f64>() {
+        return Ok(Value::Float(f));
+    }
+    Err(format!("invalid value `{word}`"))
+}
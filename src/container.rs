@@ -0,0 +1,139 @@
+// プロセスが動いているコンテナ（Docker / Podman / containerd / CRI-O）の判定
+//
+// /proc/<pid>/cgroup のパスに含まれる 64 桁のコンテナ ID を探す。
+// docker フィーチャーを有効にすると Docker のソケットに問い合わせて名前に置き換える。
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sysinfo::Pid;
+
+/// 表示に使う ID の長さ（docker ps と同じ）
+const SHORT_ID: usize = 12;
+
+/// コンテナ内のプロセスなら、コンテナ ID を返す
+#[cfg(target_os = "linux")]
+pub fn container_id(pid: Pid) -> Option<String> {
+    let text = std::fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    text.lines().find_map(parse_cgroup_line)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn container_id(_pid: Pid) -> Option<String> {
+    None
+}
+
+// "0::/system.slice/docker-<id>.scope" や "12:memory:/docker/<id>" など
+#[cfg(target_os = "linux")]
+fn parse_cgroup_line(line: &str) -> Option<String> {
+    let path = line.splitn(3, ':').nth(2)?;
+    path.rsplit('/').find_map(|segment| {
+        let segment = segment.strip_suffix(".scope").unwrap_or(segment);
+        let id = [
+            "docker-",
+            "libpod-",
+            "cri-containerd-",
+            "crio-",
+            "containerd-",
+        ]
+        .iter()
+        .find_map(|prefix| segment.strip_prefix(prefix))
+        .unwrap_or(segment);
+        (id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())).then(|| id.to_string())
+    })
+}
+
+/// コンテナ ID から表示名への変換キャッシュ
+#[derive(Default)]
+pub struct ContainerNames {
+    labels: HashMap<String, Arc<str>>,
+    /// 今回の収集で既に Docker に問い合わせたか
+    #[cfg(feature = "docker")]
+    queried: bool,
+}
+
+impl ContainerNames {
+    // 新しい収集の開始時に呼ぶ
+    pub fn begin_pass(&mut self) {
+        #[cfg(feature = "docker")]
+        {
+            self.queried = false;
+        }
+    }
+
+    pub fn label(&mut self, id: &str) -> Arc<str> {
+        if let Some(label) = self.labels.get(id) {
+            return label.clone();
+        }
+        #[cfg(feature = "docker")]
+        if !self.queried {
+            self.queried = true;
+            for (id, name) in docker::container_names() {
+                self.labels.insert(id, Arc::from(name));
+            }
+            if let Some(label) = self.labels.get(id) {
+                return label.clone();
+            }
+        }
+        let label: Arc<str> = Arc::from(&id[..SHORT_ID.min(id.len())]);
+        self.labels.insert(id.to_string(), label.clone());
+        label
+    }
+}
+
+/// Docker のソケットに接続できるか（--doctor で使う）
+#[cfg(feature = "docker")]
+pub fn check_docker() -> Result<(), String> {
+    docker::connect()
+        .map(|_| ())
+        .map_err(|e| format!("{}: {e}", docker::SOCKET))
+}
+
+#[cfg(feature = "docker")]
+mod docker {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    pub const SOCKET: &str = "/var/run/docker.sock";
+
+    /// 動いているコンテナの (ID, 名前)。Docker が無ければ空
+    pub fn container_names() -> Vec<(String, String)> {
+        request().map(|body| parse(&body)).unwrap_or_default()
+    }
+
+    pub fn connect() -> std::io::Result<UnixStream> {
+        UnixStream::connect(SOCKET)
+    }
+
+    fn request() -> std::io::Result<String> {
+        let mut stream = connect()?;
+        stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+        stream.set_write_timeout(Some(Duration::from_secs(1)))?;
+        // HTTP/1.0 にしてチャンク転送を避ける
+        stream.write_all(b"GET /containers/json HTTP/1.0\r\nHost: docker\r\n\r\n")?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        Ok(response
+            .split_once("\r\n\r\n")
+            .map_or(String::new(), |(_, body)| body.to_string()))
+    }
+
+    // [{"Id":"<id>","Names":["/<name>"],...}, ...] から必要な値だけ拾う
+    fn parse(body: &str) -> Vec<(String, String)> {
+        let mut out = Vec::new();
+        let mut rest = body;
+        while let Some(start) = rest.find("\"Id\":\"") {
+            rest = &rest[start + 6..];
+            let Some(end) = rest.find('"') else { break };
+            let id = rest[..end].to_string();
+            rest = &rest[end..];
+            let Some(names) = rest.find("\"Names\":[\"") else {
+                break;
+            };
+            let tail = &rest[names + 10..];
+            let Some(end) = tail.find('"') else { break };
+            out.push((id, tail[..end].trim_start_matches('/').to_string()));
+        }
+        out
+    }
+}
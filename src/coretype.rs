@@ -0,0 +1,138 @@
+// ハイブリッド CPU のコアの種類（Intel の P / E コア、ARM の big.LITTLE）
+//
+// 種類の違うコアが混ざっていると、全体の使用率だけでは「E コアが埋まって P コアは空いている」
+// ような偏りが見えないので、種類ごとの平均も出す。コアの種類は変わらないので最初に一度だけ調べる。
+// Intel は /sys/devices/cpu_core と cpu_atom の cpus、ARM はコアごとの cpu_capacity の違いで分ける。
+// Apple Silicon は apple フィーチャーのパネルが P / E 別に出す。
+
+/// 同じ種類のコアの CPU 番号
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreCluster {
+    /// "P-cores"、"E-cores"、"big"、"LITTLE" など
+    pub label: String,
+    pub cpus: Vec<usize>,
+}
+
+/// 種類ごとの平均使用率
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterUsage {
+    pub label: String,
+    pub count: usize,
+    /// 1 コア = 100%
+    pub usage: f32,
+}
+
+/// usages は CPU 番号の順のコアごとの使用率
+pub fn usage(clusters: &[CoreCluster], usages: &[f32]) -> Vec<ClusterUsage> {
+    clusters
+        .iter()
+        .filter_map(|cluster| {
+            let found: Vec<f32> = cluster
+                .cpus
+                .iter()
+                .filter_map(|&cpu| usages.get(cpu).copied())
+                .collect();
+            (!found.is_empty()).then(|| ClusterUsage {
+                label: cluster.label.clone(),
+                count: found.len(),
+                usage: found.iter().sum::<f32>() / found.len() as f32,
+            })
+        })
+        .collect()
+}
+
+/// 種類が 1 つしか無ければ空
+#[cfg(target_os = "linux")]
+pub fn detect() -> Vec<CoreCluster> {
+    intel_hybrid().or_else(capacities).unwrap_or_default()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn detect() -> Vec<CoreCluster> {
+    Vec::new()
+}
+
+// ハイブリッドの Intel CPU では、perf の PMU がコアの種類ごとに分かれる
+#[cfg(target_os = "linux")]
+fn intel_hybrid() -> Option<Vec<CoreCluster>> {
+    let cpus = |pmu: &str| {
+        let text = std::fs::read_to_string(format!("/sys/devices/{pmu}/cpus")).ok()?;
+        let cpus = parse_cpu_list(&text);
+        (!cpus.is_empty()).then_some(cpus)
+    };
+    Some(vec![
+        CoreCluster {
+            label: "P-cores".to_string(),
+            cpus: cpus("cpu_core")?,
+        },
+        CoreCluster {
+            label: "E-cores".to_string(),
+            cpus: cpus("cpu_atom")?,
+        },
+    ])
+}
+
+// ARM では処理能力の相対値（最大 1024）がコアの種類ごとに違う
+#[cfg(target_os = "linux")]
+fn capacities() -> Option<Vec<CoreCluster>> {
+    use std::collections::BTreeMap;
+
+    let mut groups: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+    for entry in std::fs::read_dir("/sys/devices/system/cpu").ok()?.flatten() {
+        let name = entry.file_name();
+        let Some(cpu) = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("cpu"))
+            .and_then(|n| n.parse::<usize>().ok())
+        else {
+            continue;
+        };
+        let Some(capacity) = std::fs::read_to_string(entry.path().join("cpu_capacity"))
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+        else {
+            continue;
+        };
+        groups.entry(capacity).or_default().push(cpu);
+    }
+    if groups.len() < 2 {
+        return None;
+    }
+    // 速いほうから並べる
+    let labels: &[&str] = match groups.len() {
+        2 => &["big", "LITTLE"],
+        3 => &["prime", "big", "LITTLE"],
+        _ => &[],
+    };
+    Some(
+        groups
+            .into_iter()
+            .rev()
+            .enumerate()
+            .map(|(i, (capacity, mut cpus))| {
+                cpus.sort_unstable();
+                let label = labels
+                    .get(i)
+                    .map_or_else(|| format!("capacity {capacity}"), |l| l.to_string());
+                CoreCluster { label, cpus }
+            })
+            .collect(),
+    )
+}
+
+/// "0-7,16" のような cpulist を CPU 番号に広げる
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn parse_cpu_list(text: &str) -> Vec<usize> {
+    let mut cpus = Vec::new();
+    for part in text.trim().split(',').filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                if let (Ok(start), Ok(end)) = (start.parse::<usize>(), end.parse::<usize>()) {
+                    cpus.extend(start..=end);
+                }
+            }
+            None => cpus.extend(part.parse::<usize>().ok()),
+        }
+    }
+    cpus
+}
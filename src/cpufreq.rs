@@ -0,0 +1,48 @@
+// CPU ごとの周波数と、Linux ではスケーリングガバナーと周波数の範囲
+use sysinfo::System;
+
+#[derive(Debug, Clone, Default)]
+pub struct CpuFrequency {
+    /// コアごとの現在の周波数（MHz）
+    pub current: Vec<u64>,
+    /// 使われているガバナー（コアごとに違えばすべて）
+    pub governors: Vec<String>,
+    /// スケーリングの下限と上限（MHz）
+    pub range: Option<(u64, u64)>,
+}
+
+/// 周波数は呼ぶ前に refresh_cpu_frequency で更新しておく
+pub fn collect(sys: &System) -> CpuFrequency {
+    let mut frequency = CpuFrequency {
+        current: sys.cpus().iter().map(|c| c.frequency()).collect(),
+        ..CpuFrequency::default()
+    };
+    #[cfg(target_os = "linux")]
+    read_scaling(&mut frequency, sys.cpus().len());
+    frequency
+}
+
+#[cfg(target_os = "linux")]
+fn read_scaling(frequency: &mut CpuFrequency, count: usize) {
+    use std::fs;
+
+    let mut min = u64::MAX;
+    let mut max = 0;
+    for cpu in 0..count {
+        let dir = format!("/sys/devices/system/cpu/cpu{cpu}/cpufreq");
+        let read = |name: &str| fs::read_to_string(format!("{dir}/{name}")).ok();
+        // sysfs は kHz
+        let mhz = |name: &str| read(name)?.trim().parse::<u64>().ok().map(|khz| khz / 1000);
+        if let Some(governor) = read("scaling_governor") {
+            let governor = governor.trim().to_string();
+            if !frequency.governors.contains(&governor) {
+                frequency.governors.push(governor);
+            }
+        }
+        if let (Some(low), Some(high)) = (mhz("scaling_min_freq"), mhz("scaling_max_freq")) {
+            min = min.min(low);
+            max = max.max(high);
+        }
+    }
+    frequency.range = (max > 0).then_some((min, max));
+}
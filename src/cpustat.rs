@@ -0,0 +1,190 @@
+// システム全体とコアごとの CPU 時間の内訳
+//
+// sysinfo からはコアごとの使用率しか分からないため、user / system / iowait などの
+// 割合は CPU 時間の累計の差から求める。コンテキストスイッチ・割り込み・fork の
+// 回数も累計を読んで 1 秒あたりに直す。累計は platform から読む（Linux なら /proc/stat）。
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::platform::{CpuCounters, CpuTicks, Native, Platform};
+
+/// 前回の収集からの割合（%、合計で 100）
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CpuBreakdown {
+    /// nice を含み、guest を除く
+    pub user: f64,
+    pub system: f64,
+    /// ハードウェア割り込みの処理
+    pub irq: f64,
+    pub softirq: f64,
+    pub iowait: f64,
+    /// 仮想マシンでホストに取られていた時間
+    pub steal: f64,
+    /// このマシンの上の仮想マシンが使った時間
+    pub guest: f64,
+    pub idle: f64,
+}
+
+impl CpuBreakdown {
+    /// idle 以外の合計
+    pub fn busy(&self) -> f64 {
+        100.0 - self.idle
+    }
+}
+
+/// 前回の収集からの 1 秒あたりの回数
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct KernelRates {
+    pub context_switches: f64,
+    pub interrupts: f64,
+    /// 作られたプロセス（スレッドを含む）
+    pub forks: f64,
+}
+
+/// 前回の累計と読んだ時刻を覚えておく
+#[derive(Default)]
+pub struct CpuStatReader {
+    /// 全体（None）とコアごとの前回の累計
+    last: HashMap<Option<usize>, CpuTicks>,
+    last_counts: Option<([u64; 3], Instant)>,
+}
+
+impl CpuStatReader {
+    /// 全体・コアごと（CPU 番号と内訳）・カーネルの回数。初回と累計を読めない OS では None と空
+    pub fn collect(
+        &mut self,
+    ) -> (
+        Option<CpuBreakdown>,
+        Vec<(usize, CpuBreakdown)>,
+        Option<KernelRates>,
+    ) {
+        let counters = Native.cpu_counters();
+        let rates = counters.kernel.and_then(|counts| self.rates(counts));
+        let total = counters.total.and_then(|ticks| self.breakdown(None, ticks));
+        let cores = counters
+            .cores
+            .into_iter()
+            .filter_map(|(cpu, ticks)| Some((cpu, self.breakdown(Some(cpu), ticks)?)))
+            .collect();
+        (total, cores, rates)
+    }
+
+    fn breakdown(&mut self, cpu: Option<usize>, now: CpuTicks) -> Option<CpuBreakdown> {
+        let last = self.last.insert(cpu, now)?;
+        let delta: Vec<u64> = now
+            .iter()
+            .zip(last)
+            .map(|(now, last)| now.saturating_sub(last))
+            .collect();
+        let total: u64 = delta.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let percent = |i: usize| delta[i] as f64 * 100.0 / total as f64;
+        Some(CpuBreakdown {
+            user: percent(0),
+            system: percent(1),
+            irq: percent(2),
+            softirq: percent(3),
+            iowait: percent(4),
+            steal: percent(5),
+            guest: percent(6),
+            idle: percent(7),
+        })
+    }
+
+    fn rates(&mut self, now: [u64; 3]) -> Option<KernelRates> {
+        let (last, at) = self.last_counts.replace((now, Instant::now()))?;
+        let secs = at.elapsed().as_secs_f64();
+        if secs <= 0.0 {
+            return None;
+        }
+        let rate = |i: usize| now[i].saturating_sub(last[i]) as f64 / secs;
+        Some(KernelRates {
+            context_switches: rate(0),
+            interrupts: rate(1),
+            forks: rate(2),
+        })
+    }
+}
+
+/// /proc/stat の "cpu"、"cpu0" などの行（単位は USER_HZ）と、"ctxt 123"、"intr 456 ..."
+/// （先頭が合計）、"processes 789" の行
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn parse_stat(text: &str) -> CpuCounters {
+    let mut counters = CpuCounters::default();
+    let mut counts = [None; 3];
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        let Some(name) = fields.next() else {
+            continue;
+        };
+        let slot = match name {
+            "ctxt" => &mut counts[0],
+            "intr" => &mut counts[1],
+            "processes" => &mut counts[2],
+            _ if name.starts_with("cpu") => {
+                let ticks = ticks(fields);
+                match name
+                    .strip_prefix("cpu")
+                    .and_then(|n| n.parse::<usize>().ok())
+                {
+                    Some(cpu) => counters.cores.push((cpu, ticks)),
+                    None => counters.total = Some(ticks),
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        *slot = fields.next().and_then(|n| n.parse::<u64>().ok());
+    }
+    counters.kernel = (|| Some([counts[0]?, counts[1]?, counts[2]?]))();
+    counters
+}
+
+// user nice system idle iowait irq softirq steal guest guest_nice
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn ticks<'a>(fields: impl Iterator<Item = &'a str>) -> CpuTicks {
+    let fields: Vec<u64> = fields.map(|n| n.parse().unwrap_or(0)).collect();
+    let field = |i: usize| fields.get(i).copied().unwrap_or(0);
+    // user と nice には guest と guest_nice が含まれているので、分けて数える
+    let guest = field(8) + field(9);
+    [
+        (field(0) + field(1)).saturating_sub(guest),
+        field(2),
+        field(5),
+        field(6),
+        field(4),
+        field(7),
+        guest,
+        field(3),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_stat;
+
+    #[test]
+    fn counters_move_guest_time_out_of_user() {
+        let stat = "cpu  100 20 30 400 5 6 7 8 40 10\n\
+                    cpu0 60 10 15 200 2 3 4 4 20 5\n\
+                    cpu3 40 10 15 200 3 3 3 4 20 5\n\
+                    intr 900 1 2 3\n\
+                    ctxt 1234\n\
+                    btime 1700000000\n\
+                    processes 56\n";
+        let counters = parse_stat(stat);
+        // user と nice（120）から guest と guest_nice（50）を引く
+        assert_eq!(counters.total, Some([70, 30, 6, 7, 5, 8, 50, 400]));
+        // 止めてある CPU は飛ぶので、番号は行の順ではなく名前から取る
+        let cores: Vec<usize> = counters.cores.iter().map(|(cpu, _)| *cpu).collect();
+        assert_eq!(cores, [0, 3]);
+        assert_eq!(counters.kernel, Some([1234, 900, 56]));
+
+        // 古いカーネルで列が足りなくても 0 として読み、カウンタが無ければ回数は出さない
+        let counters = parse_stat("cpu 1 2 3 4\n");
+        assert_eq!(counters.total, Some([3, 3, 0, 0, 0, 0, 0, 4]));
+        assert_eq!(counters.kernel, None);
+    }
+}
@@ -0,0 +1,94 @@
+// 設定ファイルの [[panel]]：コマンドの出力やファイルの値を表示するパネル
+//
+// 遅いコマンドがあってもサンプラーや描画を止めないよう、パネルごとにスレッドを起こし、
+// 最後に読んだ結果を共有する。
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::config::{PanelConfig, PanelSource};
+
+/// 読めなかったときはその理由
+type Output = Result<String, String>;
+
+#[derive(Debug)]
+pub struct CustomPanel {
+    pub config: PanelConfig,
+    /// まだ一度も読んでいなければ None
+    output: Arc<Mutex<Option<Output>>>,
+}
+
+impl CustomPanel {
+    pub fn output(&self) -> Option<Output> {
+        self.output.lock().expect("panel output lock").clone()
+    }
+}
+
+pub fn spawn(configs: &[PanelConfig]) -> Vec<CustomPanel> {
+    configs
+        .iter()
+        .map(|config| {
+            let output = Arc::new(Mutex::new(None));
+            let shared = Arc::clone(&output);
+            let source = config.source.clone();
+            let interval = config.interval;
+            thread::spawn(move || {
+                loop {
+                    let result = read(&source);
+                    *shared.lock().expect("panel output lock") = Some(result);
+                    thread::sleep(interval);
+                }
+            });
+            CustomPanel {
+                config: config.clone(),
+                output,
+            }
+        })
+        .collect()
+}
+
+fn read(source: &PanelSource) -> Output {
+    match source {
+        PanelSource::File(path) => {
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))
+        }
+        PanelSource::Command(command) => {
+            let output = shell(command)
+                .stdin(Stdio::null())
+                .output()
+                .map_err(|e| e.to_string())?;
+            if output.status.success() {
+                Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+            } else {
+                // 失敗したときは標準エラーの最初の行を理由にする
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Err(match stderr.lines().next() {
+                    Some(line) => format!("{}: {line}", output.status),
+                    None => output.status.to_string(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+pub fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+/// ゲージに使う値。出力の最初の数値に scale を掛けたもの
+pub fn gauge_value(output: &str, scale: f64) -> Option<f64> {
+    output
+        .split(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .find_map(|word| word.parse::<f64>().ok())
+        .map(|value| value * scale)
+}
@@ -0,0 +1,126 @@
+// 複数のエージェント（[[host]]）の概要を一覧で表示する（--dashboard）
+//
+// ホストごとに接続を張り続け、切れたら間を置いてつなぎ直す。
+// Enter で選んだホストには別に接続して通常の画面を出し、q で一覧に戻る。
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{Terminal, backend::Backend};
+
+use crate::cli::Args;
+use crate::config::{Config, HostConfig};
+use crate::locale::Locale;
+use crate::remote;
+use crate::sampler::{Snapshot, Update};
+use crate::signals;
+use crate::term;
+use crate::ui;
+
+/// 接続が切れてからつなぎ直すまでの時間
+const RETRY: Duration = Duration::from_secs(5);
+
+/// 一覧を描き直す間隔
+const POLL: Duration = Duration::from_millis(500);
+
+/// ホストへの接続の状態
+#[derive(Debug, Clone, Default)]
+pub enum HostStatus {
+    #[default]
+    Connecting,
+    Up(Arc<Snapshot>),
+    /// 接続できなかった、または切れた理由
+    Down(String),
+}
+
+pub fn run<B: Backend>(terminal: &mut Terminal<B>, args: &Args, config: &Config) -> io::Result<()> {
+    let watched: Vec<_> = config.hosts.iter().map(|host| watch(&host.addr)).collect();
+    let locale = Locale::from_config(&config.format);
+    let (colors, ascii) = term::capabilities(args, config);
+    let theme = colors.fit_theme(config.theme);
+    let mut selected = 0;
+    let mut message: Option<String> = None;
+    loop {
+        if signals::quit_requested(None) {
+            return Ok(());
+        }
+        if signals::suspend_requested(None) {
+            signals::suspend(terminal)?;
+        }
+        let hosts: Vec<(&HostConfig, HostStatus)> = config
+            .hosts
+            .iter()
+            .zip(&watched)
+            .map(|(host, status)| (host, status.lock().expect("host status lock").clone()))
+            .collect();
+        terminal.draw(|f| {
+            ui::draw_dashboard(f, &theme, &locale, &hosts, selected, message.as_deref());
+            if ascii {
+                term::asciify(f.buffer_mut());
+            }
+        })?;
+
+        if !event::poll(POLL)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if signals::quit_requested(Some(&key)) {
+            return Ok(());
+        }
+        if signals::suspend_requested(Some(&key)) {
+            signals::suspend(terminal)?;
+            continue;
+        }
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                selected = (selected + 1).min(hosts.len().saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                let host = &config.hosts[selected];
+                // 接続が切れたら一覧に戻り、理由を表示する
+                message = match remote::connect(&host.addr)
+                    .and_then(|sampler| crate::run_view(terminal, args, config, sampler))
+                {
+                    Ok(_) => None,
+                    Err(e) => Some(format!("{}: {e}", host.name)),
+                };
+                terminal.clear()?;
+            }
+            _ => {}
+        }
+    }
+}
+
+// 接続を張り続け、最新の状態を書き込むスレッドを起こす
+fn watch(addr: &str) -> Arc<Mutex<HostStatus>> {
+    let status = Arc::new(Mutex::new(HostStatus::Connecting));
+    let shared = Arc::clone(&status);
+    let addr = addr.to_string();
+    thread::spawn(move || {
+        loop {
+            let down = match remote::connect(&addr) {
+                Ok(handle) => {
+                    for update in handle.updates.iter() {
+                        if let Update::Snapshot(snapshot, _) = update {
+                            *shared.lock().expect("host status lock") = HostStatus::Up(snapshot);
+                        }
+                    }
+                    "connection closed".to_string()
+                }
+                Err(e) => e.to_string(),
+            };
+            *shared.lock().expect("host status lock") = HostStatus::Down(down);
+            thread::sleep(RETRY);
+        }
+    });
+    status
+}
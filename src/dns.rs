@@ -0,0 +1,129 @@
+// 接続一覧の相手のアドレスをホスト名に引く（net 機能、接続一覧の r）
+//
+// 逆引きは応答が無いと数秒待たされるので、描画のスレッドでは引かない。
+// 一度引いたアドレスは覚えておいた結果を返し、初めてのアドレスは別スレッドに頼んで
+// 今回は数字のまま出す。結果（名前が無かったことも含む）は一覧を閉じても捨てない。
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// 同時に引く数。応答の無いサーバーに 1 つ詰まっても残りは進む
+const WORKERS: usize = 4;
+
+/// None は引いている途中か、名前が無かった
+type Cache = Arc<Mutex<HashMap<IpAddr, Option<String>>>>;
+
+#[derive(Debug)]
+pub struct Resolver {
+    cache: Cache,
+    requests: Sender<IpAddr>,
+}
+
+impl Resolver {
+    /// 引くスレッドを立てる
+    pub fn start() -> Self {
+        let cache: Cache = Arc::default();
+        let (requests, queue) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+        for _ in 0..WORKERS {
+            let queue: Arc<Mutex<Receiver<IpAddr>>> = Arc::clone(&queue);
+            let cache = Arc::clone(&cache);
+            // Resolver を捨てると送り手が無くなって終わる
+            thread::spawn(move || {
+                loop {
+                    let Ok(ip) = queue.lock().expect("dns queue lock").recv() else {
+                        return;
+                    };
+                    let name = reverse(ip);
+                    cache.lock().expect("dns cache lock").insert(ip, name);
+                }
+            });
+        }
+        Resolver { cache, requests }
+    }
+
+    /// 引き終わっていれば名前を返す。初めてのアドレスは引くよう頼むだけ
+    pub fn lookup(&self, ip: IpAddr) -> Option<String> {
+        if ip.is_unspecified() {
+            return None;
+        }
+        let mut cache = self.cache.lock().expect("dns cache lock");
+        match cache.get(&ip) {
+            Some(name) => name.clone(),
+            None => {
+                cache.insert(ip, None);
+                self.requests.send(ip).ok();
+                None
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn reverse(ip: IpAddr) -> Option<String> {
+    use std::ffi::CStr;
+    use std::mem::size_of;
+
+    // glibc の NI_MAXHOST
+    let mut host = [0 as libc::c_char; 1025];
+    let lookup = |address: *const libc::sockaddr, length: usize, host: &mut [libc::c_char]| {
+        // SAFETY: address は length バイトの有効なソケットアドレスで、host には長さまでしか書かせない
+        unsafe {
+            libc::getnameinfo(
+                address,
+                length as libc::socklen_t,
+                host.as_mut_ptr(),
+                host.len() as libc::socklen_t,
+                std::ptr::null_mut(),
+                0,
+                libc::NI_NAMEREQD,
+            )
+        }
+    };
+    let result = match ip {
+        IpAddr::V4(ip) => {
+            // SAFETY: sockaddr_in は整数の集まりなので 0 埋めで有効
+            let mut address: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+            address.sin_family = libc::AF_INET as libc::sa_family_t;
+            address.sin_addr.s_addr = u32::from_ne_bytes(ip.octets());
+            #[cfg(target_os = "macos")]
+            {
+                address.sin_len = size_of::<libc::sockaddr_in>() as u8;
+            }
+            lookup(
+                (&raw const address).cast(),
+                size_of::<libc::sockaddr_in>(),
+                &mut host,
+            )
+        }
+        IpAddr::V6(ip) => {
+            // SAFETY: sockaddr_in6 は整数の集まりなので 0 埋めで有効
+            let mut address: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+            address.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            address.sin6_addr.s6_addr = ip.octets();
+            #[cfg(target_os = "macos")]
+            {
+                address.sin6_len = size_of::<libc::sockaddr_in6>() as u8;
+            }
+            lookup(
+                (&raw const address).cast(),
+                size_of::<libc::sockaddr_in6>(),
+                &mut host,
+            )
+        }
+    };
+    if result != 0 {
+        return None;
+    }
+    // SAFETY: 成功したときは NUL 終端の文字列が書かれている
+    let name = unsafe { CStr::from_ptr(host.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+// 接続一覧は Linux でしか集めない
+#[cfg(not(unix))]
+fn reverse(_ip: IpAddr) -> Option<String> {
+    None
+}
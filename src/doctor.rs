@@ -0,0 +1,348 @@
+// 使えるデータソースの一覧（--doctor）
+//
+// 読めないソースのパネルは出さないため、何が足りないのかをここで説明する。
+use std::io::{self, Write};
+
+use crate::access::Access;
+use crate::config::Config;
+use crate::platform::{Native, Platform};
+use crate::sampler::{Collector, Snapshot};
+use crate::termux::Restrictions;
+
+pub struct Check {
+    pub name: &'static str,
+    /// 使えるなら見つかったもの、使えなければその理由
+    pub result: Result<String, String>,
+    /// フィーチャーを有効にしてビルドしたソース。使えなければ起動時に知らせる
+    pub requested: bool,
+}
+
+impl Check {
+    fn new(name: &'static str, result: Result<String, String>) -> Self {
+        Self {
+            name,
+            result,
+            requested: false,
+        }
+    }
+
+    #[cfg_attr(
+        not(any(
+            feature = "gpu",
+            feature = "ipmi",
+            feature = "smart",
+            feature = "net",
+            feature = "docker",
+            feature = "systemd",
+            feature = "apple"
+        )),
+        allow(dead_code)
+    )]
+    fn requested(mut self) -> Self {
+        self.requested = true;
+        self
+    }
+}
+
+// Linux 以外では読まないソースの理由
+fn linux_only(reason: &str) -> String {
+    if cfg!(target_os = "linux") {
+        reason.to_string()
+    } else {
+        "only available on Linux".to_string()
+    }
+}
+
+/// 収集済みのスナップショットから、ソースごとの状態を調べる
+pub fn checks(snapshot: &Snapshot, config: &Config) -> Vec<Check> {
+    let restrictions = Restrictions::probe();
+    let mut checks = vec![
+        Check::new(
+            "CPU usage",
+            if restrictions.cpu {
+                Err("/proc/stat is not readable (restricted on Android)".to_string())
+            } else {
+                Ok(format!("{} CPUs", snapshot.cpu.count))
+            },
+        ),
+        Check::new("CPU time breakdown", {
+            let counters = Native.cpu_counters();
+            if counters.total.is_some() {
+                Ok(format!("{} cores", counters.cores.len()))
+            } else if cfg!(any(target_os = "freebsd", target_os = "openbsd")) {
+                Err("kern.cp_time is not readable".to_string())
+            } else {
+                Err(linux_only("/proc/stat is not readable"))
+            }
+        }),
+        Check::new(
+            "Processes",
+            Ok(format!("{} visible", snapshot.processes.len())),
+        ),
+        Check::new("Permissions", {
+            let access = Access::probe();
+            if access.is_limited() {
+                Err(access.summary())
+            } else {
+                Ok("every source is readable".to_string())
+            }
+        }),
+        Check::new(
+            "Battery",
+            snapshot
+                .battery
+                .as_ref()
+                .map(|b| format!("{:.0}% {}", b.percent, b.state.label()))
+                .ok_or_else(|| linux_only("no battery in /sys/class/power_supply")),
+        ),
+        Check::new(
+            "cgroup",
+            snapshot
+                .cgroup
+                .as_ref()
+                .map(|_| "cgroup v2 limits".to_string())
+                .ok_or_else(|| linux_only("not in a cgroup v2 hierarchy (/sys/fs/cgroup)")),
+        ),
+        Check::new(
+            "Pressure (PSI)",
+            snapshot
+                .pressure
+                .as_ref()
+                .map(|_| "/proc/pressure".to_string())
+                .ok_or_else(|| linux_only("the kernel has no /proc/pressure (CONFIG_PSI)")),
+        ),
+        Check::new(
+            "NUMA",
+            snapshot
+                .numa
+                .as_ref()
+                .map(|n| format!("{} nodes", n.nodes.len()))
+                .ok_or_else(|| linux_only("a single node in /sys/devices/system/node")),
+        ),
+        Check::new(
+            "tmpfs",
+            snapshot
+                .shm
+                .as_ref()
+                .map(|s| format!("{} mounts", s.mounts.len()))
+                .ok_or_else(|| linux_only("no tmpfs in /proc/self/mounts")),
+        ),
+        Check::new(
+            "zram/zswap",
+            snapshot
+                .compressed_swap
+                .as_ref()
+                .map(|c| {
+                    let zswap = if c.zswap.is_some() { ", zswap" } else { "" };
+                    format!("{} zram devices{zswap}", c.zram.len())
+                })
+                .ok_or_else(|| linux_only("no zram device in use and zswap is off")),
+        ),
+        Check::new(
+            "SoC",
+            snapshot
+                .soc
+                .as_ref()
+                .map(|soc| soc.model.clone())
+                .ok_or_else(|| "not a board with a device tree model".to_string()),
+        ),
+        Check::new(
+            "Fans and power",
+            snapshot
+                .sensors
+                .as_ref()
+                .map(|s| format!("{} fans, {} power sensors", s.fans.len(), s.power.len()))
+                .ok_or_else(|| linux_only("no fan or power sensor in /sys/class/hwmon")),
+        ),
+    ];
+
+    #[cfg(feature = "gpu")]
+    checks.push(
+        Check::new(
+            "NVIDIA GPU",
+            snapshot
+                .gpu
+                .as_ref()
+                .map(|gpu| format!("{} GPU(s)", gpu.gpus.len()))
+                .ok_or_else(|| "nvidia-smi is not installed or found no GPU".to_string()),
+        )
+        .requested(),
+    );
+    #[cfg(not(feature = "gpu"))]
+    checks.push(Check::new("NVIDIA GPU", Err(not_built("gpu"))));
+
+    #[cfg(feature = "ipmi")]
+    checks.push(
+        Check::new(
+            "IPMI sensors",
+            snapshot
+                .ipmi
+                .as_ref()
+                .map(|ipmi| format!("{} sensors", ipmi.sensors.len()))
+                .ok_or_else(|| {
+                    "ipmi-sensors / ipmitool is missing or cannot reach the BMC (try as root)"
+                        .to_string()
+                }),
+        )
+        .requested(),
+    );
+    #[cfg(not(feature = "ipmi"))]
+    checks.push(Check::new("IPMI sensors", Err(not_built("ipmi"))));
+
+    #[cfg(feature = "smart")]
+    checks.push(
+        Check::new(
+            "SMART disks",
+            snapshot
+                .smart
+                .as_ref()
+                .map(|smart| format!("{} disk(s)", smart.disks.len()))
+                .ok_or_else(|| {
+                    "smartctl is missing or cannot open the disks (try as root)".to_string()
+                }),
+        )
+        .requested(),
+    );
+    #[cfg(not(feature = "smart"))]
+    checks.push(Check::new("SMART disks", Err(not_built("smart"))));
+
+    #[cfg(feature = "apple")]
+    checks.push(
+        Check::new(
+            "Apple Silicon",
+            snapshot
+                .apple
+                .as_ref()
+                .map(|apple| {
+                    let power = if apple.power.is_some() {
+                        "package power"
+                    } else {
+                        "no power (run as root for powermetrics)"
+                    };
+                    format!("{} cores, {power}", apple.cores.len())
+                })
+                .ok_or_else(|| "not an Apple Silicon Mac (no hw.perflevel0)".to_string()),
+        )
+        .requested(),
+    );
+    #[cfg(not(feature = "apple"))]
+    checks.push(Check::new("Apple Silicon", Err(not_built("apple"))));
+
+    #[cfg(feature = "docker")]
+    checks.push(
+        Check::new(
+            "Docker names",
+            crate::container::check_docker().map(|()| "container names from Docker".to_string()),
+        )
+        .requested(),
+    );
+    #[cfg(not(feature = "docker"))]
+    checks.push(Check::new("Docker names", Err(not_built("docker"))));
+
+    #[cfg(feature = "net")]
+    checks.push(
+        Check::new(
+            "Connections",
+            if restrictions.net {
+                Err("/proc/net is not readable (restricted on Android)".to_string())
+            } else if snapshot.connections.is_empty() {
+                Err(linux_only("no sockets found in /proc/net"))
+            } else {
+                Ok(format!("{} sockets", snapshot.connections.len()))
+            },
+        )
+        .requested(),
+    );
+    #[cfg(not(feature = "net"))]
+    checks.push(Check::new("Connections", Err(not_built("net"))));
+
+    #[cfg(feature = "systemd")]
+    checks.push(
+        Check::new(
+            "Services",
+            if snapshot.services.is_empty() {
+                Err(linux_only("systemctl is missing or systemd is not running"))
+            } else {
+                Ok(format!("{} systemd services", snapshot.services.len()))
+            },
+        )
+        .requested(),
+    );
+    #[cfg(windows)]
+    checks.push(Check::new(
+        "Services",
+        if snapshot.services.is_empty() {
+            Err("the service control manager cannot be opened".to_string())
+        } else {
+            Ok(format!("{} Windows services", snapshot.services.len()))
+        },
+    ));
+    #[cfg(not(any(feature = "systemd", windows)))]
+    checks.push(Check::new("Services", Err(not_built("systemd"))));
+
+    #[cfg(feature = "history")]
+    checks.push(Check::new(
+        "History",
+        config
+            .history
+            .path
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .ok_or_else(|| "[history] path is not set in the config file".to_string()),
+    ));
+    #[cfg(not(feature = "history"))]
+    {
+        let _ = config;
+        checks.push(Check::new("History", Err(not_built("history"))));
+    }
+    checks
+}
+
+#[cfg_attr(
+    all(
+        feature = "gpu",
+        feature = "ipmi",
+        feature = "smart",
+        feature = "net",
+        feature = "docker",
+        feature = "systemd",
+        feature = "apple",
+        feature = "history"
+    ),
+    allow(dead_code)
+)]
+fn not_built(feature: &str) -> String {
+    format!("not built in (build with `--features {feature}`)")
+}
+
+/// 有効にしたのに使えなかったソースを 1 行で知らせる
+pub fn startup_notice(snapshot: &Snapshot, config: &Config) -> Option<String> {
+    let missing: Vec<&str> = checks(snapshot, config)
+        .into_iter()
+        .filter(|check| check.requested && check.result.is_err())
+        .map(|check| check.name)
+        .collect();
+    (!missing.is_empty()).then(|| {
+        format!(
+            "Not available: {} (run rust-top --doctor for details)",
+            missing.join(", ")
+        )
+    })
+}
+
+/// すべてのソースを 1 回読み、結果を表にして出力する
+pub fn run(config: &Config) -> io::Result<()> {
+    let mut collector = Collector::new();
+    collector.collect_all();
+    let checks = checks(collector.snapshot(), config);
+    let width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    let mut out = io::stdout().lock();
+    for check in &checks {
+        let (mark, text) = match &check.result {
+            Ok(found) => ("ok", found),
+            Err(reason) => ("--", reason),
+        };
+        writeln!(out, "[{mark}] {:width$}  {text}", check.name)?;
+    }
+    Ok(())
+}
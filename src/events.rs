@@ -0,0 +1,112 @@
+// 出来事の記録（L）：プロセスの起動と終了、警告、送ったシグナル、収集や操作の失敗
+//
+// 画面は今の値しか見せないので、少し前に何があったかをここに残す。
+// 直近の [events] keep 件（既定 1000）だけを覚えておき、一覧の s でファイルに書き出せる。
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::locale::{self, Locale};
+
+/// [events] keep の既定
+pub const KEEP: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Started,
+    Exited,
+    /// [[alert]]、タイマー
+    Alert,
+    /// シグナルや nice 値の変更など、プロセスへの操作（watchdog も含む）
+    Action,
+    Error,
+}
+
+impl EventKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            EventKind::Started => "start",
+            EventKind::Exited => "exit",
+            EventKind::Alert => "alert",
+            EventKind::Action => "action",
+            EventKind::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// UNIX 時刻（秒）
+    pub time: u64,
+    pub kind: EventKind,
+    pub text: String,
+}
+
+#[derive(Debug)]
+pub struct EventLog {
+    events: VecDeque<Event>,
+    keep: usize,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        EventLog::new(KEEP)
+    }
+}
+
+impl EventLog {
+    pub fn new(keep: usize) -> Self {
+        EventLog {
+            events: VecDeque::new(),
+            keep,
+        }
+    }
+
+    pub fn push(&mut self, kind: EventKind, text: String) {
+        if self.keep == 0 {
+            return;
+        }
+        if self.events.len() == self.keep {
+            self.events.pop_front();
+        }
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.events.push_back(Event { time, kind, text });
+    }
+
+    /// 古い順
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Event> + ExactSizeIterator {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// 1 行に 1 件ずつ書いたテキスト
+    pub fn text(&self, locale: &Locale) -> String {
+        let mut out = String::new();
+        for event in &self.events {
+            out.push_str(&format!(
+                "{} {:<6} {}\n",
+                locale.datetime(event.time),
+                event.kind.label(),
+                event.text
+            ));
+        }
+        out
+    }
+
+    /// カレントディレクトリに rust-top-events-<時刻>.log として保存し、そのパスを返す
+    pub fn save(&self, locale: &Locale) -> io::Result<PathBuf> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let path = PathBuf::from(format!("rust-top-events-{}.log", locale::file_stamp(now)));
+        fs::write(&path, self.text(locale))?;
+        Ok(path)
+    }
+}
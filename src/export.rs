@@ -0,0 +1,264 @@
+// 画面の内容をファイルに保存する（S キー）
+//
+// text と html は描画し終えたバッファをそのまま書き出すので、見えているものと同じになる。
+// csv と json はプロセス表を、画面と同じ絞り込み・並び順・列で、収まらない行も含めて書き出す。
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ratatui::buffer::Buffer;
+use ratatui::style::{Color, Modifier};
+use unicode_width::UnicodeWidthStr;
+
+use crate::app::App;
+use crate::batch::{self, Format};
+use crate::locale;
+use crate::sampler::Snapshot;
+use crate::ui;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Text,
+    /// 色と太字を残した HTML
+    Html,
+    /// プロセス表のすべての行
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "text" | "txt" => Some(ExportFormat::Text),
+            "html" => Some(ExportFormat::Html),
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// 画面ではなくプロセス表を書き出す形式
+    pub fn is_table(self) -> bool {
+        matches!(self, ExportFormat::Csv | ExportFormat::Json)
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Text => "txt",
+            ExportFormat::Html => "html",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+// カレントディレクトリの rust-top-<時刻>.<拡張子>
+fn stamped_path(format: ExportFormat) -> PathBuf {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    PathBuf::from(format!(
+        "rust-top-{}.{}",
+        locale::file_stamp(now),
+        format.extension()
+    ))
+}
+
+/// カレントディレクトリに rust-top-<時刻>.txt（.html）として保存し、そのパスを返す
+pub fn save(buffer: &Buffer, format: ExportFormat) -> io::Result<PathBuf> {
+    let path = stamped_path(format);
+    let contents = match format {
+        ExportFormat::Text | ExportFormat::Csv | ExportFormat::Json => text(buffer),
+        ExportFormat::Html => html(buffer),
+    };
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+/// プロセス表を rust-top-<時刻>.csv（.json）として保存し、そのパスと行数を返す
+pub fn save_processes(
+    app: &App,
+    snapshot: &Snapshot,
+    format: ExportFormat,
+) -> io::Result<(PathBuf, usize)> {
+    let mut processes = app.visible_processes(snapshot);
+    // Space で印を付けていれば、その行だけ
+    if !app.tagged.is_empty() {
+        processes.retain(|p| app.is_tagged(p.pid));
+    }
+    let columns: Vec<_> = ui::table_columns(app, snapshot)
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    let table = if format == ExportFormat::Json {
+        Format::Json
+    } else {
+        Format::Csv
+    };
+    let path = stamped_path(format);
+    let mut out = io::BufWriter::new(fs::File::create(&path)?);
+    batch::write_table(&mut out, table, app, snapshot, &processes, &columns)?;
+    out.flush()?;
+    Ok((path, processes.len()))
+}
+
+// 行ごとのセル。全角文字の後ろの埋め草のセルは飛ばす
+fn rows(buffer: &Buffer) -> impl Iterator<Item = Vec<&ratatui::buffer::Cell>> {
+    let area = buffer.area;
+    (area.top()..area.bottom()).map(move |y| {
+        let mut cells = Vec::new();
+        let mut x = area.left();
+        while x < area.right() {
+            let cell = &buffer[(x, y)];
+            cells.push(cell);
+            x += (cell.symbol().width() as u16).max(1);
+        }
+        cells
+    })
+}
+
+fn text(buffer: &Buffer) -> String {
+    let mut out = String::new();
+    for row in rows(buffer) {
+        let line: String = row.iter().map(|cell| cell.symbol()).collect();
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn html(buffer: &Buffer) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>rust-top</title>\n</head>\n\
+         <body style=\"background:#000\">\n<pre style=\"color:#ccc;background:#000;font-family:monospace\">",
+    );
+    for row in rows(buffer) {
+        // 同じ見た目のセルを 1 つの span にまとめる
+        let mut current: Option<String> = None;
+        let mut run = String::new();
+        for cell in row {
+            let style = css(cell.fg, cell.bg, cell.modifier);
+            if current.as_ref() != Some(&style) {
+                flush(&mut out, current.as_deref(), &run);
+                run.clear();
+                current = Some(style);
+            }
+            run.push_str(cell.symbol());
+        }
+        let trimmed = run.trim_end().len();
+        run.truncate(trimmed);
+        flush(&mut out, current.as_deref(), &run);
+        out.push('\n');
+    }
+    out.push_str("</pre>\n</body>\n</html>\n");
+    out
+}
+
+fn flush(out: &mut String, style: Option<&str>, run: &str) {
+    if run.is_empty() {
+        return;
+    }
+    let escaped = escape(run);
+    match style {
+        Some(style) if !style.is_empty() => {
+            out.push_str(&format!("<span style=\"{style}\">{escaped}</span>"));
+        }
+        _ => out.push_str(&escaped),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// 反転は前景と背景を入れ替える（既定色は pre の色）
+fn css(fg: Color, bg: Color, modifier: Modifier) -> String {
+    let (mut fg, mut bg) = (rgb(fg), rgb(bg));
+    if modifier.contains(Modifier::REVERSED) {
+        (fg, bg) = (
+            Some(bg.unwrap_or("#000000".to_string())),
+            Some(fg.unwrap_or("#cccccc".to_string())),
+        );
+    }
+    let mut style = Vec::new();
+    if let Some(fg) = fg {
+        style.push(format!("color:{fg}"));
+    }
+    if let Some(bg) = bg {
+        style.push(format!("background:{bg}"));
+    }
+    if modifier.contains(Modifier::BOLD) {
+        style.push("font-weight:bold".to_string());
+    }
+    if modifier.contains(Modifier::DIM) {
+        style.push("opacity:0.6".to_string());
+    }
+    if modifier.contains(Modifier::UNDERLINED) {
+        style.push("text-decoration:underline".to_string());
+    }
+    style.join(";")
+}
+
+// xterm の既定の配色
+const ANSI: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+fn rgb(color: Color) -> Option<String> {
+    let (r, g, b) = match color {
+        Color::Reset => return None,
+        Color::Black => ANSI[0],
+        Color::Red => ANSI[1],
+        Color::Green => ANSI[2],
+        Color::Yellow => ANSI[3],
+        Color::Blue => ANSI[4],
+        Color::Magenta => ANSI[5],
+        Color::Cyan => ANSI[6],
+        Color::Gray => ANSI[7],
+        Color::DarkGray => ANSI[8],
+        Color::LightRed => ANSI[9],
+        Color::LightGreen => ANSI[10],
+        Color::LightYellow => ANSI[11],
+        Color::LightBlue => ANSI[12],
+        Color::LightMagenta => ANSI[13],
+        Color::LightCyan => ANSI[14],
+        Color::White => ANSI[15],
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(i) => indexed(i),
+    };
+    Some(format!("#{r:02x}{g:02x}{b:02x}"))
+}
+
+// 256 色: 16 色、6x6x6 のキューブ、24 段階のグレー
+fn indexed(i: u8) -> (u8, u8, u8) {
+    match i {
+        0..=15 => ANSI[i as usize],
+        16..=231 => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+            let n = i - 16;
+            (level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        _ => {
+            let gray = 8 + (i - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
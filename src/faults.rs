@@ -0,0 +1,84 @@
+// プロセスごとのページフォールトの速さとスワップの量（累計は platform、スワップは /proc/<pid>/status）
+//
+// メジャーフォールト（ディスクから読み直したページ）が続いているプロセスはスワップで詰まっている。
+// 累計しか読めないので、前回の収集との差を 1 秒あたりにする。スワップの量は smaps_rollup より軽い
+// status の VmSwap から読む。フォールトの列（m）を出しているときだけ読む。
+use std::collections::HashMap;
+use std::time::Instant;
+
+use sysinfo::Pid;
+
+use crate::platform::{Native, Platform};
+
+/// 1 秒あたりのフォールトの数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultRates {
+    pub minor: u64,
+    pub major: u64,
+}
+
+/// 前回の累計を覚えておいて速さを出す
+#[derive(Debug, Default)]
+pub struct FaultCounter {
+    /// 前回の収集での（起動時刻、マイナー、メジャー）
+    last: HashMap<Pid, (u64, u64, u64)>,
+    current: HashMap<Pid, (u64, u64, u64)>,
+    last_at: Option<Instant>,
+    /// 前回の収集からの秒数。初回は None
+    secs: Option<f64>,
+}
+
+impl FaultCounter {
+    /// 収集の始めに呼ぶ
+    pub fn begin(&mut self, now: Instant) {
+        self.secs = self
+            .last_at
+            .replace(now)
+            .map(|at| now.duration_since(at).as_secs_f64())
+            .filter(|secs| *secs > 0.0);
+        self.last = std::mem::take(&mut self.current);
+    }
+
+    /// 読めなければ（他のユーザーのプロセスなど）None。初回や PID が使い回されたときも None
+    pub fn rates(&mut self, pid: Pid, start_time: u64) -> Option<FaultRates> {
+        let (minor, major) = Native.page_faults(pid)?;
+        self.current.insert(pid, (start_time, minor, major));
+        let secs = self.secs?;
+        let &(started, last_minor, last_major) = self.last.get(&pid)?;
+        if started != start_time {
+            return None;
+        }
+        let rate = |now: u64, last: u64| (now.saturating_sub(last) as f64 / secs).round() as u64;
+        Some(FaultRates {
+            minor: rate(minor, last_minor),
+            major: rate(major, last_major),
+        })
+    }
+}
+
+/// stat の 10 番目（minflt）と 12 番目（majflt）。名前に空白や括弧が入ることがあるので最後の ")" から数える
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn parse_stat(text: &str) -> Option<(u64, u64)> {
+    let (_, rest) = text.rsplit_once(')')?;
+    // rest は 3 番目の state から始まる
+    let mut fields = rest.split_whitespace();
+    let minor = fields.nth(7)?.parse().ok()?;
+    let major = fields.nth(1)?.parse().ok()?;
+    Some((minor, major))
+}
+
+/// スワップに出た量（バイト）
+#[cfg(target_os = "linux")]
+pub fn swap(pid: Pid) -> Option<u64> {
+    let text = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    text.lines()
+        .find_map(|line| line.strip_prefix("VmSwap:"))
+        .and_then(|value| value.trim().strip_suffix("kB"))
+        .and_then(|kib| kib.trim().parse::<u64>().ok())
+        .map(|kib| kib * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn swap(_pid: Pid) -> Option<u64> {
+    None
+}
@@ -0,0 +1,163 @@
+// プロセスが開いているファイル（Linux の /proc/<pid>/fd）
+//
+// 数えるだけでもリンクを 1 つずつたどるので、FDs 列（D）を出しているときだけ数える。
+// 一覧（f）は開いたときと、表を更新したときに読み直す。
+// :file はその逆で、全プロセスの fd からそのファイルを開いているものを探す。
+use std::io;
+use std::path::Path;
+
+use sysinfo::Pid;
+
+/// lsof の 1 行に当たるもの
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenFile {
+    pub fd: u32,
+    /// lsof の TYPE 列と同じ表記（REG / DIR / CHR / FIFO / sock / TCP など）
+    pub kind: &'static str,
+    /// パス、またはソケットのアドレス
+    pub name: String,
+}
+
+/// 開いているファイルの数。読めなければ None（他人のプロセスや Linux 以外）
+#[cfg(target_os = "linux")]
+pub fn count(pid: Pid) -> Option<usize> {
+    std::fs::read_dir(format!("/proc/{pid}/fd"))
+        .ok()
+        .map(|entries| entries.count())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn count(_pid: Pid) -> Option<usize> {
+    None
+}
+
+/// fd の番号順に返す
+#[cfg(target_os = "linux")]
+pub fn list(pid: Pid) -> io::Result<Vec<OpenFile>> {
+    let sockets = Sockets::read();
+    let mut files: Vec<OpenFile> = std::fs::read_dir(format!("/proc/{pid}/fd"))?
+        .flatten()
+        .filter_map(|entry| {
+            let fd = entry.file_name().to_str()?.parse().ok()?;
+            // 一覧を読んでいる間に閉じられたものは飛ばす
+            let target = std::fs::read_link(entry.path()).ok()?;
+            let target = target.to_string_lossy();
+            let (kind, name) = describe(&entry.path(), &target, &sockets);
+            Some(OpenFile { fd, kind, name })
+        })
+        .collect();
+    files.sort_by_key(|file| file.fd);
+    Ok(files)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn list(_pid: Pid) -> io::Result<Vec<OpenFile>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "open files are only listed on Linux",
+    ))
+}
+
+/// path を開いているプロセスを PID の順に返す。他のユーザーのプロセスの fd は読めないので含まない
+#[cfg(target_os = "linux")]
+pub fn holders(path: &Path) -> io::Result<Vec<Pid>> {
+    use std::str::FromStr;
+
+    // fd のリンク先はシンボリックリンクをたどった絶対パスになっている
+    let path = std::fs::canonicalize(path)?;
+    let mut pids: Vec<Pid> = std::fs::read_dir("/proc")?
+        .flatten()
+        .filter_map(|entry| {
+            let pid = Pid::from_str(entry.file_name().to_str()?).ok()?;
+            let fds = std::fs::read_dir(entry.path().join("fd")).ok()?;
+            fds.flatten()
+                .any(|fd| std::fs::read_link(fd.path()).is_ok_and(|target| target == path))
+                .then_some(pid)
+        })
+        .collect();
+    pids.sort();
+    Ok(pids)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn holders(_path: &Path) -> io::Result<Vec<Pid>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "open files are only searched on Linux",
+    ))
+}
+
+// リンク先が "socket:[123]" / "pipe:[456]" / "anon_inode:[eventfd]" / パスのどれかで種類を決める
+#[cfg(target_os = "linux")]
+fn describe(link: &std::path::Path, target: &str, sockets: &Sockets) -> (&'static str, String) {
+    use std::os::unix::fs::FileTypeExt;
+
+    let inode = |prefix: &str| {
+        target
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_prefix('['))
+            .and_then(|rest| rest.strip_suffix(']'))
+            .and_then(|inode| inode.parse::<u64>().ok())
+    };
+    if let Some(inode) = inode("socket:") {
+        return sockets
+            .describe(inode)
+            .unwrap_or(("sock", target.to_string()));
+    }
+    if target.starts_with("pipe:") {
+        return ("FIFO", target.to_string());
+    }
+    if let Some(name) = target.strip_prefix("anon_inode:") {
+        return ("a_inode", name.to_string());
+    }
+    // リンクをたどった先の種類。消されたファイルは "(deleted)" が付いて読めない
+    let kind = match std::fs::metadata(link).map(|m| m.file_type()) {
+        Ok(t) if t.is_dir() => "DIR",
+        Ok(t) if t.is_char_device() => "CHR",
+        Ok(t) if t.is_block_device() => "BLK",
+        Ok(t) if t.is_fifo() => "FIFO",
+        Ok(t) if t.is_socket() => "sock",
+        _ => "REG",
+    };
+    (kind, target.to_string())
+}
+
+// TCP / UDP のソケットは inode からアドレスを引く（net 機能があるときだけ）
+#[cfg(all(target_os = "linux", feature = "net"))]
+struct Sockets(std::collections::HashMap<u64, crate::net::Connection>);
+
+#[cfg(all(target_os = "linux", feature = "net"))]
+impl Sockets {
+    fn read() -> Self {
+        Sockets(
+            crate::net::sockets()
+                .into_iter()
+                .map(|(connection, inode)| (inode, connection))
+                .collect(),
+        )
+    }
+
+    fn describe(&self, inode: u64) -> Option<(&'static str, String)> {
+        let c = self.0.get(&inode)?;
+        let name = if c.remote.port() == 0 {
+            format!("{} ({})", c.local, c.state)
+        } else {
+            format!("{}->{} ({})", c.local, c.remote, c.state)
+        };
+        Some((c.protocol.label(), name))
+    }
+}
+
+#[cfg(all(target_os = "linux", not(feature = "net")))]
+struct Sockets;
+
+#[cfg(all(target_os = "linux", not(feature = "net")))]
+impl Sockets {
+    fn read() -> Self {
+        Sockets
+    }
+
+    fn describe(&self, _inode: u64) -> Option<(&'static str, String)> {
+        None
+    }
+}
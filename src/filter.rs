@@ -0,0 +1,496 @@
+// 絞り込みの式（:filter と --filter）
+//
+//   cpu > 50 && user == "postgres"
+//   name ~ "python.*worker" || !(state == sleeping)
+//
+// 演算子を含まない文字列は、これまでどおり名前かコマンドラインに一致するパターンとして扱う。
+use std::fmt;
+
+use sysinfo::Pid;
+
+use crate::alert;
+use crate::pattern::Pattern;
+use crate::sampler::ProcessInfo;
+use crate::ui;
+
+/// 式として読む目印。どれも無ければ全体をパターンにする
+const OPERATOR_CHARS: &[char] = &['=', '<', '>', '~', '&', '(', ')', '"', '!'];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Filter {
+    pub fn parse(source: &str) -> Result<Filter, String> {
+        let source = source.trim();
+        let expr = if source.contains(OPERATOR_CHARS) || source.contains("||") {
+            let tokens = tokenize(source)?;
+            let mut parser = Parser {
+                tokens: &tokens,
+                next: 0,
+                end: source.chars().count(),
+            };
+            let expr = parser.or()?;
+            if let Some(token) = parser.tokens.get(parser.next) {
+                return Err(format!(
+                    "expected && or || before `{}` at column {}",
+                    token.kind, token.column
+                ));
+            }
+            expr
+        } else {
+            Expr::Text(Pattern::new(source)?)
+        };
+        Ok(Filter {
+            source: source.to_string(),
+            expr,
+        })
+    }
+
+    /// cpu は表に出している値（Irix / Solaris モードで換算したもの）
+    pub fn matches(&self, process: &ProcessInfo, cpu: f64) -> bool {
+        self.expr.eval(process, cpu)
+    }
+
+    /// 名前やコマンドラインに一致させたパターン。表の中で一致した部分を強調するのに使う
+    pub fn name_patterns(&self) -> Vec<&Pattern> {
+        let mut patterns = Vec::new();
+        self.expr.name_patterns(&mut patterns);
+        patterns
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Pid,
+    Parent,
+    Name,
+    Cmd,
+    User,
+    Container,
+    State,
+    Threads,
+    Cpu,
+    Memory,
+    Virtual,
+    Shared,
+    Swap,
+    Time,
+}
+
+const FIELDS: &[(&str, Field)] = &[
+    ("pid", Field::Pid),
+    ("ppid", Field::Parent),
+    ("name", Field::Name),
+    ("cmd", Field::Cmd),
+    ("command", Field::Cmd),
+    ("user", Field::User),
+    ("container", Field::Container),
+    ("state", Field::State),
+    ("threads", Field::Threads),
+    ("thr", Field::Threads),
+    ("cpu", Field::Cpu),
+    ("mem", Field::Memory),
+    ("memory", Field::Memory),
+    ("rss", Field::Memory),
+    ("virt", Field::Virtual),
+    ("shr", Field::Shared),
+    ("swap", Field::Swap),
+    ("time", Field::Time),
+];
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        FIELDS
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, field)| *field)
+    }
+
+    fn is_text(self) -> bool {
+        matches!(
+            self,
+            Field::Name | Field::Cmd | Field::User | Field::Container | Field::State
+        )
+    }
+
+    // 数値の書き方。メモリは単位が無ければ MB、時間は単位が無ければ分
+    fn number(self, text: &str) -> Option<f64> {
+        match self {
+            Field::Memory | Field::Virtual | Field::Shared | Field::Swap => {
+                match text.parse::<f64>() {
+                    Ok(mb) => Some(mb * 1024.0 * 1024.0),
+                    Err(_) => alert::parse_bytes(text),
+                }
+            }
+            Field::Time => match text.parse::<f64>() {
+                Ok(minutes) => Some(minutes * 60.0),
+                Err(_) => alert::parse_duration(text).map(|d| d.as_secs_f64()),
+            },
+            Field::Cpu => text.trim_end_matches('%').parse().ok(),
+            _ => text.parse().ok(),
+        }
+    }
+
+    fn text(self, p: &ProcessInfo) -> &str {
+        match self {
+            Field::Name => &p.name,
+            Field::Cmd => &p.cmd,
+            Field::User => &p.user,
+            Field::Container => p.container.as_deref().unwrap_or(""),
+            _ => ui::state_label(p.status),
+        }
+    }
+
+    // 分からない値（読めなかった SHR など）は None
+    fn value(self, p: &ProcessInfo, cpu: f64) -> Option<f64> {
+        Some(match self {
+            Field::Pid => p.pid.as_u32() as f64,
+            Field::Parent => p.parent.map_or(0, Pid::as_u32) as f64,
+            Field::Threads => p.threads as f64,
+            Field::Cpu => cpu,
+            Field::Memory => p.memory as f64,
+            Field::Virtual => p.virtual_memory as f64,
+            Field::Shared => p.shared? as f64,
+            Field::Swap => p.swap? as f64,
+            Field::Time => p.cpu_time as f64 / 1000.0,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Match,
+    NotMatch,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    /// 名前かコマンドラインに一致する
+    Text(Pattern),
+    Number {
+        field: Field,
+        op: Op,
+        value: f64,
+    },
+    Equals {
+        field: Field,
+        value: String,
+        negated: bool,
+    },
+    Matches {
+        field: Field,
+        pattern: Pattern,
+        negated: bool,
+    },
+}
+
+impl Expr {
+    // 否定した側は一致しないプロセスを残すので、強調するものが無い
+    fn name_patterns<'a>(&'a self, out: &mut Vec<&'a Pattern>) {
+        match self {
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                a.name_patterns(out);
+                b.name_patterns(out);
+            }
+            Expr::Text(pattern) => out.push(pattern),
+            Expr::Matches {
+                field: Field::Name | Field::Cmd,
+                pattern,
+                negated: false,
+            } => out.push(pattern),
+            _ => {}
+        }
+    }
+
+    fn eval(&self, p: &ProcessInfo, cpu: f64) -> bool {
+        match self {
+            Expr::And(a, b) => a.eval(p, cpu) && b.eval(p, cpu),
+            Expr::Or(a, b) => a.eval(p, cpu) || b.eval(p, cpu),
+            Expr::Not(e) => !e.eval(p, cpu),
+            Expr::Text(pattern) => pattern.is_match(&p.name) || pattern.is_match(&p.cmd),
+            Expr::Number { field, op, value } => {
+                let Some(actual) = field.value(p, cpu) else {
+                    return false;
+                };
+                match op {
+                    Op::Eq => actual == *value,
+                    Op::Ne => actual != *value,
+                    Op::Gt => actual > *value,
+                    Op::Ge => actual >= *value,
+                    Op::Lt => actual < *value,
+                    Op::Le => actual <= *value,
+                    Op::Match | Op::NotMatch => false,
+                }
+            }
+            // 状態は Running / running のどちらでもよい
+            Expr::Equals {
+                field,
+                value,
+                negated,
+            } => {
+                let text = field.text(p);
+                let equal = if *field == Field::State {
+                    text.eq_ignore_ascii_case(value)
+                } else {
+                    text == value
+                };
+                equal != *negated
+            }
+            Expr::Matches {
+                field,
+                pattern,
+                negated,
+            } => pattern.is_match(field.text(p)) != *negated,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Word(String),
+    Quoted(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Word(word) => f.write_str(word),
+            Kind::Quoted(text) => write!(f, "\"{text}\""),
+            Kind::Op(op) => f.write_str(match op {
+                Op::Eq => "==",
+                Op::Ne => "!=",
+                Op::Gt => ">",
+                Op::Ge => ">=",
+                Op::Lt => "<",
+                Op::Le => "<=",
+                Op::Match => "~",
+                Op::NotMatch => "!~",
+            }),
+            Kind::And => f.write_str("&&"),
+            Kind::Or => f.write_str("||"),
+            Kind::Not => f.write_str("!"),
+            Kind::Open => f.write_str("("),
+            Kind::Close => f.write_str(")"),
+        }
+    }
+}
+
+struct Token {
+    kind: Kind,
+    /// 1 から数えた文字の位置
+    column: usize,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        let next = chars.get(i + 1).copied();
+        let (kind, len) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('=', Some('=')) => (Kind::Op(Op::Eq), 2),
+            // = 1 つでも比較として受け付ける
+            ('=', _) => (Kind::Op(Op::Eq), 1),
+            ('!', Some('=')) => (Kind::Op(Op::Ne), 2),
+            ('!', Some('~')) => (Kind::Op(Op::NotMatch), 2),
+            ('!', _) => (Kind::Not, 1),
+            ('>', Some('=')) => (Kind::Op(Op::Ge), 2),
+            ('>', _) => (Kind::Op(Op::Gt), 1),
+            ('<', Some('=')) => (Kind::Op(Op::Le), 2),
+            ('<', _) => (Kind::Op(Op::Lt), 1),
+            ('~', _) => (Kind::Op(Op::Match), 1),
+            ('&', Some('&')) => (Kind::And, 2),
+            ('|', Some('|')) => (Kind::Or, 2),
+            ('(', _) => (Kind::Open, 1),
+            (')', _) => (Kind::Close, 1),
+            ('"', _) => {
+                // \" と \\ だけを外し、パターンの \. などはそのまま残す
+                let mut text = String::new();
+                let mut j = i + 1;
+                loop {
+                    match chars.get(j) {
+                        None => return Err(format!("unterminated string at column {column}")),
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.get(j + 1), Some('"' | '\\')) => {
+                            text.push(chars[j + 1]);
+                            j += 2;
+                        }
+                        Some(&c) => {
+                            text.push(c);
+                            j += 1;
+                        }
+                    }
+                }
+                (Kind::Quoted(text), j + 1 - i)
+            }
+            ('&', _) | ('|', _) => {
+                return Err(format!(
+                    "unexpected `{c}` at column {column} (use && or ||)"
+                ));
+            }
+            _ => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|c| !c.is_whitespace() && !"=!<>~&|()\"".contains(**c))
+                    .count();
+                (Kind::Word(chars[i..i + len].iter().collect()), len)
+            }
+        };
+        tokens.push(Token { kind, column });
+        i += len;
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    next: usize,
+    /// 入力の終わりの位置（エラーの表示用）
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Kind> {
+        self.tokens.get(self.next).map(|t| &t.kind)
+    }
+
+    fn advance(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.next)?;
+        self.next += 1;
+        Some(token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map_or(self.end + 1, |t| t.column)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Kind::Or) {
+            self.next += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Kind::And) {
+            self.next += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let column = self.column();
+        let Some(token) = self.advance() else {
+            return Err(format!("expected a condition at column {column}"));
+        };
+        match &token.kind {
+            Kind::Not => Ok(Expr::Not(Box::new(self.unary()?))),
+            Kind::Open => {
+                let expr = self.or()?;
+                let column = self.column();
+                match self.advance() {
+                    Some(Token {
+                        kind: Kind::Close, ..
+                    }) => Ok(expr),
+                    _ => Err(format!("expected `)` at column {column}")),
+                }
+            }
+            Kind::Word(word) => {
+                let word = word.clone();
+                match self.peek() {
+                    Some(Kind::Op(_)) => self.comparison(&word, column),
+                    _ => Ok(Expr::Text(Pattern::new(&word)?)),
+                }
+            }
+            Kind::Quoted(text) => Ok(Expr::Text(Pattern::new(text)?)),
+            other => Err(format!(
+                "expected a condition before `{other}` at column {column}"
+            )),
+        }
+    }
+
+    fn comparison(&mut self, name: &str, column: usize) -> Result<Expr, String> {
+        let field = Field::from_name(name).ok_or_else(|| {
+            let names: Vec<&str> = FIELDS.iter().map(|(n, _)| *n).collect();
+            format!(
+                "unknown field `{name}` at column {column} ({})",
+                names.join(", ")
+            )
+        })?;
+        let op_column = self.column();
+        let Some(Kind::Op(op)) = self.advance().map(|t| t.kind.clone()) else {
+            unreachable!("comparison is called only before an operator");
+        };
+        let column = self.column();
+        let value = match self.advance().map(|t| &t.kind) {
+            Some(Kind::Word(text) | Kind::Quoted(text)) => text.clone(),
+            _ => {
+                return Err(format!(
+                    "expected a value after `{}` at column {column}",
+                    Kind::Op(op)
+                ));
+            }
+        };
+        match (field.is_text(), op) {
+            (false, Op::Match | Op::NotMatch) => Err(format!(
+                "`{name}` is a number; use ==, !=, <, <=, > or >= (column {op_column})"
+            )),
+            (true, Op::Match | Op::NotMatch) => Ok(Expr::Matches {
+                field,
+                pattern: Pattern::new(&value)?,
+                negated: op == Op::NotMatch,
+            }),
+            (true, Op::Eq | Op::Ne) => Ok(Expr::Equals {
+                field,
+                value,
+                negated: op == Op::Ne,
+            }),
+            (true, _) => Err(format!(
+                "`{name}` is text; use ==, != or ~ (column {op_column})"
+            )),
+            (false, _) => {
+                let value = field
+                    .number(&value)
+                    .ok_or_else(|| format!("invalid number `{value}` at column {column}"))?;
+                Ok(Expr::Number { field, op, value })
+            }
+        }
+    }
+}
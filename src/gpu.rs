@@ -0,0 +1,92 @@
+// NVIDIA GPU の情報（nvidia-smi の問い合わせ結果を読む）
+//
+// NVML を直接使う代わりに nvidia-smi を呼ぶ。ドライバと一緒に入るため追加の依存が要らない。
+use std::process::Command;
+
+use sysinfo::Pid;
+
+#[derive(Debug, Clone, Default)]
+pub struct GpuSnapshot {
+    pub gpus: Vec<GpuInfo>,
+    pub processes: Vec<GpuProcess>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GpuInfo {
+    pub index: u32,
+    pub name: String,
+    /// 使用率（%）
+    pub utilization: Option<u32>,
+    /// バイト
+    pub memory_used: Option<u64>,
+    pub memory_total: Option<u64>,
+    /// 摂氏
+    pub temperature: Option<u32>,
+}
+
+/// GPU を使っているプロセス
+#[derive(Debug, Clone)]
+pub struct GpuProcess {
+    pub pid: Pid,
+    /// バイト
+    pub memory_used: Option<u64>,
+}
+
+/// GPU が無い（nvidia-smi が使えない）ときは None
+pub fn collect() -> Option<GpuSnapshot> {
+    let gpus =
+        query("--query-gpu=index,name,utilization.gpu,memory.used,memory.total,temperature.gpu")?
+            .iter()
+            .filter_map(|fields| {
+                Some(GpuInfo {
+                    index: fields.first()?.parse().ok()?,
+                    name: fields.get(1)?.to_string(),
+                    utilization: number(fields.get(2)),
+                    memory_used: mebibytes(fields.get(3)),
+                    memory_total: mebibytes(fields.get(4)),
+                    temperature: number(fields.get(5)),
+                })
+            })
+            .collect::<Vec<_>>();
+    if gpus.is_empty() {
+        return None;
+    }
+    let processes = query("--query-compute-apps=pid,used_memory")
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|fields| {
+            Some(GpuProcess {
+                pid: Pid::from_u32(fields.first()?.parse().ok()?),
+                memory_used: mebibytes(fields.get(1)),
+            })
+        })
+        .collect();
+    Some(GpuSnapshot { gpus, processes })
+}
+
+// 1 行を 1 件として、カンマ区切りの値を返す
+fn query(fields: &str) -> Option<Vec<Vec<String>>> {
+    let output = Command::new("nvidia-smi")
+        .args([fields, "--format=csv,noheader,nounits"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    Some(
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.split(',').map(|f| f.trim().to_string()).collect())
+            .collect(),
+    )
+}
+
+// "[N/A]" や "[Not Supported]" は None になる
+fn number(field: Option<&String>) -> Option<u32> {
+    field?.parse().ok()
+}
+
+fn mebibytes(field: Option<&String>) -> Option<u64> {
+    field?.parse::<u64>().ok().map(|mib| mib * 1024 * 1024)
+}
@@ -0,0 +1,162 @@
+// 起動したときの健康診断
+//
+// 知らないマシンで開いたとき、最初の一覧が届いた時点で目立つ問題をまとめて知らせる。
+// スワップ・ゾンビ・ロードアベレージはスナップショットから、ディスクの空きと温度は
+// 起動時に 1 回だけ読む（スナップショットには入っていない）。
+use sysinfo::{Components, Disks, ProcessStatus};
+
+use crate::locale::Locale;
+use crate::sampler::Snapshot;
+
+/// スワップをこれ以上使っていれば知らせる（%）
+const SWAP_PERCENT: f64 = 80.0;
+/// ディスクがこれ以上埋まっていれば知らせる（%）
+const DISK_PERCENT: f64 = 90.0;
+/// ゾンビがこれ以上あれば知らせる
+const ZOMBIES: usize = 10;
+/// 温度が危険な温度からこの差（°C）より近ければ知らせる
+const TEMPERATURE_MARGIN: f32 = 10.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub name: &'static str,
+    pub detail: String,
+    /// すぐに手を打った方がよいもの（ディスクやスワップがほぼいっぱい、危険な温度を越えた）
+    pub severe: bool,
+}
+
+/// 書き込めるディスクの使用量（バイト）
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskUsage {
+    pub mount: String,
+    pub total: u64,
+    pub available: u64,
+}
+
+/// 温度センサーの値（°C）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Temperature {
+    pub label: String,
+    pub current: f32,
+    pub critical: f32,
+}
+
+/// ディスクと温度の読み取り結果。--mock や他のホストでは空のまま
+#[derive(Debug, Clone, Default)]
+pub struct Probe {
+    pub disks: Vec<DiskUsage>,
+    pub temperatures: Vec<Temperature>,
+}
+
+impl Probe {
+    /// このマシンのディスクと温度センサーを読む。危険な温度の分からないセンサーは省く
+    pub fn local() -> Self {
+        let disks = Disks::new_with_refreshed_list()
+            .iter()
+            // snap などの読み出し専用のイメージはいつも 100% なので見ない
+            .filter(|disk| disk.total_space() > 0 && !disk.is_read_only())
+            .map(|disk| DiskUsage {
+                mount: disk.mount_point().display().to_string(),
+                total: disk.total_space(),
+                available: disk.available_space(),
+            })
+            .collect();
+        let temperatures = Components::new_with_refreshed_list()
+            .iter()
+            .filter_map(|component| {
+                Some(Temperature {
+                    label: component.label().to_string(),
+                    current: component.temperature()?,
+                    critical: component.critical()?,
+                })
+            })
+            .collect();
+        Self {
+            disks,
+            temperatures,
+        }
+    }
+}
+
+/// 見つかった問題（重いものが先）。何も無ければ空
+pub fn check(snapshot: &Snapshot, probe: &Probe, locale: &Locale) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    let memory = &snapshot.memory;
+    if memory.total_swap > 0 {
+        let percent = memory.used_swap as f64 * 100.0 / memory.total_swap as f64;
+        if percent >= SWAP_PERCENT {
+            findings.push(Finding {
+                name: "Swap",
+                detail: format!(
+                    "{} used ({} of {})",
+                    locale.percent(percent),
+                    locale.megabytes(memory.used_swap, 0),
+                    locale.megabytes(memory.total_swap, 0)
+                ),
+                severe: percent >= 95.0,
+            });
+        }
+    }
+
+    for disk in &probe.disks {
+        let used = disk.total.saturating_sub(disk.available);
+        let percent = used as f64 * 100.0 / disk.total as f64;
+        if percent >= DISK_PERCENT {
+            findings.push(Finding {
+                name: "Disk",
+                detail: format!(
+                    "{} is {} full ({} free)",
+                    disk.mount,
+                    locale.percent(percent),
+                    locale.megabytes(disk.available, 0)
+                ),
+                severe: percent >= 98.0,
+            });
+        }
+    }
+
+    let zombies = snapshot
+        .processes
+        .iter()
+        .filter(|p| p.status == ProcessStatus::Zombie)
+        .count();
+    if zombies >= ZOMBIES {
+        findings.push(Finding {
+            name: "Zombies",
+            detail: format!("{zombies} zombie processes (their parents are not reaping them)"),
+            severe: false,
+        });
+    }
+
+    let cores = snapshot.cpu.count.max(1);
+    let load = snapshot.info.load_average[0];
+    if load > cores as f64 {
+        findings.push(Finding {
+            name: "Load",
+            detail: format!(
+                "1-minute load {} is above the {cores} CPUs",
+                locale.float(load, 2)
+            ),
+            severe: load > 2.0 * cores as f64,
+        });
+    }
+
+    for sensor in &probe.temperatures {
+        if sensor.current >= sensor.critical - TEMPERATURE_MARGIN {
+            findings.push(Finding {
+                name: "Temperature",
+                detail: format!(
+                    "{} at {}°C (critical at {}°C)",
+                    sensor.label,
+                    locale.float(f64::from(sensor.current), 0),
+                    locale.float(f64::from(sensor.critical), 0)
+                ),
+                severe: sensor.current >= sensor.critical,
+            });
+        }
+    }
+
+    findings.sort_by_key(|finding| !finding.severe);
+    findings
+}
@@ -0,0 +1,203 @@
+// スナップショットを SQLite に保存し、後から見返す（history フィーチャー）
+//
+// 1 行に 1 回分のスナップショットを --serve と同じ JSON で保存する。
+// `rust-top history` では ←/→ で保存した時点を行き来し、通常の画面で表示する。
+mod sqlite;
+
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{Terminal, backend::Backend};
+
+use crate::app::{App, Effect};
+use crate::cli::Args;
+use crate::clipboard;
+use crate::config::{Config, HistoryConfig};
+use crate::json;
+use crate::remote;
+use crate::sampler::{ProcessChanges, Snapshot};
+use crate::signals;
+use crate::ui;
+use sqlite::{Database, Param};
+
+const SCHEMA: &str =
+    "CREATE TABLE IF NOT EXISTS snapshots (time INTEGER PRIMARY KEY, data TEXT NOT NULL)";
+
+/// 表示中のメッセージを消すために描き直す間隔
+const POLL: Duration = Duration::from_secs(1);
+
+/// 設定した間隔ごとにスナップショットを保存する
+pub struct Recorder {
+    db: Database,
+    interval: Duration,
+    keep: Duration,
+    last: Option<Instant>,
+}
+
+impl Recorder {
+    /// [history] path が無ければ None
+    pub fn open(config: &HistoryConfig) -> io::Result<Option<Self>> {
+        let Some(path) = &config.path else {
+            return Ok(None);
+        };
+        let db = Database::open(path)?;
+        db.execute(SCHEMA, &[])?;
+        Ok(Some(Self {
+            db,
+            interval: config.interval,
+            keep: config.keep,
+            last: None,
+        }))
+    }
+
+    /// 前回の保存から間隔が空いていれば保存し、保存期間を過ぎたものを消す
+    pub fn record(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        // 使用率が確定するまでの値は残さない
+        if !snapshot.cpu_primed || self.last.is_some_and(|last| last.elapsed() < self.interval) {
+            return Ok(());
+        }
+        self.last = Some(Instant::now());
+        let now = unix_now();
+        let data = remote::encode(snapshot).to_string_compact();
+        self.db.execute(
+            "INSERT OR REPLACE INTO snapshots (time, data) VALUES (?, ?)",
+            &[Param::Int(now), Param::Text(&data)],
+        )?;
+        self.db.execute(
+            "DELETE FROM snapshots WHERE time < ?",
+            &[Param::Int(now - self.keep.as_secs() as i64)],
+        )
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// 保存したスナップショットを行き来する
+pub struct Browser {
+    db: Database,
+    /// 保存した時刻（古い順）
+    times: Vec<i64>,
+}
+
+impl Browser {
+    /// TUI に入る前に開き、保存が無ければエラーにする
+    pub fn open(config: &HistoryConfig) -> io::Result<Self> {
+        let path = config.path.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "history needs [history] path in the config file",
+            )
+        })?;
+        let db = Database::open(path)?;
+        db.execute(SCHEMA, &[])?;
+        let times = db.query("SELECT time FROM snapshots ORDER BY time", &[], |row| {
+            row.int(0)
+        })?;
+        if times.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no snapshots in {}", path.display()),
+            ));
+        }
+        Ok(Self { db, times })
+    }
+
+    fn load(&self, index: usize) -> io::Result<Snapshot> {
+        let data = self.db.query(
+            "SELECT data FROM snapshots WHERE time = ?",
+            &[Param::Int(self.times[index])],
+            |row| row.text(0),
+        )?;
+        let data = data
+            .first()
+            .ok_or_else(|| io::Error::other("snapshot was deleted"))?;
+        json::parse(data)
+            .and_then(|value| remote::decode(&value))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn run<B: Backend>(
+        &self,
+        terminal: &mut Terminal<B>,
+        args: &Args,
+        config: &Config,
+    ) -> io::Result<()> {
+        let mut app = App::new(args, config);
+        let mut index = self.times.len() - 1;
+        let mut snapshot = Arc::new(self.load(index)?);
+        app.on_snapshot(&snapshot, &ProcessChanges::default());
+        loop {
+            if signals::quit_requested(None) {
+                return Ok(());
+            }
+            if signals::suspend_requested(None) {
+                signals::suspend(terminal)?;
+            }
+            app.history = Some(format!(
+                "{} ({}/{}, Left/Right to move)",
+                app.locale.datetime(self.times[index] as u64),
+                index + 1,
+                self.times.len()
+            ));
+            let mut drawn = ui::Drawn::default();
+            let frame = terminal.draw(|f| drawn = ui::draw(f, &app, &snapshot))?;
+            app.export_screen(frame.buffer);
+            app.set_process_rows(drawn.process_rows);
+
+            if !event::poll(POLL)? {
+                continue;
+            }
+            let step = match event::read()? {
+                Event::Key(key) if signals::quit_requested(Some(&key)) => return Ok(()),
+                Event::Key(key) if signals::suspend_requested(Some(&key)) => {
+                    signals::suspend(terminal)?;
+                    None
+                }
+                Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Left => index.checked_sub(1),
+                    KeyCode::Right => (index + 1 < self.times.len()).then_some(index + 1),
+                    _ => {
+                        match app.handle_key(key, &snapshot) {
+                            Effect::Quit => return Ok(()),
+                            Effect::Send(_) | Effect::SendAll(_) => app.set_message(
+                                "Signals are not available when browsing history".to_string(),
+                            ),
+                            Effect::Interval(_) => app.set_message(
+                                "The interval is fixed when browsing history".to_string(),
+                            ),
+                            Effect::Copy(text) => {
+                                if let Err(e) = clipboard::copy(&mut io::stdout(), &text) {
+                                    app.set_message(format!("Cannot copy to the clipboard: {e}"));
+                                }
+                            }
+                            // 履歴には環境変数を記録していない
+                            #[cfg(feature = "environ")]
+                            Effect::Environ(_) => app.set_message(
+                                "Environment variables are not recorded in history".to_string(),
+                            ),
+                            Effect::None => {}
+                        }
+                        None
+                    }
+                },
+                Event::Mouse(mouse) => {
+                    let target = ui::hit_test(&drawn, mouse.column, mouse.row);
+                    app.handle_mouse(mouse, target, &snapshot);
+                    None
+                }
+                _ => None,
+            };
+            if let Some(next) = step {
+                index = next;
+                snapshot = Arc::new(self.load(index)?);
+                app.on_snapshot(&snapshot, &ProcessChanges::default());
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// システムの libsqlite3 を直接呼ぶ最小限のバインディング
+use std::ffi::{CStr, CString, c_char, c_int, c_uchar};
+use std::io;
+use std::path::Path;
+
+#[repr(C)]
+struct RawDb {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct RawStmt {
+    _private: [u8; 0],
+}
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_ROW: c_int = 100;
+const SQLITE_DONE: c_int = 101;
+const SQLITE_OPEN_READWRITE: c_int = 0x2;
+const SQLITE_OPEN_CREATE: c_int = 0x4;
+/// バインドした値を SQLite 側に複製させる
+const SQLITE_TRANSIENT: isize = -1;
+
+#[link(name = "sqlite3")]
+unsafe extern "C" {
+    fn sqlite3_open_v2(
+        filename: *const c_char,
+        db: *mut *mut RawDb,
+        flags: c_int,
+        vfs: *const c_char,
+    ) -> c_int;
+    fn sqlite3_close(db: *mut RawDb) -> c_int;
+    fn sqlite3_errmsg(db: *mut RawDb) -> *const c_char;
+    fn sqlite3_prepare_v2(
+        db: *mut RawDb,
+        sql: *const c_char,
+        len: c_int,
+        stmt: *mut *mut RawStmt,
+        tail: *mut *const c_char,
+    ) -> c_int;
+    fn sqlite3_bind_int64(stmt: *mut RawStmt, index: c_int, value: i64) -> c_int;
+    fn sqlite3_bind_text(
+        stmt: *mut RawStmt,
+        index: c_int,
+        text: *const c_char,
+        len: c_int,
+        destructor: isize,
+    ) -> c_int;
+    fn sqlite3_step(stmt: *mut RawStmt) -> c_int;
+    fn sqlite3_column_int64(stmt: *mut RawStmt, column: c_int) -> i64;
+    fn sqlite3_column_text(stmt: *mut RawStmt, column: c_int) -> *const c_uchar;
+    fn sqlite3_column_bytes(stmt: *mut RawStmt, column: c_int) -> c_int;
+    fn sqlite3_finalize(stmt: *mut RawStmt) -> c_int;
+}
+
+/// ? に順にバインドする値
+pub enum Param<'a> {
+    Int(i64),
+    Text(&'a str),
+}
+
+pub struct Database {
+    db: *mut RawDb,
+}
+
+/// 結果の 1 行
+pub struct Row<'a> {
+    stmt: &'a Statement<'a>,
+}
+
+struct Statement<'a> {
+    db: &'a Database,
+    stmt: *mut RawStmt,
+}
+
+impl Database {
+    /// ファイルが無ければ作る
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let name = CString::new(path.to_string_lossy().as_bytes())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains NUL"))?;
+        let mut db = std::ptr::null_mut();
+        // SAFETY: 失敗してもハンドルは返るので、エラーを読んでから閉じる
+        let rc = unsafe {
+            sqlite3_open_v2(
+                name.as_ptr(),
+                &mut db,
+                SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE,
+                std::ptr::null(),
+            )
+        };
+        let database = Self { db };
+        if rc != SQLITE_OK {
+            return Err(database.error());
+        }
+        Ok(database)
+    }
+
+    /// 結果を返さない文を実行する
+    pub fn execute(&self, sql: &str, params: &[Param]) -> io::Result<()> {
+        let stmt = self.prepare(sql, params)?;
+        while stmt.step()? {}
+        Ok(())
+    }
+
+    /// 行ごとに row を呼んで結果を集める
+    pub fn query<T>(
+        &self,
+        sql: &str,
+        params: &[Param],
+        mut row: impl FnMut(&Row) -> T,
+    ) -> io::Result<Vec<T>> {
+        let stmt = self.prepare(sql, params)?;
+        let mut rows = Vec::new();
+        while stmt.step()? {
+            rows.push(row(&Row { stmt: &stmt }));
+        }
+        Ok(rows)
+    }
+
+    fn prepare(&self, sql: &str, params: &[Param]) -> io::Result<Statement<'_>> {
+        let sql = CString::new(sql).expect("SQL contains NUL");
+        let mut stmt = std::ptr::null_mut();
+        // SAFETY: sql は NUL 終端されていて、文は Statement が破棄する
+        let rc = unsafe {
+            sqlite3_prepare_v2(self.db, sql.as_ptr(), -1, &mut stmt, std::ptr::null_mut())
+        };
+        let stmt = Statement { db: self, stmt };
+        if rc != SQLITE_OK {
+            return Err(self.error());
+        }
+        for (i, param) in params.iter().enumerate() {
+            let index = i as c_int + 1;
+            // SAFETY: 文字列は SQLITE_TRANSIENT で複製させる
+            let rc = unsafe {
+                match param {
+                    Param::Int(value) => sqlite3_bind_int64(stmt.stmt, index, *value),
+                    Param::Text(text) => sqlite3_bind_text(
+                        stmt.stmt,
+                        index,
+                        text.as_ptr().cast(),
+                        text.len() as c_int,
+                        SQLITE_TRANSIENT,
+                    ),
+                }
+            };
+            if rc != SQLITE_OK {
+                return Err(self.error());
+            }
+        }
+        Ok(stmt)
+    }
+
+    fn error(&self) -> io::Error {
+        // SAFETY: errmsg は db が開いている間は有効な文字列を返す
+        let message = unsafe { CStr::from_ptr(sqlite3_errmsg(self.db)) };
+        io::Error::other(format!("sqlite: {}", message.to_string_lossy()))
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        // SAFETY: 文はすべて Statement の破棄で片付いている
+        unsafe {
+            sqlite3_close(self.db);
+        }
+    }
+}
+
+impl Statement<'_> {
+    // 行があれば true
+    fn step(&self) -> io::Result<bool> {
+        // SAFETY: stmt は準備済み
+        match unsafe { sqlite3_step(self.stmt) } {
+            SQLITE_ROW => Ok(true),
+            SQLITE_DONE => Ok(false),
+            _ => Err(self.db.error()),
+        }
+    }
+}
+
+impl Drop for Statement<'_> {
+    fn drop(&mut self) {
+        // SAFETY: null を渡しても何もしない
+        unsafe {
+            sqlite3_finalize(self.stmt);
+        }
+    }
+}
+
+impl Row<'_> {
+    pub fn int(&self, column: usize) -> i64 {
+        // SAFETY: step が行を返した直後にだけ呼ばれる
+        unsafe { sqlite3_column_int64(self.stmt.stmt, column as c_int) }
+    }
+
+    pub fn text(&self, column: usize) -> String {
+        // SAFETY: 返るポインタは次の step までの間だけ有効なので、すぐに複製する
+        unsafe {
+            let text = sqlite3_column_text(self.stmt.stmt, column as c_int);
+            if text.is_null() {
+                return String::new();
+            }
+            let len = sqlite3_column_bytes(self.stmt.stmt, column as c_int) as usize;
+            let bytes = std::slice::from_raw_parts(text, len);
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    }
+}
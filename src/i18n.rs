@@ -0,0 +1,106 @@
+// 画面の文言の翻訳（英語と日本語）
+//
+// 英語の文言をそのままキーにして日本語を引く。表に無いものは英語のまま出すので、
+// 翻訳が追いついていない文言があっても表示が欠けることはない。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    English,
+    Japanese,
+}
+
+impl Language {
+    /// "en" / "ja" のほか、"ja_JP.UTF-8" のようなロケール名も受け付ける
+    pub fn from_name(name: &str) -> Option<Self> {
+        let lang = name.split(['_', '-', '.', '@']).next().unwrap_or("");
+        match lang.to_ascii_lowercase().as_str() {
+            "en" | "c" | "posix" => Some(Self::English),
+            "ja" => Some(Self::Japanese),
+            _ => None,
+        }
+    }
+
+    /// LC_ALL / LC_MESSAGES / LANG の順に見る。知らない言語なら英語
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|v| !v.is_empty())
+            .and_then(|v| Self::from_name(&v))
+            .unwrap_or_default()
+    }
+
+    pub fn text(self, english: &'static str) -> &'static str {
+        match self {
+            Self::English => english,
+            Self::Japanese => japanese(english).unwrap_or(english),
+        }
+    }
+}
+
+fn japanese(english: &str) -> Option<&'static str> {
+    Some(match english {
+        // パネルの見出し
+        "Memory" => "メモリ",
+        "Processes" => "プロセス",
+        "Info" => "情報",
+        "Pressure (% stalled)" => "負荷による停止 (%)",
+        "Shared memory" => "共有メモリ",
+        "Huge pages" => "ヒュージページ",
+        "Compressed swap" => "圧縮スワップ",
+        "Battery" => "バッテリー",
+        "Top consumers" => "使用量の上位",
+        "Leaders" => "指標ごとの上位",
+        "Containers" => "コンテナ",
+        "Profile (F12)" => "計測 (F12)",
+        "Watch (p to unpin)" => "監視 (p で解除)",
+        "Process" => "プロセス",
+        "Esc to close" => "Esc で閉じる",
+        "Health check" => "健康診断",
+        "Disk" => "ディスク",
+        "Zombies" => "ゾンビ",
+        "Load" => "負荷",
+        "Temperature" => "温度",
+        "Esc to clear" => "Esc で解除",
+        "user" => "ユーザー",
+        "filter" => "絞り込み",
+        "all, A to return" => "全体表示、A で戻る",
+        "command line, Left/Right to scroll" => "コマンドライン、左右でスクロール",
+        "grouped by name, e to expand" => "名前でまとめる、e で展開",
+        "only your own processes are visible on Android" => {
+            "Android では自分のプロセスだけが見える"
+        }
+        "other users' processes are unreadable (hidepid)" => {
+            "他のユーザーのプロセスは読めない (hidepid)"
+        }
+        "other users' processes are hidden (hidepid)" => {
+            "他のユーザーのプロセスは見えない (hidepid)"
+        }
+        // プロセス表の列
+        "User" => "ユーザー",
+        "Name" => "名前",
+        "Command" => "コマンド",
+        "Category" => "種類",
+        "Container" => "コンテナ",
+        "State" => "状態",
+        "Swap" => "スワップ",
+        "TIME+" => "時間",
+        // キーの案内
+        "Quit" => "終了",
+        "Kill" => "シグナル",
+        "Kill tree" => "子孫にも",
+        "Search" => "検索",
+        "Pin" => "ピン留め",
+        "Timer" => "タイマー",
+        "Detail" => "詳細",
+        "Threads" => "スレッド",
+        "Group" => "まとめる",
+        "Kernel" => "カーネル",
+        "Users" => "ユーザー別",
+        "Heatmap" => "ヒートマップ",
+        "NUMA nodes" => "NUMA ノード",
+        "Profile" => "計測",
+        _ => return None,
+    })
+}
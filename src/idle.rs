@@ -0,0 +1,128 @@
+// 操作も負荷も無いあいだ更新間隔を延ばす（[idle]）
+//
+// キーもマウスも触られず、CPU 使用率がしきい値を下回ったまましばらく経ったら、
+// 収集の間隔を [idle] interval まで延ばし、入力の待ち方もゆっくりにして自分の起床を減らす。
+// キーを押すか CPU が上がれば、すぐに設定どおりの間隔に戻す。
+use std::time::{Duration, Instant};
+
+use crate::config::{IdleConfig, RefreshConfig};
+
+/// 止まっている間に入力を待つ長さ（キーを押せば待たずに戻る）
+const IDLE_POLL: Duration = Duration::from_millis(500);
+
+/// 入力の無い時間と CPU 使用率から、止まっているかを決める
+#[derive(Debug)]
+pub struct IdleTracker {
+    config: IdleConfig,
+    /// 最後にキーを押されたか、CPU がしきい値を超えていた時刻
+    active_at: Instant,
+    idle: bool,
+}
+
+impl IdleTracker {
+    pub fn new(config: IdleConfig, now: Instant) -> Self {
+        Self {
+            config,
+            active_at: now,
+            idle: false,
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    /// 設定を読み直したとき。止まっていても、いったん設定どおりの間隔に戻す
+    pub fn set_config(&mut self, config: IdleConfig, now: Instant) -> Option<bool> {
+        self.config = config;
+        self.active(now)
+    }
+
+    /// キーやマウスの入力。状態が変わったら新しい状態
+    pub fn active(&mut self, now: Instant) -> Option<bool> {
+        self.active_at = now;
+        std::mem::take(&mut self.idle).then_some(false)
+    }
+
+    /// 新しいスナップショットを受け取るたびに呼ぶ。cpu は全体の使用率（%、合計で 100）
+    pub fn update(&mut self, now: Instant, cpu: f64) -> Option<bool> {
+        if !self.config.enabled || cpu >= self.config.cpu {
+            return self.active(now);
+        }
+        let idle = now.duration_since(self.active_at) >= self.config.after;
+        (idle != std::mem::replace(&mut self.idle, idle)).then_some(idle)
+    }
+
+    /// 止まっている間の間隔。:interval などで延ばしたほうが長ければそちらを使う
+    pub fn stretch(&self, refresh: &RefreshConfig) -> RefreshConfig {
+        if !self.idle {
+            return refresh.clone();
+        }
+        let mut refresh = refresh.clone();
+        for out in [
+            &mut refresh.cpu,
+            &mut refresh.memory,
+            &mut refresh.processes,
+            &mut refresh.info,
+        ] {
+            *out = (*out).max(self.config.interval);
+        }
+        refresh
+    }
+
+    /// 入力を待つ長さ。止まっている間はサンプラーからの更新も少ないので長く待つ
+    pub fn input_poll(&self, normal: Duration) -> Duration {
+        if self.idle {
+            IDLE_POLL.max(normal)
+        } else {
+            normal
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::IdleTracker;
+    use crate::config::{IdleConfig, RefreshConfig};
+
+    #[test]
+    fn tracker_stretches_the_refresh_until_a_key_or_cpu_load() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut idle = IdleTracker::new(IdleConfig::default(), start);
+        assert_eq!(idle.update(at(10), 2.0), None);
+        // 30 秒のあいだ入力が無く、CPU も 10% を下回っていた
+        assert_eq!(idle.update(at(30), 2.0), Some(true));
+        assert_eq!(idle.update(at(35), 2.0), None);
+        let refresh = RefreshConfig {
+            info: Duration::from_secs(10),
+            ..RefreshConfig::default()
+        };
+        let stretched = idle.stretch(&refresh);
+        assert_eq!(stretched.processes, Duration::from_secs(5));
+        // 設定のほうが長いものはそのまま
+        assert_eq!(stretched.info, Duration::from_secs(10));
+        assert_eq!(stretched.battery, refresh.battery);
+        assert!(idle.input_poll(Duration::from_millis(20)) > Duration::from_millis(20));
+
+        // キーを押すとすぐ戻り、また 30 秒待つ
+        assert_eq!(idle.active(at(40)), Some(false));
+        assert_eq!(idle.stretch(&refresh).processes, refresh.processes);
+        assert_eq!(idle.update(at(60), 2.0), None);
+        assert_eq!(idle.update(at(70), 2.0), Some(true));
+        // CPU が上がっても戻る
+        assert_eq!(idle.update(at(71), 55.0), Some(false));
+        assert_eq!(idle.update(at(90), 2.0), None);
+
+        let mut idle = IdleTracker::new(
+            IdleConfig {
+                enabled: false,
+                ..IdleConfig::default()
+            },
+            start,
+        );
+        assert_eq!(idle.update(at(600), 0.0), None);
+    }
+}
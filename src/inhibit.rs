@@ -0,0 +1,174 @@
+// 動かしている間はスリープさせない（:caffeinate、--caffeinate）
+//
+// ノートパソコンで長く見張っていると、自動のサスペンドで止まってしまう。
+// Linux は systemd-inhibit に cat を動かさせ、その標準入力を持っておく。rust-top が
+// どう終わってもパイプが閉じて cat が終わるので、ロックが残らない。
+// macOS は IOKit の電源のアサーション、Windows は SetThreadExecutionState を使う。
+// どれも画面の消灯は止めず、システムのスリープだけを止める。
+use std::io;
+
+const REASON: &str = "rust-top is monitoring the system";
+
+/// 持っている間はスリープを止める。捨てると元に戻る
+#[derive(Debug)]
+pub struct Inhibitor {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    child: std::process::Child,
+    #[cfg(target_os = "macos")]
+    assertion: u32,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Inhibitor {
+    pub fn start() -> io::Result<Self> {
+        use std::process::{Command, Stdio};
+        use std::time::Duration;
+
+        let mut child = Command::new("systemd-inhibit")
+            .args([
+                "--what=sleep",
+                "--who=rust-top",
+                &format!("--why={REASON}"),
+                "--mode=block",
+                "cat",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        // logind が無いなどで断られたときはすぐに終わる
+        std::thread::sleep(Duration::from_millis(100));
+        if let Some(status) = child.try_wait()? {
+            return Err(io::Error::other(format!(
+                "systemd-inhibit exited ({status})"
+            )));
+        }
+        Ok(Self { child })
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Drop for Inhibitor {
+    fn drop(&mut self) {
+        // 標準入力を閉じれば cat が終わり、systemd-inhibit もロックを外して終わる
+        drop(self.child.stdin.take());
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod iokit {
+    use std::ffi::{c_char, c_void};
+
+    /// kCFStringEncodingUTF8
+    pub const UTF8: u32 = 0x0800_0100;
+    /// kIOPMAssertionLevelOn
+    pub const LEVEL_ON: u32 = 255;
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    unsafe extern "C" {
+        pub fn CFStringCreateWithCString(
+            allocator: *const c_void,
+            text: *const c_char,
+            encoding: u32,
+        ) -> *const c_void;
+        pub fn CFRelease(object: *const c_void);
+    }
+
+    #[link(name = "IOKit", kind = "framework")]
+    unsafe extern "C" {
+        pub fn IOPMAssertionCreateWithName(
+            kind: *const c_void,
+            level: u32,
+            name: *const c_void,
+            id: *mut u32,
+        ) -> i32;
+        pub fn IOPMAssertionRelease(id: u32) -> i32;
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Inhibitor {
+    pub fn start() -> io::Result<Self> {
+        use std::ffi::CString;
+
+        let reason = CString::new(REASON).expect("no NUL in the reason");
+        let mut assertion = 0;
+        // SAFETY: 作った CFString はアサーションを作った後に解放する。null なら使わない
+        let status = unsafe {
+            let kind = iokit::CFStringCreateWithCString(
+                std::ptr::null(),
+                c"PreventUserIdleSystemSleep".as_ptr(),
+                iokit::UTF8,
+            );
+            let name =
+                iokit::CFStringCreateWithCString(std::ptr::null(), reason.as_ptr(), iokit::UTF8);
+            let status = if kind.is_null() || name.is_null() {
+                -1
+            } else {
+                iokit::IOPMAssertionCreateWithName(kind, iokit::LEVEL_ON, name, &mut assertion)
+            };
+            for object in [kind, name] {
+                if !object.is_null() {
+                    iokit::CFRelease(object);
+                }
+            }
+            status
+        };
+        if status != 0 {
+            return Err(io::Error::other(format!(
+                "IOPMAssertionCreateWithName failed ({status:#x})"
+            )));
+        }
+        Ok(Self { assertion })
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for Inhibitor {
+    fn drop(&mut self) {
+        // SAFETY: start で作ったアサーションを 1 回だけ外す
+        unsafe {
+            iokit::IOPMAssertionRelease(self.assertion);
+        }
+    }
+}
+
+// 実行の状態はスレッドごとなので、オンにしたスレッド（画面のスレッド）で戻す
+#[cfg(windows)]
+impl Inhibitor {
+    pub fn start() -> io::Result<Self> {
+        use windows_sys::Win32::System::Power::{
+            ES_CONTINUOUS, ES_SYSTEM_REQUIRED, SetThreadExecutionState,
+        };
+
+        // SAFETY: フラグを渡すだけ
+        if unsafe { SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {})
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Inhibitor {
+    fn drop(&mut self) {
+        use windows_sys::Win32::System::Power::{ES_CONTINUOUS, SetThreadExecutionState};
+
+        // SAFETY: フラグを渡すだけ
+        unsafe {
+            SetThreadExecutionState(ES_CONTINUOUS);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+impl Inhibitor {
+    pub fn start() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "not supported on this platform",
+        ))
+    }
+}
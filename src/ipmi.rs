@@ -0,0 +1,122 @@
+// IPMI（BMC）のファン・電源・温度センサー（ipmi-sensors か ipmitool の出力を読む）
+//
+// hwmon に出てこない筐体レベルのセンサーを補う。BMC への問い合わせは遅いので、
+// 既定の更新間隔は他より長くしている。
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SensorKind {
+    Temperature,
+    Fan,
+    Power,
+}
+
+impl SensorKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            SensorKind::Temperature => "Temp",
+            SensorKind::Fan => "Fan",
+            SensorKind::Power => "Power",
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            SensorKind::Temperature => "°C",
+            SensorKind::Fan => "RPM",
+            SensorKind::Power => "W",
+        }
+    }
+
+    // freeipmi は "C" / "W"、ipmitool は "degrees C" / "Watts" と書く
+    fn from_unit(unit: &str) -> Option<Self> {
+        match unit.trim() {
+            "C" | "degrees C" => Some(SensorKind::Temperature),
+            "RPM" => Some(SensorKind::Fan),
+            "W" | "Watts" => Some(SensorKind::Power),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sensor {
+    pub name: String,
+    pub kind: SensorKind,
+    pub reading: f64,
+    /// BMC が判定した状態（小文字。ok 以外は閾値を越えている）
+    pub status: String,
+}
+
+impl Sensor {
+    pub fn is_ok(&self) -> bool {
+        self.status == "ok"
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IpmiSnapshot {
+    /// 温度・ファン・電源の順
+    pub sensors: Vec<Sensor>,
+}
+
+/// BMC が無い（どちらのコマンドも使えない）ときは None
+pub fn collect() -> Option<IpmiSnapshot> {
+    let mut sensors = freeipmi().or_else(ipmitool)?;
+    if sensors.is_empty() {
+        return None;
+    }
+    sensors.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.name.cmp(&b.name)));
+    Some(IpmiSnapshot { sensors })
+}
+
+// ID,Name,Type,Reading,Units,Event の CSV
+fn freeipmi() -> Option<Vec<Sensor>> {
+    let text = run(
+        "ipmi-sensors",
+        &[
+            "--comma-separated-output",
+            "--no-header-output",
+            "--ignore-not-available-sensors",
+        ],
+    )?;
+    Some(
+        text.lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split(',').collect();
+                Some(Sensor {
+                    name: fields.get(1)?.trim().to_string(),
+                    kind: SensorKind::from_unit(fields.get(4)?)?,
+                    reading: fields.get(3)?.trim().parse().ok()?,
+                    status: fields.get(5)?.trim().trim_matches('\'').to_lowercase(),
+                })
+            })
+            .collect(),
+    )
+}
+
+// 名前 | 値 | 単位 | 状態 | 閾値... を | で区切った表
+fn ipmitool() -> Option<Vec<Sensor>> {
+    let text = run("ipmitool", &["sensor"])?;
+    Some(
+        text.lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+                Some(Sensor {
+                    name: fields.first()?.to_string(),
+                    kind: SensorKind::from_unit(fields.get(2)?)?,
+                    reading: fields.get(1)?.parse().ok()?,
+                    status: fields.get(3)?.to_lowercase(),
+                })
+            })
+            .collect(),
+    )
+}
+
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
@@ -0,0 +1,162 @@
+// 選択したプロセスのログを追いかける（J、journal 機能）
+//
+// systemd のユニットに属していればユニットのログを、そうでなければ PID のログを
+// journalctl で読む。[journal] file を書くと、代わりにそのファイル（syslog など）の
+// 末尾から、PID かユニット名を含む行を拾う。
+// CPU が跳ねた瞬間に何を書いていたかを見られるよう、開いている間は読み直し続ける。
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use sysinfo::Pid;
+
+/// 読み直す間隔
+const INTERVAL: Duration = Duration::from_secs(2);
+/// 残す行数
+const LINES: usize = 200;
+/// ログファイルの末尾から読む量
+const TAIL_BYTES: u64 = 256 * 1024;
+
+/// 読めなかったときはその理由
+type Lines = Result<Vec<String>, String>;
+
+/// ログの一覧（J）
+#[derive(Debug)]
+pub struct JournalView {
+    pub pid: Pid,
+    /// "nginx.service" など。ユニットに属していなければ None
+    pub unit: Option<String>,
+    /// 末尾から何行さかのぼっているか（0 なら最新に追従する）
+    pub scroll: usize,
+    lines: Arc<Mutex<Option<Lines>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl JournalView {
+    /// 読み始める。閉じる（drop する）まで別スレッドで読み直す
+    pub fn open(pid: Pid, file: Option<PathBuf>) -> JournalView {
+        let unit = unit_of(pid);
+        let lines = Arc::new(Mutex::new(None));
+        let stop = Arc::new(AtomicBool::new(false));
+        let shared = Arc::clone(&lines);
+        let stopped = Arc::clone(&stop);
+        let source_unit = unit.clone();
+        thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                let result = match &file {
+                    Some(path) => tail_file(path, pid, source_unit.as_deref()),
+                    None => journalctl(pid, source_unit.as_deref()),
+                };
+                *shared.lock().expect("journal lines lock") = Some(result);
+                thread::sleep(INTERVAL);
+            }
+        });
+        JournalView {
+            pid,
+            unit,
+            scroll: 0,
+            lines,
+            stop,
+        }
+    }
+
+    /// まだ一度も読んでいなければ None
+    pub fn lines(&self) -> Option<Lines> {
+        self.lines.lock().expect("journal lines lock").clone()
+    }
+}
+
+impl Drop for JournalView {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// cgroup のパスの最後の .service（ユーザーのユニットも含む）
+#[cfg(target_os = "linux")]
+fn unit_of(pid: Pid) -> Option<String> {
+    let cgroup = std::fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    // cgroup v2 の行（0::/system.slice/nginx.service）があればそれを、無ければ name=systemd の行を見る
+    let path = cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .or_else(|| {
+            cgroup
+                .lines()
+                .find_map(|line| line.split_once(":name=systemd:").map(|(_, path)| path))
+        })?;
+    path.rsplit('/')
+        .find(|part| part.ends_with(".service") && !part.starts_with("user@"))
+        .map(str::to_string)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unit_of(_pid: Pid) -> Option<String> {
+    None
+}
+
+fn journalctl(pid: Pid, unit: Option<&str>) -> Lines {
+    // ユーザーのユニットは _SYSTEMD_USER_UNIT に入るので、両方に一致させる
+    let matches = match unit {
+        Some(unit) => vec![
+            format!("_SYSTEMD_UNIT={unit}"),
+            "+".to_string(),
+            format!("_SYSTEMD_USER_UNIT={unit}"),
+        ],
+        None => vec![format!("_PID={pid}")],
+    };
+    let output = Command::new("journalctl")
+        .args([
+            "--no-pager",
+            "--output=short-iso",
+            "--lines",
+            &LINES.to_string(),
+        ])
+        .args(matches)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("journalctl: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.lines().next() {
+            Some(line) => format!("journalctl: {line}"),
+            None => format!("journalctl: {}", output.status),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        // 「-- No entries --」などの注記は除く
+        .filter(|line| !line.starts_with("-- "))
+        .map(str::to_string)
+        .collect())
+}
+
+// syslog の形式（nginx[1234]: ...）を想定し、PID かユニット名（.service を除く）を含む行を拾う
+fn tail_file(path: &Path, pid: Pid, unit: Option<&str>) -> Lines {
+    let error = |e: std::io::Error| format!("{}: {e}", path.display());
+    let mut file = File::open(path).map_err(error)?;
+    let length = file.metadata().map_err(error)?.len();
+    file.seek(SeekFrom::Start(length.saturating_sub(TAIL_BYTES)))
+        .map_err(error)?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).map_err(error)?;
+    let text = String::from_utf8_lossy(&bytes);
+    let by_pid = format!("[{pid}]");
+    let by_unit = unit.map(|unit| format!("{}[", unit.trim_end_matches(".service")));
+    let mut lines: Vec<String> = text
+        .lines()
+        // 途中から読んだ最初の行は欠けているかもしれないので捨てる
+        .skip(usize::from(length > TAIL_BYTES))
+        .filter(|line| {
+            line.contains(&by_pid) || by_unit.as_ref().is_some_and(|unit| line.contains(unit))
+        })
+        .map(str::to_string)
+        .collect();
+    lines.drain(..lines.len().saturating_sub(LINES));
+    Ok(lines)
+}
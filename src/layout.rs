@@ -0,0 +1,320 @@
+// 画面のレイアウト（上から並べるパネルと、伸びるパネルの重み）
+//
+// 組み込みのレイアウトと [layouts] に書いたものを V か :layout で切り替える。
+// 並べた順に上から置き、端末が低いときは後ろのパネルから省く（CPU とプロセス表は省かない）。
+// プロセス表・グラフ・Info は高さが伸び、ほかのパネルを置いて余った行を重みの割合で分ける。
+// 200 桁を超えるような広い端末では、Info とセンサーのパネルをプロセス表の右の列に移す。
+
+/// レイアウトに置けるパネル
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    Cpu,
+    Memory,
+    /// CPU とメモリの使用率の推移
+    Graphs,
+    Top,
+    /// CPU・メモリ・I/O それぞれの上位 3 つを横に並べた小さな表
+    Leaders,
+    Watch,
+    Cgroup,
+    Pressure,
+    CpuTime,
+    Kernel,
+    Numa,
+    Shm,
+    HugePages,
+    /// zram と zswap
+    CompressedSwap,
+    Battery,
+    Soc,
+    /// ファンの回転数と消費電力
+    Sensors,
+    Gpu,
+    Ipmi,
+    Smart,
+    Apple,
+    Processes,
+    Containers,
+    Ports,
+    Tcp,
+    /// [[panel]]
+    Custom,
+    Info,
+}
+
+impl Slot {
+    /// 設定ファイルでの名前
+    pub const NAMES: [(Slot, &str); 27] = [
+        (Slot::Cpu, "cpu"),
+        (Slot::Memory, "memory"),
+        (Slot::Graphs, "graphs"),
+        (Slot::Top, "top"),
+        (Slot::Leaders, "leaders"),
+        (Slot::Watch, "watch"),
+        (Slot::Cgroup, "cgroup"),
+        (Slot::Pressure, "pressure"),
+        (Slot::CpuTime, "cpu_time"),
+        (Slot::Kernel, "kernel"),
+        (Slot::Numa, "numa"),
+        (Slot::Shm, "shm"),
+        (Slot::HugePages, "huge_pages"),
+        (Slot::CompressedSwap, "compressed_swap"),
+        (Slot::Battery, "battery"),
+        (Slot::Soc, "soc"),
+        (Slot::Sensors, "sensors"),
+        (Slot::Gpu, "gpu"),
+        (Slot::Ipmi, "ipmi"),
+        (Slot::Smart, "smart"),
+        (Slot::Apple, "apple"),
+        (Slot::Processes, "processes"),
+        (Slot::Containers, "containers"),
+        (Slot::Ports, "ports"),
+        (Slot::Tcp, "tcp"),
+        (Slot::Custom, "custom"),
+        (Slot::Info, "info"),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Slot> {
+        Self::NAMES
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(slot, _)| *slot)
+    }
+
+    /// 余った行をもらって伸びるパネル
+    pub fn grows(self) -> bool {
+        matches!(self, Slot::Graphs | Slot::Processes | Slot::Info)
+    }
+
+    /// 広い端末で右の列に移すパネル（Info とセンサー）
+    pub fn side(self) -> bool {
+        matches!(
+            self,
+            Slot::Battery
+                | Slot::Soc
+                | Slot::Sensors
+                | Slot::Gpu
+                | Slot::Ipmi
+                | Slot::Smart
+                | Slot::Apple
+                | Slot::Info
+        )
+    }
+}
+
+/// [layouts] side。右の列を作るかどうか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SideColumn {
+    /// 枠の内側が AUTO_WIDTH 桁以上あるとき
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// auto で右の列を作り始める幅
+pub const AUTO_WIDTH: u16 = 200;
+
+impl SideColumn {
+    pub const NAMES: [&'static str; 3] = ["auto", "always", "never"];
+
+    pub fn from_name(name: &str) -> Option<SideColumn> {
+        match name {
+            "auto" => Some(SideColumn::Auto),
+            "always" => Some(SideColumn::Always),
+            "never" => Some(SideColumn::Never),
+            _ => None,
+        }
+    }
+
+    /// width は枠の内側の幅。分けても左右とも min_width より広いときだけ分ける
+    pub fn applies(self, width: u16, min_width: u16) -> bool {
+        let side = side_width(width);
+        let fits = side >= min_width && width - side > min_width;
+        match self {
+            SideColumn::Auto => width >= AUTO_WIDTH && fits,
+            SideColumn::Always => fits,
+            SideColumn::Never => false,
+        }
+    }
+}
+
+/// 右の列の幅（全体の 3 割）
+pub fn side_width(width: u16) -> u16 {
+    width * 3 / 10
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutItem {
+    pub slot: Slot,
+    /// 伸びるパネルが余りをもらう割合。0 なら最小の高さのまま（ほかに伸びるものが無いときを除く）
+    pub weight: u16,
+}
+
+impl LayoutItem {
+    /// "processes" か、重みを付けた "processes:3"
+    pub fn parse(text: &str) -> Result<LayoutItem, String> {
+        let (name, weight) = match text.split_once(':') {
+            Some((name, weight)) => (name, Some(weight)),
+            None => (text, None),
+        };
+        let slot = Slot::from_name(name.trim()).ok_or_else(|| {
+            let names: Vec<&str> = Slot::NAMES.iter().map(|(_, n)| *n).collect();
+            format!("unknown panel \"{name}\" (one of {})", names.join(", "))
+        })?;
+        let weight = match weight {
+            None => default_weight(slot),
+            Some(_) if !slot.grows() => {
+                return Err(format!("{name} has a fixed height and takes no weight"));
+            }
+            Some(weight) => weight
+                .trim()
+                .parse::<u16>()
+                .map_err(|_| format!("the weight of {name} must be a non-negative integer"))?,
+        };
+        Ok(LayoutItem { slot, weight })
+    }
+}
+
+// 何も書かなければプロセス表とグラフが余りを分け、Info は最小のまま
+fn default_weight(slot: Slot) -> u16 {
+    match slot {
+        Slot::Graphs | Slot::Processes => 1,
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenLayout {
+    pub name: String,
+    pub items: Vec<LayoutItem>,
+}
+
+impl ScreenLayout {
+    pub fn new(name: &str, items: &[&str]) -> Result<ScreenLayout, String> {
+        Ok(ScreenLayout {
+            name: name.to_string(),
+            items: items
+                .iter()
+                .map(|item| LayoutItem::parse(item))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub fn contains(&self, slot: Slot) -> bool {
+        self.items.iter().any(|item| item.slot == slot)
+    }
+}
+
+/// 組み込みのレイアウト。先頭が既定
+pub fn builtin() -> Vec<ScreenLayout> {
+    const LAYOUTS: [(&str, &[&str]); 6] = [
+        (
+            "default",
+            &[
+                "cpu",
+                "memory",
+                "top",
+                "cgroup",
+                "pressure",
+                "cpu_time",
+                "kernel",
+                "numa",
+                "shm",
+                "huge_pages",
+                "compressed_swap",
+                "battery",
+                "soc",
+                "sensors",
+                "gpu",
+                "ipmi",
+                "smart",
+                "apple",
+                "processes",
+                "watch",
+                "containers",
+                "ports",
+                "tcp",
+                "custom",
+                "info",
+            ],
+        ),
+        // top のように概要を 2 行だけ出して、残りはプロセス表
+        ("top", &["cpu", "memory", "processes"]),
+        // htop のようにコアごとの棒とメモリを上に並べる
+        ("htop", &["cpu", "cpu_time", "memory", "processes", "watch"]),
+        (
+            "graphs",
+            &["cpu", "graphs:2", "memory", "processes:1", "watch"],
+        ),
+        // 大きなプロセス表の代わりに、指標ごとの上位を並べて一目で見る
+        (
+            "leaders",
+            &[
+                "cpu",
+                "memory",
+                "leaders",
+                "graphs:1",
+                "processes:0",
+                "watch",
+            ],
+        ),
+        ("minimal", &["processes"]),
+    ];
+    LAYOUTS
+        .iter()
+        .map(|(name, items)| ScreenLayout::new(name, items).expect("built-in layout"))
+        .collect()
+}
+
+/// 組み込みのものに [layouts] のものを足す。同じ名前なら設定ファイルのものに置き換える
+pub fn all(custom: &[ScreenLayout]) -> Vec<ScreenLayout> {
+    let mut layouts = builtin();
+    for layout in custom {
+        match layouts.iter_mut().find(|l| l.name == layout.name) {
+            Some(existing) => *existing = layout.clone(),
+            None => layouts.push(layout.clone()),
+        }
+    }
+    layouts
+}
+
+/// 余った行 extra を伸びるパネルに重みの割合で分ける。panels は（高さ、重み、上限）
+/// 上限で止まった分はほかに回す。重みのあるものが無ければ重み 0 のものに等しく分ける
+pub fn share(mut extra: u16, panels: &mut [(u16, u16, u16)]) {
+    while extra > 0 {
+        let open: Vec<usize> = (0..panels.len())
+            .filter(|&i| panels[i].0 < panels[i].2)
+            .collect();
+        if open.is_empty() {
+            return;
+        }
+        let weighted = open.iter().any(|&i| panels[i].1 > 0);
+        let weights: Vec<u32> = (0..panels.len())
+            .map(|i| if weighted { panels[i].1 as u32 } else { 1 })
+            .collect();
+        let weight = |i: usize| weights[i];
+        let total: u32 = open.iter().map(|&i| weight(i)).sum();
+        let mut given = 0;
+        for &i in &open {
+            let part = (extra as u32 * weight(i) / total) as u16;
+            let room = panels[i].2 - panels[i].0;
+            let part = part.min(room);
+            panels[i].0 += part;
+            given += part;
+        }
+        // 割り切れなかった分は前から 1 行ずつ
+        if given == 0 {
+            for &i in &open {
+                if given < extra && weight(i) > 0 && panels[i].0 < panels[i].2 {
+                    panels[i].0 += 1;
+                    given += 1;
+                }
+            }
+        }
+        if given == 0 {
+            return;
+        }
+        extra -= given;
+    }
+}
@@ -0,0 +1,143 @@
+// メモリリークの疑いがあるプロセスの検出
+//
+// 一度の値では大きいだけか増え続けているのか分からないので、プロセスごとに RSS を
+// [leak] window（既定 10 分）の間に SAMPLES 回記録し、その間ほとんど減らずに
+// [leak] growth（既定 1 MB/分）以上の傾きで増え続けたものを疑う。傾きは最小二乗法で求める。
+// 疑いが付いたときに記録（L）に残し、表では Memory 列を警告の色にする。
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use sysinfo::Pid;
+
+use crate::config::LeakConfig;
+use crate::sampler::ProcessInfo;
+
+/// window の間に記録する回数
+const SAMPLES: u32 = 30;
+/// 減った回数がこの割合までなら、増え続けているとみなす（GC などで少し戻るのは許す）
+const MAX_DROPS: f64 = 0.1;
+
+#[derive(Debug)]
+struct Track {
+    /// PID が使い回されたら記録をやり直す
+    start_time: u64,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+/// 新しく疑いが付いたプロセス
+#[derive(Debug, Clone)]
+pub struct Suspect {
+    pub pid: Pid,
+    pub name: Arc<str>,
+    pub memory: u64,
+    /// 1 分あたりに増えたバイト数
+    pub growth: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct LeakDetector {
+    config: LeakConfig,
+    tracks: HashMap<Pid, Track>,
+    /// 疑いのあるプロセスと、1 分あたりに増えたバイト数
+    suspects: HashMap<Pid, f64>,
+}
+
+impl LeakDetector {
+    pub fn new(config: LeakConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.config.window
+    }
+
+    /// 疑いがあれば 1 分あたりに増えたバイト数
+    pub fn growth(&self, pid: Pid) -> Option<f64> {
+        self.suspects.get(&pid).copied()
+    }
+
+    /// 収集のたびに呼ぶ。新しく疑いが付いたプロセスを返す
+    pub fn update(&mut self, processes: &[ProcessInfo], now: Instant) -> Vec<Suspect> {
+        if !self.config.enabled {
+            return Vec::new();
+        }
+        let step = self.config.window / SAMPLES;
+        let mut found = Vec::new();
+        let mut alive = HashSet::new();
+        for p in processes.iter().filter(|p| !p.is_thread) {
+            alive.insert(p.pid);
+            let track = self.tracks.entry(p.pid).or_insert_with(|| Track {
+                start_time: p.start_time,
+                samples: VecDeque::new(),
+            });
+            if track.start_time != p.start_time {
+                track.start_time = p.start_time;
+                track.samples.clear();
+                self.suspects.remove(&p.pid);
+            }
+            if track
+                .samples
+                .back()
+                .is_some_and(|(last, _)| now.duration_since(*last) < step)
+            {
+                continue;
+            }
+            track.samples.push_back((now, p.memory));
+            if track.samples.len() > SAMPLES as usize + 1 {
+                track.samples.pop_front();
+            }
+            match growth(&track.samples, self.config.window) {
+                Some(growth) if growth >= self.config.growth as f64 => {
+                    if self.suspects.insert(p.pid, growth).is_none() {
+                        found.push(Suspect {
+                            pid: p.pid,
+                            name: Arc::clone(&p.name),
+                            memory: p.memory,
+                            growth,
+                        });
+                    }
+                }
+                _ => {
+                    self.suspects.remove(&p.pid);
+                }
+            }
+        }
+        self.tracks.retain(|pid, _| alive.contains(pid));
+        self.suspects.retain(|pid, _| alive.contains(pid));
+        found
+    }
+}
+
+// window 全体にわたって記録があり、ほとんど減っていなければ 1 分あたりの傾き
+fn growth(samples: &VecDeque<(Instant, u64)>, window: Duration) -> Option<f64> {
+    let (first, first_memory) = *samples.front()?;
+    let (last, last_memory) = *samples.back()?;
+    if last.duration_since(first) < window || last_memory <= first_memory {
+        return None;
+    }
+    let drops = samples
+        .iter()
+        .zip(samples.iter().skip(1))
+        .filter(|((_, a), (_, b))| b < a)
+        .count();
+    if drops as f64 > (samples.len() - 1) as f64 * MAX_DROPS {
+        return None;
+    }
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|(time, memory)| (time.duration_since(first).as_secs_f64(), *memory as f64))
+        .collect();
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    (variance > 0.0).then(|| covariance / variance * 60.0)
+}
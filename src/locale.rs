@@ -0,0 +1,262 @@
+// ロケールに合わせた数値・時刻の書式
+use crate::config::FormatConfig;
+use crate::i18n::Language;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale {
+    /// 3 桁区切り（空なら区切らない）
+    pub thousands: String,
+    pub decimal: char,
+    pub hour24: bool,
+    pub language: Language,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            thousands: String::new(),
+            decimal: '.',
+            hour24: true,
+            language: Language::English,
+        }
+    }
+}
+
+impl Locale {
+    /// 環境変数（LC_ALL / LC_NUMERIC / LC_TIME / LANG）と設定から決める
+    pub fn from_config(config: &FormatConfig) -> Self {
+        let numeric = config
+            .locale
+            .clone()
+            .or_else(|| env_locale("LC_NUMERIC"))
+            .unwrap_or_default();
+        let time = config
+            .locale
+            .clone()
+            .or_else(|| env_locale("LC_TIME"))
+            .unwrap_or_default();
+
+        let (thousands, decimal) = separators(&numeric);
+        let mut locale = Self {
+            thousands: thousands.to_string(),
+            decimal,
+            hour24: !uses_12h_clock(&time),
+            language: config
+                .language
+                .or_else(|| config.locale.as_deref().and_then(Language::from_name))
+                .unwrap_or_else(Language::from_env),
+        };
+        if let Some(sep) = &config.thousands_separator {
+            locale.thousands = sep.clone();
+        }
+        if let Some(sep) = config.decimal_separator {
+            locale.decimal = sep;
+        }
+        if let Some(hour24) = config.hour24 {
+            locale.hour24 = hour24;
+        }
+        locale
+    }
+
+    /// 画面の文言を選んだ言語で返す
+    pub fn text(&self, english: &'static str) -> &'static str {
+        self.language.text(english)
+    }
+
+    /// 整数を 3 桁区切りで書く
+    pub fn int(&self, n: u64) -> String {
+        let digits = n.to_string();
+        if self.thousands.is_empty() {
+            return digits;
+        }
+        let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                out.push_str(&self.thousands);
+            }
+            out.push(c);
+        }
+        out
+    }
+
+    /// 小数を指定の桁数で書く
+    pub fn float(&self, value: f64, places: usize) -> String {
+        let text = format!("{:.*}", places, value.abs());
+        let (int_part, frac) = match text.split_once('.') {
+            Some((i, f)) => (i, Some(f)),
+            None => (text.as_str(), None),
+        };
+        let mut out = String::new();
+        if value < 0.0 && text.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            out.push('-');
+        }
+        out.push_str(&self.int(int_part.parse().unwrap_or(0)));
+        if let Some(frac) = frac {
+            out.push(self.decimal);
+            out.push_str(frac);
+        }
+        out
+    }
+
+    pub fn percent(&self, value: f64) -> String {
+        format!("{}%", self.float(value, 1))
+    }
+
+    /// バイト数を MB で書く
+    pub fn megabytes(&self, bytes: u64, places: usize) -> String {
+        format!("{} MB", self.float(bytes as f64 / 1024.0 / 1024.0, places))
+    }
+
+    /// UNIX 時間をローカル時刻の「日付 時刻」で書く
+    pub fn datetime(&self, epoch_secs: u64) -> String {
+        let t = local_time(epoch_secs);
+        format!(
+            "{:04}-{:02}-{:02} {}",
+            t.year,
+            t.month,
+            t.day,
+            self.clock(&t)
+        )
+    }
+
+    /// プロセスの起動時刻を短く書く。now と同じ日なら時刻、同じ年なら月日と時分、それより前は日付だけ
+    pub fn started(&self, epoch_secs: u64, now: u64) -> String {
+        let t = local_time(epoch_secs);
+        let today = local_time(now);
+        if (t.year, t.month, t.day) == (today.year, today.month, today.day) {
+            self.clock(&t)
+        } else if t.year == today.year {
+            format!("{:02}-{:02} {:02}:{:02}", t.month, t.day, t.hour, t.minute)
+        } else {
+            format!("{:04}-{:02}-{:02}", t.year, t.month, t.day)
+        }
+    }
+
+    /// UNIX 時間をローカル時刻の時刻部分だけで書く
+    pub fn time(&self, epoch_secs: u64) -> String {
+        self.clock(&local_time(epoch_secs))
+    }
+
+    fn clock(&self, t: &LocalTime) -> String {
+        if self.hour24 {
+            format!("{:02}:{:02}:{:02}", t.hour, t.minute, t.second)
+        } else {
+            let hour = match t.hour % 12 {
+                0 => 12,
+                h => h,
+            };
+            let suffix = if t.hour < 12 { "AM" } else { "PM" };
+            format!("{hour}:{:02}:{:02} {suffix}", t.minute, t.second)
+        }
+    }
+}
+
+/// ファイル名に使うローカル時刻（20240131-235959）
+pub fn file_stamp(epoch_secs: u64) -> String {
+    let t = local_time(epoch_secs);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        t.year, t.month, t.day, t.hour, t.minute, t.second
+    )
+}
+
+/// 記録（--log-file）に使うローカル時刻（2024-01-31 23:59:59）
+pub fn log_stamp(epoch_secs: u64) -> String {
+    let t = local_time(epoch_secs);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        t.year, t.month, t.day, t.hour, t.minute, t.second
+    )
+}
+
+fn env_locale(category: &str) -> Option<String> {
+    ["LC_ALL", category, "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|v| !v.is_empty())
+}
+
+// "de_DE.UTF-8" -> ("de", "DE")
+fn split_locale(locale: &str) -> (String, String) {
+    let base = locale.split(['.', '@']).next().unwrap_or("");
+    let mut parts = base.split(['_', '-']);
+    let lang = parts.next().unwrap_or("").to_ascii_lowercase();
+    let region = parts.next().unwrap_or("").to_ascii_uppercase();
+    (lang, region)
+}
+
+fn separators(locale: &str) -> (&'static str, char) {
+    let (lang, region) = split_locale(locale);
+    match lang.as_str() {
+        "" | "c" | "posix" => ("", '.'),
+        "de" if region == "CH" => ("'", '.'),
+        "de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" => (".", ','),
+        "fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "no" | "uk" | "hu" => {
+            ("\u{a0}", ',')
+        }
+        _ => (",", '.'),
+    }
+}
+
+fn uses_12h_clock(locale: &str) -> bool {
+    let (lang, region) = split_locale(locale);
+    matches!(
+        (lang.as_str(), region.as_str()),
+        ("en", "US") | ("en", "CA") | ("en", "AU") | ("en", "NZ") | ("en", "PH") | ("en", "IN")
+    )
+}
+
+struct LocalTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+#[cfg(unix)]
+fn local_time(epoch_secs: u64) -> LocalTime {
+    let t = epoch_secs as libc::time_t;
+    // SAFETY: localtime_r は渡したバッファにだけ書き込む
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+        return utc_time(epoch_secs);
+    }
+    LocalTime {
+        year: tm.tm_year as i64 + 1900,
+        month: tm.tm_mon as u32 + 1,
+        day: tm.tm_mday as u32,
+        hour: tm.tm_hour as u32,
+        minute: tm.tm_min as u32,
+        second: tm.tm_sec as u32,
+    }
+}
+
+#[cfg(not(unix))]
+fn local_time(epoch_secs: u64) -> LocalTime {
+    utc_time(epoch_secs)
+}
+
+// 暦の計算（Howard Hinnant の days_from_civil の逆変換）
+fn utc_time(epoch_secs: u64) -> LocalTime {
+    let days = (epoch_secs / 86_400) as i64;
+    let secs = epoch_secs % 86_400;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    LocalTime {
+        year,
+        month,
+        day,
+        hour: (secs / 3_600) as u32,
+        minute: (secs / 60 % 60) as u32,
+        second: (secs % 60) as u32,
+    }
+}
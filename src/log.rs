@@ -0,0 +1,155 @@
+// 動作の記録（--log-file、[log]）
+//
+// TUI の間は代替画面に切り替えているので、stderr に出したものは見えずに消える。
+// 収集や操作の失敗、パニックを 1 行ずつファイルに追記し、書くたびに書き出して
+// 落ちたときも直前まで残るようにする。依存を増やさないよう tracing は使わず自前で書く。
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::locale;
+
+/// 記録する重さ。設定したもの以上を書く
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Level {
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub const NAMES: [&'static str; 4] = ["debug", "info", "warn", "error"];
+
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name.to_ascii_lowercase().as_str() {
+            "debug" => Some(Level::Debug),
+            "info" => Some(Level::Info),
+            "warn" | "warning" => Some(Level::Warn),
+            "error" => Some(Level::Error),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+}
+
+struct Logger {
+    file: Mutex<File>,
+    level: Level,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// ファイルを追記で開き、パニックも記録するようにする。2 回目以降は何もしない
+pub fn init(path: &Path, level: Level) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    if LOGGER
+        .set(Logger {
+            file: Mutex::new(file),
+            level,
+        })
+        .is_err()
+    {
+        return Ok(());
+    }
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let at = info
+            .location()
+            .map(|l| format!(" at {}:{}", l.file(), l.line()))
+            .unwrap_or_default();
+        let thread = std::thread::current();
+        write(
+            Level::Error,
+            format!(
+                "panic in {}{at}: {}",
+                thread.name().unwrap_or("a thread"),
+                panic_message(info.payload())
+            ),
+        );
+        previous(info);
+    }));
+    Ok(())
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(text) = payload.downcast_ref::<&str>() {
+        text
+    } else if let Some(text) = payload.downcast_ref::<String>() {
+        text
+    } else {
+        "(no message)"
+    }
+}
+
+pub fn error(message: impl Display) {
+    write(Level::Error, message);
+}
+
+pub fn warn(message: impl Display) {
+    write(Level::Warn, message);
+}
+
+pub fn info(message: impl Display) {
+    write(Level::Info, message);
+}
+
+pub fn debug(message: impl Display) {
+    write(Level::Debug, message);
+}
+
+// 開いていないか、設定より軽ければ書かない。書けなくても止めない
+fn write(level: Level, message: impl Display) {
+    let Some(logger) = LOGGER.get() else {
+        return;
+    };
+    if level < logger.level {
+        return;
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let line = format!(
+        "{} {:<5} {message}\n",
+        locale::log_stamp(now),
+        level.label()
+    );
+    // 他のスレッドがパニックして毒になっていても書く
+    let mut file = logger.file.lock().unwrap_or_else(|e| e.into_inner());
+    let _ = file.write_all(line.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Level;
+
+    #[test]
+    fn log_file_records_messages_at_or_above_the_level() {
+        let path = std::env::temp_dir().join(format!("rust-top-log-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        super::init(&path, Level::Warn).expect("open log file");
+        super::info("not recorded below warn");
+        super::error("Failed to send metrics: connection refused");
+
+        let text = std::fs::read_to_string(&path).expect("log file");
+        let _ = std::fs::remove_file(&path);
+        assert!(
+            text.lines()
+                .any(|line| line.contains("ERROR Failed to send metrics: connection refused")),
+            "{text}"
+        );
+        assert!(!text.contains("not recorded"), "{text}");
+    }
+}
@@ -1,28 +1,227 @@
+mod access;
+mod actions;
+mod alert;
+mod app;
+#[cfg(feature = "apple")]
+mod apple;
+mod baseline;
+mod batch;
+mod battery;
+mod bench;
+mod cast;
+mod cgroup;
+mod cgtree;
+mod cli;
+mod clipboard;
+#[cfg(target_os = "macos")]
+mod codesign;
+mod config;
+mod container;
+mod coretype;
+mod cpufreq;
+mod cpustat;
+mod custom;
+mod dashboard;
+#[cfg(feature = "net")]
+mod dns;
+mod doctor;
+mod events;
+mod export;
+mod faults;
+mod files;
+mod filter;
+#[cfg(feature = "gpu")]
+mod gpu;
+mod health;
+#[cfg(feature = "history")]
+mod history;
+mod i18n;
+mod idle;
+mod inhibit;
+#[cfg(feature = "ipmi")]
+mod ipmi;
+#[cfg(feature = "journal")]
+mod journal;
+mod json;
+mod layout;
+mod leak;
+mod locale;
+mod log;
+mod meminfo;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "net")]
+mod net;
+#[cfg(feature = "notify")]
+mod notify;
+mod numa;
+mod palette;
+mod par;
+mod pattern;
+mod peers;
+mod platform;
+#[cfg(feature = "plugin")]
+mod plugin;
+mod pressure;
+#[cfg(feature = "profile")]
+mod profile;
+mod remote;
+mod rpi;
+mod sampler;
+mod search;
+mod sensors;
+#[cfg(any(feature = "systemd", windows))]
+mod services;
+mod sessions;
+mod shm;
+mod signals;
+mod smaps;
+#[cfg(feature = "smart")]
+mod smart;
+mod source;
+mod state;
+mod summary;
+mod term;
+mod termux;
+mod text;
+mod theme;
+mod ui;
+mod watchdog;
+mod wchan;
+#[cfg(windows)]
+mod wininfo;
+#[cfg(windows)]
+mod winjob;
+mod zram;
+
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, Event},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
-use ratatui::{
-    Terminal,
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Style},
-    widgets::{Block, Borders, Paragraph, Row, Table},
-};
-use std::io;
+use ratatui::{Terminal, backend::CrosstermBackend};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::mpsc::TryRecvError;
 use std::time::{Duration, Instant};
-use sysinfo::{Product, System};
+
+use app::{App, Effect, FrameTimes};
+use cli::Args;
+use config::{Config, ConfigWatcher, RefreshConfig};
+use idle::IdleTracker;
+use sampler::{Paused, Request, SamplerHandle, Snapshot, Update};
+use source::MockSource;
+use watchdog::Watchdog;
 
 fn main() -> Result<(), io::Error> {
+    let args = match cli::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("rust-top: {e}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        print!("{}", cli::USAGE);
+        return Ok(());
+    }
+
+    // 設定ファイルの読み込み（TUI に入る前にエラーを表示できるように）
+    let config = config::load()?;
+
+    // 代替画面の間は stderr が見えないので、失敗とパニックはファイルに書く
+    if let Some(path) = args.log_file.as_ref().or(config.log_file.as_ref()) {
+        let level = args.log_level.or(config.log_level).unwrap_or_default();
+        if let Err(e) = log::init(path, level) {
+            eprintln!("rust-top: cannot open {}: {e}", path.display());
+            std::process::exit(2);
+        }
+        log::info(format!("rust-top {} started", env!("CARGO_PKG_VERSION")));
+    }
+
+    if args.doctor {
+        return doctor::run(&config);
+    }
+    if args.bench {
+        return bench::run(&args, &config);
+    }
+    if args.batch {
+        return batch::run(&args, &config);
+    }
+    if let Some(addr) = &args.serve {
+        return remote::serve(addr, &config);
+    }
+    #[cfg(not(feature = "history"))]
+    if args.history {
+        eprintln!("rust-top: history needs a build with the `history` feature");
+        std::process::exit(2);
+    }
+    #[cfg(feature = "history")]
+    if args.history {
+        // 保存が無いなどのエラーは TUI に入る前に表示する
+        let browser = history::Browser::open(&config.history)?;
+        return run_tui(&args, &config, |terminal| {
+            browser.run(terminal, &args, &config)
+        });
+    }
+    if let Some(name) = &args.profile
+        && !config.profiles.iter().any(|p| &p.name == name)
+    {
+        let names: Vec<&str> = config.profiles.iter().map(|p| p.name.as_str()).collect();
+        eprintln!(
+            "rust-top: no [profiles.{name}] in the config file (profiles: {})",
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        );
+        std::process::exit(2);
+    }
+    if args.dashboard && config.hosts.is_empty() {
+        eprintln!("rust-top: --dashboard needs at least one [[host]] in the config file");
+        std::process::exit(2);
+    }
+
+    // 要約は端末を元に戻してから書く
+    let mut summary = None;
+    run_tui(&args, &config, |terminal| {
+        if args.dashboard {
+            dashboard::run(terminal, &args, &config)
+        } else {
+            summary = run_app(terminal, &args, &config)?;
+            Ok(())
+        }
+    })?;
+    if let Some(summary) = summary {
+        print!("{summary}");
+    }
+    Ok(())
+}
+
+/// 端末を TUI モードに切り替えて run を呼び、終わったら元に戻す
+/// --record があれば画面への出力を録画する
+fn run_tui(
+    args: &Args,
+    config: &Config,
+    run: impl FnOnce(&mut Terminal<CrosstermBackend<cast::Output>>) -> io::Result<()>,
+) -> io::Result<()> {
+    // 録画ファイルを作れないときは TUI に入る前に終える
+    let mut output = cast::Output::new(args.record.as_deref())?;
+
     // 端末をTUIモードに切り替える
     enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    term::set_low_bandwidth(args.low_bandwidth || config.low_bandwidth);
+    execute!(output, EnterAlternateScreen)?;
+    term::enable_mouse(&mut output)?;
+    let backend = CrosstermBackend::new(output);
     let mut terminal = Terminal::new(backend)?;
+    signals::install();
 
-    let result = run_app(&mut terminal);
+    let result = run(&mut terminal);
 
     // 終了処理
     disable_raw_mode()?;
@@ -34,150 +233,472 @@ fn main() -> Result<(), io::Error> {
     terminal.show_cursor()?;
 
     if let Err(err) = result {
+        log::error(format!("stopped: {err}"));
         eprintln!("{:?}", err);
     }
 
     Ok(())
 }
 
-fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
-    let mut sys = System::new_all();
-    let tick_rate = Duration::from_secs(1);
-    let mut last_tick = Instant::now();
+/// 入力を待つ間隔。この間に届いたスナップショットは次に起きたときに描く
+const INPUT_POLL: Duration = Duration::from_millis(20);
+
+fn run_app<B: ratatui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    args: &Args,
+    config: &Config,
+) -> io::Result<Option<String>> {
+    // 収集はサンプラースレッド（またはリモートのエージェント）に任せ、
+    // ここでは描画と入力のみ扱う
+    let sampler = match &args.connect {
+        Some(addr) => remote::connect(addr)?,
+        None if args.mock => sampler::spawn_with(MockSource::default, config.refresh.clone()),
+        None => sampler::spawn(config.refresh.clone()),
+    };
+    run_view(terminal, args, config, sampler)
+}
+
+/// 通常の画面。q が押されるまで描画と入力を繰り返す
+/// --summary なら、終了したときに表示する要約を返す
+fn run_view<B: ratatui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    args: &Args,
+    config: &Config,
+    sampler: SamplerHandle,
+) -> io::Result<Option<String>> {
+    // 最初のフレームはデータを待たずに空のスナップショットで描画する。
+    // サンプラーが作った Arc をそのまま持ち、描画以外の利用者にも複製せずに渡す
+    let mut snapshot = Arc::new(Snapshot::default());
+
+    let mut refresh = config.refresh.clone();
+    let mut tick_rate = tick_rate(&refresh);
+    let mut idle = IdleTracker::new(config.idle.clone(), Instant::now());
+    // 設定ファイルが書き換わったら、配色・キー割り当て・レイアウト・更新間隔を入れ替える
+    let mut watcher = ConfigWatcher::new();
+    let mut last_draw = Instant::now();
+    let mut dirty = true;
+    // 遅い回線では、届いたデータは tick_rate ごとにまとめて描き、点滅もしない
+    let low_bandwidth = term::low_bandwidth();
+    // 前に描いてから新しいデータが届いたか。届いていなければ時計だけを描き直す
+    let mut fresh = false;
+    let mut last_frame = None;
+    let mut drawn = ui::Drawn::default();
+    let mut app = App::new(args, config);
+    // 前回の表示に戻す。ダッシュボードから開いた他のホストと --mock では読み書きしない
+    let state_path = if args.dashboard || args.mock {
+        None
+    } else {
+        state::state_path()
+    };
+    if let Some(saved) = state_path
+        .as_deref()
+        .filter(|_| !args.fresh)
+        .and_then(state::load)
+    {
+        saved.apply(&mut app);
+        if let Some(interval) = saved.interval
+            && sampler.requests.send(Request::Interval(interval)).is_err()
+        {
+            return Err(io::Error::other("sampler stopped"));
+        }
+    }
+    // --profile は前回の状態より優先する
+    if let Some(name) = &args.profile
+        && let Effect::Interval(interval) = app.apply_profile(name)
+        && sampler.requests.send(Request::Interval(interval)).is_err()
+    {
+        return Err(io::Error::other("sampler stopped"));
+    }
+    if args.caffeinate {
+        app.caffeinate(Some(true));
+    }
+    // [[panel]] と [[alert]] の run はこのマシンで動かすので、他のホストを見ているときは使わない
+    if args.connect.is_none() && !args.dashboard {
+        app.custom_panels = custom::spawn(&config.panels);
+        // --readonly では設定にあっても自動でコマンドを動かしたりシグナルを送ったりしない
+        if !args.readonly {
+            app.alert_actions = true;
+            app.watchdog = Watchdog::new(config.watchdogs.clone());
+        }
+        #[cfg(feature = "plugin")]
+        {
+            app.plugins = plugin::spawn(&config.plugins);
+        }
+    }
+    // ダッシュボードから開いた他のホストは保存しない
+    #[cfg(feature = "history")]
+    let mut recorder = if args.dashboard {
+        None
+    } else {
+        history::Recorder::open(&config.history)?
+    };
+    #[cfg(feature = "metrics")]
+    let mut metrics = if args.dashboard {
+        None
+    } else {
+        metrics::Sink::open(&config.metrics)?
+    };
+    #[cfg(feature = "mqtt")]
+    let mut mqtt = if args.dashboard {
+        None
+    } else {
+        mqtt::Publisher::open(&config.mqtt)
+    };
+    // 保存するスナップショットは欠けないように、保存中は収集を止めない
+    #[cfg(feature = "history")]
+    let may_pause = recorder.is_none();
+    #[cfg(not(feature = "history"))]
+    let may_pause = true;
+    let mut paused = Paused::default();
+    let mut memory_detail = false;
+    let mut numa_nodes = false;
+    let mut fd_counts = false;
+    let mut wait_channels = false;
+    let mut page_faults = false;
+    let mut net_traffic = false;
+    #[cfg(feature = "plugin")]
+    let mut plugin_generation = 0;
+    // 有効にしたソースが使えなければ、最初の一覧が届いたときに知らせる
+    let mut checked = args.connect.is_some() || args.dashboard;
 
     loop {
-        // 情報更新
-        sys.refresh_all();
-
-        terminal.draw(|f| {
-            let size = f.area();
-
-            // レイアウト（縦分割）
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .margin(1)
-                .constraints([
-                    Constraint::Length(3),
-                    Constraint::Length(3),
-                    Constraint::Min(8),
-                    Constraint::Min(10),
-                ])
-                .split(size);
-
-            // CPU情報
-            let cpu_usage: f32 = sys.cpus().iter().map(|c| c.cpu_usage()).sum::<f32>();
-            let all_cpu_usage: f32 = sys.cpus().len() as f32 * 100.0;
-            let cpu_block = Paragraph::new(format!(
-                "CPU Usage: {:.1}% / {}%",
-                cpu_usage, all_cpu_usage
-            ))
-            .block(Block::default().borders(Borders::ALL).title("CPU"))
-            .style(Style::default().fg(Color::Yellow));
-            f.render_widget(cpu_block, chunks[0]);
-
-            // メモリ情報
-            let total_memory = sys.total_memory() / 1024 / 1024;
-            let used_memory = (sys.used_memory()) / 1024 / 1024;
-            // let free_swap = sys.free_swap();
-            let total_swap = sys.total_swap() / 1024 / 1024;
-            let used_swap = sys.used_swap() / 1024 / 1024;
-            let mem_block = Paragraph::new(format!("Memory: {used_memory} MB / {total_memory} MB, Swap: {used_swap} MB / {total_swap} MB"))
-                .block(Block::default().borders(Borders::ALL).title("Memory"))
-                .style(Style::default().fg(Color::Cyan));
-            f.render_widget(mem_block, chunks[1]);
-
-            // プロセス情報（上位5件）
-            let mut processes: Vec<_> = sys.processes().values().collect();
-            processes.sort_by_key(|p| -(p.cpu_usage() as i32));
-            let rows: Vec<Row> = processes
-                .iter()
-                .take(5)
-                .map(|p| {
-                    Row::new(vec![
-                        p.pid().to_string(),
-                        p.name().to_string_lossy().to_string(),
-                        format!("{:.1}%", p.cpu_usage()),
-                        format!("{:.1} MB", p.memory() as f64 / 1024.0),
-                    ])
-                })
-                .collect();
-            let table = Table::new(
-                rows,
-                [
-                    Constraint::Length(8),
-                    Constraint::Length(25),
-                    Constraint::Length(10),
-                    Constraint::Length(12),
-                ],
-            )
-            .header(
-                Row::new(vec!["PID", "Name", "CPU", "Memory"])
-                    .style(Style::default().fg(Color::Green)),
-            )
-            .block(Block::default().borders(Borders::ALL).title("Processes"));
-            f.render_widget(table, chunks[2]);
-
-
-            // システム情報
-            let mut info_rows: Vec<Row> = Vec::new();
-
-            let number_of_cpus = sys.cpus().len().to_string();
-            info_rows.push(Row::new(vec!["Number of cpus", number_of_cpus.as_str()]));
-
-            let cpu_arch = System::cpu_arch();
-            info_rows.push(Row::new(vec!["CPU Architecture", cpu_arch.as_str()]));
-
-            let brand: &str = sys.cpus().get(0).map_or("Unknown", |c| c.brand());
-            info_rows.push(Row::new(vec!["Brand", brand]));
-
-            let uptime = System::uptime().to_string();
-            info_rows.push(Row::new(vec!["Uptime", uptime.as_str()]));
-
-            let kernel_long_version = System::kernel_long_version();
-            info_rows.push(Row::new(vec!["kernel long version", kernel_long_version.as_str()]));
-
-            let long_os_version = System::long_os_version();
-            info_rows.push(Row::new(vec!["long os version", long_os_version.as_deref().unwrap_or("Unknown")]));
-
-            let host_name = System::host_name();
-            info_rows.push(Row::new(vec!["Host name", host_name.as_deref().unwrap_or("Unknown")]));
-
-            let open_files_limit = System::open_files_limit();
-            let open_files_limit_str = open_files_limit
-                .map(|v| v.to_string())
-                .unwrap_or_else(|| "Unknown".to_string());
-            info_rows.push(Row::new(vec!["Open files limit", open_files_limit_str.as_str()]));
-
-            let product_name = Product::name();
-            info_rows.push(Row::new(vec!["Product Name", product_name.as_deref().unwrap_or("Unknown")]));
-            
-            let vendor_name = Product::vendor_name();
-            info_rows.push(Row::new(vec!["Vendor name", vendor_name.as_deref().unwrap_or("Unknown")]));
-
-            // info_rows.push(Row::new(vec![]));
-            // info_rows.push(Row::new(vec![]));
-
-            let info_table = Table::new(info_rows, [
-                Constraint::Length(25),
-                Constraint::Length(60),
-            ]).block(Block::default().borders(Borders::ALL).title("Info"));
-            f.render_widget(info_table, chunks[3]);
-        })?;
-
-        let timeout = tick_rate
-            .checked_sub(last_tick.elapsed())
-            .unwrap_or_else(|| Duration::from_secs(0));
-        if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                if key.code == KeyCode::Char('q') {
-                    break;
+        // 最新のスナップショットと操作結果を取り出す
+        loop {
+            match sampler.updates.try_recv() {
+                Ok(Update::Snapshot(s, changes)) => {
+                    dirty |= !low_bandwidth;
+                    fresh = true;
+                    snapshot = s;
+                    app.on_snapshot(&snapshot, &changes);
+                }
+                Ok(Update::Action(outcome)) => {
+                    dirty = true;
+                    app.handle_outcome(outcome, &snapshot);
+                }
+                Ok(Update::Interval(result)) => {
+                    dirty = true;
+                    app.handle_interval(result);
+                }
+                #[cfg(feature = "environ")]
+                Ok(Update::Environ(target, result)) => {
+                    dirty = true;
+                    app.handle_environ(target, result);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    return Err(io::Error::other("sampler stopped"));
+                }
+            }
+        }
+        // [[watchdog]] が送るシグナル
+        for command in app.take_watchdog_commands() {
+            if sampler.requests.send(Request::Action(command)).is_err() {
+                return Err(io::Error::other("sampler stopped"));
+            }
+        }
+        #[cfg(feature = "plugin")]
+        if snapshot.process_generation != plugin_generation {
+            plugin_generation = snapshot.process_generation;
+            for plugin in &app.plugins {
+                plugin.send(&snapshot);
+            }
+        }
+        #[cfg(feature = "history")]
+        if let Some(recorder) = &mut recorder
+            && let Err(e) = recorder.record(&snapshot)
+        {
+            app.error(format!("Failed to save history: {e}"));
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(sink) = &mut metrics
+            && let Err(e) = sink.send(&snapshot)
+        {
+            app.error(format!("Failed to send metrics: {e}"));
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(publisher) = &mut mqtt
+            && let Err(e) = publisher.publish(&snapshot)
+        {
+            app.error(format!("Failed to publish to MQTT: {e}"));
+        }
+        // 書き間違えても止まらず、前の設定のまま続ける
+        match watcher.as_mut().and_then(ConfigWatcher::poll) {
+            Some(Ok(reloaded)) => {
+                dirty = true;
+                log::info("config reloaded");
+                app.reload_config(&reloaded);
+                if idle
+                    .set_config(reloaded.idle.clone(), Instant::now())
+                    .is_some()
+                {
+                    apply_idle(&sampler, &mut app, &idle, &refresh)?;
                 }
+                if reloaded.refresh != refresh {
+                    refresh = reloaded.refresh;
+                    tick_rate = self::tick_rate(&refresh);
+                    app.refresh_interval = refresh.processes;
+                    app.chosen_interval = None;
+                    if sampler
+                        .requests
+                        .send(Request::Refresh(refresh.clone()))
+                        .is_err()
+                    {
+                        return Err(io::Error::other("sampler stopped"));
+                    }
+                }
+            }
+            Some(Err(e)) => {
+                dirty = true;
+                app.error(format!("Config not reloaded: {e}"));
+            }
+            None => {}
+        }
+
+        // 操作も負荷も無いあいだは収集の間隔を延ばし、戻ったら設定どおりにする
+        let cpu = f64::from(snapshot.cpu.usage_sum) / snapshot.cpu.count.max(1) as f64;
+        if idle.update(Instant::now(), cpu).is_some() {
+            dirty = true;
+            apply_idle(&sampler, &mut app, &idle, &refresh)?;
+        }
+
+        if !checked && snapshot.process_generation > 0 {
+            checked = true;
+            if let Some(text) = doctor::startup_notice(&snapshot, config) {
+                app.set_message(text);
+            }
+            // 作り物の値ではこのマシンのディスクと温度は見ない
+            let probe = if args.mock {
+                health::Probe::default()
+            } else {
+                health::Probe::local()
+            };
+            app.health = health::check(&snapshot, &probe, &app.locale);
+        }
+
+        // 隠したパネルの収集を止め、表示し直したら再開する
+        let wanted = app.paused_sources();
+        if may_pause && wanted != paused {
+            paused = wanted;
+            if sampler.requests.send(Request::Pause(paused)).is_err() {
+                return Err(io::Error::other("sampler stopped"));
+            }
+        }
+        // smaps_rollup は重いので、内訳の列を出している間だけ読ませる
+        if app.memory_detail != memory_detail {
+            memory_detail = app.memory_detail;
+            if sampler
+                .requests
+                .send(Request::MemoryDetail(memory_detail))
+                .is_err()
+            {
+                return Err(io::Error::other("sampler stopped"));
+            }
+        }
+        // numa_maps も同じく、Node 列を出している間だけ
+        if app.numa_nodes != numa_nodes {
+            numa_nodes = app.numa_nodes;
+            if sampler
+                .requests
+                .send(Request::NumaNodes(numa_nodes))
+                .is_err()
+            {
+                return Err(io::Error::other("sampler stopped"));
+            }
+        }
+        // 開いているファイルも FDs 列を出している間だけ数える
+        if app.fd_counts != fd_counts {
+            fd_counts = app.fd_counts;
+            if sampler.requests.send(Request::FdCounts(fd_counts)).is_err() {
+                return Err(io::Error::other("sampler stopped"));
+            }
+        }
+        // 待っている関数も WChan 列を出している間だけ読む
+        if app.wait_channels != wait_channels {
+            wait_channels = app.wait_channels;
+            if sampler
+                .requests
+                .send(Request::WaitChannels(wait_channels))
+                .is_err()
+            {
+                return Err(io::Error::other("sampler stopped"));
+            }
+        }
+        // フォールトの数も MinFlt / MajFlt 列を出している間だけ読む
+        if app.page_faults != page_faults {
+            page_faults = app.page_faults;
+            if sampler
+                .requests
+                .send(Request::PageFaults(page_faults))
+                .is_err()
+            {
+                return Err(io::Error::other("sampler stopped"));
+            }
+        }
+        // 送受信量も RX / TX 列を出している間だけ ss で読む
+        if app.net_traffic != net_traffic {
+            net_traffic = app.net_traffic;
+            if sampler
+                .requests
+                .send(Request::NetTraffic(net_traffic))
+                .is_err()
+            {
+                return Err(io::Error::other("sampler stopped"));
+            }
+        }
+
+        // kill や端末を閉じたことで届いたシグナルも q と同じように抜ける
+        if signals::quit_requested(None) {
+            return Ok(finish(state_path.as_deref(), &app, args));
+        }
+        // kill -TSTP で止められたとき
+        if signals::suspend_requested(None) {
+            signals::suspend(terminal)?;
+            dirty = true;
+        }
+
+        // 入力・新しいデータ・時計の更新があったときだけ描き直す。
+        // 時計しか進んでいなければ、前の画面のステータスバーだけを描き直し、
+        // それも変わらなければ端末には何も送らない
+        let due = last_draw.elapsed() >= tick_rate;
+        // スクリーンリーダー向けのカーソルは描くたびに置き直すので、plain では全体を描く
+        let status_only = last_frame.as_ref().zip(
+            drawn
+                .status
+                .filter(|_| due && !fresh && !app.ticking() && !app.plain),
+        );
+        if dirty || (app.flashing() && !low_bandwidth) || (due && status_only.is_none()) {
+            let draw_started = Instant::now();
+            let frame = terminal.draw(|f| drawn = ui::draw(f, &app, &snapshot))?;
+            last_frame = Some(frame.buffer.clone());
+            fresh = false;
+            let exported = app.export_screen(frame.buffer);
+            app.set_process_rows(drawn.process_rows);
+            app.set_popup_rows(drawn.popup_rows);
+            app.record_frame(FrameTimes {
+                prepare: drawn.prepare_time,
+                render: draw_started.elapsed().saturating_sub(drawn.prepare_time),
+            });
+            last_draw = draw_started;
+            dirty = exported;
+        } else if let Some((previous, area)) = status_only {
+            last_draw = Instant::now();
+            if let Some(buffer) = ui::redraw_status(previous, area, &app) {
+                let frame = terminal.draw(|f| {
+                    if f.area() == buffer.area {
+                        *f.buffer_mut() = buffer;
+                    }
+                })?;
+                last_frame = Some(frame.buffer.clone());
             }
         }
-        if last_tick.elapsed() >= tick_rate {
-            last_tick = Instant::now();
+        if app.take_bell() {
+            let mut stdout = io::stdout();
+            stdout.write_all(b"\x07")?;
+            stdout.flush()?;
+        }
+
+        // サンプラーからの更新も拾えるよう、入力は短い間隔で待つ。
+        // 溜まった入力（キーの連打やスクロール）はまとめて処理してから 1 回だけ描く
+        let mut timeout = idle.input_poll(INPUT_POLL);
+        while crossterm::event::poll(timeout)? {
+            timeout = Duration::ZERO;
+            dirty = true;
+            if idle.active(Instant::now()).is_some() {
+                apply_idle(&sampler, &mut app, &idle, &refresh)?;
+            }
+            match event::read()? {
+                Event::Key(key) if signals::quit_requested(Some(&key)) => {
+                    return Ok(finish(state_path.as_deref(), &app, args));
+                }
+                Event::Key(key) if signals::suspend_requested(Some(&key)) => {
+                    signals::suspend(terminal)?
+                }
+                Event::Key(key) => match app.handle_key(key, &snapshot) {
+                    Effect::Quit => {
+                        return Ok(finish(state_path.as_deref(), &app, args));
+                    }
+                    Effect::Send(command) => {
+                        if sampler.requests.send(Request::Action(command)).is_err() {
+                            return Err(io::Error::other("sampler stopped"));
+                        }
+                    }
+                    Effect::SendAll(commands) => {
+                        for command in commands {
+                            if sampler.requests.send(Request::Action(command)).is_err() {
+                                return Err(io::Error::other("sampler stopped"));
+                            }
+                        }
+                    }
+                    Effect::Interval(interval) => {
+                        if sampler.requests.send(Request::Interval(interval)).is_err() {
+                            return Err(io::Error::other("sampler stopped"));
+                        }
+                    }
+                    Effect::Copy(text) => {
+                        if let Err(e) = clipboard::copy(&mut io::stdout(), &text) {
+                            app.set_message(format!("Cannot copy to the clipboard: {e}"));
+                        }
+                    }
+                    #[cfg(feature = "environ")]
+                    Effect::Environ(target) => {
+                        if sampler.requests.send(Request::Environ(target)).is_err() {
+                            return Err(io::Error::other("sampler stopped"));
+                        }
+                    }
+                    Effect::None => {}
+                },
+                Event::Mouse(mouse) => {
+                    let target = ui::hit_test(&drawn, mouse.column, mouse.row);
+                    app.handle_mouse(mouse, target, &snapshot);
+                }
+                _ => {}
+            }
         }
     }
+}
 
-    Ok(())
+// 止まっているかどうかが変わったら、:interval で選んだ間隔も含めて送り直す
+fn apply_idle(
+    sampler: &SamplerHandle,
+    app: &mut App,
+    idle: &IdleTracker,
+    refresh: &RefreshConfig,
+) -> io::Result<()> {
+    let mut refresh = refresh.clone();
+    if let Some(interval) = app.chosen_interval {
+        refresh.cpu = interval;
+        refresh.memory = interval;
+        refresh.processes = interval;
+        refresh.info = interval;
+    }
+    let refresh = idle.stretch(&refresh);
+    app.idle_interval = idle.is_idle().then_some(refresh.processes);
+    sampler
+        .requests
+        .send(Request::Refresh(refresh))
+        .map_err(|_| io::Error::other("sampler stopped"))
+}
+
+// データが届かなくても、時計やメッセージのためにこの間隔で描き直す
+fn tick_rate(refresh: &RefreshConfig) -> Duration {
+    [refresh.cpu, refresh.memory, refresh.processes, refresh.info]
+        .into_iter()
+        .min()
+        .unwrap_or(Duration::from_secs(1))
+}
+
+/// 終了するときに状態を残し、--summary なら要約を作る
+fn finish(path: Option<&Path>, app: &App, args: &Args) -> Option<String> {
+    save_state(path, app);
+    args.summary
+        .then(|| app.session.report(&app.locale, Instant::now()))
+}
+
+// 終了を妨げないよう、書けなくてもログに残すだけで終える
+fn save_state(path: Option<&Path>, app: &App) {
+    if let Some(path) = path
+        && let Err(e) = state::save(path, &state::UiState::from_app(app))
+    {
+        log::warn(format!("cannot save {}: {e}", path.display()));
+    }
 }
@@ -0,0 +1,214 @@
+// メモリの内訳（Linux の /proc/meminfo）
+//
+// sysinfo の used は available を引いた値で、バッファとキャッシュの大きさは分からない。
+// キャッシュを含めた「使用中」で驚かせないよう、free と同じ区分で内訳を出す。
+// 同じファイルからヒュージページとオーバーコミットの状況も読む。
+
+/// 単位はバイト
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MemoryBreakdown {
+    pub free: u64,
+    pub buffers: u64,
+    /// ページキャッシュと回収できるスラブ（free の buff/cache と同じ）
+    pub cached: u64,
+    /// スワップせずに新たに使える量の見積もり
+    pub available: u64,
+    /// tmpfs と共有メモリ（Shmem）。cached に含まれるが、捨てられない
+    pub shared: u64,
+}
+
+impl MemoryBreakdown {
+    /// アプリケーションが使っている量（空き・バッファ・キャッシュを除いたもの）
+    pub fn used(&self, total: u64) -> u64 {
+        total.saturating_sub(self.free + self.buffers + self.cached)
+    }
+}
+
+/// ヒュージページとオーバーコミット。ページ数の項目以外はバイト
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HugePages {
+    /// 予約したヒュージページの数（HugePages_Total / Free / Rsvd / Surp）
+    pub total: u64,
+    pub free: u64,
+    pub reserved: u64,
+    pub surplus: u64,
+    /// ヒュージページ 1 つの大きさ
+    pub page_size: u64,
+    /// Transparent Huge Pages で確保された量（AnonHugePages と ShmemHugePages）
+    pub anon_huge: u64,
+    pub shmem_huge: u64,
+    /// THP の設定。読めなければ None
+    pub thp: Option<ThpMode>,
+    /// ヒュージページ以上の大きさで連続した空き（/proc/buddyinfo）。読めなければ None
+    pub contiguous_free: Option<u64>,
+    /// overcommit_memory が 2 のとき、これを超えて確保できない
+    pub commit_limit: u64,
+    /// 確保を約束した量の合計（Committed_AS）
+    pub committed: u64,
+    /// /proc/sys/vm/overcommit_memory。読めなければ None
+    pub overcommit: Option<Overcommit>,
+}
+
+/// /sys/kernel/mm/transparent_hugepage/enabled で [ ] に囲まれたもの
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThpMode {
+    Always,
+    Madvise,
+    Never,
+}
+
+impl ThpMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            ThpMode::Always => "always",
+            ThpMode::Madvise => "madvise",
+            ThpMode::Never => "never",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<ThpMode> {
+        [ThpMode::Always, ThpMode::Madvise, ThpMode::Never]
+            .into_iter()
+            .find(|mode| mode.label() == label)
+    }
+}
+
+/// overcommit_memory の 0 / 1 / 2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overcommit {
+    Heuristic,
+    Always,
+    /// CommitLimit を超える確保は失敗する
+    Strict,
+}
+
+impl Overcommit {
+    pub fn label(self) -> &'static str {
+        match self {
+            Overcommit::Heuristic => "heuristic",
+            Overcommit::Always => "always",
+            Overcommit::Strict => "strict",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Overcommit> {
+        [
+            Overcommit::Heuristic,
+            Overcommit::Always,
+            Overcommit::Strict,
+        ]
+        .into_iter()
+        .find(|mode| mode.label() == label)
+    }
+}
+
+/// 内訳とヒュージページ。どちらも読めなければ None（Linux 以外）
+#[cfg(target_os = "linux")]
+pub fn collect() -> (Option<MemoryBreakdown>, Option<HugePages>) {
+    let Ok(text) = std::fs::read_to_string("/proc/meminfo") else {
+        return (None, None);
+    };
+    (breakdown(&text), huge_pages(&text))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn collect() -> (Option<MemoryBreakdown>, Option<HugePages>) {
+    (None, None)
+}
+
+// "MemFree:  16318428 kB" や "HugePages_Total:  0" の形の行を (名前, 値) にする
+// kB の付いた値はバイトにする
+#[cfg(target_os = "linux")]
+fn fields(text: &str) -> impl Iterator<Item = (&str, u64)> {
+    text.lines().filter_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        let value = value.trim();
+        let bytes = match value.strip_suffix("kB") {
+            Some(kib) => kib.trim().parse::<u64>().ok()? * 1024,
+            None => value.parse().ok()?,
+        };
+        Some((key, bytes))
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn breakdown(text: &str) -> Option<MemoryBreakdown> {
+    let mut free = None;
+    let mut available = None;
+    let mut breakdown = MemoryBreakdown::default();
+    for (key, bytes) in fields(text) {
+        match key {
+            "MemFree" => free = Some(bytes),
+            "MemAvailable" => available = Some(bytes),
+            "Buffers" => breakdown.buffers = bytes,
+            "Shmem" => breakdown.shared = bytes,
+            "Cached" | "SReclaimable" => breakdown.cached += bytes,
+            _ => {}
+        }
+    }
+    breakdown.free = free?;
+    // 3.14 より前のカーネルには MemAvailable が無い
+    breakdown.available =
+        available.unwrap_or(breakdown.free + breakdown.buffers + breakdown.cached);
+    Some(breakdown)
+}
+
+#[cfg(target_os = "linux")]
+fn huge_pages(text: &str) -> Option<HugePages> {
+    let mut huge = HugePages::default();
+    let mut page_size = None;
+    for (key, value) in fields(text) {
+        match key {
+            "HugePages_Total" => huge.total = value,
+            "HugePages_Free" => huge.free = value,
+            "HugePages_Rsvd" => huge.reserved = value,
+            "HugePages_Surp" => huge.surplus = value,
+            "Hugepagesize" => page_size = Some(value),
+            "AnonHugePages" => huge.anon_huge = value,
+            "ShmemHugePages" => huge.shmem_huge = value,
+            "CommitLimit" => huge.commit_limit = value,
+            "Committed_AS" => huge.committed = value,
+            _ => {}
+        }
+    }
+    // ヒュージページに対応していないカーネルには Hugepagesize が無い
+    huge.page_size = page_size?;
+    huge.thp = std::fs::read_to_string("/sys/kernel/mm/transparent_hugepage/enabled")
+        .ok()
+        .and_then(|text| {
+            let selected = text.split_once('[')?.1.split_once(']')?.0;
+            ThpMode::from_label(selected)
+        });
+    huge.overcommit = std::fs::read_to_string("/proc/sys/vm/overcommit_memory")
+        .ok()
+        .and_then(|text| match text.trim() {
+            "0" => Some(Overcommit::Heuristic),
+            "1" => Some(Overcommit::Always),
+            "2" => Some(Overcommit::Strict),
+            _ => None,
+        });
+    huge.contiguous_free = std::fs::read_to_string("/proc/buddyinfo")
+        .ok()
+        .map(|text| contiguous_free(&text, huge.page_size));
+    Some(huge)
+}
+
+// buddyinfo の行は "Node 0, zone   Normal  120  80 ... " で、n 列目が 2^n ページの空きブロックの数
+// ヒュージページ以上の大きさのブロックを足す。断片化が進むとここが減る
+#[cfg(target_os = "linux")]
+fn contiguous_free(text: &str, huge_page_size: u64) -> u64 {
+    // SAFETY: 引数は定数だけ
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as u64;
+    text.lines()
+        .filter_map(|line| line.split_once("zone"))
+        .flat_map(|(_, rest)| {
+            rest.split_whitespace()
+                .skip(1)
+                .filter_map(|count| count.parse::<u64>().ok())
+                .enumerate()
+        })
+        .map(|(order, count)| (page_size << order, count))
+        .filter(|(block, _)| *block >= huge_page_size)
+        .map(|(block, count)| block * count)
+        .sum()
+}
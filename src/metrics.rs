@@ -0,0 +1,401 @@
+// 毎回の値を外に送る（metrics フィーチャー）
+//
+// [metrics] target に StatsD（UDP）か InfluxDB の line protocol で、CPU・メモリ・ロードアベレージと
+// CPU 上位のプロセスの値を書く。エクスポーターを別に動かさずに Grafana などでグラフにできる。
+// target は udp://host:port、tcp://host:port かファイルのパス（追記する）。
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::{MetricsConfig, MetricsFormat};
+use crate::sampler::{ProcessInfo, Snapshot};
+
+/// TCP でつなぐとき・書くときに待つ長さ
+const TIMEOUT: Duration = Duration::from_secs(1);
+/// つなげなかったときに次に試すまでの間隔。失敗が続くと倍にしていく
+const RETRY_FIRST: Duration = Duration::from_secs(1);
+const RETRY_MAX: Duration = Duration::from_secs(60);
+/// StatsD の 1 つのパケットに詰める大きさ（分割されない MTU の範囲）
+const PACKET: usize = 1400;
+
+enum Output {
+    Udp(UdpSocket),
+    /// 切れたら次に送るときにつなぎ直す
+    Tcp(Option<TcpStream>),
+    File(File),
+}
+
+/// 設定した間隔ごとに値を送る
+///
+/// 名前解決・接続・書き込みは相手が落ちていたり読まなかったりすると止まるので、
+/// 描画のスレッドでは行を作って渡すだけにし、書くのは別スレッドでする。
+pub struct Sink {
+    config: MetricsConfig,
+    last: Option<Instant>,
+    /// 書くスレッドへ。書いている途中なら今回の値は捨てる
+    lines: Option<SyncSender<Vec<String>>>,
+    /// 書くスレッドで起きた失敗
+    errors: Receiver<io::Error>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Sink {
+    /// [metrics] target が無ければ None
+    pub fn open(config: &MetricsConfig) -> io::Result<Option<Self>> {
+        let Some(target) = &config.target else {
+            return Ok(None);
+        };
+        let output = if target.starts_with("udp://") {
+            let addr = resolve(target)?;
+            let local = if addr.is_ipv6() {
+                "[::]:0"
+            } else {
+                "0.0.0.0:0"
+            };
+            let socket = UdpSocket::bind(local)?;
+            socket.connect(addr)?;
+            Output::Udp(socket)
+        } else if target.starts_with("tcp://") {
+            Output::Tcp(None)
+        } else {
+            Output::File(OpenOptions::new().create(true).append(true).open(target)?)
+        };
+        let mut writer = Writer {
+            target: target.clone(),
+            output,
+            retry: None,
+            failing: false,
+        };
+        let (lines, queue) = mpsc::sync_channel::<Vec<String>>(1);
+        let (errors_tx, errors) = mpsc::channel();
+        // Sink を捨てると送り手が無くなって終わる
+        let worker = thread::spawn(move || {
+            while let Ok(lines) = queue.recv() {
+                if let Some(e) = writer.send(&lines)
+                    && errors_tx.send(e).is_err()
+                {
+                    return;
+                }
+            }
+        });
+        Ok(Some(Self {
+            config: config.clone(),
+            last: None,
+            lines: Some(lines),
+            errors,
+            worker: Some(worker),
+        }))
+    }
+
+    /// 前回から間隔が空いていれば送るよう頼む。前に頼んだ分の失敗があれば返す
+    pub fn send(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        if let Ok(e) = self.errors.try_recv() {
+            return Err(e);
+        }
+        // 使用率が確定するまでの値は送らない
+        if !snapshot.cpu_primed
+            || self
+                .last
+                .is_some_and(|last| last.elapsed() < self.config.interval)
+        {
+            return Ok(());
+        }
+        self.last = Some(Instant::now());
+        let lines = match self.config.format {
+            MetricsFormat::Statsd => statsd(snapshot, &self.config),
+            MetricsFormat::Influx => influx(snapshot, &self.config, unix_nanos()),
+        };
+        if let Some(sender) = &self.lines
+            && let Err(TrySendError::Disconnected(_)) = sender.try_send(lines)
+        {
+            return Err(io::Error::other("the metrics thread stopped"));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Sink {
+    // 頼んだ分を書き終えるまで待つ（待つのは書き込みの時間切れまで）
+    fn drop(&mut self) {
+        self.lines = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// 書くスレッドが持つ送り先
+struct Writer {
+    target: String,
+    output: Output,
+    /// TCP でつなげなかったときの、次に試す時刻と次の間隔
+    retry: Option<(Instant, Duration)>,
+    /// 失敗が続いている間は最初の 1 回だけ知らせる
+    failing: bool,
+}
+
+impl Writer {
+    /// 知らせる失敗があれば返す
+    fn send(&mut self, lines: &[String]) -> Option<io::Error> {
+        match self.write(lines) {
+            Ok(()) => {
+                self.failing = false;
+                None
+            }
+            Err(_) if self.failing => None,
+            Err(e) => {
+                self.failing = true;
+                Some(e)
+            }
+        }
+    }
+
+    fn write(&mut self, lines: &[String]) -> io::Result<()> {
+        match &mut self.output {
+            Output::Udp(socket) => {
+                for packet in packets(lines) {
+                    socket.send(packet.as_bytes())?;
+                }
+                Ok(())
+            }
+            Output::Tcp(stream) => {
+                if stream.is_none() {
+                    if let Some((at, _)) = self.retry
+                        && Instant::now() < at
+                    {
+                        return Err(io::Error::other("waiting to reconnect"));
+                    }
+                    match connect(&self.target) {
+                        Ok(connected) => {
+                            *stream = Some(connected);
+                            self.retry = None;
+                        }
+                        Err(e) => {
+                            let wait = self.retry.map_or(RETRY_FIRST, |(_, wait)| wait);
+                            self.retry = Some((Instant::now() + wait, (wait * 2).min(RETRY_MAX)));
+                            return Err(e);
+                        }
+                    }
+                }
+                let result = stream
+                    .as_mut()
+                    .expect("just connected")
+                    .write_all(lines.concat().as_bytes());
+                if result.is_err() {
+                    *stream = None;
+                }
+                result
+            }
+            Output::File(file) => file.write_all(lines.concat().as_bytes()),
+        }
+    }
+}
+
+// 読まなくなった相手で止まらないよう、書き込みにも時間切れを付ける
+fn connect(target: &str) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(&resolve(target)?, TIMEOUT)?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    Ok(stream)
+}
+
+// udp:// や tcp:// を除いた host:port を引く
+fn resolve(target: &str) -> io::Result<SocketAddr> {
+    let addr = target.split_once("://").map_or(target, |(_, addr)| addr);
+    addr.to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::other(format!("{addr} has no address")))
+}
+
+// 改行で区切って PACKET バイトまで詰める
+fn packets(lines: &[String]) -> Vec<String> {
+    let mut packets: Vec<String> = Vec::new();
+    for line in lines {
+        match packets.last_mut() {
+            Some(packet) if packet.len() + line.len() <= PACKET => packet.push_str(line),
+            _ => packets.push(line.clone()),
+        }
+    }
+    packets
+}
+
+/// StatsD のゲージ（prefix.cpu.usage:12.5|g）。1 行ずつ改行で終わる
+fn statsd(snapshot: &Snapshot, config: &MetricsConfig) -> Vec<String> {
+    let prefix = &config.prefix;
+    let mut lines: Vec<String> = host_values(snapshot)
+        .into_iter()
+        .map(|(name, value)| format!("{prefix}.{}:{value}|g\n", name.replace('_', ".")))
+        .collect();
+    for p in top_processes(snapshot, config.processes) {
+        let name = format!("{}_{}", statsd_name(&p.name), p.pid);
+        lines.push(format!(
+            "{prefix}.process.{name}.cpu:{:.1}|g\n",
+            p.cpu_usage
+        ));
+        lines.push(format!("{prefix}.process.{name}.memory:{}|g\n", p.memory));
+    }
+    lines
+}
+
+/// InfluxDB の line protocol。ホストの値を 1 行、プロセスごとに 1 行
+fn influx(snapshot: &Snapshot, config: &MetricsConfig, time: u128) -> Vec<String> {
+    let prefix = &config.prefix;
+    let host = influx_tag(snapshot.info.host_name.as_deref().unwrap_or("unknown"));
+    let fields: Vec<String> = host_values(snapshot)
+        .into_iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect();
+    let mut lines = vec![format!(
+        "{prefix},host={host} {} {time}\n",
+        fields.join(",")
+    )];
+    for p in top_processes(snapshot, config.processes) {
+        lines.push(format!(
+            "{prefix}_process,host={host},name={},pid={} cpu={:.1},memory={} {time}\n",
+            influx_tag(&p.name),
+            p.pid,
+            p.cpu_usage,
+            p.memory
+        ));
+    }
+    lines
+}
+
+// ホスト全体の値（名前は InfluxDB のフィールド名。StatsD では _ を . にする）
+fn host_values(snapshot: &Snapshot) -> Vec<(&'static str, String)> {
+    let memory = &snapshot.memory;
+    let load = snapshot.info.load_average;
+    let processes = snapshot.processes.iter().filter(|p| !p.is_thread).count();
+    vec![
+        ("cpu_usage", format!("{:.1}", snapshot.cpu.normalized())),
+        ("memory_used", memory.used.to_string()),
+        ("memory_total", memory.total.to_string()),
+        ("swap_used", memory.used_swap.to_string()),
+        ("load_1", format!("{:.2}", load[0])),
+        ("load_5", format!("{:.2}", load[1])),
+        ("load_15", format!("{:.2}", load[2])),
+        ("processes", processes.to_string()),
+    ]
+}
+
+fn top_processes(snapshot: &Snapshot, count: usize) -> Vec<&ProcessInfo> {
+    let mut processes: Vec<_> = snapshot.processes.iter().filter(|p| !p.is_thread).collect();
+    processes.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage));
+    processes.truncate(count);
+    processes
+}
+
+// StatsD の名前では . と : と | が区切りになるので、英数字と - 以外は _ にする
+fn statsd_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// タグの値ではカンマ・等号・空白をエスケープする
+fn influx_tag(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn unix_nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Sink;
+    use crate::config::{MetricsConfig, MetricsFormat};
+    use crate::sampler::{Collector, Snapshot};
+    use crate::source::MockSource;
+
+    fn snapshot() -> Snapshot {
+        let mut collector = Collector::with_source(MockSource::default());
+        collector.collect_all();
+        collector.collect_all();
+        let mut snapshot = collector.snapshot().clone();
+        snapshot.cpu_primed = true;
+        snapshot
+    }
+
+    #[test]
+    fn sink_writes_statsd_and_influx_lines() {
+        let snapshot = snapshot();
+        let send = |format, name: &str| {
+            let path = std::env::temp_dir().join(format!("rust-top-{name}-{}", std::process::id()));
+            let config = MetricsConfig {
+                target: Some(path.display().to_string()),
+                format,
+                processes: 2,
+                ..MetricsConfig::default()
+            };
+            let mut sink = Sink::open(&config).expect("open").expect("sink");
+            sink.send(&snapshot).expect("send");
+            // 間隔が空くまでは書かない
+            sink.send(&snapshot).expect("send");
+            // 捨てると書き終えるまで待つ
+            drop(sink);
+            let text = std::fs::read_to_string(&path).expect("read metrics");
+            std::fs::remove_file(&path).ok();
+            text
+        };
+        let statsd = send(MetricsFormat::Statsd, "statsd");
+        assert_eq!(statsd.matches("rust_top.cpu.usage:").count(), 1, "{statsd}");
+        assert!(statsd.lines().all(|line| line.ends_with("|g")), "{statsd}");
+        assert_eq!(statsd.matches(".process.").count(), 4, "{statsd}");
+        let influx = send(MetricsFormat::Influx, "influx");
+        let lines: Vec<&str> = influx.lines().collect();
+        assert_eq!(lines.len(), 3, "{influx}");
+        assert!(
+            lines[0].starts_with("rust_top,host=mock cpu_usage="),
+            "{influx}"
+        );
+        assert!(
+            lines[1].starts_with("rust_top_process,host=mock,name="),
+            "{influx}"
+        );
+    }
+
+    #[test]
+    fn a_down_collector_does_not_block_the_caller() {
+        // 閉じたポートにはつなげない
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("address");
+        drop(listener);
+        let config = MetricsConfig {
+            target: Some(format!("tcp://{addr}")),
+            interval: Duration::ZERO,
+            ..MetricsConfig::default()
+        };
+        let snapshot = snapshot();
+        let mut sink = Sink::open(&config).expect("open").expect("sink");
+        let started = Instant::now();
+        sink.send(&snapshot).expect("queued");
+        assert!(started.elapsed() < Duration::from_millis(200));
+
+        // つなげなかったことは後の send で知らせる
+        let error = (0..100).find_map(|_| {
+            std::thread::sleep(Duration::from_millis(20));
+            sink.send(&snapshot).err()
+        });
+        assert!(error.is_some());
+    }
+}
@@ -0,0 +1,368 @@
+// CPU・メモリ・温度を MQTT のブローカーに publish する（mqtt フィーチャー）
+//
+// Home Assistant などで読めるよう、値はトピックを分けて数字だけを送る（"{topic}/cpu" に "12.5"）。
+// QoS 0 の PUBLISH しか使わないので、MQTT 3.1.1 の CONNECT / PUBLISH / DISCONNECT を自前で書く。
+// 接続が切れたときに "{topic}/status" が offline になるよう、接続時に will を登録しておく。
+// 名前解決・接続・CONNACK の待ちは、ブローカーが落ちていると数秒かかるので、
+// 描画のスレッドでは値を作って渡すだけにし、送るのは別スレッドでする。
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::config::MqttConfig;
+use crate::sampler::Snapshot;
+
+/// つなぐとき・CONNACK を待つとき・書くときに待つ長さ
+const TIMEOUT: Duration = Duration::from_secs(1);
+/// ポートを省いたときの MQTT の既定のポート
+const DEFAULT_PORT: u16 = 1883;
+/// つなげなかったときに次に試すまでの間隔。失敗が続くと倍にしていく
+const RETRY_FIRST: Duration = Duration::from_secs(1);
+const RETRY_MAX: Duration = Duration::from_secs(60);
+
+type Messages = Vec<(String, String)>;
+
+/// 設定した間隔ごとに値を publish する
+pub struct Publisher {
+    interval: Duration,
+    topic: String,
+    last: Option<Instant>,
+    /// 送るスレッドへ。送っている途中なら今回の値は捨てる
+    messages: Option<SyncSender<Messages>>,
+    /// 送るスレッドで起きた失敗
+    errors: Receiver<io::Error>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Publisher {
+    /// [mqtt] broker が無ければ None
+    pub fn open(config: &MqttConfig) -> Option<Self> {
+        config.broker.as_ref()?;
+        let (messages, queue) = mpsc::sync_channel::<Messages>(1);
+        let (errors_tx, errors) = mpsc::channel();
+        let mut connection = Connection {
+            config: config.clone(),
+            stream: None,
+            retry: None,
+            failing: false,
+        };
+        // Publisher を捨てると送り手が無くなって終わる
+        let worker = thread::spawn(move || {
+            while let Ok(messages) = queue.recv() {
+                if let Some(e) = connection.send(&messages)
+                    && errors_tx.send(e).is_err()
+                {
+                    return;
+                }
+            }
+        });
+        Some(Self {
+            interval: config.interval,
+            topic: config.topic.clone(),
+            last: None,
+            messages: Some(messages),
+            errors,
+            worker: Some(worker),
+        })
+    }
+
+    /// 前回から間隔が空いていれば送るよう頼む。前に頼んだ分の失敗があれば返す
+    pub fn publish(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        if let Ok(e) = self.errors.try_recv() {
+            return Err(e);
+        }
+        // 使用率が確定するまでの値は送らない
+        if !snapshot.cpu_primed || self.last.is_some_and(|last| last.elapsed() < self.interval) {
+            return Ok(());
+        }
+        self.last = Some(Instant::now());
+        if let Some(sender) = &self.messages
+            && let Err(TrySendError::Disconnected(_)) =
+                sender.try_send(messages(snapshot, &self.topic))
+        {
+            return Err(io::Error::other("the MQTT thread stopped"));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Publisher {
+    // offline を送って切るまで待つ（待つのは書き込みの時間切れまで）
+    fn drop(&mut self) {
+        self.messages = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// 送るスレッドが持つブローカーへの接続
+struct Connection {
+    config: MqttConfig,
+    /// 切れたら次に送るときにつなぎ直す
+    stream: Option<TcpStream>,
+    /// つなげなかったときの、次に試す時刻と次の間隔
+    retry: Option<(Instant, Duration)>,
+    /// 失敗が続いている間は最初の 1 回だけ知らせる
+    failing: bool,
+}
+
+impl Connection {
+    /// 知らせる失敗があれば返す
+    fn send(&mut self, messages: &[(String, String)]) -> Option<io::Error> {
+        match self.write(messages) {
+            Ok(()) => {
+                self.failing = false;
+                None
+            }
+            Err(_) if self.failing => None,
+            Err(e) => {
+                self.failing = true;
+                Some(e)
+            }
+        }
+    }
+
+    fn write(&mut self, messages: &[(String, String)]) -> io::Result<()> {
+        if self.stream.is_none() {
+            if let Some((at, _)) = self.retry
+                && Instant::now() < at
+            {
+                return Err(io::Error::other("waiting to reconnect"));
+            }
+            match self.connect() {
+                Ok(stream) => {
+                    self.stream = Some(stream);
+                    self.retry = None;
+                }
+                Err(e) => {
+                    let wait = self.retry.map_or(RETRY_FIRST, |(_, wait)| wait);
+                    self.retry = Some((Instant::now() + wait, (wait * 2).min(RETRY_MAX)));
+                    return Err(e);
+                }
+            }
+        }
+        let stream = self.stream.as_mut().expect("just connected");
+        let mut packets = Vec::new();
+        for (topic, payload) in messages {
+            packets.extend(publish(topic, payload.as_bytes(), self.config.retain));
+        }
+        let result = stream.write_all(&packets);
+        if result.is_err() {
+            self.stream = None;
+        }
+        result
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let broker = self.config.broker.as_deref().unwrap_or_default();
+        let addr = if broker.contains(':') {
+            broker.to_socket_addrs()?.next()
+        } else {
+            (broker, DEFAULT_PORT).to_socket_addrs()?.next()
+        }
+        .ok_or_else(|| io::Error::other(format!("{broker} has no address")))?;
+        let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        stream.write_all(&connect(&self.config))?;
+        // CONNACK: 0x20 0x02 フラグ 戻り値
+        let mut ack = [0; 4];
+        stream.read_exact(&mut ack)?;
+        if ack[0] != 0x20 {
+            return Err(io::Error::other("the broker did not answer with CONNACK"));
+        }
+        if ack[3] != 0 {
+            return Err(io::Error::other(match ack[3] {
+                4 => "the broker rejected the user name or password".to_string(),
+                5 => "the broker did not authorize the client".to_string(),
+                code => format!("the broker refused the connection (code {code})"),
+            }));
+        }
+        stream.write_all(&publish(
+            &format!("{}/status", self.config.topic),
+            b"online",
+            true,
+        ))?;
+        Ok(stream)
+    }
+}
+
+impl Drop for Connection {
+    // 黙って切ると will の offline が送られるので、終わるときは自分で offline にして切る
+    fn drop(&mut self) {
+        if let Some(stream) = &mut self.stream {
+            let status = format!("{}/status", self.config.topic);
+            let _ = stream.write_all(&publish(&status, b"offline", true));
+            let _ = stream.write_all(&[0xE0, 0x00]);
+        }
+    }
+}
+
+/// 送るトピックと値。温度は読めたときだけ
+fn messages(snapshot: &Snapshot, topic: &str) -> Messages {
+    let memory = &snapshot.memory;
+    let percent = |used: u64, total: u64| {
+        if total == 0 {
+            0.0
+        } else {
+            used as f64 * 100.0 / total as f64
+        }
+    };
+    let mut messages = vec![
+        ("cpu", format!("{:.1}", snapshot.cpu.normalized())),
+        (
+            "memory",
+            format!("{:.1}", percent(memory.used, memory.total)),
+        ),
+        ("memory_used", memory.used.to_string()),
+        (
+            "swap",
+            format!("{:.1}", percent(memory.used_swap, memory.total_swap)),
+        ),
+        ("load", format!("{:.2}", snapshot.info.load_average[0])),
+    ];
+    if let Some(temperature) = snapshot.soc.as_ref().and_then(|soc| soc.temperature) {
+        messages.push(("temperature", format!("{temperature:.1}")));
+    }
+    messages
+        .into_iter()
+        .map(|(name, value)| (format!("{topic}/{name}"), value))
+        .collect()
+}
+
+// CONNECT。クリーンセッションで、keep alive は送る間隔の 2 倍にする
+fn connect(config: &MqttConfig) -> Vec<u8> {
+    let keep_alive = (config.interval.as_secs() * 2).clamp(30, u16::MAX as u64) as u16;
+    // 0x02: クリーンセッション、0x04: will、0x20: will を retain
+    let mut flags = 0x02 | 0x04 | 0x20;
+    let mut body = Vec::new();
+    string(&mut body, b"MQTT");
+    body.push(4);
+    let flags_at = body.len();
+    body.push(0);
+    body.extend(keep_alive.to_be_bytes());
+    string(&mut body, config.client_id.as_bytes());
+    string(&mut body, format!("{}/status", config.topic).as_bytes());
+    string(&mut body, b"offline");
+    if let Some(username) = &config.username {
+        flags |= 0x80;
+        string(&mut body, username.as_bytes());
+    }
+    if let Some(password) = &config.password {
+        flags |= 0x40;
+        string(&mut body, password.as_bytes());
+    }
+    body[flags_at] = flags;
+    packet(0x10, &body)
+}
+
+/// QoS 0 の PUBLISH
+fn publish(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    string(&mut body, topic.as_bytes());
+    body.extend(payload);
+    packet(0x30 | u8::from(retain), &body)
+}
+
+// 固定ヘッダー（種類と、7 ビットずつの可変長の残りの長さ）を付ける
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![kind];
+    let mut length = body.len();
+    loop {
+        let byte = (length % 128) as u8;
+        length /= 128;
+        out.push(if length > 0 { byte | 0x80 } else { byte });
+        if length == 0 {
+            break;
+        }
+    }
+    out.extend(body);
+    out
+}
+
+// 2 バイトの長さが前に付いた文字列
+fn string(out: &mut Vec<u8>, value: &[u8]) {
+    out.extend((value.len() as u16).to_be_bytes());
+    out.extend(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::time::{Duration, Instant};
+
+    use super::Publisher;
+    use crate::config::MqttConfig;
+    use crate::sampler::{Collector, Snapshot};
+    use crate::source::MockSource;
+
+    #[test]
+    fn publisher_sends_values_to_the_broker() {
+        // CONNECT に CONNACK を返し、切られるまでに届いたものを集めるだけのブローカー
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let broker = listener.local_addr().expect("address").to_string();
+        let received = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let mut connect = [0; 256];
+            let n = stream.read(&mut connect).expect("read connect");
+            stream
+                .write_all(&[0x20, 0x02, 0x00, 0x00])
+                .expect("connack");
+            let mut rest = Vec::new();
+            stream.read_to_end(&mut rest).expect("read publishes");
+            (connect[..n].to_vec(), rest)
+        });
+        let config = MqttConfig {
+            broker: Some(broker),
+            topic: "home/pi".to_string(),
+            ..MqttConfig::default()
+        };
+        let mut collector = Collector::with_source(MockSource::default());
+        collector.collect_all();
+        collector.collect_all();
+        let snapshot = Snapshot {
+            cpu_primed: true,
+            ..collector.snapshot().clone()
+        };
+        let mut publisher = Publisher::open(&config).expect("publisher");
+        publisher.publish(&snapshot).expect("publish");
+        drop(publisher);
+        let (connect, rest) = received.join().expect("broker");
+        let text = String::from_utf8_lossy(&rest);
+        assert_eq!(connect[0], 0x10);
+        assert!(String::from_utf8_lossy(&connect).contains("home/pi/status"));
+        assert!(text.contains("home/pi/cpu"), "{text}");
+        assert!(text.contains("home/pi/memory_used"), "{text}");
+        // 終わるときは offline にしてから DISCONNECT
+        assert!(rest.ends_with(b"home/pi/statusoffline\xe0\x00"), "{text}");
+    }
+
+    #[test]
+    fn an_unresponsive_broker_does_not_block_the_caller() {
+        // つなげるが CONNACK を返さないブローカー
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let config = MqttConfig {
+            broker: Some(listener.local_addr().expect("address").to_string()),
+            interval: Duration::ZERO,
+            ..MqttConfig::default()
+        };
+        let snapshot = Snapshot {
+            cpu_primed: true,
+            ..Snapshot::default()
+        };
+        let mut publisher = Publisher::open(&config).expect("publisher");
+        let started = Instant::now();
+        publisher.publish(&snapshot).expect("queued");
+        assert!(started.elapsed() < Duration::from_millis(200));
+
+        // CONNACK の時間切れは後の publish で知らせる
+        let error = (0..100).find_map(|_| {
+            std::thread::sleep(Duration::from_millis(50));
+            publisher.publish(&snapshot).err()
+        });
+        assert!(error.is_some());
+    }
+}
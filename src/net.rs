@@ -0,0 +1,348 @@
+// TCP / UDP のソケット一覧（Linux の /proc/net/{tcp,tcp6,udp,udp6} を読む）
+//
+// ソケットの持ち主は /proc/<pid>/fd のリンク先（socket:[inode]）から探す。
+// 他のユーザーのプロセスは権限が無いと分からない。
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Instant;
+
+use sysinfo::Pid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    pub fn label(self) -> &'static str {
+        match self {
+            Protocol::Tcp => "TCP",
+            Protocol::Udp => "UDP",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Connection {
+    pub protocol: Protocol,
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    pub state: &'static str,
+    /// 持ち主が分からなければ None
+    pub pid: Option<Pid>,
+}
+
+impl Connection {
+    /// 待ち受けているか（TCP の LISTEN と、相手の決まっていない UDP）
+    pub fn is_listening(&self) -> bool {
+        match self.protocol {
+            Protocol::Tcp => self.state == "LISTEN",
+            Protocol::Udp => self.state == "UNCONN" && self.remote.ip().is_unspecified(),
+        }
+    }
+}
+
+/// 待ち受けているポート（ss -lntup の 1 行）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListeningPort {
+    pub protocol: Protocol,
+    pub port: u16,
+    /// すべてのアドレスで待ち受けていれば None
+    pub address: Option<IpAddr>,
+    pub pid: Option<Pid>,
+}
+
+/// ポート順（同じポートなら TCP が先）。IPv4 と IPv6 の両方で開いているものは 1 つにまとめる
+pub fn listening(connections: &[Connection]) -> Vec<ListeningPort> {
+    let mut ports: Vec<ListeningPort> = connections
+        .iter()
+        .filter(|c| c.is_listening())
+        .map(|c| ListeningPort {
+            protocol: c.protocol,
+            port: c.local.port(),
+            address: Some(c.local.ip()).filter(|ip| !ip.is_unspecified()),
+            pid: c.pid,
+        })
+        .collect();
+    ports.sort_by_key(|p| (p.port, p.protocol as u8, p.address, p.pid));
+    ports.dedup();
+    ports
+}
+
+/// 数える TCP の状態（ss の表記）。CLOSE-WAIT の増加は接続の閉じ忘れ、SYN-RECV の増加は SYN flood の兆し
+pub const TCP_STATES: [&str; 4] = ["ESTAB", "TIME-WAIT", "CLOSE-WAIT", "SYN-RECV"];
+
+#[derive(Debug, Clone, Copy)]
+pub struct TcpStates {
+    /// TCP_STATES の順
+    pub counts: [usize; 4],
+    /// 1 秒あたりの増減。初回は None
+    pub rates: Option<[f64; 4]>,
+}
+
+/// 前回の数を覚えておいて増減を出す
+#[derive(Debug, Default)]
+pub struct TcpStateCounter {
+    last: Option<([usize; 4], Instant)>,
+}
+
+impl TcpStateCounter {
+    /// ソケットが 1 つも読めなければ（Linux 以外など）None
+    pub fn count(&mut self, connections: &[Connection], now: Instant) -> Option<TcpStates> {
+        if connections.is_empty() {
+            return None;
+        }
+        let mut counts = [0; 4];
+        for c in connections.iter().filter(|c| c.protocol == Protocol::Tcp) {
+            if let Some(i) = TCP_STATES.iter().position(|state| *state == c.state) {
+                counts[i] += 1;
+            }
+        }
+        let rates = self.last.replace((counts, now)).and_then(|(last, at)| {
+            let secs = now.duration_since(at).as_secs_f64();
+            (secs > 0.0)
+                .then(|| std::array::from_fn(|i| (counts[i] as f64 - last[i] as f64) / secs))
+        });
+        Some(TcpStates { counts, rates })
+    }
+}
+
+/// プロセスごとの TCP の送受信量（RX / TX 列、b）
+///
+/// /proc/net には量が無いので、ss -tinHe でソケットごとの bytes_received / bytes_sent を読み、
+/// inode から持ち主を引いて足す。UDP の量は分からない。
+#[derive(Debug, Default)]
+pub struct TrafficCounter {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    last: Option<(Sockets, Instant)>,
+}
+
+/// inode ごとの (受信, 送信) の累計
+pub type Sockets = HashMap<u64, (u64, u64)>;
+
+impl TrafficCounter {
+    /// PID ごとの (受信, 送信) バイト/秒。初回と ss が使えないときは None
+    #[cfg(target_os = "linux")]
+    pub fn collect(&mut self, now: Instant) -> Option<HashMap<Pid, (u64, u64)>> {
+        let output = std::process::Command::new("ss")
+            .args(["-tinHe"])
+            .stdin(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let sockets = parse_ss(&String::from_utf8_lossy(&output.stdout));
+        self.update(sockets, &socket_owners(), now)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn collect(&mut self, _now: Instant) -> Option<HashMap<Pid, (u64, u64)>> {
+        None
+    }
+
+    /// 前回からの増分を持ち主ごとに足す。前回の後に開いたソケットは全量を増分とみなす
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn update(
+        &mut self,
+        sockets: Sockets,
+        owners: &HashMap<u64, Pid>,
+        now: Instant,
+    ) -> Option<HashMap<Pid, (u64, u64)>> {
+        let (last, at) = self.last.replace((sockets, now))?;
+        let secs = now.duration_since(at).as_secs_f64();
+        if secs <= 0.0 {
+            return None;
+        }
+        let (sockets, _) = self.last.as_ref().expect("just replaced");
+        let mut bytes: HashMap<Pid, (u64, u64)> = HashMap::new();
+        for (inode, &(received, sent)) in sockets {
+            let Some(&pid) = owners.get(inode) else {
+                continue;
+            };
+            let (last_received, last_sent) = last.get(inode).copied().unwrap_or_default();
+            let entry = bytes.entry(pid).or_default();
+            entry.0 += received.saturating_sub(last_received);
+            entry.1 += sent.saturating_sub(last_sent);
+        }
+        let rate = |bytes: u64| (bytes as f64 / secs).round() as u64;
+        Some(
+            bytes
+                .into_iter()
+                .map(|(pid, (received, sent))| (pid, (rate(received), rate(sent))))
+                .collect(),
+        )
+    }
+}
+
+/// ss -tinHe の出力から、inode ごとの (受信, 送信) の累計を読む
+///
+/// 1 行目にアドレスと ino:、字下げした 2 行目に bytes_sent: / bytes_received: が出る。
+/// 古いカーネルには bytes_sent が無いので、そのときは bytes_acked を使う
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn parse_ss(text: &str) -> Sockets {
+    let mut sockets = HashMap::new();
+    let mut inode = None;
+    for line in text.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            inode = line
+                .split_whitespace()
+                .find_map(|field| field.strip_prefix("ino:")?.parse::<u64>().ok())
+                // 閉じかけのソケットは inode が 0 になる
+                .filter(|&inode| inode != 0);
+            continue;
+        }
+        let Some(inode) = inode.take() else {
+            continue;
+        };
+        let field = |name: &str| {
+            line.split_whitespace()
+                .find_map(|field| field.strip_prefix(name)?.parse::<u64>().ok())
+        };
+        let received = field("bytes_received:").unwrap_or(0);
+        let sent = field("bytes_sent:")
+            .or_else(|| field("bytes_acked:"))
+            .unwrap_or(0);
+        sockets.insert(inode, (received, sent));
+    }
+    sockets
+}
+
+/// 待ち受けを先に、ローカルのポート順に並べて返す
+#[cfg(target_os = "linux")]
+pub fn collect() -> Vec<Connection> {
+    let owners: HashMap<u64, Pid> = socket_owners();
+    let mut connections: Vec<Connection> = sockets()
+        .into_iter()
+        .map(|(mut connection, inode)| {
+            connection.pid = owners.get(&inode).copied();
+            connection
+        })
+        .collect();
+    connections.sort_by_key(|c| (c.state != "LISTEN", c.local.port(), c.protocol as u8));
+    connections
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn collect() -> Vec<Connection> {
+    Vec::new()
+}
+
+/// すべてのソケットと inode の組。持ち主（pid）はまだ埋めない
+#[cfg(target_os = "linux")]
+pub fn sockets() -> Vec<(Connection, u64)> {
+    let mut sockets = Vec::new();
+    for (file, protocol) in [
+        ("tcp", Protocol::Tcp),
+        ("tcp6", Protocol::Tcp),
+        ("udp", Protocol::Udp),
+        ("udp6", Protocol::Udp),
+    ] {
+        let Ok(text) = std::fs::read_to_string(format!("/proc/net/{file}")) else {
+            continue;
+        };
+        // 1 行目は見出し
+        sockets.extend(
+            text.lines()
+                .skip(1)
+                .filter_map(|line| parse_line(line, protocol)),
+        );
+    }
+    sockets
+}
+
+// "0: 0100007F:0035 00000000:0000 0A ... inode" の形の行を読む
+#[cfg(target_os = "linux")]
+fn parse_line(line: &str, protocol: Protocol) -> Option<(Connection, u64)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let local = parse_address(fields.get(1)?)?;
+    let remote = parse_address(fields.get(2)?)?;
+    let state = u8::from_str_radix(fields.get(3)?, 16).ok()?;
+    let inode = fields.get(9)?.parse().ok()?;
+    let connection = Connection {
+        protocol,
+        local,
+        remote,
+        state: state_label(protocol, state),
+        pid: None,
+    };
+    Some((connection, inode))
+}
+
+// アドレスは 32 ビットごとにホストのバイト順（リトルエンディアン）で書かれている
+#[cfg(target_os = "linux")]
+fn parse_address(text: &str) -> Option<SocketAddr> {
+    let (ip, port) = text.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut bytes = Vec::with_capacity(16);
+    for i in (0..ip.len()).step_by(8) {
+        let word = u32::from_str_radix(ip.get(i..i + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_le_bytes());
+    }
+    let ip = match bytes.len() {
+        4 => IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?),
+        16 => IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?),
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+// ss と同じ表記にする
+#[cfg(target_os = "linux")]
+fn state_label(protocol: Protocol, state: u8) -> &'static str {
+    match (protocol, state) {
+        (_, 0x01) => "ESTAB",
+        (Protocol::Udp, 0x07) => "UNCONN",
+        (_, 0x02) => "SYN-SENT",
+        (_, 0x03) => "SYN-RECV",
+        (_, 0x04) => "FIN-WAIT-1",
+        (_, 0x05) => "FIN-WAIT-2",
+        (_, 0x06) => "TIME-WAIT",
+        (_, 0x07) => "CLOSE",
+        (_, 0x08) => "CLOSE-WAIT",
+        (_, 0x09) => "LAST-ACK",
+        (_, 0x0A) => "LISTEN",
+        (_, 0x0B) => "CLOSING",
+        _ => "UNKNOWN",
+    }
+}
+
+// ソケットの inode から持ち主の PID への対応
+#[cfg(target_os = "linux")]
+fn socket_owners() -> HashMap<u64, Pid> {
+    use std::fs;
+    use std::str::FromStr;
+
+    let mut owners = HashMap::new();
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return owners;
+    };
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| Pid::from_str(s).ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            let inode = target
+                .to_str()
+                .and_then(|t| t.strip_prefix("socket:["))
+                .and_then(|t| t.strip_suffix(']'))
+                .and_then(|t| t.parse().ok());
+            if let Some(inode) = inode {
+                owners.entry(inode).or_insert(pid);
+            }
+        }
+    }
+    owners
+}
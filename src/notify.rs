@@ -0,0 +1,61 @@
+// デスクトップ通知（notify 機能）
+//
+// Linux などでは notify-send（D-Bus の通知デーモン）、macOS では osascript、
+// Windows では PowerShell からトーストを出す。スクリプトを使うものにはタイトルと本文を
+// 環境変数で渡し、引用符などを埋め込まない。
+use std::process::Command;
+
+/// Windows でトーストを出すときの AppUserModelID（PowerShell のもの。登録しなくても表示される）
+#[cfg(windows)]
+const WINDOWS_APP_ID: &str =
+    r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe";
+
+/// デスクトップ通知を出す（通知デーモンが無いなどで失敗しても何もしない）
+pub fn desktop(title: &str, body: &str) {
+    let mut command = command(title, body);
+    // 終了を待たないと zombie が残るので、別スレッドで回収する
+    if let Ok(mut child) = command.spawn() {
+        std::thread::spawn(move || child.wait());
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn command(title: &str, body: &str) -> Command {
+    let mut command = Command::new("osascript");
+    command
+        .arg("-e")
+        .arg(
+            "display notification (system attribute \"RUST_TOP_BODY\") \
+             with title (system attribute \"RUST_TOP_TITLE\")",
+        )
+        .env("RUST_TOP_TITLE", title)
+        .env("RUST_TOP_BODY", body);
+    command
+}
+
+#[cfg(windows)]
+fn command(title: &str, body: &str) -> Command {
+    let script = format!(
+        "$m = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]; \
+         $t = $m::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+         $x = $t.GetElementsByTagName('text'); \
+         [void]$x.Item(0).AppendChild($t.CreateTextNode($env:RUST_TOP_TITLE)); \
+         [void]$x.Item(1).AppendChild($t.CreateTextNode($env:RUST_TOP_BODY)); \
+         $m::CreateToastNotifier('{WINDOWS_APP_ID}').Show([Windows.UI.Notifications.ToastNotification]::new($t))"
+    );
+    let mut command = Command::new("powershell");
+    command
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .env("RUST_TOP_TITLE", title)
+        .env("RUST_TOP_BODY", body)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    command
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn command(title: &str, body: &str) -> Command {
+    let mut command = Command::new("notify-send");
+    command.args(["-a", "rust-top", title, body]);
+    command
+}
@@ -0,0 +1,138 @@
+// NUMA ノードごとのメモリと CPU（Linux の /sys/devices/system/node）
+//
+// プロセスがどのノードのメモリを使っているかは /proc/<pid>/numa_maps から数える。
+// すべてのマッピングをたどるので重い。Node 列（n）を出しているときだけ読む。
+use std::sync::Arc;
+
+use sysinfo::Pid;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NumaNode {
+    pub id: usize,
+    /// そのノードの CPU（"0-15,32-47" のような cpulist の書き方のまま）
+    pub cpus: String,
+    /// バイト
+    pub total: u64,
+    pub free: u64,
+}
+
+impl NumaNode {
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NumaSnapshot {
+    pub nodes: Vec<NumaNode>,
+}
+
+/// ノードが 1 つしか無い（または Linux 以外）なら None
+#[cfg(target_os = "linux")]
+pub fn collect() -> Option<NumaSnapshot> {
+    let entries = std::fs::read_dir("/sys/devices/system/node").ok()?;
+    let mut nodes: Vec<NumaNode> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let id = name.to_str()?.strip_prefix("node")?.parse().ok()?;
+            let path = entry.path();
+            let meminfo = std::fs::read_to_string(path.join("meminfo")).ok()?;
+            let (total, free) = parse_meminfo(&meminfo)?;
+            let cpus = std::fs::read_to_string(path.join("cpulist"))
+                .map(|text| text.trim().to_string())
+                .unwrap_or_default();
+            Some(NumaNode {
+                id,
+                cpus,
+                total,
+                free,
+            })
+        })
+        .collect();
+    if nodes.len() < 2 {
+        return None;
+    }
+    nodes.sort_by_key(|node| node.id);
+    Some(NumaSnapshot { nodes })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn collect() -> Option<NumaSnapshot> {
+    None
+}
+
+// "Node 0 MemTotal:  6147400 kB" の形の行から合計と空きを読む
+#[cfg(target_os = "linux")]
+fn parse_meminfo(text: &str) -> Option<(u64, u64)> {
+    let mut total = None;
+    let mut free = None;
+    for line in text.lines() {
+        let mut fields = line.split_whitespace().skip(2);
+        let slot = match fields.next() {
+            Some("MemTotal:") => &mut total,
+            Some("MemFree:") => &mut free,
+            _ => continue,
+        };
+        *slot = fields.next().and_then(|kib| kib.parse::<u64>().ok());
+    }
+    Some((total? * 1024, free? * 1024))
+}
+
+/// ノードごとのページ数（ノード番号の位置）。読めなければ None
+#[cfg(target_os = "linux")]
+pub fn read_process(pid: Pid) -> Option<Arc<[u64]>> {
+    let text = std::fs::read_to_string(format!("/proc/{pid}/numa_maps")).ok()?;
+    let mut pages: Vec<u64> = Vec::new();
+    // マッピングごとに "N0=12 N1=3" のような項目が並ぶ
+    for field in text.split_whitespace() {
+        let Some((node, count)) = field
+            .strip_prefix('N')
+            .and_then(|rest| rest.split_once('='))
+        else {
+            continue;
+        };
+        let (Ok(node), Ok(count)) = (node.parse::<usize>(), count.parse::<u64>()) else {
+            continue;
+        };
+        if pages.len() <= node {
+            pages.resize(node + 1, 0);
+        }
+        pages[node] += count;
+    }
+    Some(pages.into())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_process(_pid: Pid) -> Option<Arc<[u64]>> {
+    None
+}
+
+/// いちばん多くのページを持つノード
+pub fn main_node(pages: &[u64]) -> Option<usize> {
+    pages
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .max_by_key(|(node, count)| (**count, std::cmp::Reverse(*node)))
+        .map(|(node, _)| node)
+}
+
+/// ページを持つノードを多い順に "1,0" のように並べる。複数あればノードをまたいでいる
+pub fn label(pages: &[u64]) -> String {
+    let mut nodes: Vec<(usize, u64)> = pages
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(_, count)| *count > 0)
+        .collect();
+    nodes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    if nodes.is_empty() {
+        return "-".to_string();
+    }
+    nodes
+        .iter()
+        .map(|(node, _)| node.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
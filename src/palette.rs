@@ -0,0 +1,245 @@
+// コマンドモード（:）の解析と補完
+//
+// 「:sort mem」「:kill 1234」のように、キーを覚えていなくても同じ操作ができる。
+// 「:category browser」はブラウザやシェルなどの種類で絞り込む。
+// 「:caffeinate」は動かしている間システムをスリープさせない。
+// 「:port 8080」「:file /var/log/foo.log」はポートやファイルから持ち主のプロセスを選ぶ。
+// コマンド名と列名は、他と区別できるところまでの省略を受け付ける。
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use sysinfo::{Pid, Signal};
+
+use crate::actions;
+use crate::app::SortKey;
+use crate::clipboard::Yank;
+use crate::export::ExportFormat;
+use crate::filter::Filter;
+use crate::layout;
+use crate::theme::{self, Theme};
+
+/// コマンドの名前。補完の候補にもなる
+const NAMES: [&str; 16] = [
+    "caffeinate",
+    "category",
+    "file",
+    "filter",
+    "interval",
+    "kill",
+    "layout",
+    "port",
+    "profile",
+    "quit",
+    "revert",
+    "save",
+    "sort",
+    "theme",
+    "user",
+    "yank",
+];
+
+#[derive(Debug)]
+pub enum Action {
+    Sort(SortKey),
+    /// 式（filter.rs）で絞り込む。None なら解除
+    Filter(Option<Filter>),
+    User(Option<String>),
+    Interval(Duration),
+    Kill {
+        pid: Pid,
+        signal: Signal,
+    },
+    /// そのポートで待ち受けている（無ければそのポートを使っている）プロセスを選ぶ
+    Port(u16),
+    /// そのファイルを開いているプロセスを選ぶ
+    File(PathBuf),
+    Theme(Theme),
+    /// レイアウトの名前（設定ファイルで足したものもあるので、App で探す）
+    Layout(String),
+    /// [profiles.<name>] の名前（App で探す）
+    Profile(String),
+    Save(ExportFormat),
+    /// [categories] の種類で絞り込む（名前は App で探す）。None なら解除
+    Category(Option<String>),
+    /// スリープを止める・許す。None なら今と逆にする
+    Caffeinate(Option<bool>),
+    /// 選択したプロセスの nice 値・I/O 優先度・アフィニティを rust-top で変える前に戻す
+    Revert,
+    /// 選択したプロセスの情報をクリップボードに写す
+    Yank(Yank),
+    Quit,
+}
+
+pub fn parse(text: &str) -> Result<Action, String> {
+    let text = text.trim().trim_start_matches(':');
+    let (name, rest) = text.split_once(' ').unwrap_or((text, ""));
+    let rest = rest.trim();
+    let Some(name) = unique(name, NAMES.iter().copied()) else {
+        return Err(format!("Unknown command: {name} ({})", NAMES.join(", ")));
+    };
+    let optional = (!rest.is_empty()).then(|| rest.to_string());
+    match name {
+        "sort" => {
+            let labels = SortKey::COLUMNS.map(|key| key.label());
+            unique(rest, labels)
+                .and_then(SortKey::from_name)
+                .map(Action::Sort)
+                .ok_or_else(|| format!("Unknown column: {rest} ({})", labels.join(", ")))
+        }
+        "filter" => match optional {
+            Some(text) => Filter::parse(&text)
+                .map(|filter| Action::Filter(Some(filter)))
+                .map_err(|e| format!("Invalid filter: {e}")),
+            None => Ok(Action::Filter(None)),
+        },
+        "user" => Ok(Action::User(optional)),
+        "interval" => rest
+            .parse::<f64>()
+            .ok()
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .filter(|interval| !interval.is_zero())
+            .map(Action::Interval)
+            .ok_or_else(|| format!("Invalid interval: {rest} (seconds)")),
+        "kill" => {
+            let (pid, signal) = rest.split_once(' ').unwrap_or((rest, "TERM"));
+            let pid = Pid::from_str(pid).map_err(|_| format!("Invalid PID: {pid}"))?;
+            let signal = actions::parse_signal(signal)
+                .ok_or_else(|| format!("Unknown signal: {}", signal.trim()))?;
+            Ok(Action::Kill { pid, signal })
+        }
+        "port" => rest
+            .trim_start_matches(':')
+            .parse()
+            .map(Action::Port)
+            .map_err(|_| format!("Invalid port: {rest}")),
+        "file" if rest.is_empty() => Err("Missing file path".to_string()),
+        "file" => Ok(Action::File(PathBuf::from(rest))),
+        "theme" => {
+            let names = theme::THEMES.map(|t| t.name);
+            unique(rest, names)
+                .and_then(theme::by_name)
+                .map(Action::Theme)
+                .ok_or_else(|| format!("Unknown theme: {rest} ({})", names.join(", ")))
+        }
+        "layout" if rest.is_empty() => {
+            let names: Vec<String> = layout::builtin().into_iter().map(|l| l.name).collect();
+            Err(format!("Missing layout name ({})", names.join(", ")))
+        }
+        "layout" => Ok(Action::Layout(rest.to_string())),
+        "profile" if rest.is_empty() => Err("Missing profile name".to_string()),
+        "profile" => Ok(Action::Profile(rest.to_string())),
+        "save" => ExportFormat::from_name(rest)
+            .map(Action::Save)
+            .ok_or_else(|| format!("Unknown format: {rest} (text, html, csv or json)")),
+        "category" => Ok(Action::Category(optional)),
+        "caffeinate" => match rest {
+            "" => Ok(Action::Caffeinate(None)),
+            "on" => Ok(Action::Caffeinate(Some(true))),
+            "off" => Ok(Action::Caffeinate(Some(false))),
+            _ => Err(format!("Unknown state: {rest} (on or off)")),
+        },
+        "revert" => Ok(Action::Revert),
+        "yank" if rest.is_empty() => Ok(Action::Yank(Yank::Pid)),
+        "yank" => unique(rest, Yank::NAMES)
+            .and_then(Yank::from_name)
+            .map(Action::Yank)
+            .ok_or_else(|| format!("Unknown item: {rest} ({})", Yank::NAMES.join(", "))),
+        _ => Ok(Action::Quit),
+    }
+}
+
+// 完全に一致するもの、無ければ前方一致が 1 つだけのもの（大文字小文字は区別しない）
+pub fn unique<'a>(word: &str, choices: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    if word.is_empty() {
+        return None;
+    }
+    let matches: Vec<&str> = choices
+        .into_iter()
+        .filter(|c| starts_with_ignore_case(c, word))
+        .collect();
+    match matches.as_slice() {
+        [only] => Some(only),
+        _ => matches.into_iter().find(|c| c.eq_ignore_ascii_case(word)),
+    }
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.get(..prefix.len())
+        .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+}
+
+/// Tab での補完。候補が 1 つなら埋め、複数なら共通部分まで埋めて候補を返す
+pub fn complete(text: &str) -> (String, Vec<String>) {
+    let (head, word) = match text.rfind(' ') {
+        Some(i) => text.split_at(i + 1),
+        None => ("", text),
+    };
+    let words: Vec<&str> = head.split_whitespace().collect();
+    let choices: Vec<String> = match words.as_slice() {
+        [] => NAMES.map(str::to_string).to_vec(),
+        [name] => match unique(name, NAMES.iter().copied()) {
+            Some("sort") => SortKey::COLUMNS
+                .map(|key| key.label().to_ascii_lowercase())
+                .to_vec(),
+            Some("theme") => theme::THEMES.map(|t| t.name.to_string()).to_vec(),
+            Some("layout") => layout::builtin().into_iter().map(|l| l.name).collect(),
+            Some("save") => ["text", "html", "csv", "json"].map(str::to_string).to_vec(),
+            Some("caffeinate") => ["on", "off"].map(str::to_string).to_vec(),
+            Some("yank") => Yank::NAMES.map(str::to_string).to_vec(),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+    let matches: Vec<String> = choices
+        .into_iter()
+        .filter(|c| starts_with_ignore_case(c, word))
+        .collect();
+    match matches.as_slice() {
+        [] => (text.to_string(), matches),
+        [only] => (format!("{head}{only} "), Vec::new()),
+        _ => {
+            let common = matches[1..].iter().fold(matches[0].as_str(), |common, c| {
+                let len = common
+                    .chars()
+                    .zip(c.chars())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(a, _)| a.len_utf8())
+                    .sum();
+                &common[..len]
+            });
+            let word = if common.len() > word.len() {
+                common
+            } else {
+                word
+            };
+            (format!("{head}{word}"), matches)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Action, parse};
+
+    #[test]
+    fn interval_out_of_range_is_rejected_instead_of_panicking() {
+        assert!(matches!(
+            parse(":interval 2.5"),
+            Ok(Action::Interval(interval)) if interval == Duration::from_millis(2500)
+        ));
+        for text in [
+            ":interval 1e20",
+            ":interval 0",
+            ":interval -1",
+            ":interval nan",
+        ] {
+            assert!(
+                parse(text).is_err_and(|e| e.starts_with("Invalid interval")),
+                "{text}"
+            );
+        }
+    }
+}
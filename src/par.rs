@@ -0,0 +1,68 @@
+// 要素数が多いときだけスレッドを使って並べ替える
+use std::cmp::Ordering;
+use std::thread;
+
+/// これより少なければ 1 スレッドで並べ替える
+const PARALLEL_THRESHOLD: usize = 10_000;
+
+/// 使うスレッドの上限
+const MAX_THREADS: usize = 8;
+
+/// `slice::sort_by` と同じ結果になる安定ソート
+pub fn sort_by<T, F>(items: &mut Vec<T>, compare: F)
+where
+    T: Copy + Send + Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    let threads = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_THREADS);
+    if items.len() < PARALLEL_THRESHOLD || threads < 2 {
+        items.sort_by(compare);
+        return;
+    }
+
+    // 区間ごとに並べ替えてから、隣り合う区間を順にマージする
+    let chunk = items.len().div_ceil(threads);
+    let compare = &compare;
+    thread::scope(|scope| {
+        for part in items.chunks_mut(chunk) {
+            scope.spawn(move || part.sort_by(compare));
+        }
+    });
+    let mut runs: Vec<Vec<T>> = items.chunks(chunk).map(<[T]>::to_vec).collect();
+    while runs.len() > 1 {
+        runs = thread::scope(|scope| {
+            let handles: Vec<_> = runs
+                .chunks(2)
+                .map(|pair| scope.spawn(move || merge(pair, compare)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("merge thread panicked"))
+                .collect()
+        });
+    }
+    *items = runs.pop().unwrap_or_default();
+}
+
+// 同じ値なら左の区間を先にして安定性を保つ
+fn merge<T: Copy, F: Fn(&T, &T) -> Ordering>(pair: &[Vec<T>], compare: &F) -> Vec<T> {
+    let [left, right] = pair else {
+        return pair[0].clone();
+    };
+    let mut out = Vec::with_capacity(left.len() + right.len());
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        if compare(&right[j], &left[i]) == Ordering::Less {
+            out.push(right[j]);
+            j += 1;
+        } else {
+            out.push(left[i]);
+            i += 1;
+        }
+    }
+    out.extend_from_slice(&left[i..]);
+    out.extend_from_slice(&right[j..]);
+    out
+}
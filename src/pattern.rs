@@ -0,0 +1,364 @@
+// プロセスの絞り込みに使う小さな正規表現サブセット
+//
+// 対応する構文:
+//   c  .  [abc] [a-z] [^...]  \d \w \s \. など   1 文字
+//   *  +  ?                                       直前の 1 文字の繰り返し
+//   ^  $                                          先頭・末尾
+//   a|b                                           いずれか（最上位のみ）
+//
+// 戻りながら試すと .*a.*b.*z のようなパターンで時間が爆発するので、すべての位置を
+// 同時に進める（文字数 × パターンの長さで終わる）。一致する範囲は戻りながら試したときと同じで、
+// 開始位置がいちばん左のもの、その中では左の選択肢と長い繰り返しを優先したものになる。
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Atom {
+    Char(char),
+    Any,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+}
+
+impl Atom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Atom::Char(expected) => *expected == c,
+            Atom::Any => true,
+            Atom::Class { ranges, negated } => {
+                ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != *negated
+            }
+        }
+    }
+}
+
+// + は 1 文字と * に分けて持つ
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Repeat {
+    One,
+    ZeroOrOne,
+    ZeroOrMore,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Piece {
+    atom: Atom,
+    repeat: Repeat,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Branch {
+    anchored_start: bool,
+    anchored_end: bool,
+    pieces: Vec<Piece>,
+}
+
+/// コンパイル済みのパターン
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    source: String,
+    branches: Vec<Branch>,
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Pattern {
+    pub fn new(source: &str) -> Result<Pattern, String> {
+        let branches = split_branches(source)
+            .into_iter()
+            .map(parse_branch)
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("invalid pattern `{source}`: {e}"))?;
+        Ok(Pattern {
+            source: source.to_string(),
+            branches,
+        })
+    }
+
+    /// 文字列のどこかに一致すれば true
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+
+    /// いちばん左で一致した部分（バイト位置）。空文字列に一致したときは空の範囲
+    pub fn find(&self, text: &str) -> Option<Range<usize>> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut matcher = Matcher::new(self, &chars);
+        let mut found = None;
+        let mut current = Vec::new();
+        for pos in 0..=chars.len() {
+            // 一致が見つかったら、それより右から始まる候補は足さない
+            if found.is_none() {
+                for (index, branch) in self.branches.iter().enumerate() {
+                    if !branch.anchored_start || pos == 0 {
+                        matcher.add(&mut current, (index, 0), pos, pos);
+                    }
+                }
+            }
+            if current.is_empty() {
+                break;
+            }
+            matcher.clear();
+            let mut next = Vec::new();
+            for thread in current.drain(..) {
+                match thread.state {
+                    // これより後ろの候補は優先度が低いので捨てる
+                    None => {
+                        found = Some((thread.start, pos));
+                        break;
+                    }
+                    Some((index, i)) => {
+                        let piece = &self.branches[index].pieces[i];
+                        if chars.get(pos).is_some_and(|c| piece.atom.matches(*c)) {
+                            let target = match piece.repeat {
+                                Repeat::ZeroOrMore => (index, i),
+                                _ => (index, i + 1),
+                            };
+                            matcher.add(&mut next, target, pos + 1, thread.start);
+                        }
+                    }
+                }
+            }
+            current = next;
+        }
+        let (start, end) = found?;
+        let byte = |i: usize| chars[..i].iter().map(|c| c.len_utf8()).sum();
+        Some(byte(start)..byte(end))
+    }
+}
+
+// 進めている候補。state は（選択肢, 次に読む部品）で、None なら一致している
+struct Thread {
+    state: Option<(usize, usize)>,
+    start: usize,
+}
+
+// 同じ位置で同じ状態に着いた候補は、先に着いた（優先度の高い）ものだけを残す
+struct Matcher<'a> {
+    branches: &'a [Branch],
+    len: usize,
+    offsets: Vec<usize>,
+    seen: Vec<bool>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(pattern: &'a Pattern, chars: &[char]) -> Self {
+        let mut offsets = Vec::new();
+        let mut total = 0;
+        for branch in &pattern.branches {
+            offsets.push(total);
+            total += branch.pieces.len() + 1;
+        }
+        Self {
+            branches: &pattern.branches,
+            len: chars.len(),
+            offsets,
+            seen: vec![false; total],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.seen.fill(false);
+    }
+
+    // 文字を読まずに進める先をたどり、文字を待つ状態と一致を優先度の順に足す。
+    // 繰り返しは読む方を先にする
+    fn add(
+        &mut self,
+        threads: &mut Vec<Thread>,
+        (index, i): (usize, usize),
+        pos: usize,
+        start: usize,
+    ) {
+        let key = self.offsets[index] + i;
+        if self.seen[key] {
+            return;
+        }
+        self.seen[key] = true;
+        let branch = &self.branches[index];
+        let Some(piece) = branch.pieces.get(i) else {
+            if !branch.anchored_end || pos == self.len {
+                threads.push(Thread { state: None, start });
+            }
+            return;
+        };
+        threads.push(Thread {
+            state: Some((index, i)),
+            start,
+        });
+        if piece.repeat != Repeat::One {
+            self.add(threads, (index, i + 1), pos, start);
+        }
+    }
+}
+
+// エスケープされていない | で分ける
+fn split_branches(source: &str) -> Vec<&str> {
+    let mut branches = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    let mut in_class = false;
+    for (i, c) in source.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' => in_class = true,
+            ']' => in_class = false,
+            '|' if !in_class => {
+                branches.push(&source[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    branches.push(&source[start..]);
+    branches
+}
+
+fn parse_branch(source: &str) -> Result<Branch, String> {
+    let mut chars = source.chars().peekable();
+    let anchored_start = chars.next_if_eq(&'^').is_some();
+    let mut anchored_end = false;
+    let mut pieces: Vec<Piece> = Vec::new();
+    while let Some(c) = chars.next() {
+        if anchored_end {
+            return Err("`$` must be at the end".to_string());
+        }
+        let atom = match c {
+            '$' => {
+                anchored_end = true;
+                continue;
+            }
+            '.' => Atom::Any,
+            '\\' => escape(chars.next().ok_or("trailing `\\`")?),
+            '[' => parse_class(&mut chars)?,
+            '*' | '+' | '?' => {
+                let piece = pieces
+                    .last_mut()
+                    .filter(|p| p.repeat == Repeat::One)
+                    .ok_or_else(|| format!("nothing to repeat before `{c}`"))?;
+                match c {
+                    '*' => piece.repeat = Repeat::ZeroOrMore,
+                    '?' => piece.repeat = Repeat::ZeroOrOne,
+                    _ => {
+                        let atom = piece.atom.clone();
+                        pieces.push(Piece {
+                            atom,
+                            repeat: Repeat::ZeroOrMore,
+                        });
+                    }
+                }
+                continue;
+            }
+            '(' | ')' => return Err("groups are not supported".to_string()),
+            c => Atom::Char(c),
+        };
+        pieces.push(Piece {
+            atom,
+            repeat: Repeat::One,
+        });
+    }
+    Ok(Branch {
+        anchored_start,
+        anchored_end,
+        pieces,
+    })
+}
+
+fn escape(c: char) -> Atom {
+    let class = |ranges: &[(char, char)], negated| Atom::Class {
+        ranges: ranges.to_vec(),
+        negated,
+    };
+    const DIGIT: &[(char, char)] = &[('0', '9')];
+    const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('a', 'z'), ('_', '_')];
+    const SPACE: &[(char, char)] = &[(' ', ' '), ('\t', '\r')];
+    match c {
+        'd' => class(DIGIT, false),
+        'D' => class(DIGIT, true),
+        'w' => class(WORD, false),
+        'W' => class(WORD, true),
+        's' => class(SPACE, false),
+        'S' => class(SPACE, true),
+        c => Atom::Char(c),
+    }
+}
+
+fn parse_class(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Atom, String> {
+    let negated = chars.next_if_eq(&'^').is_some();
+    let mut ranges = Vec::new();
+    loop {
+        let lo = match chars.next().ok_or("unterminated `[`")? {
+            ']' if !ranges.is_empty() => break,
+            '\\' => chars.next().ok_or("unterminated `[`")?,
+            c => c,
+        };
+        let hi = if chars.peek() == Some(&'-') {
+            chars.next();
+            match chars.next().ok_or("unterminated `[`")? {
+                // 末尾の - は文字として扱う
+                ']' => {
+                    ranges.push((lo, lo));
+                    ranges.push(('-', '-'));
+                    break;
+                }
+                '\\' => chars.next().ok_or("unterminated `[`")?,
+                c => c,
+            }
+        } else {
+            lo
+        };
+        if hi < lo {
+            return Err(format!("invalid range `{lo}-{hi}`"));
+        }
+        ranges.push((lo, hi));
+    }
+    Ok(Atom::Class { ranges, negated })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pattern;
+
+    fn find<'a>(pattern: &str, text: &'a str) -> Option<&'a str> {
+        let range = Pattern::new(pattern).expect("pattern").find(text)?;
+        Some(&text[range])
+    }
+
+    #[test]
+    fn finds_the_leftmost_match_preferring_earlier_branches_and_longer_repeats() {
+        assert_eq!(find("b+", "abbbc"), Some("bbb"));
+        assert_eq!(find("a.*c", "xabcabc"), Some("abcabc"));
+        assert_eq!(find("ab?", "ab"), Some("ab"));
+        assert_eq!(find("^b", "ab"), None);
+        assert_eq!(find("c$", "cbc"), Some("c"));
+        assert_eq!(find("x|ab|a", "zab"), Some("ab"));
+        assert_eq!(find("a|ab", "ab"), Some("a"));
+        assert_eq!(find("\\d+ms", "took 125ms"), Some("125ms"));
+        assert_eq!(find("[^ ]+$", "sleep 10"), Some("10"));
+        assert_eq!(find("z*", "abc"), Some(""));
+        assert_eq!(find("é+", "caféé!"), Some("éé"));
+        assert!(Pattern::new("a+*").is_err());
+    }
+
+    #[test]
+    fn nested_wildcards_on_a_long_command_line_finish_quickly() {
+        let cmdline = "java -Dfoo=bar ".repeat(200);
+        let started = std::time::Instant::now();
+        for pattern in [".*a.*b.*c.*z", "D.*b.*z", "a*a*a*a*a*a*a*a*z"] {
+            let pattern = Pattern::new(pattern).expect("pattern");
+            assert!(!pattern.is_match(&cmdline), "{pattern}");
+        }
+        assert_eq!(
+            find("D.*b.*z", &format!("{cmdline}z")).map(str::len),
+            Some(cmdline.len() - 5)
+        );
+        assert!(started.elapsed().as_secs() < 5);
+    }
+}
@@ -0,0 +1,231 @@
+// 選んだプロセスとつながっているローカルのプロセス（X、Linux）
+//
+// 全プロセスの /proc/<pid>/fd をたどってパイプとソケットの inode の持ち主を集め、
+// 同じパイプを反対の向きで開いているもの、UNIX ソケットの相手（ss -xH の Peer の inode）を
+// 持っているもの、ループバックの TCP で向かい合うソケットを持っているものを相手とみなす。
+// 同じ端を受け継いだだけの親子（どちらも書く側など）はやり取りしていないので数えない。
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+
+use sysinfo::Pid;
+
+/// 相手とのつながり方
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Channel {
+    /// true なら選んだプロセスが書き、相手が読む
+    Pipe { writes: bool },
+    /// どちらかの端の名前（無名なら None）
+    Unix(Option<String>),
+    /// 選んだプロセスの側と相手の側のアドレス
+    Tcp(SocketAddr, SocketAddr),
+}
+
+impl Channel {
+    pub fn describe(&self) -> String {
+        match self {
+            Channel::Pipe { writes: true } => "pipe ->".to_string(),
+            Channel::Pipe { writes: false } => "pipe <-".to_string(),
+            Channel::Unix(Some(path)) => format!("unix {path}"),
+            Channel::Unix(None) => "unix".to_string(),
+            Channel::Tcp(local, remote) => format!("TCP {local}->{remote}"),
+        }
+    }
+}
+
+/// つながっている相手 1 つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peer {
+    pub pid: Pid,
+    pub channels: Vec<Channel>,
+}
+
+/// 全プロセスの fd とソケットの一覧から集めた手がかり
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+#[derive(Debug, Default)]
+pub struct Endpoints {
+    /// パイプの inode ごとの（持ち主、書く側か）
+    pub pipes: HashMap<u64, Vec<(Pid, bool)>>,
+    /// ソケットの inode ごとの持ち主
+    pub sockets: HashMap<u64, Vec<Pid>>,
+    /// UNIX ソケットの inode ごとの（相手の inode、名前）
+    pub unix: HashMap<u64, (u64, Option<String>)>,
+    /// TCP ソケットの inode ごとの（自分、相手）のアドレス
+    pub tcp: HashMap<u64, (SocketAddr, SocketAddr)>,
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+impl Endpoints {
+    /// pid の相手を PID の順に返す
+    pub fn peers_of(&self, pid: Pid) -> Vec<Peer> {
+        let mut peers: HashMap<Pid, Vec<Channel>> = HashMap::new();
+        let mut add = |peer: Pid, channel: Channel| {
+            let channels = peers.entry(peer).or_default();
+            if !channels.contains(&channel) {
+                channels.push(channel);
+            }
+        };
+
+        for holders in self.pipes.values() {
+            let holds = |owner: Pid, writes: bool| holders.contains(&(owner, writes));
+            for &(peer, _) in holders.iter().filter(|(owner, _)| *owner != pid) {
+                if holds(pid, true) && holds(peer, false) {
+                    add(peer, Channel::Pipe { writes: true });
+                }
+                if holds(pid, false) && holds(peer, true) {
+                    add(peer, Channel::Pipe { writes: false });
+                }
+            }
+        }
+
+        let owners = |inode: &u64| self.sockets.get(inode).into_iter().flatten().copied();
+        let own: Vec<u64> = self
+            .sockets
+            .iter()
+            .filter(|(_, owners)| owners.contains(&pid))
+            .map(|(inode, _)| *inode)
+            .collect();
+        let by_address: HashMap<(SocketAddr, SocketAddr), u64> = self
+            .tcp
+            .iter()
+            .map(|(inode, addresses)| (*addresses, *inode))
+            .collect();
+        for inode in &own {
+            if let Some((peer_inode, name)) = self.unix.get(inode) {
+                let name = name
+                    .clone()
+                    .or_else(|| self.unix.get(peer_inode).and_then(|(_, name)| name.clone()));
+                for peer in owners(peer_inode).filter(|p| *p != pid) {
+                    add(peer, Channel::Unix(name.clone()));
+                }
+            }
+            if let Some(&(local, remote)) = self.tcp.get(inode)
+                && remote.ip().is_loopback()
+                && let Some(peer_inode) = by_address.get(&(remote, local))
+            {
+                for peer in owners(peer_inode).filter(|p| *p != pid) {
+                    add(peer, Channel::Tcp(local, remote));
+                }
+            }
+        }
+
+        let mut peers: Vec<Peer> = peers
+            .into_iter()
+            .map(|(pid, channels)| Peer { pid, channels })
+            .collect();
+        peers.sort_by_key(|peer| peer.pid);
+        peers
+    }
+}
+
+/// この機械のパイプとソケットを集めて、pid の相手を返す
+#[cfg(target_os = "linux")]
+pub fn collect(pid: Pid) -> io::Result<Vec<Peer>> {
+    // 選んだプロセスの fd が読めなければ、相手も分からない
+    std::fs::read_dir(format!("/proc/{pid}/fd"))?;
+    let mut endpoints = Endpoints::default();
+    read_fds(&mut endpoints);
+    if let Ok(output) = std::process::Command::new("ss")
+        .arg("-xH")
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+    {
+        endpoints.unix = parse_ss(&String::from_utf8_lossy(&output.stdout));
+    }
+    #[cfg(feature = "net")]
+    {
+        endpoints.tcp = crate::net::sockets()
+            .into_iter()
+            .filter(|(c, _)| c.protocol == crate::net::Protocol::Tcp && c.state == "ESTAB")
+            .map(|(c, inode)| (inode, (c.local, c.remote)))
+            .collect();
+    }
+    Ok(endpoints.peers_of(pid))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn collect(_pid: Pid) -> io::Result<Vec<Peer>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "connected processes are only listed on Linux",
+    ))
+}
+
+// 他のユーザーのプロセスの fd は読めないので飛ばす
+#[cfg(target_os = "linux")]
+fn read_fds(endpoints: &mut Endpoints) {
+    use std::fs;
+    use std::str::FromStr;
+
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| Pid::from_str(s).ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            let target = target.to_string_lossy();
+            let inode = |prefix: &str| {
+                target
+                    .strip_prefix(prefix)?
+                    .strip_suffix(']')?
+                    .parse::<u64>()
+                    .ok()
+            };
+            if let Some(inode) = inode("socket:[") {
+                endpoints.sockets.entry(inode).or_default().push(pid);
+            } else if let Some(inode) = inode("pipe:[") {
+                let writes = fs::read_to_string(entry.path().join("fdinfo").join(fd.file_name()))
+                    .is_ok_and(|info| writes(&info));
+                endpoints
+                    .pipes
+                    .entry(inode)
+                    .or_default()
+                    .push((pid, writes));
+            }
+        }
+    }
+}
+
+// fdinfo の "flags:" は 8 進数で、下の 2 ビットが O_RDONLY / O_WRONLY / O_RDWR
+#[cfg(target_os = "linux")]
+fn writes(fdinfo: &str) -> bool {
+    fdinfo
+        .lines()
+        .find_map(|line| line.strip_prefix("flags:"))
+        .and_then(|flags| u32::from_str_radix(flags.trim(), 8).ok())
+        .is_some_and(|flags| flags & 0o3 != 0)
+}
+
+/// ss -xH の「Netid State Recv-Q Send-Q 名前 inode 相手の名前 相手の inode」の行から、
+/// inode ごとの（相手の inode、名前）を読む。名前には空白が入ることがあるので後ろから数える
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn parse_ss(text: &str) -> HashMap<u64, (u64, Option<String>)> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let n = fields.len();
+            if n < 8 {
+                return None;
+            }
+            let peer = fields[n - 1].parse().ok()?;
+            let inode = fields[n - 3].parse().ok()?;
+            let name = fields[4..n - 3].join(" ");
+            Some((inode, (peer, (name != "*").then_some(name))))
+        })
+        // 相手のいない（待ち受けなど）ソケットの相手は 0
+        .filter(|(_, (peer, _))| *peer != 0)
+        .collect()
+}
@@ -0,0 +1,58 @@
+// OS ごとに読み方の違う累計（CPU 時間の内訳、カーネルの回数、プロセスごとのフォールト）
+//
+// sysinfo は *BSD で CPU 時間の内訳やフォールトの数を返さない。割合や 1 秒あたりの回数を出す
+// 計算（cpustat / faults）は共通にして、累計を読むところだけを OS ごとの Platform に分ける。
+// Linux は /proc、FreeBSD と OpenBSD は sysctl から読み、ほかの OS では何も読めない。
+use sysinfo::Pid;
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+mod bsd;
+#[cfg(target_os = "linux")]
+mod linux;
+
+/// CPU 時間の累計。user（nice を含み guest を除く）、system、irq、softirq、iowait、steal、guest、idle の順
+pub type CpuTicks = [u64; 8];
+
+/// CPU 時間の累計とカーネルの回数。Linux では同じ /proc/stat から読むので一緒に返す
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CpuCounters {
+    /// 全体の累計
+    pub total: Option<CpuTicks>,
+    /// コアごとの（CPU 番号、累計）
+    pub cores: Vec<(usize, CpuTicks)>,
+    /// コンテキストスイッチ・割り込み・作られたプロセスの累計
+    pub kernel: Option<[u64; 3]>,
+}
+
+pub trait Platform {
+    fn cpu_counters(&self) -> CpuCounters;
+    /// マイナーとメジャーのフォールトの累計。他のユーザーのプロセスなど、読めなければ None
+    fn page_faults(&self, pid: Pid) -> Option<(u64, u64)>;
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::Linux as Native;
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub use bsd::Bsd as Native;
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd")))]
+pub use self::Unsupported as Native;
+
+/// sysinfo の読み書きの量が、バイトではなくブロックを読み書きした回数の OS
+pub const DISK_USAGE_IN_BLOCKS: bool = cfg!(any(target_os = "freebsd", target_os = "openbsd"));
+
+/// 累計を読めない OS（macOS と Windows は sysinfo と apple.rs / wininfo.rs で読む）
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd")))]
+pub struct Unsupported;
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd")))]
+impl Platform for Unsupported {
+    fn cpu_counters(&self) -> CpuCounters {
+        CpuCounters::default()
+    }
+
+    fn page_faults(&self, _pid: Pid) -> Option<(u64, u64)> {
+        None
+    }
+}
@@ -0,0 +1,190 @@
+// FreeBSD と OpenBSD の sysctl から読む
+use std::ffi::{c_int, c_uint, c_void};
+use std::mem::size_of;
+use std::ptr;
+
+use sysinfo::Pid;
+
+use super::{CpuCounters, CpuTicks, Platform};
+
+pub struct Bsd;
+
+impl Platform for Bsd {
+    fn cpu_counters(&self) -> CpuCounters {
+        CpuCounters {
+            total: total_ticks(),
+            cores: core_ticks(),
+            kernel: kernel_counts(),
+        }
+    }
+
+    fn page_faults(&self, pid: Pid) -> Option<(u64, u64)> {
+        page_faults(pid)
+    }
+}
+
+// FreeBSD の CPUSTATES は user nice sys intr idle の順。softirq や iowait に当たるものは無い
+#[cfg(target_os = "freebsd")]
+fn to_ticks(states: &[libc::c_ulong]) -> CpuTicks {
+    let state = |i: usize| u64::from(states[i]);
+    [
+        state(0) + state(1),
+        state(2),
+        state(3),
+        0,
+        0,
+        0,
+        0,
+        state(4),
+    ]
+}
+
+#[cfg(target_os = "freebsd")]
+fn total_ticks() -> Option<CpuTicks> {
+    let states = sysctl_by_name::<libc::c_ulong>("kern.cp_time")?;
+    (states.len() >= libc::CPUSTATES as usize).then(|| to_ticks(&states))
+}
+
+#[cfg(target_os = "freebsd")]
+fn core_ticks() -> Vec<(usize, CpuTicks)> {
+    sysctl_by_name::<libc::c_ulong>("kern.cp_times")
+        .unwrap_or_default()
+        .chunks_exact(libc::CPUSTATES as usize)
+        .map(to_ticks)
+        .enumerate()
+        .collect()
+}
+
+#[cfg(target_os = "freebsd")]
+fn kernel_counts() -> Option<[u64; 3]> {
+    let count = |name: &str| {
+        sysctl_by_name::<libc::c_uint>(name)?
+            .first()
+            .map(|n| u64::from(*n))
+    };
+    // fork には vfork と rfork も数える
+    let forks = count("vm.stats.vm.v_forks")?
+        + count("vm.stats.vm.v_vforks")?
+        + count("vm.stats.vm.v_rforks")?;
+    Some([
+        count("vm.stats.sys.v_swtch")?,
+        count("vm.stats.sys.v_intr")?,
+        forks,
+    ])
+}
+
+#[cfg(target_os = "freebsd")]
+fn page_faults(pid: Pid) -> Option<(u64, u64)> {
+    let mib = [
+        libc::CTL_KERN,
+        libc::KERN_PROC,
+        libc::KERN_PROC_PID,
+        pid.as_u32() as c_int,
+    ];
+    let usage = &sysctl::<libc::kinfo_proc>(&mib)?.first()?.ki_rusage;
+    Some((usage.ru_minflt as u64, usage.ru_majflt as u64))
+}
+
+// OpenBSD の CPUSTATES は user nice sys spin intr idle の順。spin（ロック待ち）は system に入れる
+#[cfg(target_os = "openbsd")]
+fn to_ticks(states: &[u64]) -> CpuTicks {
+    [
+        states[0] + states[1],
+        states[2] + states[3],
+        states[4],
+        0,
+        0,
+        0,
+        0,
+        states[5],
+    ]
+}
+
+#[cfg(target_os = "openbsd")]
+const CPUSTATES: usize = 6;
+
+#[cfg(target_os = "openbsd")]
+fn total_ticks() -> Option<CpuTicks> {
+    let states: Vec<u64> = sysctl::<libc::c_long>(&[libc::CTL_KERN, libc::KERN_CPTIME])?
+        .into_iter()
+        .map(|n| n as u64)
+        .collect();
+    (states.len() >= CPUSTATES).then(|| to_ticks(&states))
+}
+
+// 止めてある CPU は読めないので飛ばす
+#[cfg(target_os = "openbsd")]
+fn core_ticks() -> Vec<(usize, CpuTicks)> {
+    let ncpu = sysctl::<c_int>(&[libc::CTL_HW, libc::HW_NCPU])
+        .and_then(|n| n.first().copied())
+        .unwrap_or(0);
+    (0..ncpu)
+        .filter_map(|cpu| {
+            let states = sysctl::<u64>(&[libc::CTL_KERN, libc::KERN_CPTIME2, cpu])?;
+            (states.len() >= CPUSTATES).then(|| (cpu as usize, to_ticks(&states)))
+        })
+        .collect()
+}
+
+// uvmexp は libc に無いので読まない
+#[cfg(target_os = "openbsd")]
+fn kernel_counts() -> Option<[u64; 3]> {
+    None
+}
+
+#[cfg(target_os = "openbsd")]
+fn page_faults(pid: Pid) -> Option<(u64, u64)> {
+    let mib = [
+        libc::CTL_KERN,
+        libc::KERN_PROC,
+        libc::KERN_PROC_PID,
+        pid.as_u32() as c_int,
+        size_of::<libc::kinfo_proc>() as c_int,
+        1,
+    ];
+    let info = sysctl::<libc::kinfo_proc>(&mib)?;
+    let info = info.first()?;
+    Some((info.p_uru_minflt, info.p_uru_majflt))
+}
+
+fn sysctl<T>(mib: &[c_int]) -> Option<Vec<T>> {
+    read(|old, size| {
+        // SAFETY: mib は呼び出しの間有効で、old と size は read が用意する
+        unsafe {
+            libc::sysctl(
+                mib.as_ptr(),
+                mib.len() as c_uint,
+                old,
+                size,
+                ptr::null_mut(),
+                0,
+            )
+        }
+    })
+}
+
+#[cfg(target_os = "freebsd")]
+fn sysctl_by_name<T>(name: &str) -> Option<Vec<T>> {
+    let name = std::ffi::CString::new(name).ok()?;
+    read(|old, size| {
+        // SAFETY: name は呼び出しの間有効で、old と size は read が用意する
+        unsafe { libc::sysctlbyname(name.as_ptr(), old, size, ptr::null(), 0) }
+    })
+}
+
+// 大きさを先に尋ねてから読む。読めたバイト数に収まる要素だけを返す
+fn read<T>(call: impl Fn(*mut c_void, *mut usize) -> c_int) -> Option<Vec<T>> {
+    let mut size = 0;
+    if call(ptr::null_mut(), &mut size) != 0 {
+        return None;
+    }
+    // 尋ねてから読むまでに増えることがあるので 1 つ余分に取る
+    let mut out: Vec<T> = Vec::with_capacity(size / size_of::<T>() + 1);
+    let mut size = out.capacity() * size_of::<T>();
+    if call(out.as_mut_ptr().cast(), &mut size) != 0 {
+        return None;
+    }
+    // SAFETY: sysctl は size バイトを書き込んだ。T は C の構造体か整数で、どのバイト列も有効
+    unsafe { out.set_len(size / size_of::<T>()) };
+    Some(out)
+}
@@ -0,0 +1,21 @@
+// /proc から読む
+use std::fs;
+
+use sysinfo::Pid;
+
+use super::{CpuCounters, Platform};
+use crate::{cpustat, faults};
+
+pub struct Linux;
+
+impl Platform for Linux {
+    fn cpu_counters(&self) -> CpuCounters {
+        fs::read_to_string("/proc/stat")
+            .map(|text| cpustat::parse_stat(&text))
+            .unwrap_or_default()
+    }
+
+    fn page_faults(&self, pid: Pid) -> Option<(u64, u64)> {
+        faults::parse_stat(&fs::read_to_string(format!("/proc/{pid}/stat")).ok()?)
+    }
+}
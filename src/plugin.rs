@@ -0,0 +1,223 @@
+// 外部のプラグイン（[[plugin]]）
+//
+// プラグインは標準入力と標準出力で JSON を 1 行ずつやり取りするコマンドで、言語は問わない
+// （lua や python のスクリプトでもよい）。プロセスの一覧が更新されるたびにスナップショットを送り、
+// 返ってきた列・パネル・警告を次の描画から表示する。
+//
+// Lua や WASM の処理系は組み込んでいない。mlua は Lua の C のソースを、wasmtime は大きな
+// ランタイムを一緒にビルドすることになり、依存を sysinfo・ratatui・crossterm ほどに絞っている
+// この crate には重すぎる。別のプロセスにしておけば、プラグインが落ちても固まっても画面は止まらず、
+// Lua のスクリプトも `command = "lua heap.lua"` のようにそのまま動かせる。
+//
+// 送るもの:
+//   {"cpu": 12.5, "memory": {"total": ..., "used": ...},
+//    "processes": [{"pid": 1, "name": "...", "cmd": "...", "user": "...", "cpu": 0.0, "memory": ...}]}
+// 返すもの（どれも省略できる）:
+//   {"columns": [{"name": "Heap", "values": {"1234": "512 MB"}}],
+//    "panels": [{"title": "JVM", "lines": ["..."]}],
+//    "alerts": [{"name": "JVM", "detail": "heap 95%"}]}
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Stdio};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use sysinfo::Pid;
+
+use crate::config::PluginConfig;
+use crate::custom;
+use crate::json::{self, Json};
+use crate::sampler::Snapshot;
+
+/// プラグインが最後に返したもの
+#[derive(Debug, Clone, Default)]
+pub struct PluginOutput {
+    pub columns: Vec<PluginColumn>,
+    pub panels: Vec<PluginPanel>,
+    /// 名前と詳細
+    pub alerts: Vec<(String, String)>,
+    /// 起動できない、終了した、返事が読めないなど。あれば警告として出す
+    pub error: Option<String>,
+}
+
+/// プロセス表の右端に足す列（並べ替えには使えない）
+#[derive(Debug, Clone)]
+pub struct PluginColumn {
+    pub name: String,
+    pub values: HashMap<Pid, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginPanel {
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Plugin {
+    pub name: String,
+    snapshots: Sender<Arc<Snapshot>>,
+    output: Arc<Mutex<PluginOutput>>,
+}
+
+impl Plugin {
+    /// 新しいスナップショットを渡す。前のものをまだ処理中なら、終わってから最新のものだけ送る
+    pub fn send(&self, snapshot: &Arc<Snapshot>) {
+        let _ = self.snapshots.send(Arc::clone(snapshot));
+    }
+
+    pub fn output(&self) -> PluginOutput {
+        self.output.lock().expect("plugin output lock").clone()
+    }
+}
+
+pub fn spawn(configs: &[PluginConfig]) -> Vec<Plugin> {
+    configs
+        .iter()
+        .map(|config| {
+            let (tx, rx) = mpsc::channel();
+            let output = Arc::new(Mutex::new(PluginOutput::default()));
+            let shared = Arc::clone(&output);
+            let command = config.command.clone();
+            thread::spawn(move || {
+                let error = match run(&command, &rx, &shared) {
+                    Ok(()) => return,
+                    Err(e) => e,
+                };
+                shared.lock().expect("plugin output lock").error = Some(error);
+            });
+            Plugin {
+                name: config.name.clone(),
+                snapshots: tx,
+                output,
+            }
+        })
+        .collect()
+}
+
+// 画面を閉じてチャネルが切れたら Ok
+fn run(
+    command: &str,
+    snapshots: &Receiver<Arc<Snapshot>>,
+    output: &Mutex<PluginOutput>,
+) -> Result<(), String> {
+    let mut child = custom::shell(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("failed to start: {e}"))?;
+    let result = exchange(&mut child, snapshots, output);
+    let _ = child.kill();
+    let _ = child.wait();
+    result
+}
+
+fn exchange(
+    child: &mut Child,
+    snapshots: &Receiver<Arc<Snapshot>>,
+    output: &Mutex<PluginOutput>,
+) -> Result<(), String> {
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    let mut stdout = BufReader::new(child.stdout.take().expect("piped stdout"));
+    let mut line = String::new();
+    while let Ok(mut snapshot) = snapshots.recv() {
+        // 溜まっていたら最新のものだけ送る
+        while let Ok(newer) = snapshots.try_recv() {
+            snapshot = newer;
+        }
+        writeln!(stdin, "{}", encode(&snapshot).to_string_compact())
+            .and_then(|()| stdin.flush())
+            .map_err(|_| "exited".to_string())?;
+        line.clear();
+        match stdout.read_line(&mut line) {
+            Ok(0) | Err(_) => return Err("exited".to_string()),
+            Ok(_) => {}
+        }
+        let reply = json::parse(line.trim()).map_err(|e| format!("invalid reply: {e}"))?;
+        *output.lock().expect("plugin output lock") = decode(&reply);
+    }
+    Ok(())
+}
+
+fn encode(snapshot: &Snapshot) -> Json {
+    let processes = snapshot
+        .processes
+        .iter()
+        .map(|p| {
+            Json::Object(vec![
+                ("pid".to_string(), (p.pid.as_u32() as u64).into()),
+                ("name".to_string(), p.name.as_ref().into()),
+                ("cmd".to_string(), p.cmd.as_ref().into()),
+                ("user".to_string(), p.user.as_ref().into()),
+                ("cpu".to_string(), (p.cpu_usage as f64).into()),
+                ("memory".to_string(), p.memory.into()),
+            ])
+        })
+        .collect();
+    Json::Object(vec![
+        ("cpu".to_string(), (snapshot.cpu.normalized() as f64).into()),
+        (
+            "memory".to_string(),
+            Json::Object(vec![
+                ("total".to_string(), snapshot.memory.total.into()),
+                ("used".to_string(), snapshot.memory.used.into()),
+            ]),
+        ),
+        ("processes".to_string(), Json::Array(processes)),
+    ])
+}
+
+// 形の合わない項目は読み飛ばす
+fn decode(reply: &Json) -> PluginOutput {
+    let items = |key: &str| reply.get(key).and_then(Json::as_array).unwrap_or_default();
+    let text = |item: &Json, key: &str| item.get(key).and_then(Json::as_str).map(str::to_string);
+    let columns = items("columns")
+        .iter()
+        .filter_map(|item| {
+            let values = match item.get("values")? {
+                Json::Object(fields) => fields
+                    .iter()
+                    .filter_map(|(pid, value)| {
+                        Some((Pid::from_str(pid).ok()?, value.as_str()?.to_string()))
+                    })
+                    .collect(),
+                _ => return None,
+            };
+            Some(PluginColumn {
+                name: text(item, "name")?,
+                values,
+            })
+        })
+        .collect();
+    let panels = items("panels")
+        .iter()
+        .filter_map(|item| {
+            let lines = item.get("lines")?.as_array()?;
+            Some(PluginPanel {
+                title: text(item, "title")?,
+                lines: lines
+                    .iter()
+                    .filter_map(|line| Some(line.as_str()?.to_string()))
+                    .collect(),
+            })
+        })
+        .collect();
+    let alerts = items("alerts")
+        .iter()
+        .filter_map(|item| {
+            Some((
+                text(item, "name")?,
+                text(item, "detail").unwrap_or_default(),
+            ))
+        })
+        .collect();
+    PluginOutput {
+        columns,
+        panels,
+        alerts,
+        error: None,
+    }
+}
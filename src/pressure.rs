@@ -0,0 +1,88 @@
+// Pressure Stall Information（Linux の /proc/pressure/{cpu,memory,io}）
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PressureAverages {
+    /// 直近 10 / 60 / 300 秒の待ち時間の割合（%）
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PressureLine {
+    /// 一部のタスクが待たされていた割合
+    pub some: PressureAverages,
+    /// すべてのタスクが待たされていた割合。cpu では古いカーネルだと無い
+    pub full: Option<PressureAverages>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PressureSnapshot {
+    pub cpu: Option<PressureLine>,
+    pub memory: Option<PressureLine>,
+    pub io: Option<PressureLine>,
+}
+
+/// これ以上なら注意、CRITICAL 以上なら警告の色にする（%）
+pub const CAUTION_PERCENT: f64 = 10.0;
+pub const CRITICAL_PERCENT: f64 = 40.0;
+
+impl PressureSnapshot {
+    /// 表示する順の (名前, 値)
+    pub fn resources(&self) -> [(&'static str, Option<PressureLine>); 3] {
+        [("cpu", self.cpu), ("memory", self.memory), ("io", self.io)]
+    }
+}
+
+/// PSI が無効なカーネル（または Linux 以外）では None
+#[cfg(target_os = "linux")]
+pub fn collect() -> Option<PressureSnapshot> {
+    let read = |name: &str| {
+        std::fs::read_to_string(format!("/proc/pressure/{name}"))
+            .ok()
+            .and_then(|text| parse(&text))
+    };
+    let snapshot = PressureSnapshot {
+        cpu: read("cpu"),
+        memory: read("memory"),
+        io: read("io"),
+    };
+    if snapshot.cpu.is_none() && snapshot.memory.is_none() && snapshot.io.is_none() {
+        return None;
+    }
+    Some(snapshot)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn collect() -> Option<PressureSnapshot> {
+    None
+}
+
+// "some avg10=0.00 avg60=0.00 avg300=0.00 total=0" の形の行を読む
+#[cfg(target_os = "linux")]
+fn parse(text: &str) -> Option<PressureLine> {
+    let mut some = None;
+    let mut full = None;
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        let slot = match fields.next() {
+            Some("some") => &mut some,
+            Some("full") => &mut full,
+            _ => continue,
+        };
+        let mut averages = PressureAverages::default();
+        for field in fields {
+            let Some((key, value)) = field.split_once('=') else {
+                continue;
+            };
+            let target = match key {
+                "avg10" => &mut averages.avg10,
+                "avg60" => &mut averages.avg60,
+                "avg300" => &mut averages.avg300,
+                _ => continue,
+            };
+            *target = value.parse().ok()?;
+        }
+        *slot = Some(averages);
+    }
+    Some(PressureLine { some: some?, full })
+}
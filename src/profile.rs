@@ -0,0 +1,199 @@
+// 選択したプロセスがどこで CPU を使っているか（R、profile 機能、Linux）
+//
+// perf record で数秒間サンプリングし、perf report で自分の時間（子の関数を含まない）の多い
+// シンボルから並べる。採っている間も画面を止めないよう、別スレッドで走らせて結果を置く。
+// 他のユーザーのプロセスは root か kernel.perf_event_paranoid を下げないと採れない。
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sysinfo::Pid;
+
+use crate::config::ProfileConfig;
+
+/// 1 秒あたりのサンプル数（素数にして、定期的な処理と重ならないようにする）
+const FREQUENCY: u32 = 99;
+/// 残すシンボルの数
+const SYMBOLS: usize = 50;
+/// 止められたかを確かめる間隔
+const POLL: Duration = Duration::from_millis(100);
+
+/// 採れなかったときはその理由
+type Symbols = Result<Vec<Symbol>, String>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    /// 全サンプルのうちの割合（%）
+    pub percent: f64,
+    /// 関数名。シンボルが無ければアドレス
+    pub name: String,
+    /// 実行ファイルか共有ライブラリ（カーネルなら [kernel.kallsyms]）
+    pub object: String,
+}
+
+/// プロファイルの結果（R）
+#[derive(Debug)]
+pub struct ProfileView {
+    pub pid: Pid,
+    pub name: String,
+    pub duration: Duration,
+    started: Instant,
+    result: Arc<Mutex<Option<Symbols>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ProfileView {
+    /// 採り始める。閉じる（drop する）と途中でも perf を止める
+    pub fn start(pid: Pid, name: String, config: &ProfileConfig) -> ProfileView {
+        let result = Arc::new(Mutex::new(None));
+        let stop = Arc::new(AtomicBool::new(false));
+        let shared = Arc::clone(&result);
+        let stopped = Arc::clone(&stop);
+        let perf = config.perf.clone();
+        let duration = config.duration;
+        thread::spawn(move || {
+            let data = std::env::temp_dir().join(format!(
+                "rust-top-profile-{}-{pid}.data",
+                std::process::id()
+            ));
+            let symbols = match record(&perf, pid, duration, &data, &stopped) {
+                Ok(true) => report(&perf, &data),
+                // 閉じられたので、結果を置く先も無い
+                Ok(false) => return,
+                Err(e) => Err(e),
+            };
+            fs::remove_file(&data).ok();
+            *shared.lock().expect("profile result lock") = Some(symbols);
+        });
+        ProfileView {
+            pid,
+            name,
+            duration,
+            started: Instant::now(),
+            result,
+            stop,
+        }
+    }
+
+    /// 採っている間は None
+    pub fn result(&self) -> Option<Symbols> {
+        self.result.lock().expect("profile result lock").clone()
+    }
+
+    /// 採り終わるまでの時間の目安
+    pub fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.started.elapsed())
+    }
+}
+
+impl Drop for ProfileView {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// 採り終われば true、途中で閉じられたら false
+fn record(
+    perf: &str,
+    pid: Pid,
+    duration: Duration,
+    data: &Path,
+    stop: &AtomicBool,
+) -> Result<bool, String> {
+    // -p と一緒に sleep を渡すと、sleep が終わるまで（プロセスが先に終われば、そこまで）採る
+    let mut child = Command::new(perf)
+        .args([
+            "record",
+            "-F",
+            &FREQUENCY.to_string(),
+            "-p",
+            &pid.to_string(),
+            "-o",
+        ])
+        .arg(data)
+        .args(["--", "sleep", &format!("{:.1}", duration.as_secs_f64())])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{perf}: {e}"))?;
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            child.kill().ok();
+            child.wait().ok();
+            fs::remove_file(data).ok();
+            return Ok(false);
+        }
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Ok(true),
+            Ok(Some(status)) => return Err(failure(perf, &mut child, status)),
+            Ok(None) => thread::sleep(POLL),
+            Err(e) => return Err(format!("{perf}: {e}")),
+        }
+    }
+}
+
+fn report(perf: &str, data: &Path) -> Symbols {
+    let output = Command::new(perf)
+        .args(["report", "--stdio", "--no-children", "--sort", "dso,sym"])
+        .args(["--field-separator", "\t", "-i"])
+        .arg(data)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("{perf}: {e}"))?;
+    if !output.status.success() {
+        return Err(error_line(perf, &output.stderr, output.status));
+    }
+    Ok(parse_report(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn failure(perf: &str, child: &mut Child, status: ExitStatus) -> String {
+    let mut stderr = Vec::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        pipe.read_to_end(&mut stderr).ok();
+    }
+    error_line(perf, &stderr, status)
+}
+
+// perf は「Error:」だけの行のあとに理由を書くので、中身のある最初の行を使う
+fn error_line(perf: &str, stderr: &[u8], status: ExitStatus) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    match stderr
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && *line != "Error:")
+    {
+        Some(line) => format!("{perf}: {line}"),
+        None => format!("{perf}: {status}"),
+    }
+}
+
+/// perf report --field-separator の「割合、オブジェクト、[.] シンボル」の行。# の行は見出し
+pub fn parse_report(text: &str) -> Vec<Symbol> {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split('\t').map(str::trim);
+            let percent = fields.next()?.strip_suffix('%')?.parse().ok()?;
+            let object = fields.next()?.to_string();
+            // [.] はユーザー空間、[k] はカーネル
+            let symbol = fields.next()?;
+            let name = symbol
+                .split_once("] ")
+                .map_or(symbol, |(_, name)| name)
+                .trim()
+                .to_string();
+            Some(Symbol {
+                percent,
+                name,
+                object,
+            })
+        })
+        .take(SYMBOLS)
+        .collect()
+}
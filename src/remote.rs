@@ -0,0 +1,1064 @@
+// TCP でスナップショットを送受信する（--serve / --connect）
+//
+// 1 フレームは 4 バイト（ビッグエンディアン）の長さと、スナップショット全体の JSON。
+// 接続した側は表示するだけで、シグナルなどの操作は送らない。
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use sysinfo::{Pid, ProcessStatus};
+
+use crate::actions::{ActionError, ActionOutcome};
+use crate::battery::{BatterySnapshot, ChargeState};
+use crate::cgroup::CgroupSnapshot;
+use crate::config::Config;
+use crate::coretype::ClusterUsage;
+use crate::cpufreq::CpuFrequency;
+use crate::cpustat::{CpuBreakdown, KernelRates};
+use crate::json::{self, Json};
+use crate::meminfo::{HugePages, MemoryBreakdown, Overcommit, ThpMode};
+use crate::numa::{NumaNode, NumaSnapshot};
+use crate::pressure::{PressureAverages, PressureLine, PressureSnapshot};
+use crate::rpi::SocSnapshot;
+use crate::sampler::{
+    self, CpuSnapshot, MemorySnapshot, ProcessChanges, ProcessInfo, Request, SamplerHandle,
+    Snapshot, SystemInfo, Update,
+};
+use crate::sensors::{Reading, SensorsSnapshot};
+use crate::sessions::Session;
+use crate::shm::{ShmSnapshot, TmpfsMount};
+use crate::zram::{CompressedSwap, ZramDevice, Zswap};
+
+/// これより大きいフレームは壊れているものとして接続を切る
+const MAX_FRAME: u32 = 64 * 1024 * 1024;
+
+/// 送り先が詰まっていたら諦めるまでの時間
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 相手ごとに送らずに溜めておくフレームの数。溢れた分は捨てて、次の新しいものを送る
+const QUEUED_FRAMES: usize = 2;
+
+/// このマシンのスナップショットを接続してきた相手に送り続ける
+/// [mqtt] があれば、画面を持たないこのモードでもブローカーに publish する
+pub fn serve(addr: &str, config: &Config) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("rust-top: serving snapshots on {}", listener.local_addr()?);
+    // 遅い相手が他の相手と受け付けを待たせないよう、書き込みは相手ごとのスレッドでする
+    let clients: Arc<Mutex<Vec<SyncSender<Arc<str>>>>> = Arc::default();
+
+    let accepted = Arc::clone(&clients);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+            let _ = stream.set_nodelay(true);
+            let (frame_tx, frames) = mpsc::sync_channel::<Arc<str>>(QUEUED_FRAMES);
+            thread::spawn(move || {
+                let mut out = BufWriter::new(stream);
+                // 書き込めなかった（切断された・詰まった）ら終わり、送る側から外れる
+                while let Ok(frame) = frames.recv() {
+                    if write_frame(&mut out, frame.as_bytes()).is_err() {
+                        break;
+                    }
+                }
+            });
+            accepted.lock().expect("clients lock").push(frame_tx);
+        }
+    });
+
+    let handle = sampler::spawn(config.refresh.clone());
+    #[cfg(feature = "mqtt")]
+    let mut mqtt = crate::mqtt::Publisher::open(&config.mqtt);
+    loop {
+        let update = handle
+            .updates
+            .recv()
+            .map_err(|_| io::Error::other("sampler stopped"))?;
+        let snapshot = match update {
+            Update::Snapshot(snapshot, _) => snapshot,
+            Update::Action(_) | Update::Interval(_) => continue,
+            #[cfg(feature = "environ")]
+            Update::Environ(..) => continue,
+        };
+        #[cfg(feature = "mqtt")]
+        if let Some(publisher) = &mut mqtt
+            && let Err(e) = publisher.publish(&snapshot)
+        {
+            eprintln!("rust-top: failed to publish to MQTT: {e}");
+        }
+        let frame: Arc<str> = encode(&snapshot).to_string_compact().into();
+        clients.lock().expect("clients lock").retain(|client| {
+            match client.try_send(Arc::clone(&frame)) {
+                Ok(()) | Err(TrySendError::Full(_)) => true,
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+    }
+}
+
+/// リモートのエージェントに接続し、サンプラーの代わりにスナップショットを受け取る
+pub fn connect(addr: &str) -> io::Result<SamplerHandle> {
+    let stream = TcpStream::connect(addr)?;
+    let (update_tx, update_rx) = mpsc::channel();
+    let (request_tx, request_rx) = mpsc::channel();
+    let addr = addr.to_string();
+    thread::spawn(move || {
+        let (frames_tx, frames) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stream);
+            while let Ok(frame) = read_frame(&mut reader) {
+                if frames_tx.send(frame).is_err() {
+                    break;
+                }
+            }
+        });
+        // プロセスの出入りを UI に知らせるため、前に受け取ったものと比べる
+        let mut previous: Option<Arc<Snapshot>> = None;
+        loop {
+            // 操作はリモートでは実行できないので、すぐに断る
+            while let Ok(request) = request_rx.try_recv() {
+                let update = match request {
+                    Request::Action(command) => Update::Action(ActionOutcome {
+                        command,
+                        result: Err(ActionError::Failed(format!(
+                            "connected to {addr} (read-only)"
+                        ))),
+                    }),
+                    Request::Interval(_) => {
+                        Update::Interval(Err(format!("the agent on {addr} sets it")))
+                    }
+                    // 環境変数はエージェントから送らない
+                    #[cfg(feature = "environ")]
+                    Request::Environ(target) => {
+                        Update::Environ(target, Err(format!("connected to {addr} (read-only)")))
+                    }
+                    Request::Pause(_)
+                    | Request::Refresh(_)
+                    | Request::MemoryDetail(_)
+                    | Request::NumaNodes(_)
+                    | Request::FdCounts(_)
+                    | Request::WaitChannels(_)
+                    | Request::PageFaults(_)
+                    | Request::NetTraffic(_) => continue,
+                };
+                if update_tx.send(update).is_err() {
+                    return;
+                }
+            }
+            let frame = match frames.recv_timeout(Duration::from_millis(100)) {
+                Ok(frame) => frame,
+                Err(RecvTimeoutError::Timeout) => continue,
+                // 接続が切れたらチャネルを閉じ、UI 側で終了させる
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            let Ok(snapshot) = String::from_utf8(frame)
+                .map_err(|e| e.to_string())
+                .and_then(|text| json::parse(&text))
+                .and_then(|value| decode(&value))
+            else {
+                return;
+            };
+            let changes = match &previous {
+                Some(previous) if previous.process_generation != snapshot.process_generation => {
+                    snapshot.changes_since(previous)
+                }
+                _ => ProcessChanges::default(),
+            };
+            let snapshot = Arc::new(snapshot);
+            previous = Some(Arc::clone(&snapshot));
+            if update_tx.send(Update::Snapshot(snapshot, changes)).is_err() {
+                return;
+            }
+        }
+    });
+    Ok(SamplerHandle {
+        updates: update_rx,
+        requests: request_tx,
+    })
+}
+
+fn write_frame(out: &mut impl Write, frame: &[u8]) -> io::Result<()> {
+    out.write_all(&(frame.len() as u32).to_be_bytes())?;
+    out.write_all(frame)?;
+    out.flush()
+}
+
+fn read_frame(input: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    input.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame too large",
+        ));
+    }
+    let mut frame = vec![0; len as usize];
+    input.read_exact(&mut frame)?;
+    Ok(frame)
+}
+
+fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(
+        fields
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+    )
+}
+
+pub fn encode(snapshot: &Snapshot) -> Json {
+    let info = &snapshot.info;
+    object(vec![
+        ("cpu_primed", snapshot.cpu_primed.into()),
+        (
+            "cpu",
+            object(vec![
+                ("usage_sum", (snapshot.cpu.usage_sum as f64).into()),
+                ("count", (snapshot.cpu.count as u64).into()),
+                (
+                    "breakdown",
+                    snapshot.cpu.breakdown.map(encode_breakdown).into(),
+                ),
+                (
+                    "core_breakdown",
+                    Json::Array(
+                        snapshot
+                            .cpu
+                            .core_breakdown
+                            .iter()
+                            .map(|&(cpu, b)| {
+                                Json::Array(vec![(cpu as u64).into(), encode_breakdown(b)])
+                            })
+                            .collect(),
+                    ),
+                ),
+                (
+                    "kernel",
+                    snapshot
+                        .cpu
+                        .kernel
+                        .map(|k| {
+                            object(vec![
+                                ("context_switches", k.context_switches.into()),
+                                ("interrupts", k.interrupts.into()),
+                                ("forks", k.forks.into()),
+                            ])
+                        })
+                        .into(),
+                ),
+                (
+                    "clusters",
+                    Json::Array(
+                        snapshot
+                            .cpu
+                            .clusters
+                            .iter()
+                            .map(|c| {
+                                object(vec![
+                                    ("label", c.label.as_str().into()),
+                                    ("count", (c.count as u64).into()),
+                                    ("usage", (c.usage as f64).into()),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+                (
+                    "usages",
+                    Json::Array(
+                        snapshot
+                            .cpu
+                            .usages
+                            .iter()
+                            .map(|&u| (u as f64).into())
+                            .collect(),
+                    ),
+                ),
+            ]),
+        ),
+        (
+            "memory",
+            object(vec![
+                ("total", snapshot.memory.total.into()),
+                ("used", snapshot.memory.used.into()),
+                ("total_swap", snapshot.memory.total_swap.into()),
+                ("used_swap", snapshot.memory.used_swap.into()),
+                (
+                    "breakdown",
+                    snapshot
+                        .memory
+                        .breakdown
+                        .map(|b| {
+                            object(vec![
+                                ("free", b.free.into()),
+                                ("buffers", b.buffers.into()),
+                                ("cached", b.cached.into()),
+                                ("available", b.available.into()),
+                                ("shared", b.shared.into()),
+                            ])
+                        })
+                        .into(),
+                ),
+                (
+                    "huge_pages",
+                    snapshot
+                        .memory
+                        .huge_pages
+                        .map(|h| {
+                            object(vec![
+                                ("total", h.total.into()),
+                                ("free", h.free.into()),
+                                ("reserved", h.reserved.into()),
+                                ("surplus", h.surplus.into()),
+                                ("page_size", h.page_size.into()),
+                                ("anon_huge", h.anon_huge.into()),
+                                ("shmem_huge", h.shmem_huge.into()),
+                                ("thp", h.thp.map(ThpMode::label).into()),
+                                ("contiguous_free", h.contiguous_free.into()),
+                                ("commit_limit", h.commit_limit.into()),
+                                ("committed", h.committed.into()),
+                                ("overcommit", h.overcommit.map(Overcommit::label).into()),
+                            ])
+                        })
+                        .into(),
+                ),
+            ]),
+        ),
+        (
+            "processes",
+            Json::Array(snapshot.processes.iter().map(encode_process).collect()),
+        ),
+        ("process_generation", snapshot.process_generation.into()),
+        (
+            "info",
+            object(vec![
+                ("cpu_count", (info.cpu_count as u64).into()),
+                ("cpu_arch", info.cpu_arch.as_str().into()),
+                ("brand", info.brand.as_str().into()),
+                (
+                    "frequency",
+                    object(vec![
+                        (
+                            "current",
+                            Json::Array(info.frequency.current.iter().map(|&f| f.into()).collect()),
+                        ),
+                        (
+                            "governors",
+                            Json::Array(
+                                info.frequency
+                                    .governors
+                                    .iter()
+                                    .map(|g| g.as_str().into())
+                                    .collect(),
+                            ),
+                        ),
+                        (
+                            "range",
+                            info.frequency.range.map_or(Json::Null, |(min, max)| {
+                                Json::Array(vec![min.into(), max.into()])
+                            }),
+                        ),
+                    ]),
+                ),
+                ("uptime", info.uptime.into()),
+                ("boot_time", info.boot_time.into()),
+                (
+                    "sessions",
+                    Json::Array(
+                        info.sessions
+                            .iter()
+                            .map(|s| {
+                                object(vec![
+                                    ("user", s.user.as_str().into()),
+                                    ("line", s.line.as_str().into()),
+                                    ("host", s.host.as_deref().into()),
+                                    ("since", s.since.into()),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+                (
+                    "load_average",
+                    Json::Array(info.load_average.iter().map(|&l| l.into()).collect()),
+                ),
+                (
+                    "kernel_long_version",
+                    info.kernel_long_version.as_str().into(),
+                ),
+                ("long_os_version", info.long_os_version.clone().into()),
+                ("edition", info.edition.clone().into()),
+                ("os_build", info.os_build.clone().into()),
+                ("host_name", info.host_name.clone().into()),
+                (
+                    "open_files_limit",
+                    info.open_files_limit.map(|n| n as u64).into(),
+                ),
+                ("product_name", info.product_name.clone().into()),
+                ("vendor_name", info.vendor_name.clone().into()),
+            ]),
+        ),
+        (
+            "battery",
+            snapshot.battery.as_ref().map_or(Json::Null, |b| {
+                object(vec![
+                    ("percent", b.percent.into()),
+                    ("state", b.state.label().into()),
+                    (
+                        "time_remaining",
+                        b.time_remaining.map(|d| d.as_secs()).into(),
+                    ),
+                    ("power", b.power.into()),
+                ])
+            }),
+        ),
+        (
+            "cgroup",
+            snapshot.cgroup.as_ref().map_or(Json::Null, |c| {
+                object(vec![
+                    ("path", c.path.as_str().into()),
+                    ("cpu_usage", c.cpu_usage.into()),
+                    ("cpu_limit", c.cpu_limit.into()),
+                    ("memory_used", c.memory_used.into()),
+                    ("memory_limit", c.memory_limit.into()),
+                ])
+            }),
+        ),
+        (
+            "pressure",
+            snapshot.pressure.as_ref().map_or(Json::Null, |p| {
+                let line = |line: Option<PressureLine>| {
+                    line.map_or(Json::Null, |l| {
+                        object(vec![
+                            ("some", encode_averages(l.some)),
+                            ("full", l.full.map_or(Json::Null, encode_averages)),
+                        ])
+                    })
+                };
+                object(vec![
+                    ("cpu", line(p.cpu)),
+                    ("memory", line(p.memory)),
+                    ("io", line(p.io)),
+                ])
+            }),
+        ),
+        (
+            "numa",
+            snapshot.numa.as_ref().map_or(Json::Null, |n| {
+                Json::Array(
+                    n.nodes
+                        .iter()
+                        .map(|node| {
+                            object(vec![
+                                ("id", (node.id as u64).into()),
+                                ("cpus", node.cpus.as_str().into()),
+                                ("total", node.total.into()),
+                                ("free", node.free.into()),
+                            ])
+                        })
+                        .collect(),
+                )
+            }),
+        ),
+        (
+            "shm",
+            snapshot.shm.as_ref().map_or(Json::Null, |s| {
+                object(vec![
+                    (
+                        "mounts",
+                        Json::Array(
+                            s.mounts
+                                .iter()
+                                .map(|m| {
+                                    object(vec![
+                                        ("path", m.path.as_str().into()),
+                                        ("used", m.used.into()),
+                                        ("total", m.total.into()),
+                                    ])
+                                })
+                                .collect(),
+                        ),
+                    ),
+                    (
+                        "sysv",
+                        s.sysv.map_or(Json::Null, |(count, bytes)| {
+                            object(vec![
+                                ("segments", (count as u64).into()),
+                                ("bytes", bytes.into()),
+                            ])
+                        }),
+                    ),
+                ])
+            }),
+        ),
+        (
+            "compressed_swap",
+            snapshot.compressed_swap.as_ref().map_or(Json::Null, |c| {
+                object(vec![
+                    (
+                        "zram",
+                        Json::Array(
+                            c.zram
+                                .iter()
+                                .map(|d| {
+                                    object(vec![
+                                        ("name", d.name.as_str().into()),
+                                        ("algorithm", d.algorithm.as_deref().into()),
+                                        ("disksize", d.disksize.into()),
+                                        ("original", d.original.into()),
+                                        ("compressed", d.compressed.into()),
+                                        ("used", d.used.into()),
+                                        ("same_pages", d.same_pages.into()),
+                                    ])
+                                })
+                                .collect(),
+                        ),
+                    ),
+                    (
+                        "zswap",
+                        c.zswap.as_ref().map_or(Json::Null, |z| {
+                            object(vec![
+                                ("compressor", z.compressor.as_deref().into()),
+                                ("pool", z.pool.into()),
+                                ("stored", z.stored.into()),
+                            ])
+                        }),
+                    ),
+                    ("page_size", c.page_size.into()),
+                ])
+            }),
+        ),
+        (
+            "soc",
+            snapshot.soc.as_ref().map_or(Json::Null, |s| {
+                object(vec![
+                    ("model", s.model.as_str().into()),
+                    ("temperature", s.temperature.into()),
+                    ("core_volts", s.core_volts.into()),
+                    ("throttled", s.throttled.map(u64::from).into()),
+                ])
+            }),
+        ),
+        (
+            "sensors",
+            snapshot.sensors.as_ref().map_or(Json::Null, |s| {
+                object(vec![
+                    ("fans", encode_readings(&s.fans)),
+                    ("power", encode_readings(&s.power)),
+                    ("package_watts", s.package_watts.into()),
+                ])
+            }),
+        ),
+    ])
+}
+
+fn encode_readings(readings: &[Reading]) -> Json {
+    Json::Array(
+        readings
+            .iter()
+            .map(|r| {
+                object(vec![
+                    ("name", r.name.as_str().into()),
+                    ("value", r.value.into()),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn encode_breakdown(b: CpuBreakdown) -> Json {
+    object(vec![
+        ("user", b.user.into()),
+        ("system", b.system.into()),
+        ("irq", b.irq.into()),
+        ("softirq", b.softirq.into()),
+        ("iowait", b.iowait.into()),
+        ("steal", b.steal.into()),
+        ("guest", b.guest.into()),
+        ("idle", b.idle.into()),
+    ])
+}
+
+fn encode_averages(a: PressureAverages) -> Json {
+    Json::Array(vec![a.avg10.into(), a.avg60.into(), a.avg300.into()])
+}
+
+fn encode_process(p: &ProcessInfo) -> Json {
+    object(vec![
+        ("pid", (p.pid.as_u32() as u64).into()),
+        ("parent", p.parent.map(|pid| pid.as_u32() as u64).into()),
+        ("name", (*p.name).into()),
+        ("cmd", (*p.cmd).into()),
+        ("user", (*p.user).into()),
+        ("container", p.container.as_deref().into()),
+        ("status", status_name(p.status).into()),
+        ("threads", (p.threads as u64).into()),
+        ("is_thread", p.is_thread.into()),
+        ("start_time", p.start_time.into()),
+        ("cpu_usage", (p.cpu_usage as f64).into()),
+        ("cpu_time", p.cpu_time.into()),
+        ("memory", p.memory.into()),
+        ("virtual_memory", p.virtual_memory.into()),
+        ("shared", p.shared.into()),
+        ("swap", p.swap.into()),
+        (
+            "numa_pages",
+            p.numa_pages.as_ref().map_or(Json::Null, |pages| {
+                Json::Array(pages.iter().map(|&n| n.into()).collect())
+            }),
+        ),
+        ("open_files", p.open_files.map(|n| n as u64).into()),
+        ("wait_channel", p.wait_channel.as_deref().into()),
+        ("minor_faults", p.minor_faults.into()),
+        ("major_faults", p.major_faults.into()),
+        ("net_received", p.net_received.into()),
+        ("net_sent", p.net_sent.into()),
+        (
+            "cpu_history",
+            Json::Array(p.cpu_history.iter().map(|&v| (v as f64).into()).collect()),
+        ),
+        ("disk_read", p.disk_read.into()),
+        ("disk_written", p.disk_written.into()),
+    ])
+}
+
+pub fn decode(value: &Json) -> Result<Snapshot, String> {
+    let missing = |key: &str| format!("missing `{key}`");
+    let num = |v: &Json, key: &str| {
+        v.get(key)
+            .and_then(Json::as_u64)
+            .ok_or_else(|| missing(key))
+    };
+    let float = |v: &Json, key: &str| {
+        v.get(key)
+            .and_then(Json::as_f64)
+            .ok_or_else(|| missing(key))
+    };
+    let text = |v: &Json, key: &str| v.get(key).and_then(Json::as_str).map(str::to_string);
+    let field = |key: &str| value.get(key).ok_or_else(|| missing(key));
+
+    let cpu = field("cpu")?;
+    let memory = field("memory")?;
+    let info = field("info")?;
+    let processes = field("processes")?
+        .as_array()
+        .ok_or_else(|| missing("processes"))?
+        .iter()
+        .map(decode_process)
+        .collect::<Result<_, _>>()?;
+    let non_null = |key: &str| value.get(key).filter(|v| **v != Json::Null);
+    let averages = |v: &Json| -> Option<PressureAverages> {
+        let items = v.as_array()?;
+        Some(PressureAverages {
+            avg10: items.first()?.as_f64()?,
+            avg60: items.get(1)?.as_f64()?,
+            avg300: items.get(2)?.as_f64()?,
+        })
+    };
+    // irq・softirq・guest の無い古いエージェントでは 0（irq と softirq は system に入っている）
+    let breakdown = |b: &Json| {
+        if *b == Json::Null {
+            return None;
+        }
+        Some(CpuBreakdown {
+            user: float(b, "user").ok()?,
+            system: float(b, "system").ok()?,
+            irq: float(b, "irq").unwrap_or(0.0),
+            softirq: float(b, "softirq").unwrap_or(0.0),
+            iowait: float(b, "iowait").ok()?,
+            steal: float(b, "steal").ok()?,
+            guest: float(b, "guest").unwrap_or(0.0),
+            idle: float(b, "idle").ok()?,
+        })
+    };
+    let pressure_line = |v: &Json, key: &str| -> Option<PressureLine> {
+        let line = v.get(key)?;
+        Some(PressureLine {
+            some: averages(line.get("some")?)?,
+            full: line.get("full").and_then(averages),
+        })
+    };
+    Ok(Snapshot {
+        cpu_primed: value
+            .get("cpu_primed")
+            .and_then(Json::as_bool)
+            .unwrap_or(false),
+        cpu: CpuSnapshot {
+            usage_sum: float(cpu, "usage_sum")? as f32,
+            count: num(cpu, "count")? as usize,
+            breakdown: cpu.get("breakdown").and_then(breakdown),
+            core_breakdown: cpu
+                .get("core_breakdown")
+                .and_then(Json::as_array)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|core| match core.as_array()? {
+                    [cpu, b] => Some((cpu.as_u64()? as usize, breakdown(b)?)),
+                    _ => None,
+                })
+                .collect(),
+            kernel: cpu
+                .get("kernel")
+                .filter(|k| **k != Json::Null)
+                .and_then(|k| {
+                    Some(KernelRates {
+                        context_switches: float(k, "context_switches").ok()?,
+                        interrupts: float(k, "interrupts").ok()?,
+                        forks: float(k, "forks").ok()?,
+                    })
+                }),
+            clusters: cpu
+                .get("clusters")
+                .and_then(Json::as_array)
+                .unwrap_or_default()
+                .iter()
+                .map(|c| ClusterUsage {
+                    label: text(c, "label").unwrap_or_default(),
+                    count: num(c, "count").unwrap_or(0) as usize,
+                    usage: float(c, "usage").unwrap_or(0.0) as f32,
+                })
+                .collect(),
+            usages: cpu
+                .get("usages")
+                .and_then(Json::as_array)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|v| Some(v.as_f64()? as f32))
+                .collect(),
+        },
+        memory: MemorySnapshot {
+            total: num(memory, "total")?,
+            used: num(memory, "used")?,
+            total_swap: num(memory, "total_swap")?,
+            used_swap: num(memory, "used_swap")?,
+            breakdown: memory
+                .get("breakdown")
+                .filter(|b| **b != Json::Null)
+                .and_then(|b| {
+                    Some(MemoryBreakdown {
+                        free: num(b, "free").ok()?,
+                        buffers: num(b, "buffers").ok()?,
+                        cached: num(b, "cached").ok()?,
+                        available: num(b, "available").ok()?,
+                        shared: num(b, "shared").unwrap_or(0),
+                    })
+                }),
+            huge_pages: memory
+                .get("huge_pages")
+                .filter(|h| **h != Json::Null)
+                .and_then(|h| {
+                    Some(HugePages {
+                        total: num(h, "total").ok()?,
+                        free: num(h, "free").ok()?,
+                        reserved: num(h, "reserved").unwrap_or(0),
+                        surplus: num(h, "surplus").unwrap_or(0),
+                        page_size: num(h, "page_size").ok()?,
+                        anon_huge: num(h, "anon_huge").unwrap_or(0),
+                        shmem_huge: num(h, "shmem_huge").unwrap_or(0),
+                        thp: text(h, "thp").as_deref().and_then(ThpMode::from_label),
+                        contiguous_free: num(h, "contiguous_free").ok(),
+                        commit_limit: num(h, "commit_limit").unwrap_or(0),
+                        committed: num(h, "committed").unwrap_or(0),
+                        overcommit: text(h, "overcommit")
+                            .as_deref()
+                            .and_then(Overcommit::from_label),
+                    })
+                }),
+        },
+        processes,
+        process_generation: num(value, "process_generation")?,
+        info: SystemInfo {
+            cpu_count: num(info, "cpu_count")? as usize,
+            cpu_arch: text(info, "cpu_arch").unwrap_or_default(),
+            brand: text(info, "brand").unwrap_or_default(),
+            frequency: info
+                .get("frequency")
+                .map_or_else(CpuFrequency::default, |f| {
+                    let items = |key: &str| f.get(key).and_then(Json::as_array).unwrap_or_default();
+                    CpuFrequency {
+                        current: items("current").iter().filter_map(Json::as_u64).collect(),
+                        governors: items("governors")
+                            .iter()
+                            .filter_map(|g| g.as_str().map(str::to_string))
+                            .collect(),
+                        range: match items("range") {
+                            [min, max] => min.as_u64().zip(max.as_u64()),
+                            _ => None,
+                        },
+                    }
+                }),
+            uptime: num(info, "uptime")?,
+            boot_time: num(info, "boot_time").unwrap_or(0),
+            sessions: info
+                .get("sessions")
+                .and_then(Json::as_array)
+                .unwrap_or_default()
+                .iter()
+                .map(|s| Session {
+                    user: text(s, "user").unwrap_or_default(),
+                    line: text(s, "line").unwrap_or_default(),
+                    host: text(s, "host"),
+                    since: num(s, "since").unwrap_or(0),
+                })
+                .collect(),
+            load_average: match info.get("load_average").and_then(Json::as_array) {
+                Some([one, five, fifteen]) => {
+                    [one, five, fifteen].map(|l| l.as_f64().unwrap_or(0.0))
+                }
+                _ => [0.0; 3],
+            },
+            kernel_long_version: text(info, "kernel_long_version").unwrap_or_default(),
+            long_os_version: text(info, "long_os_version"),
+            edition: text(info, "edition"),
+            os_build: text(info, "os_build"),
+            host_name: text(info, "host_name"),
+            open_files_limit: num(info, "open_files_limit").ok().map(|n| n as usize),
+            product_name: text(info, "product_name"),
+            vendor_name: text(info, "vendor_name"),
+        },
+        battery: non_null("battery").map(|b| BatterySnapshot {
+            percent: float(b, "percent").unwrap_or(0.0),
+            state: match b.get("state").and_then(Json::as_str) {
+                Some("Charging") => ChargeState::Charging,
+                Some("Discharging") => ChargeState::Discharging,
+                Some("Full") => ChargeState::Full,
+                _ => ChargeState::Unknown,
+            },
+            time_remaining: num(b, "time_remaining").ok().map(Duration::from_secs),
+            power: float(b, "power").ok(),
+        }),
+        cgroup: non_null("cgroup").map(|c| CgroupSnapshot {
+            path: text(c, "path").unwrap_or_default(),
+            cpu_usage: float(c, "cpu_usage").ok(),
+            cpu_limit: float(c, "cpu_limit").ok(),
+            memory_used: num(c, "memory_used").unwrap_or(0),
+            memory_limit: num(c, "memory_limit").ok(),
+        }),
+        pressure: non_null("pressure").map(|p| PressureSnapshot {
+            cpu: pressure_line(p, "cpu"),
+            memory: pressure_line(p, "memory"),
+            io: pressure_line(p, "io"),
+        }),
+        numa: non_null("numa")
+            .and_then(Json::as_array)
+            .map(|nodes| NumaSnapshot {
+                nodes: nodes
+                    .iter()
+                    .map(|node| NumaNode {
+                        id: num(node, "id").unwrap_or(0) as usize,
+                        cpus: text(node, "cpus").unwrap_or_default(),
+                        total: num(node, "total").unwrap_or(0),
+                        free: num(node, "free").unwrap_or(0),
+                    })
+                    .collect(),
+            }),
+        shm: non_null("shm").map(|s| ShmSnapshot {
+            mounts: s
+                .get("mounts")
+                .and_then(Json::as_array)
+                .unwrap_or_default()
+                .iter()
+                .map(|m| TmpfsMount {
+                    path: text(m, "path").unwrap_or_default(),
+                    used: num(m, "used").unwrap_or(0),
+                    total: num(m, "total").unwrap_or(0),
+                })
+                .collect(),
+            sysv: s
+                .get("sysv")
+                .filter(|v| **v != Json::Null)
+                .and_then(|v| Some((num(v, "segments").ok()? as usize, num(v, "bytes").ok()?))),
+        }),
+        compressed_swap: non_null("compressed_swap").map(|c| CompressedSwap {
+            zram: c
+                .get("zram")
+                .and_then(Json::as_array)
+                .unwrap_or_default()
+                .iter()
+                .map(|d| ZramDevice {
+                    name: text(d, "name").unwrap_or_default(),
+                    algorithm: text(d, "algorithm"),
+                    disksize: num(d, "disksize").unwrap_or(0),
+                    original: num(d, "original").unwrap_or(0),
+                    compressed: num(d, "compressed").unwrap_or(0),
+                    used: num(d, "used").unwrap_or(0),
+                    same_pages: num(d, "same_pages").unwrap_or(0),
+                })
+                .collect(),
+            zswap: c.get("zswap").filter(|z| **z != Json::Null).map(|z| Zswap {
+                compressor: text(z, "compressor"),
+                pool: num(z, "pool").unwrap_or(0),
+                stored: num(z, "stored").unwrap_or(0),
+            }),
+            page_size: num(c, "page_size").unwrap_or(4096),
+        }),
+        soc: non_null("soc").map(|s| SocSnapshot {
+            model: text(s, "model").unwrap_or_default(),
+            temperature: float(s, "temperature").ok(),
+            core_volts: float(s, "core_volts").ok(),
+            throttled: num(s, "throttled").ok().map(|bits| bits as u32),
+        }),
+        sensors: non_null("sensors").map(|s| {
+            let readings = |key: &str| {
+                s.get(key)
+                    .and_then(Json::as_array)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|r| Reading {
+                        name: text(r, "name").unwrap_or_default(),
+                        value: float(r, "value").unwrap_or(0.0),
+                    })
+                    .collect()
+            };
+            SensorsSnapshot {
+                fans: readings("fans"),
+                power: readings("power"),
+                package_watts: float(s, "package_watts").ok(),
+            }
+        }),
+        ..Snapshot::default()
+    })
+}
+
+fn decode_process(value: &Json) -> Result<ProcessInfo, String> {
+    let missing = |key: &str| format!("process: missing `{key}`");
+    let num = |key: &str| {
+        value
+            .get(key)
+            .and_then(Json::as_u64)
+            .ok_or_else(|| missing(key))
+    };
+    let text = |key: &str| {
+        value
+            .get(key)
+            .and_then(Json::as_str)
+            .map(Arc::<str>::from)
+            .ok_or_else(|| missing(key))
+    };
+    Ok(ProcessInfo {
+        pid: Pid::from_u32(num("pid")? as u32),
+        parent: num("parent").ok().map(|pid| Pid::from_u32(pid as u32)),
+        name: text("name")?,
+        cmd: text("cmd")?,
+        user: text("user")?,
+        container: text("container").ok(),
+        status: status_from_name(value.get("status").and_then(Json::as_str).unwrap_or("")),
+        threads: num("threads")? as usize,
+        is_thread: value
+            .get("is_thread")
+            .and_then(Json::as_bool)
+            .unwrap_or(false),
+        start_time: num("start_time")?,
+        cpu_usage: value
+            .get("cpu_usage")
+            .and_then(Json::as_f64)
+            .ok_or_else(|| missing("cpu_usage"))? as f32,
+        cpu_time: num("cpu_time").unwrap_or(0),
+        memory: num("memory")?,
+        virtual_memory: num("virtual_memory").unwrap_or(0),
+        shared: num("shared").ok(),
+        swap: num("swap").ok(),
+        numa_pages: value
+            .get("numa_pages")
+            .and_then(Json::as_array)
+            .map(|pages| pages.iter().filter_map(Json::as_u64).collect()),
+        open_files: num("open_files").ok().map(|n| n as usize),
+        wait_channel: text("wait_channel").ok(),
+        minor_faults: num("minor_faults").ok(),
+        major_faults: num("major_faults").ok(),
+        net_received: num("net_received").ok(),
+        net_sent: num("net_sent").ok(),
+        cpu_history: value
+            .get("cpu_history")
+            .and_then(Json::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|v| Some(v.as_f64()? as f32))
+            .collect(),
+        disk_read: num("disk_read").unwrap_or(0),
+        disk_written: num("disk_written").unwrap_or(0),
+    })
+}
+
+const STATUSES: [(&str, ProcessStatus); 12] = [
+    ("idle", ProcessStatus::Idle),
+    ("run", ProcessStatus::Run),
+    ("sleep", ProcessStatus::Sleep),
+    ("stop", ProcessStatus::Stop),
+    ("zombie", ProcessStatus::Zombie),
+    ("tracing", ProcessStatus::Tracing),
+    ("dead", ProcessStatus::Dead),
+    ("wakekill", ProcessStatus::Wakekill),
+    ("waking", ProcessStatus::Waking),
+    ("parked", ProcessStatus::Parked),
+    ("lock_blocked", ProcessStatus::LockBlocked),
+    ("disk_sleep", ProcessStatus::UninterruptibleDiskSleep),
+];
+
+fn status_name(status: ProcessStatus) -> &'static str {
+    STATUSES
+        .iter()
+        .find(|(_, s)| *s == status)
+        .map_or("unknown", |(name, _)| name)
+}
+
+fn status_from_name(name: &str) -> ProcessStatus {
+    STATUSES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map_or(ProcessStatus::Unknown(0), |(_, s)| *s)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    use sysinfo::Pid;
+
+    use super::{connect, encode, write_frame};
+    use crate::sampler::{Collector, Update};
+    use crate::source::MockSource;
+
+    #[test]
+    fn connected_ui_hears_about_started_and_exited_processes() {
+        let mut collector = Collector::with_source(MockSource::default());
+        collector.collect_all();
+        let first = collector.snapshot().clone();
+        let mut second = first.clone();
+        let exited = second.processes.remove(0).pid;
+        let mut started = second.processes[0].clone();
+        started.pid = Pid::from_u32(99_999);
+        second.processes.push(started);
+        second.process_generation += 1;
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("address").to_string();
+        let agent = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            for snapshot in [&first, &second] {
+                let frame = encode(snapshot).to_string_compact();
+                write_frame(&mut stream, frame.as_bytes()).expect("frame");
+            }
+            // 読み終えるまで切らない
+            std::thread::sleep(Duration::from_millis(500));
+        });
+        let handle = connect(&addr).expect("connect");
+        let mut changes = handle.updates.iter().filter_map(|update| match update {
+            Update::Snapshot(_, changes) => Some(changes),
+            _ => None,
+        });
+        let first = changes.next().expect("first snapshot");
+        assert!(first.added.is_empty() && first.exited.is_empty());
+        let second = changes.next().expect("second snapshot");
+        assert_eq!(second.added, vec![Pid::from_u32(99_999)]);
+        assert_eq!(
+            second.exited.iter().map(|p| p.pid).collect::<Vec<_>>(),
+            vec![exited]
+        );
+        agent.join().expect("agent");
+    }
+}
@@ -0,0 +1,121 @@
+// Raspberry Pi などの ARM ボードの SoC の温度・電圧・スロットリング（vcgencmd と sysfs を読む）
+//
+// デバイスツリーにモデル名があるボードなら温度は出す。電圧とスロットリングは
+// Raspberry Pi のファームウェアにしか無いので、ほかのボードでは None になる。
+use std::fs;
+use std::process::Command;
+
+#[derive(Debug, Clone)]
+pub struct SocSnapshot {
+    /// デバイスツリーのモデル名（"Raspberry Pi 4 Model B Rev 1.4" など）
+    pub model: String,
+    /// SoC の温度（℃）
+    pub temperature: Option<f64>,
+    /// コア電圧（V）
+    pub core_volts: Option<f64>,
+    /// get_throttled のビット列（読めなければ None）
+    pub throttled: Option<u32>,
+}
+
+// get_throttled の各ビット。下位は現在の状態、上位は起動してから起きたかどうか
+pub const UNDER_VOLTAGE: u32 = 1 << 0;
+pub const FREQUENCY_CAPPED: u32 = 1 << 1;
+pub const THROTTLED: u32 = 1 << 2;
+pub const SOFT_TEMP_LIMIT: u32 = 1 << 3;
+/// 現在のビットをこれだけずらすと「起きたことがある」ビットになる
+pub const OCCURRED_SHIFT: u32 = 16;
+
+impl SocSnapshot {
+    /// 現在の状態
+    pub fn now(&self, flag: u32) -> bool {
+        self.throttled.is_some_and(|bits| bits & flag != 0)
+    }
+
+    /// 起動してから一度でも起きたか
+    pub fn occurred(&self, flag: u32) -> bool {
+        self.throttled
+            .is_some_and(|bits| bits & (flag << OCCURRED_SHIFT) != 0)
+    }
+}
+
+/// デバイスツリーのあるボードでなければ None
+pub fn collect() -> Option<SocSnapshot> {
+    // モデル名は NUL で終わる
+    let model = fs::read_to_string("/proc/device-tree/model").ok()?;
+    let model = model.trim_end_matches('\0').trim().to_string();
+    if model.is_empty() {
+        return None;
+    }
+    // 新しいカーネルは sysfs にも出すので、vcgencmd が無くても読めることがある
+    let throttled = vcgencmd(&["get_throttled"], "throttled=")
+        .or_else(|| {
+            fs::read_to_string("/sys/devices/platform/soc/soc:firmware/get_throttled")
+                .ok()
+                .map(|s| s.trim().to_string())
+        })
+        .and_then(|s| u32::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+        .or_else(under_voltage_alarm);
+    let core_volts = vcgencmd(&["measure_volts", "core"], "volt=")
+        .and_then(|s| s.trim_end_matches('V').parse::<f64>().ok());
+    Some(SocSnapshot {
+        model,
+        temperature: temperature(),
+        core_volts,
+        throttled,
+    })
+}
+
+// CPU か SoC の温度のゾーン（cpu-thermal、soc-thermal など）。無ければ最初のゾーン
+fn temperature() -> Option<f64> {
+    let mut zones: Vec<_> = fs::read_dir("/sys/class/thermal")
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("thermal_zone"))
+        })
+        .collect();
+    zones.sort();
+    let zone = zones
+        .iter()
+        .find(|zone| {
+            fs::read_to_string(zone.join("type"))
+                .is_ok_and(|kind| kind.contains("cpu") || kind.contains("soc"))
+        })
+        .or(zones.first())?;
+    fs::read_to_string(zone.join("temp"))
+        .ok()
+        .and_then(|s| s.trim().parse::<f64>().ok())
+        .map(|millidegrees| millidegrees / 1000.0)
+}
+
+// get_throttled が読めないカーネルでも、rpi_volt の hwmon から電圧低下だけはわかる
+fn under_voltage_alarm() -> Option<u32> {
+    fs::read_dir("/sys/class/hwmon")
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|hwmon| {
+            fs::read_to_string(hwmon.join("name")).is_ok_and(|name| name.trim() == "rpi_volt")
+        })
+        .and_then(|hwmon| fs::read_to_string(hwmon.join("in0_lcrit_alarm")).ok())
+        .map(|alarm| {
+            if alarm.trim() == "1" {
+                UNDER_VOLTAGE
+            } else {
+                0
+            }
+        })
+}
+
+// "key=value" 形式の出力から値を取り出す
+fn vcgencmd(args: &[&str], prefix: &str) -> Option<String> {
+    let output = Command::new("vcgencmd").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    text.trim().strip_prefix(prefix).map(str::to_string)
+}
@@ -0,0 +1,1033 @@
+// バックグラウンドでシステム情報を収集するサンプラー
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sysinfo::{MINIMUM_CPU_UPDATE_INTERVAL, Pid, ProcessStatus};
+
+#[cfg(feature = "environ")]
+use crate::actions::ProcessRef;
+use crate::actions::{ActionOutcome, Command};
+#[cfg(feature = "apple")]
+use crate::apple::{self, AppleSnapshot};
+use crate::battery::{self, BatterySnapshot};
+use crate::cgroup::{CgroupReader, CgroupSnapshot};
+use crate::config::RefreshConfig;
+use crate::container::{self, ContainerNames};
+use crate::coretype::{self, ClusterUsage, CoreCluster};
+use crate::cpufreq::CpuFrequency;
+use crate::cpustat::{CpuBreakdown, CpuStatReader, KernelRates};
+use crate::faults::{self, FaultCounter};
+use crate::files;
+#[cfg(feature = "gpu")]
+use crate::gpu::{self, GpuSnapshot};
+#[cfg(feature = "ipmi")]
+use crate::ipmi::{self, IpmiSnapshot};
+use crate::log;
+use crate::meminfo::{self, HugePages, MemoryBreakdown};
+#[cfg(feature = "net")]
+use crate::net::{self, Connection, TcpStateCounter, TcpStates, TrafficCounter};
+use crate::numa::{self, NumaSnapshot};
+use crate::pressure::{self, PressureSnapshot};
+use crate::rpi::{self, SocSnapshot};
+use crate::sensors::{SensorReader, SensorsSnapshot};
+#[cfg(any(feature = "systemd", windows))]
+use crate::services::{Service, ServiceReader};
+use crate::sessions::Session;
+use crate::shm::{self, ShmSnapshot};
+use crate::smaps;
+#[cfg(feature = "smart")]
+use crate::smart::{self, SmartSnapshot};
+use crate::source::{SysinfoSource, SystemSource};
+use crate::wchan;
+use crate::zram::{self, CompressedSwap};
+
+#[derive(Debug, Clone, Default)]
+pub struct CpuSnapshot {
+    /// コアごとの使用率の合計（1 コア = 100%）
+    pub usage_sum: f32,
+    pub count: usize,
+    /// user / system / iowait などの割合。読めなければ None（Linux 以外と初回）
+    pub breakdown: Option<CpuBreakdown>,
+    /// コアごとの内訳（CPU 番号と割合）。読めなければ空
+    pub core_breakdown: Vec<(usize, CpuBreakdown)>,
+    /// コンテキストスイッチ・割り込み・fork の毎秒の回数。読めなければ None（Linux 以外と初回）
+    pub kernel: Option<KernelRates>,
+    /// ハイブリッド CPU のコアの種類ごとの使用率。種類が 1 つなら空
+    pub clusters: Vec<ClusterUsage>,
+    /// コアごとの使用率（CPU 番号の順、1 コア = 100%）
+    pub usages: Vec<f32>,
+}
+
+impl CpuSnapshot {
+    /// すべてのコアを合わせて 100% とした使用率
+    pub fn normalized(&self) -> f32 {
+        self.usage_sum / self.count.max(1) as f32
+    }
+}
+
+/// 単位はバイト
+#[derive(Debug, Clone, Default)]
+pub struct MemorySnapshot {
+    pub total: u64,
+    pub used: u64,
+    pub total_swap: u64,
+    pub used_swap: u64,
+    /// バッファ・キャッシュ・available。読めなければ None（Linux 以外）
+    pub breakdown: Option<MemoryBreakdown>,
+    /// ヒュージページとオーバーコミット。読めなければ None（Linux 以外）
+    pub huge_pages: Option<HugePages>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessInfo {
+    pub pid: Pid,
+    pub parent: Option<Pid>,
+    // 毎回変わることは少ないので、収集間で共有する
+    pub name: Arc<str>,
+    pub cmd: Arc<str>,
+    pub user: Arc<str>,
+    /// コンテナ内のプロセスならコンテナ名（分からなければ短い ID）
+    pub container: Option<Arc<str>>,
+    pub status: ProcessStatus,
+    /// スレッド数（分からなければ 0）
+    pub threads: usize,
+    /// 他のプロセスのスレッドとして列挙されたものか（parent が持ち主）
+    pub is_thread: bool,
+    /// 起動時刻（UNIX 時間の秒）
+    pub start_time: u64,
+    pub cpu_usage: f32,
+    /// 起動してから使った CPU 時間（ミリ秒）
+    pub cpu_time: u64,
+    /// 常駐メモリ（RSS、バイト）
+    pub memory: u64,
+    /// 仮想メモリ（VIRT、バイト）
+    pub virtual_memory: u64,
+    /// 共有している常駐メモリ。内訳を読んでいなければ None
+    pub shared: Option<u64>,
+    /// スワップに出た量。内訳を読んでいなければ None
+    pub swap: Option<u64>,
+    /// NUMA ノードごとのページ数（ノード番号の位置）。Node 列を出していなければ None
+    pub numa_pages: Option<Arc<[u64]>>,
+    /// 開いているファイルの数。FDs 列を出していなければ None
+    pub open_files: Option<usize>,
+    /// 眠っていれば待っているカーネルの関数。WChan 列を出していないか、動いていれば None
+    pub wait_channel: Option<Arc<str>>,
+    /// 1 秒あたりのマイナー / メジャーフォールト。MinFlt / MajFlt 列を出していないか、初回なら None
+    pub minor_faults: Option<u64>,
+    pub major_faults: Option<u64>,
+    /// TCP の受信量と送信量（バイト/秒）。RX / TX 列を出していないか、初回なら None
+    pub net_received: Option<u64>,
+    pub net_sent: Option<u64>,
+    /// 最近の収集での CPU 使用率（古い順、CPU_HISTORY 個まで）
+    pub cpu_history: Arc<[f32]>,
+    /// 前回の収集から読み書きした量（バイト。FreeBSD と OpenBSD ではブロックの数）
+    pub disk_read: u64,
+    pub disk_written: u64,
+}
+
+impl ProcessInfo {
+    /// Linux のカーネルスレッド（kthreadd とその子で、コマンドラインが空のもの）
+    pub fn is_kernel_thread(&self) -> bool {
+        let kthreadd = Pid::from_u32(2);
+        self.cmd.is_empty() && (self.pid == kthreadd || self.parent == Some(kthreadd))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SystemInfo {
+    pub cpu_count: usize,
+    pub cpu_arch: String,
+    pub brand: String,
+    pub frequency: CpuFrequency,
+    pub uptime: u64,
+    /// 起動した時刻（UNIX 時間の秒）
+    pub boot_time: u64,
+    /// ログイン中のセッション
+    pub sessions: Vec<Session>,
+    /// 1 分、5 分、15 分の平均
+    pub load_average: [f64; 3],
+    pub kernel_long_version: String,
+    pub long_os_version: Option<String>,
+    /// Windows のエディション（Professional など）
+    pub edition: Option<String>,
+    /// Windows の機能更新の版とビルド番号
+    pub os_build: Option<String>,
+    pub host_name: Option<String>,
+    pub open_files_limit: Option<usize>,
+    pub product_name: Option<String>,
+    pub vendor_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    /// CPU 使用率が 2 回目の計測を経て意味のある値になったか
+    pub cpu_primed: bool,
+    pub cpu: CpuSnapshot,
+    pub memory: MemorySnapshot,
+    pub processes: Vec<ProcessInfo>,
+    /// プロセス一覧を収集するたびに増える番号
+    pub process_generation: u64,
+    pub info: SystemInfo,
+    /// バッテリーが無ければ None
+    pub battery: Option<BatterySnapshot>,
+    /// cgroup に上限が無ければ None
+    pub cgroup: Option<CgroupSnapshot>,
+    /// PSI が使えなければ None
+    pub pressure: Option<PressureSnapshot>,
+    /// NUMA ノードが 1 つなら None
+    pub numa: Option<NumaSnapshot>,
+    /// tmpfs が無ければ None
+    pub shm: Option<ShmSnapshot>,
+    /// zram も zswap も使っていなければ None
+    pub compressed_swap: Option<CompressedSwap>,
+    /// Raspberry Pi でなければ None
+    pub soc: Option<SocSnapshot>,
+    /// ファンも電力も読めなければ None
+    pub sensors: Option<SensorsSnapshot>,
+    /// GPU が見つからなければ None
+    #[cfg(feature = "gpu")]
+    pub gpu: Option<GpuSnapshot>,
+    /// BMC が無ければ None
+    #[cfg(feature = "ipmi")]
+    pub ipmi: Option<IpmiSnapshot>,
+    /// smartctl が無いか、読めるディスクが無ければ None
+    #[cfg(feature = "smart")]
+    pub smart: Option<SmartSnapshot>,
+    /// Apple Silicon でなければ None
+    #[cfg(feature = "apple")]
+    pub apple: Option<AppleSnapshot>,
+    #[cfg(feature = "net")]
+    pub connections: Vec<Connection>,
+    /// TCP の状態ごとの数と増減。ソケットが読めなければ None
+    #[cfg(feature = "net")]
+    pub tcp_states: Option<TcpStates>,
+    /// systemd が無ければ空
+    #[cfg(any(feature = "systemd", windows))]
+    pub services: Vec<Service>,
+    pub collect_times: CollectTimes,
+    /// rust-top 自身の使用量（リモートのスナップショットでは None）
+    pub own_usage: Option<OwnUsage>,
+}
+
+impl Snapshot {
+    /// 前に送ったスナップショットと比べた、新しく現れたプロセスと終了したプロセス
+    pub(crate) fn changes_since(&self, previous: &Snapshot) -> ProcessChanges {
+        let before: HashSet<Pid> = previous.processes.iter().map(|p| p.pid).collect();
+        let now: HashSet<Pid> = self.processes.iter().map(|p| p.pid).collect();
+        ProcessChanges {
+            added: self
+                .processes
+                .iter()
+                .map(|p| p.pid)
+                .filter(|pid| !before.contains(pid))
+                .collect(),
+            exited: previous
+                .processes
+                .iter()
+                .filter(|p| !now.contains(&p.pid))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// 前回送ったときからのプロセスの出入り
+#[derive(Debug, Default)]
+pub struct ProcessChanges {
+    pub added: Vec<Pid>,
+    /// 終了したプロセスの最後の値
+    pub exited: Vec<ProcessInfo>,
+}
+
+/// rust-top のプロセスの CPU 使用率（1 コア = 100%）とメモリ（バイト）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OwnUsage {
+    pub cpu_usage: f32,
+    pub memory: u64,
+}
+
+/// ソースごとの直近の収集にかかった時間
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CollectTimes {
+    pub cpu: Duration,
+    pub memory: Duration,
+    pub processes: Duration,
+    pub info: Duration,
+    pub battery: Duration,
+    pub cgroup: Duration,
+    pub pressure: Duration,
+    pub soc: Duration,
+    pub sensors: Duration,
+    #[cfg(feature = "gpu")]
+    pub gpu: Duration,
+    #[cfg(feature = "ipmi")]
+    pub ipmi: Duration,
+    #[cfg(feature = "smart")]
+    pub smart: Duration,
+    #[cfg(feature = "apple")]
+    pub apple: Duration,
+    #[cfg(feature = "net")]
+    pub connections: Duration,
+    #[cfg(any(feature = "systemd", windows))]
+    pub services: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Source {
+    Cpu,
+    Memory,
+    Processes,
+    Info,
+    Battery,
+    Cgroup,
+    Pressure,
+    Soc,
+    Sensors,
+    #[cfg(feature = "gpu")]
+    Gpu,
+    #[cfg(feature = "ipmi")]
+    Ipmi,
+    #[cfg(feature = "smart")]
+    Smart,
+    #[cfg(feature = "apple")]
+    Apple,
+    #[cfg(feature = "net")]
+    Connections,
+    #[cfg(any(feature = "systemd", windows))]
+    Services,
+}
+
+const SOURCES: &[Source] = &[
+    Source::Cpu,
+    Source::Memory,
+    Source::Processes,
+    Source::Info,
+    Source::Battery,
+    Source::Cgroup,
+    Source::Pressure,
+    Source::Soc,
+    Source::Sensors,
+    #[cfg(feature = "gpu")]
+    Source::Gpu,
+    #[cfg(feature = "ipmi")]
+    Source::Ipmi,
+    #[cfg(feature = "smart")]
+    Source::Smart,
+    #[cfg(feature = "apple")]
+    Source::Apple,
+    #[cfg(feature = "net")]
+    Source::Connections,
+    #[cfg(any(feature = "systemd", windows))]
+    Source::Services,
+];
+
+/// プロセスごとに覚えておく CPU 使用率の数
+pub const CPU_HISTORY: usize = 30;
+
+/// プロセスごとに前回の名前とコマンドラインを覚えておき、変わっていなければ使い回す
+/// CPU 使用率の推移もここに溜める
+struct ProcessStrings {
+    start_time: u64,
+    name: Arc<str>,
+    cmd: Arc<str>,
+    container: Option<Arc<str>>,
+    cpu_history: VecDeque<f32>,
+    /// 最後に現れた収集の番号
+    pass: u64,
+}
+
+/// SystemSource を持ち、ソースごとに収集してスナップショットを組み立てる
+pub struct Collector<S = SysinfoSource> {
+    source: S,
+    strings: HashMap<Pid, ProcessStrings>,
+    containers: ContainerNames,
+    cgroup: CgroupReader,
+    cpustat: CpuStatReader,
+    sensors: SensorReader,
+    /// コアの種類。最初の CPU の収集で調べる
+    core_types: Option<Vec<CoreCluster>>,
+    #[cfg(feature = "net")]
+    tcp_states: TcpStateCounter,
+    #[cfg(any(feature = "systemd", windows))]
+    services: ServiceReader,
+    /// プロセスごとに smaps_rollup を読む
+    memory_detail: bool,
+    /// プロセスごとに numa_maps を読む
+    numa_nodes: bool,
+    /// プロセスごとに /proc/<pid>/fd を数える
+    fd_counts: bool,
+    /// 眠っているプロセスごとに /proc/<pid>/wchan を読む
+    wait_channels: bool,
+    /// プロセスごとに stat のフォールトの数と status のスワップの量を読む
+    page_faults: bool,
+    faults: FaultCounter,
+    /// プロセスごとの送受信量を ss から読む
+    net_traffic: bool,
+    #[cfg(feature = "net")]
+    traffic: TrafficCounter,
+    /// プロセスの収集の回数（終了したプロセスの後始末に使う）
+    pass: u64,
+    /// 送った後に書き換えるときだけ複製される
+    snapshot: Arc<Snapshot>,
+}
+
+struct Sampler<S> {
+    collector: Collector<S>,
+    refresh: RefreshConfig,
+    next_due: [Instant; SOURCES.len()],
+    updates: Sender<Update>,
+    requests: Receiver<Request>,
+    /// 画面に出ていないので収集を止めているソース
+    paused: Paused,
+    /// 最後に送ったスナップショット（最初に送るまでは None）
+    sent: Option<Arc<Snapshot>>,
+}
+
+/// サンプラーから UI への通知
+///
+/// プロセス一覧の差分は送らない。差分を当てるには受け取った側が自分の複製を書き換えることになり、
+/// Arc を他の利用者と共有していると結局まるごと複製するため。出入りだけを ProcessChanges で添える。
+pub enum Update {
+    /// 収集したスナップショットと前回からのプロセスの出入り。受け取った側は複製せずに共有できる
+    Snapshot(Arc<Snapshot>, ProcessChanges),
+    Action(ActionOutcome),
+    /// 更新間隔を変えた結果
+    Interval(Result<Duration, String>),
+    /// 頼まれたプロセスの環境変数
+    #[cfg(feature = "environ")]
+    Environ(ProcessRef, Result<Vec<String>, String>),
+}
+
+/// UI からサンプラーへの依頼
+pub enum Request {
+    Action(Command),
+    /// 表示していないパネルのソースを止める
+    Pause(Paused),
+    /// CPU・メモリ・プロセス・情報パネルの更新間隔を変える
+    Interval(Duration),
+    /// 設定ファイルを読み直したときに、すべてのソースの更新間隔を置き換える
+    Refresh(RefreshConfig),
+    /// プロセスのメモリの内訳（共有・スワップ）を読むかどうか
+    MemoryDetail(bool),
+    /// プロセスの NUMA ノードごとのページ数を読むかどうか
+    NumaNodes(bool),
+    /// プロセスの開いているファイルを数えるかどうか
+    FdCounts(bool),
+    /// 眠っているプロセスが待っている関数を読むかどうか
+    WaitChannels(bool),
+    /// プロセスのページフォールトの速さとスワップの量を読むかどうか
+    PageFaults(bool),
+    NetTraffic(bool),
+    #[cfg(feature = "environ")]
+    Environ(ProcessRef),
+}
+
+/// 収集を止めるソース。再開したときはすぐに読み直す
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Paused {
+    pub cpu: bool,
+    pub memory: bool,
+    pub info: bool,
+}
+
+impl Paused {
+    fn contains(self, source: Source) -> bool {
+        match source {
+            Source::Cpu => self.cpu,
+            Source::Memory => self.memory,
+            Source::Info => self.info,
+            _ => false,
+        }
+    }
+}
+
+/// UI 側が持つサンプラーとの通信路
+pub struct SamplerHandle {
+    pub updates: Receiver<Update>,
+    pub requests: Sender<Request>,
+}
+
+/// サンプラースレッドを起動する
+pub fn spawn(refresh: RefreshConfig) -> SamplerHandle {
+    spawn_with(SysinfoSource::new, refresh)
+}
+
+/// 窓口を指定して起動する。窓口はサンプラースレッドの中で作る
+pub fn spawn_with<S: SystemSource>(
+    source: impl FnOnce() -> S + Send + 'static,
+    refresh: RefreshConfig,
+) -> SamplerHandle {
+    let (update_tx, update_rx) = mpsc::channel();
+    let (request_tx, request_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let now = Instant::now();
+        let sampler = Sampler {
+            collector: Collector::with_source(source()),
+            refresh,
+            next_due: [now; SOURCES.len()],
+            updates: update_tx,
+            requests: request_rx,
+            paused: Paused::default(),
+            sent: None,
+        };
+        sampler.run();
+    });
+    SamplerHandle {
+        updates: update_rx,
+        requests: request_tx,
+    }
+}
+
+impl<S: SystemSource> Sampler<S> {
+    fn run(mut self) {
+        if !self.prime() {
+            return;
+        }
+        loop {
+            let now = Instant::now();
+            for (i, source) in SOURCES.iter().enumerate() {
+                if self.next_due[i] <= now {
+                    if !self.paused.contains(*source) {
+                        self.collector.collect(*source);
+                    }
+                    self.next_due[i] = now + self.interval(*source);
+                }
+            }
+
+            if !self.send_snapshot() {
+                return;
+            }
+
+            let next = self.next_due.iter().min().copied().unwrap_or(now);
+            if !self.wait_until(next) {
+                return;
+            }
+        }
+    }
+
+    // UI 側がチャネルを閉じたら false
+    fn send_snapshot(&mut self) -> bool {
+        let snapshot = Arc::clone(&self.collector.snapshot);
+        let changes = match &self.sent {
+            Some(sent) if sent.process_generation != snapshot.process_generation => {
+                snapshot.changes_since(sent)
+            }
+            _ => ProcessChanges::default(),
+        };
+        self.sent = Some(Arc::clone(&snapshot));
+        self.updates
+            .send(Update::Snapshot(snapshot, changes))
+            .is_ok()
+    }
+
+    // 次の収集時刻まで UI からの依頼を処理しながら待つ
+    fn wait_until(&mut self, deadline: Instant) -> bool {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.requests.recv_timeout(timeout) {
+            Ok(Request::Pause(paused)) => {
+                let now = Instant::now();
+                for (i, source) in SOURCES.iter().enumerate() {
+                    if self.paused.contains(*source) && !paused.contains(*source) {
+                        self.next_due[i] = now;
+                    }
+                }
+                self.paused = paused;
+                log::debug(format!("paused sources: {paused:?}"));
+                true
+            }
+            Ok(Request::Interval(interval)) => {
+                let refresh = &mut self.refresh;
+                for out in [
+                    &mut refresh.cpu,
+                    &mut refresh.memory,
+                    &mut refresh.processes,
+                    &mut refresh.info,
+                ] {
+                    *out = interval;
+                }
+                // 長くしたときも次の収集を待たせすぎないよう、予定を引き直す
+                let now = Instant::now();
+                for source in [Source::Cpu, Source::Memory, Source::Processes, Source::Info] {
+                    self.next_due[source as usize] = now + interval;
+                }
+                log::debug(format!("interval changed to {interval:?}"));
+                self.updates.send(Update::Interval(Ok(interval))).is_ok()
+            }
+            Ok(Request::Refresh(refresh)) => {
+                log::debug(format!("refresh intervals changed to {refresh:?}"));
+                self.refresh = refresh;
+                // 短くしたソースは新しい間隔で、長くしたソースは今の予定のまま次を読む
+                let now = Instant::now();
+                for (i, source) in SOURCES.iter().enumerate() {
+                    self.next_due[i] = self.next_due[i].min(now + self.interval(*source));
+                }
+                true
+            }
+            Ok(Request::MemoryDetail(enabled)) => {
+                self.collector.set_memory_detail(enabled);
+                if enabled {
+                    self.next_due[Source::Processes as usize] = Instant::now();
+                }
+                true
+            }
+            Ok(Request::NumaNodes(enabled)) => {
+                self.collector.set_numa_nodes(enabled);
+                if enabled {
+                    self.next_due[Source::Processes as usize] = Instant::now();
+                }
+                true
+            }
+            Ok(Request::FdCounts(enabled)) => {
+                self.collector.set_fd_counts(enabled);
+                if enabled {
+                    self.next_due[Source::Processes as usize] = Instant::now();
+                }
+                true
+            }
+            Ok(Request::WaitChannels(enabled)) => {
+                self.collector.set_wait_channels(enabled);
+                if enabled {
+                    self.next_due[Source::Processes as usize] = Instant::now();
+                }
+                true
+            }
+            Ok(Request::PageFaults(enabled)) => {
+                self.collector.set_page_faults(enabled);
+                if enabled {
+                    self.next_due[Source::Processes as usize] = Instant::now();
+                }
+                true
+            }
+            Ok(Request::NetTraffic(enabled)) => {
+                self.collector.set_net_traffic(enabled);
+                if enabled {
+                    self.next_due[Source::Processes as usize] = Instant::now();
+                }
+                true
+            }
+            #[cfg(feature = "environ")]
+            Ok(Request::Environ(target)) => {
+                let result = self.collector.source.environ(target);
+                self.updates.send(Update::Environ(target, result)).is_ok()
+            }
+            Ok(Request::Action(command)) => {
+                let outcome = self.collector.source.execute(command);
+                if outcome.result.is_ok() {
+                    // 操作の結果をすぐ表に反映させる
+                    self.next_due[Source::Processes as usize] = Instant::now();
+                }
+                self.updates.send(Update::Action(outcome)).is_ok()
+            }
+            Err(RecvTimeoutError::Timeout) => true,
+            Err(RecvTimeoutError::Disconnected) => false,
+        }
+    }
+
+    // 起動直後: 軽いソースから順に送り、短い間隔で CPU を 2 回計測して使用率を確定させる
+    fn prime(&mut self) -> bool {
+        let collector = &mut self.collector;
+        collector.collect(Source::Memory);
+        collector.collect(Source::Cpu);
+        collector.collect(Source::Info);
+        collector.collect(Source::Battery);
+        collector.collect(Source::Cgroup);
+        collector.collect(Source::Pressure);
+        collector.collect(Source::Soc);
+        collector.collect(Source::Sensors);
+        if !self.send_snapshot() {
+            return false;
+        }
+        self.collector.collect(Source::Processes);
+        #[cfg(feature = "gpu")]
+        self.collector.collect(Source::Gpu);
+        #[cfg(feature = "ipmi")]
+        self.collector.collect(Source::Ipmi);
+        #[cfg(feature = "smart")]
+        self.collector.collect(Source::Smart);
+        #[cfg(feature = "net")]
+        self.collector.collect(Source::Connections);
+        #[cfg(any(feature = "systemd", windows))]
+        self.collector.collect(Source::Services);
+        if !self.send_snapshot() {
+            return false;
+        }
+
+        thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL);
+        let collector = &mut self.collector;
+        collector.collect(Source::Cpu);
+        collector.collect(Source::Processes);
+        Arc::make_mut(&mut collector.snapshot).cpu_primed = true;
+        // コアごとの使用率も確定した値で出す
+        #[cfg(feature = "apple")]
+        collector.collect(Source::Apple);
+
+        let now = Instant::now();
+        for (i, source) in SOURCES.iter().enumerate() {
+            self.next_due[i] = now + self.interval(*source);
+        }
+        self.send_snapshot()
+    }
+
+    fn interval(&self, source: Source) -> Duration {
+        match source {
+            Source::Cpu => self.refresh.cpu,
+            Source::Memory => self.refresh.memory,
+            Source::Processes => self.refresh.processes,
+            Source::Info => self.refresh.info,
+            Source::Battery => self.refresh.battery,
+            Source::Cgroup => self.refresh.cgroup,
+            Source::Pressure => self.refresh.pressure,
+            Source::Soc => self.refresh.soc,
+            Source::Sensors => self.refresh.sensors,
+            #[cfg(feature = "gpu")]
+            Source::Gpu => self.refresh.gpu,
+            #[cfg(feature = "ipmi")]
+            Source::Ipmi => self.refresh.ipmi,
+            #[cfg(feature = "smart")]
+            Source::Smart => self.refresh.smart,
+            #[cfg(feature = "apple")]
+            Source::Apple => self.refresh.apple,
+            #[cfg(feature = "net")]
+            Source::Connections => self.refresh.connections,
+            #[cfg(any(feature = "systemd", windows))]
+            Source::Services => self.refresh.services,
+        }
+    }
+}
+
+impl Collector {
+    pub fn new() -> Self {
+        Self::with_source(SysinfoSource::new())
+    }
+}
+
+impl<S: SystemSource> Collector<S> {
+    pub fn with_source(source: S) -> Self {
+        Self {
+            source,
+            strings: HashMap::new(),
+            containers: ContainerNames::default(),
+            cgroup: CgroupReader::default(),
+            cpustat: CpuStatReader::default(),
+            sensors: SensorReader::default(),
+            core_types: None,
+            #[cfg(feature = "net")]
+            tcp_states: TcpStateCounter::default(),
+            #[cfg(any(feature = "systemd", windows))]
+            services: ServiceReader::default(),
+            memory_detail: false,
+            numa_nodes: false,
+            fd_counts: false,
+            wait_channels: false,
+            page_faults: false,
+            faults: FaultCounter::default(),
+            net_traffic: false,
+            #[cfg(feature = "net")]
+            traffic: TrafficCounter::default(),
+            pass: 0,
+            snapshot: Arc::default(),
+        }
+    }
+
+    pub fn set_memory_detail(&mut self, enabled: bool) {
+        self.memory_detail = enabled;
+    }
+
+    pub fn set_numa_nodes(&mut self, enabled: bool) {
+        self.numa_nodes = enabled;
+    }
+
+    pub fn set_fd_counts(&mut self, enabled: bool) {
+        self.fd_counts = enabled;
+    }
+
+    pub fn set_wait_channels(&mut self, enabled: bool) {
+        self.wait_channels = enabled;
+    }
+
+    pub fn set_page_faults(&mut self, enabled: bool) {
+        self.page_faults = enabled;
+        // 久しぶりに有効にしたときの増分は当てにならない
+        if !enabled {
+            self.faults = FaultCounter::default();
+        }
+    }
+
+    pub fn set_net_traffic(&mut self, enabled: bool) {
+        self.net_traffic = enabled;
+        // 久しぶりに有効にしたときの増分は当てにならない
+        #[cfg(feature = "net")]
+        if !enabled {
+            self.traffic = TrafficCounter::default();
+        }
+    }
+
+    /// すべてのソースを 1 回ずつ収集する
+    pub fn collect_all(&mut self) {
+        for source in SOURCES {
+            self.collect(*source);
+        }
+    }
+
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    fn collect(&mut self, source: Source) {
+        let started = Instant::now();
+        self.collect_source(source);
+        let times = &mut Arc::make_mut(&mut self.snapshot).collect_times;
+        let slot = match source {
+            Source::Cpu => &mut times.cpu,
+            Source::Memory => &mut times.memory,
+            Source::Processes => &mut times.processes,
+            Source::Info => &mut times.info,
+            Source::Battery => &mut times.battery,
+            Source::Cgroup => &mut times.cgroup,
+            Source::Pressure => &mut times.pressure,
+            Source::Soc => &mut times.soc,
+            Source::Sensors => &mut times.sensors,
+            #[cfg(feature = "gpu")]
+            Source::Gpu => &mut times.gpu,
+            #[cfg(feature = "ipmi")]
+            Source::Ipmi => &mut times.ipmi,
+            #[cfg(feature = "smart")]
+            Source::Smart => &mut times.smart,
+            #[cfg(feature = "apple")]
+            Source::Apple => &mut times.apple,
+            #[cfg(feature = "net")]
+            Source::Connections => &mut times.connections,
+            #[cfg(any(feature = "systemd", windows))]
+            Source::Services => &mut times.services,
+        };
+        *slot = started.elapsed();
+    }
+
+    fn collect_source(&mut self, kind: Source) {
+        let source = &mut self.source;
+        // UI がまだ前のスナップショットを持っていれば、ここで 1 回だけ複製する
+        let snapshot = Arc::make_mut(&mut self.snapshot);
+        // 作り物の値を使うときは、この機械の cgroup や /proc を混ぜない
+        let host = source.reads_host();
+        match kind {
+            Source::Cpu => {
+                source.refresh_cpu();
+                let usages = source.cpu_usages();
+                let (breakdown, core_breakdown, kernel) = if host {
+                    self.cpustat.collect()
+                } else {
+                    (None, Vec::new(), None)
+                };
+                let clusters = if host {
+                    let core_types = self.core_types.get_or_insert_with(coretype::detect);
+                    coretype::usage(core_types, &usages)
+                } else {
+                    Vec::new()
+                };
+                snapshot.cpu = CpuSnapshot {
+                    usage_sum: usages.iter().sum::<f32>(),
+                    count: usages.len(),
+                    breakdown,
+                    core_breakdown,
+                    kernel,
+                    clusters,
+                    usages,
+                };
+            }
+            Source::Memory => {
+                let memory = source.memory();
+                let (breakdown, huge_pages) = if host {
+                    meminfo::collect()
+                } else {
+                    (None, None)
+                };
+                snapshot.memory = MemorySnapshot {
+                    total: memory.total,
+                    used: memory.used,
+                    total_swap: memory.total_swap,
+                    used_swap: memory.used_swap,
+                    breakdown,
+                    huge_pages,
+                };
+                snapshot.numa = if host { numa::collect() } else { None };
+                snapshot.shm = if host { shm::collect() } else { None };
+                snapshot.compressed_swap = if host { zram::collect() } else { None };
+            }
+            Source::Processes => {
+                source.refresh_processes();
+                let strings = &mut self.strings;
+                let memory_detail = self.memory_detail && host;
+                let numa_nodes = self.numa_nodes && host;
+                let fd_counts = self.fd_counts && host;
+                let wait_channels = self.wait_channels && host;
+                let page_faults = self.page_faults && host;
+                let faults = &mut self.faults;
+                if page_faults {
+                    faults.begin(Instant::now());
+                }
+                #[cfg(feature = "net")]
+                let traffic = if self.net_traffic && host {
+                    self.traffic.collect(Instant::now())
+                } else {
+                    None
+                };
+                #[cfg(not(feature = "net"))]
+                let traffic: Option<HashMap<Pid, (u64, u64)>> = None;
+                let containers = &mut self.containers;
+                containers.begin_pass();
+                // cgroup は新しいプロセスのときだけ読む
+                let mut container_of = |pid: Pid| {
+                    host.then(|| container::container_id(pid))
+                        .flatten()
+                        .map(|id| containers.label(&id))
+                };
+                self.pass += 1;
+                let pass = self.pass;
+                let mut processes = Vec::with_capacity(snapshot.processes.len());
+                source.for_each_process(&mut |p| {
+                    let cached = strings.entry(p.pid()).or_insert_with(|| ProcessStrings {
+                        start_time: p.start_time(),
+                        name: Arc::from(p.name()),
+                        cmd: p.cmd(),
+                        container: container_of(p.pid()),
+                        cpu_history: VecDeque::with_capacity(CPU_HISTORY),
+                        pass,
+                    });
+                    cached.pass = pass;
+                    // 同じ PID でも別のプロセスになっていれば作り直す
+                    if cached.start_time != p.start_time() {
+                        cached.start_time = p.start_time();
+                        cached.cmd = p.cmd();
+                        cached.container = container_of(p.pid());
+                        cached.cpu_history.clear();
+                    }
+                    if cached.cpu_history.len() == CPU_HISTORY {
+                        cached.cpu_history.pop_front();
+                    }
+                    cached.cpu_history.push_back(p.cpu_usage());
+                    let detail = memory_detail.then(|| smaps::read(p.pid())).flatten();
+                    let name = p.name();
+                    if *cached.name != *name {
+                        cached.name = Arc::from(name);
+                    }
+                    let (disk_read, disk_written) = p.disk_usage();
+                    let fault_rates = page_faults
+                        .then(|| faults.rates(p.pid(), p.start_time()))
+                        .flatten();
+                    // 内訳を読まないときでも、スワップの量は status から軽く読める
+                    let swap = match detail {
+                        Some(detail) => Some(detail.swap),
+                        None => page_faults.then(|| faults::swap(p.pid())).flatten(),
+                    };
+                    processes.push(ProcessInfo {
+                        pid: p.pid(),
+                        parent: p.parent(),
+                        name: cached.name.clone(),
+                        cmd: cached.cmd.clone(),
+                        container: cached.container.clone(),
+                        user: p.user(),
+                        status: p.status(),
+                        threads: p.threads(),
+                        is_thread: p.is_thread(),
+                        start_time: p.start_time(),
+                        cpu_usage: p.cpu_usage(),
+                        cpu_time: p.cpu_time(),
+                        memory: p.memory(),
+                        virtual_memory: p.virtual_memory(),
+                        shared: detail.map(|d| d.shared),
+                        swap,
+                        numa_pages: numa_nodes.then(|| numa::read_process(p.pid())).flatten(),
+                        open_files: fd_counts.then(|| files::count(p.pid())).flatten(),
+                        // 動いているものは何も待っていない
+                        wait_channel: (wait_channels && p.status() != ProcessStatus::Run)
+                            .then(|| wchan::read(p.pid()))
+                            .flatten(),
+                        minor_faults: fault_rates.map(|r| r.minor),
+                        major_faults: fault_rates.map(|r| r.major),
+                        net_received: traffic
+                            .as_ref()
+                            .map(|t| t.get(&p.pid()).map_or(0, |&(received, _)| received)),
+                        net_sent: traffic
+                            .as_ref()
+                            .map(|t| t.get(&p.pid()).map_or(0, |&(_, sent)| sent)),
+                        cpu_history: cached.cpu_history.iter().copied().collect(),
+                        disk_read,
+                        disk_written,
+                    });
+                });
+                // 今回現れなかったプロセスは終了している
+                strings.retain(|_, cached| cached.pass == pass);
+                snapshot.processes = processes;
+                snapshot.own_usage = sysinfo::get_current_pid()
+                    .ok()
+                    .and_then(|pid| source.usage_of(pid));
+                snapshot.process_generation += 1;
+            }
+            Source::Info => snapshot.info = source.info(),
+            _ if !host => {}
+            Source::Battery => snapshot.battery = battery::collect(),
+            Source::Cgroup => snapshot.cgroup = self.cgroup.collect(),
+            Source::Pressure => snapshot.pressure = pressure::collect(),
+            Source::Soc => snapshot.soc = rpi::collect(),
+            Source::Sensors => snapshot.sensors = self.sensors.collect(),
+            #[cfg(feature = "gpu")]
+            Source::Gpu => snapshot.gpu = gpu::collect(),
+            #[cfg(feature = "ipmi")]
+            Source::Ipmi => snapshot.ipmi = ipmi::collect(),
+            #[cfg(feature = "smart")]
+            Source::Smart => snapshot.smart = smart::collect(),
+            #[cfg(feature = "apple")]
+            Source::Apple => snapshot.apple = apple::collect(&source.cpu_usages()),
+            #[cfg(feature = "net")]
+            Source::Connections => {
+                snapshot.connections = net::collect();
+                snapshot.tcp_states = self.tcp_states.count(&snapshot.connections, Instant::now());
+            }
+            #[cfg(any(feature = "systemd", windows))]
+            Source::Services => snapshot.services = self.services.collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MockSource;
+
+    #[test]
+    fn sent_snapshots_are_shared_and_left_alone_by_the_next_collection() {
+        let mut collector = Collector::with_source(MockSource::default());
+        collector.collect_all();
+        let sent = Arc::clone(&collector.snapshot);
+        let generation = sent.process_generation;
+        collector.collect(Source::Processes);
+        assert_eq!(sent.process_generation, generation);
+        assert_eq!(collector.snapshot.process_generation, generation + 1);
+
+        // 誰も持っていなければ複製せずにそのまま書き換える
+        drop(sent);
+        let kept = Arc::as_ptr(&collector.snapshot);
+        collector.collect(Source::Cpu);
+        assert_eq!(Arc::as_ptr(&collector.snapshot), kept);
+    }
+
+    #[test]
+    fn changes_list_new_and_exited_processes() {
+        let mut collector = Collector::with_source(MockSource::default());
+        collector.collect_all();
+        let previous = collector.snapshot().clone();
+        let mut current = previous.clone();
+        let exited = current.processes.remove(0);
+        let mut started = current.processes[0].clone();
+        started.pid = Pid::from_u32(99_999);
+        current.processes.push(started);
+
+        let changes = current.changes_since(&previous);
+        assert_eq!(changes.added, vec![Pid::from_u32(99_999)]);
+        assert_eq!(changes.exited.len(), 1);
+        assert_eq!(changes.exited[0].pid, exited.pid);
+    }
+}
@@ -0,0 +1,98 @@
+// プロセス・接続・コンテナをまとめて検索する（/ キー）
+use std::sync::Arc;
+
+#[cfg(feature = "net")]
+use std::net::SocketAddr;
+
+use sysinfo::Pid;
+
+use crate::pattern::Pattern;
+use crate::sampler::Snapshot;
+
+/// 検索結果から移動する先
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchHit {
+    /// プロセス表の行
+    Process(Pid),
+    /// 接続一覧の行。並びが変わっても追えるようにアドレスの組で持つ
+    #[cfg(feature = "net")]
+    Connection {
+        local: SocketAddr,
+        remote: SocketAddr,
+    },
+    /// コンテナ。そのコンテナで CPU を最も使っているプロセスを選ぶ
+    Container(Arc<str>),
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub hit: SearchHit,
+    /// 「Process」「Connection」「Container」
+    pub kind: &'static str,
+    pub text: String,
+}
+
+/// 検索結果の一覧（ポップアップで表示中）
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub query: String,
+    pub results: Vec<SearchResult>,
+    pub selected: usize,
+}
+
+/// 名前・コマンドライン・PID、接続のアドレスと状態、コンテナ名から探す
+pub fn search(snapshot: &Snapshot, pattern: &Pattern) -> Vec<SearchResult> {
+    let mut results = Vec::new();
+    for p in &snapshot.processes {
+        if pattern.is_match(&p.name)
+            || pattern.is_match(&p.cmd)
+            || pattern.is_match(&p.pid.to_string())
+        {
+            results.push(SearchResult {
+                hit: SearchHit::Process(p.pid),
+                kind: "Process",
+                text: format!("{} {} {}", p.pid, p.name, p.cmd),
+            });
+        }
+    }
+    #[cfg(feature = "net")]
+    for c in &snapshot.connections {
+        let owner = c
+            .pid
+            .and_then(|pid| snapshot.processes.iter().find(|p| p.pid == pid));
+        let text = format!(
+            "{} {} -> {} {}{}",
+            c.protocol.label(),
+            c.local,
+            c.remote,
+            c.state,
+            owner.map_or(String::new(), |p| format!(" ({} {})", p.pid, p.name))
+        );
+        if pattern.is_match(&text) {
+            results.push(SearchResult {
+                hit: SearchHit::Connection {
+                    local: c.local,
+                    remote: c.remote,
+                },
+                kind: "Connection",
+                text,
+            });
+        }
+    }
+    let mut containers: Vec<&Arc<str>> = Vec::new();
+    for name in snapshot
+        .processes
+        .iter()
+        .filter_map(|p| p.container.as_ref())
+    {
+        if !containers.contains(&name) && pattern.is_match(name) {
+            containers.push(name);
+        }
+    }
+    results.extend(containers.into_iter().map(|name| SearchResult {
+        hit: SearchHit::Container(Arc::clone(name)),
+        kind: "Container",
+        text: name.to_string(),
+    }));
+    results
+}
@@ -0,0 +1,177 @@
+// ファンの回転数と消費電力（hwmon と RAPL の sysfs を読む、Linux）
+//
+// 温度を追うときは、ファンがどれだけ回っていて何ワット食っているかも並べて見たい。
+// hwmon の fanN_input（RPM）と powerN_input / powerN_average（µW）を読む。
+// RAPL（/sys/class/powercap/intel-rapl:N、AMD も同じ名前で出る）は積算のエネルギーしか
+// 出さないので、前回の値との差を経過時間で割ってワットにする。最初の収集では出ない。
+// energy_uj は多くのカーネルで root しか読めない。
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// センサー 1 つの名前と値
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reading {
+    pub name: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SensorsSnapshot {
+    /// ファンの回転数（RPM）
+    pub fans: Vec<Reading>,
+    /// 消費電力（W）。RAPL のパッケージが先
+    pub power: Vec<Reading>,
+    /// パッケージ全体の消費電力（W）。RAPL の合計か、無ければ hwmon の電力の合計
+    pub package_watts: Option<f64>,
+}
+
+/// RAPL のエネルギーの前回の値を覚えておく
+#[derive(Debug, Default)]
+pub struct SensorReader {
+    energy: HashMap<PathBuf, (u64, Instant)>,
+}
+
+impl SensorReader {
+    /// ファンも電力も読めなければ None
+    pub fn collect(&mut self) -> Option<SensorsSnapshot> {
+        let (fans, hwmon_power) = hwmon(Path::new("/sys/class/hwmon"));
+        let rapl = self.rapl(Path::new("/sys/class/powercap"), Instant::now());
+        let package_watts = if !rapl.is_empty() {
+            Some(rapl.iter().map(|r| r.value).sum())
+        } else if !hwmon_power.is_empty() {
+            Some(hwmon_power.iter().map(|r| r.value).sum())
+        } else {
+            None
+        };
+        let power: Vec<Reading> = rapl.into_iter().chain(hwmon_power).collect();
+        if fans.is_empty() && power.is_empty() {
+            return None;
+        }
+        Some(SensorsSnapshot {
+            fans,
+            power,
+            package_watts,
+        })
+    }
+
+    // 最上位のゾーン（intel-rapl:0 など）がパッケージ。intel-rapl:0:0 のような下位のゾーンは除く
+    fn rapl(&mut self, root: &Path, now: Instant) -> Vec<Reading> {
+        let mut zones: Vec<PathBuf> = fs::read_dir(root)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_prefix("intel-rapl:"))
+                    .is_some_and(|rest| !rest.contains(':'))
+            })
+            .collect();
+        zones.sort();
+        let mut readings = Vec::new();
+        for zone in zones {
+            let Some(energy) = read_number(&zone.join("energy_uj")) else {
+                continue;
+            };
+            let previous = self.energy.insert(zone.clone(), (energy, now));
+            let Some((before, then)) = previous else {
+                continue;
+            };
+            let range = read_number(&zone.join("max_energy_range_uj")).unwrap_or(u64::MAX);
+            let seconds = now.duration_since(then).as_secs_f64();
+            if let Some(watts) = watts(before, energy, range, seconds) {
+                let name = read_text(&zone.join("name")).unwrap_or_else(|| "package".to_string());
+                readings.push(Reading { name, value: watts });
+            }
+        }
+        readings
+    }
+}
+
+/// 積算のエネルギー（µJ）の差からワットを出す。range で一周したものは足し戻す
+pub fn watts(before: u64, after: u64, range: u64, seconds: f64) -> Option<f64> {
+    if seconds <= 0.0 {
+        return None;
+    }
+    let used = if after >= before {
+        after - before
+    } else {
+        range.checked_sub(before)? + after
+    };
+    Some(used as f64 / 1e6 / seconds)
+}
+
+// hwmonN ごとに fanK_input と powerK_input（無ければ powerK_average）を読む。電力は µW
+fn hwmon(root: &Path) -> (Vec<Reading>, Vec<Reading>) {
+    let mut chips: Vec<PathBuf> = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    chips.sort();
+    let mut fans = Vec::new();
+    let mut power = Vec::new();
+    for chip in chips {
+        let chip_name = read_text(&chip.join("name")).unwrap_or_default();
+        let label = |prefix: &str, index: u32| {
+            read_text(&chip.join(format!("{prefix}{index}_label")))
+                .unwrap_or_else(|| format!("{chip_name} {prefix}{index}"))
+        };
+        for index in indexes(&chip, "fan", "_input") {
+            if let Some(rpm) = read_number(&chip.join(format!("fan{index}_input"))) {
+                fans.push(Reading {
+                    name: label("fan", index),
+                    value: rpm as f64,
+                });
+            }
+        }
+        let mut powers = indexes(&chip, "power", "_input");
+        powers.extend(indexes(&chip, "power", "_average"));
+        powers.sort_unstable();
+        powers.dedup();
+        for index in powers {
+            let microwatts = read_number(&chip.join(format!("power{index}_input")))
+                .or_else(|| read_number(&chip.join(format!("power{index}_average"))));
+            if let Some(microwatts) = microwatts {
+                power.push(Reading {
+                    name: label("power", index),
+                    value: microwatts as f64 / 1e6,
+                });
+            }
+        }
+    }
+    (fans, power)
+}
+
+// fan1_input, fan2_input … の番号
+fn indexes(chip: &Path, prefix: &str, suffix: &str) -> Vec<u32> {
+    let mut indexes: Vec<u32> = fs::read_dir(chip)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            name.to_str()?
+                .strip_prefix(prefix)?
+                .strip_suffix(suffix)?
+                .parse()
+                .ok()
+        })
+        .collect();
+    indexes.sort_unstable();
+    indexes
+}
+
+fn read_text(path: &Path) -> Option<String> {
+    let text = fs::read_to_string(path).ok()?;
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn read_number(path: &Path) -> Option<u64> {
+    read_text(path)?.parse().ok()
+}
@@ -0,0 +1,275 @@
+// サービスの一覧
+//
+// Linux（systemd フィーチャー）では systemctl show の結果と各ユニットの cgroup を読む。
+// D-Bus に直接つなぐ代わりに systemctl を呼ぶ。CPU とメモリは systemd の
+// アカウンティング（CPUUsageNSec / MemoryCurrent）なので、無効なユニットでは分からない。
+// Windows ではサービスコントロールマネージャーに問い合わせる。
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use sysinfo::Pid;
+
+#[derive(Debug, Clone)]
+pub struct Service {
+    /// "ssh.service" など
+    pub name: Arc<str>,
+    /// ActiveState（active / inactive / failed など）。Windows では running / stopped など
+    pub active: String,
+    /// SubState（running / exited / dead など）。Windows では空
+    pub sub: String,
+    pub main_pid: Option<Pid>,
+    /// CPU 使用率（1 コア = 100%）。前回の値が無い初回や Windows では None
+    pub cpu_usage: Option<f32>,
+    /// 単位はバイト
+    pub memory: Option<u64>,
+    /// ユニットの cgroup に属するプロセス（Windows ではサービスのプロセス）
+    pub pids: Vec<Pid>,
+}
+
+impl Service {
+    pub fn is_active(&self) -> bool {
+        self.active == "active" || self.active == "running"
+    }
+}
+
+/// CPU 使用率を出すために、ユニットごとに前回の累計（ns）を覚えておく
+#[derive(Default)]
+pub struct ServiceReader {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    last_cpu: HashMap<Arc<str>, (Instant, u64)>,
+}
+
+impl ServiceReader {
+    /// 動いているものを先に、名前順に並べて返す。systemd が無ければ空
+    #[cfg(target_os = "linux")]
+    pub fn collect(&mut self) -> Vec<Service> {
+        use std::process::Command;
+
+        let Ok(output) = Command::new("systemctl")
+            .args([
+                "show",
+                "--property=Id,ActiveState,SubState,MainPID,ControlGroup,MemoryCurrent,CPUUsageNSec",
+                "*.service",
+            ])
+            .output()
+        else {
+            return Vec::new();
+        };
+        if !output.status.success() {
+            return Vec::new();
+        }
+        let text = String::from_utf8_lossy(&output.stdout);
+        let now = Instant::now();
+        let mut last_cpu = HashMap::new();
+        let mut services: Vec<Service> = text
+            .split("\n\n")
+            .filter_map(|block| {
+                let fields: HashMap<&str, &str> = block
+                    .lines()
+                    .filter_map(|line| line.split_once('='))
+                    .collect();
+                let name: Arc<str> = Arc::from(*fields.get("Id")?);
+                let usage = number(fields.get("CPUUsageNSec"));
+                let cpu_usage = match (usage, self.last_cpu.get(&name)) {
+                    (Some(usage), Some((at, last))) => {
+                        let secs = now.duration_since(*at).as_secs_f64();
+                        (secs > 0.0)
+                            .then(|| (usage.saturating_sub(*last) as f64 / 1e7 / secs) as f32)
+                    }
+                    _ => None,
+                };
+                if let Some(usage) = usage {
+                    last_cpu.insert(Arc::clone(&name), (now, usage));
+                }
+                Some(Service {
+                    name,
+                    active: fields.get("ActiveState")?.to_string(),
+                    sub: fields.get("SubState").unwrap_or(&"").to_string(),
+                    main_pid: number(fields.get("MainPID"))
+                        .filter(|pid| *pid != 0)
+                        .map(|pid| Pid::from_u32(pid as u32)),
+                    cpu_usage,
+                    memory: number(fields.get("MemoryCurrent")),
+                    pids: fields
+                        .get("ControlGroup")
+                        .filter(|path| !path.is_empty())
+                        .map_or_else(Vec::new, |path| cgroup_pids(path)),
+                })
+            })
+            .collect();
+        self.last_cpu = last_cpu;
+        services.sort_by(|a, b| {
+            b.is_active()
+                .cmp(&a.is_active())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        services
+    }
+
+    /// 動いているものを先に、名前順に並べて返す
+    #[cfg(windows)]
+    pub fn collect(&mut self) -> Vec<Service> {
+        let mut services = scm::services();
+        services.sort_by(|a, b| {
+            b.is_active()
+                .cmp(&a.is_active())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        services
+    }
+
+    #[cfg(not(any(target_os = "linux", windows)))]
+    pub fn collect(&mut self) -> Vec<Service> {
+        Vec::new()
+    }
+}
+
+// 未設定は "[not set]"、アカウンティングが無効なら u64::MAX になる
+#[cfg(target_os = "linux")]
+fn number(field: Option<&&str>) -> Option<u64> {
+    field?.parse().ok().filter(|n| *n != u64::MAX)
+}
+
+// 子の cgroup（サービスが作ったもの）も含めて PID を集める。
+// v2 の統合階層のほか、v1 との混在（unified・systemd の階層）にも対応する
+#[cfg(target_os = "linux")]
+fn cgroup_pids(path: &str) -> Vec<Pid> {
+    use std::path::Path;
+
+    fn walk(dir: &Path, pids: &mut Vec<Pid>) {
+        if let Ok(text) = std::fs::read_to_string(dir.join("cgroup.procs")) {
+            pids.extend(
+                text.lines()
+                    .filter_map(|line| line.trim().parse().ok())
+                    .map(Pid::from_u32),
+            );
+        }
+        if let Ok(entries) = std::fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                    walk(&entry.path(), pids);
+                }
+            }
+        }
+    }
+
+    let relative = path.trim_start_matches('/');
+    let mut pids = Vec::new();
+    for root in [
+        "/sys/fs/cgroup",
+        "/sys/fs/cgroup/unified",
+        "/sys/fs/cgroup/systemd",
+    ] {
+        let dir = Path::new(root).join(relative);
+        if dir.join("cgroup.procs").exists() {
+            walk(&dir, &mut pids);
+            break;
+        }
+    }
+    pids
+}
+
+#[cfg(windows)]
+mod scm {
+    use std::sync::Arc;
+
+    use sysinfo::Pid;
+    use windows_sys::Win32::Foundation::{ERROR_MORE_DATA, GetLastError};
+    use windows_sys::Win32::System::Services::{
+        CloseServiceHandle, ENUM_SERVICE_STATUS_PROCESSW, EnumServicesStatusExW, OpenSCManagerW,
+        SC_ENUM_PROCESS_INFO, SC_MANAGER_ENUMERATE_SERVICE, SERVICE_CONTINUE_PENDING,
+        SERVICE_PAUSE_PENDING, SERVICE_PAUSED, SERVICE_RUNNING, SERVICE_START_PENDING,
+        SERVICE_STATE_ALL, SERVICE_STOP_PENDING, SERVICE_STOPPED, SERVICE_WIN32,
+    };
+
+    use super::Service;
+
+    /// Win32 サービスの一覧。SCM を開けなければ空
+    pub fn services() -> Vec<Service> {
+        // SAFETY: ハンドルはこの関数の中でだけ使い、最後に閉じる。
+        // バッファは EnumServicesStatusExW が求めた大きさを確保してから渡す
+        unsafe {
+            let manager = OpenSCManagerW(
+                std::ptr::null(),
+                std::ptr::null(),
+                SC_MANAGER_ENUMERATE_SERVICE,
+            );
+            if manager.is_null() {
+                return Vec::new();
+            }
+            let mut services = Vec::new();
+            // 構造体の並びを保つため u64 単位で確保する
+            let mut buffer: Vec<u64> = Vec::new();
+            let mut resume = 0u32;
+            loop {
+                let mut needed = 0u32;
+                let mut returned = 0u32;
+                let ok = EnumServicesStatusExW(
+                    manager,
+                    SC_ENUM_PROCESS_INFO,
+                    SERVICE_WIN32,
+                    SERVICE_STATE_ALL,
+                    buffer.as_mut_ptr().cast(),
+                    (buffer.len() * 8) as u32,
+                    &mut needed,
+                    &mut returned,
+                    &mut resume,
+                    std::ptr::null(),
+                ) != 0;
+                let more = !ok && GetLastError() == ERROR_MORE_DATA;
+                let entries = std::slice::from_raw_parts(
+                    buffer.as_ptr().cast::<ENUM_SERVICE_STATUS_PROCESSW>(),
+                    returned as usize,
+                );
+                services.extend(entries.iter().map(|entry| service(entry)));
+                if !more {
+                    break;
+                }
+                // 足りなかった分を広げて続きを読む
+                buffer.resize((needed as usize).div_ceil(8).max(buffer.len()), 0);
+            }
+            CloseServiceHandle(manager);
+            services
+        }
+    }
+
+    // SAFETY: entry の文字列は EnumServicesStatusExW が書いた終端付きのもの
+    unsafe fn service(entry: &ENUM_SERVICE_STATUS_PROCESSW) -> Service {
+        let status = &entry.ServiceStatusProcess;
+        let active = match status.dwCurrentState {
+            SERVICE_RUNNING => "running",
+            SERVICE_STOPPED => "stopped",
+            SERVICE_START_PENDING => "starting",
+            SERVICE_STOP_PENDING => "stopping",
+            SERVICE_PAUSED => "paused",
+            SERVICE_PAUSE_PENDING => "pausing",
+            SERVICE_CONTINUE_PENDING => "resuming",
+            _ => "unknown",
+        };
+        let main_pid = (status.dwProcessId != 0).then(|| Pid::from_u32(status.dwProcessId));
+        Service {
+            name: Arc::from(unsafe { wide(entry.lpServiceName) }),
+            active: active.to_string(),
+            sub: String::new(),
+            main_pid,
+            cpu_usage: None,
+            memory: None,
+            pids: main_pid.into_iter().collect(),
+        }
+    }
+
+    unsafe fn wide(text: *const u16) -> String {
+        if text.is_null() {
+            return String::new();
+        }
+        let mut len = 0;
+        // SAFETY: 終端の 0 までは読める
+        unsafe {
+            while *text.add(len) != 0 {
+                len += 1;
+            }
+            String::from_utf16_lossy(std::slice::from_raw_parts(text, len))
+        }
+    }
+}
@@ -0,0 +1,63 @@
+// ログイン中のユーザー（utmp のログインセッション）
+//
+// sysinfo::Users はアカウントの一覧でログイン状態は分からないため、
+// who と同じく utmpx の USER_PROCESS の記録を読む。
+
+/// ログインセッション 1 つ
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub user: String,
+    /// 端末（pts/0、tty1 など）
+    pub line: String,
+    /// リモートから入ったときの接続元
+    pub host: Option<String>,
+    /// ログインした時刻（UNIX 時間の秒）
+    pub since: u64,
+}
+
+#[cfg(unix)]
+pub fn collect() -> Vec<Session> {
+    use std::os::raw::c_char;
+
+    // utmpx の文字列は配列いっぱいだと終端が無い
+    fn field(chars: &[c_char]) -> String {
+        let bytes: Vec<u8> = chars
+            .iter()
+            .map(|&c| c as u8)
+            .take_while(|&b| b != 0)
+            .collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    let mut sessions = Vec::new();
+    // SAFETY: getutxent が返すポインタは次の呼び出しまで有効で、その間に読み終える。
+    // 呼ぶのはサンプラーのスレッドだけ
+    unsafe {
+        libc::setutxent();
+        loop {
+            let entry = libc::getutxent();
+            if entry.is_null() {
+                break;
+            }
+            let entry = &*entry;
+            if entry.ut_type != libc::USER_PROCESS {
+                continue;
+            }
+            let host = field(&entry.ut_host);
+            sessions.push(Session {
+                user: field(&entry.ut_user),
+                line: field(&entry.ut_line),
+                host: (!host.is_empty()).then_some(host),
+                since: entry.ut_tv.tv_sec as u64,
+            });
+        }
+        libc::endutxent();
+    }
+    sessions.sort_by(|a, b| (&a.user, a.since).cmp(&(&b.user, b.since)));
+    sessions
+}
+
+#[cfg(not(unix))]
+pub fn collect() -> Vec<Session> {
+    Vec::new()
+}
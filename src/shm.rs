@@ -0,0 +1,116 @@
+// tmpfs と共有メモリ（Linux の /proc/self/mounts と /proc/sysvipc/shm）
+//
+// tmpfs に置いたファイルや共有メモリはメモリの「キャッシュ」に数えられるが、
+// 空きが足りなくなっても捨てられない。どこがどれだけ持っているかを出す。
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TmpfsMount {
+    pub path: String,
+    /// バイト
+    pub used: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ShmSnapshot {
+    /// 使用量の多い順
+    pub mounts: Vec<TmpfsMount>,
+    /// System V の共有メモリのセグメント数と合計（バイト）。読めなければ None
+    pub sysv: Option<(usize, u64)>,
+}
+
+impl ShmSnapshot {
+    /// tmpfs に置かれている量の合計
+    pub fn tmpfs_used(&self) -> u64 {
+        self.mounts.iter().map(|m| m.used).sum()
+    }
+}
+
+/// tmpfs が 1 つも無ければ（または Linux 以外）None
+#[cfg(target_os = "linux")]
+pub fn collect() -> Option<ShmSnapshot> {
+    use std::collections::HashSet;
+    use std::os::unix::fs::MetadataExt;
+
+    let text = std::fs::read_to_string("/proc/self/mounts").ok()?;
+    // bind mount で同じ tmpfs が何度も出てくるので、デバイス番号で 1 つにする
+    let mut seen = HashSet::new();
+    let mut mounts: Vec<TmpfsMount> = text
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let path = fields.nth(1)?;
+            (fields.next()? == "tmpfs").then(|| unescape(path))
+        })
+        .filter(|path| std::fs::metadata(path).is_ok_and(|m| seen.insert(m.dev())))
+        .filter_map(|path| {
+            let (used, total) = usage(&path)?;
+            Some(TmpfsMount { path, used, total })
+        })
+        .collect();
+    if mounts.is_empty() {
+        return None;
+    }
+    mounts.sort_by(|a, b| b.used.cmp(&a.used).then_with(|| a.path.cmp(&b.path)));
+    Some(ShmSnapshot {
+        mounts,
+        sysv: std::fs::read_to_string("/proc/sysvipc/shm")
+            .ok()
+            .map(|text| parse_sysv(&text)),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn collect() -> Option<ShmSnapshot> {
+    None
+}
+
+// mounts では空白などが "\040" のような 8 進数で書かれている
+#[cfg(target_os = "linux")]
+fn unescape(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match octal {
+            Some(byte) if bytes[i] == b'\\' => {
+                out.push(byte);
+                i += 4;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// statvfs で使用量と大きさを読む（df と同じ）
+#[cfg(target_os = "linux")]
+fn usage(path: &str) -> Option<(u64, u64)> {
+    let path = std::ffi::CString::new(path).ok()?;
+    // SAFETY: 0 埋めの statvfs に書き込ませるだけ
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: path は NUL 終端の文字列、stat は有効な領域
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let block = stat.f_frsize as u64;
+    let total = stat.f_blocks as u64 * block;
+    let free = stat.f_bfree as u64 * block;
+    Some((total.saturating_sub(free), total))
+}
+
+// 1 行目は見出しで、4 列目（0 から数えて 3）がセグメントの大きさ
+#[cfg(target_os = "linux")]
+fn parse_sysv(text: &str) -> (usize, u64) {
+    text.lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(3)?.parse::<u64>().ok())
+        .fold((0, 0), |(count, total), size| (count + 1, total + size))
+}
@@ -0,0 +1,112 @@
+// TUI の間に届くシグナル（unix）と、それに当たるキー
+//
+// raw モードの端末は Ctrl-Z や Ctrl-C をシグナルにしないので、キーとして受け取って自分で処理する。
+// kill や端末を閉じたことで外から届いたときも、そのままだと端末が raw モードのまま残るので、
+// ハンドラでは印を付けるだけにして、画面のループで q や一時停止と同じ手順を踏む。
+use std::io;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::Terminal;
+use ratatui::backend::Backend;
+
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(unix)]
+static SUSPEND: AtomicBool = AtomicBool::new(false);
+/// 一度届いたら、入れ子の画面（ダッシュボードから開いたホスト）も含めてすべて抜ける
+#[cfg(unix)]
+static QUIT: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_tstp(_: libc::c_int) {
+    SUSPEND.store(true, Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+extern "C" fn on_quit(_: libc::c_int) {
+    QUIT.store(true, Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+fn handle(signal: libc::c_int, handler: extern "C" fn(libc::c_int)) {
+    // SAFETY: ハンドラはアトミック変数に書くだけで、シグナルの中でも安全
+    unsafe {
+        libc::signal(signal, handler as libc::sighandler_t);
+    }
+}
+
+/// TUI に入ったら呼ぶ
+#[cfg(unix)]
+pub fn install() {
+    handle(libc::SIGTSTP, on_tstp);
+    // SIGHUP は端末のウィンドウを閉じたとき
+    for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+        handle(signal, on_quit);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install() {}
+
+fn is_ctrl(key: Option<&KeyEvent>, c: char) -> bool {
+    key.is_some_and(|key| {
+        key.kind == KeyEventKind::Press
+            && key.code == KeyCode::Char(c)
+            && key.modifiers.contains(KeyModifiers::CONTROL)
+    })
+}
+
+/// Ctrl-C が押されたか、終了のシグナルが届いていれば true
+pub fn quit_requested(key: Option<&KeyEvent>) -> bool {
+    #[cfg(unix)]
+    if QUIT.load(Ordering::SeqCst) {
+        return true;
+    }
+    is_ctrl(key, 'c')
+}
+
+/// Ctrl-Z が押されたか、SIGTSTP が届いていれば true
+pub fn suspend_requested(key: Option<&KeyEvent>) -> bool {
+    let pressed = is_ctrl(key, 'z');
+    #[cfg(unix)]
+    {
+        SUSPEND.swap(false, Ordering::SeqCst) || pressed
+    }
+    // Windows には一時停止が無い
+    #[cfg(not(unix))]
+    {
+        let _ = pressed;
+        false
+    }
+}
+
+/// 端末を元に戻して止まり、fg で再開したら TUI に戻して全体を描き直させる
+#[cfg(unix)]
+pub fn suspend<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
+    use crossterm::event::DisableMouseCapture;
+    use crossterm::execute;
+    use crossterm::terminal::{
+        EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+    };
+
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+    terminal.show_cursor()?;
+    // SAFETY: 既定の動作に戻して自分に送ると、SIGCONT までここで止まる
+    unsafe {
+        libc::signal(libc::SIGTSTP, libc::SIG_DFL);
+        libc::raise(libc::SIGTSTP);
+    }
+    handle(libc::SIGTSTP, on_tstp);
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    crate::term::enable_mouse(&mut io::stdout())?;
+    terminal.hide_cursor()?;
+    terminal.clear()
+}
+
+#[cfg(not(unix))]
+pub fn suspend<B: Backend>(_terminal: &mut Terminal<B>) -> io::Result<()> {
+    Ok(())
+}
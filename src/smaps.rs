@@ -0,0 +1,42 @@
+// プロセスのメモリの内訳（/proc/<pid>/smaps_rollup、Linux）
+//
+// カーネルがすべてのマッピングをたどるので重い。内訳の列（M）を出しているときだけ読む。
+use sysinfo::Pid;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryDetail {
+    /// 他のプロセスと共有している常駐メモリ（バイト）
+    pub shared: u64,
+    /// スワップに追い出された量（バイト）
+    pub swap: u64,
+}
+
+#[cfg(target_os = "linux")]
+pub fn read(pid: Pid) -> Option<MemoryDetail> {
+    let text = std::fs::read_to_string(format!("/proc/{pid}/smaps_rollup")).ok()?;
+    let mut detail = MemoryDetail::default();
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        // 値は "1240 kB" の形
+        let Some(kib) = value
+            .trim()
+            .strip_suffix("kB")
+            .and_then(|n| n.trim().parse::<u64>().ok())
+        else {
+            continue;
+        };
+        match key {
+            "Shared_Clean" | "Shared_Dirty" => detail.shared += kib * 1024,
+            "Swap" => detail.swap = kib * 1024,
+            _ => {}
+        }
+    }
+    Some(detail)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read(_pid: Pid) -> Option<MemoryDetail> {
+    None
+}
@@ -0,0 +1,104 @@
+// ディスクの S.M.A.R.T.（smartctl --json の出力を読む）
+//
+// 健康状態・温度・代替処理済みセクタ数を、壊れかけたディスクの早めの知らせとして出す。
+// smartctl は root でないとデバイスを開けないことが多く、問い合わせも遅いので、
+// 既定の更新間隔は長くしている。
+use std::process::Command;
+
+use crate::json::{self, Json};
+
+#[derive(Debug, Clone)]
+pub struct Disk {
+    /// "/dev/sda" など
+    pub name: String,
+    pub model: String,
+    /// SMART の総合判定。読めなければ None
+    pub passed: Option<bool>,
+    /// °C
+    pub temperature: Option<f64>,
+    /// 代替処理済みセクタ数（ATA の 5 番）。NVMe では None
+    pub reallocated: Option<u64>,
+}
+
+impl Disk {
+    /// 判定が失敗か、代替処理済みのセクタがあれば警告する
+    pub fn is_ok(&self) -> bool {
+        self.passed != Some(false) && self.reallocated.unwrap_or(0) == 0
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SmartSnapshot {
+    pub disks: Vec<Disk>,
+}
+
+/// smartctl が無い、または読めるディスクが無いときは None
+pub fn collect() -> Option<SmartSnapshot> {
+    let scan = json::parse(&run(&["--scan", "--json"])?).ok()?;
+    let disks: Vec<Disk> = scan
+        .get("devices")?
+        .as_array()?
+        .iter()
+        .filter_map(|device| {
+            let name = device.get("name")?.as_str()?;
+            let kind = device.get("type")?.as_str()?;
+            parse(
+                name,
+                &run(&[
+                    "--info",
+                    "--health",
+                    "--attributes",
+                    "--json",
+                    "-d",
+                    kind,
+                    name,
+                ])?,
+            )
+        })
+        .collect();
+    (!disks.is_empty()).then_some(SmartSnapshot { disks })
+}
+
+/// smartctl --info --health --attributes --json の出力を読む
+pub fn parse(name: &str, text: &str) -> Option<Disk> {
+    let value = json::parse(text).ok()?;
+    // デバイスを開けなかったとき（権限が無いなど）は判定も属性も無い
+    let passed = value
+        .get("smart_status")
+        .and_then(|status| status.get("passed"))
+        .and_then(Json::as_bool);
+    let temperature = value
+        .get("temperature")
+        .and_then(|t| t.get("current"))
+        .and_then(Json::as_f64);
+    if passed.is_none() && temperature.is_none() {
+        return None;
+    }
+    let reallocated = value
+        .get("ata_smart_attributes")
+        .and_then(|attributes| attributes.get("table"))
+        .and_then(Json::as_array)
+        .and_then(|table| {
+            table
+                .iter()
+                .find(|attribute| attribute.get("id").and_then(Json::as_u64) == Some(5))
+        })
+        .and_then(|attribute| attribute.get("raw")?.get("value")?.as_u64());
+    Some(Disk {
+        name: name.to_string(),
+        model: value
+            .get("model_name")
+            .and_then(Json::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        passed,
+        temperature,
+        reallocated,
+    })
+}
+
+// smartctl の終了コードはビットごとの意味を持ち、ディスクの異常でも 0 以外になるので見ない
+fn run(args: &[&str]) -> Option<String> {
+    let output = Command::new("smartctl").args(args).output().ok()?;
+    String::from_utf8(output.stdout).ok()
+}
@@ -0,0 +1,615 @@
+// 収集の元になる OS の窓口
+//
+// Collector は sysinfo を直接触らず、この SystemSource を通して CPU・メモリ・プロセス・情報を読む。
+// 普段は SysinfoSource、--mock では決まった値を返す MockSource を使うので、
+// 実機に依らない画面（TestBackend での描画や計測）を作れる。
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sysinfo::{
+    Pid, Process, ProcessRefreshKind, ProcessStatus, ProcessesToUpdate, Product, System,
+    ThreadKind, Uid, UpdateKind, Users,
+};
+
+#[cfg(feature = "environ")]
+use crate::actions::ProcessRef;
+use crate::actions::{self, ActionError, ActionOutcome, Command, Originals};
+use crate::cpufreq::{self, CpuFrequency};
+use crate::sampler::{OwnUsage, SystemInfo};
+use crate::sessions;
+
+/// 単位はバイト
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryReading {
+    pub total: u64,
+    pub used: u64,
+    pub total_swap: u64,
+    pub used_swap: u64,
+}
+
+pub trait SystemSource: Send + 'static {
+    fn refresh_cpu(&mut self);
+    /// コアごとの使用率（1 コア = 100%）。refresh_cpu で読んだもの
+    fn cpu_usages(&self) -> Vec<f32>;
+    fn memory(&mut self) -> MemoryReading;
+    fn refresh_processes(&mut self);
+    /// refresh_processes で読んだプロセスを一つずつ渡す
+    fn for_each_process(&mut self, visit: &mut dyn FnMut(&dyn SourceProcess));
+    fn usage_of(&self, pid: Pid) -> Option<OwnUsage>;
+    fn info(&mut self) -> SystemInfo;
+    fn execute(&mut self, command: Command) -> ActionOutcome;
+    /// 環境変数（"名前=値"）。読めないときや PID が使い回されていれば Err
+    #[cfg(feature = "environ")]
+    fn environ(&mut self, target: ProcessRef) -> Result<Vec<String>, String>;
+    /// cgroup・PSI・バッテリーなど、sysinfo 以外の読み取りもこの機械から行うか
+    fn reads_host(&self) -> bool {
+        true
+    }
+}
+
+pub trait SourceProcess {
+    fn pid(&self) -> Pid;
+    fn parent(&self) -> Option<Pid>;
+    fn name(&self) -> Cow<'_, str>;
+    /// 引数を空白でつないだもの。新しいプロセスのときだけ呼ばれる
+    fn cmd(&self) -> Arc<str>;
+    fn user(&self) -> Arc<str>;
+    fn status(&self) -> ProcessStatus;
+    /// スレッド数（分からなければ 0）
+    fn threads(&self) -> usize;
+    fn is_thread(&self) -> bool;
+    fn start_time(&self) -> u64;
+    fn cpu_usage(&self) -> f32;
+    /// ミリ秒
+    fn cpu_time(&self) -> u64;
+    fn memory(&self) -> u64;
+    fn virtual_memory(&self) -> u64;
+    /// 前回の読み直しから読んだ量と書いた量（バイト）
+    fn disk_usage(&self) -> (u64, u64);
+}
+
+/// sysinfo で読む、普段の窓口
+pub struct SysinfoSource {
+    sys: System,
+    users: UserCache,
+    /// 操作で変えた nice 値などの元の値（:revert で戻す）
+    originals: Originals,
+}
+
+impl SysinfoSource {
+    pub fn new() -> Self {
+        Self {
+            sys: System::new(),
+            users: UserCache::new(),
+            originals: Originals::default(),
+        }
+    }
+}
+
+impl SystemSource for SysinfoSource {
+    fn refresh_cpu(&mut self) {
+        self.sys.refresh_cpu_usage();
+    }
+
+    fn cpu_usages(&self) -> Vec<f32> {
+        self.sys.cpus().iter().map(|c| c.cpu_usage()).collect()
+    }
+
+    fn memory(&mut self) -> MemoryReading {
+        self.sys.refresh_memory();
+        MemoryReading {
+            total: self.sys.total_memory(),
+            used: self.sys.used_memory(),
+            total_swap: self.sys.total_swap(),
+            used_swap: self.sys.used_swap(),
+        }
+    }
+
+    fn refresh_processes(&mut self) {
+        self.sys.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::nothing()
+                .with_memory()
+                .with_cpu()
+                .with_disk_usage()
+                .with_cmd(UpdateKind::OnlyIfNotSet)
+                .with_user(UpdateKind::OnlyIfNotSet),
+        );
+        self.users.begin_pass();
+    }
+
+    fn for_each_process(&mut self, visit: &mut dyn FnMut(&dyn SourceProcess)) {
+        for process in self.sys.processes().values() {
+            let user = self.users.name(process);
+            visit(&SysinfoProcess { process, user });
+        }
+    }
+
+    fn usage_of(&self, pid: Pid) -> Option<OwnUsage> {
+        self.sys.process(pid).map(|p| OwnUsage {
+            cpu_usage: p.cpu_usage(),
+            memory: p.memory(),
+        })
+    }
+
+    fn info(&mut self) -> SystemInfo {
+        let sys = &mut self.sys;
+        sys.refresh_cpu_frequency();
+        SystemInfo {
+            cpu_count: sys.cpus().len(),
+            cpu_arch: System::cpu_arch(),
+            brand: sys
+                .cpus()
+                .first()
+                .map_or("Unknown", |c| c.brand())
+                .to_string(),
+            frequency: cpufreq::collect(sys),
+            uptime: System::uptime(),
+            boot_time: System::boot_time(),
+            sessions: sessions::collect(),
+            load_average: {
+                let load = System::load_average();
+                [load.one, load.five, load.fifteen]
+            },
+            kernel_long_version: System::kernel_long_version(),
+            long_os_version: System::long_os_version(),
+            #[cfg(windows)]
+            edition: crate::wininfo::edition(),
+            #[cfg(windows)]
+            os_build: crate::wininfo::build(),
+            #[cfg(not(windows))]
+            edition: None,
+            #[cfg(not(windows))]
+            os_build: None,
+            host_name: System::host_name(),
+            open_files_limit: System::open_files_limit(),
+            product_name: Product::name(),
+            vendor_name: Product::vendor_name(),
+        }
+    }
+
+    fn execute(&mut self, command: Command) -> ActionOutcome {
+        actions::execute(&mut self.sys, &mut self.originals, command)
+    }
+
+    // 環境変数は大きく、秘密も入るので、頼まれたときに対象だけ読む
+    #[cfg(feature = "environ")]
+    fn environ(&mut self, target: ProcessRef) -> Result<Vec<String>, String> {
+        self.sys.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&[target.pid]),
+            false,
+            ProcessRefreshKind::nothing().with_environ(UpdateKind::Always),
+        );
+        let process = self
+            .sys
+            .process(target.pid)
+            .filter(|p| p.start_time() == target.start_time)
+            .ok_or_else(|| format!("Process {} no longer exists", target.pid))?;
+        // 読む権限が無くても sysinfo は空で返す
+        if process.environ().is_empty() {
+            return Err(format!(
+                "No environment visible for {} (other users' processes need root)",
+                target.pid
+            ));
+        }
+        Ok(process
+            .environ()
+            .iter()
+            .map(|var| var.to_string_lossy().into_owned())
+            .collect())
+    }
+}
+
+struct SysinfoProcess<'a> {
+    process: &'a Process,
+    user: Arc<str>,
+}
+
+impl SourceProcess for SysinfoProcess<'_> {
+    fn pid(&self) -> Pid {
+        self.process.pid()
+    }
+
+    fn parent(&self) -> Option<Pid> {
+        self.process.parent()
+    }
+
+    fn name(&self) -> Cow<'_, str> {
+        self.process.name().to_string_lossy()
+    }
+
+    fn cmd(&self) -> Arc<str> {
+        self.process
+            .cmd()
+            .iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+            .into()
+    }
+
+    fn user(&self) -> Arc<str> {
+        Arc::clone(&self.user)
+    }
+
+    fn status(&self) -> ProcessStatus {
+        self.process.status()
+    }
+
+    // tasks にはメインスレッドが含まれない
+    fn threads(&self) -> usize {
+        self.process.tasks().map_or(0, |tasks| tasks.len() + 1)
+    }
+
+    fn is_thread(&self) -> bool {
+        self.process.thread_kind() == Some(ThreadKind::Userland)
+    }
+
+    fn start_time(&self) -> u64 {
+        self.process.start_time()
+    }
+
+    fn cpu_usage(&self) -> f32 {
+        self.process.cpu_usage()
+    }
+
+    fn cpu_time(&self) -> u64 {
+        self.process.accumulated_cpu_time()
+    }
+
+    fn memory(&self) -> u64 {
+        self.process.memory()
+    }
+
+    fn virtual_memory(&self) -> u64 {
+        self.process.virtual_memory()
+    }
+
+    fn disk_usage(&self) -> (u64, u64) {
+        let disk = self.process.disk_usage();
+        (disk.read_bytes, disk.written_bytes)
+    }
+}
+
+/// UID からユーザー名への変換キャッシュ
+struct UserCache {
+    users: Users,
+    names: HashMap<Uid, Arc<str>>,
+    refreshed: bool,
+}
+
+impl UserCache {
+    fn new() -> Self {
+        Self {
+            users: Users::new_with_refreshed_list(),
+            names: HashMap::new(),
+            refreshed: true,
+        }
+    }
+
+    // 新しい収集の開始時に呼ぶ
+    fn begin_pass(&mut self) {
+        self.refreshed = false;
+    }
+
+    fn name(&mut self, process: &Process) -> Arc<str> {
+        let Some(uid) = process.user_id() else {
+            return Arc::from("?");
+        };
+        if let Some(name) = self.names.get(uid) {
+            return name.clone();
+        }
+        // 未知の UID が現れたらユーザー一覧を（1 回の収集につき 1 度だけ）読み直す
+        if !self.refreshed {
+            self.users.refresh();
+            self.refreshed = true;
+        }
+        let name: Arc<str> = self
+            .users
+            .get_user_by_id(uid)
+            .map_or_else(|| Arc::from(uid.to_string()), |u| Arc::from(u.name()));
+        self.names.insert(uid.clone(), name.clone());
+        name
+    }
+}
+
+const MOCK_CPUS: usize = 4;
+const MIB: u64 = 1024 * 1024;
+const GIB: u64 = 1024 * MIB;
+/// 起動した時刻として見せる値（2024-01-01 00:00 UTC）
+const MOCK_BOOT_TIME: u64 = 1_704_067_200;
+
+/// PID、親、名前、コマンドライン、ユーザー、基準の CPU 使用率、メモリ（MB）
+const MOCK_PROCESSES: [(u32, u32, &str, &str, &str, f32, u64); 12] = [
+    (1, 0, "systemd", "/sbin/init", "root", 0.1, 12),
+    (412, 1, "sshd", "sshd: /usr/sbin/sshd -D", "root", 0.0, 8),
+    (
+        530,
+        1,
+        "postgres",
+        "postgres -D /var/lib/postgresql",
+        "postgres",
+        2.0,
+        180,
+    ),
+    (
+        531,
+        530,
+        "postgres",
+        "postgres: checkpointer",
+        "postgres",
+        0.5,
+        40,
+    ),
+    (
+        532,
+        530,
+        "postgres",
+        "postgres: walwriter",
+        "postgres",
+        1.5,
+        36,
+    ),
+    (610, 1, "nginx", "nginx: master process", "root", 0.0, 6),
+    (
+        611,
+        610,
+        "nginx",
+        "nginx: worker process",
+        "www-data",
+        4.0,
+        24,
+    ),
+    (
+        700,
+        1,
+        "python3",
+        "python3 -m app.worker --queue default",
+        "alice",
+        35.0,
+        420,
+    ),
+    (
+        701,
+        1,
+        "python3",
+        "python3 -m app.worker --queue slow",
+        "alice",
+        12.0,
+        310,
+    ),
+    (812, 412, "bash", "-bash", "alice", 0.0, 5),
+    (
+        900,
+        812,
+        "cargo",
+        "cargo build --release",
+        "alice",
+        90.0,
+        900,
+    ),
+    (950, 1, "leaky", "./leaky --grow", "alice", 3.0, 200),
+];
+
+/// 決まった値を返す作り物（--mock）。収集のたびに値を決まった順に変える
+#[derive(Debug, Default)]
+pub struct MockSource {
+    cpu_ticks: u64,
+    process_ticks: u64,
+    /// MOCK_PROCESSES の後ろに足す作り物のプロセスの数（bench --processes）
+    synthetic: usize,
+}
+
+/// 足したプロセスの PID はここから振る
+const SYNTHETIC_PID: u32 = 10_000;
+
+impl MockSource {
+    /// 全部で count 個のプロセスを返す。MOCK_PROCESSES を PID を変えて繰り返す
+    pub fn with_processes(count: usize) -> Self {
+        Self {
+            synthetic: count.saturating_sub(MOCK_PROCESSES.len()),
+            ..Self::default()
+        }
+    }
+
+    // 0.5〜1.4 倍を 10 回で一巡する
+    fn wave(tick: u64, seed: u64) -> f32 {
+        0.5 + ((tick + seed) % 10) as f32 / 10.0
+    }
+}
+
+impl SystemSource for MockSource {
+    fn refresh_cpu(&mut self) {
+        self.cpu_ticks += 1;
+    }
+
+    fn cpu_usages(&self) -> Vec<f32> {
+        (0..MOCK_CPUS as u64)
+            .map(|i| 40.0 * Self::wave(self.cpu_ticks, i * 3))
+            .collect()
+    }
+
+    fn memory(&mut self) -> MemoryReading {
+        MemoryReading {
+            total: 16 * GIB,
+            used: 6 * GIB + (self.process_ticks % 8) * 128 * MIB,
+            total_swap: 2 * GIB,
+            used_swap: 256 * MIB,
+        }
+    }
+
+    fn refresh_processes(&mut self) {
+        self.process_ticks += 1;
+    }
+
+    fn for_each_process(&mut self, visit: &mut dyn FnMut(&dyn SourceProcess)) {
+        let tick = self.process_ticks;
+        let synthetic = (0..self.synthetic).map(|i| {
+            let (_, parent, name, cmd, user, cpu, memory) =
+                MOCK_PROCESSES[i % MOCK_PROCESSES.len()];
+            // 親は同じ並びの元のプロセスにして、木の形を崩さない
+            (
+                SYNTHETIC_PID + i as u32,
+                parent,
+                name,
+                cmd,
+                user,
+                cpu,
+                memory,
+            )
+        });
+        for (pid, parent, name, cmd, user, cpu, memory) in
+            MOCK_PROCESSES.into_iter().chain(synthetic)
+        {
+            let cpu_usage = cpu * Self::wave(tick, pid as u64);
+            // leaky だけは収集のたびにメモリが増える
+            let growth = if name == "leaky" && pid < SYNTHETIC_PID {
+                tick * 64 * MIB
+            } else {
+                0
+            };
+            visit(&MockProcess {
+                pid: Pid::from_u32(pid),
+                parent: (parent != 0).then(|| Pid::from_u32(parent)),
+                name,
+                cmd,
+                user,
+                cpu_usage,
+                cpu_time: (cpu as f64 * 10.0 * tick as f64) as u64 + pid as u64 * 1000,
+                memory: memory * MIB + growth,
+            });
+        }
+    }
+
+    fn usage_of(&self, _pid: Pid) -> Option<OwnUsage> {
+        None
+    }
+
+    fn info(&mut self) -> SystemInfo {
+        SystemInfo {
+            cpu_count: MOCK_CPUS,
+            cpu_arch: "x86_64".to_string(),
+            brand: "Mock CPU @ 2.40GHz".to_string(),
+            frequency: CpuFrequency {
+                current: vec![2400; MOCK_CPUS],
+                ..CpuFrequency::default()
+            },
+            uptime: 3 * 24 * 60 * 60,
+            boot_time: MOCK_BOOT_TIME,
+            sessions: Vec::new(),
+            load_average: [1.25, 0.9, 0.6],
+            kernel_long_version: "Linux 6.1.0-mock".to_string(),
+            long_os_version: Some("Mock Linux 1.0".to_string()),
+            edition: None,
+            os_build: None,
+            host_name: Some("mock".to_string()),
+            open_files_limit: Some(1024),
+            product_name: None,
+            vendor_name: None,
+        }
+    }
+
+    fn execute(&mut self, command: Command) -> ActionOutcome {
+        ActionOutcome {
+            command,
+            result: Err(ActionError::Unsupported),
+        }
+    }
+
+    #[cfg(feature = "environ")]
+    fn environ(&mut self, target: ProcessRef) -> Result<Vec<String>, String> {
+        let &(_, _, _, _, user, _, _) = MOCK_PROCESSES
+            .iter()
+            .find(|p| p.0 == target.pid.as_u32())
+            .ok_or_else(|| format!("Process {} no longer exists", target.pid))?;
+        Ok(vec![
+            format!("HOME=/home/{user}"),
+            "LANG=C.UTF-8".to_string(),
+            "PATH=/usr/local/bin:/usr/bin:/bin".to_string(),
+            format!("USER={user}"),
+        ])
+    }
+
+    fn reads_host(&self) -> bool {
+        false
+    }
+}
+
+struct MockProcess {
+    pid: Pid,
+    parent: Option<Pid>,
+    name: &'static str,
+    cmd: &'static str,
+    user: &'static str,
+    cpu_usage: f32,
+    cpu_time: u64,
+    memory: u64,
+}
+
+impl SourceProcess for MockProcess {
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn parent(&self) -> Option<Pid> {
+        self.parent
+    }
+
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self.name)
+    }
+
+    fn cmd(&self) -> Arc<str> {
+        Arc::from(self.cmd)
+    }
+
+    fn user(&self) -> Arc<str> {
+        Arc::from(self.user)
+    }
+
+    fn status(&self) -> ProcessStatus {
+        if self.cpu_usage >= 10.0 {
+            ProcessStatus::Run
+        } else {
+            ProcessStatus::Sleep
+        }
+    }
+
+    fn threads(&self) -> usize {
+        1
+    }
+
+    fn is_thread(&self) -> bool {
+        false
+    }
+
+    fn start_time(&self) -> u64 {
+        MOCK_BOOT_TIME + self.pid.as_u32() as u64
+    }
+
+    fn cpu_usage(&self) -> f32 {
+        self.cpu_usage
+    }
+
+    fn cpu_time(&self) -> u64 {
+        self.cpu_time
+    }
+
+    fn memory(&self) -> u64 {
+        self.memory
+    }
+
+    fn virtual_memory(&self) -> u64 {
+        self.memory * 3
+    }
+
+    fn disk_usage(&self) -> (u64, u64) {
+        // 書き込みが多いのは postgres の walwriter
+        match self.pid.as_u32() {
+            532 => (0, 4 * MIB),
+            _ => (0, 0),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+// 前回の表示の状態（並べ替え・絞り込み・レイアウト・列・更新間隔）
+//
+// 開くたびに同じ表示に直さなくて済むよう、終了するときに状態ファイルに書き、次に起動したときに戻す。
+// 場所は $XDG_STATE_HOME/rust-top/state.toml（無ければ ~/.local/state）。--fresh なら読まない。
+// 設定ファイルと同じ TOML のサブセットで書き、読めない値は黙って既定のままにする。
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::app::{App, SortKey};
+use crate::config::parser;
+use crate::filter::Filter;
+
+/// C / M / H / n / D / i / d / m / b / E で出し入れする列の、状態ファイルでの名前
+pub const COLUMNS: [&str; 10] = [
+    "command", "memory", "history", "node", "fds", "wchan", "category", "faults", "net", "times",
+];
+
+/// COLUMNS のうち names に挙げた列だけを出す（[profiles.<name>] columns でも使う）
+pub fn show_columns(app: &mut App, names: &[String]) {
+    let shown = |name: &str| names.iter().any(|c| c == name);
+    app.full_command = shown("command");
+    app.memory_detail = shown("memory");
+    app.cpu_history = shown("history");
+    app.numa_nodes = shown("node");
+    app.fd_counts = shown("fds");
+    app.wait_channels = shown("wchan");
+    app.category_column = shown("category");
+    app.page_faults = shown("faults");
+    app.net_traffic = shown("net");
+    app.process_times = shown("times");
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UiState {
+    pub sort: Option<SortKey>,
+    pub sort_reversed: bool,
+    /// :filter の式
+    pub filter: Option<String>,
+    pub user: Option<String>,
+    /// レイアウトの名前
+    pub layout: Option<String>,
+    /// COLUMNS のうち出していたもの
+    pub columns: Vec<String>,
+    /// :interval で変えたときだけ
+    pub interval: Option<Duration>,
+}
+
+impl UiState {
+    pub fn from_app(app: &App) -> UiState {
+        let shown = [
+            app.full_command,
+            app.memory_detail,
+            app.cpu_history,
+            app.numa_nodes,
+            app.fd_counts,
+            app.wait_channels,
+            app.category_column,
+            app.page_faults,
+            app.net_traffic,
+            app.process_times,
+        ];
+        UiState {
+            sort: Some(app.sort),
+            sort_reversed: app.sort_reversed,
+            filter: app.name_filter.as_ref().map(Filter::to_string),
+            user: app.user_filter.clone(),
+            layout: app.layouts.get(app.layout).map(|l| l.name.clone()),
+            columns: COLUMNS
+                .iter()
+                .zip(shown)
+                .filter(|(_, shown)| *shown)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            interval: app.chosen_interval,
+        }
+    }
+
+    /// 戻す。--filter を付けて起動したときはそちらを使う
+    pub fn apply(&self, app: &mut App) {
+        if let Some(sort) = self.sort {
+            app.sort = sort;
+            app.sort_reversed = self.sort_reversed;
+        }
+        if app.name_filter.is_none() {
+            app.name_filter = self.filter.as_deref().and_then(|f| Filter::parse(f).ok());
+        }
+        app.user_filter = self.user.clone();
+        if let Some(position) = self
+            .layout
+            .as_ref()
+            .and_then(|name| app.layouts.iter().position(|l| &l.name == name))
+        {
+            app.layout = position;
+        }
+        show_columns(app, &self.columns);
+        if let Some(interval) = self.interval {
+            app.refresh_interval = interval;
+            app.chosen_interval = Some(interval);
+        }
+    }
+
+    /// after までに変わったところを文章にする（--plain で読み上げる）
+    pub fn changes(&self, after: &UiState) -> Vec<String> {
+        let mut changes = Vec::new();
+        if (self.sort, self.sort_reversed) != (after.sort, after.sort_reversed)
+            && let Some(sort) = after.sort
+        {
+            let reversed = if after.sort_reversed {
+                ", reversed"
+            } else {
+                ""
+            };
+            changes.push(format!("Sorted by {}{reversed}", sort.label()));
+        }
+        if self.filter != after.filter {
+            changes.push(match &after.filter {
+                Some(filter) => format!("Filter: {filter}"),
+                None => "Filter cleared".to_string(),
+            });
+        }
+        if self.user != after.user {
+            changes.push(match &after.user {
+                Some(user) => format!("Showing the processes of {user}"),
+                None => "Showing all users".to_string(),
+            });
+        }
+        if self.layout != after.layout
+            && let Some(layout) = &after.layout
+        {
+            changes.push(format!("Layout: {layout}"));
+        }
+        let listed = |from: &UiState, to: &UiState| -> Vec<String> {
+            to.columns
+                .iter()
+                .filter(|c| !from.columns.contains(c))
+                .cloned()
+                .collect()
+        };
+        let shown = listed(self, after);
+        if !shown.is_empty() {
+            changes.push(format!("Shown: {}", shown.join(", ")));
+        }
+        let hidden = listed(after, self);
+        if !hidden.is_empty() {
+            changes.push(format!("Hidden: {}", hidden.join(", ")));
+        }
+        if self.interval != after.interval
+            && let Some(interval) = after.interval
+        {
+            changes.push(format!("Interval: {}s", interval.as_secs_f64()));
+        }
+        changes
+    }
+
+    pub fn parse(text: &str) -> Result<UiState, String> {
+        let doc = parser::parse(text).map_err(|e| e.to_string())?;
+        let mut state = UiState::default();
+        let Some(table) = doc.table("") else {
+            return Ok(state);
+        };
+        let string = |key: &str| table.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        state.sort = string("sort").and_then(|name| SortKey::from_name(&name));
+        state.sort_reversed = table
+            .get("sort_reversed")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        state.filter = string("filter");
+        state.user = string("user");
+        state.layout = string("layout");
+        state.columns = table
+            .get("columns")
+            .and_then(|v| v.as_array())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|v| v.as_str())
+            .filter(|name| COLUMNS.contains(name))
+            .map(str::to_string)
+            .collect();
+        state.interval = table
+            .get("interval")
+            .and_then(|v| v.as_f64())
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .filter(|interval| !interval.is_zero());
+        Ok(state)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out =
+            String::from("# rust-top が終了するときに書く。--fresh で起動すると読まない\n");
+        if let Some(sort) = self.sort {
+            out.push_str(&format!("sort = {}\n", quote(sort.label())));
+            out.push_str(&format!("sort_reversed = {}\n", self.sort_reversed));
+        }
+        for (key, value) in [
+            ("filter", &self.filter),
+            ("user", &self.user),
+            ("layout", &self.layout),
+        ] {
+            if let Some(value) = value {
+                out.push_str(&format!("{key} = {}\n", quote(value)));
+            }
+        }
+        let columns: Vec<String> = self.columns.iter().map(|c| quote(c)).collect();
+        out.push_str(&format!("columns = [{}]\n", columns.join(", ")));
+        if let Some(interval) = self.interval {
+            out.push_str(&format!("interval = {}\n", interval.as_secs_f64()));
+        }
+        out
+    }
+}
+
+// 設定ファイルのパーサーが読める "..." の文字列にする
+fn quote(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub fn state_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local").join("state"))
+        })?;
+    Some(base.join("rust-top").join("state.toml"))
+}
+
+/// ファイルが無いか読めなければ None（前回の状態が無いものとして起動する）
+pub fn load(path: &Path) -> Option<UiState> {
+    UiState::parse(&fs::read_to_string(path).ok()?).ok()
+}
+
+pub fn save(path: &Path, state: &UiState) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, state.to_text())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::UiState;
+
+    #[test]
+    fn unreadable_intervals_are_ignored() {
+        let state = UiState::parse("interval = 2.5\n").expect("state");
+        assert_eq!(state.interval, Some(Duration::from_millis(2500)));
+        for text in ["interval = 1e20\n", "interval = -1\n", "interval = 0\n"] {
+            assert_eq!(
+                UiState::parse(text).expect("state").interval,
+                None,
+                "{text}"
+            );
+        }
+    }
+}
@@ -0,0 +1,221 @@
+// 起動してから終了するまでの統計（--summary で終了時に表示する）
+//
+// 表示用の履歴（UsageHistory）は古いものから捨てるので、全体の平均と最大、プロセスごとの
+// CPU 時間の合計は別に積み上げる。プロセスの一覧が新しくなったときだけ数える。
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use sysinfo::Pid;
+
+use crate::alert;
+use crate::locale::Locale;
+use crate::sampler::Snapshot;
+
+/// 要約に挙げるプロセスの数
+const TOP: usize = 5;
+/// 要約に挙げる警告の数（新しいもの）
+const ALERTS: usize = 10;
+
+#[derive(Debug)]
+pub struct SessionStats {
+    started: Instant,
+    /// 前回数えた一覧の世代と時刻
+    last: Option<(u64, Instant)>,
+    samples: u64,
+    cpu_sum: f64,
+    cpu_peak: f64,
+    memory_sum: f64,
+    memory_peak: u64,
+    memory_total: u64,
+    /// （PID、起動時刻）ごとの名前と CPU 時間（秒）の合計
+    processes: HashMap<(Pid, u64), (Arc<str>, f64)>,
+    alert_count: usize,
+    /// 新しい ALERTS 件の（UNIX 時刻、内容）
+    alerts: Vec<(u64, String)>,
+}
+
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            last: None,
+            samples: 0,
+            cpu_sum: 0.0,
+            cpu_peak: 0.0,
+            memory_sum: 0.0,
+            memory_peak: 0,
+            memory_total: 0,
+            processes: HashMap::new(),
+            alert_count: 0,
+            alerts: Vec::new(),
+        }
+    }
+}
+
+impl SessionStats {
+    /// スナップショットが届くたびに呼ぶ。一覧が前回と同じなら何もしない
+    pub fn record(&mut self, snapshot: &Snapshot, now: Instant) {
+        if !snapshot.cpu_primed
+            || self
+                .last
+                .is_some_and(|(generation, _)| generation == snapshot.process_generation)
+        {
+            return;
+        }
+        let since = self
+            .last
+            .replace((snapshot.process_generation, now))
+            .map(|(_, at)| now.duration_since(at).as_secs_f64());
+        let cpu = f64::from(snapshot.cpu.normalized());
+        self.samples += 1;
+        self.cpu_sum += cpu;
+        self.cpu_peak = self.cpu_peak.max(cpu);
+        let memory = &snapshot.memory;
+        self.memory_sum += memory.used as f64;
+        self.memory_peak = self.memory_peak.max(memory.used);
+        self.memory_total = memory.total;
+        // 使用率は前回からの平均なので、経った時間を掛けて CPU 時間にする
+        let Some(secs) = since else {
+            return;
+        };
+        for p in snapshot.processes.iter().filter(|p| !p.is_thread) {
+            let entry = self
+                .processes
+                .entry((p.pid, p.start_time))
+                .or_insert_with(|| (p.name.clone(), 0.0));
+            entry.1 += f64::from(p.cpu_usage) / 100.0 * secs;
+        }
+    }
+
+    /// 警告が出るたびに呼ぶ
+    pub fn alert(&mut self, text: String) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.alert_count += 1;
+        if self.alerts.len() == ALERTS {
+            self.alerts.remove(0);
+        }
+        self.alerts.push((time, text));
+    }
+
+    /// 終了したときに標準出力に書く要約
+    pub fn report(&self, locale: &Locale, now: Instant) -> String {
+        let elapsed = now.duration_since(self.started);
+        let mut out = String::from("rust-top session summary\n");
+        let _ = writeln!(
+            out,
+            "  duration: {}",
+            alert::format_duration(elapsed.max(Duration::from_secs(1)))
+        );
+        if self.samples == 0 {
+            out.push_str("  no samples were collected\n");
+            return out;
+        }
+        let samples = self.samples as f64;
+        let _ = writeln!(
+            out,
+            "  CPU:      average {}, peak {}",
+            locale.percent(self.cpu_sum / samples),
+            locale.percent(self.cpu_peak)
+        );
+        let _ = writeln!(
+            out,
+            "  memory:   average {}, peak {} of {}",
+            locale.megabytes((self.memory_sum / samples) as u64, 0),
+            locale.megabytes(self.memory_peak, 0),
+            locale.megabytes(self.memory_total, 0)
+        );
+
+        let mut top: Vec<_> = self
+            .processes
+            .iter()
+            .filter(|(_, (_, secs))| *secs > 0.0)
+            .collect();
+        top.sort_by(|a, b| b.1.1.total_cmp(&a.1.1).then(a.0.cmp(b.0)));
+        out.push_str("  top CPU consumers:\n");
+        if top.is_empty() {
+            out.push_str("    none\n");
+        }
+        for (i, ((pid, _), (name, secs))) in top.into_iter().take(TOP).enumerate() {
+            let _ = writeln!(
+                out,
+                "    {}. {name} ({pid}): {} CPU seconds",
+                i + 1,
+                locale.float(*secs, 1)
+            );
+        }
+
+        match self.alert_count {
+            0 => out.push_str("  alerts:   none\n"),
+            count => {
+                let _ = writeln!(out, "  alerts:   {count} fired");
+                if count > self.alerts.len() {
+                    let _ = writeln!(out, "    (latest {})", self.alerts.len());
+                }
+                for (time, text) in &self.alerts {
+                    let _ = writeln!(out, "    {} {text}", locale.time(*time));
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::SessionStats;
+    use crate::locale::Locale;
+    use crate::sampler::Collector;
+    use crate::source::MockSource;
+
+    #[test]
+    fn summary_averages_the_samples_and_ranks_cpu_time() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut collector = Collector::with_source(MockSource::default());
+        collector.collect_all();
+        collector.collect_all();
+        let mut snapshot = collector.snapshot().clone();
+        snapshot.cpu_primed = true;
+        snapshot.processes.retain(|p| !p.is_thread);
+        for p in &mut snapshot.processes {
+            p.cpu_usage = 0.0;
+        }
+        snapshot.processes[0].cpu_usage = 20.0;
+        snapshot.processes[1].cpu_usage = 50.0;
+        snapshot.cpu.count = 4;
+        snapshot.cpu.usage_sum = 80.0;
+        snapshot.memory.total = 4096 * 1024 * 1024;
+        snapshot.memory.used = 1024 * 1024 * 1024;
+        let mut session = SessionStats::default();
+        session.record(&snapshot, at(0));
+        // 一覧が同じなら数えない
+        session.record(&snapshot, at(1));
+        snapshot.process_generation += 1;
+        snapshot.cpu.usage_sum = 240.0;
+        snapshot.memory.used = 3 * 1024 * 1024 * 1024;
+        session.record(&snapshot, at(2));
+        session.alert("High CPU: 60%".to_string());
+
+        let report = session.report(&Locale::default(), at(90));
+        assert!(report.contains("CPU:      average 40.0%, peak 60.0%"));
+        let first = format!(
+            "1. {} ({}): 1.0 CPU seconds",
+            snapshot.processes[1].name, snapshot.processes[1].pid
+        );
+        let second = format!(
+            "2. {} ({}): 0.4 CPU seconds",
+            snapshot.processes[0].name, snapshot.processes[0].pid
+        );
+        assert!(report.contains(&first));
+        assert!(report.contains(&second));
+        assert!(!report.contains("3. "));
+        assert!(report.contains("alerts:   1 fired"));
+        assert!(report.contains("High CPU: 60%"));
+    }
+}
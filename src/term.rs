@@ -0,0 +1,271 @@
+// 端末の能力（使える色の数・Unicode を出せるか・SSH 越しに使われているか）
+//
+// SSH の先では COLORTERM が渡らないことが多く、256 色すら出ない端末もある。
+// 配色は使える色に丸めてから使い、クリップボードは手元に届く OSC 52 で送る。
+// シリアルコンソールなど罫線を出せない端末では、描いた画面を ASCII に置き換える。
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crossterm::event::EnableMouseCapture;
+use crossterm::execute;
+use ratatui::buffer::Buffer;
+use ratatui::style::{Color, Style};
+
+use crate::cli::Args;
+use crate::config::Config;
+use crate::theme::Theme;
+
+/// SSH でログインした先で動いているか
+pub fn over_ssh() -> bool {
+    ["SSH_CONNECTION", "SSH_CLIENT", "SSH_TTY"]
+        .iter()
+        .any(|name| std::env::var_os(name).is_some())
+}
+
+// --low-bandwidth か [terminal] low_bandwidth。端末の状態と同じくプロセス全体で 1 つ
+static LOW_BANDWIDTH: AtomicBool = AtomicBool::new(false);
+
+pub fn set_low_bandwidth(on: bool) {
+    LOW_BANDWIDTH.store(on, Ordering::Relaxed);
+}
+
+pub fn low_bandwidth() -> bool {
+    LOW_BANDWIDTH.load(Ordering::Relaxed)
+}
+
+/// マウスの報告を有効にする。遅い回線では、動かすたびに届く報告（と描き直し）を止め、
+/// クリックとホイールだけにする
+pub fn enable_mouse(output: &mut impl Write) -> io::Result<()> {
+    if low_bandwidth() && cfg!(unix) {
+        output.write_all(b"\x1b[?1000h\x1b[?1006h")?;
+        return output.flush();
+    }
+    execute!(output, EnableMouseCapture)
+}
+
+/// 使う色と、ASCII で描くか。設定に無ければ環境から判定する
+pub fn capabilities(args: &Args, config: &Config) -> (ColorDepth, bool) {
+    let ascii = args.ascii || config.ascii.unwrap_or_else(|| !unicode());
+    let colors = config.colors.unwrap_or_else(ColorDepth::detect);
+    // --ascii は古い端末向けなので、色も 16 色に抑える
+    if args.ascii {
+        (colors.min(ColorDepth::Basic), ascii)
+    } else {
+        (colors, ascii)
+    }
+}
+
+/// 罫線やブロックの文字を出せそうか。
+/// TERM が古い端末のもの、またはロケールが UTF-8 以外の文字コードなら出せないとみなす
+pub fn unicode() -> bool {
+    const ASCII_TERMS: [&str; 7] = ["dumb", "vt52", "vt100", "vt102", "vt220", "ansi", "cons25"];
+    if std::env::var("TERM").is_ok_and(|term| ASCII_TERMS.contains(&term.as_str())) {
+        return false;
+    }
+    // C や POSIX は未設定のコンテナでもよく見るので、文字コードが書かれているときだけ見る
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+    match locale.as_deref().and_then(|locale| locale.split_once('.')) {
+        Some((_, charset)) => {
+            let charset = charset.split('@').next().unwrap_or_default();
+            charset.eq_ignore_ascii_case("utf-8") || charset.eq_ignore_ascii_case("utf8")
+        }
+        None => true,
+    }
+}
+
+/// --plain。スクリーンリーダーが読み上げないよう、罫線と棒・グラフの文字を空白にする。
+/// 値は見出しやラベルの数字で読める。矢印などの残りは asciify で ASCII にする
+pub fn plainify(buffer: &mut Buffer) {
+    for cell in &mut buffer.content {
+        let blank = cell.symbol().chars().next().is_some_and(|c| {
+            // 罫線（U+2500..）、ブロック（U+2580..）、点字で描いたグラフ（U+2800..）
+            matches!(c, '\u{2500}'..='\u{259F}' | '\u{2800}'..='\u{28FF}')
+        });
+        if blank {
+            cell.set_symbol(" ");
+        }
+    }
+}
+
+/// 描いた画面の罫線・ブロック・矢印を ASCII に置き換え、残りの ASCII 以外は ? にする
+pub fn asciify(buffer: &mut Buffer) {
+    for cell in &mut buffer.content {
+        let symbol = cell.symbol();
+        if symbol.is_ascii() {
+            continue;
+        }
+        let ascii = match symbol {
+            "─" | "┄" | "┈" | "╌" => "-",
+            // LineGauge の埋まった側。空いた側（─）と区別する
+            "━" | "═" => "=",
+            "│" | "┃" | "║" | "┆" | "┊" | "╎" => "|",
+            "┌" | "┐" | "└" | "┘" | "╭" | "╮" | "╰" | "╯" | "┏" | "┓" | "┗" | "┛" | "╔" | "╗"
+            | "╚" | "╝" | "├" | "┤" | "┬" | "┴" | "┼" | "┣" | "┫" | "┳" | "┻" | "╋" => {
+                "+"
+            }
+            "█" | "▇" | "▉" | "▊" => "#",
+            "▆" | "▅" | "▓" | "▋" | "▌" => "=",
+            "▄" | "▃" | "▒" | "▍" | "▎" => "-",
+            "▂" | "▁" | "░" | "▏" => ".",
+            "▲" | "▴" => "^",
+            "▼" | "▾" => "v",
+            "▸" | "▶" => ">",
+            "…" => "~",
+            "°" => " ",
+            _ => "?",
+        };
+        cell.set_symbol(ascii);
+    }
+}
+
+/// 端末が出せる色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ColorDepth {
+    /// ANSI の 16 色
+    Basic,
+    /// xterm の 256 色
+    Indexed,
+    #[default]
+    TrueColor,
+}
+
+impl ColorDepth {
+    pub const NAMES: [&'static str; 4] = ["auto", "16", "256", "truecolor"];
+
+    /// "auto" は None（環境から判定する）
+    pub fn from_name(name: &str) -> Option<Option<ColorDepth>> {
+        match name {
+            "auto" => Some(None),
+            "16" => Some(Some(ColorDepth::Basic)),
+            "256" => Some(Some(ColorDepth::Indexed)),
+            "truecolor" | "24bit" => Some(Some(ColorDepth::TrueColor)),
+            _ => None,
+        }
+    }
+
+    /// COLORTERM と TERM から判定する
+    pub fn detect() -> ColorDepth {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return ColorDepth::TrueColor;
+        }
+        match std::env::var("TERM") {
+            Ok(term) if term.contains("256color") || term.contains("direct") => ColorDepth::Indexed,
+            Ok(_) => ColorDepth::Basic,
+            // Windows のコンソールは TERM を設定しないが、24 ビットの色を出せる
+            Err(_) if cfg!(windows) => ColorDepth::TrueColor,
+            Err(_) => ColorDepth::Basic,
+        }
+    }
+
+    /// 出せない色は、出せる中で一番近いものにする
+    pub fn fit(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorDepth::TrueColor, _) => color,
+            (ColorDepth::Indexed, Color::Rgb(r, g, b)) => Color::Indexed(to_indexed([r, g, b])),
+            (ColorDepth::Basic, Color::Rgb(r, g, b)) => to_basic([r, g, b]),
+            (ColorDepth::Basic, Color::Indexed(n)) => to_basic(indexed_rgb(n)),
+            _ => color,
+        }
+    }
+
+    pub fn fit_style(self, style: Style) -> Style {
+        Style {
+            fg: style.fg.map(|c| self.fit(c)),
+            bg: style.bg.map(|c| self.fit(c)),
+            ..style
+        }
+    }
+
+    pub fn fit_theme(self, theme: Theme) -> Theme {
+        let fit = |color| self.fit(color);
+        Theme {
+            cpu: fit(theme.cpu),
+            memory: fit(theme.memory),
+            header: fit(theme.header),
+            message: fit(theme.message),
+            zombie: fit(theme.zombie),
+            exited: fit(theme.exited),
+            caution: fit(theme.caution),
+            warning: fit(theme.warning),
+            new_process: theme.new_process.map(fit),
+            changed: theme.changed.map(fit),
+            watch_cpu: fit(theme.watch_cpu),
+            watch_memory: fit(theme.watch_memory),
+            ..theme
+        }
+    }
+}
+
+// xterm の既定の 16 色
+const BASIC: [(Color, [u8; 3]); 16] = [
+    (Color::Black, [0, 0, 0]),
+    (Color::Red, [205, 0, 0]),
+    (Color::Green, [0, 205, 0]),
+    (Color::Yellow, [205, 205, 0]),
+    (Color::Blue, [0, 0, 238]),
+    (Color::Magenta, [205, 0, 205]),
+    (Color::Cyan, [0, 205, 205]),
+    (Color::Gray, [229, 229, 229]),
+    (Color::DarkGray, [127, 127, 127]),
+    (Color::LightRed, [255, 0, 0]),
+    (Color::LightGreen, [0, 255, 0]),
+    (Color::LightYellow, [255, 255, 0]),
+    (Color::LightBlue, [92, 92, 255]),
+    (Color::LightMagenta, [255, 0, 255]),
+    (Color::LightCyan, [0, 255, 255]),
+    (Color::White, [255, 255, 255]),
+];
+
+// 256 色の 16〜231 は 6 段階の RGB の立方体
+const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b)
+        .map(|(&x, y)| (x as i32 - y as i32).pow(2) as u32)
+        .sum()
+}
+
+fn indexed_rgb(n: u8) -> [u8; 3] {
+    match n {
+        0..=15 => BASIC[n as usize].1,
+        16..=231 => {
+            let i = n - 16;
+            [
+                LEVELS[(i / 36) as usize],
+                LEVELS[(i / 6 % 6) as usize],
+                LEVELS[(i % 6) as usize],
+            ]
+        }
+        // 232〜255 は灰色の 24 段階
+        _ => [8 + 10 * (n - 232); 3],
+    }
+}
+
+fn to_basic(rgb: [u8; 3]) -> Color {
+    BASIC
+        .iter()
+        .min_by_key(|(_, basic)| distance(*basic, rgb))
+        .map_or(Color::Reset, |(color, _)| *color)
+}
+
+// 立方体の一番近い点と灰色の一番近い段のうち、近い方
+fn to_indexed(rgb: [u8; 3]) -> u8 {
+    let level = |v: u8| {
+        (0..6)
+            .min_by_key(|&i| (LEVELS[i] as i32 - v as i32).abs())
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * level(rgb[0]) + 6 * level(rgb[1]) + level(rgb[2]);
+    let average = rgb.iter().map(|&v| v as u32).sum::<u32>() / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+    if distance(indexed_rgb(gray), rgb) < distance(indexed_rgb(cube), rgb) {
+        gray
+    } else {
+        cube
+    }
+}
@@ -0,0 +1,32 @@
+// Android / Termux 向けの互換モード
+//
+// Android では /proc/stat や他のユーザーのプロセスが見えないため、
+// 見えない情報は空の値を出さずに理由を表示する。
+use std::fs::File;
+
+/// 互換モードで読めなかったもの
+#[derive(Debug, Clone, Copy)]
+pub struct Restrictions {
+    /// /proc/stat が読めず、CPU 全体の使用率が分からない
+    pub cpu: bool,
+    /// /proc/net が読めず、接続一覧が分からない
+    #[cfg(feature = "net")]
+    pub net: bool,
+}
+
+/// Termux（または Android）の上で動いているか
+pub fn detected() -> bool {
+    cfg!(target_os = "android")
+        || std::env::var_os("TERMUX_VERSION").is_some()
+        || std::env::var("PREFIX").is_ok_and(|prefix| prefix.contains("/com.termux/"))
+}
+
+impl Restrictions {
+    pub fn probe() -> Self {
+        Self {
+            cpu: File::open("/proc/stat").is_err(),
+            #[cfg(feature = "net")]
+            net: File::open("/proc/net/tcp").is_err(),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+// 表示幅（全角文字や絵文字は 2 桁）を考慮した文字列の切り詰めと埋め草
+use ratatui::layout::Alignment;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// 端末上の表示幅
+pub fn width(text: &str) -> usize {
+    text.width()
+}
+
+/// 表示幅が width を超える場合は末尾を「…」にして収める
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        // 「…」の 1 桁分を残す
+        if used + w > width - 1 {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push('…');
+    out
+}
+
+/// 表示幅が width になるように空白を足す（幅を超える分は切り詰める）
+pub fn pad(text: &str, width: usize, alignment: Alignment) -> String {
+    let text = truncate(text, width);
+    let fill = " ".repeat(width.saturating_sub(text.width()));
+    match alignment {
+        Alignment::Right => format!("{fill}{text}"),
+        _ => format!("{text}{fill}"),
+    }
+}
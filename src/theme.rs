@@ -0,0 +1,148 @@
+// 配色。色覚特性に配慮したものも用意する
+use std::str::FromStr;
+
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::app::{CHANGE_TICKS, NEW_PROCESS_TICKS};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub name: &'static str,
+    pub cpu: Color,
+    pub memory: Color,
+    pub header: Color,
+    pub message: Color,
+    pub zombie: Color,
+    /// 終了した後もしばらく残しているプロセス
+    pub exited: Color,
+    /// warning ほどではないが気にしておきたい値
+    pub caution: Color,
+    /// バッテリー残量の低下など、注意を促す表示
+    pub warning: Color,
+    /// 新しいプロセスの強調色（現れてからの収集回数ごとに薄くしていく）
+    pub new_process: [Color; NEW_PROCESS_TICKS as usize],
+    /// 前回から大きく動いた CPU / メモリのセル（動いてからの収集回数ごとに薄くしていく）
+    pub changed: [Color; CHANGE_TICKS as usize],
+    pub watch_cpu: Color,
+    pub watch_memory: Color,
+    /// 色だけに頼らず、行頭の記号でも状態を示す
+    pub symbols: bool,
+}
+
+pub const DEFAULT: Theme = Theme {
+    name: "default",
+    cpu: Color::Yellow,
+    memory: Color::Cyan,
+    header: Color::Green,
+    message: Color::Yellow,
+    zombie: Color::Red,
+    exited: Color::DarkGray,
+    caution: Color::Yellow,
+    warning: Color::Red,
+    new_process: [Color::LightGreen, Color::Green, Color::DarkGray],
+    changed: [Color::LightMagenta, Color::Magenta, Color::DarkGray],
+    watch_cpu: Color::Yellow,
+    watch_memory: Color::Cyan,
+    symbols: false,
+};
+
+// 赤と緑を区別しにくい人向けに、青と橙を軸にした配色（Okabe-Ito に近い 256 色）
+pub const DEUTERANOPIA: Theme = Theme {
+    name: "deuteranopia",
+    cpu: Color::Indexed(214),
+    memory: Color::Indexed(75),
+    header: Color::Indexed(75),
+    message: Color::Indexed(227),
+    zombie: Color::Indexed(208),
+    exited: Color::Indexed(244),
+    caution: Color::Indexed(227),
+    warning: Color::Indexed(208),
+    new_process: [Color::Indexed(39), Color::Indexed(33), Color::Indexed(240)],
+    changed: [
+        Color::Indexed(227),
+        Color::Indexed(178),
+        Color::Indexed(240),
+    ],
+    watch_cpu: Color::Indexed(214),
+    watch_memory: Color::Indexed(75),
+    symbols: true,
+};
+
+// 1 型色覚では赤が暗く見えるため、注意を引く色には明るい黄色を使う
+pub const PROTANOPIA: Theme = Theme {
+    name: "protanopia",
+    cpu: Color::Indexed(220),
+    memory: Color::Indexed(75),
+    header: Color::Indexed(117),
+    message: Color::Indexed(227),
+    zombie: Color::Indexed(226),
+    exited: Color::Indexed(244),
+    caution: Color::Indexed(229),
+    warning: Color::Indexed(226),
+    new_process: [Color::Indexed(45), Color::Indexed(32), Color::Indexed(240)],
+    changed: [
+        Color::Indexed(213),
+        Color::Indexed(170),
+        Color::Indexed(240),
+    ],
+    watch_cpu: Color::Indexed(220),
+    watch_memory: Color::Indexed(75),
+    symbols: true,
+};
+
+pub const THEMES: [Theme; 3] = [DEFAULT, DEUTERANOPIA, PROTANOPIA];
+
+impl Default for Theme {
+    fn default() -> Self {
+        DEFAULT
+    }
+}
+
+pub fn by_name(name: &str) -> Option<Theme> {
+    THEMES.iter().find(|t| t.name == name).copied()
+}
+
+/// [[theme.threshold]] 使用率（%）が above 以上の棒やセルの見た目。
+/// 書いたときは既定の注意・警告の色の代わりに使う
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Threshold {
+    pub above: f64,
+    pub style: Style,
+}
+
+/// 一番高い above を超えたものを返す。thresholds は above の小さい順
+pub fn threshold(thresholds: &[Threshold], percent: f64) -> Option<Style> {
+    thresholds
+        .iter()
+        .rev()
+        .find(|t| percent >= t.above)
+        .map(|t| t.style)
+}
+
+/// "red bold" や "#ff8700 reversed" のように、色（名前、0〜255、#rrggbb）と
+/// bold / dim / italic / underlined / reversed を空白で区切って並べる
+pub fn parse_style(text: &str) -> Result<Style, String> {
+    let mut style = Style::default();
+    let mut color = false;
+    for word in text.split_whitespace() {
+        let modifier = match word.to_ascii_lowercase().as_str() {
+            "bold" => Modifier::BOLD,
+            "dim" => Modifier::DIM,
+            "italic" => Modifier::ITALIC,
+            "underlined" => Modifier::UNDERLINED,
+            "reversed" => Modifier::REVERSED,
+            _ if color => return Err(format!("more than one color in \"{text}\"")),
+            _ => {
+                let fg = Color::from_str(word).map_err(|_| format!("unknown color \"{word}\""))?;
+                style = style.fg(fg);
+                color = true;
+                continue;
+            }
+        };
+        style = style.add_modifier(modifier);
+    }
+    if style == Style::default() {
+        return Err("a style needs a color or a modifier".to_string());
+    }
+    Ok(style)
+}
@@ -0,0 +1,4127 @@
+// 画面描画
+mod rows;
+mod status;
+#[cfg(test)]
+mod tests;
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ratatui::text::Span;
+use ratatui::{
+    Frame,
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
+    style::{Color, Modifier, Style},
+    symbols,
+    text::Line,
+    widgets::{
+        Block, Borders, Cell, Clear, LineGauge, Paragraph, Row, Sparkline, Table, Widget, Wrap,
+    },
+};
+use sysinfo::ProcessStatus;
+
+use crate::access::Access;
+use crate::actions::{self, ProcessRef};
+use crate::alert::{self, TimerKind};
+#[cfg(feature = "environ")]
+use crate::app::EnvironView;
+use crate::app::{
+    AFFINITY_COLUMNS, AffinityDialog, App, BaselineView, COLUMN_COUNT, CgroupTreeView, ClickTarget,
+    CpuMode, FilesView, FrameTimes, PROCESS_ROWS, Panel, PeersView, SortKey, UserSort,
+};
+#[cfg(feature = "apple")]
+use crate::apple::{AppleSnapshot, CoreKind, ThermalPressure};
+use crate::baseline::Change;
+use crate::battery::{BatterySnapshot, ChargeState};
+use crate::cgroup::CgroupSnapshot;
+use crate::cgtree::TreeRow;
+use crate::config::{HostConfig, PanelKind};
+use crate::cpustat::{CpuBreakdown, KernelRates};
+use crate::custom::{self, CustomPanel};
+use crate::dashboard::HostStatus;
+use crate::filter::Filter;
+#[cfg(feature = "gpu")]
+use crate::gpu::GpuSnapshot;
+#[cfg(feature = "ipmi")]
+use crate::ipmi::IpmiSnapshot;
+#[cfg(feature = "journal")]
+use crate::journal::JournalView;
+use crate::layout::{self, LayoutItem, Slot};
+use crate::locale::Locale;
+use crate::meminfo::{HugePages, MemoryBreakdown, Overcommit, ThpMode};
+#[cfg(feature = "net")]
+use crate::net::{self, TcpStates};
+use crate::numa::{self, NumaSnapshot};
+use crate::pattern::Pattern;
+use crate::platform;
+#[cfg(feature = "plugin")]
+use crate::plugin::{PluginColumn, PluginPanel};
+use crate::pressure::{self, PressureAverages, PressureSnapshot};
+#[cfg(feature = "profile")]
+use crate::profile::ProfileView;
+use crate::rpi::{self, SocSnapshot};
+use crate::sampler::{CPU_HISTORY, CpuSnapshot, ProcessInfo, Snapshot, SystemInfo};
+use crate::search::Search;
+use crate::sensors::SensorsSnapshot;
+use crate::shm::ShmSnapshot;
+#[cfg(feature = "smart")]
+use crate::smart::SmartSnapshot;
+use crate::term;
+use crate::text;
+use crate::theme::{self, Theme};
+use crate::wchan;
+use crate::zram::{self, CompressedSwap};
+pub use rows::RowCache;
+use status::StatusBar;
+
+/// プロセス表の既定の列幅（SortKey::COLUMNS の順）
+const PROCESS_WIDTHS: [u16; COLUMN_COUNT] = [
+    8,
+    10,
+    10,
+    25,
+    14,
+    9,
+    28,
+    5,
+    6,
+    10,
+    CPU_HISTORY as u16,
+    12,
+    12,
+    10,
+    10,
+    8,
+    8,
+    6,
+    10,
+    10,
+    10,
+    12,
+    9,
+];
+
+/// 表示する列と幅（SortKey::COLUMNS の順）。出さない列は幅 0
+// Container 列はコンテナ内のプロセスがあるときだけ出す
+fn process_widths(app: &App, show_container: bool) -> [u16; COLUMN_COUNT] {
+    let mut widths = [0; COLUMN_COUNT];
+    for (i, key) in SortKey::COLUMNS.into_iter().enumerate() {
+        if !show_container && key == SortKey::Container {
+            continue;
+        }
+        // Swap はフォールトの列（m）と一緒にも出す
+        let swap = key == SortKey::Swap && app.page_faults;
+        if key.is_memory_detail() && !app.memory_detail && !swap {
+            continue;
+        }
+        if key.is_fault() && !app.page_faults {
+            continue;
+        }
+        if key == SortKey::History && !app.cpu_history {
+            continue;
+        }
+        if key == SortKey::Node && !app.numa_nodes {
+            continue;
+        }
+        if key == SortKey::Files && !app.fd_counts {
+            continue;
+        }
+        if key == SortKey::WaitChannel && !app.wait_channels {
+            continue;
+        }
+        if key == SortKey::Category && !app.category_column {
+            continue;
+        }
+        if key.is_process_time() && !app.process_times {
+            continue;
+        }
+        if key.is_traffic() && !app.net_traffic {
+            continue;
+        }
+        let configured = app.columns.get(&key).and_then(|c| c.min_width);
+        // 見出しと並び順の矢印は必ず収まるようにする
+        let header = text::width(key.label()) as u16 + 2;
+        widths[i] = configured.unwrap_or(PROCESS_WIDTHS[i]).max(header);
+    }
+    widths
+}
+
+fn process_columns(widths: [u16; COLUMN_COUNT]) -> impl Iterator<Item = (SortKey, u16)> {
+    SortKey::COLUMNS
+        .into_iter()
+        .zip(widths)
+        .filter(|(_, width)| *width > 0)
+}
+
+// 数値の列は既定で右寄せにする
+pub fn column_alignment(app: &App, key: SortKey) -> Alignment {
+    app.columns
+        .get(&key)
+        .and_then(|c| c.align)
+        .unwrap_or(if key.is_numeric() {
+            Alignment::Right
+        } else {
+            Alignment::Left
+        })
+}
+
+/// 描画の結果。マウス操作の判定と計測に使う
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Drawn {
+    pub processes: Rect,
+    /// プロセス表に収まった行数（表を出していなければ 0）
+    pub process_rows: usize,
+    /// 開いている一覧のポップアップに収まった行数（出していなければ 0）
+    pub popup_rows: usize,
+    /// プロセス表の列幅（SortKey::COLUMNS の順、出していない列は 0）
+    pub widths: [u16; COLUMN_COUNT],
+    /// 並べ替えと表の整形にかかった時間
+    pub prepare_time: Duration,
+    /// ステータスバーの位置。ウィンドウに重なって一部でも隠れていれば None
+    pub status: Option<Rect>,
+}
+
+/// 上下の枠を含むパネルの高さ
+const HEADER_HEIGHT: u16 = 3;
+const PROCESS_HEIGHT: u16 = PROCESS_ROWS as u16 + 3;
+/// Info は 3 行以上表示できるときだけ出す
+const INFO_MIN_HEIGHT: u16 = 5;
+/// これより狭いと何も表示しない
+const MIN_WIDTH: u16 = 40;
+
+/// 画面の大きさとレイアウトに合わせて決めたパネルの配置（上から順）
+struct Panels(Vec<(Slot, Rect)>);
+
+impl Panels {
+    fn get(&self, slot: Slot) -> Option<Rect> {
+        self.0
+            .iter()
+            .find(|(s, _)| *s == slot)
+            .map(|(_, area)| *area)
+    }
+}
+
+/// グラフのパネルは 2 行以上の棒が出せるときだけ出す
+const GRAPHS_MIN_HEIGHT: u16 = 4;
+
+// 高さの決まったパネルの高さ（0 なら出さない）。権限が無くて読めなかったパネルは理由を 1 行で出す
+fn fixed_height(slot: Slot, app: &App, snapshot: &Snapshot) -> u16 {
+    match data_height(slot, app, snapshot) {
+        0 if app.access.denied(slot).is_some() => HEADER_HEIGHT,
+        height => height,
+    }
+}
+
+// 読めた値を出すのに要る高さ
+fn data_height(slot: Slot, app: &App, snapshot: &Snapshot) -> u16 {
+    let shown = |shown: bool| if shown { HEADER_HEIGHT } else { 0 };
+    match slot {
+        Slot::Cpu => HEADER_HEIGHT,
+        // 内訳が分かれば積み上げの棒を 1 行足す
+        Slot::Memory => shown(true) + u16::from(snapshot.memory.breakdown.is_some()),
+        Slot::Top => shown(!snapshot.processes.is_empty()),
+        // 見出しと上位の LEADERS 行
+        Slot::Leaders => {
+            if snapshot.processes.is_empty() {
+                0
+            } else {
+                3 + LEADERS as u16
+            }
+        }
+        Slot::Watch => match app.pinned.len() as u16 {
+            0 => 0,
+            pinned => 2 + 2 * pinned,
+        },
+        Slot::Cgroup => shown(snapshot.cgroup.is_some()),
+        // 見出しと cpu / memory / io の 3 行
+        Slot::Pressure => {
+            if snapshot.pressure.is_some() {
+                6
+            } else {
+                0
+            }
+        }
+        Slot::CpuTime => cpu_time_height(&snapshot.cpu),
+        Slot::Kernel => shown(snapshot.cpu.kernel.is_some()),
+        // 見出しとノードごとに 1 行
+        Slot::Numa => snapshot
+            .numa
+            .as_ref()
+            .map_or(0, |n| 3 + n.nodes.len() as u16),
+        // 見出しと tmpfs ごとに 1 行、最後に共有メモリの内訳
+        Slot::Shm => snapshot
+            .shm
+            .as_ref()
+            .map_or(0, |s| 4 + s.mounts.len().min(SHM_MOUNTS) as u16),
+        // ヒュージページ、THP、コミットの 3 行
+        Slot::HugePages => {
+            if snapshot.memory.huge_pages.is_some() {
+                5
+            } else {
+                0
+            }
+        }
+        // 見出しとデバイスごとに 1 行
+        Slot::CompressedSwap => snapshot.compressed_swap.as_ref().map_or(0, |c| {
+            2 + c.zram.len() as u16 + u16::from(c.zswap.is_some())
+        }),
+        Slot::Battery => shown(snapshot.battery.is_some()),
+        Slot::Soc => shown(snapshot.soc.is_some()),
+        Slot::Sensors => sensors_height(snapshot),
+        Slot::Gpu => gpu_height(snapshot),
+        Slot::Ipmi => ipmi_height(snapshot),
+        Slot::Smart => smart_height(snapshot),
+        Slot::Apple => apple_height(snapshot),
+        Slot::Containers => containers_height(snapshot),
+        Slot::Ports => ports_height(snapshot),
+        Slot::Tcp => tcp_height(snapshot),
+        Slot::Custom => custom_area_height(app),
+        // 伸びるパネルの最小の高さ
+        Slot::Graphs => GRAPHS_MIN_HEIGHT,
+        Slot::Processes => PROCESS_HEIGHT,
+        Slot::Info => INFO_MIN_HEIGHT,
+    }
+}
+
+// レイアウトに並べた順に上から置く。Alt+数字で隠したパネルは場所を取らない。A ではプロセス表だけにする
+// 広い端末では Info とセンサーのパネルを右の列に移し、左右それぞれで上から積む
+fn plan_panels(size: Rect, app: &App, snapshot: &Snapshot) -> Option<Panels> {
+    let inner = size.inner(Margin::new(1, 1));
+    let layout = &app.layouts[app.layout];
+    let show_all = app.show_all && app.shows(Panel::Processes) && layout.contains(Slot::Processes);
+    let shown = |slot: Slot| match slot {
+        Slot::Cpu => app.shows(Panel::Cpu),
+        Slot::Memory => app.shows(Panel::Memory),
+        Slot::Processes => app.shows(Panel::Processes),
+        Slot::Info => app.shows(Panel::Info),
+        _ => true,
+    };
+    let items: Vec<LayoutItem> = layout
+        .items
+        .iter()
+        .copied()
+        .filter(|item| shown(item.slot) && (!show_all || item.slot == Slot::Processes))
+        .collect();
+    let page = app
+        .page_rows
+        .filter(|_| !show_all)
+        .map(|rows| rows.saturating_add(3).min(u16::MAX as usize) as u16);
+    let split = app.side_column.applies(inner.width, MIN_WIDTH)
+        && items
+            .iter()
+            .any(|item| item.slot.side() && fixed_height(item.slot, app, snapshot) > 0);
+    if !split {
+        return stack(&items, inner, page, app, snapshot).map(Panels);
+    }
+    let (main, side): (Vec<LayoutItem>, Vec<LayoutItem>) =
+        items.into_iter().partition(|item| !item.slot.side());
+    let [left, right] = Layout::horizontal([
+        Constraint::Fill(1),
+        Constraint::Length(layout::side_width(inner.width)),
+    ])
+    .spacing(1)
+    .areas(inner);
+    let mut panels = stack(&main, left, page, app, snapshot)?;
+    panels.extend(stack(&side, right, None, app, snapshot)?);
+    Some(Panels(panels))
+}
+
+// 1 つの列に上から積む。CPU とプロセス表は必ず表示し、ほかは残りの高さに入るものを前から選ぶ
+// 余った行はプロセス表・グラフ・Info が重みの割合で分ける
+// page（[processes] rows）があればプロセス表はその高さまでで、残りはほかの伸びるパネルが使う
+fn stack(
+    items: &[LayoutItem],
+    area: Rect,
+    page: Option<u16>,
+    app: &App,
+    snapshot: &Snapshot,
+) -> Option<Vec<(Slot, Rect)>> {
+    // （高さ、重み、上限）
+    let mut heights: Vec<(u16, u16, u16)> = items
+        .iter()
+        .map(|item| {
+            let height = fixed_height(item.slot, app, snapshot);
+            match item.slot {
+                Slot::Processes => match page {
+                    Some(page) => (PROCESS_HEIGHT.min(page), item.weight, page),
+                    None => (height, item.weight, u16::MAX),
+                },
+                slot if slot.grows() => (height, item.weight, u16::MAX),
+                _ => (height, 0, height),
+            }
+        })
+        .collect();
+    let required = |slot: Slot| matches!(slot, Slot::Cpu | Slot::Processes);
+    let required_height: u16 = items
+        .iter()
+        .zip(&heights)
+        .filter(|(item, _)| required(item.slot))
+        .map(|(_, (height, _, _))| *height)
+        .sum();
+    if area.width < MIN_WIDTH || area.height < required_height {
+        return None;
+    }
+    let mut spare = area.height - required_height;
+    let chosen: Vec<bool> = items
+        .iter()
+        .zip(&heights)
+        .map(|(item, (height, _, _))| {
+            if required(item.slot) {
+                return true;
+            }
+            let fits = *height > 0 && spare >= *height;
+            if fits {
+                spare -= height;
+            }
+            fits
+        })
+        .collect();
+    let mut growing: Vec<(u16, u16, u16)> = heights
+        .iter()
+        .zip(&chosen)
+        .filter(|(_, chosen)| **chosen)
+        .map(|(height, _)| *height)
+        .collect();
+    layout::share(spare, &mut growing);
+    let mut growing = growing.into_iter();
+    for (height, _) in heights
+        .iter_mut()
+        .zip(&chosen)
+        .filter(|(_, chosen)| **chosen)
+    {
+        *height = growing.next().unwrap_or(*height);
+    }
+    let placed: Vec<(Slot, u16)> = items
+        .iter()
+        .zip(&heights)
+        .zip(&chosen)
+        .filter(|(_, chosen)| **chosen)
+        .map(|((item, (height, _, _)), _)| (item.slot, *height))
+        .collect();
+    let areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(placed.iter().map(|(_, height)| Constraint::Length(*height)))
+        .split(area);
+    Some(
+        placed
+            .iter()
+            .map(|(slot, _)| *slot)
+            .zip(areas.iter().copied())
+            .collect(),
+    )
+}
+
+pub fn draw(f: &mut Frame, app: &App, snapshot: &Snapshot) -> Drawn {
+    let mut drawn = draw_screen(f, app, snapshot);
+    finish(f.buffer_mut(), app);
+    // 描いたとおりに見えているときだけ、後でステータスバーだけを描き直せる
+    drawn.status = drawn.status.filter(|&area| {
+        let status = status_bar(app, area);
+        area.positions()
+            .all(|position| f.buffer_mut()[position] == status[position])
+    });
+    drawn
+}
+
+/// 時計のほかに何も変わっていないときに、前の画面のステータスバーだけを描き直した画面。
+/// 前の画面と同じなら None（端末に何も送らなくてよい）
+pub fn redraw_status(previous: &Buffer, area: Rect, app: &App) -> Option<Buffer> {
+    let status = status_bar(app, area);
+    if area
+        .positions()
+        .all(|position| previous[position] == status[position])
+    {
+        return None;
+    }
+    let mut buffer = previous.clone();
+    buffer.merge(&status);
+    Some(buffer)
+}
+
+// ステータスバーだけを描いた、その大きさのバッファ
+fn status_bar(app: &App, area: Rect) -> Buffer {
+    let mut buffer = Buffer::empty(area);
+    StatusBar::new(app).render(area, &mut buffer);
+    finish(&mut buffer, app);
+    buffer
+}
+
+fn finish(buffer: &mut Buffer, app: &App) {
+    if app.plain {
+        term::plainify(buffer);
+    }
+    // 罫線などを出せない端末では、描き終えてから ASCII に置き換える
+    if app.ascii {
+        term::asciify(buffer);
+    }
+}
+
+fn draw_screen(f: &mut Frame, app: &App, snapshot: &Snapshot) -> Drawn {
+    let size = f.area();
+
+    // レイアウト（縦分割）。小さい端末では優先度の低いパネルを省く
+    let Some(panels) = plan_panels(size, app, snapshot) else {
+        let text = format!(
+            "Terminal too small ({}x{}), need at least {}x{}",
+            size.width,
+            size.height,
+            MIN_WIDTH + 2,
+            HEADER_HEIGHT + PROCESS_HEIGHT + 2
+        );
+        f.render_widget(Paragraph::new(text).wrap(Wrap { trim: true }), size);
+        return Drawn::default();
+    };
+
+    let locale = &app.locale;
+    let theme = &app.theme;
+
+    // CPU情報
+    if let Some(area) = panels.get(Slot::Cpu) {
+        draw_cpu(f, app, snapshot, area);
+    }
+
+    // メモリ情報
+    if let Some(area) = panels.get(Slot::Memory) {
+        draw_memory(f, app, snapshot, area);
+    }
+
+    if let Some(area) = panels.get(Slot::Graphs) {
+        draw_graphs(f, app, area);
+    }
+
+    if let Some(area) = panels.get(Slot::Top) {
+        draw_top(f, app, snapshot, area);
+    }
+
+    if let Some(area) = panels.get(Slot::Leaders) {
+        draw_leaders(f, app, snapshot, area);
+    }
+
+    if let (Some(area), Some(cgroup)) = (panels.get(Slot::Cgroup), &snapshot.cgroup) {
+        draw_cgroup(f, app, cgroup, area);
+    }
+
+    if let (Some(area), Some(pressure)) = (panels.get(Slot::Pressure), &snapshot.pressure) {
+        draw_pressure(f, app, pressure, area);
+    }
+
+    if let (Some(area), Some(breakdown)) = (panels.get(Slot::CpuTime), snapshot.cpu.breakdown) {
+        draw_cpu_time(f, app, breakdown, &snapshot.cpu.core_breakdown, area);
+    }
+
+    if let (Some(area), Some(kernel)) = (panels.get(Slot::Kernel), snapshot.cpu.kernel) {
+        draw_kernel(f, app, kernel, area);
+    }
+
+    if let (Some(area), Some(numa)) = (panels.get(Slot::Numa), &snapshot.numa) {
+        draw_numa(f, app, numa, area);
+    }
+
+    if let (Some(area), Some(shm)) = (panels.get(Slot::Shm), &snapshot.shm) {
+        draw_shm(f, app, shm, snapshot.memory.breakdown, area);
+    }
+
+    if let (Some(area), Some(huge)) = (panels.get(Slot::HugePages), snapshot.memory.huge_pages) {
+        draw_huge_pages(f, app, huge, area);
+    }
+
+    if let (Some(area), Some(compressed)) =
+        (panels.get(Slot::CompressedSwap), &snapshot.compressed_swap)
+    {
+        draw_compressed_swap(f, app, compressed, area);
+    }
+
+    if let (Some(area), Some(battery)) = (panels.get(Slot::Battery), &snapshot.battery) {
+        draw_battery(f, app, battery, area);
+    }
+
+    if let (Some(area), Some(soc)) = (panels.get(Slot::Soc), &snapshot.soc) {
+        draw_soc(f, app, soc, area);
+    }
+
+    if let (Some(area), Some(sensors)) = (panels.get(Slot::Sensors), &snapshot.sensors) {
+        draw_sensors(f, app, sensors, area);
+    }
+
+    #[cfg(feature = "gpu")]
+    if let (Some(area), Some(gpu)) = (panels.get(Slot::Gpu), &snapshot.gpu) {
+        draw_gpu(f, app, snapshot, gpu, area);
+    }
+
+    #[cfg(feature = "ipmi")]
+    if let (Some(area), Some(ipmi)) = (panels.get(Slot::Ipmi), &snapshot.ipmi) {
+        draw_ipmi(f, app, ipmi, area);
+    }
+
+    #[cfg(feature = "smart")]
+    if let (Some(area), Some(smart)) = (panels.get(Slot::Smart), &snapshot.smart) {
+        draw_smart(f, app, smart, area);
+    }
+
+    #[cfg(feature = "apple")]
+    if let (Some(area), Some(apple)) = (panels.get(Slot::Apple), &snapshot.apple) {
+        draw_apple(f, app, apple, area);
+    }
+
+    // 権限が無くて読めなかったパネルには、空のままにせず理由を書く
+    for &(slot, area) in &panels.0 {
+        if let Some((name, path)) = app.access.denied(slot)
+            && data_height(slot, app, snapshot) == 0
+        {
+            draw_denied(f, app, name, path, area);
+        }
+    }
+
+    let mut drawn = Drawn::default();
+    if let Some(area) = panels.get(Slot::Processes) {
+        drawn = draw_processes(f, app, snapshot, area);
+    }
+
+    if let Some(area) = panels.get(Slot::Watch) {
+        draw_watch(f, app, snapshot, area);
+    }
+
+    if let Some(area) = panels.get(Slot::Containers) {
+        draw_containers(f, app, snapshot, area);
+    }
+
+    #[cfg(feature = "net")]
+    if let Some(area) = panels.get(Slot::Ports) {
+        draw_ports(f, snapshot, area);
+    }
+
+    #[cfg(feature = "net")]
+    if let (Some(area), Some(tcp)) = (panels.get(Slot::Tcp), snapshot.tcp_states) {
+        draw_tcp_states(f, app, tcp, area);
+    }
+
+    if let Some(area) = panels.get(Slot::Custom) {
+        draw_custom(f, app, area);
+    }
+
+    if let Some(area) = panels.get(Slot::Info) {
+        draw_info(f, locale, &snapshot.info, &app.access, area);
+    }
+
+    if let Some(pid) = app.detail
+        && let Some(process) = snapshot.processes.iter().find(|p| p.pid == pid)
+    {
+        draw_detail(f, app, process);
+    }
+
+    if let Some(pid) = app.threads
+        && let Some(process) = snapshot.processes.iter().find(|p| p.pid == pid)
+    {
+        draw_threads(f, app, snapshot, process);
+    }
+
+    // 発報中の警告は上端の余白に表示する。Pi の電圧低下は設定が無くても出す
+    let under_voltage = snapshot
+        .soc
+        .as_ref()
+        .is_some_and(|soc| soc.now(rpi::UNDER_VOLTAGE))
+        .then(|| "Under-voltage: check the power supply".to_string());
+    let alerts: Vec<String> = under_voltage
+        .into_iter()
+        .chain(app.alerts.active().map(|a| {
+            format!(
+                "{}: {} for {}s",
+                a.name,
+                a.detail,
+                a.since.elapsed().as_secs()
+            )
+        }))
+        .collect();
+    // プラグインが返した警告と、プラグインが動いていない理由
+    #[cfg(feature = "plugin")]
+    let alerts: Vec<String> = alerts
+        .into_iter()
+        .chain(app.plugins.iter().flat_map(|plugin| {
+            let output = plugin.output();
+            let error = output
+                .error
+                .map(|error| format!("Plugin {}: {error}", plugin.name));
+            error.into_iter().chain(
+                output
+                    .alerts
+                    .into_iter()
+                    .map(|(name, detail)| format!("{name}: {detail}")),
+            )
+        }))
+        .collect();
+    if !alerts.is_empty() {
+        let marker = if theme.symbols { "! " } else { "" };
+        let bar = Paragraph::new(format!("{marker}ALERT {}", alerts.join(" | "))).style(
+            Style::default()
+                .fg(theme.warning)
+                .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+        );
+        f.render_widget(
+            bar,
+            Rect::new(size.x + 1, size.y, size.width.saturating_sub(2), 1),
+        );
+    }
+
+    // 保存したスナップショットの時刻は上端の右に表示する
+    #[cfg(feature = "history")]
+    if let Some(history) = &app.history {
+        let text = format!(" History: {history} ");
+        let width = (text::width(&text) as u16).min(size.width.saturating_sub(2));
+        let bar = Paragraph::new(text).style(
+            Style::default()
+                .fg(theme.header)
+                .add_modifier(Modifier::REVERSED),
+        );
+        f.render_widget(bar, Rect::new(size.right() - 1 - width, size.y, width, 1));
+    }
+
+    // ピン留めしたプロセスの終了を画面の反転で知らせる
+    if app.flashing() {
+        f.buffer_mut()
+            .set_style(size, Style::default().add_modifier(Modifier::REVERSED));
+    }
+
+    // ステータスバーは下端の余白に表示する
+    let status_area = Rect::new(
+        size.x + 1,
+        size.bottom() - 1,
+        size.width.saturating_sub(2),
+        1,
+    );
+    f.render_widget(StatusBar::new(app), status_area);
+    drawn.status = Some(status_area);
+
+    #[cfg(feature = "net")]
+    if let Some(row) = app.connections {
+        drawn.popup_rows = draw_connections(f, app, snapshot, row);
+    }
+
+    #[cfg(any(feature = "systemd", windows))]
+    if let Some(row) = app.services {
+        drawn.popup_rows = draw_services(f, app, snapshot, row);
+    }
+
+    if let Some(row) = app.users {
+        drawn.popup_rows = draw_users(f, app, snapshot, row);
+    }
+
+    if let Some(row) = app.events_view {
+        drawn.popup_rows = draw_events(f, app, row);
+    }
+
+    if let Some(dialog) = &app.affinity {
+        draw_affinity(f, dialog);
+    }
+
+    if let Some(view) = &app.files {
+        drawn.popup_rows = draw_files(f, view);
+    }
+
+    if let Some(view) = &app.peers {
+        drawn.popup_rows = draw_peers(f, snapshot, view);
+    }
+
+    if let Some(view) = &app.baseline_view {
+        drawn.popup_rows = draw_baseline(f, app, view);
+    }
+
+    if let Some(view) = &app.cgroup_tree {
+        drawn.popup_rows = draw_cgroup_tree(f, app, snapshot, view);
+    }
+
+    #[cfg(feature = "environ")]
+    if let Some(view) = &app.environ {
+        drawn.popup_rows = draw_environ(f, view);
+    }
+
+    #[cfg(feature = "journal")]
+    if let Some(view) = &app.journal {
+        drawn.popup_rows = draw_journal(f, view);
+    }
+
+    #[cfg(feature = "profile")]
+    if let Some(view) = &app.cpu_profile {
+        draw_cpu_profile(f, app, view);
+    }
+
+    if app.watchdog_log {
+        draw_watchdog(f, app);
+    }
+
+    if app.heatmap {
+        draw_heatmap(f, app);
+    }
+
+    if !app.health.is_empty() {
+        draw_health(f, app);
+    }
+
+    if let Some(search) = &app.search {
+        drawn.popup_rows = draw_search(f, search);
+    }
+
+    if let Some(times) = app.profile {
+        draw_profile(f, app, snapshot, times, size);
+    }
+
+    drawn
+}
+
+/// プラグインの列の幅の上限
+#[cfg(feature = "plugin")]
+const PLUGIN_COLUMN_WIDTH: usize = 16;
+
+// プラグインが返した列と、その幅（見出しと値の広い方）
+#[cfg(feature = "plugin")]
+fn plugin_columns(app: &App) -> Vec<(PluginColumn, u16)> {
+    app.plugins
+        .iter()
+        .flat_map(|plugin| plugin.output().columns)
+        .map(|column| {
+            let width = column
+                .values
+                .values()
+                .map(|value| text::width(value))
+                .chain([text::width(&column.name)])
+                .max()
+                .unwrap_or(0)
+                .min(PLUGIN_COLUMN_WIDTH);
+            (column, width as u16)
+        })
+        .collect()
+}
+
+// プロセス情報（枠に収まる行数だけ、選択行が見える位置から）
+fn draw_processes(f: &mut Frame, app: &App, snapshot: &Snapshot, area: Rect) -> Drawn {
+    let theme = &app.theme;
+    // 枠の上下と見出しの 3 行を除いた分。Row は見えている行の分だけ作る
+    let process_rows = area.height.saturating_sub(3) as usize;
+    let prepare_started = Instant::now();
+    let processes = app.visible_processes(snapshot);
+    let show_container = snapshot.processes.iter().any(|p| p.container.is_some());
+    let widths = process_widths(app, show_container);
+    let columns: Vec<(SortKey, u16)> = process_columns(widths).collect();
+    // 全角文字を含む名前でも列がずれないよう、表示幅で切り詰める
+    let aligned = |key: SortKey, width: u16, cell: String| {
+        Cell::from(
+            Line::from(text::truncate(&cell, width as usize)).alignment(column_alignment(app, key)),
+        )
+    };
+    let shown: Vec<&ProcessInfo> = processes
+        .iter()
+        .skip(app.offset)
+        .take(process_rows)
+        .map(|p| p.as_ref())
+        .collect();
+    let mut cache = app.row_cache.lock().expect("row cache lock");
+    cache.update(app, snapshot, &columns, &shown);
+    let cache = &*cache;
+    #[cfg(feature = "plugin")]
+    let plugin_columns = plugin_columns(app);
+    // 探している（l）パターンと、絞り込みに使った名前のパターン
+    let patterns: Vec<&Pattern> = app
+        .locate
+        .iter()
+        .chain(app.name_filter.iter().flat_map(Filter::name_patterns))
+        .collect();
+    let rows: Vec<Row> = shown
+        .iter()
+        .map(|p| {
+            let cells = columns
+                .iter()
+                .zip(cache.cells(p.pid))
+                .map(|((key, _), cell)| {
+                    let line = if *key == SortKey::Name && !patterns.is_empty() {
+                        highlight_matches(app, cell, &patterns)
+                    } else {
+                        Line::from(cell.as_str())
+                    };
+                    let cell = Cell::from(line.alignment(column_alignment(app, *key)));
+                    // 増え続けているメモリは、動いた直後の色より優先して警告の色にする
+                    if *key == SortKey::Memory && app.leaks.growth(p.pid).is_some() {
+                        return cell.style(
+                            Style::default()
+                                .fg(theme.warning)
+                                .add_modifier(Modifier::BOLD),
+                        );
+                    }
+                    match app.change_age(p.pid, *key) {
+                        Some(age) => cell.style(changed_style(app, age)),
+                        None => cell,
+                    }
+                });
+            #[cfg(feature = "plugin")]
+            let cells = cells.chain(plugin_columns.iter().map(|(column, width)| {
+                let value = column.values.get(&p.pid).map_or("-", String::as_str);
+                Cell::from(text::truncate(value, *width as usize))
+            }));
+            Row::new(cells).style(process_style(app, p))
+        })
+        .collect();
+    let header: Vec<Cell> = columns
+        .iter()
+        .map(|(key, width)| {
+            let label = app
+                .locale
+                .text(if *key == SortKey::Name && app.full_command {
+                    "Command"
+                } else {
+                    key.label()
+                });
+            let label = if *key == app.sort {
+                format!("{label} {}", sort_arrow(app))
+            } else {
+                label.to_string()
+            };
+            aligned(*key, *width, label)
+        })
+        .collect();
+    let widths_of_columns = columns.iter().map(|(_, w)| *w);
+    // プラグインの列は並べ替えに使えないので、右端に足すだけ
+    #[cfg(feature = "plugin")]
+    let header: Vec<Cell> = header
+        .into_iter()
+        .chain(
+            plugin_columns
+                .iter()
+                .map(|(column, width)| Cell::from(text::truncate(&column.name, *width as usize))),
+        )
+        .collect();
+    #[cfg(feature = "plugin")]
+    let widths_of_columns = widths_of_columns.chain(plugin_columns.iter().map(|(_, w)| *w));
+    let table = Table::new(rows, widths_of_columns.map(Constraint::Length))
+        .header(Row::new(header).style(Style::default().fg(theme.header)))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(process_title(app)),
+        );
+    let prepare_time = prepare_started.elapsed();
+    f.render_widget(table, area);
+    // スクリーンリーダーがカーソルの行を読むので、選んでいるプロセスの行に置く
+    if app.plain
+        && let Some(row) = shown.iter().position(|p| app.selected == Some(p.pid))
+    {
+        f.set_cursor_position((area.x + 1, area.y + 2 + row as u16));
+    }
+
+    Drawn {
+        processes: area,
+        process_rows,
+        widths,
+        prepare_time,
+        popup_rows: 0,
+        status: None,
+    }
+}
+
+// 一致した部分を目立たせる。重なった一致はまとめる
+fn highlight_matches<'a>(app: &App, text: &'a str, patterns: &[&Pattern]) -> Line<'a> {
+    let mut ranges: Vec<Range<usize>> = patterns
+        .iter()
+        .filter_map(|pattern| pattern.find(text))
+        .filter(|range| !range.is_empty())
+        .collect();
+    ranges.sort_by_key(|range| range.start);
+    let style = Style::default()
+        .fg(app.theme.caution)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let mut spans = Vec::new();
+    let mut end = 0;
+    for range in ranges {
+        if range.end <= end {
+            continue;
+        }
+        let start = range.start.max(end);
+        spans.push(Span::raw(&text[end..start]));
+        spans.push(Span::styled(&text[start..range.end], style));
+        end = range.end;
+    }
+    spans.push(Span::raw(&text[end..]));
+    Line::from(spans)
+}
+
+// コンテナ内ではホスト全体の値より意味があるので、cgroup の上限と使用量を出す
+fn draw_cgroup(f: &mut Frame, app: &App, cgroup: &CgroupSnapshot, area: Rect) {
+    let locale = &app.locale;
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let cores = |value: Option<f64>| optional(value.map(|c| locale.float(c, 2)));
+    let mut text = format!(
+        "CPU: {} / {} cores, Memory: {} / {}",
+        cores(cgroup.cpu_usage),
+        cgroup
+            .cpu_limit
+            .map_or_else(|| "max".to_string(), |c| locale.float(c, 2)),
+        locale.megabytes(cgroup.memory_used, 0),
+        cgroup
+            .memory_limit
+            .map_or_else(|| "max".to_string(), |m| locale.megabytes(m, 0)),
+    );
+    if let Some(ratio) = cgroup.memory_ratio() {
+        text.push_str(&format!(" ({})", locale.percent(ratio * 100.0)));
+    }
+    let mut style = Style::default();
+    if cgroup.memory_near_limit() {
+        style = style.fg(app.theme.warning).add_modifier(Modifier::BOLD);
+        if app.theme.symbols {
+            text = format!("! {text} (near memory.max)");
+        }
+    }
+    let block = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Cgroup {}", cgroup.path)),
+        )
+        .style(style);
+    f.render_widget(block, area);
+}
+
+// 資源ごとの some / full の平均。高い値ほど目立つ色にする
+fn draw_pressure(f: &mut Frame, app: &App, pressure: &PressureSnapshot, area: Rect) {
+    let locale = &app.locale;
+    let cell = |value: f64| {
+        let mut text = locale.float(value, 2);
+        let mut style = Style::default();
+        if value >= pressure::CRITICAL_PERCENT {
+            style = style.fg(app.theme.warning).add_modifier(Modifier::BOLD);
+            if app.theme.symbols {
+                text.push_str(" !");
+            }
+        } else if value >= pressure::CAUTION_PERCENT {
+            style = style.fg(app.theme.caution);
+        }
+        Cell::from(text).style(style)
+    };
+    let averages = |values: Option<PressureAverages>| -> Vec<Cell> {
+        match values {
+            Some(v) => vec![cell(v.avg10), cell(v.avg60), cell(v.avg300)],
+            None => vec![Cell::from("-"), Cell::from("-"), Cell::from("-")],
+        }
+    };
+    let rows: Vec<Row> = pressure
+        .resources()
+        .into_iter()
+        .map(|(name, line)| {
+            let mut cells = vec![Cell::from(name)];
+            cells.extend(averages(line.map(|l| l.some)));
+            cells.extend(averages(line.and_then(|l| l.full)));
+            Row::new(cells)
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [Constraint::Length(8)]
+            .into_iter()
+            .chain([Constraint::Length(8); 6]),
+    )
+    .header(
+        Row::new(vec![
+            "", "some10", "some60", "some300", "full10", "full60", "full300",
+        ])
+        .style(Style::default().fg(app.theme.header)),
+    )
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(app.locale.text("Pressure (% stalled)")),
+    );
+    f.render_widget(table, area);
+}
+
+/// 共有メモリのパネルに出す tmpfs の数（使用量の多い順）
+const SHM_MOUNTS: usize = 5;
+
+// tmpfs ごとの使用量と、SysV の共有メモリ、それ以外（memfd や共有の匿名メモリ）の内訳
+// それ以外は Shmem から tmpfs と SysV を引いた残りで、おおよその値
+fn draw_shm(
+    f: &mut Frame,
+    app: &App,
+    shm: &ShmSnapshot,
+    breakdown: Option<MemoryBreakdown>,
+    area: Rect,
+) {
+    let locale = &app.locale;
+    let block = Block::default().borders(Borders::ALL).title(format!(
+        "{} ({} tmpfs)",
+        locale.text("Shared memory"),
+        shm.mounts.len()
+    ));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let [table_area, summary_area] =
+        Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(inner);
+    let rows: Vec<Row> = shm
+        .mounts
+        .iter()
+        .take(SHM_MOUNTS)
+        .map(|mount| {
+            let percent = mount.used as f64 / mount.total.max(1) as f64 * 100.0;
+            Row::new(vec![
+                mount.path.clone(),
+                locale.megabytes(mount.used, 0),
+                locale.megabytes(mount.total, 0),
+                locale.percent(percent),
+            ])
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(12),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(8),
+        ],
+    )
+    .header(
+        Row::new(vec!["Mount", "Used", "Size", "Use%"])
+            .style(Style::default().fg(app.theme.header)),
+    );
+    f.render_widget(table, table_area);
+    let mut parts = Vec::new();
+    if let Some((segments, bytes)) = shm.sysv {
+        parts.push(format!(
+            "SysV: {} in {segments} segments",
+            locale.megabytes(bytes, 0)
+        ));
+    }
+    if let Some(breakdown) = breakdown {
+        let sysv = shm.sysv.map_or(0, |(_, bytes)| bytes);
+        let other = breakdown.shared.saturating_sub(shm.tmpfs_used() + sysv);
+        parts.push(format!("memfd and other: {}", locale.megabytes(other, 0)));
+        parts.push(format!(
+            "Shmem total: {}",
+            locale.megabytes(breakdown.shared, 0)
+        ));
+    }
+    f.render_widget(Paragraph::new(parts.join(", ")), summary_area);
+}
+
+// データベース向けの調整で見る値。連続した空きが減ると THP やヒュージページの確保に失敗しやすい
+// コミットが上限を超えると、strict のときは確保が失敗する
+fn draw_huge_pages(f: &mut Frame, app: &App, huge: HugePages, area: Rect) {
+    let locale = &app.locale;
+    let megabytes = |bytes: u64| locale.megabytes(bytes, 0);
+    let mut thp = format!(
+        "THP ({}): {} anon, {} shmem",
+        huge.thp.map_or("-", ThpMode::label),
+        megabytes(huge.anon_huge),
+        megabytes(huge.shmem_huge)
+    );
+    if let Some(free) = huge.contiguous_free {
+        thp.push_str(&format!(
+            ", free in blocks of {} or more: {}",
+            megabytes(huge.page_size),
+            megabytes(free)
+        ));
+    }
+    let ratio = huge.committed as f64 / huge.commit_limit.max(1) as f64;
+    let commit_style = match huge.overcommit {
+        _ if ratio < 1.0 => Style::default(),
+        Some(Overcommit::Strict) => Style::default().fg(app.theme.warning),
+        _ => Style::default().fg(app.theme.caution),
+    };
+    let lines = vec![
+        Line::from(format!(
+            "HugePages ({} each): {} total, {} free, {} reserved, {} surplus",
+            megabytes(huge.page_size),
+            huge.total,
+            huge.free,
+            huge.reserved,
+            huge.surplus
+        )),
+        Line::from(thp),
+        Line::styled(
+            format!(
+                "Committed: {} of {} limit ({}), overcommit {}",
+                megabytes(huge.committed),
+                megabytes(huge.commit_limit),
+                locale.percent(ratio * 100.0),
+                huge.overcommit.map_or("-", Overcommit::label)
+            ),
+            commit_style,
+        ),
+    ];
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(app.locale.text("Huge pages")),
+    );
+    f.render_widget(paragraph, area);
+}
+
+// スワップの使用量は圧縮前の大きさなので、実際に食っているメモリと圧縮率を並べる
+fn draw_compressed_swap(f: &mut Frame, app: &App, compressed: &CompressedSwap, area: Rect) {
+    let locale = &app.locale;
+    let megabytes = |bytes: u64| locale.megabytes(bytes, 0);
+    let ratio = |original: u64, compressed: u64| {
+        zram::ratio(original, compressed)
+            .map_or("-".to_string(), |r| format!("{}x", locale.float(r, 1)))
+    };
+    let pages = |bytes: u64| locale.int(bytes / compressed.page_size.max(1));
+    let mut lines: Vec<Line> = compressed
+        .zram
+        .iter()
+        .map(|d| {
+            Line::from(format!(
+                "{} ({}): {} stored in {} ({}), {} pages, {} same-filled, {} used of {}",
+                d.name,
+                d.algorithm.as_deref().unwrap_or("-"),
+                megabytes(d.original),
+                megabytes(d.compressed),
+                ratio(d.original, d.compressed),
+                pages(d.original),
+                locale.int(d.same_pages),
+                megabytes(d.used),
+                megabytes(d.disksize)
+            ))
+        })
+        .collect();
+    if let Some(z) = &compressed.zswap {
+        lines.push(Line::from(format!(
+            "zswap ({}): {} stored in a {} pool ({}), {} pages",
+            z.compressor.as_deref().unwrap_or("-"),
+            megabytes(z.stored),
+            megabytes(z.pool),
+            ratio(z.stored, z.pool),
+            pages(z.stored)
+        )));
+    }
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(locale.text("Compressed swap")),
+    );
+    f.render_widget(paragraph, area);
+}
+
+/// CPU 時間の内訳でコアを並べる列の数と、行の数の上限
+const CORE_COLUMNS: usize = 4;
+const CORE_ROWS: usize = 8;
+
+/// 内訳の棒の記号と名前。idle は空白。iowait と steal は色で見分ける
+const CPU_TIME_SEGMENTS: [(char, &str); 7] = [
+    ('█', "user"),
+    ('▓', "system"),
+    ('▒', "irq"),
+    ('░', "softirq"),
+    ('▚', "guest"),
+    ('█', "iowait"),
+    ('█', "steal"),
+];
+
+// 全体の棒と凡例の 2 行に、コアごとの棒を CORE_COLUMNS 列で並べた行を足す
+fn cpu_time_height(cpu: &CpuSnapshot) -> u16 {
+    if cpu.breakdown.is_none() {
+        return 0;
+    }
+    let cores = cpu.core_breakdown.len().min(CORE_COLUMNS * CORE_ROWS);
+    4 + cores.div_ceil(CORE_COLUMNS) as u16
+}
+
+fn cpu_time_values(b: CpuBreakdown) -> [f64; 7] {
+    [
+        b.user, b.system, b.irq, b.softirq, b.guest, b.iowait, b.steal,
+    ]
+}
+
+fn cpu_time_style(app: &App, name: &str) -> Style {
+    match name {
+        "iowait" => Style::default().fg(app.theme.caution),
+        "steal" => Style::default().fg(app.theme.warning),
+        _ => Style::default().fg(app.theme.cpu),
+    }
+}
+
+// memory_bar と同じく、端数は累計で丸めて幅をそろえる
+fn cpu_time_bar(app: &App, breakdown: CpuBreakdown, width: usize) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut filled = 0;
+    let mut cumulative = 0.0;
+    for ((symbol, name), percent) in CPU_TIME_SEGMENTS
+        .into_iter()
+        .zip(cpu_time_values(breakdown))
+    {
+        cumulative += percent;
+        let end = ((cumulative / 100.0) * width as f64).round() as usize;
+        let cells = end.min(width).saturating_sub(filled);
+        filled += cells;
+        spans.push(Span::styled(
+            symbol.to_string().repeat(cells),
+            cpu_time_style(app, name),
+        ));
+    }
+    spans.push(Span::raw(" ".repeat(width - filled)));
+    spans
+}
+
+// I/O 待ちか、ハイパーバイザーに取られているのか（steal）を使用率の内訳で見分ける
+fn draw_cpu_time(
+    f: &mut Frame,
+    app: &App,
+    breakdown: CpuBreakdown,
+    cores: &[(usize, CpuBreakdown)],
+    area: Rect,
+) {
+    let locale = &app.locale;
+    let shown = cores.len().min(CORE_COLUMNS * CORE_ROWS);
+    let title = if shown < cores.len() {
+        format!("CPU time (first {shown} of {} cores)", cores.len())
+    } else {
+        "CPU time".to_string()
+    };
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let width = block.inner(area).width as usize;
+    let mut lines = vec![Line::from(cpu_time_bar(app, breakdown, width))];
+    let mut legend = Vec::new();
+    for ((symbol, name), percent) in CPU_TIME_SEGMENTS
+        .into_iter()
+        .zip(cpu_time_values(breakdown))
+    {
+        // 凡例が幅に収まるよう、使われていない種類は省く（user・system・iowait は常に出す）
+        if percent == 0.0 && !matches!(name, "user" | "system" | "iowait") {
+            continue;
+        }
+        legend.push(Span::styled(symbol.to_string(), cpu_time_style(app, name)));
+        legend.push(Span::raw(format!(" {name} {}, ", locale.percent(percent))));
+    }
+    legend.push(Span::raw(format!(
+        "idle {}",
+        locale.percent(breakdown.idle)
+    )));
+    lines.push(Line::from(legend));
+    // 上から下へ、左の列から順に並べる
+    let rows = shown.div_ceil(CORE_COLUMNS);
+    let column = width / CORE_COLUMNS;
+    for row in 0..rows {
+        let mut spans = Vec::new();
+        for (cpu, core) in (0..CORE_COLUMNS).filter_map(|c| cores[..shown].get(c * rows + row)) {
+            let label = format!("{cpu:>3} ");
+            let percent = format!(" {:>6} ", locale.percent(core.busy()));
+            let bar = column.saturating_sub(label.len() + percent.len());
+            spans.push(Span::raw(label));
+            spans.extend(cpu_time_bar(app, *core, bar));
+            spans.push(Span::raw(percent));
+        }
+        lines.push(Line::from(spans));
+    }
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+// CPU 使用率が低いのに遅いときは、切り替えや割り込みの多さが原因のことがある
+fn draw_kernel(f: &mut Frame, app: &App, kernel: KernelRates, area: Rect) {
+    let locale = &app.locale;
+    let rate = |value: f64| format!("{}/s", locale.int(value.round() as u64));
+    let text = format!(
+        "Context switches: {}, Interrupts: {}, Forks: {}",
+        rate(kernel.context_switches),
+        rate(kernel.interrupts),
+        rate(kernel.forks)
+    );
+    let block = Paragraph::new(text).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(app.locale.text("Kernel")),
+    );
+    f.render_widget(block, area);
+}
+
+// ノードごとの CPU とメモリ。使用率が偏っていればノードをまたいだ確保が起きやすい
+fn draw_numa(f: &mut Frame, app: &App, numa: &NumaSnapshot, area: Rect) {
+    let locale = &app.locale;
+    let rows: Vec<Row> = numa
+        .nodes
+        .iter()
+        .map(|node| {
+            let percent = node.used() as f64 / node.total.max(1) as f64 * 100.0;
+            Row::new(vec![
+                node.id.to_string(),
+                node.cpus.clone(),
+                locale.megabytes(node.used(), 0),
+                locale.megabytes(node.total, 0),
+                locale.percent(percent),
+            ])
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(6),
+            Constraint::Min(12),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(8),
+        ],
+    )
+    .header(
+        Row::new(vec!["Node", "CPUs", "Used", "Total", "Use%"])
+            .style(Style::default().fg(app.theme.header)),
+    )
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(app.locale.text("NUMA nodes")),
+    );
+    f.render_widget(table, area);
+}
+
+// 残量・充電状態・残り時間・電力。残量が少なければ警告色（と記号）で示す
+fn draw_battery(f: &mut Frame, app: &App, battery: &BatterySnapshot, area: Rect) {
+    let mut text = format!(
+        "Battery: {} {}",
+        app.locale.percent(battery.percent),
+        battery.state.label()
+    );
+    if let Some(remaining) = battery.time_remaining {
+        let minutes = remaining.as_secs() / 60;
+        let label = match battery.state {
+            ChargeState::Charging => "until full",
+            _ => "remaining",
+        };
+        text.push_str(&format!(", {}:{:02} {label}", minutes / 60, minutes % 60));
+    }
+    if let Some(power) = battery.power {
+        text.push_str(&format!(", {} W", app.locale.float(power, 1)));
+    }
+    let mut style = Style::default();
+    if battery.is_low() {
+        style = style.fg(app.theme.warning).add_modifier(Modifier::BOLD);
+        if app.theme.symbols {
+            text = format!("! {text} (low)");
+        }
+    }
+    let block = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(app.locale.text("Battery")),
+        )
+        .style(style);
+    f.render_widget(block, area);
+}
+
+// 直近の収集で CPU・メモリ・I/O をいちばん使ったプロセス。スレッドの行は持ち主と重なるので除く
+/// Leaders パネルで指標ごとに挙げる数
+const LEADERS: usize = 3;
+
+/// 指標の見出しと、多い順に並べたプロセスとその値
+type Ranking<'a> = (&'static str, Vec<(&'a ProcessInfo, String)>);
+
+// CPU・メモリ・I/O それぞれで多い順に count 個。Top consumers と Leaders で使う
+fn rankings<'a>(app: &App, snapshot: &'a Snapshot, count: usize) -> [Ranking<'a>; 3] {
+    let locale = &app.locale;
+    // [noisy] exclude_from_top なら、ビルドなど負荷をかけるのが仕事のプロセスは挙げない
+    let expected =
+        |p: &ProcessInfo| app.noisy.exclude_from_top && app.noisy.kind(&p.name).is_some();
+    let processes = || {
+        snapshot
+            .processes
+            .iter()
+            .filter(|p| !p.is_thread && !expected(p))
+    };
+    let mut cpu: Vec<&ProcessInfo> = processes().collect();
+    cpu.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage));
+    let cpu = cpu
+        .into_iter()
+        .take(count)
+        .map(|p| {
+            let usage = app.cpu_mode.scale(p.cpu_usage, snapshot.cpu.count);
+            (p, locale.percent(usage as f64))
+        })
+        .collect();
+    let mut memory: Vec<&ProcessInfo> = processes().collect();
+    memory.sort_by_key(|p| std::cmp::Reverse(p.memory));
+    let memory = memory
+        .into_iter()
+        .take(count)
+        .map(|p| (p, locale.megabytes(p.memory, 1)))
+        .collect();
+    // 読み書きの量は収集の間隔あたりなので、毎秒に直す
+    let seconds = app.current_interval().as_secs_f64().max(0.001);
+    let mut io: Vec<(&ProcessInfo, u64)> = processes()
+        .map(|p| (p, p.disk_read + p.disk_written))
+        .filter(|(_, amount)| *amount > 0)
+        .collect();
+    io.sort_by_key(|(_, amount)| std::cmp::Reverse(*amount));
+    let io = io
+        .into_iter()
+        .take(count)
+        .map(|(p, amount)| {
+            let rate = amount as f64 / seconds;
+            let value = if platform::DISK_USAGE_IN_BLOCKS {
+                format!("{} blk/s", locale.float(rate, 0))
+            } else {
+                byte_rate(locale, rate)
+            };
+            (p, value)
+        })
+        .collect();
+    [("CPU", cpu), ("Mem", memory), ("I/O", io)]
+}
+
+fn draw_top(f: &mut Frame, app: &App, snapshot: &Snapshot, area: Rect) {
+    let locale = &app.locale;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(locale.text("Top consumers"));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let columns = Layout::horizontal([Constraint::Ratio(1, 3); 3])
+        .spacing(2)
+        .split(inner);
+    for ((label, top), area) in rankings(app, snapshot, 1).into_iter().zip(columns.iter()) {
+        let mut spans = vec![Span::styled(
+            format!("{label} "),
+            Style::default().fg(app.theme.header),
+        )];
+        match top.into_iter().next() {
+            Some((p, value)) => {
+                spans.push(Span::styled(
+                    value,
+                    Style::default().add_modifier(Modifier::BOLD),
+                ));
+                spans.push(Span::raw(format!(" {} ({})", p.name, p.pid)));
+            }
+            None => spans.push(Span::raw("-")),
+        }
+        f.render_widget(Paragraph::new(Line::from(spans)), *area);
+    }
+}
+
+// 指標ごとの上位 LEADERS 個を、名前・PID・値の小さな表にして横に並べる
+fn draw_leaders(f: &mut Frame, app: &App, snapshot: &Snapshot, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(app.locale.text("Leaders"));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let columns = Layout::horizontal([Constraint::Ratio(1, 3); 3])
+        .spacing(2)
+        .split(inner);
+    for ((label, top), area) in rankings(app, snapshot, LEADERS)
+        .into_iter()
+        .zip(columns.iter())
+    {
+        let rows: Vec<Row> = top
+            .into_iter()
+            .map(|(p, value)| {
+                Row::new(vec![
+                    Cell::from(p.name.to_string()),
+                    Cell::from(p.pid.to_string()),
+                    Cell::from(Line::from(value).alignment(Alignment::Right))
+                        .style(Style::default().add_modifier(Modifier::BOLD)),
+                ])
+            })
+            .collect();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(7),
+                Constraint::Length(11),
+            ],
+        )
+        .header(Row::new(vec![label, "PID", ""]).style(Style::default().fg(app.theme.header)));
+        f.render_widget(table, *area);
+    }
+}
+
+fn draw_soc(f: &mut Frame, app: &App, soc: &SocSnapshot, area: Rect) {
+    let locale = &app.locale;
+    let mut parts = Vec::new();
+    if let Some(temperature) = soc.temperature {
+        parts.push(format!("Temp: {}°C", locale.float(temperature, 1)));
+    }
+    if let Some(volts) = soc.core_volts {
+        parts.push(format!("Core: {} V", locale.float(volts, 2)));
+    }
+    let flags = [
+        (rpi::UNDER_VOLTAGE, "under-voltage"),
+        (rpi::FREQUENCY_CAPPED, "frequency capped"),
+        (rpi::THROTTLED, "throttled"),
+        (rpi::SOFT_TEMP_LIMIT, "soft temp limit"),
+    ];
+    let now: Vec<&str> = flags
+        .iter()
+        .filter(|(flag, _)| soc.now(*flag))
+        .map(|(_, label)| *label)
+        .collect();
+    let occurred: Vec<&str> = flags
+        .iter()
+        .filter(|(flag, _)| !soc.now(*flag) && soc.occurred(*flag))
+        .map(|(_, label)| *label)
+        .collect();
+    if soc.throttled.is_some() {
+        parts.push(if now.is_empty() {
+            "Throttling: none".to_string()
+        } else {
+            format!("Throttling: {}", now.join(", "))
+        });
+    }
+    if !occurred.is_empty() {
+        parts.push(format!("since boot: {}", occurred.join(", ")));
+    }
+    let mut text = parts.join(", ");
+    // 電圧低下は Pi でよくある不調の原因なので、他より目立たせる
+    let mut style = Style::default();
+    if soc.now(rpi::UNDER_VOLTAGE) {
+        style = style.fg(app.theme.warning).add_modifier(Modifier::BOLD);
+        if app.theme.symbols {
+            text = format!("! {text}");
+        }
+    } else if !now.is_empty() || soc.occurred(rpi::UNDER_VOLTAGE) {
+        style = style.fg(app.theme.caution);
+    }
+    let block = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(soc.model.as_str()),
+        )
+        .style(style);
+    f.render_widget(block, area);
+}
+
+/// 使用率の棒をこれ以上で注意、WARNING 以上で警告の色にする（%）
+const GAUGE_CAUTION_PERCENT: f64 = 70.0;
+const GAUGE_WARNING_PERCENT: f64 = 90.0;
+
+// [[theme.threshold]] があればそちらの見た目を、普段の色に重ねる
+fn usage_style(app: &App, ratio: f64, normal: Color) -> Style {
+    let percent = ratio * 100.0;
+    let style = Style::default().fg(normal);
+    if !app.thresholds.is_empty() {
+        return theme::threshold(&app.thresholds, percent).map_or(style, |t| style.patch(t));
+    }
+    if percent >= GAUGE_WARNING_PERCENT {
+        style.fg(app.theme.warning)
+    } else if percent >= GAUGE_CAUTION_PERCENT {
+        style.fg(app.theme.caution)
+    } else {
+        style
+    }
+}
+
+// 使用率の棒。ラベルの後ろに割合の長さだけ線を引く
+fn gauge(app: &App, label: String, ratio: f64, normal: Color) -> LineGauge<'static> {
+    let ratio = ratio.clamp(0.0, 1.0);
+    LineGauge::default()
+        .label(label)
+        .ratio(ratio)
+        .line_set(symbols::line::THICK)
+        .filled_style(usage_style(app, ratio, normal))
+        .unfilled_style(Style::default().add_modifier(Modifier::DIM))
+}
+
+fn draw_cpu(f: &mut Frame, app: &App, snapshot: &Snapshot, area: Rect) {
+    let locale = &app.locale;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!("CPU ({})", app.cpu_mode.label()));
+    // 起動直後は 2 回目の計測が終わるまで値を出さない
+    let waiting = if app.termux.is_some_and(|r| r.cpu) {
+        Some("CPU Usage: not visible on Android (/proc/stat is restricted)".to_string())
+    } else if let Some((_, path)) = app.access.denied(Slot::Cpu) {
+        Some(format!(
+            "CPU Usage: insufficient permissions to read {path}"
+        ))
+    } else if !snapshot.cpu_primed {
+        Some("CPU Usage: measuring...".to_string())
+    } else {
+        None
+    };
+    if let Some(text) = waiting {
+        let paragraph = Paragraph::new(text)
+            .block(block)
+            .style(Style::default().fg(app.theme.cpu));
+        f.render_widget(paragraph, area);
+        return;
+    }
+    let cpu = &snapshot.cpu;
+    let normalized = cpu.normalized() as f64;
+    // 全体を 100% とした値を先に出し、Irix モードではコアごとの合計も添える
+    let mut label = format!("CPU Usage: {}", locale.percent(normalized));
+    if app.cpu_mode == CpuMode::Irix && cpu.count > 1 {
+        label.push_str(&format!(
+            " ({} / {}%)",
+            locale.percent(cpu.usage_sum as f64),
+            locale.int(cpu.count as u64 * 100)
+        ));
+    }
+    // ハイブリッド CPU ではコアの種類ごとの平均も出す
+    for cluster in &cpu.clusters {
+        label.push_str(&format!(
+            ", {} ({}) {}",
+            cluster.label,
+            cluster.count,
+            locale.percent(cluster.usage as f64)
+        ));
+    }
+    if let Some(b) = cpu.breakdown {
+        let mut parts = vec![("user", b.user), ("system", b.system), ("iowait", b.iowait)];
+        for (name, percent) in [
+            ("irq", b.irq + b.softirq),
+            ("steal", b.steal),
+            ("guest", b.guest),
+        ] {
+            if percent > 0.0 {
+                parts.push((name, percent));
+            }
+        }
+        parts.push(("idle", b.idle));
+        for (name, percent) in parts {
+            label.push_str(&format!(", {name} {}", locale.percent(percent)));
+        }
+    }
+    f.render_widget(
+        gauge(app, label, normalized / 100.0, app.theme.cpu).block(block),
+        area,
+    );
+}
+
+fn draw_memory(f: &mut Frame, app: &App, snapshot: &Snapshot, area: Rect) {
+    let locale = &app.locale;
+    let memory = &snapshot.memory;
+    let swap = format!(
+        "Swap: {} / {}",
+        locale.megabytes(memory.used_swap, 0),
+        locale.megabytes(memory.total_swap, 0)
+    );
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(app.locale.text("Memory"));
+    let lines = match memory.breakdown {
+        _ if memory.total == 0 => vec![Line::from("Memory: -")],
+        // 内訳が無ければメモリとスワップの棒を並べる
+        None => {
+            let inner = block.inner(area);
+            f.render_widget(block, area);
+            let halves = Layout::horizontal([Constraint::Percentage(60), Constraint::Fill(1)])
+                .spacing(2)
+                .split(inner);
+            let label = format!(
+                "Memory: {} / {}",
+                locale.megabytes(memory.used, 0),
+                locale.megabytes(memory.total, 0),
+            );
+            let ratio = memory.used as f64 / memory.total as f64;
+            f.render_widget(gauge(app, label, ratio, app.theme.memory), halves[0]);
+            let ratio = memory.used_swap as f64 / memory.total_swap.max(1) as f64;
+            f.render_widget(gauge(app, swap, ratio, app.theme.memory), halves[1]);
+            return;
+        }
+        Some(breakdown) => {
+            let width = area.width.saturating_sub(2) as usize;
+            vec![
+                memory_bar(app, memory.total, breakdown, width),
+                Line::from(format!(
+                    "{} {} used, {} {} buffers, {} {} cached ({} shared), {} free ({} available of {}), {swap}",
+                    MEMORY_SEGMENTS[0],
+                    locale.megabytes(breakdown.used(memory.total), 0),
+                    MEMORY_SEGMENTS[1],
+                    locale.megabytes(breakdown.buffers, 0),
+                    MEMORY_SEGMENTS[2],
+                    locale.megabytes(breakdown.cached, 0),
+                    locale.megabytes(breakdown.shared, 0),
+                    locale.megabytes(breakdown.free, 0),
+                    locale.megabytes(breakdown.available, 0),
+                    locale.megabytes(memory.total, 0),
+                )),
+            ]
+        }
+    };
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .style(Style::default().fg(app.theme.memory));
+    f.render_widget(paragraph, area);
+}
+
+/// 積み上げの棒の記号（使用中・バッファ・キャッシュ）。空きは空白
+const MEMORY_SEGMENTS: [char; 3] = ['█', '▓', '░'];
+
+// 使用中・バッファ・キャッシュ・空きの順に、幅を大きさで分ける
+fn memory_bar(app: &App, total: u64, breakdown: MemoryBreakdown, width: usize) -> Line<'static> {
+    let sizes = [breakdown.used(total), breakdown.buffers, breakdown.cached];
+    let mut spans = Vec::new();
+    let mut filled = 0;
+    let mut cumulative = 0;
+    for (symbol, size) in MEMORY_SEGMENTS.into_iter().zip(sizes) {
+        // 端数は累計で丸め、全体の幅がずれないようにする
+        cumulative += size;
+        let end = ((cumulative as f64 / total.max(1) as f64) * width as f64).round() as usize;
+        let end = end.min(width);
+        let cells = end.saturating_sub(filled);
+        filled += cells;
+        // 使用中の部分だけ、多ければ注意・警告の色にする
+        let style = if symbol == MEMORY_SEGMENTS[0] {
+            let ratio = size as f64 / total.max(1) as f64;
+            usage_style(app, ratio, app.theme.memory)
+        } else {
+            Style::default()
+                .fg(app.theme.memory)
+                .add_modifier(Modifier::DIM)
+        };
+        spans.push(Span::styled(symbol.to_string().repeat(cells), style));
+    }
+    Line::from(spans)
+}
+
+/// [[panel]] のテキストで表示する行数の上限
+const CUSTOM_LINES: usize = 5;
+
+fn custom_height(panel: &CustomPanel) -> u16 {
+    match (&panel.config.kind, panel.output()) {
+        (PanelKind::Text, Some(Ok(text))) => 2 + text.lines().count().clamp(1, CUSTOM_LINES) as u16,
+        _ => HEADER_HEIGHT,
+    }
+}
+
+#[cfg(feature = "plugin")]
+fn plugin_panel_height(panel: &PluginPanel) -> u16 {
+    2 + panel.lines.len().clamp(1, CUSTOM_LINES) as u16
+}
+
+// [[panel]] とプラグインのパネルを合わせた高さ
+fn custom_area_height(app: &App) -> u16 {
+    #[cfg(feature = "plugin")]
+    let plugins: u16 = app
+        .plugins
+        .iter()
+        .flat_map(|plugin| plugin.output().panels)
+        .map(|panel| plugin_panel_height(&panel))
+        .sum();
+    #[cfg(not(feature = "plugin"))]
+    let plugins = 0;
+    app.custom_panels.iter().map(custom_height).sum::<u16>() + plugins
+}
+
+// 設定ファイルで定義したパネルを上から順に並べ、その下にプラグインのパネルを並べる
+fn draw_custom(f: &mut Frame, app: &App, area: Rect) {
+    #[cfg(feature = "plugin")]
+    let plugin_panels: Vec<PluginPanel> = app
+        .plugins
+        .iter()
+        .flat_map(|plugin| plugin.output().panels)
+        .collect();
+    let heights = app.custom_panels.iter().map(custom_height);
+    #[cfg(feature = "plugin")]
+    let heights = heights.chain(plugin_panels.iter().map(plugin_panel_height));
+    let areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(heights.map(Constraint::Length))
+        .split(area);
+    #[cfg(feature = "plugin")]
+    for (panel, area) in plugin_panels
+        .iter()
+        .zip(areas.iter().skip(app.custom_panels.len()))
+    {
+        let lines: Vec<Line> = panel
+            .lines
+            .iter()
+            .take(CUSTOM_LINES)
+            .map(|line| Line::from(line.as_str()))
+            .collect();
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(panel.title.as_str());
+        f.render_widget(Paragraph::new(lines).block(block), *area);
+    }
+    for (panel, area) in app.custom_panels.iter().zip(areas.iter()) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(panel.config.title.as_str());
+        let inner = block.inner(*area);
+        f.render_widget(block, *area);
+        let output = match panel.output() {
+            None => {
+                f.render_widget(Paragraph::new("Reading..."), inner);
+                continue;
+            }
+            Some(Err(e)) => {
+                let style = Style::default().fg(app.theme.warning);
+                f.render_widget(Paragraph::new(e).style(style), inner);
+                continue;
+            }
+            Some(Ok(output)) => output,
+        };
+        match &panel.config.kind {
+            PanelKind::Text => {
+                let lines: Vec<Line> = output
+                    .lines()
+                    .take(CUSTOM_LINES)
+                    .map(|line| Line::from(line.to_string()))
+                    .collect();
+                f.render_widget(Paragraph::new(lines), inner);
+            }
+            PanelKind::Gauge { max, scale, unit } => {
+                let Some(value) = custom::gauge_value(&output, *scale) else {
+                    let style = Style::default().fg(app.theme.warning);
+                    f.render_widget(
+                        Paragraph::new("No number in the output").style(style),
+                        inner,
+                    );
+                    continue;
+                };
+                let label = format!("{}{unit}", app.locale.float(value, 1));
+                f.render_widget(gauge(app, label, value / max, app.theme.cpu), inner);
+            }
+        }
+    }
+}
+
+/// コンテナパネルに表示する行数の上限
+const CONTAINER_ROWS: usize = 5;
+
+/// コンテナごとのプロセス数・CPU・メモリの合計
+struct ContainerUsage<'a> {
+    name: &'a str,
+    processes: usize,
+    cpu_usage: f32,
+    memory: u64,
+}
+
+// CPU 使用率の高い順
+fn container_usage(snapshot: &Snapshot) -> Vec<ContainerUsage<'_>> {
+    let mut usage: Vec<ContainerUsage> = Vec::new();
+    for p in &snapshot.processes {
+        let Some(name) = p.container.as_deref() else {
+            continue;
+        };
+        let entry = match usage.iter().position(|u| u.name == name) {
+            Some(i) => &mut usage[i],
+            None => {
+                usage.push(ContainerUsage {
+                    name,
+                    processes: 0,
+                    cpu_usage: 0.0,
+                    memory: 0,
+                });
+                usage.last_mut().expect("just pushed")
+            }
+        };
+        entry.processes += 1;
+        entry.cpu_usage += p.cpu_usage;
+        entry.memory += p.memory;
+    }
+    usage.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage));
+    usage
+}
+
+fn containers_height(snapshot: &Snapshot) -> u16 {
+    let count = container_usage(snapshot).len().min(CONTAINER_ROWS) as u16;
+    if count == 0 { 0 } else { 3 + count }
+}
+
+fn draw_containers(f: &mut Frame, app: &App, snapshot: &Snapshot, area: Rect) {
+    let locale = &app.locale;
+    let rows: Vec<Row> = container_usage(snapshot)
+        .iter()
+        .take(CONTAINER_ROWS)
+        .map(|u| {
+            Row::new(vec![
+                u.name.to_string(),
+                locale.int(u.processes as u64),
+                if snapshot.cpu_primed {
+                    locale.percent(app.cpu_mode.scale(u.cpu_usage, snapshot.cpu.count) as f64)
+                } else {
+                    "-".to_string()
+                },
+                locale.megabytes(u.memory, 1),
+            ])
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(25),
+            Constraint::Length(9),
+            Constraint::Length(10),
+            Constraint::Length(12),
+        ],
+    )
+    .header(
+        Row::new(vec!["Container", "Processes", "CPU", "Memory"])
+            .style(Style::default().fg(app.theme.header)),
+    )
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(app.locale.text("Containers")),
+    );
+    f.render_widget(table, area);
+}
+
+/// 待ち受けポートのパネルの 1 行に並べる数と、行数の上限
+#[cfg(feature = "net")]
+const PORT_COLUMNS: usize = 4;
+#[cfg(feature = "net")]
+const PORT_ROWS: usize = 3;
+
+#[cfg(feature = "net")]
+fn ports_height(snapshot: &Snapshot) -> u16 {
+    let count = net::listening(&snapshot.connections).len();
+    if count == 0 {
+        0
+    } else {
+        2 + count.div_ceil(PORT_COLUMNS).min(PORT_ROWS) as u16
+    }
+}
+
+#[cfg(not(feature = "net"))]
+fn ports_height(_snapshot: &Snapshot) -> u16 {
+    0
+}
+
+// ss -lntup の要約。「TCP *:22 sshd」を 1 行に PORT_COLUMNS 個ずつ並べる
+#[cfg(feature = "net")]
+fn draw_ports(f: &mut Frame, snapshot: &Snapshot, area: Rect) {
+    let ports = net::listening(&snapshot.connections);
+    let cells: Vec<String> = ports
+        .iter()
+        .take(PORT_COLUMNS * PORT_ROWS)
+        .map(|p| {
+            let address = p.address.map_or("*".to_string(), |ip| match ip {
+                std::net::IpAddr::V6(ip) => format!("[{ip}]"),
+                std::net::IpAddr::V4(ip) => ip.to_string(),
+            });
+            let name = p.pid.and_then(|pid| {
+                snapshot
+                    .processes
+                    .iter()
+                    .find(|process| process.pid == pid)
+                    .map(|process| process.name.to_string())
+            });
+            format!(
+                "{} {address}:{} {}",
+                p.protocol.label(),
+                p.port,
+                name.as_deref().unwrap_or("-")
+            )
+        })
+        .collect();
+    let rows: Vec<Row> = cells
+        .chunks(PORT_COLUMNS)
+        .map(|chunk| Row::new(chunk.to_vec()))
+        .collect();
+    let hidden = ports.len().saturating_sub(PORT_COLUMNS * PORT_ROWS);
+    let title = if hidden > 0 {
+        format!(
+            "Listening ports ({}, {hidden} more, P to go to a port)",
+            ports.len()
+        )
+    } else {
+        format!("Listening ports ({}, P to go to a port)", ports.len())
+    };
+    let table = Table::new(
+        rows,
+        [Constraint::Ratio(1, PORT_COLUMNS as u32); PORT_COLUMNS],
+    )
+    .block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(table, area);
+}
+
+#[cfg(feature = "net")]
+fn tcp_height(snapshot: &Snapshot) -> u16 {
+    if snapshot.tcp_states.is_some() {
+        HEADER_HEIGHT
+    } else {
+        0
+    }
+}
+
+#[cfg(not(feature = "net"))]
+fn tcp_height(_snapshot: &Snapshot) -> u16 {
+    0
+}
+
+// 状態ごとの数と 1 秒あたりの増減。CLOSE-WAIT と SYN-RECV が増えていれば警告色にする
+#[cfg(feature = "net")]
+fn draw_tcp_states(f: &mut Frame, app: &App, tcp: TcpStates, area: Rect) {
+    let locale = &app.locale;
+    let mut spans = Vec::new();
+    for (i, state) in net::TCP_STATES.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw("  "));
+        }
+        let rate = tcp.rates.map(|rates| rates[i]);
+        let text = match rate {
+            Some(rate) => format!(
+                "{state} {} ({}{}/s)",
+                locale.int(tcp.counts[i] as u64),
+                if rate > 0.0 { "+" } else { "" },
+                locale.float(rate, 1)
+            ),
+            None => format!("{state} {}", locale.int(tcp.counts[i] as u64)),
+        };
+        let rising =
+            rate.is_some_and(|rate| rate > 0.0) && matches!(*state, "CLOSE-WAIT" | "SYN-RECV");
+        spans.push(if rising {
+            Span::styled(text, Style::default().fg(app.theme.warning))
+        } else {
+            Span::raw(text)
+        });
+    }
+    let block = Paragraph::new(Line::from(spans))
+        .block(Block::default().borders(Borders::ALL).title("TCP"));
+    f.render_widget(block, area);
+}
+
+/// GPU パネルに表示するプロセスの最大数
+#[cfg(feature = "gpu")]
+const GPU_PROCESS_ROWS: usize = 3;
+
+#[cfg(feature = "gpu")]
+fn gpu_height(snapshot: &Snapshot) -> u16 {
+    snapshot.gpu.as_ref().map_or(0, |gpu| {
+        2 + gpu.gpus.len() as u16 + gpu.processes.len().min(GPU_PROCESS_ROWS) as u16
+    })
+}
+
+#[cfg(not(feature = "gpu"))]
+fn gpu_height(_snapshot: &Snapshot) -> u16 {
+    0
+}
+
+// GPU ごとの使用率・VRAM・温度と、GPU を使っているプロセス
+#[cfg(feature = "gpu")]
+fn draw_gpu(f: &mut Frame, app: &App, snapshot: &Snapshot, gpu: &GpuSnapshot, area: Rect) {
+    let locale = &app.locale;
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let mut rows: Vec<Row> = gpu
+        .gpus
+        .iter()
+        .map(|g| {
+            Row::new(vec![
+                format!("GPU {}", g.index),
+                g.name.clone(),
+                optional(g.utilization.map(|u| format!("{u}%"))),
+                format!(
+                    "{} / {}",
+                    optional(g.memory_used.map(|b| locale.megabytes(b, 0))),
+                    optional(g.memory_total.map(|b| locale.megabytes(b, 0)))
+                ),
+                optional(g.temperature.map(|t| format!("{t}°C"))),
+            ])
+        })
+        .collect();
+    for p in gpu.processes.iter().take(GPU_PROCESS_ROWS) {
+        let name = snapshot
+            .processes
+            .iter()
+            .find(|info| info.pid == p.pid)
+            .map_or("?", |info| &*info.name);
+        rows.push(
+            Row::new(vec![
+                format!("  {}", p.pid),
+                name.to_string(),
+                String::new(),
+                optional(p.memory_used.map(|b| locale.megabytes(b, 0))),
+                String::new(),
+            ])
+            .style(Style::default().add_modifier(Modifier::DIM)),
+        );
+    }
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(8),
+            Constraint::Length(28),
+            Constraint::Length(6),
+            Constraint::Length(24),
+            Constraint::Length(6),
+        ],
+    )
+    .block(Block::default().borders(Borders::ALL).title("GPU"));
+    f.render_widget(table, area);
+}
+
+/// IPMI パネルに表示するセンサーの上限
+#[cfg(feature = "ipmi")]
+const IPMI_ROWS: usize = 8;
+
+#[cfg(feature = "ipmi")]
+fn ipmi_height(snapshot: &Snapshot) -> u16 {
+    snapshot
+        .ipmi
+        .as_ref()
+        .map_or(0, |ipmi| 3 + ipmi.sensors.len().min(IPMI_ROWS) as u16)
+}
+
+#[cfg(not(feature = "ipmi"))]
+fn ipmi_height(_snapshot: &Snapshot) -> u16 {
+    0
+}
+
+// BMC のセンサー。閾値を越えたものは警告色にして先に並べる
+#[cfg(feature = "ipmi")]
+fn draw_ipmi(f: &mut Frame, app: &App, ipmi: &IpmiSnapshot, area: Rect) {
+    let locale = &app.locale;
+    let mut sensors: Vec<_> = ipmi.sensors.iter().collect();
+    sensors.sort_by_key(|s| s.is_ok());
+    let rows: Vec<Row> = sensors
+        .iter()
+        .take(IPMI_ROWS)
+        .map(|s| {
+            let places = if s.kind == crate::ipmi::SensorKind::Temperature {
+                1
+            } else {
+                0
+            };
+            let row = Row::new(vec![
+                s.kind.label().to_string(),
+                s.name.clone(),
+                format!("{} {}", locale.float(s.reading, places), s.kind.unit()),
+                s.status.clone(),
+            ]);
+            if s.is_ok() {
+                row
+            } else {
+                row.style(Style::default().fg(app.theme.warning))
+            }
+        })
+        .collect();
+    let hidden = ipmi.sensors.len().saturating_sub(IPMI_ROWS);
+    let title = if hidden > 0 {
+        format!("Sensors (IPMI, {hidden} more)")
+    } else {
+        "Sensors (IPMI)".to_string()
+    };
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(6),
+            Constraint::Length(24),
+            Constraint::Length(12),
+            Constraint::Min(6),
+        ],
+    )
+    .header(
+        Row::new(vec!["Kind", "Sensor", "Reading", "Status"])
+            .style(Style::default().fg(app.theme.header)),
+    )
+    .block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(table, area);
+}
+
+/// センサーのパネルに表示するファンと電力の行数の上限
+const SENSOR_ROWS: usize = 6;
+
+// 見出しとファン・電力の行。パッケージの電力が分かれば推移の行を足す
+fn sensors_height(snapshot: &Snapshot) -> u16 {
+    snapshot.sensors.as_ref().map_or(0, |s| {
+        let rows = (s.fans.len() + s.power.len()).min(SENSOR_ROWS) as u16;
+        3 + rows + u16::from(s.package_watts.is_some())
+    })
+}
+
+// hwmon のファンと、RAPL・hwmon の消費電力。パッケージの電力は推移も出す
+fn draw_sensors(f: &mut Frame, app: &App, sensors: &SensorsSnapshot, area: Rect) {
+    let locale = &app.locale;
+    let readings: Vec<Row> = sensors
+        .fans
+        .iter()
+        .map(|r| ("Fan", r, format!("{} RPM", locale.float(r.value, 0))))
+        .chain(
+            sensors
+                .power
+                .iter()
+                .map(|r| ("Power", r, format!("{} W", locale.float(r.value, 1)))),
+        )
+        .take(SENSOR_ROWS)
+        .map(|(kind, r, value)| Row::new(vec![kind.to_string(), r.name.clone(), value]))
+        .collect();
+    let hidden = (sensors.fans.len() + sensors.power.len()).saturating_sub(SENSOR_ROWS);
+    let title = if hidden > 0 {
+        format!("Sensors ({hidden} more)")
+    } else {
+        "Sensors".to_string()
+    };
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let [table_area, history_area] = Layout::vertical([
+        Constraint::Fill(1),
+        Constraint::Length(u16::from(sensors.package_watts.is_some())),
+    ])
+    .areas(inner);
+    let table = Table::new(
+        readings,
+        [
+            Constraint::Length(6),
+            Constraint::Fill(1),
+            Constraint::Length(10),
+        ],
+    )
+    .header(
+        Row::new(vec!["Kind", "Sensor", "Reading"]).style(Style::default().fg(app.theme.header)),
+    );
+    f.render_widget(table, table_area);
+    if let Some(watts) = sensors.package_watts {
+        let label = format!("Package {} W ", locale.float(watts, 1));
+        let [label_area, graph_area] = Layout::horizontal([
+            Constraint::Length(text::width(&label) as u16),
+            Constraint::Fill(1),
+        ])
+        .areas(history_area);
+        f.render_widget(
+            Paragraph::new(label).style(Style::default().fg(app.theme.header)),
+            label_area,
+        );
+        f.render_widget(
+            Sparkline::default()
+                .data(tail(&app.usage_history.power, graph_area.width))
+                .style(Style::default().fg(app.theme.watch_cpu)),
+            graph_area,
+        );
+    }
+}
+
+/// SMART パネルに表示するディスクの上限
+#[cfg(feature = "smart")]
+const SMART_ROWS: usize = 8;
+
+#[cfg(feature = "smart")]
+fn smart_height(snapshot: &Snapshot) -> u16 {
+    snapshot
+        .smart
+        .as_ref()
+        .map_or(0, |smart| 3 + smart.disks.len().min(SMART_ROWS) as u16)
+}
+
+#[cfg(not(feature = "smart"))]
+fn smart_height(_snapshot: &Snapshot) -> u16 {
+    0
+}
+
+// 物理ディスクの SMART。判定が失敗か、代替処理済みのセクタがあるものは警告色にして先に並べる
+#[cfg(feature = "smart")]
+fn draw_smart(f: &mut Frame, app: &App, smart: &SmartSnapshot, area: Rect) {
+    let locale = &app.locale;
+    let mut disks: Vec<_> = smart.disks.iter().collect();
+    disks.sort_by_key(|d| d.is_ok());
+    let rows: Vec<Row> = disks
+        .iter()
+        .take(SMART_ROWS)
+        .map(|d| {
+            let row = Row::new(vec![
+                d.name.clone(),
+                d.model.clone(),
+                match d.passed {
+                    Some(true) => "PASSED".to_string(),
+                    Some(false) => "FAILED".to_string(),
+                    None => "-".to_string(),
+                },
+                d.temperature
+                    .map_or("-".to_string(), |t| format!("{} °C", locale.float(t, 0))),
+                d.reallocated.map_or("-".to_string(), |n| n.to_string()),
+            ]);
+            if d.is_ok() {
+                row
+            } else {
+                row.style(Style::default().fg(app.theme.warning))
+            }
+        })
+        .collect();
+    let hidden = smart.disks.len().saturating_sub(SMART_ROWS);
+    let title = if hidden > 0 {
+        format!("Disks (SMART, {hidden} more)")
+    } else {
+        "Disks (SMART)".to_string()
+    };
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(14),
+            Constraint::Min(12),
+            Constraint::Length(7),
+            Constraint::Length(6),
+            Constraint::Length(11),
+        ],
+    )
+    .header(
+        Row::new(vec!["Device", "Model", "Health", "Temp", "Reallocated"])
+            .style(Style::default().fg(app.theme.header)),
+    )
+    .block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(table, area);
+}
+
+// 見出しと、種類ごとの平均・熱・電力の行、コアごとの行
+#[cfg(feature = "apple")]
+fn apple_height(snapshot: &Snapshot) -> u16 {
+    if snapshot.apple.is_some() { 4 } else { 0 }
+}
+
+#[cfg(not(feature = "apple"))]
+fn apple_height(_snapshot: &Snapshot) -> u16 {
+    0
+}
+
+// P コアと E コアを分けた使用率と、熱による制限・電力
+#[cfg(feature = "apple")]
+fn draw_apple(f: &mut Frame, app: &App, apple: &AppleSnapshot, area: Rect) {
+    let locale = &app.locale;
+    let mut parts = Vec::new();
+    for kind in [CoreKind::Performance, CoreKind::Efficiency] {
+        if let Some((count, usage)) = apple.average(kind) {
+            parts.push(format!(
+                "{}-cores ({count}): {}",
+                kind.label(),
+                locale.percent(usage as f64)
+            ));
+        }
+    }
+    if let Some(thermal) = apple.thermal {
+        parts.push(format!("Thermal: {}", thermal.label()));
+    }
+    if let Some(power) = apple.power {
+        parts.push(format!("Power: {} W", locale.float(power, 1)));
+    }
+    let summary = Line::from(parts.join(", "));
+    // コアごとの使用率。P と E で色を変え、番号の前に種類を書く
+    let mut spans = Vec::new();
+    let mut numbers = [0; 2];
+    for (kind, usage) in &apple.cores {
+        let (index, color) = match kind {
+            CoreKind::Performance => (0, app.theme.cpu),
+            CoreKind::Efficiency => (1, app.theme.memory),
+        };
+        spans.push(Span::styled(
+            format!("{}{} {:>3.0}% ", kind.label(), numbers[index], usage),
+            Style::default().fg(color),
+        ));
+        numbers[index] += 1;
+    }
+    let style = match apple.thermal {
+        Some(t) if t >= ThermalPressure::Heavy => Style::default().fg(app.theme.warning),
+        Some(ThermalPressure::Moderate) => Style::default().fg(app.theme.caution),
+        _ => Style::default(),
+    };
+    let block = Paragraph::new(vec![summary.style(style), Line::from(spans)]).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Apple Silicon"),
+    );
+    f.render_widget(block, area);
+}
+
+// 右上に収集・整形・描画の時間を表示する
+fn draw_profile(f: &mut Frame, app: &App, snapshot: &Snapshot, times: FrameTimes, size: Rect) {
+    let collect = &snapshot.collect_times;
+    let mut rows: Vec<Row> = Vec::new();
+    // 自分自身の負荷（接続先のスナップショットには無い）
+    if let Some(own) = snapshot.own_usage {
+        let cpu = if snapshot.cpu_primed {
+            app.locale.percent(own.cpu_usage as f64)
+        } else {
+            "-".to_string()
+        };
+        let memory = app.locale.megabytes(own.memory, 1);
+        rows.push(Row::new(vec!["self cpu".to_string(), format!("{cpu:>11}")]));
+        rows.push(Row::new(vec![
+            "self memory".to_string(),
+            format!("{memory:>11}"),
+        ]));
+    }
+    let times = [
+        ("collect cpu", collect.cpu),
+        ("collect memory", collect.memory),
+        ("collect procs", collect.processes),
+        ("collect info", collect.info),
+        ("collect battery", collect.battery),
+        ("collect cgroup", collect.cgroup),
+        ("collect pressure", collect.pressure),
+        ("collect soc", collect.soc),
+        ("collect sensors", collect.sensors),
+        #[cfg(feature = "gpu")]
+        ("collect gpu", collect.gpu),
+        #[cfg(feature = "ipmi")]
+        ("collect ipmi", collect.ipmi),
+        #[cfg(feature = "smart")]
+        ("collect smart", collect.smart),
+        #[cfg(feature = "apple")]
+        ("collect apple", collect.apple),
+        #[cfg(feature = "net")]
+        ("collect conns", collect.connections),
+        #[cfg(any(feature = "systemd", windows))]
+        ("collect svcs", collect.services),
+        ("prepare", times.prepare),
+        ("render", times.render),
+    ]
+    .map(|(label, time)| {
+        Row::new(vec![
+            label.to_string(),
+            format!("{:>8.2} ms", time.as_secs_f64() * 1000.0),
+        ])
+    });
+    rows.extend(times);
+    let width = 34.min(size.width);
+    let height = (rows.len() as u16 + 2).min(size.height);
+    let area = Rect::new(size.right() - width, size.y, width, height);
+    let table = Table::new(rows, [Constraint::Length(17), Constraint::Min(11)]).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(app.locale.text("Profile (F12)")),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+}
+
+// システム情報
+// uptime と同じく「3 days, 4:05:09」の形
+fn uptime(secs: u64) -> String {
+    let clock = format!(
+        "{}:{:02}:{:02}",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    );
+    match secs / 86_400 {
+        0 => clock,
+        1 => format!("1 day, {clock}"),
+        days => format!("{days} days, {clock}"),
+    }
+}
+
+fn draw_info(f: &mut Frame, locale: &Locale, info: &SystemInfo, access: &Access, area: Rect) {
+    let mut info_rows: Vec<Row> = Vec::new();
+
+    let number_of_cpus = locale.int(info.cpu_count as u64);
+    info_rows.push(Row::new(vec!["Number of cpus", number_of_cpus.as_str()]));
+
+    info_rows.push(Row::new(vec!["CPU Architecture", info.cpu_arch.as_str()]));
+
+    info_rows.push(Row::new(vec!["Brand", info.brand.as_str()]));
+
+    let frequency = &info.frequency;
+    let current = frequency
+        .current
+        .iter()
+        .map(|&mhz| locale.int(mhz))
+        .collect::<Vec<_>>()
+        .join(" ");
+    info_rows.push(Row::new(vec!["Frequency (MHz)", current.as_str()]));
+    if !frequency.governors.is_empty() {
+        let governors = frequency.governors.join(", ");
+        info_rows.push(Row::new(vec!["Governor".to_string(), governors]));
+    }
+    if let Some((min, max)) = frequency.range {
+        let range = format!("{} - {} MHz", locale.int(min), locale.int(max));
+        info_rows.push(Row::new(vec!["Scaling range".to_string(), range]));
+    }
+
+    info_rows.push(Row::new(vec!["Uptime".to_string(), uptime(info.uptime)]));
+    if info.boot_time > 0 {
+        info_rows.push(Row::new(vec![
+            "Boot time".to_string(),
+            locale.datetime(info.boot_time),
+        ]));
+    }
+    // 「alice pts/0 (10.0.0.2), bob tty1」
+    if !info.sessions.is_empty() {
+        let sessions = info
+            .sessions
+            .iter()
+            .map(|s| match &s.host {
+                Some(host) => format!("{} {} ({host})", s.user, s.line),
+                None => format!("{} {}", s.user, s.line),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        info_rows.push(Row::new(vec![
+            format!("Logged in ({})", info.sessions.len()),
+            sessions,
+        ]));
+    }
+
+    // Windows には load average が無い（常に 0）
+    let load_average = info.load_average.map(|l| locale.float(l, 2)).join(" ");
+    if !cfg!(windows) {
+        info_rows.push(Row::new(vec!["Load average", load_average.as_str()]));
+    }
+
+    info_rows.push(Row::new(vec![
+        "kernel long version",
+        info.kernel_long_version.as_str(),
+    ]));
+
+    info_rows.push(Row::new(vec![
+        "long os version",
+        info.long_os_version.as_deref().unwrap_or("Unknown"),
+    ]));
+    if let Some(edition) = &info.edition {
+        info_rows.push(Row::new(vec!["Edition", edition.as_str()]));
+    }
+    if let Some(build) = &info.os_build {
+        info_rows.push(Row::new(vec!["Build", build.as_str()]));
+    }
+
+    info_rows.push(Row::new(vec![
+        "Host name",
+        info.host_name.as_deref().unwrap_or("Unknown"),
+    ]));
+
+    let open_files_limit_str = info
+        .open_files_limit
+        .map(|v| locale.int(v as u64))
+        .unwrap_or_else(|| "Unknown".to_string());
+    // Windows にはプロセスごとのファイル数の上限が無い
+    if !(cfg!(windows) && info.open_files_limit.is_none()) {
+        info_rows.push(Row::new(vec![
+            "Open files limit",
+            open_files_limit_str.as_str(),
+        ]));
+    }
+
+    info_rows.push(Row::new(vec![
+        "Product Name",
+        info.product_name.as_deref().unwrap_or("Unknown"),
+    ]));
+
+    info_rows.push(Row::new(vec![
+        "Vendor name",
+        info.vendor_name.as_deref().unwrap_or("Unknown"),
+    ]));
+
+    // 起動時に調べた、権限で読めなかったもの。足りないときは低いパネルでも見えるよう先頭に出す
+    let permissions = Row::new(vec!["Permissions".to_string(), access.summary()]);
+    if access.is_limited() {
+        info_rows.insert(0, permissions);
+    } else {
+        info_rows.push(permissions);
+    }
+
+    let info_table = Table::new(info_rows, [Constraint::Length(25), Constraint::Length(60)]).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(locale.text("Info")),
+    );
+    f.render_widget(info_table, area);
+}
+
+// 権限で断られたソースのパネル
+fn draw_denied(f: &mut Frame, app: &App, name: &str, path: &str, area: Rect) {
+    let paragraph = Paragraph::new(format!("insufficient permissions to read {path}"))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(name.to_string()),
+        )
+        .style(Style::default().fg(app.theme.warning));
+    f.render_widget(paragraph, area);
+}
+
+/// 画面上の位置がプロセス表のどこに当たるか
+pub fn hit_test(areas: &Drawn, column: u16, row: u16) -> Option<ClickTarget> {
+    let inner = Block::default()
+        .borders(Borders::ALL)
+        .inner(areas.processes);
+    if column < inner.x || column >= inner.right() || row < inner.y || row >= inner.bottom() {
+        return None;
+    }
+    if row > inner.y {
+        return Some(ClickTarget::Row((row - inner.y - 1) as usize));
+    }
+    // 列の間には 1 文字の隙間がある
+    let mut x = inner.x;
+    for (key, width) in process_columns(areas.widths) {
+        if column < x + width + 1 {
+            return Some(ClickTarget::Header(key));
+        }
+        x += width + 1;
+    }
+    None
+}
+
+// ピン留めしたプロセスごとに CPU とメモリの推移を表示する
+fn draw_watch(f: &mut Frame, app: &App, snapshot: &Snapshot, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(app.locale.text("Watch (p to unpin)"));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    for (i, pid) in app.pinned.iter().enumerate() {
+        let y = inner.y + 2 * i as u16;
+        if y + 1 >= inner.bottom() {
+            break;
+        }
+        let label = match snapshot.processes.iter().find(|p| p.pid == *pid) {
+            Some(p) => format!(
+                "{pid} {}  CPU {}  MEM {}",
+                p.name,
+                app.locale
+                    .percent(app.cpu_mode.scale(p.cpu_usage, snapshot.cpu.count) as f64),
+                app.locale.megabytes(p.memory, 1)
+            ),
+            None => format!("{pid} (not running)"),
+        };
+        f.render_widget(Paragraph::new(label), Rect::new(inner.x, y, inner.width, 1));
+
+        let Some(history) = app.pin_history.get(pid) else {
+            continue;
+        };
+        let halves = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(Rect::new(inner.x, y + 1, inner.width, 1));
+        let cpu = tail(&history.cpu, halves[0].width);
+        let memory = tail(&history.memory, halves[1].width);
+        f.render_widget(
+            Sparkline::default()
+                .data(&cpu)
+                .style(Style::default().fg(app.theme.watch_cpu)),
+            halves[0],
+        );
+        f.render_widget(
+            Sparkline::default()
+                .data(&memory)
+                .style(Style::default().fg(app.theme.watch_memory)),
+            halves[1],
+        );
+    }
+}
+
+// システム全体の CPU とメモリの使用率の推移を左右に並べる
+fn draw_graphs(f: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title("Graphs");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let halves = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .spacing(1)
+        .split(inner);
+    let history = &app.usage_history;
+    let graphs = [
+        ("CPU", &history.cpu, app.theme.cpu),
+        ("Memory", &history.memory, app.theme.memory),
+    ];
+    for ((name, values, color), half) in graphs.into_iter().zip(halves.iter()) {
+        // 0.1% 単位
+        let label = match values.back() {
+            Some(&last) => format!("{name} {}", app.locale.percent(last as f64 / 10.0)),
+            None => format!("{name} -"),
+        };
+        let rows = Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).split(*half);
+        f.render_widget(
+            Paragraph::new(label).style(Style::default().fg(color)),
+            rows[0],
+        );
+        f.render_widget(
+            Sparkline::default()
+                .data(tail(values, rows[1].width))
+                .max(1000)
+                .style(Style::default().fg(color)),
+            rows[1],
+        );
+    }
+}
+
+// 幅に収まる分だけ新しい方から取り出す
+fn tail(values: &VecDeque<u64>, width: u16) -> Vec<u64> {
+    let skip = values.len().saturating_sub(width as usize);
+    values.iter().skip(skip).copied().collect()
+}
+
+fn process_style(app: &App, process: &ProcessInfo) -> Style {
+    let mut style = Style::default();
+    // 終了したプロセスは最後の値のまま、消えるまで取り消し線で残す
+    if app.is_exited(process.pid) {
+        style = style
+            .fg(app.theme.exited)
+            .add_modifier(Modifier::CROSSED_OUT);
+    } else if process.status == ProcessStatus::Zombie {
+        style = style.fg(app.theme.zombie).add_modifier(Modifier::BOLD);
+    } else if process.status == ProcessStatus::Stop {
+        // SIGSTOP で止めたプロセスは、動いているものと見分けられるよう薄くする
+        style = style.add_modifier(Modifier::DIM | Modifier::ITALIC);
+    } else if let Some(age) = app.new_process_age(process.pid) {
+        style = style.fg(app.theme.new_process[age as usize]);
+    }
+    // 追いかけているプロセスは選択を外しても見分けられるようにする
+    if app.is_followed(process.pid) {
+        style = style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    }
+    // Space で印を付けたもの
+    if app.is_tagged(process.pid) {
+        style = style.fg(app.theme.message).add_modifier(Modifier::BOLD);
+    }
+    if app.selected == Some(process.pid) {
+        style = style.add_modifier(Modifier::REVERSED);
+    }
+    style
+}
+
+// 大きく動いた直後は太字にして、収集のたびに色を薄くしていく
+fn changed_style(app: &App, age: u8) -> Style {
+    let style = Style::default().fg(app.theme.changed[age as usize]);
+    if age == 0 {
+        style.add_modifier(Modifier::BOLD)
+    } else {
+        style
+    }
+}
+
+/// プロセス表に出す列と幅
+pub fn table_columns(app: &App, snapshot: &Snapshot) -> Vec<(SortKey, u16)> {
+    let show_container = snapshot.processes.iter().any(|p| p.container.is_some());
+    process_columns(process_widths(app, show_container)).collect()
+}
+
+/// 1 行分のセルを列の幅に切り詰めて作る
+pub fn format_row(
+    app: &App,
+    snapshot: &Snapshot,
+    columns: &[(SortKey, u16)],
+    p: &ProcessInfo,
+) -> Vec<String> {
+    columns
+        .iter()
+        .map(|(key, width)| text::truncate(&process_cell(app, snapshot, p, *key), *width as usize))
+        .collect()
+}
+
+pub fn process_cell(app: &App, snapshot: &Snapshot, p: &ProcessInfo, key: SortKey) -> String {
+    let locale = &app.locale;
+    match key {
+        SortKey::Pid => p.pid.to_string(),
+        SortKey::User => p.user.to_string(),
+        SortKey::Category => app
+            .categories
+            .classify(&p.name, p.is_kernel_thread())
+            .map_or_else(|| "-".to_string(), |c| c.label(app.categories.icons)),
+        SortKey::Name => process_name(app, p),
+        SortKey::Container => p.container.as_deref().unwrap_or("-").to_string(),
+        SortKey::State => state_label(p.status).to_string(),
+        SortKey::WaitChannel => p
+            .wait_channel
+            .as_deref()
+            .map_or_else(|| "-".to_string(), wchan::describe),
+        SortKey::Threads if p.threads == 0 => "-".to_string(),
+        SortKey::Threads => locale.int(p.threads as u64),
+        SortKey::Cpu if snapshot.cpu_primed => {
+            locale.percent(app.cpu_mode.scale(p.cpu_usage, snapshot.cpu.count) as f64)
+        }
+        SortKey::Cpu => "-".to_string(),
+        SortKey::History => sparkline(
+            p.cpu_history
+                .iter()
+                .map(|&usage| app.cpu_mode.scale(usage, snapshot.cpu.count)),
+        ),
+        SortKey::Memory => locale.megabytes(p.memory, 1),
+        SortKey::Virtual => locale.megabytes(p.virtual_memory, 1),
+        // smaps_rollup を読めなかった（他のユーザーのプロセスなど）
+        SortKey::Shared | SortKey::Swap => {
+            let value = if key == SortKey::Shared {
+                p.shared
+            } else {
+                p.swap
+            };
+            value.map_or_else(|| "-".to_string(), |bytes| locale.megabytes(bytes, 1))
+        }
+        // 1 回目の収集では差が取れない
+        SortKey::MinorFaults | SortKey::MajorFaults => {
+            let rate = if key == SortKey::MinorFaults {
+                p.minor_faults
+            } else {
+                p.major_faults
+            };
+            rate.map_or_else(|| "-".to_string(), |rate| locale.int(rate))
+        }
+        SortKey::Node => p
+            .numa_pages
+            .as_deref()
+            .map_or_else(|| "-".to_string(), numa::label),
+        // 他のユーザーのプロセスは root でないと数えられない
+        SortKey::Files => p
+            .open_files
+            .map_or_else(|| "-".to_string(), |n| n.to_string()),
+        // 1 回目の収集では差が取れない
+        SortKey::Received | SortKey::Sent => {
+            let rate = if key == SortKey::Received {
+                p.net_received
+            } else {
+                p.net_sent
+            };
+            rate.map_or_else(|| "-".to_string(), |rate| byte_rate(locale, rate as f64))
+        }
+        SortKey::Time => cpu_time(p.cpu_time),
+        SortKey::Started => locale.started(p.start_time, unix_now()),
+        SortKey::Elapsed => elapsed(unix_now().saturating_sub(p.start_time)),
+    }
+}
+
+// 1 MB/s 未満は KB/s で出す
+fn byte_rate(locale: &Locale, rate: f64) -> String {
+    if rate < 1024.0 * 1024.0 {
+        format!("{} KB/s", locale.float(rate / 1024.0, 0))
+    } else {
+        format!("{}/s", locale.megabytes(rate as u64, 1))
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// 大きい方から 2 つの単位だけ（3d 4h、12m 5s）
+fn elapsed(secs: u64) -> String {
+    let units = [(86_400, "d"), (3_600, "h"), (60, "m"), (1, "s")];
+    let Some(first) = units.iter().position(|(size, _)| secs >= *size) else {
+        return "0s".to_string();
+    };
+    units[first..]
+        .iter()
+        .take(2)
+        .scan(secs, |rest, (size, unit)| {
+            let value = *rest / size;
+            *rest %= size;
+            Some(format!("{value}{unit}"))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// 1 文字で 1 回分。1 コア分（100%）で一番上まで、0 は空白
+fn sparkline(values: impl Iterator<Item = f32>) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    values
+        .map(|usage| match (usage / 100.0 * 8.0).ceil() as usize {
+            0 => ' ',
+            level => BLOCKS[level.min(8) - 1],
+        })
+        .collect()
+}
+
+// top の TIME+ と同じ mm:ss.cc
+fn cpu_time(millis: u64) -> String {
+    let centis = millis / 10;
+    format!(
+        "{}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+fn sort_arrow(app: &App) -> &'static str {
+    if app.sort_ascending() { "▲" } else { "▼" }
+}
+
+// 記号を使う配色では、Space で印を付けたものに「#」、ゾンビに「!」、リークの疑いに「^」、新しいプロセスに「+」、CPU かメモリが大きく動いたものに「*」を付ける
+// C でコマンドライン表示にしたときは、引数の無いカーネルスレッドだけ名前を出す
+fn process_name(app: &App, process: &ProcessInfo) -> String {
+    let name: String = if app.full_command && !process.cmd.is_empty() {
+        process.cmd.chars().skip(app.command_scroll).collect()
+    } else {
+        process.name.to_string()
+    };
+    // ビルドや負荷試験など、負荷が高くて当たり前のもの
+    let name = match app.noisy.kind(&process.name) {
+        Some(kind) => format!("[{kind}] {name}"),
+        None => name,
+    };
+    // SIGSTOP（Z や Ctrl-Z）で止まっているもの
+    let name = if process.status == ProcessStatus::Stop {
+        format!("[stopped] {name}")
+    } else {
+        name
+    };
+    match process_marker(app, process) {
+        "" => name,
+        marker => format!("{marker}{name}"),
+    }
+}
+
+// 記号を使わない配色では空
+fn process_marker(app: &App, process: &ProcessInfo) -> &'static str {
+    if !app.theme.symbols {
+        ""
+    } else if app.is_exited(process.pid) {
+        "x "
+    } else if app.is_tagged(process.pid) {
+        "# "
+    } else if process.status == ProcessStatus::Zombie {
+        "! "
+    } else if app.leaks.growth(process.pid).is_some() {
+        "^ "
+    } else if app.new_process_age(process.pid).is_some() {
+        "+ "
+    } else if [SortKey::Cpu, SortKey::Memory]
+        .into_iter()
+        .any(|key| app.change_age(process.pid, key).is_some())
+    {
+        "* "
+    } else if app.is_followed(process.pid) {
+        "> "
+    } else {
+        "  "
+    }
+}
+
+pub fn state_label(status: ProcessStatus) -> &'static str {
+    match status {
+        ProcessStatus::Run => "Running",
+        ProcessStatus::Sleep | ProcessStatus::Idle | ProcessStatus::Parked => "Sleeping",
+        ProcessStatus::UninterruptibleDiskSleep => "DiskWait",
+        ProcessStatus::Stop | ProcessStatus::Tracing => "Stopped",
+        ProcessStatus::Zombie => "Zombie",
+        ProcessStatus::Dead => "Dead",
+        ProcessStatus::Waking | ProcessStatus::Wakekill => "Waking",
+        ProcessStatus::LockBlocked => "Locked",
+        ProcessStatus::Unknown(_) => "Unknown",
+    }
+}
+
+// プロセスのスレッド（メインスレッドを含む）を CPU 使用率の高い順に表示する
+fn draw_threads(f: &mut Frame, app: &App, snapshot: &Snapshot, process: &ProcessInfo) {
+    let locale = &app.locale;
+    let mut threads: Vec<&ProcessInfo> = snapshot
+        .processes
+        .iter()
+        .filter(|p| p.pid == process.pid || (p.is_thread && p.parent == Some(process.pid)))
+        .collect();
+    threads.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage));
+    let area = centered(f.area(), 70, 20);
+    let rows: Vec<Row> = threads
+        .iter()
+        .take(area.height.saturating_sub(3) as usize)
+        .map(|t| {
+            Row::new(vec![
+                t.pid.to_string(),
+                t.name.to_string(),
+                state_label(t.status).to_string(),
+                if snapshot.cpu_primed {
+                    locale.percent(app.cpu_mode.scale(t.cpu_usage, snapshot.cpu.count) as f64)
+                } else {
+                    "-".to_string()
+                },
+            ])
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(8),
+            Constraint::Min(20),
+            Constraint::Length(9),
+            Constraint::Length(8),
+        ],
+    )
+    .header(
+        Row::new(vec!["TID", "Name", "State", "CPU"]).style(Style::default().fg(app.theme.header)),
+    )
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        "Threads of {} {} ({}, Esc to close)",
+        process.pid,
+        process.name,
+        threads.len()
+    )));
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+}
+
+// プロセスの詳細をポップアップで表示する
+fn draw_detail(f: &mut Frame, app: &App, process: &ProcessInfo) {
+    let locale = &app.locale;
+    let parent = process
+        .parent
+        .map(|p| p.to_string())
+        .unwrap_or_else(|| "-".to_string());
+    let mut rows = vec![
+        Row::new(vec!["PID".to_string(), process.pid.to_string()]),
+        Row::new(vec!["Parent".to_string(), parent]),
+        Row::new(vec!["Name".to_string(), process.name.to_string()]),
+        Row::new(vec!["User".to_string(), process.user.to_string()]),
+        Row::new(vec!["Command".to_string(), process.cmd.to_string()]),
+        // Windows の値はワーキングセット（タスクマネージャーのコミットサイズではない）
+        Row::new(vec![
+            if cfg!(windows) {
+                "Memory (working set)"
+            } else {
+                "Memory"
+            }
+            .to_string(),
+            locale.megabytes(process.memory, 1),
+        ]),
+        Row::new(vec![
+            "Started".to_string(),
+            locale.datetime(process.start_time),
+        ]),
+        Row::new(vec![
+            "Nice".to_string(),
+            actions::current_nice(process.pid).map_or_else(|| "-".to_string(), |n| n.to_string()),
+        ]),
+        Row::new(vec![
+            "I/O prio".to_string(),
+            actions::current_io_priority(process.pid)
+                .map_or_else(|| "-".to_string(), |p| p.to_string()),
+        ]),
+        Row::new(vec![
+            "Affinity".to_string(),
+            actions::current_affinity(process.pid)
+                .map_or_else(|| "-".to_string(), |cpus| actions::cpu_list(&cpus)),
+        ]),
+    ];
+    // WChan 列を出していなくても、詳細を開いたプロセスの分だけは読む
+    if process.status != ProcessStatus::Run
+        && let Some(symbol) = process
+            .wait_channel
+            .clone()
+            .or_else(|| wchan::read(process.pid))
+    {
+        rows.push(Row::new(vec![
+            "Waiting on".to_string(),
+            wchan::describe(&symbol),
+        ]));
+    }
+    let target = ProcessRef {
+        pid: process.pid,
+        start_time: process.start_time,
+    };
+    for timer in app.alerts.timers_for(target) {
+        let text = match timer.kind {
+            TimerKind::StillRunning { deadline, .. } => format!(
+                "alert if still running in {}",
+                alert::format_duration(deadline.saturating_duration_since(Instant::now()))
+            ),
+            TimerKind::Exit => "alert when it exits".to_string(),
+        };
+        rows.push(Row::new(vec!["Timer".to_string(), text]));
+    }
+    // ジョブ全体は終了できないので、K（子孫ごと終了）を案内する
+    #[cfg(windows)]
+    {
+        let job = crate::winjob::job_info(process.pid);
+        let in_job = match job.in_job {
+            Some(true) => "yes (K ends the process tree)",
+            Some(false) => "no",
+            None => "-",
+        };
+        rows.push(Row::new(vec!["Job".to_string(), in_job.to_string()]));
+        rows.push(Row::new(vec![
+            "Package".to_string(),
+            job.package.unwrap_or_else(|| "-".to_string()),
+        ]));
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let signing = crate::codesign::signing_info(process.pid, process.start_time);
+        let yes_no = |value: Option<bool>| match value {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "-",
+        };
+        rows.push(Row::new(vec![
+            "Sandboxed".to_string(),
+            yes_no(signing.sandboxed).to_string(),
+        ]));
+        rows.push(Row::new(vec![
+            "Hardened".to_string(),
+            yes_no(signing.hardened).to_string(),
+        ]));
+        rows.push(Row::new(vec![
+            "Signed by".to_string(),
+            signing.identity.unwrap_or_else(|| "-".to_string()),
+        ]));
+    }
+    let area = centered(f.area(), 70, rows.len() as u16 + 2);
+    let table = Table::new(rows, [Constraint::Length(10), Constraint::Min(10)]).block(
+        Block::default().borders(Borders::ALL).title(format!(
+            "{} {} ({})",
+            app.locale.text("Process"),
+            process.pid,
+            app.locale.text("Esc to close")
+        )),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+}
+
+// 接続一覧をポップアップで表示する。選択行が見える位置までずらす
+// r で相手をホスト名にする（引き終わるまでは数字のまま）
+#[cfg(feature = "net")]
+fn draw_connections(f: &mut Frame, app: &App, snapshot: &Snapshot, selected: usize) -> usize {
+    let resolver = app.resolver.as_ref().filter(|_| app.resolve_names);
+    let area = centered(f.area(), 110, 20);
+    let visible = area.height.saturating_sub(3) as usize;
+    let offset = (selected + 1).saturating_sub(visible);
+    let rows: Vec<Row> = snapshot
+        .connections
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(visible)
+        .map(|(i, c)| {
+            let name = c.pid.and_then(|pid| {
+                snapshot
+                    .processes
+                    .iter()
+                    .find(|p| p.pid == pid)
+                    .map(|p| p.name.to_string())
+            });
+            let row = Row::new(vec![
+                c.protocol.label().to_string(),
+                c.local.to_string(),
+                match resolver.and_then(|r| r.lookup(c.remote.ip())) {
+                    Some(host) => format!("{host}:{}", c.remote.port()),
+                    None => c.remote.to_string(),
+                },
+                c.state.to_string(),
+                c.pid.map_or_else(|| "-".to_string(), |pid| pid.to_string()),
+                name.unwrap_or_else(|| "-".to_string()),
+            ]);
+            if i == selected {
+                row.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(5),
+            Constraint::Length(30),
+            Constraint::Length(30),
+            Constraint::Length(11),
+            Constraint::Length(8),
+            Constraint::Min(10),
+        ],
+    )
+    .header(Row::new(vec![
+        "Proto", "Local", "Remote", "State", "PID", "Name",
+    ]))
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        "Connections ({}, Enter to select the process, r for {}, Esc to close)",
+        snapshot.connections.len(),
+        if resolver.is_some() {
+            "addresses"
+        } else {
+            "host names"
+        }
+    )));
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+    visible
+}
+
+// サービス一覧をポップアップで表示する。選択行が見える位置までずらす
+#[cfg(any(feature = "systemd", windows))]
+fn draw_services(f: &mut Frame, app: &App, snapshot: &Snapshot, selected: usize) -> usize {
+    let area = centered(f.area(), 110, 20);
+    let visible = area.height.saturating_sub(3) as usize;
+    let offset = (selected + 1).saturating_sub(visible);
+    let rows: Vec<Row> = snapshot
+        .services
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(visible)
+        .map(|(i, s)| {
+            // サービス単位の計測が無ければ（Windows など）、属するプロセスの合計にする
+            let members = || {
+                snapshot
+                    .processes
+                    .iter()
+                    .filter(|p| s.pids.contains(&p.pid))
+            };
+            let cpu = s
+                .cpu_usage
+                .or_else(|| (!s.pids.is_empty()).then(|| members().map(|p| p.cpu_usage).sum()));
+            let memory = s
+                .memory
+                .or_else(|| (!s.pids.is_empty()).then(|| members().map(|p| p.memory).sum()));
+            let state = if s.sub.is_empty() {
+                s.active.clone()
+            } else {
+                format!("{}/{}", s.active, s.sub)
+            };
+            let row = Row::new(vec![
+                s.name.to_string(),
+                state,
+                s.main_pid
+                    .map_or_else(|| "-".to_string(), |pid| pid.to_string()),
+                s.pids.len().to_string(),
+                cpu.map_or_else(|| "-".to_string(), |cpu| app.locale.percent(cpu as f64)),
+                memory.map_or_else(|| "-".to_string(), |bytes| app.locale.megabytes(bytes, 1)),
+            ]);
+            let row = if s.active == "failed" {
+                row.style(Style::default().fg(app.theme.warning))
+            } else {
+                row
+            };
+            if i == selected {
+                row.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(30),
+            Constraint::Length(20),
+            Constraint::Length(8),
+            Constraint::Length(5),
+            Constraint::Length(8),
+            Constraint::Length(12),
+        ],
+    )
+    .header(Row::new(vec![
+        "Service", "State", "Main PID", "Procs", "CPU", "Memory",
+    ]))
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        "Services ({}, Enter to select the main process, f to show its processes, Esc to close)",
+        snapshot.services.len()
+    )));
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+    visible
+}
+
+// ユーザーごとの集計をポップアップで表示する。選択行が見える位置までずらす
+fn draw_users(f: &mut Frame, app: &App, snapshot: &Snapshot, selected: usize) -> usize {
+    let users = app.user_usage(snapshot);
+    let area = centered(f.area(), 70, 20);
+    let visible = area.height.saturating_sub(3) as usize;
+    let offset = (selected + 1).saturating_sub(visible);
+    let rows: Vec<Row> = users
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(visible)
+        .map(|(i, u)| {
+            let row = Row::new(vec![
+                u.user.to_string(),
+                u.processes.to_string(),
+                app.locale.percent(u.cpu as f64),
+                app.locale.megabytes(u.memory, 1),
+            ]);
+            if i == selected {
+                row.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        })
+        .collect();
+    // 並べ替えている列の見出しに印を付ける
+    let header = [
+        UserSort::User,
+        UserSort::Processes,
+        UserSort::Cpu,
+        UserSort::Memory,
+    ]
+    .map(|key| {
+        if key == app.user_sort {
+            format!("{} ▼", key.label())
+        } else {
+            key.label().to_string()
+        }
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(16),
+            Constraint::Length(7),
+            Constraint::Length(10),
+            Constraint::Length(14),
+        ],
+    )
+    .header(Row::new(header.to_vec()))
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        "Users ({}, Enter to show their processes, s to sort, Esc to close)",
+        users.len()
+    )));
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+    visible
+}
+
+// 開いているファイルをポップアップで表示する。選択行が見える位置までずらす
+fn draw_files(f: &mut Frame, view: &FilesView) -> usize {
+    let area = centered(f.area(), 110, 20);
+    let visible = area.height.saturating_sub(3) as usize;
+    let offset = (view.selected + 1).saturating_sub(visible);
+    let rows: Vec<Row> = view
+        .files
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(visible)
+        .map(|(i, file)| {
+            let row = Row::new(vec![
+                file.fd.to_string(),
+                file.kind.to_string(),
+                file.name.clone(),
+            ]);
+            if i == view.selected {
+                row.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Min(10),
+        ],
+    )
+    .header(Row::new(vec!["FD", "Type", "Name"]))
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        "Open files of {} ({}, Esc to close)",
+        view.pid,
+        view.files.len()
+    )));
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+    visible
+}
+
+// 相手ごとに 1 行で、つながり方をまとめて並べる
+fn draw_peers(f: &mut Frame, snapshot: &Snapshot, view: &PeersView) -> usize {
+    let area = centered(f.area(), 110, 20);
+    let visible = area.height.saturating_sub(3) as usize;
+    let offset = (view.selected + 1).saturating_sub(visible);
+    let mut rows: Vec<Row> = view
+        .peers
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(visible)
+        .map(|(i, peer)| {
+            let name = snapshot
+                .processes
+                .iter()
+                .find(|p| p.pid == peer.pid)
+                .map(|p| p.name.to_string())
+                .unwrap_or_default();
+            let channels: Vec<String> = peer.channels.iter().map(|c| c.describe()).collect();
+            let row = Row::new(vec![peer.pid.to_string(), name, channels.join(", ")]);
+            if i == view.selected {
+                row.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        })
+        .collect();
+    if rows.is_empty() {
+        rows.push(Row::new(vec![
+            String::new(),
+            String::new(),
+            "No pipes or sockets to other readable processes".to_string(),
+        ]));
+    }
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(8),
+            Constraint::Length(18),
+            Constraint::Min(10),
+        ],
+    )
+    .header(Row::new(vec!["PID", "Name", "Via"]))
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        "Connected to {} ({}, Enter to select the process, Esc to close)",
+        view.pid,
+        view.peers.len()
+    )));
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+    visible
+}
+
+// 増えたもの・消えたもの・続いているものの順に、基準からの増減を符号付きで並べる
+fn draw_baseline(f: &mut Frame, app: &App, view: &BaselineView) -> usize {
+    let area = centered(f.area(), 100, 24);
+    let visible = area.height.saturating_sub(3) as usize;
+    let offset = (view.selected + 1).saturating_sub(visible);
+    let sign = |n: i64| if n < 0 { "-" } else { "+" };
+    let mut rows: Vec<Row> = view
+        .rows
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(visible)
+        .map(|(i, diff)| {
+            let change = match diff.change {
+                Change::Started => "started",
+                Change::Exited => "exited",
+                Change::Running => "",
+            };
+            let cpu = match diff.change {
+                Change::Exited => String::new(),
+                _ => format!(
+                    "{}{}",
+                    sign(diff.cpu_time),
+                    cpu_time(diff.cpu_time.unsigned_abs())
+                ),
+            };
+            let memory = format!(
+                "{}{}",
+                sign(diff.memory),
+                app.locale.megabytes(diff.memory.unsigned_abs(), 1)
+            );
+            let row = Row::new(vec![
+                change.to_string(),
+                diff.pid.to_string(),
+                diff.name.to_string(),
+                cpu,
+                memory,
+            ]);
+            if i == view.selected {
+                row.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        })
+        .collect();
+    if rows.is_empty() {
+        rows.push(Row::new(vec![
+            String::new(),
+            String::new(),
+            "No processes changed".to_string(),
+        ]));
+    }
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Min(18),
+            Constraint::Length(12),
+            Constraint::Length(14),
+        ],
+    )
+    .header(Row::new(vec!["", "PID", "Name", "CPU time", "Memory"]))
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        "Since {} ({}, Enter to select, m to mark now, Esc to close)",
+        app.locale.time(view.since),
+        view.rows.len()
+    )));
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+    visible
+}
+
+// グループは深さの分だけ字下げし、畳めるものに +/- を付ける。プロセスはその下にもう 1 段下げる
+fn draw_cgroup_tree(f: &mut Frame, app: &App, snapshot: &Snapshot, view: &CgroupTreeView) -> usize {
+    let area = centered(f.area(), 100, 30);
+    let visible = area.height.saturating_sub(3) as usize;
+    let tree_rows = view.rows();
+    let offset = (view.selected + 1).saturating_sub(visible);
+    let rows: Vec<Row> = tree_rows
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(visible)
+        .map(|(i, tree_row)| {
+            let row = match *tree_row {
+                TreeRow::Group(index) => {
+                    let node = &view.tree.nodes[index];
+                    let marker = match (node.has_children, view.collapsed.contains(&node.path)) {
+                        (false, _) => " ",
+                        (true, true) => "+",
+                        (true, false) => "-",
+                    };
+                    Row::new(vec![
+                        format!("{}{marker} {}", "  ".repeat(node.depth), node.name()),
+                        node.cpu
+                            .map_or_else(String::new, |cpu| format!("{cpu:.1}%")),
+                        node.memory
+                            .map_or_else(String::new, |m| app.locale.megabytes(m, 1)),
+                        node.pids.len().to_string(),
+                    ])
+                }
+                TreeRow::Process(index, pid) => {
+                    let depth = view.tree.nodes[index].depth + 1;
+                    let process = snapshot.processes.iter().find(|p| p.pid == pid);
+                    Row::new(vec![
+                        format!(
+                            "{}  {pid} {}",
+                            "  ".repeat(depth),
+                            process.map(|p| p.name.to_string()).unwrap_or_default()
+                        ),
+                        process.map_or_else(String::new, |p| format!("{:.1}%", p.cpu_usage)),
+                        process.map_or_else(String::new, |p| app.locale.megabytes(p.memory, 1)),
+                        String::new(),
+                    ])
+                    .style(Style::default().fg(Color::DarkGray))
+                }
+            };
+            if i == view.selected {
+                row.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(30),
+            Constraint::Length(8),
+            Constraint::Length(12),
+            Constraint::Length(6),
+        ],
+    )
+    .header(Row::new(vec!["Group", "CPU", "Memory", "Procs"]))
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        "Cgroups ({}, <-/-> to fold, Enter to select a process, Esc to close)",
+        view.tree.nodes.len()
+    )));
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+    visible
+}
+
+// ログの末尾（さかのぼっていればその分だけ前）を、折り返さずに並べる
+#[cfg(feature = "journal")]
+fn draw_journal(f: &mut Frame, view: &JournalView) -> usize {
+    let area = centered(f.area(), 140, 30);
+    let visible = area.height.saturating_sub(2) as usize;
+    let source = match &view.unit {
+        Some(unit) => unit.clone(),
+        None => format!("PID {}", view.pid),
+    };
+    let (title, lines) = match view.lines() {
+        None => (format!("Log of {source} (reading…)"), Vec::new()),
+        Some(Err(e)) => (format!("Log of {source}"), vec![e]),
+        Some(Ok(lines)) => {
+            let end = lines.len().saturating_sub(view.scroll.min(lines.len()));
+            let start = end.saturating_sub(visible);
+            let state = if view.scroll == 0 {
+                "following".to_string()
+            } else {
+                format!("{} lines back, End to follow", lines.len() - end)
+            };
+            (
+                format!("Log of {source} ({}, {state}, Esc to close)", lines.len()),
+                lines[start..end].to_vec(),
+            )
+        }
+    };
+    let text: Vec<Line> = lines.into_iter().map(Line::from).collect();
+    let paragraph = Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+    visible
+}
+
+// 自分の時間の多いシンボルから、入るだけ並べる
+#[cfg(feature = "profile")]
+fn draw_cpu_profile(f: &mut Frame, app: &App, view: &ProfileView) {
+    let area = centered(f.area(), 120, 30);
+    let source = format!("{} ({})", view.name, view.pid);
+    let message = |title: String, text: String| {
+        Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title(title))
+    };
+    f.render_widget(Clear, area);
+    let symbols = match view.result() {
+        None => {
+            let left = view.remaining().as_secs_f64().ceil();
+            let title = format!("Profile of {source} (sampling, {left:.0}s left, Esc to cancel)");
+            let text = format!(
+                "Recording on-CPU samples with perf for {:.0} seconds…",
+                view.duration.as_secs_f64()
+            );
+            f.render_widget(message(title, text), area);
+            return;
+        }
+        Some(Err(e)) => {
+            f.render_widget(message(format!("Profile of {source}"), e), area);
+            return;
+        }
+        Some(Ok(symbols)) => symbols,
+    };
+    let mut rows: Vec<Row> = symbols
+        .iter()
+        .map(|symbol| {
+            Row::new(vec![
+                app.locale.percent(symbol.percent),
+                symbol.name.clone(),
+                symbol.object.clone(),
+            ])
+        })
+        .collect();
+    if rows.is_empty() {
+        rows.push(Row::new(vec![
+            String::new(),
+            "No samples (the process was idle or exited)".to_string(),
+            String::new(),
+        ]));
+    }
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(7),
+            Constraint::Min(20),
+            Constraint::Length(28),
+        ],
+    )
+    .header(Row::new(vec!["Self", "Symbol", "Object"]).style(Style::default().fg(app.theme.header)))
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        "Profile of {source} ({} symbols over {:.0}s, Esc to close)",
+        symbols.len(),
+        view.duration.as_secs_f64()
+    )));
+    f.render_widget(table, area);
+}
+
+// 記録を古い順に並べる。選択行が見える位置までずらす
+fn draw_events(f: &mut Frame, app: &App, selected: usize) -> usize {
+    let area = centered(f.area(), 120, 24);
+    let visible = area.height.saturating_sub(3) as usize;
+    let offset = (selected + 1).saturating_sub(visible);
+    let mut rows: Vec<Row> = app
+        .events
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(visible)
+        .map(|(i, event)| {
+            let row = Row::new(vec![
+                app.locale.time(event.time),
+                event.kind.label().to_string(),
+                event.text.clone(),
+            ]);
+            if i == selected {
+                row.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        })
+        .collect();
+    if rows.is_empty() {
+        rows.push(Row::new(vec![
+            String::new(),
+            String::new(),
+            "Nothing yet".to_string(),
+        ]));
+    }
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(11),
+            Constraint::Length(6),
+            Constraint::Min(10),
+        ],
+    )
+    .header(Row::new(vec!["Time", "Kind", "Event"]))
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        "Events ({}, s to save, Esc to close)",
+        app.events.len()
+    )));
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+    visible
+}
+
+// watchdog がしたことを古い順に並べ、最新のものが見えるように下に寄せる
+fn draw_watchdog(f: &mut Frame, app: &App) {
+    let area = centered(f.area(), 110, 20);
+    let visible = area.height.saturating_sub(2) as usize;
+    let log = app.watchdog.log();
+    let total = log.len();
+    let mut rows: Vec<Row> = log
+        .skip(total.saturating_sub(visible))
+        .map(|entry| Row::new(vec![app.locale.time(entry.time), entry.text.clone()]))
+        .collect();
+    if rows.is_empty() {
+        rows.push(Row::new(vec![String::new(), "Nothing yet".to_string()]));
+    }
+    let table = Table::new(rows, [Constraint::Length(11), Constraint::Min(10)]).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("Watchdog ({total}, Esc to close)")),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+}
+
+// 起動したときの健康診断の結果。重いものは warning、それ以外は caution の色
+fn draw_health(f: &mut Frame, app: &App) {
+    let findings = &app.health;
+    let area = centered(f.area(), 90, findings.len() as u16 + 2);
+    let rows = findings.iter().map(|finding| {
+        let color = if finding.severe {
+            app.theme.warning
+        } else {
+            app.theme.caution
+        };
+        Row::new(vec![
+            app.locale.text(finding.name).to_string(),
+            finding.detail.clone(),
+        ])
+        .style(Style::default().fg(color))
+    });
+    let table = Table::new(rows, [Constraint::Length(12), Constraint::Min(10)]).block(
+        Block::default().borders(Borders::ALL).title(format!(
+            "{} ({}, {})",
+            app.locale.text("Health check"),
+            findings.len(),
+            app.locale.text("Esc to close")
+        )),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+}
+
+/// ヒートマップの濃さ（20% ごと）。色の無い端末でも濃さで分かるようにする
+const HEAT_SHADES: [&str; 5] = [" ", "░", "▒", "▓", "█"];
+const HEAT_SHADES_ASCII: [&str; 5] = [" ", ".", ":", "o", "#"];
+
+// コアごとの使用率の推移。縦がコア、横が時間（右が新しい）。
+// コアが行に収まらなければ隣り合うコアを 1 行にまとめ、張り付いたコアが埋もれないよう最大値を出す
+fn draw_heatmap(f: &mut Frame, app: &App) {
+    let history = &app.usage_history.cores;
+    let cores = history.back().map_or(0, Vec::len);
+    let screen = f.area();
+    let area = centered(
+        screen,
+        screen.width.saturating_sub(4),
+        (cores as u16 + 3).max(6),
+    );
+    let block = Block::default().borders(Borders::ALL).title(format!(
+        "CPU heatmap ({cores} cores, newest on the right, Esc to close)"
+    ));
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+    if cores == 0 {
+        f.render_widget(Paragraph::new("Waiting for the second CPU sample"), inner);
+        return;
+    }
+    let rows = inner.height.saturating_sub(1).max(1) as usize;
+    let per_row = cores.div_ceil(rows);
+    let label_width = 8;
+    let width = inner.width.saturating_sub(label_width) as usize;
+    let shades = if app.ascii {
+        HEAT_SHADES_ASCII
+    } else {
+        HEAT_SHADES
+    };
+    let samples: Vec<&Vec<u8>> = history
+        .iter()
+        .skip(history.len().saturating_sub(width))
+        .collect();
+    let mut lines: Vec<Line> = (0..cores)
+        .step_by(per_row)
+        .map(|first| {
+            let last = (first + per_row).min(cores) - 1;
+            let label = if first == last {
+                format!("cpu{first}")
+            } else {
+                format!("{first}-{last}")
+            };
+            let mut spans = vec![Span::raw(format!(
+                "{label:<width$}",
+                width = label_width as usize
+            ))];
+            spans.extend(samples.iter().map(|sample| {
+                let usage = sample
+                    .get(first..=last.min(sample.len().saturating_sub(1)))
+                    .and_then(|cores| cores.iter().max())
+                    .copied()
+                    .unwrap_or(0);
+                let shade = (usage as usize / 20).min(shades.len() - 1);
+                Span::styled(
+                    shades[shade],
+                    usage_style(app, usage as f64 / 100.0, app.theme.cpu),
+                )
+            }));
+            Line::from(spans)
+        })
+        .collect();
+    let legend: Vec<String> = shades
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, shade)| format!("{shade} {}%+", i * 20))
+        .collect();
+    lines.push(Line::from(format!(
+        "{:width$}{}",
+        "",
+        legend.join("  "),
+        width = label_width as usize
+    )));
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
+// 環境変数を名前と値に分けて並べる。値が長ければ右端で切れる
+#[cfg(feature = "environ")]
+fn draw_environ(f: &mut Frame, view: &EnvironView) -> usize {
+    let area = centered(f.area(), 110, 20);
+    let visible = area.height.saturating_sub(3) as usize;
+    let offset = (view.selected + 1).saturating_sub(visible);
+    let shown = view.shown();
+    let rows: Vec<Row> = shown
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(visible)
+        .map(|(i, var)| {
+            let (name, value) = var.split_once('=').unwrap_or((var, ""));
+            let row = Row::new(vec![name.to_string(), value.to_string()]);
+            if i == view.selected {
+                row.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        })
+        .collect();
+    let filter = if view.query.is_empty() {
+        "type to filter".to_string()
+    } else {
+        format!("filter: {}", view.query)
+    };
+    let table = Table::new(rows, [Constraint::Length(24), Constraint::Min(10)])
+        .header(Row::new(vec!["Name", "Value"]))
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Environment of {} ({}/{}, {filter}, Esc to close)",
+            view.target.pid,
+            shown.len(),
+            view.vars.len()
+        )));
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+    visible
+}
+
+// CPU を格子状に並べ、動かしてよいものに印を付ける。カーソルの行が見える位置までずらす
+fn draw_affinity(f: &mut Frame, dialog: &AffinityDialog) {
+    const CELL: usize = 8;
+    let visible = dialog.cpus.len().div_ceil(AFFINITY_COLUMNS).min(14);
+    let cursor_row = dialog.cursor / AFFINITY_COLUMNS;
+    let offset = (cursor_row + 1).saturating_sub(visible);
+    let selected: Vec<usize> = (0..dialog.cpus.len())
+        .filter(|&cpu| dialog.cpus[cpu])
+        .collect();
+    let mut lines = vec![
+        Line::from(format!("Allowed: {}", actions::cpu_list(&selected))),
+        Line::default(),
+    ];
+    for (row, chunk) in dialog
+        .cpus
+        .chunks(AFFINITY_COLUMNS)
+        .enumerate()
+        .skip(offset)
+        .take(visible)
+    {
+        let spans: Vec<Span> = chunk
+            .iter()
+            .enumerate()
+            .map(|(column, &allowed)| {
+                let cpu = row * AFFINITY_COLUMNS + column;
+                let mark = if allowed { 'x' } else { ' ' };
+                let text = format!("[{mark}] {cpu:<width$}", width = CELL - 4);
+                if cpu == dialog.cursor {
+                    Span::styled(text, Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    Span::raw(text)
+                }
+            })
+            .collect();
+        lines.push(Line::from(spans));
+    }
+    lines.push(Line::default());
+    lines.push(Line::from(
+        "Space to toggle, a for all, Enter to apply, Esc to cancel",
+    ));
+    let area = centered(
+        f.area(),
+        (AFFINITY_COLUMNS * CELL) as u16 + 2,
+        lines.len() as u16 + 2,
+    );
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("CPU affinity of {}", dialog.target.pid)),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+// 検索結果をポップアップで表示する。選択行が見える位置までずらす
+fn draw_search(f: &mut Frame, search: &Search) -> usize {
+    let area = centered(f.area(), 110, 20);
+    let visible = area.height.saturating_sub(2) as usize;
+    let offset = (search.selected + 1).saturating_sub(visible);
+    let rows: Vec<Row> = search
+        .results
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(visible)
+        .map(|(i, result)| {
+            let row = Row::new(vec![result.kind.to_string(), result.text.clone()]);
+            if i == search.selected {
+                row.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        })
+        .collect();
+    let table = Table::new(rows, [Constraint::Length(10), Constraint::Min(10)]).block(
+        Block::default().borders(Borders::ALL).title(format!(
+            "Search \"{}\" ({}, Enter to jump, Esc to close)",
+            search.query,
+            search.results.len()
+        )),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+    visible
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+// プロンプト入力中はタイトルに入力欄を表示する
+fn process_title(app: &App) -> String {
+    let text = |english| app.locale.text(english);
+    if let Some(prompt) = &app.prompt {
+        return format!(
+            "{} - {}: {}_",
+            text("Processes"),
+            prompt.kind.label(),
+            prompt.text
+        );
+    }
+    let mut title = match &app.user_filter {
+        Some(user) => format!("{} ({}: {user})", text("Processes"), text("user")),
+        None => text("Processes").to_string(),
+    };
+    if let Some(preset) = app.current_preset() {
+        title.push_str(&format!(" [{}]", preset.name));
+    }
+    if let Some(filter) = &app.name_filter {
+        title.push_str(&format!(
+            " [{}: {filter}, {}]",
+            text("filter"),
+            text("Esc to clear")
+        ));
+    }
+    #[cfg(any(feature = "systemd", windows))]
+    if let Some(service) = &app.service_filter {
+        title.push_str(&format!(" [{service}, {}]", text("Esc to clear")));
+    }
+    if app.show_all {
+        title.push_str(&format!(" [{}]", text("all, A to return")));
+    }
+    if app.full_command {
+        title.push_str(&format!(
+            " - {}",
+            text("command line, Left/Right to scroll")
+        ));
+    }
+    if app.group_by_name {
+        title.push_str(&format!(" - {}", text("grouped by name, e to expand")));
+    }
+    // 他のユーザーのプロセスは見えないので、一覧が少ない理由を添える
+    match (app.termux, app.access.hidepid) {
+        (Some(_), _) => format!(
+            "{title} - {}",
+            text("only your own processes are visible on Android")
+        ),
+        (None, Some(hidepid)) => format!("{title} - {}", text(hidepid.note())),
+        (None, None) => title,
+    }
+}
+
+/// --dashboard のホスト一覧
+pub fn draw_dashboard(
+    f: &mut Frame,
+    theme: &Theme,
+    locale: &Locale,
+    hosts: &[(&HostConfig, HostStatus)],
+    selected: usize,
+    message: Option<&str>,
+) {
+    let rows: Vec<Row> = hosts
+        .iter()
+        .enumerate()
+        .map(|(i, (host, status))| {
+            let mut style = Style::default();
+            let cells = match status {
+                HostStatus::Up(snapshot) => {
+                    let cpu = snapshot.cpu.normalized();
+                    let memory = &snapshot.memory;
+                    let memory_percent = memory.used as f64 * 100.0 / memory.total.max(1) as f64;
+                    let load = snapshot.info.load_average.map(|l| locale.float(l, 2));
+                    if cpu >= 90.0 || memory_percent >= 90.0 {
+                        style = style.fg(theme.caution);
+                    }
+                    [
+                        locale.percent(cpu as f64),
+                        format!(
+                            "{} of {} MB",
+                            locale.percent(memory_percent),
+                            locale.int(memory.total / 1024 / 1024)
+                        ),
+                        load.join(" "),
+                        locale.int(snapshot.processes.len() as u64),
+                        "up".to_string(),
+                    ]
+                }
+                HostStatus::Connecting => ["-", "-", "-", "-", "connecting..."].map(str::to_string),
+                HostStatus::Down(reason) => {
+                    style = style.fg(theme.warning);
+                    let mut cells = ["-", "-", "-", "-", ""].map(str::to_string);
+                    cells[4] = format!("down: {reason}");
+                    cells
+                }
+            };
+            if i == selected {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            let mut row = vec![host.name.clone(), host.addr.clone()];
+            row.extend(cells);
+            Row::new(row).style(style)
+        })
+        .collect();
+    let title = match message {
+        Some(message) => format!("Hosts - {message}"),
+        None => "Hosts (Enter to open, q to quit)".to_string(),
+    };
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(16),
+            Constraint::Length(22),
+            Constraint::Length(8),
+            Constraint::Length(20),
+            Constraint::Length(17),
+            Constraint::Length(7),
+            Constraint::Min(10),
+        ],
+    )
+    .header(
+        Row::new(vec![
+            "Host", "Address", "CPU", "Memory", "Load", "Procs", "Status",
+        ])
+        .style(Style::default().fg(theme.header)),
+    )
+    .block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(table, f.area());
+}
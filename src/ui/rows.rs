@@ -0,0 +1,102 @@
+// プロセス表のセルの文字列を PID ごとに覚えておく
+//
+// 1 秒ごとの更新でも、キー入力やマウスのたびに描画し直すので、値の変わっていない行まで
+// 毎回整形すると文字列の確保が多い。整形に使った値と一緒に覚え、変わった行だけ作り直す。
+use std::collections::HashMap;
+
+use sysinfo::Pid;
+
+use crate::app::{App, CpuMode, SortKey};
+use crate::sampler::{ProcessInfo, Snapshot};
+
+/// 表全体に効く表示の設定。変わったら覚えた文字列をすべて捨てる
+#[derive(Debug, Default, PartialEq)]
+struct Layout {
+    columns: Vec<(SortKey, u16)>,
+    full_command: bool,
+    command_scroll: usize,
+    cpu_mode: CpuMode,
+    cpu_count: usize,
+    cpu_primed: bool,
+    symbols: bool,
+    /// Category 列に Nerd Font のアイコンを出す
+    category_icons: bool,
+    /// 起動時刻の列を出しているときの現在時刻（秒が変わったら経過時間を作り直す）
+    clock: Option<u64>,
+}
+
+#[derive(Debug)]
+struct CachedRow {
+    /// 整形したときの値。まとめた行は合計なので、PID だけでは見分けられない
+    process: ProcessInfo,
+    marker: &'static str,
+    /// 列の幅に切り詰めたもの
+    cells: Vec<String>,
+    /// 今回の描画で見えていたか
+    visible: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct RowCache {
+    layout: Layout,
+    rows: HashMap<Pid, CachedRow>,
+}
+
+impl RowCache {
+    /// 見えている行を整形し直す。値も印も変わっていない行はそのまま使い、見えなくなった行は捨てる
+    pub fn update(
+        &mut self,
+        app: &App,
+        snapshot: &Snapshot,
+        columns: &[(SortKey, u16)],
+        processes: &[&ProcessInfo],
+    ) {
+        let layout = Layout {
+            columns: columns.to_vec(),
+            full_command: app.full_command,
+            command_scroll: app.command_scroll,
+            cpu_mode: app.cpu_mode,
+            cpu_count: snapshot.cpu.count,
+            cpu_primed: snapshot.cpu_primed,
+            symbols: app.theme.symbols,
+            category_icons: app.categories.icons,
+            clock: columns
+                .iter()
+                .any(|(key, _)| key.is_process_time())
+                .then(super::unix_now),
+        };
+        if layout != self.layout {
+            self.layout = layout;
+            self.rows.clear();
+        }
+        for row in self.rows.values_mut() {
+            row.visible = false;
+        }
+        for &p in processes {
+            let marker = super::process_marker(app, p);
+            if let Some(row) = self.rows.get_mut(&p.pid)
+                && row.marker == marker
+                && row.process == *p
+            {
+                row.visible = true;
+                continue;
+            }
+            let cells = super::format_row(app, snapshot, columns, p);
+            self.rows.insert(
+                p.pid,
+                CachedRow {
+                    process: p.clone(),
+                    marker,
+                    cells,
+                    visible: true,
+                },
+            );
+        }
+        self.rows.retain(|_, row| row.visible);
+    }
+
+    /// update で整形した行のセル
+    pub fn cells(&self, pid: Pid) -> &[String] {
+        self.rows.get(&pid).map_or(&[], |row| &row.cells)
+    }
+}
@@ -0,0 +1,161 @@
+// 画面下端のステータスバー
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::Widget,
+};
+
+use crate::app::App;
+use crate::config::Keymap;
+
+/// 主なキーとその説明
+const KEY_HINTS: [(&str, &str); 17] = [
+    ("q", "Quit"),
+    ("k", "Kill"),
+    ("K", "Kill tree"),
+    ("Z", "Freeze"),
+    ("u", "User"),
+    ("W", "Users"),
+    ("O", "Heatmap"),
+    ("/", "Search"),
+    (":", "Command"),
+    ("p", "Pin"),
+    ("T", "Timer"),
+    ("Enter", "Detail"),
+    ("t", "Threads"),
+    ("g", "Group"),
+    // K は子孫ごとの kill なので h
+    ("h", "Kernel"),
+    ("I", "Irix"),
+    ("F12", "Profile"),
+];
+
+/// 時刻・更新間隔・並び順・絞り込みと、キーの案内（またはメッセージ）を 1 行で表示する
+pub struct StatusBar<'a> {
+    app: &'a App,
+}
+
+impl<'a> StatusBar<'a> {
+    pub fn new(app: &'a App) -> Self {
+        Self { app }
+    }
+
+    fn stats(&self) -> String {
+        let app = self.app;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let mut parts = vec![
+            app.locale.time(now),
+            format!(
+                "every {}s{}",
+                app.locale
+                    .float(app.current_interval().as_secs_f64(), 1)
+                    .trim_end_matches(['0'])
+                    .trim_end_matches(app.locale.decimal),
+                if app.idle_interval.is_some() {
+                    " (idle)"
+                } else {
+                    ""
+                }
+            ),
+            format!("sort: {} {}", app.sort.label(), sort_order(app)),
+        ];
+        if app.readonly {
+            parts.push("read-only".to_string());
+        }
+        if app.caffeinated() {
+            parts.push("awake".to_string());
+        }
+        if let Some(user) = &app.user_filter {
+            parts.push(format!("user: {user}"));
+        }
+        if let Some(category) = &app.category_filter {
+            parts.push(format!("category: {category}"));
+        }
+        if let Some(preset) = app.current_preset() {
+            parts.push(format!("preset: {}", preset.name));
+        }
+        if let Some(filter) = &app.name_filter {
+            parts.push(format!("filter: {filter}"));
+        }
+        if let Some(pattern) = &app.locate {
+            parts.push(format!("locate: {pattern} (n / <)"));
+        }
+        #[cfg(any(feature = "systemd", windows))]
+        if let Some(service) = &app.service_filter {
+            parts.push(format!("service: {service}"));
+        }
+        if let Some(followed) = &app.followed {
+            let state = if followed.exited { ", exited" } else { "" };
+            parts.push(format!(
+                "following {} ({}{state})",
+                followed.name, followed.pid
+            ));
+        }
+        if !app.tagged.is_empty() {
+            parts.push(format!("tagged {}", app.tagged.len()));
+        }
+        if !app.pinned.is_empty() {
+            parts.push(format!("watching {}", app.pinned.len()));
+        }
+        // メッセージが消えたあとも、最後の失敗は残しておく
+        if let Some((error, at)) = &app.last_error {
+            let secs = at.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+            parts.push(format!("last error {}: {error}", app.locale.time(secs)));
+        }
+        // --plain では区切りの記号を読み上げさせない
+        parts.join(if app.plain { "; " } else { " | " })
+    }
+}
+
+// --plain では矢印の代わりに言葉で書く
+fn sort_order(app: &App) -> &'static str {
+    match (app.plain, app.sort_ascending()) {
+        (false, _) => super::sort_arrow(app),
+        (true, true) => "ascending",
+        (true, false) => "descending",
+    }
+}
+
+impl Widget for StatusBar<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let mut spans = vec![Span::raw(self.stats()), Span::raw("  ")];
+        // メッセージがあるうちはキーの案内の代わりに表示する
+        if let Some(message) = self.app.current_message() {
+            spans.push(Span::styled(
+                message.to_string(),
+                Style::default().fg(self.app.theme.message),
+            ));
+        } else {
+            for (key, label) in KEY_HINTS {
+                // --readonly では断られるキーを案内しない
+                if self.app.readonly && matches!(key, "k" | "K" | "Z") {
+                    continue;
+                }
+                // vim のキー割り当てでは k と g が x と z に移る
+                let key = match (self.app.keymap, key) {
+                    (Keymap::Vim, "k") => "x",
+                    (Keymap::Vim, "g") => "z",
+                    _ => key,
+                };
+                let label = self.app.locale.text(label);
+                // 反転で区切った「qQuit」はそのまま読まれてしまう
+                if self.app.plain {
+                    spans.push(Span::raw(format!("{key}: {label}, ")));
+                    continue;
+                }
+                spans.push(Span::styled(
+                    key,
+                    Style::default().add_modifier(Modifier::REVERSED),
+                ));
+                spans.push(Span::raw(format!("{label} ")));
+            }
+        }
+        Line::from(spans).render(area, buf);
+    }
+}
@@ -0,0 +1,2380 @@
+// 描画のテスト。--mock と同じ作り物の値を TestBackend に描いて、画面の文字と色を確かめる
+use std::io;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+use ratatui::buffer::Buffer;
+use ratatui::style::{Color, Modifier, Style};
+use sysinfo::Signal;
+
+use crate::access::{Access, HidePid};
+use crate::actions::{ActionError, ActionOutcome, Command, ProcessRef};
+use crate::alert::Metric;
+use crate::app::{
+    AffinityDialog, App, CgroupTreeView, Effect, FilesView, PeersView, Prompt, PromptKind, SortKey,
+};
+use crate::baseline::Change;
+use crate::cli::Args;
+use crate::clipboard;
+use crate::config::{Config, ConfigWatcher, Keymap};
+use crate::faults;
+use crate::files::OpenFile;
+use crate::filter::Filter;
+use crate::i18n::Language;
+use crate::layout::{self, ScreenLayout, SideColumn, Slot};
+use crate::locale::Locale;
+use crate::meminfo::{HugePages, MemoryBreakdown, Overcommit, ThpMode};
+use crate::numa::{NumaNode, NumaSnapshot};
+use crate::pattern::Pattern;
+use crate::peers::{self, Channel, Endpoints};
+use crate::sampler::{Collector, ProcessChanges, Snapshot};
+use crate::shm::{ShmSnapshot, TmpfsMount};
+use crate::source::MockSource;
+use crate::state::UiState;
+use crate::term::ColorDepth;
+use crate::text;
+use crate::theme;
+use crate::watchdog::{Watchdog, WatchdogRule};
+
+// 2 回収集して CPU 使用率が確定した扱いにする
+fn snapshot() -> Snapshot {
+    let mut collector = Collector::with_source(MockSource::default());
+    collector.collect_all();
+    collector.collect_all();
+    let mut snapshot = collector.snapshot().clone();
+    snapshot.cpu_primed = true;
+    snapshot
+}
+
+// 環境変数のロケールや TERM に左右されないよう、英語の既定の表記と Unicode の罫線にする
+fn app(snapshot: &Snapshot) -> App {
+    let mut app = App::new(&Args::default(), &Config::default());
+    app.locale = Locale::default();
+    app.ascii = false;
+    app.on_snapshot(snapshot, &ProcessChanges::default());
+    app
+}
+
+fn render(app: &App, snapshot: &Snapshot, width: u16, height: u16) -> Buffer {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).expect("test terminal");
+    terminal
+        .draw(|f| {
+            super::draw(f, app, snapshot);
+        })
+        .expect("draw");
+    terminal.backend().buffer().clone()
+}
+
+// 全角文字の右半分のセルは読み飛ばす
+fn lines(buffer: &Buffer) -> Vec<String> {
+    let area = buffer.area;
+    (0..area.height)
+        .map(|y| {
+            let mut line = String::new();
+            let mut x = 0;
+            while x < area.width {
+                let symbol = buffer[(x, y)].symbol();
+                line.push_str(symbol);
+                x += text::width(symbol).max(1) as u16;
+            }
+            line
+        })
+        .collect()
+}
+
+fn contains(buffer: &Buffer, text: &str) -> bool {
+    lines(buffer).iter().any(|line| line.contains(text))
+}
+
+// 枠の見出し（┌Title─）が何行目にあるか
+fn title_row(buffer: &Buffer, title: &str) -> Option<usize> {
+    lines(buffer)
+        .iter()
+        .position(|line| line.contains(&format!("┌{title}")))
+}
+
+fn press(app: &mut App, snapshot: &Snapshot, code: KeyCode, modifiers: KeyModifiers) {
+    app.handle_key(KeyEvent::new(code, modifiers), snapshot);
+}
+
+#[test]
+fn draws_every_panel_on_a_large_terminal() {
+    let snapshot = snapshot();
+    let buffer = render(&app(&snapshot), &snapshot, 160, 50);
+    let titles = ["CPU (Irix)", "Memory", "Top consumers", "Processes", "Info"];
+    let rows: Vec<usize> = titles
+        .iter()
+        .map(|title| title_row(&buffer, title).unwrap_or_else(|| panic!("{title} is missing")))
+        .collect();
+    // 上から決まった順に並ぶ
+    assert!(rows.windows(2).all(|pair| pair[0] < pair[1]), "{rows:?}");
+}
+
+#[test]
+fn cpu_and_memory_panels_show_the_totals() {
+    let snapshot = snapshot();
+    let buffer = render(&app(&snapshot), &snapshot, 160, 50);
+    assert!(contains(&buffer, "/ 400%"));
+    assert!(contains(&buffer, "/ 16384 MB"));
+    assert!(contains(&buffer, "Swap: 256 MB / 2048 MB"));
+}
+
+#[test]
+fn process_table_is_sorted_by_cpu() {
+    let snapshot = snapshot();
+    let buffer = render(&app(&snapshot), &snapshot, 160, 50);
+    let lines = lines(&buffer);
+    let header = title_row(&buffer, "Processes").expect("process table") + 1;
+    assert!(lines[header].contains("PID"));
+    assert!(lines[header].contains("CPU ▼"));
+    assert!(lines[header + 1].contains("cargo"), "{}", lines[header + 1]);
+    assert!(
+        lines[header + 2].contains("python3"),
+        "{}",
+        lines[header + 2]
+    );
+}
+
+#[test]
+fn equal_cpu_rows_fall_back_to_the_secondary_keys() {
+    let mut snapshot = snapshot();
+    let processes = &mut snapshot.processes;
+    for (i, p) in processes.iter_mut().enumerate() {
+        // 小数点以下だけが違う値と、まったく同じ値を混ぜる
+        p.cpu_usage = [0.2, 0.7, 0.0][i % 3];
+        p.memory = (i as u64 % 2 + 1) * 1024 * 1024;
+    }
+    processes.reverse();
+    let config = Config {
+        sort_then: vec![SortKey::Memory],
+        ..Config::default()
+    };
+    let mut app = App::new(&Args::default(), &config);
+    app.on_snapshot(&snapshot, &ProcessChanges::default());
+    let keys: Vec<_> = app
+        .visible_processes(&snapshot)
+        .iter()
+        .map(|p| (p.cpu_usage, p.memory, p.pid))
+        .collect();
+    let mut expected = keys.clone();
+    expected.sort_by(|a, b| b.0.total_cmp(&a.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+    assert_eq!(keys, expected);
+    assert_eq!(keys[0].0, 0.7);
+}
+
+#[test]
+fn top_consumers_name_the_busiest_processes() {
+    let snapshot = snapshot();
+    let buffer = render(&app(&snapshot), &snapshot, 160, 50);
+    let row = title_row(&buffer, "Top consumers").expect("top consumers") + 1;
+    let line = &lines(&buffer)[row];
+    assert!(line.contains("cargo (900)"), "{line}");
+    assert!(line.contains("postgres (532)"), "{line}");
+}
+
+#[test]
+fn leaders_layout_lists_the_top_three_by_cpu_memory_and_io() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    press(&mut app, &snapshot, KeyCode::Char(':'), KeyModifiers::NONE);
+    app.prompt.as_mut().expect("command prompt").text = "layout leaders".to_string();
+    press(&mut app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+    assert_eq!(app.layouts[app.layout].name, "leaders");
+    let buffer = render(&app, &snapshot, 160, 50);
+    let row = title_row(&buffer, "Leaders").expect("leaders panel");
+    let lines = lines(&buffer);
+    // 見出しの下に指標ごとの表が横に並び、それぞれ多い順
+    assert!(lines[row + 1].contains("CPU") && lines[row + 1].contains("I/O"));
+    let first = &lines[row + 2];
+    assert!(
+        first.contains("63.0%") && first.contains("900.0 MB"),
+        "{first}"
+    );
+    assert!(
+        first.contains("postgres") && first.contains("4.0 MB/s"),
+        "{first}"
+    );
+    assert!(lines[row + 4].contains("9.6%") && lines[row + 4].contains("leaky"));
+    assert!(title_row(&buffer, "Top consumers").is_none());
+}
+
+#[test]
+fn noisy_processes_are_labelled_and_can_leave_the_top_consumers() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(contains(&buffer, "[build] cargo"));
+
+    app.noisy.exclude_from_top = true;
+    let buffer = render(&app, &snapshot, 160, 50);
+    let row = title_row(&buffer, "Top consumers").expect("top consumers") + 1;
+    let line = &lines(&buffer)[row];
+    assert!(!line.contains("cargo"), "{line}");
+    assert!(line.contains("CPU "), "{line}");
+}
+
+#[test]
+fn category_column_tags_processes_and_category_filters_them() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    assert!(!contains(&render(&app, &snapshot, 160, 50), "Category"));
+    press(&mut app, &snapshot, KeyCode::Char('d'), KeyModifiers::NONE);
+    let buffer = render(&app, &snapshot, 160, 50);
+    let lines = lines(&buffer);
+    let row = |pid: &str| {
+        lines
+            .iter()
+            .find(|line| line.contains(&format!(" {pid} ")))
+            .unwrap_or_else(|| panic!("{pid}"))
+            .clone()
+    };
+    assert!(contains(&buffer, "Category"));
+    assert!(row("530").contains(" db "), "{}", row("530"));
+    assert!(row("812").contains(" sh "), "{}", row("812"));
+    assert!(row("611").contains(" - "), "{}", row("611"));
+
+    // Nerd Font のアイコン
+    app.categories.icons = true;
+    assert!(contains(&render(&app, &snapshot, 160, 50), "\u{f1c0}"));
+
+    // :category は前方一致で選ぶ
+    let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Char(':'), KeyModifiers::NONE);
+    app.prompt.as_mut().expect("command prompt").text = "category data".to_string();
+    app.handle_key(enter, &snapshot);
+    assert_eq!(app.category_filter.as_deref(), Some("database"));
+    let visible = app.visible_processes(&snapshot);
+    assert_eq!(visible.len(), 3);
+    assert!(visible.iter().all(|p| &*p.name == "postgres"));
+    assert!(contains(
+        &render(&app, &snapshot, 160, 50),
+        "category: database"
+    ));
+
+    press(&mut app, &snapshot, KeyCode::Char(':'), KeyModifiers::NONE);
+    app.prompt.as_mut().expect("command prompt").text = "category nope".to_string();
+    app.handle_key(enter, &snapshot);
+    assert_eq!(app.category_filter.as_deref(), Some("database"));
+    assert_eq!(
+        app.message.as_ref().map(|m| m.0.as_str()),
+        Some("Unknown category: nope (browser, compiler, database, container, shell, kernel)")
+    );
+}
+
+#[test]
+fn saving_as_csv_or_json_writes_every_filtered_row_in_table_order() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    app.name_filter = Some(Filter::parse("python").expect("filter"));
+    let mut saved = |format: &str| {
+        press(&mut app, &snapshot, KeyCode::Char('S'), KeyModifiers::NONE);
+        app.prompt.as_mut().expect("save prompt").text = format.to_string();
+        press(&mut app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+        let message = app.message.as_ref().expect("message").0.clone();
+        let path = message
+            .strip_prefix("Saved 2 processes to ")
+            .unwrap_or_else(|| panic!("{message}"))
+            .to_string();
+        let contents = std::fs::read_to_string(&path).expect("exported file");
+        std::fs::remove_file(&path).ok();
+        (path, contents)
+    };
+    // 画面と同じ列と並び順（CPU の多い順）
+    let (path, csv) = saved("csv");
+    assert!(path.starts_with("rust-top-") && path.ends_with(".csv"));
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3, "{csv}");
+    assert!(lines[0].starts_with("pid,user,name"), "{csv}");
+    assert!(lines[1].starts_with("700,") && lines[2].starts_with("701,"));
+
+    let (_, json) = saved("json");
+    assert!(json.starts_with("[\n  {\"pid\": 700"), "{json}");
+}
+
+#[test]
+fn info_shows_the_system() {
+    let snapshot = snapshot();
+    let buffer = render(&app(&snapshot), &snapshot, 160, 50);
+    assert!(contains(&buffer, "Mock CPU @ 2.40GHz"));
+    assert!(contains(&buffer, "x86_64"));
+}
+
+#[test]
+fn too_small_terminal_shows_a_notice() {
+    let snapshot = snapshot();
+    let buffer = render(&app(&snapshot), &snapshot, 30, 8);
+    assert!(contains(&buffer, "Terminal too small"));
+}
+
+#[test]
+fn short_terminal_drops_optional_panels_first() {
+    let snapshot = snapshot();
+    let buffer = render(&app(&snapshot), &snapshot, 100, 16);
+    assert!(title_row(&buffer, "CPU (Irix)").is_some());
+    assert!(title_row(&buffer, "Processes").is_some());
+    assert!(title_row(&buffer, "Info").is_none());
+}
+
+#[test]
+fn hidden_panels_leave_room_for_the_table() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    press(&mut app, &snapshot, KeyCode::Char('2'), KeyModifiers::ALT);
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(title_row(&buffer, "Memory").is_none());
+    assert!(title_row(&buffer, "Processes").is_some());
+}
+
+#[test]
+fn show_all_draws_only_the_process_table() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    app.show_all = true;
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(title_row(&buffer, "CPU (Irix)").is_none());
+    assert!(title_row(&buffer, "Info").is_none());
+    assert!(contains(&buffer, "[all, A to return]"));
+}
+
+#[test]
+fn header_uses_the_theme_color() {
+    let snapshot = snapshot();
+    for theme in theme::THEMES {
+        let mut app = app(&snapshot);
+        app.theme = theme;
+        let buffer = render(&app, &snapshot, 160, 50);
+        let row = title_row(&buffer, "Processes").expect("process table") + 1;
+        let pid = lines(&buffer)[row].find("PID").expect("PID header");
+        let x = lines(&buffer)[row][..pid].chars().count() as u16;
+        assert_eq!(buffer[(x, row as u16)].fg, theme.header, "{}", theme.name);
+    }
+}
+
+#[test]
+fn sixteen_color_terminals_get_the_nearest_basic_colors() {
+    let snapshot = snapshot();
+    let config = Config {
+        theme: theme::DEUTERANOPIA,
+        colors: Some(ColorDepth::Basic),
+        ascii: Some(false),
+        ..Config::default()
+    };
+    let mut app = App::new(&Args::default(), &config);
+    app.locale = Locale::default();
+    app.on_snapshot(&snapshot, &ProcessChanges::default());
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(
+        buffer
+            .content
+            .iter()
+            .all(|cell| !matches!(cell.fg, Color::Indexed(_) | Color::Rgb(..)))
+    );
+    let row = title_row(&buffer, "Processes").expect("process table") + 1;
+    let pid = lines(&buffer)[row].find("PID").expect("PID header");
+    let x = lines(&buffer)[row][..pid].chars().count() as u16;
+    // 256 色の 75 番（#5fafff）に一番近いのは明るい青
+    assert_eq!(buffer[(x, row as u16)].fg, Color::LightBlue);
+}
+
+#[test]
+fn ascii_mode_draws_plain_borders_and_sixteen_colors() {
+    let snapshot = snapshot();
+    let args = Args {
+        ascii: true,
+        ..Args::default()
+    };
+    let mut app = App::new(&args, &Config::default());
+    app.locale = Locale::default();
+    app.on_snapshot(&snapshot, &ProcessChanges::default());
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(buffer.content.iter().all(|cell| cell.symbol().is_ascii()));
+    assert!(contains(&buffer, "+Processes"));
+    assert!(
+        buffer
+            .content
+            .iter()
+            .all(|cell| !matches!(cell.fg, Color::Indexed(_) | Color::Rgb(..)))
+    );
+}
+
+#[test]
+fn symbol_themes_mark_the_name_column() {
+    let snapshot = snapshot();
+    let column = |theme| {
+        let mut app = app(&snapshot);
+        app.theme = theme;
+        let buffer = render(&app, &snapshot, 160, 50);
+        let row = title_row(&buffer, "Processes").expect("process table") + 2;
+        lines(&buffer)[row].find("cargo").expect("cargo row")
+    };
+    // 記号を使う配色では、何も無い行にも印の幅の空白を入れて名前の位置をそろえる
+    assert_eq!(column(theme::DEUTERANOPIA), column(theme::DEFAULT) + 2);
+}
+
+#[test]
+fn japanese_titles() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    app.locale.language = Language::Japanese;
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(title_row(&buffer, "メモリ").is_some());
+    assert!(title_row(&buffer, "使用量の上位").is_some());
+}
+
+#[test]
+fn users_view_drills_down_into_a_user() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    press(&mut app, &snapshot, KeyCode::Char('W'), KeyModifiers::NONE);
+    let buffer = render(&app, &snapshot, 160, 50);
+    let row = title_row(&buffer, "Users").expect("users view") + 2;
+    // cargo と python3 を動かしている alice がいちばん CPU を使う
+    assert!(
+        lines(&buffer)[row].contains("alice"),
+        "{}",
+        lines(&buffer)[row]
+    );
+    press(&mut app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+    assert_eq!(app.user_filter.as_deref(), Some("alice"));
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(title_row(&buffer, "Users").is_none());
+    // 使用量の上位のパネルは絞り込まないので、プロセス表の中だけを見る
+    let table = title_row(&buffer, "Processes").expect("process table");
+    let info = title_row(&buffer, "Info").expect("info");
+    let rows = &lines(&buffer)[table..info];
+    assert!(rows.iter().any(|line| line.contains("cargo")));
+    assert!(!rows.iter().any(|line| line.contains("postgres")));
+}
+
+#[test]
+fn numa_panel_lists_each_node() {
+    let mut snapshot = snapshot();
+    let node = |id: usize, cpus: &str| NumaNode {
+        id,
+        cpus: cpus.to_string(),
+        total: 8 * 1024 * 1024 * 1024,
+        free: 2 * 1024 * 1024 * 1024,
+    };
+    snapshot.numa = Some(NumaSnapshot {
+        nodes: vec![node(0, "0-1"), node(1, "2-3")],
+    });
+    let buffer = render(&app(&snapshot), &snapshot, 160, 50);
+    let row = title_row(&buffer, "NUMA nodes").expect("numa panel") + 1;
+    let lines = lines(&buffer);
+    assert!(lines[row].contains("CPUs"));
+    assert!(lines[row + 2].contains("2-3"), "{}", lines[row + 2]);
+    assert!(lines[row + 2].contains("6144 MB"), "{}", lines[row + 2]);
+    assert!(lines[row + 2].contains("75.0%"), "{}", lines[row + 2]);
+}
+
+#[test]
+fn locate_highlights_and_jumps_without_filtering() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let visible = app.visible_processes(&snapshot).len();
+    press(&mut app, &snapshot, KeyCode::Char('l'), KeyModifiers::NONE);
+    for c in "postgres".chars() {
+        press(&mut app, &snapshot, KeyCode::Char(c), KeyModifiers::NONE);
+    }
+    press(&mut app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+    assert_eq!(app.visible_processes(&snapshot).len(), visible);
+    let name = |app: &App| {
+        let pid = app.selected.expect("selection");
+        let process = snapshot.processes.iter().find(|p| p.pid == pid);
+        process.expect("selected process").name.to_string()
+    };
+    assert_eq!(name(&app), "postgres");
+    // 一致する 3 行を n で一巡して、< で戻る
+    let first = app.selected;
+    press(&mut app, &snapshot, KeyCode::Char('n'), KeyModifiers::NONE);
+    assert_ne!(app.selected, first);
+    assert_eq!(name(&app), "postgres");
+    press(&mut app, &snapshot, KeyCode::Char('n'), KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Char('n'), KeyModifiers::NONE);
+    assert_eq!(app.selected, first);
+    press(&mut app, &snapshot, KeyCode::Char('<'), KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Char('n'), KeyModifiers::NONE);
+    assert_eq!(app.selected, first);
+    // 探している間も N は renice
+    press(&mut app, &snapshot, KeyCode::Char('N'), KeyModifiers::NONE);
+    assert!(matches!(
+        app.prompt.as_ref().map(|p| &p.kind),
+        Some(PromptKind::Renice(_))
+    ));
+    press(&mut app, &snapshot, KeyCode::Esc, KeyModifiers::NONE);
+    assert!(app.locate.is_some());
+
+    let buffer = render(&app, &snapshot, 160, 50);
+    let table = title_row(&buffer, "Processes").expect("process table");
+    let lines = lines(&buffer);
+    let row = (table..lines.len())
+        .find(|&row| lines[row].contains("postgres"))
+        .expect("postgres row");
+    // User 列も postgres なので、Name 列の位置から探す
+    let column = lines[table + 1].find("Name").expect("Name header");
+    let x = (column + lines[row][column..].find("postgres").expect("name")) as u16;
+    assert_eq!(buffer[(x, row as u16)].fg, app.theme.caution);
+    assert!(contains(&buffer, "locate: postgres (n / <)"));
+}
+
+#[test]
+fn exited_processes_linger_struck_through() {
+    let mut snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let at = snapshot.processes.iter().position(|p| &*p.name == "cargo");
+    let cargo = snapshot.processes.remove(at.expect("cargo"));
+    snapshot.process_generation += 1;
+    let changes = ProcessChanges {
+        added: Vec::new(),
+        exited: vec![cargo],
+    };
+    app.on_snapshot(&snapshot, &changes);
+    let buffer = render(&app, &snapshot, 160, 50);
+    let table = title_row(&buffer, "Processes").expect("process table");
+    let row = table + 2;
+    // 最後の値のまま元の位置に残り、灰色の取り消し線で描く
+    let line = &lines(&buffer)[row];
+    let x = line.find("cargo").expect("cargo row") as u16;
+    assert!(
+        buffer[(x, row as u16)]
+            .modifier
+            .contains(Modifier::CROSSED_OUT)
+    );
+    assert_eq!(buffer[(x, row as u16)].fg, app.theme.exited);
+
+    for _ in 0..2 {
+        snapshot.process_generation += 1;
+        app.on_snapshot(&snapshot, &ProcessChanges::default());
+    }
+    let buffer = render(&app, &snapshot, 160, 50);
+    let info = title_row(&buffer, "Info").expect("info");
+    assert!(
+        !lines(&buffer)[table..info]
+            .iter()
+            .any(|line| line.contains("cargo"))
+    );
+}
+
+#[test]
+fn event_log_records_started_and_exited_processes_and_errors() {
+    let mut snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let at = snapshot.processes.iter().position(|p| &*p.name == "leaky");
+    let leaky = snapshot.processes.remove(at.expect("leaky"));
+    let cargo = snapshot.processes.iter().find(|p| &*p.name == "cargo");
+    snapshot.process_generation += 1;
+    let changes = ProcessChanges {
+        added: vec![cargo.expect("cargo").pid],
+        exited: vec![leaky],
+    };
+    app.on_snapshot(&snapshot, &changes);
+    app.error("Failed to save history: disk full".to_string());
+    press(&mut app, &snapshot, KeyCode::Char('L'), KeyModifiers::NONE);
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(contains(&buffer, "Events (3, s to save"));
+    assert!(contains(
+        &buffer,
+        "start  cargo (900) cargo build --release"
+    ));
+    assert!(contains(&buffer, "exit   leaky (950)"));
+    assert!(contains(
+        &buffer,
+        "error  Failed to save history: disk full"
+    ));
+    let text = app.events.text(&app.locale);
+    assert_eq!(text.lines().count(), 3);
+    assert!(
+        text.lines()
+            .last()
+            .is_some_and(|line| line.ends_with("error  Failed to save history: disk full"))
+    );
+}
+
+#[test]
+fn affinity_dialog_toggles_cores_and_applies() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let cargo = snapshot.processes.iter().find(|p| &*p.name == "cargo");
+    let cargo = cargo.expect("cargo");
+    app.affinity = Some(AffinityDialog {
+        target: ProcessRef {
+            pid: cargo.pid,
+            start_time: cargo.start_time,
+        },
+        cpus: vec![true; 4],
+        cursor: 0,
+    });
+    // a で全部外すと、何も選んでいないので設定しない
+    press(&mut app, &snapshot, KeyCode::Char('a'), KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+    assert!(app.affinity.is_some());
+    press(&mut app, &snapshot, KeyCode::Char(' '), KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Right, KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Char(' '), KeyModifiers::NONE);
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(title_row(&buffer, &format!("CPU affinity of {}", cargo.pid)).is_some());
+    assert!(contains(&buffer, "Allowed: 0-1"));
+    assert!(contains(&buffer, "[x] 0"));
+    assert!(contains(&buffer, "[ ] 3"));
+
+    let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+    let effect = app.handle_key(enter, &snapshot);
+    assert!(matches!(
+        effect,
+        Effect::Send(Command::SetAffinity { ref cpus, .. }) if cpus == &[0, 1]
+    ));
+    assert!(app.affinity.is_none());
+}
+
+#[test]
+fn io_priority_prompt_sends_the_parsed_class() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let submit = |app: &mut App, text: &str| {
+        press(app, &snapshot, KeyCode::Char('o'), KeyModifiers::NONE);
+        let prompt = app.prompt.as_mut().expect("I/O priority prompt");
+        prompt.text = text.to_string();
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE), &snapshot)
+    };
+    assert!(matches!(submit(&mut app, "rt 9"), Effect::None));
+    let effect = submit(&mut app, "be 7");
+    let Effect::Send(Command::SetIoPriority { priority, .. }) = effect else {
+        panic!("expected an I/O priority command");
+    };
+    assert_eq!(priority.to_string(), "best-effort 7");
+
+    press(&mut app, &snapshot, KeyCode::Char('o'), KeyModifiers::NONE);
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(contains(&buffer, "I/O priority of 900"));
+}
+
+#[test]
+fn fault_columns_show_rates_and_swap() {
+    // 名前に空白と括弧が入っていても、最後の ")" から数える
+    let stat = "4242 (web (worker) 1) S 1 4242 4242 0 -1 4194560 1500 0 37 0 12 3 0 0 20 0 1";
+    assert_eq!(faults::parse_stat(stat), Some((1500, 37)));
+
+    let mut snapshot = snapshot();
+    let mut app = app(&snapshot);
+    press(&mut app, &snapshot, KeyCode::Char('m'), KeyModifiers::NONE);
+    assert!(app.page_faults);
+    let busy = snapshot
+        .processes
+        .iter_mut()
+        .find(|p| p.pid.as_u32() == 900)
+        .expect("pid 900");
+    busy.minor_faults = Some(1200);
+    busy.major_faults = Some(45);
+    busy.swap = Some(64 * 1024 * 1024);
+    app.sort = SortKey::MajorFaults;
+    let buffer = render(&app, &snapshot, 200, 50);
+    let header = title_row(&buffer, "Processes").expect("process table") + 1;
+    let lines = lines(&buffer);
+    for label in ["MinFlt", "MajFlt", "Swap"] {
+        assert!(lines[header].contains(label), "{}", lines[header]);
+    }
+    // 内訳（M）を出していなければ VIRT と SHR は出さない
+    assert!(!lines[header].contains("VIRT"));
+    let first = &lines[header + 1];
+    assert!(first.contains(" 900 "), "{first}");
+    assert!(
+        first.contains(" 1200 ") && first.contains(" 45 "),
+        "{first}"
+    );
+    assert!(first.contains("64.0 MB"), "{first}");
+}
+
+#[test]
+fn fd_column_and_open_files_view() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    press(&mut app, &snapshot, KeyCode::Char('D'), KeyModifiers::NONE);
+    let buffer = render(&app, &snapshot, 160, 50);
+    let header = title_row(&buffer, "Processes").expect("process table") + 1;
+    assert!(lines(&buffer)[header].contains("FDs"));
+
+    let file = |fd, kind, name: &str| OpenFile {
+        fd,
+        kind,
+        name: name.to_string(),
+    };
+    app.files = Some(FilesView {
+        pid: sysinfo::Pid::from_u32(900),
+        files: vec![
+            file(0, "CHR", "/dev/null"),
+            file(3, "TCP", "127.0.0.1:5432 (LISTEN)"),
+        ],
+        selected: 0,
+    });
+    press(&mut app, &snapshot, KeyCode::Down, KeyModifiers::NONE);
+    let buffer = render(&app, &snapshot, 160, 50);
+    let row = title_row(&buffer, "Open files of 900 (2").expect("open files") + 3;
+    let line = &lines(&buffer)[row];
+    assert!(
+        line.contains("TCP") && line.contains("127.0.0.1:5432"),
+        "{line}"
+    );
+    let x = line[..line.find("TCP").expect("TCP")].chars().count() as u16;
+    assert!(
+        buffer[(x, row as u16)]
+            .modifier
+            .contains(Modifier::REVERSED)
+    );
+    press(&mut app, &snapshot, KeyCode::Esc, KeyModifiers::NONE);
+    assert!(app.files.is_none());
+}
+
+#[cfg(feature = "net")]
+#[test]
+fn listening_ports_panel_jumps_to_the_owner() {
+    use crate::net::{Connection, Protocol};
+
+    let mut snapshot = snapshot();
+    let owner = snapshot.processes[2].clone();
+    let socket = |protocol, local: &str, remote: &str, state| Connection {
+        protocol,
+        local: local.parse().expect("local address"),
+        remote: remote.parse().expect("remote address"),
+        state,
+        pid: Some(owner.pid),
+    };
+    snapshot.connections = vec![
+        socket(Protocol::Tcp, "0.0.0.0:8080", "0.0.0.0:0", "LISTEN"),
+        // IPv6 でも同じポートを開いているものは 1 つにまとめる
+        socket(Protocol::Tcp, "[::]:8080", "[::]:0", "LISTEN"),
+        socket(Protocol::Udp, "127.0.0.1:53", "0.0.0.0:0", "UNCONN"),
+        socket(Protocol::Tcp, "10.0.0.2:40000", "10.0.0.9:443", "ESTAB"),
+    ];
+    let mut app = app(&snapshot);
+    let buffer = render(&app, &snapshot, 160, 60);
+    let row =
+        title_row(&buffer, "Listening ports (2, P to go to a port)").expect("ports panel") + 1;
+    let line = &lines(&buffer)[row];
+    assert!(line.contains("UDP 127.0.0.1:53"), "{line}");
+    assert!(
+        line.contains(&format!("TCP *:8080 {}", owner.name)),
+        "{line}"
+    );
+    assert!(!line.contains("40000"), "{line}");
+    press(&mut app, &snapshot, KeyCode::Char('P'), KeyModifiers::NONE);
+    for c in "8080".chars() {
+        press(&mut app, &snapshot, KeyCode::Char(c), KeyModifiers::NONE);
+    }
+    press(&mut app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+    assert_eq!(app.selected, Some(owner.pid));
+    press(&mut app, &snapshot, KeyCode::Char('P'), KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Char('9'), KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+    assert!(contains(
+        &render(&app, &snapshot, 160, 60),
+        "Nothing is listening on port 9"
+    ));
+
+    // :port は待ち受けていないポートでも、使っている接続の持ち主へ移る
+    app.selected = Some(snapshot.processes[0].pid);
+    press(&mut app, &snapshot, KeyCode::Char(':'), KeyModifiers::NONE);
+    app.prompt.as_mut().expect("command prompt").text = "port 40000".to_string();
+    press(&mut app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+    assert_eq!(app.selected, Some(owner.pid));
+}
+
+#[cfg(feature = "net")]
+#[test]
+fn tcp_states_show_counts_and_rates() {
+    use crate::net::{Connection, Protocol, TcpStateCounter};
+
+    let socket = |state| Connection {
+        protocol: Protocol::Tcp,
+        local: "10.0.0.2:8080".parse().expect("local address"),
+        remote: "10.0.0.9:50000".parse().expect("remote address"),
+        state,
+        pid: None,
+    };
+    let mut counter = TcpStateCounter::default();
+    let start = Instant::now();
+    let first = counter
+        .count(&[socket("ESTAB"), socket("CLOSE-WAIT")], start)
+        .expect("first count");
+    assert!(first.rates.is_none());
+    let sockets = [
+        socket("ESTAB"),
+        socket("CLOSE-WAIT"),
+        socket("CLOSE-WAIT"),
+        socket("CLOSE-WAIT"),
+        socket("LISTEN"),
+    ];
+    let mut snapshot = snapshot();
+    snapshot.tcp_states = counter.count(&sockets, start + Duration::from_secs(2));
+    let app = app(&snapshot);
+    let buffer = render(&app, &snapshot, 160, 60);
+    let row = title_row(&buffer, "TCP").expect("tcp panel") + 1;
+    let line = &lines(&buffer)[row];
+    assert!(line.contains("ESTAB 1 (0.0/s)"), "{line}");
+    assert!(line.contains("CLOSE-WAIT 3 (+1.0/s)"), "{line}");
+    assert!(line.contains("SYN-RECV 0 (0.0/s)"), "{line}");
+    let x = line[..line.find("CLOSE-WAIT").expect("CLOSE-WAIT")]
+        .chars()
+        .count() as u16;
+    assert_eq!(buffer[(x, row as u16)].fg, app.theme.warning);
+}
+
+#[cfg(feature = "net")]
+#[test]
+fn connections_resolve_remote_host_names_in_the_background() {
+    use crate::net::{Connection, Protocol};
+
+    let mut snapshot = snapshot();
+    snapshot.connections = vec![Connection {
+        protocol: Protocol::Tcp,
+        local: "127.0.0.1:40000".parse().expect("local address"),
+        remote: "127.0.0.1:5432".parse().expect("remote address"),
+        state: "ESTAB",
+        pid: None,
+    }];
+    let mut app = app(&snapshot);
+    press(&mut app, &snapshot, KeyCode::Char('c'), KeyModifiers::NONE);
+    assert!(contains(
+        &render(&app, &snapshot, 160, 50),
+        "127.0.0.1:5432"
+    ));
+    press(&mut app, &snapshot, KeyCode::Char('r'), KeyModifiers::NONE);
+    // 最初の描画では数字のまま。引き終わるのを待つ
+    let resolver = app.resolver.as_ref().expect("resolver");
+    let ip = "127.0.0.1".parse().expect("ip");
+    let mut name = resolver.lookup(ip);
+    for _ in 0..200 {
+        if name.is_some() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+        name = resolver.lookup(ip);
+    }
+    let name = name.expect("127.0.0.1 has a name");
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(contains(&buffer, &format!("{name}:5432")));
+    assert!(contains(&buffer, "r for addresses"));
+    press(&mut app, &snapshot, KeyCode::Char('r'), KeyModifiers::NONE);
+    assert!(contains(
+        &render(&app, &snapshot, 160, 50),
+        "127.0.0.1:5432"
+    ));
+}
+
+#[cfg(feature = "net")]
+#[test]
+fn traffic_columns_add_up_socket_bytes_per_process() {
+    use std::collections::HashMap;
+
+    use crate::net::{self, TrafficCounter};
+
+    let ss = |received: u64, sent: u64| {
+        format!(
+            "ESTAB 0 0 10.0.0.2:443 10.0.0.9:50000 ino:100 sk:1 <->\n\
+             \t cubic wscale:7,7 rto:204 bytes_sent:{sent} bytes_acked:{sent} bytes_received:{received}\n\
+             TIME-WAIT 0 0 10.0.0.2:443 10.0.0.9:50001 ino:0 sk:2\n\
+             \t cubic bytes_sent:999 bytes_received:999\n"
+        )
+    };
+    let first = net::parse_ss(&ss(1000, 500));
+    assert_eq!(first, HashMap::from([(100, (1000, 500))]));
+    let mut snapshot = snapshot();
+    let pid = snapshot.processes[0].pid;
+    let owners = HashMap::from([(100, pid)]);
+    let mut counter = TrafficCounter::default();
+    let start = Instant::now();
+    assert!(counter.update(first, &owners, start).is_none());
+    let rates = counter
+        .update(
+            net::parse_ss(&ss(1000 + 4096, 500 + 2 * 1024 * 1024)),
+            &owners,
+            start + Duration::from_secs(2),
+        )
+        .expect("rates");
+    assert_eq!(rates[&pid], (2048, 1024 * 1024));
+    for p in &mut snapshot.processes {
+        let (received, sent) = rates.get(&p.pid).copied().unwrap_or_default();
+        p.net_received = Some(received);
+        p.net_sent = Some(sent);
+    }
+    let mut app = app(&snapshot);
+    assert!(!contains(&render(&app, &snapshot, 200, 50), " RX"));
+    press(&mut app, &snapshot, KeyCode::Char('b'), KeyModifiers::NONE);
+    let buffer = render(&app, &snapshot, 200, 50);
+    let line = lines(&buffer)
+        .into_iter()
+        .find(|line| {
+            line.trim_start_matches(['│', ' '])
+                .starts_with(&format!("{pid} "))
+        })
+        .expect("process row");
+    assert!(line.contains("2 KB/s"), "{line}");
+    assert!(line.contains("1.0 MB/s"), "{line}");
+}
+
+#[cfg(feature = "environ")]
+#[test]
+fn environment_view_filters_by_name_or_value() {
+    use crate::source::SystemSource;
+
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let effect = app.handle_key(
+        KeyEvent::new(KeyCode::Char('v'), KeyModifiers::NONE),
+        &snapshot,
+    );
+    let Effect::Environ(target) = effect else {
+        panic!("v should ask for the environment");
+    };
+    let vars = MockSource::default().environ(target);
+    app.handle_environ(target, vars);
+    for c in "BIN".chars() {
+        press(&mut app, &snapshot, KeyCode::Char(c), KeyModifiers::NONE);
+    }
+    let buffer = render(&app, &snapshot, 160, 50);
+    let title = format!("Environment of {} (1/4, filter: BIN", target.pid);
+    let row = title_row(&buffer, &title).expect("environment") + 2;
+    let line = &lines(&buffer)[row];
+    assert!(line.contains("PATH") && line.contains("/usr/bin"), "{line}");
+    press(&mut app, &snapshot, KeyCode::Backspace, KeyModifiers::NONE);
+    assert_eq!(
+        app.environ.as_ref().map(|view| view.query.as_str()),
+        Some("BI")
+    );
+    press(&mut app, &snapshot, KeyCode::Esc, KeyModifiers::NONE);
+    assert!(app.environ.is_none());
+}
+
+#[test]
+fn cpu_time_panel_stacks_the_breakdown_per_core() {
+    use crate::cpustat::CpuBreakdown;
+
+    let busy = CpuBreakdown {
+        user: 20.0,
+        system: 10.0,
+        iowait: 40.0,
+        steal: 5.0,
+        idle: 25.0,
+        ..CpuBreakdown::default()
+    };
+    let idle = CpuBreakdown {
+        idle: 100.0,
+        ..CpuBreakdown::default()
+    };
+    let mut snapshot = snapshot();
+    snapshot.cpu.breakdown = Some(busy);
+    snapshot.cpu.core_breakdown = vec![(0, busy), (1, idle)];
+    let app = app(&snapshot);
+    let buffer = render(&app, &snapshot, 120, 60);
+    let lines = lines(&buffer);
+    let row = lines
+        .iter()
+        .position(|line| line.contains("CPU time"))
+        .expect("CPU time panel");
+    // 全体の棒は幅を割合で分け、凡例は使われていない種類を省く
+    let bar = lines[row + 1].trim_matches(['│', ' ']);
+    assert!(
+        bar.starts_with('█') && bar.contains("█▓▓") && bar.contains("▓▓█"),
+        "{bar}"
+    );
+    assert!(
+        lines[row + 2]
+            .contains("user 20.0%, ▓ system 10.0%, █ iowait 40.0%, █ steal 5.0%, idle 25.0%")
+    );
+    assert!(!lines[row + 2].contains("softirq"));
+    assert!(lines[row + 3].contains("  0 "), "{}", lines[row + 3]);
+    assert!(lines[row + 3].contains("75.0%"), "{}", lines[row + 3]);
+    assert!(lines[row + 3].contains("  1 "), "{}", lines[row + 3]);
+    assert!(lines[row + 3].contains("0.0%"), "{}", lines[row + 3]);
+}
+
+#[test]
+fn cpu_panel_shows_usage_per_core_type_on_hybrid_cpus() {
+    use crate::coretype::{self, CoreCluster};
+
+    let clusters = [
+        CoreCluster {
+            label: "P-cores".to_string(),
+            cpus: coretype::parse_cpu_list("0-1\n"),
+        },
+        CoreCluster {
+            label: "E-cores".to_string(),
+            cpus: coretype::parse_cpu_list("2,3"),
+        },
+    ];
+    let mut snapshot = snapshot();
+    snapshot.cpu.clusters = coretype::usage(&clusters, &[90.0, 70.0, 10.0, 0.0]);
+    let app = app(&snapshot);
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(contains(&buffer, "P-cores (2) 80.0%, E-cores (2) 5.0%"));
+}
+
+#[test]
+fn soc_panel_shows_the_board_model_and_pi_throttling() {
+    use crate::rpi::{self, SocSnapshot};
+
+    let mut snapshot = snapshot();
+    // Pi 以外のボードは温度だけ
+    snapshot.soc = Some(SocSnapshot {
+        model: "Radxa ROCK 5B".to_string(),
+        temperature: Some(48.5),
+        core_volts: None,
+        throttled: None,
+    });
+    let app = app(&snapshot);
+    let buffer = render(&app, &snapshot, 120, 50);
+    assert!(contains(&buffer, "Radxa ROCK 5B"));
+    assert!(contains(&buffer, "Temp: 48.5°C"));
+    assert!(!contains(&buffer, "Throttling"));
+
+    snapshot.soc = Some(SocSnapshot {
+        model: "Raspberry Pi 4 Model B Rev 1.4".to_string(),
+        temperature: Some(81.0),
+        core_volts: Some(0.85),
+        throttled: Some(rpi::UNDER_VOLTAGE | rpi::THROTTLED << rpi::OCCURRED_SHIFT),
+    });
+    let buffer = render(&app, &snapshot, 120, 50);
+    assert!(contains(&buffer, "Raspberry Pi 4 Model B Rev 1.4"));
+    assert!(contains(&buffer, "Core: 0.85 V"));
+    assert!(contains(&buffer, "Throttling: under-voltage"));
+    assert!(contains(&buffer, "since boot: throttled"));
+    assert!(contains(&buffer, "Under-voltage: check the power supply"));
+}
+
+#[test]
+fn sensors_panel_shows_fans_and_power_with_the_package_history() {
+    use crate::sensors::{self, Reading, SensorsSnapshot};
+
+    let mut snapshot = snapshot();
+    snapshot.sensors = Some(SensorsSnapshot {
+        fans: vec![Reading {
+            name: "cpu_fan".to_string(),
+            value: 1450.0,
+        }],
+        power: vec![Reading {
+            name: "package-0".to_string(),
+            value: 23.46,
+        }],
+        package_watts: Some(23.46),
+    });
+    let mut app = app(&snapshot);
+    app.usage_history.power.extend([80, 150, 234]);
+    let buffer = render(&app, &snapshot, 120, 50);
+    let row = title_row(&buffer, "Sensors").expect("sensors panel");
+    let lines = lines(&buffer);
+    assert!(lines[row + 2].contains("Fan") && lines[row + 2].contains("1450 RPM"));
+    assert!(lines[row + 3].contains("package-0") && lines[row + 3].contains("23.5 W"));
+    assert!(
+        lines[row + 4].contains("Package 23.5 W █"),
+        "{}",
+        lines[row + 4]
+    );
+
+    // RAPL のカウンターが一周しても負にならない
+    assert_eq!(
+        sensors::watts(1_000_000, 3_000_000, u64::MAX, 2.0),
+        Some(1.0)
+    );
+    assert_eq!(
+        sensors::watts(9_000_000, 1_000_000, 10_000_000, 1.0),
+        Some(2.0)
+    );
+}
+
+#[cfg(feature = "smart")]
+#[test]
+fn smart_panel_puts_failing_disks_first() {
+    use crate::smart::{self, SmartSnapshot};
+
+    let ata = r#"{"model_name": "WDC WD40EFRX", "smart_status": {"passed": true},
+        "temperature": {"current": 38},
+        "ata_smart_attributes": {"table": [
+            {"id": 1, "name": "Raw_Read_Error_Rate", "raw": {"value": 0}},
+            {"id": 5, "name": "Reallocated_Sector_Ct", "raw": {"value": 16}}]}}"#;
+    let nvme = r#"{"model_name": "Samsung SSD 980", "smart_status": {"passed": true},
+        "temperature": {"current": 41}}"#;
+    // 権限が無くて開けなかったディスクは出さない
+    let denied = r#"{"smartctl": {"exit_status": 2}}"#;
+    assert!(smart::parse("/dev/sdb", denied).is_none());
+    let mut snapshot = snapshot();
+    snapshot.smart = Some(SmartSnapshot {
+        disks: vec![
+            smart::parse("/dev/nvme0", nvme).expect("nvme"),
+            smart::parse("/dev/sda", ata).expect("ata"),
+        ],
+    });
+    let app = app(&snapshot);
+    let buffer = render(&app, &snapshot, 160, 60);
+    let row = title_row(&buffer, "Disks (SMART)").expect("smart panel") + 2;
+    let lines = lines(&buffer);
+    assert!(lines[row].contains("/dev/sda"), "{}", lines[row]);
+    assert!(lines[row].contains("38 °C"), "{}", lines[row]);
+    assert!(lines[row].contains("16"), "{}", lines[row]);
+    let x = lines[row].find("/dev/sda").expect("sda") as u16;
+    assert_eq!(buffer[(x, row as u16)].fg, app.theme.warning);
+    assert!(lines[row + 1].contains("/dev/nvme0"), "{}", lines[row + 1]);
+    assert!(lines[row + 1].contains("PASSED"), "{}", lines[row + 1]);
+}
+
+#[cfg(feature = "journal")]
+#[test]
+fn journal_view_tails_the_log_lines_of_the_process() {
+    use crate::journal::JournalView;
+
+    let path = std::env::temp_dir().join(format!("rust-top-journal-{}.log", std::process::id()));
+    std::fs::write(
+        &path,
+        "Jan  1 00:00:00 host worker[700]: started\n\
+         Jan  1 00:00:01 host cron[42]: ignored\n\
+         Jan  1 00:00:02 host worker[700]: queue is full\n",
+    )
+    .expect("write log");
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let view = JournalView::open(sysinfo::Pid::from_u32(700), Some(path.clone()));
+    // 読むのは別スレッドなので、最初の結果を待つ
+    for _ in 0..100 {
+        if view.lines().is_some() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    app.journal = Some(view);
+    let buffer = render(&app, &snapshot, 160, 50);
+    std::fs::remove_file(&path).ok();
+    assert!(contains(
+        &buffer,
+        "Log of PID 700 (2, following, Esc to close)"
+    ));
+    assert!(contains(&buffer, "worker[700]: queue is full"));
+    assert!(!contains(&buffer, "cron[42]"));
+}
+
+#[cfg(feature = "profile")]
+#[test]
+fn profile_view_lists_the_hottest_symbols_from_perf() {
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::config::ProfileConfig;
+    use crate::profile;
+
+    // record は -o のファイルを作るだけ、report は --field-separator の形で書く。PID 1 は断る
+    let perf = std::env::temp_dir().join(format!("rust-top-perf-{}", std::process::id()));
+    std::fs::write(
+        &perf,
+        "#!/bin/sh\n\
+         if [ \"$1\" = record ]; then\n\
+         [ \"$5\" = 1 ] && printf 'Error:\\nAccess to performance monitoring is limited.\\n' >&2 && exit 255\n\
+         while [ \"$1\" != -o ]; do shift; done; : > \"$2\"; exit 0\n\
+         fi\n\
+         printf '# Samples: 16\\n# Overhead\\tShared Object\\tSymbol\\n'\n\
+         printf '    62.50%%\\tworker\\t[.] compute_hash\\n'\n\
+         printf '    25.00%%\\t[kernel.kallsyms]\\t[k] copy_user_generic\\n'\n\
+         printf '    12.50%%\\tlibstdc++.so.6\\t[.] operator new(unsigned long)\\n'\n",
+    )
+    .expect("write perf");
+    std::fs::set_permissions(&perf, std::fs::Permissions::from_mode(0o755)).expect("chmod perf");
+    let config = ProfileConfig {
+        perf: perf.display().to_string(),
+        duration: Duration::from_millis(100),
+    };
+    let wait = |view: &profile::ProfileView| {
+        for _ in 0..200 {
+            if view.result().is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    };
+
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let view = profile::ProfileView::start(sysinfo::Pid::from_u32(700), "worker".into(), &config);
+    wait(&view);
+    let symbols = view.result().expect("finished").expect("symbols");
+    assert_eq!(symbols.len(), 3);
+    assert_eq!(symbols[2].name, "operator new(unsigned long)");
+    app.cpu_profile = Some(view);
+    let screen = lines(&render(&app, &snapshot, 160, 50));
+    let has = |text: &str| screen.iter().any(|line| line.contains(text));
+    assert!(
+        has("Profile of worker (700) (3 symbols over 0s, Esc to close)"),
+        "{screen:#?}"
+    );
+    assert!(has("62.5%"));
+    assert!(has("compute_hash"));
+    assert!(has("[kernel.kallsyms]"));
+    press(&mut app, &snapshot, KeyCode::Esc, KeyModifiers::NONE);
+    assert!(app.cpu_profile.is_none());
+
+    // 権限が無ければ perf の理由をそのまま出す
+    let view = profile::ProfileView::start(sysinfo::Pid::from_u32(1), "init".into(), &config);
+    wait(&view);
+    std::fs::remove_file(&perf).ok();
+    let error = view.result().expect("finished").expect_err("denied");
+    assert!(
+        error.ends_with(": Access to performance monitoring is limited."),
+        "{error}"
+    );
+}
+
+#[test]
+fn yank_copies_the_selected_process() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let yank = |app: &mut App, code, text: &str| {
+        if code == KeyCode::Char(':') {
+            press(app, &snapshot, code, KeyModifiers::NONE);
+            app.prompt.as_mut().expect("command prompt").text = text.to_string();
+            let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+            return app.handle_key(enter, &snapshot);
+        }
+        app.handle_key(KeyEvent::new(code, KeyModifiers::NONE), &snapshot)
+    };
+    let pid = app.selected.expect("selected process");
+    let process = snapshot
+        .processes
+        .iter()
+        .find(|p| p.pid == pid)
+        .expect("process");
+    let Effect::Copy(text) = yank(&mut app, KeyCode::Char('y'), "") else {
+        panic!("y should copy the PID");
+    };
+    assert_eq!(text, pid.to_string());
+    let Effect::Copy(text) = yank(&mut app, KeyCode::Char('Y'), "") else {
+        panic!("Y should copy the command line");
+    };
+    assert_eq!(text, &*process.cmd);
+    let Effect::Copy(text) = yank(&mut app, KeyCode::Char(':'), "yank sum") else {
+        panic!(":yank summary should copy a summary");
+    };
+    assert!(text.starts_with(&format!("{pid} {} {}", process.user, process.name)));
+    assert!(text.ends_with(&*process.cmd), "{text}");
+
+    let mut out = Vec::new();
+    clipboard::osc52(&mut out, "ps").expect("write");
+    assert_eq!(out, b"\x1b]52;c;cHM=\x07");
+}
+
+#[test]
+fn watchdog_signals_runaway_processes_once_and_logs_it() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let rule = |process: &str, mib: f64, dry_run| WatchdogRule {
+        name: process.to_string(),
+        process: Pattern::new(process).expect("pattern"),
+        metric: Metric::ProcessMemory,
+        above: mib * 1024.0 * 1024.0,
+        duration: Duration::ZERO,
+        signal: Signal::Kill,
+        dry_run,
+    };
+    app.watchdog = Watchdog::new(vec![
+        rule("queue slow", 300.0, false),
+        rule("leaky", 100.0, true),
+    ]);
+    app.on_snapshot(&snapshot, &ProcessChanges::default());
+    let commands = app.take_watchdog_commands();
+    let [Command::Signal { target, signal }] = commands.as_slice() else {
+        panic!("one signal expected: {commands:?}")
+    };
+    assert_eq!((target.pid.as_u32(), *signal), (701, Signal::Kill));
+    // 同じプロセスには 2 回送らない。dry_run は記録だけ
+    app.on_snapshot(&snapshot, &ProcessChanges::default());
+    assert!(app.take_watchdog_commands().is_empty());
+    press(&mut app, &snapshot, KeyCode::Char('w'), KeyModifiers::NONE);
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(contains(&buffer, "Sending SIGKILL to python3 (701)"));
+    assert!(!contains(&buffer, "Sent SIGKILL"));
+    assert!(contains(&buffer, "Would send SIGKILL to leaky (950)"));
+    // 送れなかったときは、送ったとは書かない
+    app.handle_outcome(
+        ActionOutcome {
+            command: commands[0].clone(),
+            result: Err(ActionError::Failed("Operation not permitted".to_string())),
+        },
+        &snapshot,
+    );
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(contains(
+        &buffer,
+        "Failed to send SIGKILL to python3 (701): Operation not permitted"
+    ));
+    assert!(!contains(&buffer, "Sent SIGKILL"));
+}
+
+#[test]
+fn shared_memory_panel_splits_shmem() {
+    let mut snapshot = snapshot();
+    let mb = 1024 * 1024;
+    let mount = |path: &str, used| TmpfsMount {
+        path: path.to_string(),
+        used,
+        total: 1024 * mb,
+    };
+    snapshot.shm = Some(ShmSnapshot {
+        mounts: vec![mount("/dev/shm", 200 * mb), mount("/run", 8 * mb)],
+        sysv: Some((2, 32 * mb)),
+    });
+    snapshot.memory.breakdown = Some(MemoryBreakdown {
+        shared: 300 * mb,
+        ..MemoryBreakdown::default()
+    });
+    let buffer = render(&app(&snapshot), &snapshot, 160, 50);
+    let row = title_row(&buffer, "Shared memory (2 tmpfs)").expect("shared memory panel");
+    let lines = lines(&buffer);
+    assert!(lines[row + 2].contains("/dev/shm"), "{}", lines[row + 2]);
+    assert!(lines[row + 2].contains("19.5%"), "{}", lines[row + 2]);
+    // 300 MB から tmpfs の 208 MB と SysV の 32 MB を引いた残り
+    let summary = &lines[row + 4];
+    assert!(summary.contains("SysV: 32 MB in 2 segments"), "{summary}");
+    assert!(summary.contains("memfd and other: 60 MB"), "{summary}");
+    assert!(contains(&buffer, "(300 MB shared)"));
+}
+
+#[test]
+fn huge_pages_panel_warns_when_commit_exceeds_a_strict_limit() {
+    let mut snapshot = snapshot();
+    let mb = 1024 * 1024;
+    snapshot.memory.huge_pages = Some(HugePages {
+        total: 512,
+        free: 128,
+        reserved: 16,
+        page_size: 2 * mb,
+        anon_huge: 300 * mb,
+        thp: Some(ThpMode::Madvise),
+        contiguous_free: Some(64 * mb),
+        commit_limit: 1000 * mb,
+        committed: 1200 * mb,
+        overcommit: Some(Overcommit::Strict),
+        ..HugePages::default()
+    });
+    let app = app(&snapshot);
+    let buffer = render(&app, &snapshot, 160, 50);
+    let row = title_row(&buffer, "Huge pages").expect("huge pages panel");
+    let lines = lines(&buffer);
+    assert!(lines[row + 1].contains("512 total, 128 free, 16 reserved"));
+    assert!(lines[row + 2].contains("THP (madvise): 300 MB anon"));
+    assert!(lines[row + 2].contains("2 MB or more: 64 MB"));
+    assert!(lines[row + 3].contains("1200 MB of 1000 MB limit (120.0%)"));
+    assert_eq!(buffer[(2, row as u16 + 3)].fg, app.theme.warning);
+}
+
+#[test]
+fn compressed_swap_panel_shows_the_stored_size_pool_and_ratio() {
+    use crate::zram::{self, CompressedSwap, ZramDevice, Zswap};
+
+    const MB: u64 = 1024 * 1024;
+    let mut snapshot = snapshot();
+    // mm_stat の 1・2・3・6 番目
+    let stat = format!(
+        "{} {} {} 0 {} 1200 0 0 0",
+        800 * MB,
+        200 * MB,
+        210 * MB,
+        300 * MB
+    );
+    let (original, compressed, used, same_pages) = zram::parse_mm_stat(&stat).expect("mm_stat");
+    snapshot.compressed_swap = Some(CompressedSwap {
+        zram: vec![ZramDevice {
+            name: "zram0".to_string(),
+            algorithm: Some("zstd".to_string()),
+            disksize: 4096 * MB,
+            original,
+            compressed,
+            used,
+            same_pages,
+        }],
+        zswap: Some(Zswap {
+            compressor: Some("lz4".to_string()),
+            pool: 100 * MB,
+            stored: 250 * MB,
+        }),
+        page_size: 4096,
+    });
+    let buffer = render(&app(&snapshot), &snapshot, 160, 50);
+    let row = title_row(&buffer, "Compressed swap").expect("compressed swap panel");
+    let lines = lines(&buffer);
+    assert!(
+        lines[row + 1].contains("zram0 (zstd): 800 MB stored in 200 MB (4.0x), 204800 pages"),
+        "{}",
+        lines[row + 1]
+    );
+    assert!(lines[row + 1].contains("1200 same-filled, 210 MB used of 4096 MB"));
+    assert!(lines[row + 2].contains("zswap (lz4): 250 MB stored in a 100 MB pool (2.5x)"));
+    assert_eq!(zram::ratio(1, 0), None);
+}
+
+#[test]
+fn layouts_switch_with_v_and_share_rows_by_weight() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    // top は CPU・メモリ・プロセス表だけで、プロセス表が残りを使う
+    press(&mut app, &snapshot, KeyCode::Char('V'), KeyModifiers::NONE);
+    assert_eq!(
+        app.message.as_ref().map(|m| m.0.as_str()),
+        Some("Layout: top")
+    );
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(title_row(&buffer, "Processes").is_some());
+    assert!(title_row(&buffer, "Top consumers").is_none());
+    assert!(title_row(&buffer, "Info").is_none());
+
+    // :layout は前方一致で選ぶ。グラフがプロセス表の 2 倍の余りをもらう
+    press(&mut app, &snapshot, KeyCode::Char(':'), KeyModifiers::NONE);
+    app.prompt.as_mut().expect("command prompt").text = "layout gr".to_string();
+    press(&mut app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+    assert_eq!(app.layouts[app.layout].name, "graphs");
+    app.on_snapshot(&snapshot, &ProcessChanges::default());
+    let buffer = render(&app, &snapshot, 160, 50);
+    let graphs = title_row(&buffer, "Graphs").expect("graphs panel");
+    let memory = title_row(&buffer, "Memory").expect("memory panel");
+    let processes = title_row(&buffer, "Processes").expect("process table");
+    assert!(graphs < memory && memory < processes);
+    assert!(lines(&buffer)[graphs + 1].contains("CPU "));
+    assert!(
+        memory - graphs > 50 - processes,
+        "{graphs} {memory} {processes}"
+    );
+
+    // [layouts] で足したものは V の順の最後に来る
+    app.layouts = layout::all(&[ScreenLayout::new("mine", &["processes", "cpu"]).unwrap()]);
+    app.layout = app.layouts.len() - 2;
+    press(&mut app, &snapshot, KeyCode::Char('V'), KeyModifiers::NONE);
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(title_row(&buffer, "Processes") < title_row(&buffer, "CPU"));
+    assert!(ScreenLayout::new("bad", &["memory:2"]).is_err());
+
+    let mut panels = [(4, 2, u16::MAX), (10, 1, 12), (5, 0, u16::MAX)];
+    layout::share(9, &mut panels);
+    assert_eq!(panels.map(|p| p.0), [11, 12, 5]);
+}
+
+#[test]
+fn wide_terminals_move_info_beside_the_process_table() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    // 見出しの行と桁
+    let find = |buffer: &Buffer, title: &str| {
+        lines(buffer).iter().enumerate().find_map(|(row, line)| {
+            let at = line.find(&format!("┌{title}"))?;
+            Some((row, text::width(&line[..at])))
+        })
+    };
+    let buffer = render(&app, &snapshot, 240, 50);
+    let processes = find(&buffer, "Processes").expect("process table");
+    let info = find(&buffer, "Info").expect("info panel");
+    assert!(info.1 > processes.1 + 100, "{info:?} {processes:?}");
+    // 右の列は上から積み、Info が残りの高さを使う
+    assert_eq!(info.0, 1);
+    assert!(lines(&buffer)[info.0 + 1].contains("Number of cpus"));
+
+    let buffer = render(&app, &snapshot, 160, 50);
+    let info = find(&buffer, "Info").expect("info panel");
+    assert!(info.0 > find(&buffer, "Processes").expect("process table").0);
+
+    app.side_column = SideColumn::Never;
+    let buffer = render(&app, &snapshot, 240, 50);
+    assert_eq!(find(&buffer, "Info").expect("info panel").1, 1);
+    app.side_column = SideColumn::Always;
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(find(&buffer, "Info").expect("info panel").1 > 100);
+}
+
+#[test]
+fn ui_state_round_trips_through_the_state_file() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    app.sort = SortKey::Memory;
+    app.sort_reversed = true;
+    app.name_filter = Some(Filter::parse(r#"name ~ "py\\d" && user == "root""#).unwrap());
+    app.user_filter = Some("postgres".to_string());
+    press(&mut app, &snapshot, KeyCode::Char('V'), KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Char('M'), KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Char('E'), KeyModifiers::NONE);
+    app.handle_interval(Ok(Duration::from_millis(2500)));
+
+    let text = UiState::from_app(&app).to_text();
+    assert!(text.contains("columns = [\"memory\", \"times\"]"), "{text}");
+    let state = UiState::parse(&text).expect("state file");
+    assert_eq!(state, UiState::from_app(&app));
+
+    let mut restored = App::new(&Args::default(), &Config::default());
+    state.apply(&mut restored);
+    assert_eq!(restored.sort, SortKey::Memory);
+    assert!(restored.sort_reversed);
+    assert_eq!(
+        restored.name_filter.map(|f| f.to_string()),
+        app.name_filter.map(|f| f.to_string())
+    );
+    assert_eq!(restored.user_filter.as_deref(), Some("postgres"));
+    assert_eq!(restored.layouts[restored.layout].name, "top");
+    assert!(restored.memory_detail && restored.process_times && !restored.cpu_history);
+    assert_eq!(restored.refresh_interval, Duration::from_millis(2500));
+
+    // --filter を付けて起動したときはそちらが勝つ。読めない値は無視する
+    let mut restored = App::new(&Args::default(), &Config::default());
+    restored.name_filter = Some(Filter::parse("nginx").unwrap());
+    let state = UiState::parse("sort = \"nope\"\ncolumns = [\"bogus\", \"fds\"]\n").unwrap();
+    state.apply(&mut restored);
+    assert_eq!(restored.sort, SortKey::default());
+    assert_eq!(
+        restored.name_filter.map(|f| f.to_string()).as_deref(),
+        Some("nginx")
+    );
+    assert!(restored.fd_counts && !restored.memory_detail);
+}
+
+#[test]
+fn config_watcher_reloads_the_theme_keys_and_layouts() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let path = std::env::temp_dir().join(format!("rust-top-reload-{}.toml", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut watcher = ConfigWatcher::watch(path.clone()).expect("watcher");
+    assert!(watcher.poll().is_none());
+
+    std::fs::write(
+        &path,
+        "[theme]\nname = \"protanopia\"\n[keys]\nkeymap = \"vim\"\n\
+         [layouts]\nstart = \"mine\"\nmine = [\"cpu\", \"processes\"]\n",
+    )
+    .unwrap();
+    std::thread::sleep(Duration::from_millis(1100));
+    let config = watcher.poll().expect("changed").expect("valid config");
+    app.reload_config(&config);
+    assert_eq!(app.theme.name, "protanopia");
+    assert_eq!(app.keymap, Keymap::Vim);
+    // 使っていたレイアウトが残っていればそのまま
+    assert_eq!(app.layouts[app.layout].name, "default");
+    assert!(app.layouts.iter().any(|l| l.name == "mine"));
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(contains(&buffer, "Config reloaded"));
+
+    // 書き間違えたときは Err を返し、App は前の設定のまま
+    std::fs::write(&path, "[theme]\nname = \"nope\"\n").unwrap();
+    std::thread::sleep(Duration::from_millis(1100));
+    let error = watcher.poll().expect("changed").expect_err("invalid theme");
+    assert!(error.to_string().contains("theme.name"), "{error}");
+    assert!(watcher.poll().is_none());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn profiles_switch_layout_columns_sort_thresholds_and_interval() {
+    let snapshot = snapshot();
+    let path = std::env::temp_dir().join(format!("rust-top-profiles-{}.toml", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut watcher = ConfigWatcher::watch(path.clone()).expect("watcher");
+    std::fs::write(
+        &path,
+        "[profiles.db-server]\nlayout = \"htop\"\ncolumns = [\"wchan\", \"fds\"]\n\
+         sort = \"memory\"\ninterval = 5\n\
+         [[profiles.db-server.threshold]]\nabove = 90\nstyle = \"red bold\"\n\
+         [profiles.laptop]\nlayout = \"minimal\"\n",
+    )
+    .unwrap();
+    std::thread::sleep(Duration::from_millis(1100));
+    let config = watcher.poll().expect("changed").expect("valid config");
+    let _ = std::fs::remove_file(&path);
+    let names: Vec<&str> = config.profiles.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["db-server", "laptop"]);
+
+    let mut app = App::new(&Args::default(), &config);
+    app.on_snapshot(&snapshot, &ProcessChanges::default());
+    app.full_command = true;
+    // :profile は前方一致で選び、更新間隔は送らせる
+    press(&mut app, &snapshot, KeyCode::Char(':'), KeyModifiers::NONE);
+    app.prompt.as_mut().expect("command prompt").text = "profile db".to_string();
+    let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+    let Effect::Interval(interval) = app.handle_key(enter, &snapshot) else {
+        panic!("profile with an interval");
+    };
+    assert_eq!(interval, Duration::from_secs(5));
+    assert_eq!(app.layouts[app.layout].name, "htop");
+    assert!(app.wait_channels && app.fd_counts && !app.full_command);
+    assert_eq!(app.sort, SortKey::Memory);
+    assert_eq!(app.thresholds.len(), 1);
+    assert_eq!(app.active_profile.as_deref(), Some("db-server"));
+
+    // 書いていないものは変えない
+    assert!(matches!(app.apply_profile("laptop"), Effect::None));
+    assert_eq!(app.layouts[app.layout].name, "minimal");
+    assert!(app.wait_channels && app.thresholds.len() == 1);
+    app.apply_profile("nope");
+    assert_eq!(
+        app.message.as_ref().map(|m| m.0.as_str()),
+        Some("Unknown profile: nope (db-server, laptop)")
+    );
+}
+
+#[test]
+fn caffeinate_toggles_the_sleep_inhibitor_and_shows_awake() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Char(':'), KeyModifiers::NONE);
+    app.prompt.as_mut().expect("command prompt").text = "caff".to_string();
+    app.handle_key(enter, &snapshot);
+    // systemd-inhibit が無い環境では断られ、理由を知らせる
+    let message = app
+        .message
+        .as_ref()
+        .map(|m| m.0.clone())
+        .unwrap_or_default();
+    if app.caffeinated() {
+        app.message = None;
+        assert!(contains(&render(&app, &snapshot, 160, 40), "| awake"));
+    } else {
+        assert!(
+            message.starts_with("Cannot keep the system awake"),
+            "{message}"
+        );
+        assert!(!contains(&render(&app, &snapshot, 160, 40), "| awake"));
+    }
+    app.caffeinate(Some(false));
+    assert!(!app.caffeinated());
+    assert_eq!(
+        crate::palette::parse("caffeinate maybe").unwrap_err(),
+        "Unknown state: maybe (on or off)"
+    );
+}
+
+#[test]
+fn space_tags_rows_for_bulk_signals_renice_and_export() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let pid = sysinfo::Pid::from_u32;
+    // Space は印を付けて次の行へ進む
+    app.selected = Some(pid(700));
+    app.sync_selection(&snapshot);
+    press(&mut app, &snapshot, KeyCode::Char(' '), KeyModifiers::NONE);
+    assert_eq!(app.selected, Some(pid(701)));
+    press(&mut app, &snapshot, KeyCode::Char(' '), KeyModifiers::NONE);
+    assert!(app.is_tagged(pid(700)) && app.is_tagged(pid(701)));
+    assert!(contains(&render(&app, &snapshot, 160, 50), "| tagged 2"));
+
+    let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Char('k'), KeyModifiers::NONE);
+    let prompt = app.prompt.as_ref().expect("signal prompt");
+    assert_eq!(prompt.kind.label(), "Send signal to 2 tagged processes");
+    let Effect::SendAll(commands) = app.handle_key(enter, &snapshot) else {
+        panic!("k should signal every tagged process");
+    };
+    let sent: Vec<_> = commands
+        .iter()
+        .map(|c| match c {
+            Command::Signal { target, signal } => (target.pid.as_u32(), *signal),
+            other => panic!("{other}"),
+        })
+        .collect();
+    assert_eq!(sent, [(700, Signal::Term), (701, Signal::Term)]);
+
+    press(&mut app, &snapshot, KeyCode::Char('N'), KeyModifiers::NONE);
+    app.prompt.as_mut().expect("renice prompt").text = "10".to_string();
+    let Effect::SendAll(commands) = app.handle_key(enter, &snapshot) else {
+        panic!("N should renice every tagged process");
+    };
+    assert!(
+        commands
+            .iter()
+            .all(|c| matches!(c, Command::Renice { nice: 10, .. }))
+    );
+
+    // S は印を付けた行だけを書き出す
+    press(&mut app, &snapshot, KeyCode::Char('S'), KeyModifiers::NONE);
+    app.prompt.as_mut().expect("save prompt").text = "csv".to_string();
+    app.handle_key(enter, &snapshot);
+    let message = app.message.as_ref().expect("message").0.clone();
+    let path = message
+        .strip_prefix("Saved 2 processes to ")
+        .unwrap_or_else(|| panic!("{message}"));
+    std::fs::remove_file(path).ok();
+
+    // 保護したプロセスが混ざっていれば送らない
+    app.selected = Some(pid(1));
+    app.sync_selection(&snapshot);
+    press(&mut app, &snapshot, KeyCode::Char(' '), KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Char('k'), KeyModifiers::NONE);
+    assert!(matches!(app.handle_key(enter, &snapshot), Effect::None));
+    assert_eq!(
+        app.message.as_ref().map(|m| m.0.as_str()),
+        Some("Cancelled: systemd is protected, untag it first")
+    );
+    press(&mut app, &snapshot, KeyCode::Esc, KeyModifiers::NONE);
+    assert!(app.tagged.is_empty());
+}
+
+#[test]
+fn z_freezes_and_continues_the_selected_process() {
+    let mut snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let pid = app.selected.expect("selected process");
+    let z = KeyEvent::new(KeyCode::Char('Z'), KeyModifiers::NONE);
+    let Effect::Send(Command::Signal { target, signal }) = app.handle_key(z, &snapshot) else {
+        panic!("Z should send a signal");
+    };
+    assert_eq!((target.pid, signal), (pid, Signal::Stop));
+
+    let process = snapshot
+        .processes
+        .iter_mut()
+        .find(|p| p.pid == pid)
+        .expect("process");
+    process.status = sysinfo::ProcessStatus::Stop;
+    let name = process.name.to_string();
+    app.selected = None;
+    let buffer = render(&app, &snapshot, 160, 50);
+    let lines = lines(&buffer);
+    let row = lines
+        .iter()
+        .position(|line| line.contains("[stopped]"))
+        .expect("frozen tag");
+    let x = text::width(&lines[row][..lines[row].find(&name).expect("name")]) as u16;
+    assert!(buffer[(x, row as u16)].modifier.contains(Modifier::DIM));
+
+    app.selected = Some(pid);
+    let Effect::Send(Command::Signal { signal, .. }) = app.handle_key(z, &snapshot) else {
+        panic!("Z should send a signal");
+    };
+    assert_eq!(signal, Signal::Continue);
+}
+
+#[test]
+fn leak_detector_flags_steady_memory_growth() {
+    let mut snapshot = snapshot();
+    let mut config = Config::default();
+    config.leak.window = Duration::from_millis(60);
+    let mut app = App::new(&Args::default(), &config);
+    app.locale = Locale::default();
+    app.ascii = false;
+    let leaky = snapshot
+        .processes
+        .iter()
+        .position(|p| &*p.name == "leaky")
+        .expect("leaky");
+    let pid = snapshot.processes[leaky].pid;
+    // 1 回だけ少し減っても、ほとんど増え続けていれば疑う
+    for i in 0..40 {
+        let grow = if i == 20 { -100_000 } else { 1_000_000 };
+        let memory = &mut snapshot.processes[leaky].memory;
+        *memory = memory.saturating_add_signed(grow);
+        snapshot.process_generation += 1;
+        app.on_snapshot(&snapshot, &ProcessChanges::default());
+        std::thread::sleep(Duration::from_millis(3));
+    }
+    assert!(app.leaks.growth(pid).is_some());
+    let leaks: Vec<_> = app
+        .events
+        .iter()
+        .filter(|e| e.text.starts_with("Possible memory leak"))
+        .collect();
+    assert_eq!(leaks.len(), 1, "reported once");
+    assert!(leaks[0].text.contains("leaky (950)"), "{}", leaks[0].text);
+    // 値の変わらないプロセスは疑わない
+    assert!(
+        snapshot
+            .processes
+            .iter()
+            .filter(|p| p.pid != pid)
+            .all(|p| app.leaks.growth(p.pid).is_none())
+    );
+
+    let buffer = render(&app, &snapshot, 160, 50);
+    let lines = lines(&buffer);
+    let row = lines
+        .iter()
+        .position(|line| line.contains(" leaky "))
+        .expect("leaky row");
+    let x = lines[row].find(" MB").expect("memory cell") as u16 - 1;
+    assert_eq!(buffer[(x, row as u16)].fg, app.theme.warning);
+
+    // 減り始めたら疑いを外す
+    for _ in 0..10 {
+        snapshot.processes[leaky].memory -= 5_000_000;
+        snapshot.process_generation += 1;
+        app.on_snapshot(&snapshot, &ProcessChanges::default());
+        std::thread::sleep(Duration::from_millis(3));
+    }
+    assert!(app.leaks.growth(pid).is_none());
+}
+
+#[test]
+fn heatmap_shows_each_core_over_time() {
+    let mut snapshot = snapshot();
+    let mut app = app(&snapshot);
+    app.ascii = false;
+    // cpu1 だけ張り付いている
+    for i in 0..12 {
+        snapshot.cpu.usages = vec![10.0 + i as f32, 100.0, 45.0, 0.0];
+        snapshot.process_generation += 1;
+        app.on_snapshot(&snapshot, &ProcessChanges::default());
+    }
+    press(&mut app, &snapshot, KeyCode::Char('O'), KeyModifiers::NONE);
+    assert!(app.heatmap);
+    let buffer = render(&app, &snapshot, 120, 40);
+    assert!(contains(&buffer, "CPU heatmap (4 cores"));
+    let screen = lines(&buffer);
+    let row = |label: &str| {
+        screen
+            .iter()
+            .position(|line| line.contains(&format!("│{label} ")))
+            .unwrap_or_else(|| panic!("{label} row"))
+    };
+    assert!(screen[row("cpu1")].contains(&"█".repeat(12)));
+    assert!(screen[row("cpu2")].contains(&"▒".repeat(12)));
+    let hot = row("cpu1") as u16;
+    let x = screen[hot as usize]
+        .find('█')
+        .map(|byte| text::width(&screen[hot as usize][..byte]));
+    assert_eq!(buffer[(x.expect("cell") as u16, hot)].fg, app.theme.warning);
+
+    // コアが行に収まらなければまとめて、いちばん高いコアを出す
+    let mut usages = vec![5.0; 40];
+    usages[1] = 100.0;
+    snapshot.cpu.usages = usages;
+    snapshot.process_generation += 1;
+    app.on_snapshot(&snapshot, &ProcessChanges::default());
+    let buffer = render(&app, &snapshot, 120, 40);
+    let pair = lines(&buffer)
+        .into_iter()
+        .find(|line| line.contains("│0-1 "))
+        .expect("cpu0 and cpu1 in one row");
+    assert!(pair.contains(&"█".repeat(13)), "{pair}");
+
+    press(&mut app, &snapshot, KeyCode::Esc, KeyModifiers::NONE);
+    assert!(!app.heatmap);
+}
+
+#[test]
+fn plain_mode_drops_borders_and_spells_out_changes() {
+    let snapshot = snapshot();
+    let args = Args {
+        plain: true,
+        ..Args::default()
+    };
+    let mut app = App::new(&args, &Config::default());
+    app.locale = Locale::default();
+    app.on_snapshot(&snapshot, &ProcessChanges::default());
+    assert!(app.ascii && app.theme.symbols);
+
+    let mut terminal = Terminal::new(TestBackend::new(160, 50)).expect("test terminal");
+    terminal
+        .draw(|f| {
+            super::draw(f, &app, &snapshot);
+        })
+        .expect("draw");
+    let buffer = terminal.backend().buffer().clone();
+    let screen = lines(&buffer);
+    // 罫線も、ASCII に置き換えた罫線も読ませない
+    for line in &screen {
+        assert!(!line.contains("--") && !line.contains('|'), "{line}");
+    }
+    let header = screen
+        .iter()
+        .position(|line| line.contains("PID") && line.contains("Name"))
+        .expect("process table header");
+    // 選んでいるプロセスの行にカーソルを置く
+    let cursor = terminal.get_cursor_position().expect("cursor");
+    assert_eq!(cursor.y as usize, header + 1);
+
+    press(&mut app, &snapshot, KeyCode::Char('D'), KeyModifiers::NONE);
+    assert_eq!(app.current_message(), Some("Shown: fds"));
+    press(&mut app, &snapshot, KeyCode::Down, KeyModifiers::NONE);
+    let message = app.current_message().expect("announced").to_string();
+    let selected = app.selected.expect("selected");
+    assert!(
+        message.contains(&format!("({selected}), CPU ")),
+        "{message}"
+    );
+    // キーが自分で出したメッセージはそのまま
+    press(&mut app, &snapshot, KeyCode::Char('V'), KeyModifiers::NONE);
+    assert_eq!(app.current_message(), Some("Layout: top"));
+}
+
+#[test]
+fn status_bar_keeps_the_last_error() {
+    let snapshot = snapshot();
+    let mut app = App::new(&Args::default(), &Config::default());
+    app.locale = Locale::default();
+    app.on_snapshot(&snapshot, &ProcessChanges::default());
+    app.error("Failed to send metrics: connection refused".to_string());
+    // メッセージが消えても、ステータスバーに最後の失敗が残る
+    app.message = None;
+
+    let mut terminal = Terminal::new(TestBackend::new(200, 50)).expect("test terminal");
+    terminal
+        .draw(|f| {
+            super::draw(f, &app, &snapshot);
+        })
+        .expect("draw");
+    let screen = lines(terminal.backend().buffer());
+    assert!(
+        screen
+            .iter()
+            .any(|line| line.contains("last error") && line.contains("connection refused")),
+        "{screen:?}"
+    );
+}
+
+#[test]
+fn denied_sources_show_a_note_instead_of_disappearing() {
+    let snapshot = snapshot();
+    assert!(snapshot.pressure.is_none());
+    let mounts = "proc /proc proc rw,nosuid,nodev,noexec,relatime,hidepid=invisible 0 0\n";
+    let open = |path: &str| match path {
+        "/proc/stat" | "/proc/pressure/cpu" => Err(io::ErrorKind::PermissionDenied.into()),
+        "/proc/net/tcp" => Err(io::ErrorKind::NotFound.into()),
+        _ => Ok(()),
+    };
+    // root なら hidepid が掛かっていても全部見える
+    assert_eq!(Access::check(open, mounts, true).hidepid, None);
+    let access = Access::check(open, mounts, false);
+    assert_eq!(access.hidepid, Some(HidePid::Invisible));
+    assert_eq!(access.denied(Slot::Tcp), None);
+
+    let mut app = app(&snapshot);
+    app.access = access;
+    let screen = lines(&render(&app, &snapshot, 160, 80));
+    let has = |text: &str| screen.iter().any(|line| line.contains(text));
+    assert!(has(
+        "CPU Usage: insufficient permissions to read /proc/stat"
+    ));
+    assert!(has("Pressure"));
+    assert!(has("insufficient permissions to read /proc/pressure/cpu"));
+    assert!(has("other users' processes are hidden (hidepid)"));
+    assert!(
+        has("limited: CPU usage, Pressure unreadable, hidepid"),
+        "{screen:#?}"
+    );
+}
+
+#[test]
+fn peers_view_lists_processes_on_the_other_end_of_pipes_and_sockets() {
+    let pid = sysinfo::Pid::from_u32;
+    // 700 の接続は名前の無い側なので、相手（postgres）の側の名前を使う
+    let ss = "u_str ESTAB 0 0 * 9001 * 9002\n\
+              u_str ESTAB 0 0 /var/run/postgresql/.s.PGSQL.5432 9002 * 9001\n\
+              u_str LISTEN 0 0 /run/app sock 9003 * 0\n";
+    let mut endpoints = Endpoints {
+        unix: peers::parse_ss(ss),
+        ..Endpoints::default()
+    };
+    assert_eq!(endpoints.unix.len(), 2);
+    endpoints.sockets.insert(9001, vec![pid(700)]);
+    endpoints.sockets.insert(9002, vec![pid(530)]);
+    // bash が書き、700 が読む。同じ読む側を受け継いだ 701 とはやり取りしていない
+    endpoints.pipes.insert(
+        50,
+        vec![(pid(812), true), (pid(700), false), (pid(701), false)],
+    );
+    let local = "127.0.0.1:41000".parse().expect("address");
+    let remote = "127.0.0.1:8080".parse().expect("address");
+    endpoints.tcp.insert(9101, (local, remote));
+    endpoints.tcp.insert(9102, (remote, local));
+    endpoints.sockets.insert(9101, vec![pid(700)]);
+    endpoints.sockets.insert(9102, vec![pid(611)]);
+
+    let peers = endpoints.peers_of(pid(700));
+    let pids: Vec<u32> = peers.iter().map(|peer| peer.pid.as_u32()).collect();
+    assert_eq!(pids, [530, 611, 812]);
+    assert_eq!(peers[2].channels, [Channel::Pipe { writes: false }]);
+    let pids: Vec<u32> = endpoints
+        .peers_of(pid(701))
+        .iter()
+        .map(|peer| peer.pid.as_u32())
+        .collect();
+    assert_eq!(pids, [812]);
+
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    app.peers = Some(PeersView {
+        pid: pid(700),
+        peers,
+        selected: 0,
+    });
+    let screen = lines(&render(&app, &snapshot, 160, 50));
+    let has = |text: &str| screen.iter().any(|line| line.contains(text));
+    assert!(has(
+        "Connected to 700 (3, Enter to select the process, Esc to close)"
+    ));
+    assert!(has("unix /var/run/postgresql/.s.PGSQL.5432"), "{screen:#?}");
+    assert!(has("TCP 127.0.0.1:41000->127.0.0.1:8080"));
+    assert!(has("pipe <-"));
+
+    press(&mut app, &snapshot, KeyCode::Down, KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+    assert!(app.peers.is_none());
+    assert_eq!(app.selected, Some(pid(611)));
+}
+
+#[test]
+fn status_bar_shows_the_stretched_idle_interval() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    app.idle_interval = Some(Duration::from_secs(5));
+    assert!(contains(
+        &render(&app, &snapshot, 200, 40),
+        "every 5s (idle)"
+    ));
+}
+
+#[test]
+fn baseline_view_lists_started_and_exited_processes_and_their_growth() {
+    let mut snapshot = snapshot();
+    snapshot.processes.retain(|p| !p.is_thread);
+    let mut app = app(&snapshot);
+    let deploy = sysinfo::Pid::from_u32(9001);
+    press(&mut app, &snapshot, KeyCode::Char('B'), KeyModifiers::NONE);
+    assert!(app.baseline.is_some());
+    assert!(app.baseline_view.is_none());
+
+    // 1 つ目は終わり、2 つ目は CPU 時間とメモリが増え、新しいプロセスが 1 つ起動した
+    let exited = snapshot.processes.remove(0);
+    snapshot.processes[0].cpu_time += 2_500;
+    snapshot.processes[0].memory += 10 * 1024 * 1024;
+    let grown = snapshot.processes[0].clone();
+    let mut started = grown.clone();
+    started.pid = deploy;
+    started.name = "deploy".into();
+    started.cpu_time = 1_000;
+    snapshot.processes.push(started);
+    snapshot.process_generation += 1;
+    app.on_snapshot(&snapshot, &ProcessChanges::default());
+    press(&mut app, &snapshot, KeyCode::Char('B'), KeyModifiers::NONE);
+
+    let view = app.baseline_view.as_ref().expect("diff view");
+    let changes: Vec<_> = view.rows.iter().map(|r| (r.change, r.pid)).collect();
+    assert_eq!(
+        changes,
+        vec![
+            (Change::Started, deploy),
+            (Change::Exited, exited.pid),
+            (Change::Running, grown.pid),
+        ]
+    );
+    assert_eq!(view.rows[2].cpu_time, 2_500);
+    let buffer = render(&app, &snapshot, 200, 40);
+    assert!(contains(&buffer, "+0:02.50"));
+    assert!(contains(&buffer, "+10.0 MB"));
+    assert!(contains(&buffer, "exited"));
+
+    press(&mut app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+    assert!(app.baseline_view.is_none());
+    assert_eq!(app.selected, Some(deploy));
+}
+
+#[test]
+fn cgroup_tree_folds_slices_and_lists_the_processes_of_leaf_groups() {
+    use crate::cgtree::{CgroupTree, TreeRow};
+
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let pid = snapshot.processes[0].pid;
+    let root = std::env::temp_dir().join(format!("rust-top-cgroup-{}", std::process::id()));
+    let service = root.join("system.slice").join("nginx.service");
+    std::fs::create_dir_all(&service).expect("create cgroups");
+    std::fs::create_dir_all(root.join("user.slice")).expect("create cgroups");
+    let usage = |dir: &std::path::Path, usec: u64| {
+        std::fs::write(
+            dir.join("cpu.stat"),
+            format!("usage_usec {usec}\nuser_usec 0\n"),
+        )
+        .expect("write cpu.stat");
+    };
+    usage(&service, 1_000_000);
+    std::fs::write(service.join("memory.current"), "52428800\n").expect("write memory");
+    std::fs::write(service.join("cgroup.procs"), format!("{pid}\n")).expect("write procs");
+
+    // 1 秒で 0.5 秒分の CPU を使った
+    let start = Instant::now();
+    let mut tree = CgroupTree::read(&root, start);
+    usage(&service, 1_500_000);
+    tree.refresh(start + Duration::from_secs(1));
+    let _ = std::fs::remove_dir_all(&root);
+    let paths: Vec<&str> = tree.nodes.iter().map(|n| n.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "/",
+            "/system.slice",
+            "/system.slice/nginx.service",
+            "/user.slice"
+        ]
+    );
+    assert_eq!(tree.nodes[2].cpu, Some(50.0));
+
+    // 最初は system.slice を畳んでいる
+    app.cgroup_tree = Some(CgroupTreeView::new(tree));
+    let view = app.cgroup_tree.as_ref().expect("tree");
+    assert_eq!(
+        view.rows(),
+        [TreeRow::Group(0), TreeRow::Group(1), TreeRow::Group(3)]
+    );
+    press(&mut app, &snapshot, KeyCode::Down, KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Right, KeyModifiers::NONE);
+    let view = app.cgroup_tree.as_ref().expect("tree");
+    assert_eq!(view.rows()[3], TreeRow::Process(2, pid));
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(contains(&buffer, "- system.slice"));
+    assert!(contains(&buffer, "nginx.service"));
+    assert!(contains(&buffer, "50.0%"));
+    assert!(contains(&buffer, "50.0 MB"));
+
+    press(&mut app, &snapshot, KeyCode::Down, KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Down, KeyModifiers::NONE);
+    press(&mut app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+    assert!(app.cgroup_tree.is_none());
+    assert_eq!(app.selected, Some(pid));
+}
+
+#[test]
+fn readonly_mode_refuses_every_action_that_changes_a_process() {
+    let snapshot = snapshot();
+    let args = Args {
+        readonly: true,
+        ..Args::default()
+    };
+    let mut app = App::new(&args, &Config::default());
+    app.locale = Locale::default();
+    app.on_snapshot(&snapshot, &ProcessChanges::default());
+    let pid = app.selected.expect("selected process");
+    for key in ['k', 'K', 'Z', 'N', 'o', 'a'] {
+        let effect = app.handle_key(
+            KeyEvent::new(KeyCode::Char(key), KeyModifiers::NONE),
+            &snapshot,
+        );
+        assert!(matches!(effect, Effect::None), "{key}");
+        assert!(app.prompt.is_none(), "{key}");
+        assert!(app.affinity.is_none(), "{key}");
+    }
+
+    // コマンドモードの :kill も送らない
+    press(&mut app, &snapshot, KeyCode::Char(':'), KeyModifiers::NONE);
+    app.prompt.as_mut().expect("command prompt").text = format!("kill {pid} KILL");
+    let effect = app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE), &snapshot);
+    assert!(matches!(effect, Effect::None));
+    // 印を付けたプロセスへの一括送信も、プロンプトがどこから開いても送らない
+    let tagged: Vec<ProcessRef> = snapshot
+        .processes
+        .iter()
+        .take(2)
+        .map(|p| ProcessRef {
+            pid: p.pid,
+            start_time: p.start_time,
+        })
+        .collect();
+    app.prompt = Some(Prompt {
+        kind: PromptKind::ReniceTagged(tagged),
+        text: "5".to_string(),
+    });
+    let effect = app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE), &snapshot);
+    assert!(matches!(effect, Effect::None));
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(contains(&buffer, "read-only"));
+    assert!(contains(&buffer, "Read-only: processes cannot be changed"));
+}
+
+#[test]
+fn threshold_styles_replace_the_caution_and_warning_colors_of_usage_bars() {
+    use crate::theme::{self, Threshold};
+
+    assert_eq!(
+        theme::parse_style("208 bold reversed"),
+        Ok(Style::default()
+            .fg(Color::Indexed(208))
+            .add_modifier(Modifier::BOLD | Modifier::REVERSED))
+    );
+    assert_eq!(
+        theme::parse_style("#0072b2").map(|s| s.fg),
+        Ok(Some(Color::Rgb(0, 0x72, 0xb2)))
+    );
+    assert!(theme::parse_style("red blue").is_err());
+    assert!(theme::parse_style("sparkly").is_err());
+
+    // 使用率は 0% 以上なので必ず下の段、100% は超えないので上の段は使わない
+    let snapshot = snapshot();
+    let config = Config {
+        thresholds: vec![
+            Threshold {
+                above: 0.0,
+                style: theme::parse_style("blue underlined").expect("style"),
+            },
+            Threshold {
+                above: 100.5,
+                style: theme::parse_style("red").expect("style"),
+            },
+        ],
+        ..Config::default()
+    };
+    let mut app = App::new(&Args::default(), &config);
+    app.locale = Locale::default();
+    app.ascii = false;
+    app.on_snapshot(&snapshot, &ProcessChanges::default());
+    let buffer = render(&app, &snapshot, 160, 50);
+    let filled: Vec<_> = buffer
+        .content
+        .iter()
+        .filter(|cell| cell.symbol() == "━" && cell.modifier.contains(Modifier::UNDERLINED))
+        .collect();
+    assert!(!filled.is_empty());
+    assert!(filled.iter().all(|cell| cell.fg == Color::Blue));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn file_command_finds_the_processes_holding_a_file_open() {
+    let path = std::env::temp_dir().join(format!("rust-top-held-{}.log", std::process::id()));
+    let file = std::fs::File::create(&path).expect("create file");
+    let me = sysinfo::Pid::from_u32(std::process::id());
+    assert!(crate::files::holders(&path).expect("holders").contains(&me));
+
+    // このテスト自身は作り物の一覧に無い
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let selected = app.selected;
+    let command = |app: &mut App, text: String| {
+        press(app, &snapshot, KeyCode::Char(':'), KeyModifiers::NONE);
+        app.prompt.as_mut().expect("command prompt").text = text;
+        press(app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+    };
+    command(&mut app, format!("file {}", path.display()));
+    assert_eq!(app.selected, selected);
+    assert!(contains(
+        &render(&app, &snapshot, 160, 50),
+        "is open only in processes hidden from the table"
+    ));
+    drop(file);
+    let _ = std::fs::remove_file(&path);
+    command(&mut app, format!("file {}", path.display()));
+    assert!(contains(
+        &render(&app, &snapshot, 200, 50),
+        "Cannot search for"
+    ));
+}
+
+#[test]
+fn wait_channel_column_names_what_sleeping_processes_are_blocked_on() {
+    use crate::wchan;
+
+    let stack = "[<0>] __schedule+0x2e3/0x860\n\
+                 [<0>] schedule+0x5a/0xc0\n\
+                 [<0>] futex_wait_queue+0x63/0x90\n\
+                 [<0>] futex_wait+0x166/0x260\n";
+    assert_eq!(wchan::parse_stack(stack), Some("futex_wait_queue"));
+    assert_eq!(wchan::kind("folio_wait_bit_common"), Some("disk"));
+    assert_eq!(wchan::kind("clock_nanosleep"), Some("sleep"));
+    assert_eq!(wchan::kind("sk_wait_data"), Some("network"));
+    assert_eq!(wchan::kind("do_epoll_wait"), Some("poll"));
+    assert_eq!(wchan::kind("kthread_worker_fn"), None);
+
+    let mut snapshot = snapshot();
+    let mut app = app(&snapshot);
+    press(&mut app, &snapshot, KeyCode::Char('i'), KeyModifiers::NONE);
+    assert!(app.wait_channels);
+    let blocked = snapshot
+        .processes
+        .iter_mut()
+        .find(|p| p.pid.as_u32() == 900)
+        .expect("pid 900");
+    blocked.status = sysinfo::ProcessStatus::UninterruptibleDiskSleep;
+    blocked.wait_channel = Some("folio_wait_bit_common".into());
+    app.sort = SortKey::WaitChannel;
+    let buffer = render(&app, &snapshot, 220, 50);
+    assert!(contains(&buffer, "WChan"));
+    assert!(contains(&buffer, "folio_wait_bit_common (disk)"));
+
+    app.selected = Some(sysinfo::Pid::from_u32(900));
+    press(&mut app, &snapshot, KeyCode::Enter, KeyModifiers::NONE);
+    let buffer = render(&app, &snapshot, 220, 50);
+    assert!(contains(&buffer, "Waiting on"));
+}
+
+#[test]
+fn clock_ticks_redraw_only_the_status_bar_and_skip_unchanged_frames() {
+    let snapshot = snapshot();
+    let mut app = app(&snapshot);
+    let mut terminal = Terminal::new(TestBackend::new(160, 50)).expect("test terminal");
+    let mut drawn = super::Drawn::default();
+    let frame = terminal
+        .draw(|f| drawn = super::draw(f, &app, &snapshot))
+        .expect("draw");
+    let previous = frame.buffer.clone();
+    let area = drawn.status.expect("status bar is visible");
+    assert_eq!(area.y, 49);
+
+    // ステータスバーの外はそのまま、ステータスバーだけが新しくなる
+    app.set_message("Saved screen to /tmp/screen.txt".to_string());
+    let updated = super::redraw_status(&previous, area, &app).expect("status changed");
+    let (before, after) = (lines(&previous), lines(&updated));
+    assert_eq!(before[..49], after[..49]);
+    assert!(after[49].contains("Saved screen to"));
+
+    // 何も変わらなければ描かない（秒が変わったときは 1 度だけ描き直す）
+    let current = super::redraw_status(&updated, area, &app).unwrap_or(updated);
+    assert!(super::redraw_status(&current, area, &app).is_none());
+
+    // 経過時間の列などは時計と一緒に全体を描き直す
+    assert!(!app.ticking());
+    app.process_times = true;
+    assert!(app.ticking());
+}
+
+#[test]
+fn startup_health_check_lists_findings_until_dismissed() {
+    use crate::health::{self, DiskUsage, Probe, Temperature};
+
+    let mut snapshot = snapshot();
+    let locale = Locale::default();
+    snapshot.memory.total_swap = 1000 * 1024 * 1024;
+    snapshot.memory.used_swap = 0;
+    snapshot.info.load_average = [0.5, 0.5, 0.5];
+    for p in &mut snapshot.processes {
+        p.status = sysinfo::ProcessStatus::Sleep;
+    }
+    assert!(health::check(&snapshot, &Probe::default(), &locale).is_empty());
+
+    snapshot.memory.used_swap = 900 * 1024 * 1024;
+    snapshot.info.load_average[0] = snapshot.cpu.count as f64 + 1.0;
+    for p in snapshot.processes.iter_mut().take(10) {
+        p.status = sysinfo::ProcessStatus::Zombie;
+    }
+    let probe = Probe {
+        disks: vec![
+            DiskUsage {
+                mount: "/".to_string(),
+                total: 100,
+                available: 50,
+            },
+            DiskUsage {
+                mount: "/var".to_string(),
+                total: 1000,
+                available: 10,
+            },
+        ],
+        temperatures: vec![Temperature {
+            label: "Package id 0".to_string(),
+            current: 95.0,
+            critical: 100.0,
+        }],
+    };
+    let findings = health::check(&snapshot, &probe, &locale);
+    let names: Vec<&str> = findings.iter().map(|f| f.name).collect();
+    // 重いもの（ディスクがほぼいっぱい）が先
+    assert_eq!(names, ["Disk", "Swap", "Zombies", "Load", "Temperature"]);
+    assert!(findings[0].severe && findings[0].detail.contains("/var is 99.0% full"));
+    assert!(findings[1].detail.contains("90.0% used"));
+
+    let mut app = app(&snapshot);
+    app.health = findings;
+    let buffer = render(&app, &snapshot, 160, 50);
+    assert!(contains(&buffer, "Health check (5, Esc to close)"));
+    assert!(contains(
+        &buffer,
+        "Package id 0 at 95°C (critical at 100°C)"
+    ));
+    press(&mut app, &snapshot, KeyCode::Esc, KeyModifiers::NONE);
+    assert!(app.health.is_empty());
+    assert!(!contains(&render(&app, &snapshot, 160, 50), "Health check"));
+}
@@ -0,0 +1,195 @@
+// 設定ファイルの [[watchdog]]：決まったプロセスが使い過ぎたら、自動でシグナルを送る
+//
+// 例（CI のランナーで、暴走したテストがホストのメモリを使い切る前に止める）:
+//   [[watchdog]]
+//   name = "runaway tests"
+//   process = "^pytest"     # 名前かコマンドラインに一致するプロセスだけを見る（必須）
+//   metric = "process_memory"   # process_cpu（1 コア = 100%）か process_memory（バイト）
+//   above = "8GiB"
+//   for = 30                # 秒。この間ずっと超えていたら送る
+//   signal = "KILL"         # 既定は TERM
+//   dry_run = true          # 送らずに、送るはずだったことを記録だけする
+//
+// [[watchdog]] を書いたときだけ、手元のマシンを見ているときだけ動く。
+// [protect] のプロセスには送らない。同じプロセスには 1 回だけ送り、w の一覧に記録する。
+// 送るのはサンプラーなので、一覧には送ろうとしたときと結果が返ってきたときの 2 行を残す。
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use sysinfo::Signal;
+
+use crate::actions::{self, ActionError, ActionOutcome, Command, ProcessRef};
+use crate::alert::{self, Metric};
+use crate::config::ProtectConfig;
+use crate::pattern::Pattern;
+use crate::sampler::Snapshot;
+
+/// 一覧に残す件数
+const LOG_LEN: usize = 200;
+
+#[derive(Debug, Clone)]
+pub struct WatchdogRule {
+    pub name: String,
+    pub process: Pattern,
+    /// process_cpu か process_memory
+    pub metric: Metric,
+    pub above: f64,
+    pub duration: Duration,
+    pub signal: Signal,
+    pub dry_run: bool,
+}
+
+/// 条件を満たしたプロセスにしたこと
+#[derive(Debug)]
+pub struct Action {
+    /// 「Sending SIGKILL to pytest (1234) (...)」など。一覧にも同じものを残す
+    pub text: String,
+    /// dry_run や [protect] なら None
+    pub command: Option<Command>,
+}
+
+/// 一覧（w）の 1 行
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// UNIX 時刻（秒）
+    pub time: u64,
+    pub text: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Watchdog {
+    rules: Vec<WatchdogRule>,
+    /// 条件を満たし始めた時刻（ルールの番号とプロセスごと）
+    exceeded_since: HashMap<(usize, ProcessRef), Instant>,
+    /// 送った（dry_run なら送るはずだった）もの。終了するまで同じプロセスには送らない
+    acted: HashSet<(usize, ProcessRef)>,
+    /// サンプラーに頼んで結果を待っているシグナルと、一覧に書くプロセスの名前
+    sending: HashMap<ProcessRef, (Signal, String)>,
+    log: VecDeque<Entry>,
+}
+
+impl Watchdog {
+    pub fn new(rules: Vec<WatchdogRule>) -> Self {
+        Self {
+            rules,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 古い順
+    pub fn log(&self) -> impl DoubleEndedIterator<Item = &Entry> + ExactSizeIterator {
+        self.log.iter()
+    }
+
+    /// 新しいスナップショットで評価し、したことを返す。一覧にも記録する
+    pub fn evaluate(
+        &mut self,
+        snapshot: &Snapshot,
+        protect: &ProtectConfig,
+        now: Instant,
+    ) -> Vec<Action> {
+        if self.rules.is_empty() || !snapshot.cpu_primed {
+            return Vec::new();
+        }
+        let mut done = Vec::new();
+        let mut exceeded = HashSet::new();
+        let mut alive = HashSet::new();
+        for process in snapshot.processes.iter().filter(|p| !p.is_thread) {
+            let target = ProcessRef {
+                pid: process.pid,
+                start_time: process.start_time,
+            };
+            alive.insert(target);
+            for (i, rule) in self.rules.iter().enumerate() {
+                let value = alert::process_value(rule.metric, process);
+                if value <= rule.above
+                    || !(rule.process.is_match(&process.name)
+                        || rule.process.is_match(&process.cmd))
+                {
+                    continue;
+                }
+                exceeded.insert((i, target));
+                let since = *self.exceeded_since.entry((i, target)).or_insert(now);
+                if now.duration_since(since) < rule.duration || self.acted.contains(&(i, target)) {
+                    continue;
+                }
+                self.acted.insert((i, target));
+                let reason = format!(
+                    "{}: {} {} > {}",
+                    rule.name,
+                    rule.metric.label(),
+                    rule.metric.format(value),
+                    rule.metric.format(rule.above)
+                );
+                let signal = actions::signal_name(rule.signal);
+                let subject = format!("{} ({})", process.name, process.pid);
+                done.push(if protect.protects(process.pid, &process.name) {
+                    Action {
+                        text: format!("Skipped protected {subject} ({reason})"),
+                        command: None,
+                    }
+                } else if rule.dry_run {
+                    Action {
+                        text: format!("Would send SIG{signal} to {subject} ({reason}, dry run)"),
+                        command: None,
+                    }
+                } else {
+                    self.sending.insert(target, (rule.signal, subject.clone()));
+                    Action {
+                        text: format!("Sending SIG{signal} to {subject} ({reason})"),
+                        command: Some(Command::Signal {
+                            target,
+                            signal: rule.signal,
+                        }),
+                    }
+                });
+            }
+        }
+        // 下がったものは数え直し、終了したものは忘れる
+        self.exceeded_since.retain(|key, _| exceeded.contains(key));
+        self.acted.retain(|(_, target)| alive.contains(target));
+        for action in &done {
+            self.record(action.text.clone());
+        }
+        done
+    }
+
+    /// watchdog が送ったシグナルなら、その結果を一覧に書く
+    pub fn record_outcome(&mut self, outcome: &ActionOutcome) {
+        let Command::Signal { target, signal } = outcome.command else {
+            return;
+        };
+        match self.sending.get(&target) {
+            Some((sent, _)) if *sent == signal => {}
+            _ => return,
+        }
+        let (_, subject) = self.sending.remove(&target).expect("checked above");
+        let signal = actions::signal_name(signal);
+        self.record(match &outcome.result {
+            Ok(()) => format!("Sent SIG{signal} to {subject}"),
+            Err(e) => {
+                let reason = match e {
+                    ActionError::NoSuchProcess => "it had already exited",
+                    ActionError::PidReused => "its PID belongs to a different process now",
+                    ActionError::Unsupported => "unsupported",
+                    ActionError::Failed(reason) => reason,
+                };
+                format!("Failed to send SIG{signal} to {subject}: {reason}")
+            }
+        });
+    }
+
+    fn record(&mut self, text: String) {
+        if self.log.len() == LOG_LEN {
+            self.log.pop_front();
+        }
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.log.push_back(Entry { time, text });
+    }
+}
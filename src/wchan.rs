@@ -0,0 +1,83 @@
+// 眠っているプロセスが待っているもの（WChan 列と詳細、Linux）
+//
+// /proc/<pid>/wchan にはカーネルの中で止まっている関数の名前が入る。動いているものは 0。
+// wchan を隠すカーネルでは、root なら /proc/<pid>/stack の一番上の関数で代える。
+// 関数名だけでは何を待っているのか分かりにくいので、ディスク・ロック・ネットワークなどの
+// 大まかな種類を添える。D 状態のプロセスがディスクとロックのどちらで詰まっているかを見分けたい。
+use std::sync::Arc;
+
+use sysinfo::Pid;
+
+/// 大まかな種類と、関数名に含まれていればそれとみなす語。上から順に調べる
+const KINDS: [(&str, &[&str]); 8] = [
+    (
+        "disk",
+        &[
+            "io_schedule",
+            "folio_wait",
+            "wait_on_page",
+            "blk_",
+            "bio_",
+            "jbd2",
+            "ext4",
+            "xfs",
+            "btrfs",
+            "nfs",
+            "fsync",
+            "writeback",
+        ],
+    ),
+    // clock_nanosleep を lock と取り違えないよう、先に調べる
+    ("sleep", &["nanosleep", "hrtimer", "schedule_timeout"]),
+    ("lock", &["futex", "mutex", "rwsem", "lock"]),
+    (
+        "network",
+        &["sk_wait", "inet_", "tcp_", "udp_", "unix_", "sock", "skb_"],
+    ),
+    ("terminal", &["tty"]),
+    ("pipe", &["pipe_"]),
+    ("poll", &["poll", "select", "epoll"]),
+    ("child", &["do_wait", "wait_consider"]),
+];
+
+/// 関数名から大まかな種類を決める。当てはまらなければ None
+pub fn kind(symbol: &str) -> Option<&'static str> {
+    KINDS
+        .iter()
+        .find(|(_, words)| words.iter().any(|word| symbol.contains(word)))
+        .map(|(kind, _)| *kind)
+}
+
+/// 列と詳細に出す「関数名 (種類)」
+pub fn describe(symbol: &str) -> String {
+    match kind(symbol) {
+        Some(kind) => format!("{symbol} ({kind})"),
+        None => symbol.to_string(),
+    }
+}
+
+/// /proc/<pid>/stack の「[<0>] do_sys_poll+0x3d3/0x5a0」の並びから、スケジューラーの中を除いた
+/// 一番上の関数の名前を返す
+pub fn parse_stack(text: &str) -> Option<&str> {
+    text.lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(|frame| frame.split_once('+').map_or(frame, |(name, _)| name))
+        .find(|name| !name.starts_with("__schedule") && !name.starts_with("schedule"))
+}
+
+/// 待っている関数の名前。動いているか、読めなければ None
+#[cfg(target_os = "linux")]
+pub fn read(pid: Pid) -> Option<Arc<str>> {
+    let wchan = std::fs::read_to_string(format!("/proc/{pid}/wchan")).unwrap_or_default();
+    let wchan = wchan.trim();
+    if !wchan.is_empty() && wchan != "0" {
+        return Some(Arc::from(wchan));
+    }
+    let stack = std::fs::read_to_string(format!("/proc/{pid}/stack")).ok()?;
+    parse_stack(&stack).map(Arc::from)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read(_pid: Pid) -> Option<Arc<str>> {
+    None
+}
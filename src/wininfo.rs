@@ -0,0 +1,80 @@
+// Windows のエディションとビルド番号（レジストリの CurrentVersion を読む）
+//
+// sysinfo の OS 名には "23H2" などの機能更新の版や累積更新の番号が含まれないため、
+// winver と同じ値をここで補う。
+use windows_sys::Win32::Foundation::ERROR_SUCCESS;
+use windows_sys::Win32::System::Registry::{
+    HKEY_LOCAL_MACHINE, RRF_RT_REG_DWORD, RRF_RT_REG_SZ, RegGetValueW,
+};
+
+const CURRENT_VERSION: &str = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion";
+
+/// "Professional" など
+pub fn edition() -> Option<String> {
+    string("EditionID")
+}
+
+/// "23H2 (build 22631.4317)" の形。古い Windows で DisplayVersion が無ければビルド番号だけ
+pub fn build() -> Option<String> {
+    let build = string("CurrentBuild")?;
+    let build = match dword("UBR") {
+        Some(revision) => format!("{build}.{revision}"),
+        None => build,
+    };
+    Some(
+        match string("DisplayVersion").or_else(|| string("ReleaseId")) {
+            Some(version) => format!("{version} (build {build})"),
+            None => format!("build {build}"),
+        },
+    )
+}
+
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(Some(0)).collect()
+}
+
+fn string(name: &str) -> Option<String> {
+    let key = wide(CURRENT_VERSION);
+    let name = wide(name);
+    let mut buffer = [0u16; 256];
+    let mut size = std::mem::size_of_val(&buffer) as u32;
+    // SAFETY: 文字列は終端付きで、size はバッファのバイト数
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            key.as_ptr(),
+            name.as_ptr(),
+            RRF_RT_REG_SZ,
+            std::ptr::null_mut(),
+            buffer.as_mut_ptr().cast(),
+            &mut size,
+        )
+    };
+    if status != ERROR_SUCCESS {
+        return None;
+    }
+    // size は終端を含むバイト数
+    let len = (size as usize / 2).saturating_sub(1).min(buffer.len());
+    let text = String::from_utf16_lossy(&buffer[..len]);
+    (!text.is_empty()).then_some(text)
+}
+
+fn dword(name: &str) -> Option<u32> {
+    let key = wide(CURRENT_VERSION);
+    let name = wide(name);
+    let mut value = 0u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    // SAFETY: 文字列は終端付きで、size は value のバイト数
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            key.as_ptr(),
+            name.as_ptr(),
+            RRF_RT_REG_DWORD,
+            std::ptr::null_mut(),
+            (&mut value as *mut u32).cast(),
+            &mut size,
+        )
+    };
+    (status == ERROR_SUCCESS).then_some(value)
+}
@@ -0,0 +1,45 @@
+// Windows のジョブオブジェクトと UWP パッケージの情報
+//
+// 他のプロセスが属するジョブのハンドルは得られないため、
+// 所属しているかどうかとパッケージ名だけを調べる。
+use sysinfo::Pid;
+use windows_sys::Win32::Foundation::{CloseHandle, ERROR_SUCCESS, FALSE};
+use windows_sys::Win32::Storage::Packaging::Appx::GetPackageFullName;
+use windows_sys::Win32::System::JobObjects::IsProcessInJob;
+use windows_sys::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+#[derive(Debug, Clone, Default)]
+pub struct JobInfo {
+    /// ジョブオブジェクトに属しているか（調べられなければ None）
+    pub in_job: Option<bool>,
+    /// UWP / MSIX パッケージのフルネーム
+    pub package: Option<String>,
+}
+
+/// パッケージのフルネームの最大長（PACKAGE_FULL_NAME_MAX_LENGTH + 終端）
+const PACKAGE_NAME_LEN: usize = 128;
+
+pub fn job_info(pid: Pid) -> JobInfo {
+    // SAFETY: 得たハンドルはこの関数の中でだけ使い、最後に閉じる
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid.as_u32());
+        if handle.is_null() {
+            return JobInfo::default();
+        }
+        let mut in_job = FALSE;
+        // ジョブのハンドルに null を渡すと、いずれかのジョブに属しているかを返す
+        let in_job = (IsProcessInJob(handle, std::ptr::null_mut(), &mut in_job) != FALSE)
+            .then_some(in_job != FALSE);
+
+        let mut buffer = [0u16; PACKAGE_NAME_LEN];
+        let mut len = buffer.len() as u32;
+        let package = (GetPackageFullName(handle, &mut len, buffer.as_mut_ptr()) == ERROR_SUCCESS)
+            .then(|| {
+                let end = (len as usize).saturating_sub(1).min(buffer.len());
+                String::from_utf16_lossy(&buffer[..end])
+            });
+
+        CloseHandle(handle);
+        JobInfo { in_job, package }
+    }
+}
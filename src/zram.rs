@@ -0,0 +1,147 @@
+// 圧縮したスワップ（zram と zswap、Linux）
+//
+// zram はメモリの中の圧縮したブロックデバイスで、zswap はスワップに書き出す前に圧縮して持つ
+// キャッシュ。どちらも「スワップの使用量」だけでは実際に食っているメモリが分からないので、
+// 元の大きさ・圧縮後の大きさ・圧縮率・入っているページの数を出す。
+// zram は /sys/block/zramN の mm_stat、zswap は /proc/meminfo の Zswap / Zswapped（5.19 以降）を読む。
+
+/// 単位はバイト
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompressedSwap {
+    /// 使っている zram デバイス（名前の順）
+    pub zram: Vec<ZramDevice>,
+    /// zswap が有効でなければ None
+    pub zswap: Option<Zswap>,
+    pub page_size: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ZramDevice {
+    pub name: String,
+    /// 選んでいる圧縮方式（lz4、zstd など）
+    pub algorithm: Option<String>,
+    /// デバイスの大きさ
+    pub disksize: u64,
+    /// 入っているデータの元の大きさ
+    pub original: u64,
+    /// 圧縮後の大きさ
+    pub compressed: u64,
+    /// 管理の領域を含めて実際に使っているメモリ
+    pub used: u64,
+    /// 同じ値で埋まっていて、圧縮せずに値だけ覚えているページの数
+    pub same_pages: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Zswap {
+    pub compressor: Option<String>,
+    /// 圧縮したプールの大きさ
+    pub pool: u64,
+    /// 入っているページの元の大きさ
+    pub stored: u64,
+}
+
+/// 元の大きさが圧縮後の何倍か。圧縮後が 0 なら None
+pub fn ratio(original: u64, compressed: u64) -> Option<f64> {
+    (compressed > 0).then(|| original as f64 / compressed as f64)
+}
+
+/// mm_stat の「orig_data_size compr_data_size mem_used_total mem_limit mem_used_max same_pages ...」
+/// から（元の大きさ、圧縮後、使っているメモリ、同じ値のページ）を取り出す
+pub fn parse_mm_stat(text: &str) -> Option<(u64, u64, u64, u64)> {
+    let fields: Vec<u64> = text
+        .split_whitespace()
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    Some((
+        *fields.first()?,
+        *fields.get(1)?,
+        *fields.get(2)?,
+        *fields.get(5)?,
+    ))
+}
+
+/// zram も zswap も使っていなければ None
+#[cfg(target_os = "linux")]
+pub fn collect() -> Option<CompressedSwap> {
+    let zram = zram_devices();
+    let zswap = zswap();
+    if zram.is_empty() && zswap.is_none() {
+        return None;
+    }
+    // SAFETY: 引数は定数だけ
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as u64;
+    Some(CompressedSwap {
+        zram,
+        zswap,
+        page_size,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn collect() -> Option<CompressedSwap> {
+    None
+}
+
+// 大きさが 0 のデバイスは作っただけで使っていない
+#[cfg(target_os = "linux")]
+fn zram_devices() -> Vec<ZramDevice> {
+    let read = |path: &std::path::Path| std::fs::read_to_string(path).ok();
+    let mut devices: Vec<ZramDevice> = std::fs::read_dir("/sys/block")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("zram"))
+        .filter_map(|entry| {
+            let path = entry.path();
+            let disksize = read(&path.join("disksize"))?.trim().parse().ok()?;
+            if disksize == 0 {
+                return None;
+            }
+            let (original, compressed, used, same_pages) =
+                parse_mm_stat(&read(&path.join("mm_stat"))?)?;
+            Some(ZramDevice {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                algorithm: read(&path.join("comp_algorithm")).and_then(|text| selected(&text)),
+                disksize,
+                original,
+                compressed,
+                used,
+                same_pages,
+            })
+        })
+        .collect();
+    devices.sort_by(|a, b| a.name.cmp(&b.name));
+    devices
+}
+
+// "lzo lzo-rle [lz4] zstd" のうち [ ] で囲んだもの
+#[cfg(target_os = "linux")]
+fn selected(text: &str) -> Option<String> {
+    Some(text.split_once('[')?.1.split_once(']')?.0.to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn zswap() -> Option<Zswap> {
+    let parameter = |name: &str| {
+        std::fs::read_to_string(format!("/sys/module/zswap/parameters/{name}"))
+            .ok()
+            .map(|text| text.trim().to_string())
+    };
+    if parameter("enabled")? != "Y" {
+        return None;
+    }
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |name: &str| {
+        meminfo.lines().find_map(|line| {
+            let value = line.strip_prefix(name)?.strip_prefix(':')?;
+            let kib: u64 = value.trim().strip_suffix("kB")?.trim().parse().ok()?;
+            Some(kib * 1024)
+        })
+    };
+    Some(Zswap {
+        compressor: parameter("compressor"),
+        pool: field("Zswap")?,
+        stored: field("Zswapped")?,
+    })
+}